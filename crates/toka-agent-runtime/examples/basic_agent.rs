@@ -11,53 +11,18 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::Result;
-use serde_json::json;
-use tokio::time::sleep;
-
-use toka_agent_runtime::{AgentExecutor, AgentContext, AgentExecutionState, AgentMetrics};
+use toka_agent_runtime::AgentExecutor;
+use toka_auth::JwtHs256Validator;
+use toka_bus_core::InMemoryBus;
+use toka_kernel::{Kernel, WorldState};
+use toka_llm_gateway::{Config as LlmConfig, LlmGateway};
+use toka_runtime::{RuntimeKernel, RuntimeManager};
 use toka_types::{
     AgentConfig, AgentMetadata, AgentSpecConfig, AgentPriority, AgentCapabilities,
     AgentTasks, TaskConfig, TaskPriority, AgentDependencies, ReportingConfig,
     ReportingFrequency, SecurityConfig, ResourceLimits, AgentObjective, EntityId,
 };
 
-/// Mock LLM Gateway for demonstration
-struct MockLlmGateway;
-
-#[async_trait::async_trait]
-impl toka_llm_gateway::LlmGateway for MockLlmGateway {
-    async fn complete(&self, request: toka_llm_gateway::LlmRequest) -> Result<toka_llm_gateway::LlmResponse> {
-        // Simulate LLM processing time
-        sleep(Duration::from_millis(100)).await;
-        
-        let response_content = format!(
-            "Task completed successfully. Analyzed: {}",
-            request.messages().first().map(|m| &m.content).unwrap_or("unknown")
-        );
-        
-        Ok(toka_llm_gateway::LlmResponse::new(
-            response_content,
-            toka_llm_gateway::Usage {
-                prompt_tokens: 50,
-                completion_tokens: 25,
-                total_tokens: 75,
-            }
-        ))
-    }
-}
-
-/// Mock Runtime Manager for demonstration
-struct MockRuntimeManager;
-
-#[async_trait::async_trait]
-impl toka_runtime::RuntimeManager for MockRuntimeManager {
-    async fn submit(&self, _message: toka_types::Message) -> Result<()> {
-        // Mock implementation - just log the message
-        println!("Runtime received message for processing");
-        Ok(())
-    }
-}
-
 /// Create a sample agent configuration for testing
 fn create_sample_agent_config() -> AgentConfig {
     AgentConfig {
@@ -139,6 +104,7 @@ fn create_sample_agent_config() -> AgentConfig {
                 timeout: "10m".to_string(),
             },
         },
+        placement: Default::default(),
     }
 }
 
@@ -154,9 +120,23 @@ async fn main() -> Result<()> {
     println!("Generated: 2025-07-11 (UTC)");
     println!("========================================");
 
-    // Create mock services
-    let llm_gateway = Arc::new(MockLlmGateway);
-    let runtime_manager = Arc::new(MockRuntimeManager);
+    // Create services; the LLM provider is configured through the
+    // environment (ANTHROPIC_API_KEY or OPENAI_API_KEY)
+    let llm_config = match LlmConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            println!("⚠️  No LLM provider configured: {}", e);
+            println!("   Set ANTHROPIC_API_KEY or OPENAI_API_KEY to run this demo");
+            return Ok(());
+        }
+    };
+    let llm_gateway = Arc::new(LlmGateway::new(llm_config).await?);
+    let kernel = Kernel::new(
+        WorldState::default(),
+        Arc::new(JwtHs256Validator::new("basic-agent-example")),
+        Arc::new(InMemoryBus::new(1024)),
+    );
+    let runtime_manager = Arc::new(RuntimeManager::with_default_engines(RuntimeKernel::new(kernel)).await?);
 
     // Create agent configuration
     let agent_config = create_sample_agent_config();
@@ -207,11 +187,9 @@ async fn main() -> Result<()> {
     println!("   ✅ Resource management");
     println!("   ✅ Error handling and recovery");
 
-    println!("\n🔗 To integrate with real services:");
-    println!("   1. Replace MockLlmGateway with actual LLM service");
-    println!("   2. Replace MockRuntimeManager with toka-runtime");
-    println!("   3. Connect to toka-orchestration for coordination");
-    println!("   4. Add real task implementations");
+    println!("\n🔗 Next steps:");
+    println!("   1. Connect to toka-orchestration for coordination");
+    println!("   2. Add real task implementations");
 
     Ok(())
 }
@@ -246,15 +224,4 @@ mod tests {
         assert!(!config.security.capabilities_required.is_empty());
         assert!(!config.security.resource_limits.max_memory.is_empty());
     }
-
-    #[tokio::test]
-    async fn test_mock_llm_gateway() {
-        let gateway = MockLlmGateway;
-        let request = toka_llm_gateway::LlmRequest::new("Test prompt".to_string()).unwrap();
-        
-        let response = gateway.complete(request).await.unwrap();
-        
-        assert!(response.content().contains("Task completed successfully"));
-        assert_eq!(response.usage().total_tokens, 75);
-    }
-}
\ No newline at end of file
+}
@@ -20,7 +20,9 @@ use tracing::{error, info, warn};
 use toka_agent_runtime::{AgentExecutor, AgentContext, AgentExecutionState, AgentMetrics};
 use toka_llm_gateway::{LlmGateway, Config as LlmConfig};
 use toka_runtime::{RuntimeManager, RuntimeKernel, ToolKernel};
-use toka_kernel::Kernel;
+use toka_auth::JwtHs256Validator;
+use toka_bus_core::InMemoryBus;
+use toka_kernel::{Kernel, WorldState};
 use toka_types::{
     AgentConfig, AgentMetadata, AgentSpecConfig, AgentPriority, AgentCapabilities,
     AgentTasks, TaskConfig, TaskPriority, AgentDependencies, ReportingConfig,
@@ -154,11 +156,13 @@ async fn initialize_runtime_manager() -> Result<Arc<RuntimeManager>> {
     info!("Initializing Toka kernel...");
     
     // Create kernel instance
-    let kernel = Kernel::new();
-    let tool_kernel = ToolKernel::new(RuntimeKernel::new(kernel));
+    let bus = Arc::new(InMemoryBus::new(1024));
+    let auth = Arc::new(JwtHs256Validator::new("real-integration-example"));
+    let kernel = Kernel::new(WorldState::default(), auth, bus);
+    let tool_kernel: ToolKernel = RuntimeKernel::new(kernel);
     
     // Create runtime manager
-    let runtime = RuntimeManager::new(tool_kernel).await?;
+    let runtime = RuntimeManager::with_default_engines(tool_kernel).await?;
     Ok(Arc::new(runtime))
 }
 
@@ -251,6 +255,7 @@ fn create_real_world_agent_config() -> AgentConfig {
                 timeout: "15m".to_string(),
             },
         },
+        placement: Default::default(),
     }
 }
 
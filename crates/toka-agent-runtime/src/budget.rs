@@ -0,0 +1,278 @@
+//! Session budgets exposed to agents as planning hints.
+//!
+//! An orchestration session may cap the tokens, wall-clock time and cost it
+//! spends. Rather than cutting agents off once a cap is hit, the session's
+//! [`BudgetTracker`] hands each agent a [`RemainingBudget`] snapshot through
+//! its [`AgentContext`](crate::AgentContext) before every task. Planners can
+//! then adapt as the budget shrinks: the [`BudgetPressure`] tells how tight
+//! it is, [`RemainingBudget::planning_hint`] describes it for the prompt, and
+//! [`RemainingBudget::response_token_limit`] scales down response sizes.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Smallest response token limit handed out under budget pressure.
+pub const MIN_RESPONSE_TOKENS: u32 = 256;
+
+/// Limits of an orchestration session. `None` leaves a dimension unlimited.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionBudget {
+    /// Maximum LLM tokens consumed across all agents
+    #[serde(default)]
+    pub max_tokens: Option<u64>,
+    /// Maximum wall-clock duration of the session
+    #[serde(default)]
+    pub max_duration: Option<Duration>,
+    /// Maximum spend in US dollars
+    #[serde(default)]
+    pub max_cost_usd: Option<f64>,
+    /// Price per 1000 tokens used to estimate spend from token usage
+    #[serde(default)]
+    pub usd_per_1k_tokens: Option<f64>,
+}
+
+impl SessionBudget {
+    /// Whether no dimension is limited.
+    pub fn is_unlimited(&self) -> bool {
+        self.max_tokens.is_none() && self.max_duration.is_none() && self.max_cost_usd.is_none()
+    }
+}
+
+/// How tight the remaining budget is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum BudgetPressure {
+    /// More than 30% of every limit remains
+    Ample,
+    /// The tightest limit is below 30%
+    Low,
+    /// The tightest limit is below 10%
+    Critical,
+    /// A limit has been used up
+    Exhausted,
+}
+
+/// Budget left in a session at one point in time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemainingBudget {
+    /// Tokens left, if tokens are limited
+    pub tokens: Option<u64>,
+    /// Time left, if the session duration is limited
+    pub time: Option<Duration>,
+    /// Dollars left, if spend is limited
+    pub cost_usd: Option<f64>,
+    /// Share left of the tightest limit (0.0 to 1.0), if any is set
+    pub fraction: Option<f64>,
+    /// When the snapshot was taken
+    pub as_of: DateTime<Utc>,
+}
+
+impl RemainingBudget {
+    /// How tight the budget is.
+    pub fn pressure(&self) -> BudgetPressure {
+        match self.fraction {
+            None => BudgetPressure::Ample,
+            Some(fraction) if fraction <= 0.0 => BudgetPressure::Exhausted,
+            Some(fraction) if fraction < 0.1 => BudgetPressure::Critical,
+            Some(fraction) if fraction < 0.3 => BudgetPressure::Low,
+            Some(_) => BudgetPressure::Ample,
+        }
+    }
+
+    /// Prompt section asking the agent to plan within the budget.
+    ///
+    /// `None` while the budget is ample, so unconstrained sessions keep
+    /// their prompts unchanged.
+    pub fn planning_hint(&self) -> Option<String> {
+        let advice = match self.pressure() {
+            BudgetPressure::Ample => return None,
+            BudgetPressure::Low => {
+                "The session budget is running low. Keep reasoning brief, avoid optional exploration and prefer cheaper tools."
+            }
+            BudgetPressure::Critical => {
+                "The session budget is nearly used up. Do only what is essential to finish the task and answer concisely."
+            }
+            BudgetPressure::Exhausted => {
+                "The session budget is used up. Wrap up immediately: report what was accomplished and what remains."
+            }
+        };
+
+        let mut remaining = Vec::new();
+        if let Some(tokens) = self.tokens {
+            remaining.push(format!("{} tokens", tokens));
+        }
+        if let Some(time) = self.time {
+            remaining.push(format!("{}s", time.as_secs()));
+        }
+        if let Some(cost) = self.cost_usd {
+            remaining.push(format!("${:.2}", cost));
+        }
+        Some(format!("Budget remaining: {}. {}", remaining.join(", "), advice))
+    }
+
+    /// Response token limit for a request that would normally ask for
+    /// `requested` tokens.
+    ///
+    /// The limit halves under [`BudgetPressure::Low`], drops to a quarter
+    /// beyond that and never exceeds the tokens left, but stays at least
+    /// [`MIN_RESPONSE_TOKENS`] so the agent can still report back.
+    pub fn response_token_limit(&self, requested: u32) -> u32 {
+        let scaled = match self.pressure() {
+            BudgetPressure::Ample => requested,
+            BudgetPressure::Low => requested / 2,
+            BudgetPressure::Critical | BudgetPressure::Exhausted => requested / 4,
+        };
+        let capped = match self.tokens {
+            Some(tokens) => scaled.min(u32::try_from(tokens).unwrap_or(u32::MAX)),
+            None => scaled,
+        };
+        capped.max(MIN_RESPONSE_TOKENS.min(requested))
+    }
+}
+
+/// Consumption recorded against a [`SessionBudget`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct BudgetUsage {
+    /// LLM tokens consumed
+    pub tokens: u64,
+    /// Dollars spent
+    pub cost_usd: f64,
+}
+
+/// Tracks a session's consumption against its budget.
+///
+/// Shared by the orchestration engine and every agent executor of the
+/// session; the clock starts when the tracker is created.
+#[derive(Debug)]
+pub struct BudgetTracker {
+    budget: SessionBudget,
+    started_at: Instant,
+    usage: Mutex<BudgetUsage>,
+}
+
+impl BudgetTracker {
+    /// Start tracking against `budget`.
+    pub fn new(budget: SessionBudget) -> Self {
+        Self {
+            budget,
+            started_at: Instant::now(),
+            usage: Mutex::new(BudgetUsage::default()),
+        }
+    }
+
+    /// Limits being tracked.
+    pub fn budget(&self) -> &SessionBudget {
+        &self.budget
+    }
+
+    /// Consumption so far.
+    pub fn usage(&self) -> BudgetUsage {
+        *self.usage.lock().expect("budget usage poisoned")
+    }
+
+    /// Record `tokens` consumed, estimating their cost from the budget's
+    /// token price if one is set.
+    pub fn record_tokens(&self, tokens: u64) {
+        let mut usage = self.usage.lock().expect("budget usage poisoned");
+        usage.tokens += tokens;
+        if let Some(price) = self.budget.usd_per_1k_tokens {
+            usage.cost_usd += tokens as f64 / 1000.0 * price;
+        }
+    }
+
+    /// Record `cost_usd` spent outside of token usage.
+    pub fn record_cost(&self, cost_usd: f64) {
+        self.usage.lock().expect("budget usage poisoned").cost_usd += cost_usd;
+    }
+
+    /// Snapshot of what is left.
+    pub fn remaining(&self) -> RemainingBudget {
+        self.remaining_after(self.started_at.elapsed())
+    }
+
+    fn remaining_after(&self, elapsed: Duration) -> RemainingBudget {
+        let usage = self.usage();
+        let mut fractions = Vec::new();
+
+        let tokens = self.budget.max_tokens.map(|max| {
+            fractions.push(share(max.saturating_sub(usage.tokens) as f64, max as f64));
+            max.saturating_sub(usage.tokens)
+        });
+        let time = self.budget.max_duration.map(|max| {
+            let left = max.saturating_sub(elapsed);
+            fractions.push(share(left.as_secs_f64(), max.as_secs_f64()));
+            left
+        });
+        let cost_usd = self.budget.max_cost_usd.map(|max| {
+            let left = (max - usage.cost_usd).max(0.0);
+            fractions.push(share(left, max));
+            left
+        });
+
+        RemainingBudget {
+            tokens,
+            time,
+            cost_usd,
+            fraction: fractions.into_iter().reduce(f64::min),
+            as_of: Utc::now(),
+        }
+    }
+}
+
+fn share(left: f64, max: f64) -> f64 {
+    if max > 0.0 {
+        (left / max).clamp(0.0, 1.0)
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pressure_follows_tightest_limit() {
+        let tracker = BudgetTracker::new(SessionBudget {
+            max_tokens: Some(10_000),
+            max_duration: Some(Duration::from_secs(100)),
+            max_cost_usd: Some(1.0),
+            usd_per_1k_tokens: Some(0.05),
+        });
+
+        let fresh = tracker.remaining_after(Duration::from_secs(10));
+        assert_eq!(fresh.pressure(), BudgetPressure::Ample);
+        assert!(fresh.planning_hint().is_none());
+        assert_eq!(fresh.response_token_limit(4096), 4096);
+
+        tracker.record_tokens(8_000);
+        let low = tracker.remaining_after(Duration::from_secs(10));
+        assert_eq!(low.tokens, Some(2_000));
+        assert!((low.cost_usd.unwrap() - 0.6).abs() < 1e-9);
+        assert_eq!(low.pressure(), BudgetPressure::Low);
+        assert!(low.planning_hint().unwrap().contains("2000 tokens"));
+        assert_eq!(low.response_token_limit(4096), 2000);
+
+        let late = tracker.remaining_after(Duration::from_secs(95));
+        assert_eq!(late.pressure(), BudgetPressure::Critical);
+        assert_eq!(late.response_token_limit(4096), 1024);
+
+        tracker.record_tokens(5_000);
+        let spent = tracker.remaining_after(Duration::from_secs(10));
+        assert_eq!(spent.pressure(), BudgetPressure::Exhausted);
+        assert_eq!(spent.response_token_limit(4096), MIN_RESPONSE_TOKENS);
+    }
+
+    #[test]
+    fn test_unlimited_budget_never_constrains() {
+        let tracker = BudgetTracker::new(SessionBudget::default());
+        tracker.record_tokens(1_000_000);
+        let remaining = tracker.remaining();
+        assert!(tracker.budget().is_unlimited());
+        assert_eq!(remaining.fraction, None);
+        assert_eq!(remaining.pressure(), BudgetPressure::Ample);
+        assert_eq!(remaining.response_token_limit(4096), 4096);
+    }
+}
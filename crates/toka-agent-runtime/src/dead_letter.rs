@@ -0,0 +1,371 @@
+//! Persistent dead-letter queue for agent tasks.
+//!
+//! A task that still fails after the executor has exhausted its retries is
+//! recorded here instead of only being counted in
+//! [`AgentMetrics::tasks_failed`](crate::AgentMetrics::tasks_failed).
+//! Operators can list the dead letters, fix up the task parameters and send
+//! them back to the owning agent's task queue, or discard them.
+//!
+//! Like [`DurableQueue`], every change is stored as an event in a
+//! [`QueryableStorage`] backend and the queue state is rebuilt from those
+//! events when it is opened, so dead letters survive restarts.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use toka_store_core::{
+    causal_hash, create_event_header, decode_payload, DurableQueue, EventHeader, EventPayload,
+    IntentId, QueryableStorage, StorageError, TaskId,
+};
+use toka_types::{EntityId, TaskConfig};
+
+/// Prefix of the event kinds written by the dead-letter queue.
+pub const DEAD_LETTER_KIND_PREFIX: &str = "dead_letter";
+
+/// Identifier of a dead-lettered task.
+pub type DeadLetterId = Uuid;
+
+/// Where a dead letter stands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeadLetterStatus {
+    /// Waiting for an operator decision
+    Pending,
+    /// Sent back to the agent's task queue
+    Requeued,
+    /// Dropped without retrying
+    Discarded,
+}
+
+/// A task that exhausted its retries.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetteredTask {
+    /// Dead letter identifier
+    pub id: DeadLetterId,
+    /// Entity ID of the owning agent
+    pub agent_id: EntityId,
+    /// Configuration name of the owning agent
+    pub agent_name: String,
+    /// Task identifier assigned by the executor
+    pub task_id: String,
+    /// Task parameters, including any operator edits
+    pub task: TaskConfig,
+    /// Error reported by the final attempt
+    pub error: String,
+    /// When the task was dead-lettered
+    pub failed_at: DateTime<Utc>,
+    /// Current status
+    pub status: DeadLetterStatus,
+    /// How often the task has been requeued from the dead-letter queue
+    pub requeue_count: u32,
+}
+
+/// State change recorded in the store.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum DeadLetterRecord {
+    Added {
+        id: DeadLetterId,
+        agent_id: EntityId,
+        agent_name: String,
+        task_id: String,
+        task: TaskConfig,
+        error: String,
+    },
+    Edited { id: DeadLetterId, task: TaskConfig },
+    Requeued { id: DeadLetterId },
+    Discarded { id: DeadLetterId },
+}
+
+impl DeadLetterRecord {
+    fn id(&self) -> DeadLetterId {
+        match self {
+            DeadLetterRecord::Added { id, .. }
+            | DeadLetterRecord::Edited { id, .. }
+            | DeadLetterRecord::Requeued { id }
+            | DeadLetterRecord::Discarded { id } => *id,
+        }
+    }
+
+    fn kind(&self) -> String {
+        let action = match self {
+            DeadLetterRecord::Added { .. } => "added",
+            DeadLetterRecord::Edited { .. } => "edited",
+            DeadLetterRecord::Requeued { .. } => "requeued",
+            DeadLetterRecord::Discarded { .. } => "discarded",
+        };
+        format!("{}.{}", DEAD_LETTER_KIND_PREFIX, action)
+    }
+}
+
+#[derive(Default)]
+struct DeadLetterState {
+    entries: HashMap<DeadLetterId, DeadLetteredTask>,
+    /// Insertion order, used to list oldest first
+    order: Vec<DeadLetterId>,
+    /// Latest record per entry, used as the causal parent of the next one
+    heads: HashMap<DeadLetterId, EventHeader>,
+}
+
+impl DeadLetterState {
+    fn apply(&mut self, record: DeadLetterRecord, header: EventHeader) {
+        let id = record.id();
+        let timestamp = header.timestamp;
+        self.heads.insert(id, header);
+
+        match record {
+            DeadLetterRecord::Added { id, agent_id, agent_name, task_id, task, error } => {
+                self.order.push(id);
+                self.entries.insert(
+                    id,
+                    DeadLetteredTask {
+                        id,
+                        agent_id,
+                        agent_name,
+                        task_id,
+                        task,
+                        error,
+                        failed_at: timestamp,
+                        status: DeadLetterStatus::Pending,
+                        requeue_count: 0,
+                    },
+                );
+            }
+            DeadLetterRecord::Edited { id, task } => {
+                if let Some(entry) = self.entries.get_mut(&id) {
+                    entry.task = task;
+                }
+            }
+            DeadLetterRecord::Requeued { id } => {
+                if let Some(entry) = self.entries.get_mut(&id) {
+                    entry.status = DeadLetterStatus::Requeued;
+                    entry.requeue_count += 1;
+                }
+            }
+            DeadLetterRecord::Discarded { id } => {
+                if let Some(entry) = self.entries.get_mut(&id) {
+                    entry.status = DeadLetterStatus::Discarded;
+                }
+            }
+        }
+    }
+
+    fn pending(&self, id: DeadLetterId) -> Result<&DeadLetteredTask> {
+        match self.entries.get(&id) {
+            Some(entry) if entry.status == DeadLetterStatus::Pending => Ok(entry),
+            Some(entry) => anyhow::bail!("Dead letter {} is not pending (status: {:?})", id, entry.status),
+            None => anyhow::bail!("Dead letter {} not found", id),
+        }
+    }
+}
+
+/// Dead-letter queue persisted in a storage backend.
+///
+/// All operations on one instance are serialized. Run a single instance per
+/// queue name.
+pub struct DeadLetterQueue {
+    store: Arc<dyn QueryableStorage>,
+    intent: IntentId,
+    state: Mutex<DeadLetterState>,
+}
+
+impl DeadLetterQueue {
+    /// Open the dead-letter queue `name`, replaying its history from `store`.
+    pub async fn open(store: Arc<dyn QueryableStorage>, name: &str) -> Result<Self> {
+        let intent = dead_letter_intent(name);
+        let mut state = DeadLetterState::default();
+
+        let headers: Vec<EventHeader> = store.events_by_intent(&intent).await?.try_collect().await?;
+        for header in headers {
+            if !header.kind.starts_with(DEAD_LETTER_KIND_PREFIX) {
+                continue;
+            }
+            let bytes = store
+                .payload_bytes(&header.digest)
+                .await?
+                .ok_or_else(|| StorageError::EventNotFound(format!("dead letter record {}", header.id)))?;
+            let record: DeadLetterRecord = decode_payload(&header, &bytes)?;
+            state.apply(record, header);
+        }
+
+        Ok(Self {
+            store,
+            intent,
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Record a task that exhausted its retries.
+    pub async fn add(
+        &self,
+        agent_id: EntityId,
+        agent_name: &str,
+        task_id: &str,
+        task: TaskConfig,
+        error: &str,
+    ) -> Result<DeadLetterId> {
+        let id = Uuid::new_v4();
+        let mut state = self.state.lock().await;
+        self.record(
+            &mut state,
+            DeadLetterRecord::Added {
+                id,
+                agent_id,
+                agent_name: agent_name.to_string(),
+                task_id: task_id.to_string(),
+                task,
+                error: error.to_string(),
+            },
+        )
+        .await?;
+        Ok(id)
+    }
+
+    /// Look up a dead letter.
+    pub async fn get(&self, id: DeadLetterId) -> Option<DeadLetteredTask> {
+        self.state.lock().await.entries.get(&id).cloned()
+    }
+
+    /// Dead letters awaiting a decision, oldest first.
+    pub async fn pending(&self) -> Vec<DeadLetteredTask> {
+        self.list(|entry| entry.status == DeadLetterStatus::Pending).await
+    }
+
+    /// Pending dead letters of one agent, oldest first.
+    pub async fn pending_for_agent(&self, agent_name: &str) -> Vec<DeadLetteredTask> {
+        self.list(|entry| entry.status == DeadLetterStatus::Pending && entry.agent_name == agent_name)
+            .await
+    }
+
+    async fn list(&self, filter: impl Fn(&DeadLetteredTask) -> bool) -> Vec<DeadLetteredTask> {
+        let state = self.state.lock().await;
+        state
+            .order
+            .iter()
+            .filter_map(|id| state.entries.get(id))
+            .filter(|entry| filter(entry))
+            .cloned()
+            .collect()
+    }
+
+    /// Replace the parameters of a pending dead letter before requeueing it.
+    pub async fn edit(&self, id: DeadLetterId, task: TaskConfig) -> Result<()> {
+        let mut state = self.state.lock().await;
+        state.pending(id)?;
+        self.record(&mut state, DeadLetterRecord::Edited { id, task }).await
+    }
+
+    /// Drop a pending dead letter without retrying it.
+    pub async fn discard(&self, id: DeadLetterId) -> Result<()> {
+        let mut state = self.state.lock().await;
+        state.pending(id)?;
+        self.record(&mut state, DeadLetterRecord::Discarded { id }).await
+    }
+
+    /// Send a pending dead letter back to the owning agent's task queue.
+    ///
+    /// `to_payload` turns the dead letter (with any edits applied) into the
+    /// queue's payload type. The task is enqueued before the dead letter is
+    /// marked requeued, so a crash in between can at worst requeue it twice.
+    pub async fn requeue_into<T, F>(
+        &self,
+        id: DeadLetterId,
+        queue: &DurableQueue<T>,
+        to_payload: F,
+    ) -> Result<TaskId>
+    where
+        T: EventPayload + Clone,
+        F: FnOnce(&DeadLetteredTask) -> Result<T> + Send,
+    {
+        let mut state = self.state.lock().await;
+        let payload = to_payload(state.pending(id)?)?;
+        let task_id = queue.enqueue(payload).await?;
+        self.record(&mut state, DeadLetterRecord::Requeued { id }).await?;
+        Ok(task_id)
+    }
+
+    /// Persist a record, then apply it to the in-memory state.
+    async fn record(&self, state: &mut DeadLetterState, record: DeadLetterRecord) -> Result<()> {
+        let parents: Vec<EventHeader> = state.heads.get(&record.id()).cloned().into_iter().collect();
+        let header = create_event_header(&parents, self.intent, record.kind(), &record)
+            .map_err(|e| StorageError::SerializationFailed(e.to_string()))?;
+        let payload = rmp_serde::to_vec_named(&record)
+            .map_err(|e| StorageError::SerializationFailed(e.to_string()))?;
+
+        self.store.commit(&header, &payload).await?;
+        state.apply(record, header);
+        Ok(())
+    }
+}
+
+/// Deterministic intent ID grouping all records of the dead-letter queue `name`.
+pub fn dead_letter_intent(name: &str) -> IntentId {
+    let digest = causal_hash(format!("{}:{}", DEAD_LETTER_KIND_PREFIX, name).as_bytes(), &[]);
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    Uuid::from_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use toka_store_core::QueueConfig;
+    use toka_store_memory::MemoryBackend;
+    use toka_types::TaskPriority;
+
+    fn task(description: &str) -> TaskConfig {
+        TaskConfig {
+            description: description.to_string(),
+            priority: TaskPriority::High,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dead_letters_survive_reopen_and_requeue() {
+        let store: Arc<dyn QueryableStorage> = Arc::new(MemoryBackend::new());
+        let agent = EntityId(7);
+
+        let dlq = DeadLetterQueue::open(store.clone(), "agents").await.unwrap();
+        let id = dlq
+            .add(agent, "builder", "task-builder-0", task("Build with -j64"), "out of memory")
+            .await
+            .unwrap();
+        let other = dlq
+            .add(agent, "tester", "task-tester-0", task("Run tests"), "timeout")
+            .await
+            .unwrap();
+
+        // State is rebuilt from the store
+        let dlq = DeadLetterQueue::open(store.clone(), "agents").await.unwrap();
+        assert_eq!(dlq.pending().await.len(), 2);
+        let pending = dlq.pending_for_agent("builder").await;
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].error, "out of memory");
+
+        dlq.edit(id, task("Build with -j4")).await.unwrap();
+        dlq.discard(other).await.unwrap();
+        assert!(dlq.edit(other, task("Run tests again")).await.is_err());
+
+        let queue: DurableQueue<(EntityId, TaskConfig)> =
+            DurableQueue::open(store.clone(), "builder-tasks", QueueConfig::default()).await.unwrap();
+        dlq.requeue_into(id, &queue, |entry| Ok((entry.agent_id, entry.task.clone())))
+            .await
+            .unwrap();
+
+        let claimed = queue.claim("worker", Duration::from_secs(30)).await.unwrap().unwrap();
+        assert_eq!(claimed.payload, (agent, task("Build with -j4")));
+
+        let dlq = DeadLetterQueue::open(store, "agents").await.unwrap();
+        let entry = dlq.get(id).await.unwrap();
+        assert_eq!(entry.status, DeadLetterStatus::Requeued);
+        assert_eq!(entry.requeue_count, 1);
+        assert!(dlq.pending().await.is_empty());
+    }
+}
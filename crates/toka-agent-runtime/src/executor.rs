@@ -13,7 +13,7 @@ use tokio::sync::RwLock;
 use tracing::{debug, error, info, instrument, warn};
 
 use toka_llm_gateway::LlmGateway;
-use toka_types::{AgentConfig, TaskConfig};
+use toka_types::{AgentConfig, TaskConfig, TaskPriority, TaskSpec};
 use toka_runtime::RuntimeManager;
 use toka_types::EntityId;
 
@@ -21,6 +21,8 @@ use crate::{
     AgentContext, AgentExecutionState, AgentMetrics, ExecutionConfig, TaskExecutor,
     ProgressReporter, TaskResult, AgentRuntimeError, AgentRuntimeResult,
 };
+use crate::budget::BudgetTracker;
+use crate::dead_letter::DeadLetterQueue;
 use crate::task::LlmTask;
 use crate::AgentTask;
 
@@ -40,6 +42,10 @@ pub struct AgentExecutor {
     execution_config: ExecutionConfig,
     /// Execution start time
     start_time: Instant,
+    /// Dead-letter queue receiving tasks that exhausted their retries
+    dead_letters: Option<Arc<DeadLetterQueue>>,
+    /// Session budget tracker shared with the orchestration engine
+    budget: Option<Arc<BudgetTracker>>,
 }
 
 impl AgentExecutor {
@@ -62,6 +68,7 @@ impl AgentExecutor {
             last_activity: Utc::now(),
             metrics: AgentMetrics::default(),
             environment: std::collections::HashMap::new(),
+            budget: None,
         };
 
         // Create task executor
@@ -85,9 +92,40 @@ impl AgentExecutor {
             progress_reporter: Arc::new(RwLock::new(progress_reporter)),
             execution_config,
             start_time: Instant::now(),
+            dead_letters: None,
+            budget: None,
         })
     }
 
+    /// Inject the `top_k` most relevant tools into each task prompt.
+    pub fn with_tool_suggester(
+        mut self,
+        suggester: Arc<dyn crate::ToolSuggester>,
+        top_k: usize,
+    ) -> Self {
+        self.task_executor = self.task_executor.with_tool_suggester(suggester, top_k);
+        self
+    }
+
+    /// Run tools through `invoker` and trace their invocations in task results.
+    pub fn with_tool_invoker(mut self, invoker: Arc<dyn crate::ToolInvoker>) -> Self {
+        self.task_executor = self.task_executor.with_tool_invoker(invoker);
+        self
+    }
+
+    /// Record tasks that still fail after all retries in `queue`.
+    pub fn with_dead_letter_queue(mut self, queue: Arc<DeadLetterQueue>) -> Self {
+        self.dead_letters = Some(queue);
+        self
+    }
+
+    /// Expose the remaining session budget to each task and charge the
+    /// tokens it consumes to `tracker`.
+    pub fn with_budget(mut self, tracker: Arc<BudgetTracker>) -> Self {
+        self.budget = Some(tracker);
+        self
+    }
+
     /// Main execution loop - interprets and executes agent configuration
     #[instrument(skip(self), fields(agent_name = %self.get_agent_name()))]
     pub async fn run(mut self) -> Result<()> {
@@ -198,6 +236,26 @@ impl AgentExecutor {
         Ok(())
     }
 
+    /// Execute a task scheduled for this agent through the kernel.
+    ///
+    /// The outcome is reported like that of a default task, and its
+    /// `TaskCompleted` or `TaskFailed` event carries the correlation id of
+    /// the request that scheduled `task`.
+    pub async fn execute_scheduled_task(&mut self, task: &TaskSpec) -> Result<TaskResult> {
+        let task_config = TaskConfig {
+            description: task.description.clone(),
+            priority: TaskPriority::Medium,
+        };
+        let task_index = self.context.read().await.metrics.tasks_attempted as usize;
+        let task_result = self
+            .execute_single_task(&task_config, task_index)
+            .await?
+            .with_correlation_id(task.correlation_id.clone());
+
+        self.progress_reporter.write().await.report_task_completion(task_result.clone()).await?;
+        Ok(task_result)
+    }
+
     /// Execute a single task with full error handling and reporting
     #[instrument(skip(self, task_config), fields(task_desc = %task_config.description))]
     async fn execute_single_task(&mut self, task_config: &TaskConfig, task_index: usize) -> Result<TaskResult> {
@@ -215,6 +273,11 @@ impl AgentExecutor {
         let llm_task = LlmTask::new(task_config.clone())
             .with_id(task_id.clone());
 
+        // Let the planner see how much budget is left before it starts
+        if let Some(tracker) = &self.budget {
+            self.context.write().await.budget = Some(tracker.remaining());
+        }
+
         // Execute task
         let context = self.context.read().await.clone();
         let task_result = match self.task_executor.execute_task(&llm_task, &context).await {
@@ -238,6 +301,13 @@ impl AgentExecutor {
 
         // Update agent metrics
         self.update_metrics_from_task_result(&task_result).await?;
+        if let (Some(tracker), Some(tokens)) = (&self.budget, task_result.llm_tokens_used) {
+            tracker.record_tokens(tokens);
+        }
+
+        if !task_result.success {
+            self.dead_letter(task_config, &task_result).await?;
+        }
 
         // Return to ready state
         self.update_state(AgentExecutionState::Ready).await?;
@@ -245,6 +315,24 @@ impl AgentExecutor {
         Ok(task_result)
     }
 
+    /// Move a failed task to the dead-letter queue, if one is configured
+    async fn dead_letter(&self, task_config: &TaskConfig, task_result: &TaskResult) -> Result<()> {
+        let Some(queue) = &self.dead_letters else {
+            return Ok(());
+        };
+
+        let (agent_id, agent_name) = {
+            let context = self.context.read().await;
+            (context.agent_id, context.config.metadata.name.clone())
+        };
+        let error = task_result.output.as_deref().unwrap_or("unknown error");
+        let id = queue
+            .add(agent_id, &agent_name, &task_result.task_id, task_config.clone(), error)
+            .await?;
+        warn!("Task {} dead-lettered as {}", task_result.task_id, id);
+        Ok(())
+    }
+
     /// Validate that agent objectives have been completed
     async fn validate_objectives_completion(&self) -> Result<()> {
         let context = self.context.read().await;
@@ -440,6 +528,7 @@ mod tests {
                     timeout: "5m".to_string(),
                 },
             },
+            placement: Default::default(),
         }
     }
 
@@ -494,6 +583,7 @@ mod tests {
             last_activity: Utc::now(),
             metrics: AgentMetrics::default(),
             environment: HashMap::new(),
+            budget: None,
         };
 
         assert_eq!(context.agent_id, agent_id);
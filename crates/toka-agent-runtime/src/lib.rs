@@ -13,6 +13,8 @@
 //! - **Progress Reporting**: Real-time progress updates to orchestration system via kernel events
 //! - **Resource Management**: CPU, memory, and timeout enforcement
 //! - **Capability Validation**: Runtime permission checking against declared capabilities
+//! - **Dead-Letter Queue**: Persistent record of tasks that exhausted their retries
+//! - **Budget Hints**: Remaining session budget exposed to agent planners
 //! - **Orchestration Integration**: Full integration with toka-orchestration for coordinated execution
 //!
 //! ## Architecture
@@ -54,7 +56,7 @@
 //! let llm_gateway = Arc::new(LlmGateway::new(llm_config).await?);
 //! 
 //! let kernel = toka_kernel::Kernel::new();
-//! let runtime = Arc::new(RuntimeManager::new(ToolKernel::new(kernel)).await?);
+//! let runtime = Arc::new(RuntimeManager::with_default_engines(ToolKernel::new(kernel)).await?);
 //! 
 //! // Load agent configuration (example)
 //! # let config: AgentConfig = unimplemented!();
@@ -75,15 +77,16 @@
 //!
 //! ### Orchestration Integration
 //!
+//! toka-orchestration depends on this crate and implements
+//! [`OrchestrationBackend`] for its engine:
+//!
 //! ```rust,ignore
-//! use toka_agent_runtime::orchestration_integration::{
-//!     OrchestrationIntegration, OrchestrationEngineExt
-//! };
+//! use toka_agent_runtime::OrchestrationEngineExt;
 //! use toka_orchestration::OrchestrationEngine;
 //!
 //! # #[tokio::main]
 //! # async fn main() -> anyhow::Result<()> {
-//! # let orchestration_engine = Arc::new(unimplemented!());
+//! # let orchestration_engine: Arc<OrchestrationEngine> = unimplemented!();
 //! # let runtime_manager = Arc::new(unimplemented!());
 //! # let llm_gateway = Arc::new(unimplemented!());
 //!
@@ -135,6 +138,10 @@ pub mod task;
 pub mod capability;
 pub mod resource;
 pub mod progress;
+pub mod tool_suggestions;
+pub mod tool_trace;
+pub mod dead_letter;
+pub mod budget;
 pub mod orchestration_integration;
 
 pub use executor::AgentExecutor;
@@ -142,10 +149,17 @@ pub use process::AgentProcessManager;
 pub use task::TaskExecutor;
 pub use capability::CapabilityValidator;
 pub use resource::ResourceManager;
-pub use progress::{ProgressReporter, AgentProgress, TaskResult};
+pub use progress::{
+    ProgressReporter, AgentProgress, TaskResult,
+    ProgressAggregator, ProgressRollup, WorkstreamProgress, SessionProgress,
+};
+pub use tool_suggestions::{ToolSuggester, ToolSuggestion, DEFAULT_SUGGESTED_TOOLS};
+pub use tool_trace::{ToolInvocation, ToolInvoker, ToolOutcome, ToolTrace, TracedToolInvoker};
+pub use dead_letter::{DeadLetterId, DeadLetterQueue, DeadLetterStatus, DeadLetteredTask};
+pub use budget::{BudgetPressure, BudgetTracker, BudgetUsage, RemainingBudget, SessionBudget};
 pub use orchestration_integration::{
-    OrchestrationIntegration, OrchestrationEngineExt, ProgressUpdate, 
-    ActiveAgentInfo, IntegrationMetrics
+    OrchestrationIntegration, OrchestrationEngineExt, OrchestrationBackend, OrchestrationStatus,
+    ProgressUpdate, ActiveAgentInfo, IntegrationMetrics,
 };
 
 /// Maximum time to wait for agent startup
@@ -204,6 +218,9 @@ pub struct AgentContext {
     pub metrics: AgentMetrics,
     /// Environment variables and context
     pub environment: HashMap<String, String>,
+    /// Session budget left when the current task started, if tracked
+    #[serde(default)]
+    pub budget: Option<RemainingBudget>,
 }
 
 /// Metrics collected during agent execution
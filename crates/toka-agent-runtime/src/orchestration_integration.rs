@@ -4,31 +4,64 @@
 //! toka-orchestration, enabling agents to be spawned, managed, and coordinated
 //! through the orchestration system.
 //!
+//! toka-orchestration depends on this crate, so the integration drives its
+//! engine through the [`OrchestrationBackend`] trait, which toka-orchestration
+//! implements for its `OrchestrationEngine`.
+//!
 //! Generated: 2025-07-11 (UTC) - Phase 2 Integration
 
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use anyhow::Result;
+use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use tokio::sync::{mpsc, RwLock};
-use tracing::{debug, error, info, warn, instrument};
+use tokio::task::AbortHandle;
+use tracing::{debug, info, warn, instrument};
 
-use toka_orchestration::{OrchestrationEngine, SpawnedAgent, AgentState, OrchestrationSession};
 use toka_runtime::RuntimeManager;
 use toka_llm_gateway::LlmGateway;
 use toka_types::{AgentConfig, EntityId};
 
-use crate::{
-    AgentExecutor, AgentContext, AgentExecutionState, AgentMetrics, 
-    ProgressReporter, TaskResult, AgentRuntimeError, AgentRuntimeResult
-};
+use crate::{AgentExecutor, AgentExecutionState, AgentMetrics, AgentRuntimeError, AgentRuntimeResult};
+
+/// Interval between checks of the orchestration session
+const MONITOR_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Orchestration engine driven by an [`OrchestrationIntegration`].
+#[async_trait]
+pub trait OrchestrationBackend: Send + Sync + 'static {
+    /// Handle of a started orchestration session
+    type Session: Send;
+
+    /// Start an orchestration session
+    async fn start_orchestration(self: Arc<Self>) -> Result<Self::Session>;
+
+    /// Current state of the orchestration session
+    async fn orchestration_status(&self) -> OrchestrationStatus;
+}
+
+/// Snapshot of an orchestration session reported by an [`OrchestrationBackend`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrchestrationStatus {
+    /// Session ID
+    pub session_id: String,
+    /// Name of the current orchestration phase
+    pub phase: String,
+    /// Overall progress (0.0 to 1.0)
+    pub progress: f64,
+    /// Number of agents spawned so far
+    pub spawned_agents: usize,
+    /// Whether the session is complete
+    pub completed: bool,
+}
 
 /// Integration bridge between agent runtime and orchestration system
-pub struct OrchestrationIntegration {
+pub struct OrchestrationIntegration<E: OrchestrationBackend> {
     /// Orchestration engine reference
-    orchestration_engine: Arc<OrchestrationEngine>,
+    orchestration_engine: Arc<E>,
     /// Runtime manager for agent execution
     runtime_manager: Arc<RuntimeManager>,
     /// LLM gateway for intelligent execution
@@ -39,6 +72,8 @@ pub struct OrchestrationIntegration {
     progress_channels: Arc<RwLock<HashMap<EntityId, mpsc::UnboundedSender<ProgressUpdate>>>>,
     /// Integration metrics
     metrics: Arc<RwLock<IntegrationMetrics>>,
+    /// Task monitoring the current orchestration session
+    session_monitor: Mutex<Option<AbortHandle>>,
 }
 
 /// Information about an active agent in the runtime
@@ -46,8 +81,8 @@ pub struct OrchestrationIntegration {
 pub struct ActiveAgentInfo {
     /// Agent configuration
     pub config: AgentConfig,
-    /// Agent executor handle
-    pub executor_handle: Option<tokio::task::JoinHandle<Result<()>>>,
+    /// Handle aborting the agent executor task
+    pub executor_handle: Option<AbortHandle>,
     /// Progress reporter
     pub progress_tx: mpsc::UnboundedSender<ProgressUpdate>,
     /// Agent start time
@@ -94,10 +129,10 @@ pub struct IntegrationMetrics {
     pub last_updated: DateTime<Utc>,
 }
 
-impl OrchestrationIntegration {
+impl<E: OrchestrationBackend> OrchestrationIntegration<E> {
     /// Create a new orchestration integration
     pub async fn new(
-        orchestration_engine: Arc<OrchestrationEngine>,
+        orchestration_engine: Arc<E>,
         runtime_manager: Arc<RuntimeManager>,
         llm_gateway: Arc<LlmGateway>,
     ) -> Result<Self> {
@@ -110,12 +145,13 @@ impl OrchestrationIntegration {
             active_agents: Arc::new(RwLock::new(HashMap::new())),
             progress_channels: Arc::new(RwLock::new(HashMap::new())),
             metrics: Arc::new(RwLock::new(IntegrationMetrics::default())),
+            session_monitor: Mutex::new(None),
         })
     }
 
     /// Start orchestration session with agent runtime integration
     #[instrument(skip(self))]
-    pub async fn start_orchestrated_execution(&self) -> Result<OrchestrationSession> {
+    pub async fn start_orchestrated_execution(&self) -> Result<E::Session> {
         info!("Starting orchestrated execution with agent runtime integration");
 
         // Start orchestration session
@@ -129,9 +165,9 @@ impl OrchestrationIntegration {
         }
 
         // Start progress monitoring for orchestration
-        self.start_orchestration_monitoring().await?;
+        self.start_orchestration_monitoring();
 
-        info!("Orchestration session started with runtime integration: {}", session.session_id());
+        info!("Orchestration session started with runtime integration");
         Ok(session)
     }
 
@@ -171,10 +207,10 @@ impl OrchestrationIntegration {
         // Create active agent info
         let active_info = ActiveAgentInfo {
             config: config.clone(),
-            executor_handle: Some(executor_handle),
+            executor_handle: Some(executor_handle.abort_handle()),
             progress_tx,
             started_at: Utc::now(),
-            state: AgentExecutionState::Spawning,
+            state: AgentExecutionState::Initializing,
             metrics: AgentMetrics::default(),
         };
 
@@ -185,7 +221,7 @@ impl OrchestrationIntegration {
         }
 
         // Start progress monitoring for this agent
-        self.start_agent_progress_monitoring(agent_id, progress_rx).await;
+        self.start_agent_progress_monitoring(agent_id, progress_rx);
 
         // Update metrics
         {
@@ -199,27 +235,27 @@ impl OrchestrationIntegration {
         Ok(())
     }
 
-    /// Start progress monitoring for orchestration
-    async fn start_orchestration_monitoring(&self) -> Result<()> {
+    /// Start progress monitoring for orchestration, replacing the monitor of
+    /// a previous session
+    fn start_orchestration_monitoring(&self) {
         debug!("Starting orchestration progress monitoring");
 
         let orchestration_engine = self.orchestration_engine.clone();
         let integration_metrics = self.metrics.clone();
 
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(10));
+        let monitor = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(MONITOR_INTERVAL);
 
             loop {
                 interval.tick().await;
 
                 // Get orchestration state
-                let session_state = orchestration_engine.get_session_state().await;
-                let spawned_agents = orchestration_engine.get_spawned_agents();
+                let status = orchestration_engine.orchestration_status().await;
 
-                debug!("Orchestration monitoring: phase={:?}, progress={:.1}%, agents={}",
-                       session_state.current_phase,
-                       session_state.progress * 100.0,
-                       spawned_agents.len());
+                debug!("Orchestration monitoring: phase={}, progress={:.1}%, agents={}",
+                       status.phase,
+                       status.progress * 100.0,
+                       status.spawned_agents);
 
                 // Update integration metrics
                 {
@@ -228,18 +264,23 @@ impl OrchestrationIntegration {
                 }
 
                 // Check if orchestration is complete
-                if session_state.completed {
-                    info!("Orchestration session completed - stopping monitoring");
+                if status.completed {
+                    info!("Orchestration session {} completed - stopping monitoring", status.session_id);
                     break;
                 }
             }
         });
 
-        Ok(())
+        let previous = self.session_monitor.lock()
+            .expect("session monitor lock poisoned")
+            .replace(monitor.abort_handle());
+        if let Some(previous) = previous {
+            previous.abort();
+        }
     }
 
     /// Start progress monitoring for a specific agent
-    async fn start_agent_progress_monitoring(
+    fn start_agent_progress_monitoring(
         &self,
         agent_id: EntityId,
         mut progress_rx: mpsc::UnboundedReceiver<ProgressUpdate>,
@@ -264,17 +305,22 @@ impl OrchestrationIntegration {
                 }
 
                 // Handle completion
-                if matches!(progress_update.state, 
-                           AgentExecutionState::Completed | 
+                if matches!(progress_update.state,
+                           AgentExecutionState::Completed |
                            AgentExecutionState::Failed { .. }) {
                     info!("Agent {} execution completed: state={:?}",
                           agent_id.0, progress_update.state);
 
+                    // Clean up completed agent, unless it was already stopped
+                    if active_agents.write().await.remove(&agent_id).is_none() {
+                        break;
+                    }
+
                     // Update integration metrics
                     {
                         let mut integration_metrics = metrics.write().await;
-                        integration_metrics.active_agents -= 1;
-                        
+                        integration_metrics.active_agents = integration_metrics.active_agents.saturating_sub(1);
+
                         match progress_update.state {
                             AgentExecutionState::Completed => {
                                 integration_metrics.completed_agents += 1;
@@ -284,14 +330,8 @@ impl OrchestrationIntegration {
                             }
                             _ => {}
                         }
-                        
-                        integration_metrics.last_updated = Utc::now();
-                    }
 
-                    // Clean up completed agent
-                    {
-                        let mut agents = active_agents.write().await;
-                        agents.remove(&agent_id);
+                        integration_metrics.last_updated = Utc::now();
                     }
 
                     break;
@@ -327,7 +367,7 @@ impl OrchestrationIntegration {
             // Update metrics
             {
                 let mut metrics = self.metrics.write().await;
-                metrics.active_agents -= 1;
+                metrics.active_agents = metrics.active_agents.saturating_sub(1);
                 metrics.last_updated = Utc::now();
             }
 
@@ -345,6 +385,11 @@ impl OrchestrationIntegration {
     pub async fn shutdown(&self) -> Result<()> {
         info!("Shutting down orchestration integration");
 
+        // Stop monitoring the orchestration session
+        if let Some(monitor) = self.session_monitor.lock().expect("session monitor lock poisoned").take() {
+            monitor.abort();
+        }
+
         // Stop all active agents
         let agent_ids: Vec<EntityId> = {
             let agents = self.active_agents.read().await;
@@ -379,22 +424,22 @@ impl OrchestrationIntegration {
     }
 }
 
-/// Extension trait to add orchestration capabilities to OrchestrationEngine
-pub trait OrchestrationEngineExt {
+/// Extension trait to add orchestration capabilities to orchestration engines
+pub trait OrchestrationEngineExt: OrchestrationBackend + Sized {
     /// Create orchestration integration with agent runtime
     fn with_agent_runtime_integration(
         self: Arc<Self>,
         runtime_manager: Arc<RuntimeManager>,
         llm_gateway: Arc<LlmGateway>,
-    ) -> impl std::future::Future<Output = Result<OrchestrationIntegration>>;
+    ) -> impl std::future::Future<Output = Result<OrchestrationIntegration<Self>>> + Send;
 }
 
-impl OrchestrationEngineExt for OrchestrationEngine {
+impl<E: OrchestrationBackend> OrchestrationEngineExt for E {
     async fn with_agent_runtime_integration(
         self: Arc<Self>,
         runtime_manager: Arc<RuntimeManager>,
         llm_gateway: Arc<LlmGateway>,
-    ) -> Result<OrchestrationIntegration> {
+    ) -> Result<OrchestrationIntegration<Self>> {
         OrchestrationIntegration::new(self, runtime_manager, llm_gateway).await
     }
 }
@@ -408,6 +453,73 @@ mod tests {
         SecurityConfig, ResourceLimits, TaskConfig, TaskPriority
     };
     use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use toka_bus_core::InMemoryBus;
+    use toka_kernel::{Kernel, WorldState};
+    use toka_runtime::RuntimeKernel;
+
+    /// Backend whose sessions never complete
+    #[derive(Default)]
+    struct FakeOrchestration {
+        sessions: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl OrchestrationBackend for FakeOrchestration {
+        type Session = usize;
+
+        async fn start_orchestration(self: Arc<Self>) -> Result<usize> {
+            Ok(self.sessions.fetch_add(1, Ordering::SeqCst))
+        }
+
+        async fn orchestration_status(&self) -> OrchestrationStatus {
+            OrchestrationStatus {
+                session_id: "fake".to_string(),
+                phase: "Monitoring".to_string(),
+                progress: 0.5,
+                spawned_agents: 0,
+                completed: false,
+            }
+        }
+    }
+
+    /// Validator accepting any token as its own subject
+    struct AllowAllValidator;
+
+    #[async_trait]
+    impl toka_auth::TokenValidator for AllowAllValidator {
+        async fn validate(&self, raw: &str) -> toka_auth::Result<toka_auth::Claims> {
+            Ok(toka_auth::Claims {
+                sub: raw.to_string(),
+                vault: "test".into(),
+                permissions: vec![],
+                iat: 0,
+                exp: u64::MAX,
+                jti: "test".into(),
+            })
+        }
+    }
+
+    async fn create_test_integration() -> OrchestrationIntegration<FakeOrchestration> {
+        let bus = Arc::new(InMemoryBus::new(16));
+        let kernel = Kernel::new(WorldState::default(), Arc::new(AllowAllValidator), bus);
+        let runtime = RuntimeManager::new(RuntimeKernel::new(kernel)).await.unwrap();
+        // Never reached: a well-formed key pointing at a closed local port
+        let config = toka_llm_gateway::Config::new(toka_llm_gateway::ProviderConfig::Anthropic {
+            api_key: secrecy::Secret::new("sk-ant-test".to_string()),
+            model: "claude-3-5-sonnet-20241022".to_string(),
+            base_url: Some("http://127.0.0.1:9".to_string()),
+        });
+        let gateway = LlmGateway::new(config).await.unwrap();
+        Arc::new(FakeOrchestration::default())
+            .with_agent_runtime_integration(Arc::new(runtime), Arc::new(gateway))
+            .await
+            .unwrap()
+    }
+
+    fn session_monitor<E: OrchestrationBackend>(integration: &OrchestrationIntegration<E>) -> Option<AbortHandle> {
+        integration.session_monitor.lock().unwrap().clone()
+    }
 
     fn create_test_agent_config() -> AgentConfig {
         AgentConfig {
@@ -454,6 +566,7 @@ mod tests {
                     timeout: "5m".to_string(),
                 },
             },
+            placement: Default::default(),
         }
     }
 
@@ -476,7 +589,7 @@ mod tests {
     #[test]
     fn test_integration_metrics_default() {
         let metrics = IntegrationMetrics::default();
-        
+
         assert_eq!(metrics.total_agents_spawned, 0);
         assert_eq!(metrics.active_agents, 0);
         assert_eq!(metrics.completed_agents, 0);
@@ -493,11 +606,32 @@ mod tests {
             executor_handle: None,
             progress_tx: tx,
             started_at: Utc::now(),
-            state: AgentExecutionState::Spawning,
+            state: AgentExecutionState::Initializing,
             metrics: AgentMetrics::default(),
         };
 
         assert_eq!(active_info.config.metadata.name, "test-integration-agent");
-        assert_eq!(active_info.state, AgentExecutionState::Spawning);
+        assert_eq!(active_info.state, AgentExecutionState::Initializing);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_sessions_replace_monitor_until_shutdown() {
+        let integration = create_test_integration().await;
+
+        assert_eq!(integration.start_orchestrated_execution().await.unwrap(), 0);
+        let first = session_monitor(&integration).unwrap();
+        assert_eq!(integration.start_orchestrated_execution().await.unwrap(), 1);
+        let second = session_monitor(&integration).unwrap();
+        tokio::task::yield_now().await;
+
+        // Only the latest session is monitored
+        assert!(first.is_finished());
+        assert!(!second.is_finished());
+        assert_eq!(integration.get_metrics().await.orchestration_sessions, 2);
+
+        integration.shutdown().await.unwrap();
+        tokio::task::yield_now().await;
+        assert!(second.is_finished());
+        assert!(session_monitor(&integration).is_none());
+    }
+}
@@ -545,6 +545,7 @@ mod tests {
                     timeout: "5m".to_string(),
                 },
             },
+            placement: Default::default(),
         }
     }
 
@@ -1,8 +1,10 @@
 //! Progress reporting for agent execution.
 //!
 //! This module provides the ProgressReporter that communicates agent progress
-//! back to the orchestration system in real-time.
+//! back to the orchestration system in real-time, and the ProgressAggregator
+//! that rolls individual agent reports up into workstream and session summaries.
 
+use std::collections::HashMap;
 use std::time::Duration;
 
 use anyhow::Result;
@@ -10,10 +12,12 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use tracing::{debug, info, instrument};
 
+use toka_bus_core::{FailureReason, KernelEvent, TaskResult as EventTaskResult};
 use toka_runtime::RuntimeManager;
 use toka_types::{Message, Operation, EntityId};
 
 use crate::{AgentContext, AgentMetrics};
+use crate::tool_trace::ToolInvocation;
 
 /// Progress reporter for communicating agent status to orchestration
 pub struct ProgressReporter {
@@ -27,6 +31,8 @@ pub struct ProgressReporter {
     last_report: DateTime<Utc>,
     /// Agent metrics for reporting
     metrics: AgentMetrics,
+    /// Tool invocations of completed tasks not yet included in a progress report
+    pending_tool_invocations: Vec<ToolInvocation>,
 }
 
 /// Agent progress report sent to orchestration
@@ -36,6 +42,9 @@ pub struct AgentProgress {
     pub agent_id: crate::EntityId,
     /// Agent name
     pub agent_name: String,
+    /// Workstream the agent belongs to
+    #[serde(default)]
+    pub workstream: String,
     /// Current progress (0.0 to 1.0)
     pub progress: f64,
     /// Current status message
@@ -46,6 +55,9 @@ pub struct AgentProgress {
     pub timestamp: DateTime<Utc>,
     /// Agent metrics
     pub metrics: AgentMetrics,
+    /// Tool invocations of tasks completed since the previous report
+    #[serde(default)]
+    pub tool_invocations: Vec<ToolInvocation>,
 }
 
 /// Task execution result
@@ -65,6 +77,12 @@ pub struct TaskResult {
     pub llm_tokens_used: Option<u64>,
     /// Task completion timestamp
     pub completed_at: DateTime<Utc>,
+    /// Tools invoked while executing the task, in invocation order
+    #[serde(default)]
+    pub tool_invocations: Vec<ToolInvocation>,
+    /// Correlation identifier of the request that scheduled the task, if any
+    #[serde(default)]
+    pub correlation_id: Option<String>,
 }
 
 impl TaskResult {
@@ -83,6 +101,8 @@ impl TaskResult {
             duration,
             llm_tokens_used: None,
             completed_at: Utc::now(),
+            tool_invocations: Vec::new(),
+            correlation_id: None,
         }
     }
 
@@ -101,6 +121,8 @@ impl TaskResult {
             duration,
             llm_tokens_used: None,
             completed_at: Utc::now(),
+            tool_invocations: Vec::new(),
+            correlation_id: None,
         }
     }
 
@@ -109,6 +131,44 @@ impl TaskResult {
         self.llm_tokens_used = Some(tokens);
         self
     }
+
+    /// Add the tool invocations performed during the task
+    pub fn with_tool_invocations(mut self, invocations: Vec<ToolInvocation>) -> Self {
+        self.tool_invocations = invocations;
+        self
+    }
+
+    /// Tag the result with the correlation id of the scheduled task
+    pub fn with_correlation_id(mut self, correlation_id: Option<String>) -> Self {
+        self.correlation_id = correlation_id;
+        self
+    }
+
+    /// Event closing the task executed by `agent`
+    fn lifecycle_event(&self, agent: EntityId) -> KernelEvent {
+        if self.success {
+            KernelEvent::TaskCompleted {
+                task_id: self.task_id.clone(),
+                agent,
+                result: match &self.output {
+                    Some(output) => EventTaskResult::SuccessText { result: output.clone() },
+                    None => EventTaskResult::SuccessEmpty,
+                },
+                execution_time_ms: self.duration.as_millis() as u64,
+                correlation_id: self.correlation_id.clone(),
+                timestamp: self.completed_at,
+            }
+        } else {
+            KernelEvent::TaskFailed {
+                task_id: self.task_id.clone(),
+                agent,
+                error: self.output.clone().unwrap_or_default(),
+                failure_reason: FailureReason::AgentError,
+                correlation_id: self.correlation_id.clone(),
+                timestamp: self.completed_at,
+            }
+        }
+    }
 }
 
 impl ProgressReporter {
@@ -123,6 +183,7 @@ impl ProgressReporter {
             current_progress: 0.0,
             last_report: Utc::now(),
             metrics: AgentMetrics::default(),
+            pending_tool_invocations: Vec::new(),
         }
     }
 
@@ -141,11 +202,13 @@ impl ProgressReporter {
         let progress_report = AgentProgress {
             agent_id: self.agent_context.agent_id,
             agent_name: self.agent_context.config.metadata.name.clone(),
+            workstream: self.agent_context.config.metadata.workstream.clone(),
             progress,
             message: message.clone(),
             state: self.agent_context.state.clone(),
             timestamp: self.last_report,
             metrics: self.metrics.clone(),
+            tool_invocations: std::mem::take(&mut self.pending_tool_invocations),
         };
 
         debug!("Reporting progress: {}% for agent: {}", 
@@ -161,7 +224,7 @@ impl ProgressReporter {
             format!("progress-reporting-{}", self.agent_context.config.metadata.workstream),
             Operation::EmitObservation {
                 agent: self.agent_context.agent_id,
-                data: observation_data,
+                data: observation_data.clone(),
             },
         ).map_err(|e| anyhow::anyhow!("Failed to create progress message: {}", e))?;
 
@@ -180,6 +243,7 @@ impl ProgressReporter {
                 );
             }
         }
+        self.publish_observation(observation_data);
 
         // Also log progress for immediate visibility
         info!("Agent {} progress: {}%{}", 
@@ -209,6 +273,8 @@ impl ProgressReporter {
             self.metrics.llm_tokens_consumed += tokens;
             self.metrics.llm_requests += 1;
         }
+        self.pending_tool_invocations
+            .extend(task_result.tool_invocations.iter().cloned());
 
         // Serialize task completion as observation data
         let observation_data = serde_json::to_vec(&task_result)?;
@@ -219,7 +285,7 @@ impl ProgressReporter {
             format!("task-completion-{}", self.agent_context.config.metadata.workstream),
             Operation::EmitObservation {
                 agent: self.agent_context.agent_id,
-                data: observation_data,
+                data: observation_data.clone(),
             },
         ).map_err(|e| anyhow::anyhow!("Failed to create task completion message: {}", e))?;
 
@@ -238,6 +304,15 @@ impl ProgressReporter {
                 );
             }
         }
+        self.publish_observation(observation_data);
+
+        // Close the task's lifecycle under the correlation id it was scheduled with
+        if let Some(bus) = self.runtime.event_bus() {
+            let event = task_result.lifecycle_event(self.agent_context.agent_id);
+            if let Err(e) = bus.publish(&event) {
+                tracing::warn!("Failed to publish {} event for task {}: {}", event.kind(), task_result.task_id, e);
+            }
+        }
         
         Ok(())
     }
@@ -274,7 +349,7 @@ impl ProgressReporter {
             format!("agent-completion-{}", self.agent_context.config.metadata.workstream),
             Operation::EmitObservation {
                 agent: self.agent_context.agent_id,
-                data: completion_observation,
+                data: completion_observation.clone(),
             },
         ).map_err(|e| anyhow::anyhow!("Failed to create completion message: {}", e))?;
 
@@ -293,6 +368,7 @@ impl ProgressReporter {
                 );
             }
         }
+        self.publish_observation(completion_observation);
         
         info!("Agent {} completed with {} (final progress: {}%)",
               self.agent_context.config.metadata.name,
@@ -302,6 +378,21 @@ impl ProgressReporter {
         Ok(())
     }
 
+    /// Publish `data` as an observation of this agent on the runtime's event
+    /// bus, if configured, so orchestration can follow the agent's reports
+    fn publish_observation(&self, data: Vec<u8>) {
+        if let Some(bus) = self.runtime.event_bus() {
+            let event = KernelEvent::ObservationEmitted {
+                agent: self.agent_context.agent_id,
+                data: data.into(),
+                timestamp: Utc::now(),
+            };
+            if let Err(e) = bus.publish(&event) {
+                tracing::warn!("Failed to publish observation of agent {}: {}", self.agent_context.agent_id.0, e);
+            }
+        }
+    }
+
     /// Update metrics
     pub fn update_metrics(&mut self, metrics: AgentMetrics) {
         self.metrics = metrics;
@@ -318,10 +409,193 @@ impl ProgressReporter {
     }
 }
 
+/// Aggregated progress across a group of agents.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProgressRollup {
+    /// Weighted progress (0.0 to 1.0), weighted by each agent's task count
+    pub progress: f64,
+    /// Number of agents contributing to this rollup
+    pub agents: usize,
+    /// Agents that have finished (completed or failed)
+    pub agents_finished: usize,
+    /// Tasks attempted across all agents
+    pub tasks_attempted: u64,
+    /// Tasks completed successfully across all agents
+    pub tasks_completed: u64,
+    /// Tasks failed across all agents
+    pub tasks_failed: u64,
+    /// LLM tokens consumed across all agents
+    pub llm_tokens_consumed: u64,
+    /// Estimated time remaining, if enough progress has been observed
+    pub eta: Option<Duration>,
+    /// Timestamp of the most recent contributing report
+    pub last_update: Option<DateTime<Utc>>,
+}
+
+/// Progress summary for a single workstream.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkstreamProgress {
+    /// Workstream name
+    pub workstream: String,
+    /// Aggregated progress for the workstream
+    pub rollup: ProgressRollup,
+}
+
+/// Progress summary for a whole orchestration session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionProgress {
+    /// Aggregated progress across all workstreams
+    pub overall: ProgressRollup,
+    /// Per-workstream summaries keyed by workstream name
+    pub workstreams: HashMap<String, WorkstreamProgress>,
+}
+
+/// Rolls individual [`AgentProgress`] reports into workstream and session summaries.
+///
+/// Only the latest report per agent is kept. Each agent is weighted by the
+/// number of tasks it has attempted (minimum one), so an agent working through
+/// many tasks moves the summary more than an agent with a single task. ETAs are
+/// a linear extrapolation from the time the first report was observed.
+#[derive(Debug, Clone, Default)]
+pub struct ProgressAggregator {
+    /// Latest report per agent
+    latest: HashMap<crate::EntityId, AgentProgress>,
+    /// Timestamp of the first report per workstream
+    first_seen: HashMap<String, DateTime<Utc>>,
+}
+
+impl ProgressAggregator {
+    /// Create an empty aggregator
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a progress report, replacing any previous report for the same agent
+    pub fn record(&mut self, report: AgentProgress) {
+        self.first_seen
+            .entry(report.workstream.clone())
+            .and_modify(|first| *first = (*first).min(report.timestamp))
+            .or_insert(report.timestamp);
+        self.latest.insert(report.agent_id, report);
+    }
+
+    /// Forget an agent, e.g. after it has been removed from the session
+    pub fn remove(&mut self, agent_id: &crate::EntityId) -> Option<AgentProgress> {
+        self.latest.remove(agent_id)
+    }
+
+    /// Summarize progress for a single workstream
+    pub fn workstream(&self, workstream: &str) -> Option<WorkstreamProgress> {
+        let reports: Vec<&AgentProgress> = self
+            .latest
+            .values()
+            .filter(|report| report.workstream == workstream)
+            .collect();
+        if reports.is_empty() {
+            return None;
+        }
+
+        Some(WorkstreamProgress {
+            workstream: workstream.to_string(),
+            rollup: Self::rollup(&reports, self.first_seen.get(workstream).copied()),
+        })
+    }
+
+    /// Summarize progress for the whole session
+    pub fn session(&self) -> SessionProgress {
+        let mut names: Vec<&str> = self.latest.values().map(|r| r.workstream.as_str()).collect();
+        names.sort_unstable();
+        names.dedup();
+
+        let workstreams = names
+            .into_iter()
+            .filter_map(|name| self.workstream(name).map(|ws| (name.to_string(), ws)))
+            .collect();
+
+        let reports: Vec<&AgentProgress> = self.latest.values().collect();
+        let started = self.first_seen.values().min().copied();
+
+        SessionProgress {
+            overall: Self::rollup(&reports, started),
+            workstreams,
+        }
+    }
+
+    /// Build a rollup from a set of agent reports
+    fn rollup(reports: &[&AgentProgress], started: Option<DateTime<Utc>>) -> ProgressRollup {
+        let mut weighted = 0.0;
+        let mut total_weight = 0.0;
+        let mut rollup = ProgressRollup {
+            progress: 0.0,
+            agents: reports.len(),
+            agents_finished: 0,
+            tasks_attempted: 0,
+            tasks_completed: 0,
+            tasks_failed: 0,
+            llm_tokens_consumed: 0,
+            eta: None,
+            last_update: None,
+        };
+
+        for report in reports {
+            let finished = matches!(
+                report.state,
+                crate::AgentExecutionState::Completed | crate::AgentExecutionState::Failed { .. }
+            );
+            // Finished agents count as fully progressed for rollup purposes
+            let progress = if finished { 1.0 } else { report.progress.clamp(0.0, 1.0) };
+            let weight = report.metrics.tasks_attempted.max(1) as f64;
+
+            weighted += progress * weight;
+            total_weight += weight;
+
+            if finished {
+                rollup.agents_finished += 1;
+            }
+            rollup.tasks_attempted += report.metrics.tasks_attempted;
+            rollup.tasks_completed += report.metrics.tasks_completed;
+            rollup.tasks_failed += report.metrics.tasks_failed;
+            rollup.llm_tokens_consumed += report.metrics.llm_tokens_consumed;
+            rollup.last_update = rollup.last_update.max(Some(report.timestamp));
+        }
+
+        if total_weight > 0.0 {
+            rollup.progress = weighted / total_weight;
+        }
+        rollup.eta = Self::estimate_eta(rollup.progress, started, rollup.last_update);
+        rollup
+    }
+
+    /// Linear ETA: elapsed * (remaining / done)
+    fn estimate_eta(
+        progress: f64,
+        started: Option<DateTime<Utc>>,
+        last_update: Option<DateTime<Utc>>,
+    ) -> Option<Duration> {
+        if progress >= 1.0 {
+            return Some(Duration::ZERO);
+        }
+        if progress <= 0.0 {
+            return None;
+        }
+
+        let elapsed = (last_update? - started?).to_std().ok()?;
+        if elapsed.is_zero() {
+            return None;
+        }
+        Some(elapsed.mul_f64((1.0 - progress) / progress))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{AgentExecutionState, EntityId};
+    use std::sync::Arc;
+    use toka_bus_core::{EventBus, InMemoryBus};
+    use toka_kernel::{Kernel, WorldState};
+    use toka_runtime::{RuntimeBuilder, RuntimeKernel};
+    use toka_types::TaskSpec;
     use toka_types::{
         AgentConfig, AgentMetadata, AgentSpecConfig, AgentPriority, TaskPriority
     };
@@ -369,12 +643,14 @@ mod tests {
                         timeout: "5m".to_string(),
                     },
                 },
+                placement: Default::default(),
             },
             state: AgentExecutionState::Ready,
             started_at: Utc::now(),
             last_activity: Utc::now(),
             metrics: AgentMetrics::default(),
             environment: HashMap::new(),
+            budget: None,
         }
     }
 
@@ -415,10 +691,84 @@ mod tests {
         assert_eq!(result.llm_tokens_used, Some(150));
     }
 
+    #[test]
+    fn test_task_result_tool_invocations() {
+        let invocation = ToolInvocation {
+            tool: "read_file".to_string(),
+            started_at: Utc::now(),
+            duration: Duration::from_millis(12),
+            outcome: crate::ToolOutcome::Succeeded,
+            artifacts: vec!["Cargo.toml".to_string()],
+        };
+        let result = TaskResult::success(
+            "task-1".to_string(),
+            "Inspect manifest".to_string(),
+            None,
+            Duration::from_secs(1),
+        ).with_tool_invocations(vec![invocation.clone()]);
+
+        let serialized = serde_json::to_value(&result).unwrap();
+        let deserialized: TaskResult = serde_json::from_value(serialized.clone()).unwrap();
+        assert_eq!(deserialized.tool_invocations, vec![invocation]);
+
+        // Results reported before tool tracing existed still parse
+        let mut legacy = serialized;
+        legacy.as_object_mut().unwrap().remove("tool_invocations");
+        let legacy: TaskResult = serde_json::from_value(legacy).unwrap();
+        assert!(legacy.tool_invocations.is_empty());
+    }
+
+    /// Validator accepting any token as its own subject
+    struct AllowAllValidator;
+
+    #[async_trait::async_trait]
+    impl toka_auth::TokenValidator for AllowAllValidator {
+        async fn validate(&self, raw: &str) -> toka_auth::Result<toka_auth::Claims> {
+            Ok(toka_auth::Claims {
+                sub: raw.to_string(),
+                vault: "test".into(),
+                permissions: vec![],
+                iat: 0,
+                exp: u64::MAX,
+                jti: "test".into(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_task_outcome_carries_scheduled_correlation_id() {
+        let bus = Arc::new(InMemoryBus::new(16));
+        let kernel = Kernel::new(WorldState::default(), Arc::new(AllowAllValidator), bus.clone());
+        let agent = EntityId(123);
+        let task = TaskSpec::new("traced task".to_string()).unwrap();
+        let schedule = Message::new(agent, "123".to_string(), Operation::ScheduleAgentTask { agent, task })
+            .unwrap()
+            .with_correlation_id("req-1");
+        kernel.submit(schedule).await.unwrap();
+        let queued = kernel.state_ptr().read().await.agent_tasks[&agent][0].clone();
+
+        let runtime = RuntimeBuilder::new(RuntimeKernel::new(kernel)).with_event_bus(bus.clone()).build().await.unwrap();
+        let mut events = bus.subscribe();
+        let mut reporter = ProgressReporter::new(create_test_context(), Arc::new(runtime));
+        let result = TaskResult::success("task-1".to_string(), queued.description, None, Duration::from_millis(5))
+            .with_correlation_id(queued.correlation_id);
+        reporter.report_task_completion(result).await.unwrap();
+
+        // The completion observation is followed by the event closing the task
+        assert!(matches!(events.recv().await.unwrap(), KernelEvent::ObservationEmitted { .. }));
+        match events.recv().await.unwrap() {
+            KernelEvent::TaskCompleted { task_id, agent: completed_by, correlation_id, .. } => {
+                assert_eq!((task_id.as_str(), completed_by), ("task-1", agent));
+                assert_eq!(correlation_id.as_deref(), Some("req-1"));
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
     #[tokio::test]
     async fn test_progress_clamping() {
         // Test that progress is properly clamped
-        let test_values = vec![-0.5, 0.0, 0.5, 1.0, 1.5];
+        let test_values: Vec<f64> = vec![-0.5, 0.0, 0.5, 1.0, 1.5];
         let expected = vec![0.0, 0.0, 0.5, 1.0, 1.0];
         
         for (input, expected) in test_values.iter().zip(expected.iter()) {
@@ -432,11 +782,13 @@ mod tests {
         let progress = AgentProgress {
             agent_id: EntityId(456),
             agent_name: "test-agent".to_string(),
+            workstream: "test".to_string(),
             progress: 0.75,
             message: Some("Test message".to_string()),
             state: AgentExecutionState::Ready,
             timestamp: Utc::now(),
             metrics: AgentMetrics::default(),
+            tool_invocations: Vec::new(),
         };
 
         let serialized = serde_json::to_string(&progress).unwrap();
@@ -446,4 +798,54 @@ mod tests {
         assert_eq!(progress.progress, deserialized.progress);
         assert_eq!(progress.agent_name, deserialized.agent_name);
     }
+
+    fn report(
+        id: u128,
+        workstream: &str,
+        progress: f64,
+        tasks: u64,
+        timestamp: DateTime<Utc>,
+    ) -> AgentProgress {
+        AgentProgress {
+            agent_id: EntityId(id),
+            agent_name: format!("agent-{}", id),
+            workstream: workstream.to_string(),
+            progress,
+            message: None,
+            state: AgentExecutionState::Ready,
+            timestamp,
+            metrics: AgentMetrics {
+                tasks_attempted: tasks,
+                ..AgentMetrics::default()
+            },
+            tool_invocations: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_progress_rollup_weighting_and_eta() {
+        let start = Utc::now();
+        let later = start + chrono::Duration::seconds(60);
+        let mut aggregator = ProgressAggregator::new();
+
+        // Agent with 3 tasks at 100%, agent with 1 task at 0% => 75% weighted
+        aggregator.record(report(1, "build", 0.0, 3, start));
+        aggregator.record(report(1, "build", 1.0, 3, later));
+        aggregator.record(report(2, "build", 0.0, 1, later));
+        aggregator.record(report(3, "docs", 0.5, 1, later));
+
+        let build = aggregator.workstream("build").unwrap();
+        assert_eq!(build.rollup.agents, 2);
+        assert_eq!(build.rollup.tasks_attempted, 4);
+        assert!((build.rollup.progress - 0.75).abs() < 1e-9);
+        // 60s elapsed for 75% => 20s remaining
+        let eta = build.rollup.eta.unwrap();
+        assert!((eta.as_secs_f64() - 20.0).abs() < 1e-3);
+
+        let session = aggregator.session();
+        assert_eq!(session.overall.agents, 3);
+        assert_eq!(session.workstreams.len(), 2);
+        assert!((session.overall.progress - 0.7).abs() < 1e-9);
+        assert!(aggregator.workstream("missing").is_none());
+    }
 }
\ No newline at end of file
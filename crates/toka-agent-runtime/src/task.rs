@@ -15,6 +15,8 @@ use uuid::Uuid;
 use toka_llm_gateway::{LlmGateway, LlmRequest, LlmResponse};
 use toka_types::{TaskConfig, TaskPriority, SecurityConfig, EntityId};
 
+use crate::tool_suggestions::{render_tool_suggestions, ToolSuggester, ToolSuggestion, DEFAULT_SUGGESTED_TOOLS};
+use crate::tool_trace::{ToolInvoker, ToolTrace, TracedToolInvoker};
 use crate::{
     AgentContext, AgentTask, TaskResult, CapabilityValidator, ResourceManager,
     AgentRuntimeError, AgentRuntimeResult, ExecutionConfig, RetryConfig,
@@ -30,6 +32,12 @@ pub struct TaskExecutor {
     resource_manager: ResourceManager,
     /// Execution configuration
     execution_config: ExecutionConfig,
+    /// Optional source of task-relevant tool suggestions
+    tool_suggester: Option<std::sync::Arc<dyn ToolSuggester>>,
+    /// Number of tools to suggest per task
+    suggested_tools: usize,
+    /// Optional tool invoker whose invocations are traced per task
+    tool_invoker: Option<std::sync::Arc<TracedToolInvoker>>,
 }
 
 /// LLM-based task implementation
@@ -58,6 +66,9 @@ pub struct TaskExecutionContext {
     pub available_tools: Vec<String>,
     /// Previous task results for context
     pub previous_results: Vec<TaskResult>,
+    /// Tools suggested as relevant to the current task
+    #[serde(default)]
+    pub suggested_tools: Vec<ToolSuggestion>,
 }
 
 /// LLM prompt template for task execution
@@ -90,9 +101,42 @@ impl TaskExecutor {
             capability_validator,
             resource_manager,
             execution_config,
+            tool_suggester: None,
+            suggested_tools: DEFAULT_SUGGESTED_TOOLS,
+            tool_invoker: None,
         })
     }
 
+    /// Suggest the `top_k` most relevant tools in each task prompt.
+    ///
+    /// Without a suggester the prompt lists every available tool.
+    pub fn with_tool_suggester(
+        mut self,
+        suggester: std::sync::Arc<dyn ToolSuggester>,
+        top_k: usize,
+    ) -> Self {
+        self.tool_suggester = Some(suggester);
+        self.suggested_tools = top_k;
+        self
+    }
+
+    /// Run tools through `invoker`, recording each invocation in the
+    /// [`TaskResult`] of the task that made it.
+    pub fn with_tool_invoker(mut self, invoker: std::sync::Arc<dyn ToolInvoker>) -> Self {
+        self.tool_invoker = Some(std::sync::Arc::new(TracedToolInvoker::new(invoker, ToolTrace::new())));
+        self
+    }
+
+    /// Tool invoker for tasks to run tools through, if one is configured.
+    ///
+    /// Invocations made through it are attached to the result of the task
+    /// currently executing.
+    pub fn tool_invoker(&self) -> Option<std::sync::Arc<dyn ToolInvoker>> {
+        self.tool_invoker
+            .clone()
+            .map(|invoker| invoker as std::sync::Arc<dyn ToolInvoker>)
+    }
+
     /// Execute a task with LLM assistance and security validation
     #[instrument(skip(self, context), fields(task_id = %task.task_id()))]
     pub async fn execute_task(
@@ -105,6 +149,11 @@ impl TaskExecutor {
 
         info!("Starting task execution: {}", task_id);
 
+        // Invocations left over from an earlier task do not belong to this one
+        if let Some(invoker) = &self.tool_invoker {
+            invoker.trace().take();
+        }
+
         // Validate task permissions
         self.validate_task_permissions(task, context)?;
 
@@ -120,7 +169,7 @@ impl TaskExecutor {
                 Ok(result) => {
                     let duration = start_time.elapsed();
                     info!("Task completed successfully: {} (duration: {:?})", task_id, duration);
-                    return Ok(result);
+                    return Ok(self.attach_tool_invocations(result));
                 }
                 Err(error) => {
                     retry_count += 1;
@@ -130,12 +179,12 @@ impl TaskExecutor {
                         error!("Task failed after {} attempts: {} (error: {})", 
                                retry_count, task_id, error);
                         
-                        return Ok(TaskResult::failure(
+                        return Ok(self.attach_tool_invocations(TaskResult::failure(
                             task_id,
                             task.description().to_string(),
                             error.to_string(),
                             duration,
-                        ));
+                        )));
                     }
 
                     // Calculate retry delay
@@ -161,7 +210,8 @@ impl TaskExecutor {
         let task_id = task.task_id().to_string();
 
         // Create task execution context
-        let task_context = self.create_task_context(context).await?;
+        let mut task_context = self.create_task_context(context).await?;
+        task_context.suggested_tools = self.suggest_tools(task).await;
 
         // Build LLM prompt
         let prompt = self.build_task_prompt(task, &task_context, retry_count)?;
@@ -169,9 +219,14 @@ impl TaskExecutor {
         // Execute with LLM
         debug!("Sending task to LLM: {}", task_id);
         
-        // Create proper LLM request with agent metadata
+        // Create proper LLM request with agent metadata, asking for shorter
+        // responses as the session budget runs out
+        let max_tokens = context
+            .budget
+            .as_ref()
+            .map_or(4096, |budget| budget.response_token_limit(4096));
         let mut llm_request = LlmRequest::new(prompt)?
-            .with_max_tokens(4096);
+            .with_max_tokens(max_tokens);
         
         // Add retry context with lower temperature for more deterministic results
         if retry_count > 0 {
@@ -202,6 +257,33 @@ impl TaskExecutor {
         Ok(task_result)
     }
 
+    /// Move the tool invocations traced during the task into its result.
+    fn attach_tool_invocations(&self, result: TaskResult) -> TaskResult {
+        match &self.tool_invoker {
+            Some(invoker) => result.with_tool_invocations(invoker.trace().take()),
+            None => result,
+        }
+    }
+
+    /// Look up tools relevant to the task, falling back to none on failure.
+    async fn suggest_tools(&self, task: &dyn AgentTask) -> Vec<ToolSuggestion> {
+        let Some(suggester) = &self.tool_suggester else {
+            return Vec::new();
+        };
+
+        match suggester.suggest_tools(task.description(), self.suggested_tools).await {
+            Ok(mut suggestions) => {
+                suggestions.truncate(self.suggested_tools);
+                suggestions
+            }
+            Err(error) => {
+                // Suggestions only refine the prompt; never fail the task over them
+                warn!("Tool suggestion failed, listing all tools: {}", error);
+                Vec::new()
+            }
+        }
+    }
+
     /// Set agent metadata on LLM request
     fn set_agent_metadata_on_request(&self, request: &mut LlmRequest, context: &AgentContext) -> Result<()> {
         // Access the metadata through the request to update it
@@ -256,6 +338,19 @@ impl TaskExecutor {
         task_environment.insert("AGENT_DOMAIN".to_string(), context.config.spec.domain.clone());
         task_environment.insert("AGENT_NAME".to_string(), context.config.metadata.name.clone());
 
+        // Expose the remaining session budget to tools and subprocesses
+        if let Some(budget) = &context.budget {
+            if let Some(tokens) = budget.tokens {
+                task_environment.insert("BUDGET_TOKENS_REMAINING".to_string(), tokens.to_string());
+            }
+            if let Some(time) = budget.time {
+                task_environment.insert("BUDGET_SECONDS_REMAINING".to_string(), time.as_secs().to_string());
+            }
+            if let Some(cost) = budget.cost_usd {
+                task_environment.insert("BUDGET_USD_REMAINING".to_string(), format!("{:.2}", cost));
+            }
+        }
+
         // Add working directory
         let working_directory = std::env::current_dir()
             .unwrap_or_else(|_| "/workspace".into())
@@ -271,6 +366,7 @@ impl TaskExecutor {
             working_directory,
             available_tools,
             previous_results: vec![], // TODO: Implement task history
+            suggested_tools: Vec::new(),
         })
     }
 
@@ -288,15 +384,28 @@ impl TaskExecutor {
             .replace("{agent_domain}", &context.agent_context.config.spec.domain)
             .replace("{workstream}", &context.agent_context.config.metadata.workstream);
 
+        // Prefer the targeted suggestions over the full tool catalogue
+        let tools = if context.suggested_tools.is_empty() {
+            format!("Available Tools: {}", context.available_tools.join(", "))
+        } else {
+            render_tool_suggestions(&context.suggested_tools)
+        };
+
         let task_prompt = format!(
-            "{}\n\nTask: {}\nDescription: {}\nWorking Directory: {}\nAvailable Tools: {}\n\nPlease execute this task step by step and provide a clear summary of what was accomplished.",
+            "{}\n\nTask: {}\nDescription: {}\nWorking Directory: {}\n{}\n\nPlease execute this task step by step and provide a clear summary of what was accomplished.",
             system_prompt,
             task.task_id(),
             task.description(),
             context.working_directory,
-            context.available_tools.join(", ")
+            tools
         );
 
+        // Ask the planner to adapt to a shrinking budget
+        let task_prompt = match context.agent_context.budget.as_ref().and_then(|budget| budget.planning_hint()) {
+            Some(hint) => format!("{}\n\n{}", task_prompt, hint),
+            None => task_prompt,
+        };
+
         // Add retry context if this is a retry attempt
         let final_prompt = if retry_count > 0 {
             format!(
@@ -536,9 +645,9 @@ mod tests {
         assert!(task.is_retryable());
     }
 
-    #[test]
-    fn test_capability_inference() {
-        let executor = create_mock_task_executor();
+    #[tokio::test]
+    async fn test_capability_inference() {
+        let executor = create_mock_task_executor().await;
         
         let test_cases = vec![
             ("Read configuration file", vec!["filesystem-read"]),
@@ -557,9 +666,9 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_retry_delay_calculation() {
-        let executor = create_mock_task_executor();
+    #[tokio::test]
+    async fn test_retry_delay_calculation() {
+        let executor = create_mock_task_executor().await;
         
         let delay1 = executor.calculate_retry_delay(1);
         let delay2 = executor.calculate_retry_delay(2);
@@ -574,9 +683,9 @@ mod tests {
         assert!(delay3 <= max_delay);
     }
 
-    #[test]
-    fn test_prompt_template_selection() {
-        let executor = create_mock_task_executor();
+    #[tokio::test]
+    async fn test_prompt_template_selection() {
+        let executor = create_mock_task_executor().await;
         
         let infrastructure_template = executor.get_prompt_template("infrastructure");
         assert!(infrastructure_template.system_prompt.contains("infrastructure agent"));
@@ -588,12 +697,10 @@ mod tests {
         assert!(default_template.system_prompt.contains("intelligent agent"));
     }
 
-    fn create_mock_task_executor() -> TaskExecutor {
+    async fn create_mock_task_executor() -> TaskExecutor {
         let security_config = create_test_security_config();
         let execution_config = ExecutionConfig::default();
         
-        // This is a mock for testing - we can't easily create a real LlmGateway in tests
-        // without API keys and network access
         let capability_validator = CapabilityValidator::new(
             security_config.capabilities_required.clone(),
             security_config.clone(),
@@ -601,43 +708,24 @@ mod tests {
         let resource_manager = ResourceManager::new(security_config.resource_limits.clone()).unwrap();
         
         TaskExecutor {
-            llm_gateway: std::sync::Arc::new(MockLlmGateway::new()),
+            llm_gateway: std::sync::Arc::new(offline_gateway().await),
             capability_validator,
             resource_manager,
             execution_config,
+            tool_suggester: None,
+            suggested_tools: DEFAULT_SUGGESTED_TOOLS,
+            tool_invoker: None,
         }
     }
 
-    // Mock LLM Gateway for testing
-    struct MockLlmGateway;
-    
-    impl MockLlmGateway {
-        fn new() -> Self {
-            Self
-        }
-    }
-    
-    #[async_trait::async_trait]
-    impl LlmGateway for MockLlmGateway {
-        async fn complete(&self, _request: LlmRequest) -> Result<LlmResponse> {
-            // Mock implementation for testing
-            use toka_llm_gateway::{TokenUsage, ResponseMetadata};
-            
-            LlmResponse::new(
-                "Mock LLM response".to_string(),
-                TokenUsage {
-                    prompt_tokens: 10,
-                    completion_tokens: 20,
-                    total_tokens: 30,
-                },
-                "mock".to_string(),
-                "mock-model".to_string(),
-                std::time::Duration::from_millis(100),
-            )
-        }
-        
-        async fn metrics(&self) -> toka_llm_gateway::GatewayMetrics {
-            toka_llm_gateway::GatewayMetrics::default()
-        }
+    /// Gateway that is never reached in these tests: a well-formed key
+    /// pointing at a closed local port, so no API key or network is needed
+    async fn offline_gateway() -> LlmGateway {
+        let config = toka_llm_gateway::Config::new(toka_llm_gateway::ProviderConfig::Anthropic {
+            api_key: secrecy::Secret::new("sk-ant-test".to_string()),
+            model: "claude-3-5-sonnet-20241022".to_string(),
+            base_url: Some("http://127.0.0.1:9".to_string()),
+        });
+        LlmGateway::new(config).await.unwrap()
     }
-}
\ No newline at end of file
+}
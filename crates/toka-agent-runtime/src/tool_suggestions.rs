@@ -0,0 +1,88 @@
+//! Task-relevant tool suggestions for LLM prompts.
+//!
+//! Listing every registered tool in each planning prompt wastes context and
+//! makes the model more likely to pick an unsuitable tool. Instead, the task
+//! executor can consult a [`ToolSuggester`] (typically backed by a vector
+//! index over tool descriptions) for the top-K tools relevant to the current
+//! task and inject only their schemas and examples into the prompt.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// Default number of tools suggested per task.
+pub const DEFAULT_SUGGESTED_TOOLS: usize = 5;
+
+/// A tool judged relevant to a task.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolSuggestion {
+    /// Tool name as registered
+    pub name: String,
+    /// Human-readable description
+    pub description: String,
+    /// JSON schema of the tool's parameters, if known
+    #[serde(default)]
+    pub schema: Option<serde_json::Value>,
+    /// Example invocations
+    #[serde(default)]
+    pub examples: Vec<String>,
+    /// Relevance score (higher is more relevant)
+    pub score: f32,
+}
+
+/// Source of task-relevant tool suggestions.
+#[async_trait]
+pub trait ToolSuggester: Send + Sync {
+    /// Return up to `top_k` tools relevant to `task_description`, most relevant first.
+    async fn suggest_tools(&self, task_description: &str, top_k: usize) -> Result<Vec<ToolSuggestion>>;
+}
+
+/// Render suggestions as a prompt section.
+pub fn render_tool_suggestions(suggestions: &[ToolSuggestion]) -> String {
+    let mut rendered = String::from("Relevant Tools:");
+    for suggestion in suggestions {
+        rendered.push_str(&format!("\n- {}: {}", suggestion.name, suggestion.description));
+        if let Some(schema) = &suggestion.schema {
+            rendered.push_str(&format!("\n  Parameters: {}", schema));
+        }
+        for example in &suggestion.examples {
+            rendered.push_str(&format!("\n  Example: {}", example));
+        }
+    }
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_tool_suggestions() {
+        let suggestions = vec![
+            ToolSuggestion {
+                name: "file-reader".to_string(),
+                description: "Read files from the workspace".to_string(),
+                schema: Some(serde_json::json!({"path": "string"})),
+                examples: vec!["file-reader path=Cargo.toml".to_string()],
+                score: 0.92,
+            },
+            ToolSuggestion {
+                name: "git-status".to_string(),
+                description: "Show working tree status".to_string(),
+                schema: None,
+                examples: Vec::new(),
+                score: 0.71,
+            },
+        ];
+
+        let rendered = render_tool_suggestions(&suggestions);
+        assert_eq!(
+            rendered,
+            "Relevant Tools:\n\
+             - file-reader: Read files from the workspace\n  \
+             Parameters: {\"path\":\"string\"}\n  \
+             Example: file-reader path=Cargo.toml\n\
+             - git-status: Show working tree status"
+        );
+    }
+}
@@ -0,0 +1,209 @@
+//! Tracing of tool invocations performed by agent tasks.
+//!
+//! A task result that only says "completed" tells a reviewer little about
+//! what the agent actually did. Tools invoked through a [`TracedToolInvoker`]
+//! are recorded in a shared [`ToolTrace`], which the task executor drains
+//! into each [`TaskResult`](crate::TaskResult) and the progress reporter
+//! forwards in [`AgentProgress`](crate::AgentProgress) reports.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use toka_types::traits::ToolParams;
+use toka_types::ToolResult;
+
+/// Tool arguments that name files or directories a tool worked on.
+pub const ARTIFACT_ARGS: &[&str] = &["path", "workspace_path"];
+
+/// Executes tools on behalf of agent tasks.
+///
+/// Typically backed by a tool registry; kept as a trait so the agent runtime
+/// does not depend on a particular registry implementation.
+#[async_trait]
+pub trait ToolInvoker: Send + Sync {
+    /// Run the tool named in `params`.
+    async fn invoke_tool(&self, params: &ToolParams) -> Result<ToolResult>;
+}
+
+/// How a traced tool invocation ended.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ToolOutcome {
+    /// The tool ran and reported success
+    Succeeded,
+    /// The tool ran but reported failure
+    Failed {
+        /// Tool output describing the failure
+        output: String,
+    },
+    /// The tool could not be run
+    Error {
+        /// Error raised by the invoker
+        message: String,
+    },
+}
+
+impl ToolOutcome {
+    /// Whether the tool succeeded
+    pub fn is_success(&self) -> bool {
+        matches!(self, ToolOutcome::Succeeded)
+    }
+}
+
+/// A single tool invocation performed during a task.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolInvocation {
+    /// Tool name
+    pub tool: String,
+    /// When the invocation started
+    pub started_at: DateTime<Utc>,
+    /// Wall-clock duration of the invocation
+    pub duration: Duration,
+    /// How the invocation ended
+    pub outcome: ToolOutcome,
+    /// Files or directories the tool worked on
+    #[serde(default)]
+    pub artifacts: Vec<String>,
+}
+
+/// Shared, append-only record of tool invocations.
+///
+/// Cloning a trace yields a handle to the same record.
+#[derive(Debug, Clone, Default)]
+pub struct ToolTrace {
+    invocations: Arc<Mutex<Vec<ToolInvocation>>>,
+}
+
+impl ToolTrace {
+    /// Create an empty trace
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append an invocation
+    pub fn record(&self, invocation: ToolInvocation) {
+        self.invocations.lock().unwrap().push(invocation);
+    }
+
+    /// Number of invocations recorded and not yet taken
+    pub fn len(&self) -> usize {
+        self.invocations.lock().unwrap().len()
+    }
+
+    /// Whether no invocations are waiting to be taken
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove and return all recorded invocations, oldest first
+    pub fn take(&self) -> Vec<ToolInvocation> {
+        std::mem::take(&mut *self.invocations.lock().unwrap())
+    }
+}
+
+/// [`ToolInvoker`] wrapper recording every invocation in a [`ToolTrace`].
+pub struct TracedToolInvoker {
+    inner: Arc<dyn ToolInvoker>,
+    trace: ToolTrace,
+}
+
+impl TracedToolInvoker {
+    /// Record the invocations of `inner` in `trace`
+    pub fn new(inner: Arc<dyn ToolInvoker>, trace: ToolTrace) -> Self {
+        Self { inner, trace }
+    }
+
+    /// The trace invocations are recorded in
+    pub fn trace(&self) -> &ToolTrace {
+        &self.trace
+    }
+}
+
+#[async_trait]
+impl ToolInvoker for TracedToolInvoker {
+    async fn invoke_tool(&self, params: &ToolParams) -> Result<ToolResult> {
+        let started_at = Utc::now();
+        let start = Instant::now();
+        let result = self.inner.invoke_tool(params).await;
+
+        let outcome = match &result {
+            Ok(result) if result.success => ToolOutcome::Succeeded,
+            Ok(result) => ToolOutcome::Failed { output: result.output.clone() },
+            Err(error) => ToolOutcome::Error { message: error.to_string() },
+        };
+        let artifacts = ARTIFACT_ARGS
+            .iter()
+            .filter_map(|arg| params.args.get(*arg).cloned())
+            .collect();
+
+        self.trace.record(ToolInvocation {
+            tool: params.name.clone(),
+            started_at,
+            duration: start.elapsed(),
+            outcome,
+            artifacts,
+        });
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use toka_types::ToolMetadata;
+
+    struct EchoInvoker;
+
+    #[async_trait]
+    impl ToolInvoker for EchoInvoker {
+        async fn invoke_tool(&self, params: &ToolParams) -> Result<ToolResult> {
+            match params.name.as_str() {
+                "missing" => Err(anyhow::anyhow!("tool not found: missing")),
+                name => Ok(ToolResult {
+                    success: name != "failing",
+                    output: format!("ran {}", name),
+                    metadata: ToolMetadata {
+                        execution_time_ms: 0,
+                        tool_version: "1.0.0".to_string(),
+                        timestamp: 0,
+                    },
+                }),
+            }
+        }
+    }
+
+    fn params(name: &str, args: &[(&str, &str)]) -> ToolParams {
+        ToolParams {
+            name: name.to_string(),
+            args: args
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<HashMap<_, _>>(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_traced_invoker_records_invocations() {
+        let trace = ToolTrace::new();
+        let invoker = TracedToolInvoker::new(Arc::new(EchoInvoker), trace.clone());
+
+        invoker.invoke_tool(&params("read_file", &[("path", "Cargo.toml")])).await.unwrap();
+        invoker.invoke_tool(&params("failing", &[])).await.unwrap();
+        assert!(invoker.invoke_tool(&params("missing", &[])).await.is_err());
+
+        let invocations = trace.take();
+        assert!(trace.is_empty());
+        assert_eq!(invocations.len(), 3);
+        assert_eq!(invocations[0].tool, "read_file");
+        assert_eq!(invocations[0].outcome, ToolOutcome::Succeeded);
+        assert_eq!(invocations[0].artifacts, vec!["Cargo.toml".to_string()]);
+        assert_eq!(invocations[1].outcome, ToolOutcome::Failed { output: "ran failing".to_string() });
+        assert!(matches!(invocations[2].outcome, ToolOutcome::Error { .. }));
+    }
+}
@@ -0,0 +1,33 @@
+//! Token validation latency with and without the validation cache.
+//!
+//! Run with `cargo bench -p toka-auth --bench token_validation`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use toka_auth::{CachingValidator, CapabilityToken, JwtHs256Token, JwtHs256Validator, TokenValidator};
+
+const SECRET: &str = "bench-secret";
+
+fn token_validation(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let token = JwtHs256Token::new("agent", "vault", vec!["read".into(), "write".into()], SECRET, 3600)
+        .unwrap();
+    let raw = token.as_str();
+
+    let mut group = c.benchmark_group("token_validation");
+
+    let uncached = JwtHs256Validator::new(SECRET);
+    group.bench_function("hs256", |b| {
+        b.iter(|| runtime.block_on(uncached.validate(raw)).unwrap())
+    });
+
+    let cached = CachingValidator::new(JwtHs256Validator::new(SECRET));
+    runtime.block_on(cached.validate(raw)).unwrap();
+    group.bench_function("hs256_cached", |b| {
+        b.iter(|| runtime.block_on(cached.validate(raw)).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, token_validation);
+criterion_main!(benches);
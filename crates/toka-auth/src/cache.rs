@@ -0,0 +1,356 @@
+//! Validation cache for capability tokens.
+//!
+//! Agents submit many operations with the same token, and checking an HS256
+//! signature on every message dominates kernel submission latency.
+//! [`CachingValidator`] wraps any [`TokenValidator`] and remembers the claims
+//! of tokens it has already verified until they expire.
+//!
+//! Entries are keyed by the full raw token rather than by its `jti` alone:
+//! the `jti` is only trustworthy once the signature is checked, so a forged
+//! token reusing a cached `jti` still goes to the inner validator. The `jti`
+//! indexes entries for revocation through a shared [`RevocationList`].
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+
+use crate::{Claims, Error, Result, TokenValidator};
+
+/// Default number of tokens kept by [`CachingValidator`].
+pub const DEFAULT_CACHE_CAPACITY: usize = 1024;
+
+/// Default longest time a verified token is trusted without re-validation.
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(300);
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        // A clock before the epoch makes every token look expired
+        .unwrap_or(u64::MAX)
+}
+
+/// Token identifiers (`jti`) that must no longer be accepted.
+///
+/// Clones share the same list, so the component that revokes tokens (an
+/// admin API, a logout handler) and every [`CachingValidator`] consulting it
+/// see revocations immediately. Revocations are forgotten once the token
+/// would have expired anyway.
+#[derive(Clone, Debug, Default)]
+pub struct RevocationList {
+    revoked: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+impl RevocationList {
+    /// Create an empty list.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reject the token `jti` until `exp` (seconds since the Unix epoch).
+    pub fn revoke(&self, jti: impl Into<String>, exp: u64) {
+        let mut revoked = self.revoked.lock().unwrap();
+        let now = unix_now();
+        revoked.retain(|_, until| *until > now);
+        revoked.insert(jti.into(), exp);
+    }
+
+    /// Whether `jti` is currently revoked.
+    pub fn is_revoked(&self, jti: &str) -> bool {
+        self.revoked
+            .lock()
+            .unwrap()
+            .get(jti)
+            .is_some_and(|until| *until > unix_now())
+    }
+
+    /// Number of revocations still in effect.
+    pub fn len(&self) -> usize {
+        let now = unix_now();
+        self.revoked.lock().unwrap().values().filter(|until| **until > now).count()
+    }
+
+    /// Whether no revocation is in effect.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Hit and miss counters of a [`CachingValidator`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Validations answered from the cache
+    pub hits: u64,
+    /// Validations passed to the inner validator
+    pub misses: u64,
+    /// Tokens rejected because their `jti` was revoked
+    pub revoked: u64,
+    /// Tokens currently cached
+    pub entries: usize,
+}
+
+#[derive(Debug)]
+struct CachedToken {
+    claims: Claims,
+    verified_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    tokens: HashMap<String, CachedToken>,
+    by_jti: HashMap<String, Vec<String>>,
+}
+
+impl CacheState {
+    fn remove(&mut self, raw: &str) {
+        if let Some(cached) = self.tokens.remove(raw) {
+            if let Some(raws) = self.by_jti.get_mut(&cached.claims.jti) {
+                raws.retain(|r| r != raw);
+                if raws.is_empty() {
+                    self.by_jti.remove(&cached.claims.jti);
+                }
+            }
+        }
+    }
+
+    fn remove_jti(&mut self, jti: &str) {
+        for raw in self.by_jti.remove(jti).unwrap_or_default() {
+            self.tokens.remove(&raw);
+        }
+    }
+
+    /// Drop expired and stale tokens, then the oldest ones beyond `capacity`.
+    fn evict(&mut self, now: u64, ttl: Duration, capacity: usize) {
+        let stale: Vec<String> = self
+            .tokens
+            .iter()
+            .filter(|(_, cached)| cached.claims.exp <= now || cached.verified_at.elapsed() >= ttl)
+            .map(|(raw, _)| raw.clone())
+            .collect();
+        for raw in stale {
+            self.remove(&raw);
+        }
+
+        if self.tokens.len() >= capacity {
+            let mut by_age: Vec<(Instant, String)> = self
+                .tokens
+                .iter()
+                .map(|(raw, cached)| (cached.verified_at, raw.clone()))
+                .collect();
+            by_age.sort();
+            let excess = self.tokens.len() + 1 - capacity;
+            for (_, raw) in by_age.into_iter().take(excess) {
+                self.remove(&raw);
+            }
+        }
+    }
+}
+
+/// [`TokenValidator`] that caches the claims of verified tokens.
+///
+/// A cached token is trusted until the earliest of its `exp`, the cache TTL
+/// and the revocation of its `jti`. Failed validations are never cached.
+pub struct CachingValidator<V> {
+    inner: V,
+    revocations: RevocationList,
+    capacity: usize,
+    ttl: Duration,
+    state: Mutex<CacheState>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    revoked: AtomicU64,
+}
+
+impl<V: TokenValidator> CachingValidator<V> {
+    /// Cache the results of `inner` with the default capacity and TTL.
+    pub fn new(inner: V) -> Self {
+        Self {
+            inner,
+            revocations: RevocationList::new(),
+            capacity: DEFAULT_CACHE_CAPACITY,
+            ttl: DEFAULT_CACHE_TTL,
+            state: Mutex::new(CacheState::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            revoked: AtomicU64::new(0),
+        }
+    }
+
+    /// Keep at most `capacity` tokens, evicting the oldest first.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Re-validate cached tokens once they were verified `ttl` ago.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Consult `revocations`, typically shared with whatever revokes tokens.
+    pub fn with_revocations(mut self, revocations: RevocationList) -> Self {
+        self.revocations = revocations;
+        self
+    }
+
+    /// The revocation list consulted on every validation.
+    pub fn revocations(&self) -> &RevocationList {
+        &self.revocations
+    }
+
+    /// Revoke `jti` until `exp` and drop its cached tokens.
+    pub fn revoke(&self, jti: &str, exp: u64) {
+        self.revocations.revoke(jti, exp);
+        self.state.lock().unwrap().remove_jti(jti);
+    }
+
+    /// Forget every cached token.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.tokens.clear();
+        state.by_jti.clear();
+    }
+
+    /// Hit and miss counters.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            revoked: self.revoked.load(Ordering::Relaxed),
+            entries: self.state.lock().unwrap().tokens.len(),
+        }
+    }
+
+    /// The wrapped validator.
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    fn reject_revoked(&self, claims: &Claims) -> Result<()> {
+        if self.revocations.is_revoked(&claims.jti) {
+            self.revoked.fetch_add(1, Ordering::Relaxed);
+            self.state.lock().unwrap().remove_jti(&claims.jti);
+            return Err(Error::new("Token has been revoked"));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<V: TokenValidator> TokenValidator for CachingValidator<V> {
+    async fn validate(&self, raw: &str) -> Result<Claims> {
+        let now = unix_now();
+        let cached = {
+            let mut state = self.state.lock().unwrap();
+            match state.tokens.get(raw) {
+                Some(hit) if hit.claims.exp > now && hit.verified_at.elapsed() < self.ttl => {
+                    Some(hit.claims.clone())
+                }
+                Some(_) => {
+                    state.remove(raw);
+                    None
+                }
+                None => None,
+            }
+        };
+        if let Some(claims) = cached {
+            self.reject_revoked(&claims)?;
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(claims);
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let claims = self.inner.validate(raw).await?;
+        self.reject_revoked(&claims)?;
+
+        let mut state = self.state.lock().unwrap();
+        state.evict(now, self.ttl, self.capacity);
+        state
+            .by_jti
+            .entry(claims.jti.clone())
+            .or_default()
+            .push(raw.to_string());
+        state.tokens.insert(
+            raw.to_string(),
+            CachedToken {
+                claims: claims.clone(),
+                verified_at: Instant::now(),
+            },
+        );
+        Ok(claims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hs256::{JwtHs256Token, JwtHs256Validator};
+    use crate::CapabilityToken;
+
+    const SECRET: &str = "cache-test-secret";
+
+    fn token(subject: &str) -> String {
+        JwtHs256Token::new(subject, "vault", vec!["read".into()], SECRET, 60)
+            .unwrap()
+            .as_str()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_cache_hits_and_rejects_forgeries() {
+        let validator = CachingValidator::new(JwtHs256Validator::new(SECRET));
+        let raw = token("agent-1");
+
+        let first = validator.validate(&raw).await.unwrap();
+        assert_eq!(validator.validate(&raw).await.unwrap(), first);
+        assert_eq!(validator.stats().hits, 1);
+        assert_eq!(validator.stats().misses, 1);
+
+        // Same claims, same jti, but a tampered signature
+        let forged = format!("{}x", raw);
+        assert!(validator.validate(&forged).await.is_err());
+        assert_eq!(validator.stats().entries, 1);
+    }
+
+    #[tokio::test]
+    async fn test_revocation_and_expiry() {
+        let revocations = RevocationList::new();
+        let validator = CachingValidator::new(JwtHs256Validator::new(SECRET))
+            .with_revocations(revocations.clone());
+        let raw = token("agent-2");
+        let claims = validator.validate(&raw).await.unwrap();
+
+        // Revoked through the shared list, not the validator
+        revocations.revoke(claims.jti.clone(), claims.exp);
+        assert!(validator.validate(&raw).await.is_err());
+        assert_eq!(validator.stats().revoked, 1);
+        assert_eq!(validator.stats().entries, 0);
+
+        // A zero TTL always goes back to the inner validator
+        let uncached = CachingValidator::new(JwtHs256Validator::new(SECRET)).with_ttl(Duration::ZERO);
+        let other = token("agent-3");
+        uncached.validate(&other).await.unwrap();
+        uncached.validate(&other).await.unwrap();
+        assert_eq!(uncached.stats().hits, 0);
+        assert_eq!(uncached.stats().misses, 2);
+    }
+
+    #[tokio::test]
+    async fn test_capacity_evicts_oldest() {
+        let validator = CachingValidator::new(JwtHs256Validator::new(SECRET)).with_capacity(2);
+        let tokens: Vec<String> = (0..3).map(|i| token(&format!("agent-{}", i))).collect();
+        for raw in &tokens {
+            validator.validate(raw).await.unwrap();
+        }
+        assert_eq!(validator.stats().entries, 2);
+
+        validator.validate(&tokens[2]).await.unwrap();
+        assert_eq!(validator.stats().hits, 1);
+        validator.validate(&tokens[0]).await.unwrap();
+        assert_eq!(validator.stats().misses, 4);
+    }
+}
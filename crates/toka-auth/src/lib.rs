@@ -314,11 +314,21 @@ pub mod hs256 {
     }
 }
 
+//─────────────────────────────
+//  Validation cache
+//─────────────────────────────
+
+/// Caching wrapper around any [`TokenValidator`], with revocation.
+pub mod cache;
+
+pub use cache::{CacheStats, CachingValidator, RevocationList};
+
 /// Top-level convenience export re-exporting the HS256 implementation.
 pub use hs256::prelude::*;
 
 /// Single‐line glob import for downstream crates.
 pub mod prelude {
     pub use super::{Claims, CapabilityToken, TokenValidator};
+    pub use super::{CachingValidator, RevocationList};
     pub use super::hs256::prelude::*;
 }
@@ -0,0 +1,49 @@
+//! Broadcasting the largest allowed observation to many subscribers.
+//!
+//! Each subscriber receives its own clone of the event. `vec_clone` measures
+//! what that costs when the data is copied per subscriber, `publish` the bus
+//! with shared `Bytes`.
+//!
+//! Run with `cargo bench -p toka-bus-core --bench observation_broadcast`.
+
+use chrono::Utc;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use toka_bus_core::{EventBus, InMemoryBus, KernelEvent};
+use toka_types::{EntityId, MAX_OBSERVATION_DATA_LEN};
+
+const SUBSCRIBERS: [usize; 3] = [1, 8, 32];
+
+fn observation_broadcast(c: &mut Criterion) {
+    let data = vec![0xA5u8; MAX_OBSERVATION_DATA_LEN];
+    let event = KernelEvent::ObservationEmitted {
+        agent: EntityId(1),
+        data: data.clone().into(),
+        timestamp: Utc::now(),
+    };
+
+    let mut group = c.benchmark_group("observation_broadcast");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+
+    for subscribers in SUBSCRIBERS {
+        group.bench_function(BenchmarkId::new("vec_clone", subscribers), |b| {
+            b.iter(|| (0..subscribers).map(|_| data.clone()).collect::<Vec<_>>())
+        });
+
+        let bus = InMemoryBus::new(16);
+        let mut receivers: Vec<_> = (0..subscribers).map(|_| bus.subscribe()).collect();
+        group.bench_function(BenchmarkId::new("publish", subscribers), |b| {
+            b.iter(|| {
+                bus.publish(&event).unwrap();
+                receivers
+                    .iter_mut()
+                    .map(|rx| rx.try_recv().unwrap())
+                    .collect::<Vec<_>>()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, observation_broadcast);
+criterion_main!(benches);
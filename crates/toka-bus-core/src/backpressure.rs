@@ -0,0 +1,482 @@
+//! Bounded event bus with per-subscriber overflow policies.
+//!
+//! [`InMemoryBus`](crate::InMemoryBus) shares one ring buffer between all
+//! subscribers and silently overwrites events a slow subscriber has not read
+//! yet. [`BackpressureBus`] instead gives every bounded subscriber its own
+//! queue and lets the subscriber choose what happens when that queue is full
+//! through an [`OverflowPolicy`]. Per-subscriber [`SubscriberMetrics`] show
+//! how far each one lags and how many events it lost. Lost events can be
+//! kept in a [`DeadLetterQueue`] attached with
+//! [`with_dead_letters`](BackpressureBus::with_dead_letters).
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, Notify};
+
+use crate::{BusError, DeadLetterQueue, DeadLetterReason, EventBus, EventFilter, KernelEvent};
+
+/// What a bounded subscription does when its queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OverflowPolicy {
+    /// Hold the publisher back until the subscriber catches up.
+    ///
+    /// [`EventBus::publish`] cannot wait, so it fails with
+    /// [`BusError::Backpressure`] without delivering the event to anyone;
+    /// [`BackpressureBus::publish_async`] waits for room instead.
+    Block,
+    /// Discard the oldest queued event to make room
+    DropOldest,
+    /// Discard the event being published
+    DropNewest,
+    /// Disconnect the subscriber; it receives what is queued, then `None`
+    Disconnect,
+}
+
+/// Delivery statistics of one bounded subscriber.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubscriberMetrics {
+    /// Identifier of the subscription on its bus
+    pub id: u64,
+    /// Overflow policy of the subscription
+    pub policy: OverflowPolicy,
+    /// Queue capacity
+    pub capacity: usize,
+    /// Events queued but not yet received (the current lag)
+    pub queued: usize,
+    /// Highest lag observed
+    pub max_queued: usize,
+    /// Events received by the subscriber
+    pub delivered: u64,
+    /// Events discarded by the overflow policy
+    pub dropped: u64,
+    /// Whether the subscriber was disconnected for overflowing
+    pub disconnected: bool,
+}
+
+/// Result of offering an event to a subscriber queue.
+enum Offer {
+    Queued,
+    /// Queued after discarding the oldest queued event
+    Evicted(KernelEvent),
+    Dropped,
+    Disconnected,
+}
+
+struct QueueState {
+    events: VecDeque<KernelEvent>,
+    metrics: SubscriberMetrics,
+    /// The subscriber was dropped
+    closed: bool,
+    /// The bus was dropped
+    bus_closed: bool,
+}
+
+/// Queue of one bounded subscriber, shared with the bus.
+struct SubscriberQueue {
+    filter: Option<EventFilter>,
+    state: Mutex<QueueState>,
+    readable: Notify,
+    writable: Notify,
+}
+
+impl SubscriberQueue {
+    fn lock(&self) -> std::sync::MutexGuard<'_, QueueState> {
+        self.state.lock().expect("subscriber queue poisoned")
+    }
+
+    fn accepts(&self, event: &KernelEvent) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter.matches(event))
+    }
+
+    fn is_live(&self) -> bool {
+        let state = self.lock();
+        !state.closed && !state.metrics.disconnected
+    }
+
+    /// Whether `offer` would have to wait under [`OverflowPolicy::Block`].
+    fn would_block(&self) -> bool {
+        let state = self.lock();
+        state.metrics.policy == OverflowPolicy::Block && state.events.len() >= state.metrics.capacity
+    }
+
+    fn offer(&self, event: &KernelEvent) -> Offer {
+        let mut state = self.lock();
+        let offer = if state.events.len() < state.metrics.capacity {
+            state.events.push_back(event.clone());
+            Offer::Queued
+        } else {
+            match state.metrics.policy {
+                // Checked by the bus before offering
+                OverflowPolicy::Block => return Offer::Dropped,
+                OverflowPolicy::DropOldest => {
+                    let oldest = state.events.pop_front().expect("queue is full");
+                    state.events.push_back(event.clone());
+                    state.metrics.dropped += 1;
+                    Offer::Evicted(oldest)
+                }
+                OverflowPolicy::DropNewest => {
+                    state.metrics.dropped += 1;
+                    Offer::Dropped
+                }
+                OverflowPolicy::Disconnect => {
+                    state.metrics.disconnected = true;
+                    Offer::Disconnected
+                }
+            }
+        };
+        state.metrics.queued = state.events.len();
+        state.metrics.max_queued = state.metrics.max_queued.max(state.events.len());
+        drop(state);
+        self.readable.notify_one();
+        offer
+    }
+}
+
+struct BusShared {
+    subscribers: Mutex<Vec<Arc<SubscriberQueue>>>,
+    next_id: AtomicU64,
+}
+
+impl Drop for BusShared {
+    fn drop(&mut self) {
+        let subscribers = self.subscribers.get_mut().expect("subscribers poisoned");
+        for queue in subscribers.drain(..) {
+            queue.lock().bus_closed = true;
+            queue.readable.notify_one();
+        }
+    }
+}
+
+/// Event bus with bounded, per-subscriber queues.
+///
+/// Bounded subscribers are created with
+/// [`subscribe_bounded`](Self::subscribe_bounded). Plain
+/// [`EventBus::subscribe`] receivers are still served from a broadcast ring
+/// buffer of `capacity` events and may lag like on an `InMemoryBus`.
+#[derive(Clone)]
+pub struct BackpressureBus {
+    shared: Arc<BusShared>,
+    tx: Arc<broadcast::Sender<KernelEvent>>,
+    dead_letters: Option<DeadLetterQueue>,
+}
+
+impl Default for BackpressureBus {
+    fn default() -> Self {
+        Self::new(1024)
+    }
+}
+
+impl std::fmt::Debug for BackpressureBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BackpressureBus")
+            .field("subscribers", &self.metrics())
+            .finish()
+    }
+}
+
+impl BackpressureBus {
+    /// Create a bus whose plain subscribers share a ring buffer of
+    /// `capacity` events.
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _rx) = broadcast::channel(capacity);
+        Self {
+            shared: Arc::new(BusShared {
+                subscribers: Mutex::new(Vec::new()),
+                next_id: AtomicU64::new(1),
+            }),
+            tx: Arc::new(tx),
+            dead_letters: None,
+        }
+    }
+
+    /// Capture events rejected by validation or discarded by an overflow
+    /// policy in `dead_letters`.
+    pub fn with_dead_letters(mut self, dead_letters: DeadLetterQueue) -> Self {
+        self.dead_letters = Some(dead_letters);
+        self
+    }
+
+    /// Subscribe with a queue of `capacity` events handled by `policy`.
+    pub fn subscribe_bounded(&self, capacity: usize, policy: OverflowPolicy) -> BoundedSubscriber {
+        self.add_subscriber(capacity, policy, None)
+    }
+
+    /// Subscribe to the events passing `filter`, with a queue of `capacity`
+    /// events handled by `policy`.
+    pub fn subscribe_bounded_filtered(
+        &self,
+        capacity: usize,
+        policy: OverflowPolicy,
+        filter: EventFilter,
+    ) -> BoundedSubscriber {
+        self.add_subscriber(capacity, policy, Some(filter))
+    }
+
+    fn add_subscriber(&self, capacity: usize, policy: OverflowPolicy, filter: Option<EventFilter>) -> BoundedSubscriber {
+        let queue = Arc::new(SubscriberQueue {
+            filter,
+            state: Mutex::new(QueueState {
+                events: VecDeque::with_capacity(capacity),
+                metrics: SubscriberMetrics {
+                    id: self.shared.next_id.fetch_add(1, Ordering::Relaxed),
+                    policy,
+                    capacity: capacity.max(1),
+                    queued: 0,
+                    max_queued: 0,
+                    delivered: 0,
+                    dropped: 0,
+                    disconnected: false,
+                },
+                closed: false,
+                bus_closed: false,
+            }),
+            readable: Notify::new(),
+            writable: Notify::new(),
+        });
+        self.shared
+            .subscribers
+            .lock()
+            .expect("subscribers poisoned")
+            .push(queue.clone());
+        BoundedSubscriber { queue }
+    }
+
+    /// Metrics of every connected bounded subscriber.
+    pub fn metrics(&self) -> Vec<SubscriberMetrics> {
+        self.shared
+            .subscribers
+            .lock()
+            .expect("subscribers poisoned")
+            .iter()
+            .map(|queue| queue.lock().metrics.clone())
+            .collect()
+    }
+
+    /// Publish `event`, waiting for room in the queues of blocking
+    /// subscribers instead of failing.
+    pub async fn publish_async(&self, event: &KernelEvent) -> Result<()> {
+        self.validate(event)?;
+        loop {
+            let Some(full) = self.try_deliver(event) else {
+                return Ok(());
+            };
+            let writable = full.writable.notified();
+            if !full.would_block() || !full.is_live() {
+                continue;
+            }
+            writable.await;
+        }
+    }
+
+    /// Deliver `event` to every subscriber, unless a blocking subscriber
+    /// is full, in which case nothing is delivered and that subscriber is
+    /// returned.
+    fn try_deliver(&self, event: &KernelEvent) -> Option<Arc<SubscriberQueue>> {
+        // Holding the list lock serializes publishers, so the queues checked
+        // below cannot fill up before the event is offered to them
+        let mut subscribers = self.shared.subscribers.lock().expect("subscribers poisoned");
+        subscribers.retain(|queue| queue.is_live());
+
+        let matching: Vec<&Arc<SubscriberQueue>> = subscribers.iter().filter(|queue| queue.accepts(event)).collect();
+        if let Some(full) = matching.iter().find(|queue| queue.would_block()) {
+            return Some(Arc::clone(full));
+        }
+
+        let _ = self.tx.send(event.clone());
+        let mut disconnected = false;
+        for queue in matching {
+            let lost = match queue.offer(event) {
+                Offer::Queued => None,
+                Offer::Evicted(oldest) => Some(oldest),
+                Offer::Dropped => Some(event.clone()),
+                Offer::Disconnected => {
+                    disconnected = true;
+                    Some(event.clone())
+                }
+            };
+            if let (Some(lost), Some(dead_letters)) = (lost, &self.dead_letters) {
+                let metrics = queue.lock().metrics.clone();
+                dead_letters.push(
+                    lost,
+                    DeadLetterReason::Overflow {
+                        subscriber: metrics.id,
+                        policy: metrics.policy,
+                    },
+                );
+            }
+        }
+        if disconnected {
+            subscribers.retain(|queue| queue.is_live());
+        }
+        None
+    }
+
+    fn validate(&self, event: &KernelEvent) -> Result<()> {
+        event.validate().map_err(|e| {
+            if let Some(dead_letters) = &self.dead_letters {
+                dead_letters.push(event.clone(), DeadLetterReason::Invalid(e.clone()));
+            }
+            BusError::PublishFailed(e).into()
+        })
+    }
+}
+
+impl EventBus for BackpressureBus {
+    fn publish(&self, event: &KernelEvent) -> Result<()> {
+        self.validate(event)?;
+        match self.try_deliver(event) {
+            None => Ok(()),
+            Some(full) => {
+                let id = full.lock().metrics.id;
+                Err(BusError::Backpressure(format!("subscriber {} is full", id)).into())
+            }
+        }
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<KernelEvent> {
+        self.tx.subscribe()
+    }
+}
+
+/// Receiving end of a bounded subscription.
+///
+/// Dropping it unsubscribes and releases any publisher blocked on it.
+pub struct BoundedSubscriber {
+    queue: Arc<SubscriberQueue>,
+}
+
+impl BoundedSubscriber {
+    /// Receive the next event.
+    ///
+    /// Returns `None` once the bus is gone or the subscriber was
+    /// disconnected by its overflow policy, after the queued events have
+    /// been received.
+    pub async fn recv(&mut self) -> Option<KernelEvent> {
+        loop {
+            let queue = Arc::clone(&self.queue);
+            let readable = queue.readable.notified();
+            match self.try_recv() {
+                Ok(event) => return Some(event),
+                Err(true) => return None,
+                Err(false) => readable.await,
+            }
+        }
+    }
+
+    /// Receive the next event if one is queued.
+    ///
+    /// `Err(true)` means no event will ever arrive again, `Err(false)` that
+    /// none is queued right now.
+    pub fn try_recv(&mut self) -> std::result::Result<KernelEvent, bool> {
+        let mut state = self.queue.lock();
+        match state.events.pop_front() {
+            Some(event) => {
+                state.metrics.delivered += 1;
+                state.metrics.queued = state.events.len();
+                drop(state);
+                self.queue.writable.notify_one();
+                Ok(event)
+            }
+            None => Err(state.bus_closed || state.metrics.disconnected),
+        }
+    }
+
+    /// Delivery statistics of this subscription.
+    pub fn metrics(&self) -> SubscriberMetrics {
+        self.queue.lock().metrics.clone()
+    }
+}
+
+impl Drop for BoundedSubscriber {
+    fn drop(&mut self) {
+        self.queue.lock().closed = true;
+        self.queue.writable.notify_waiters();
+    }
+}
+
+impl std::fmt::Debug for BoundedSubscriber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BoundedSubscriber")
+            .field("metrics", &self.metrics())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use toka_types::EntityId;
+
+    fn observation(n: u8) -> KernelEvent {
+        KernelEvent::ObservationEmitted {
+            agent: EntityId(1),
+            data: vec![n].into(),
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn data(event: Option<KernelEvent>) -> u8 {
+        match event {
+            Some(KernelEvent::ObservationEmitted { data, .. }) => data[0],
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_overflow_policies() {
+        let bus = BackpressureBus::new(16);
+        let mut oldest = bus.subscribe_bounded(2, OverflowPolicy::DropOldest);
+        let mut newest = bus.subscribe_bounded(2, OverflowPolicy::DropNewest);
+        let mut strict = bus.subscribe_bounded(2, OverflowPolicy::Disconnect);
+
+        for n in 0..3 {
+            bus.publish(&observation(n)).unwrap();
+        }
+
+        assert_eq!((data(oldest.recv().await), data(oldest.recv().await)), (1, 2));
+        assert_eq!((data(newest.recv().await), data(newest.recv().await)), (0, 1));
+        assert_eq!(newest.metrics().dropped, 1);
+        assert_eq!(oldest.metrics().max_queued, 2);
+
+        // The disconnected subscriber drains its queue, then ends
+        assert_eq!(data(strict.recv().await), 0);
+        assert_eq!(data(strict.recv().await), 1);
+        assert!(strict.recv().await.is_none());
+        assert!(strict.metrics().disconnected);
+        assert_eq!(bus.metrics().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_blocking_subscriber_holds_back_publishers() {
+        let bus = BackpressureBus::new(16);
+        let mut slow = bus.subscribe_bounded(1, OverflowPolicy::Block);
+        let mut other = bus.subscribe_bounded(4, OverflowPolicy::DropNewest);
+
+        bus.publish(&observation(0)).unwrap();
+        let err = bus.publish(&observation(1)).unwrap_err();
+        assert!(matches!(err.downcast_ref::<BusError>(), Some(BusError::Backpressure(_))));
+        // Nobody received the rejected event
+        assert_eq!(other.metrics().queued, 1);
+
+        let publisher = {
+            let bus = bus.clone();
+            tokio::spawn(async move { bus.publish_async(&observation(1)).await })
+        };
+        assert_eq!(data(slow.recv().await), 0);
+        publisher.await.unwrap().unwrap();
+        assert_eq!(data(slow.recv().await), 1);
+        assert_eq!(data(other.recv().await), 0);
+        assert_eq!(data(other.recv().await), 1);
+
+        // Dropping the slow subscriber unblocks publishing
+        bus.publish(&observation(2)).unwrap();
+        drop(slow);
+        bus.publish(&observation(3)).unwrap();
+        assert_eq!(other.metrics().queued, 2);
+    }
+}
@@ -0,0 +1,321 @@
+//! Coalescing of high-frequency resource events.
+//!
+//! Agents report [`MemoryAllocated`](KernelEvent::MemoryAllocated),
+//! [`CPUUtilization`](KernelEvent::CPUUtilization) and
+//! [`IOOperation`](KernelEvent::IOOperation) events far more often than
+//! dashboards and other subscribers need them. [`CoalescingBus`] wraps a bus
+//! and, instead of publishing each of these events, rolls them up per agent
+//! and publishes one [`ResourceSummary`](KernelEvent::ResourceSummary) per
+//! agent and window. All other events pass through unchanged.
+//!
+//! The individual events are not lost: with
+//! [`with_detail`](CoalescingBus::with_detail) they are published to a
+//! separate detail bus, typically one a `BusTap` persists into storage.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use toka_types::EntityId;
+
+use crate::{BusError, EventBus, EventFilter, FilteredReceiver, KernelEvent};
+
+/// Default length of a coalescing window.
+pub const DEFAULT_COALESCING_WINDOW: Duration = Duration::from_secs(1);
+
+/// Counters of a [`CoalescingBus`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CoalescingStats {
+    /// Resource events rolled up instead of published
+    pub coalesced: u64,
+    /// Summaries published
+    pub summaries: u64,
+}
+
+/// Usage of one agent in the current window.
+#[derive(Debug, Clone, Default)]
+struct Rollup {
+    window_start: DateTime<Utc>,
+    window_end: DateTime<Utc>,
+    events: u64,
+    memory_allocated: u64,
+    peak_memory: u64,
+    cpu_samples: u64,
+    cpu_total: f64,
+    max_cpu_percent: f64,
+    io_bytes: u64,
+    io_operations: u64,
+}
+
+impl Rollup {
+    fn add(&mut self, event: &KernelEvent) {
+        match event {
+            KernelEvent::MemoryAllocated { amount, total_allocated, timestamp, .. } => {
+                self.memory_allocated = self.memory_allocated.saturating_add(*amount);
+                self.peak_memory = self.peak_memory.max(*total_allocated);
+                self.extend(*timestamp);
+            }
+            KernelEvent::CPUUtilization { cpu_percent, timestamp, .. } => {
+                self.cpu_samples += 1;
+                self.cpu_total += cpu_percent;
+                self.max_cpu_percent = self.max_cpu_percent.max(*cpu_percent);
+                self.extend(*timestamp);
+            }
+            KernelEvent::IOOperation { bytes, timestamp, .. } => {
+                self.io_bytes = self.io_bytes.saturating_add(*bytes);
+                self.io_operations += 1;
+                self.extend(*timestamp);
+            }
+            _ => return,
+        }
+        self.events += 1;
+    }
+
+    fn extend(&mut self, at: DateTime<Utc>) {
+        if self.events == 0 {
+            self.window_start = at;
+            self.window_end = at;
+        } else {
+            self.window_start = self.window_start.min(at);
+            self.window_end = self.window_end.max(at);
+        }
+    }
+
+    fn into_event(self, agent: EntityId) -> KernelEvent {
+        KernelEvent::ResourceSummary {
+            agent,
+            window_start: self.window_start,
+            window_end: self.window_end,
+            events: self.events,
+            memory_allocated: self.memory_allocated,
+            peak_memory: self.peak_memory,
+            avg_cpu_percent: if self.cpu_samples == 0 {
+                0.0
+            } else {
+                self.cpu_total / self.cpu_samples as f64
+            },
+            max_cpu_percent: self.max_cpu_percent,
+            io_bytes: self.io_bytes,
+            io_operations: self.io_operations,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+struct Shared<B> {
+    inner: B,
+    rollups: Mutex<HashMap<EntityId, Rollup>>,
+    coalesced: AtomicU64,
+    summaries: AtomicU64,
+}
+
+impl<B: EventBus> Shared<B> {
+    fn flush(&self) -> Result<usize> {
+        let rollups = std::mem::take(&mut *self.rollups.lock().expect("rollups poisoned"));
+        let count = rollups.len();
+        for (agent, rollup) in rollups {
+            self.inner.publish(&rollup.into_event(agent))?;
+            self.summaries.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(count)
+    }
+}
+
+/// Event bus wrapper rolling up resource events per agent.
+///
+/// A background task publishes the summaries of the current window every
+/// `window`; [`flush`](Self::flush) publishes them right away. Summaries
+/// still pending are flushed when the bus is dropped.
+pub struct CoalescingBus<B: EventBus + 'static> {
+    shared: Arc<Shared<B>>,
+    detail: Option<Arc<dyn EventBus>>,
+    flusher: JoinHandle<()>,
+}
+
+impl<B: EventBus + 'static> CoalescingBus<B> {
+    /// Roll up the resource events published on `inner` over `window`.
+    pub fn new(inner: B, window: Duration) -> Self {
+        let shared = Arc::new(Shared {
+            inner,
+            rollups: Mutex::new(HashMap::new()),
+            coalesced: AtomicU64::new(0),
+            summaries: AtomicU64::new(0),
+        });
+        let flusher = {
+            let shared = shared.clone();
+            tokio::spawn(async move {
+                let mut ticks = tokio::time::interval(window.max(Duration::from_millis(1)));
+                // The first tick completes immediately
+                ticks.tick().await;
+                loop {
+                    ticks.tick().await;
+                    // Summaries are valid by construction; a closed bus has
+                    // no subscribers left to miss them
+                    let _ = shared.flush();
+                }
+            })
+        };
+        Self {
+            shared,
+            detail: None,
+            flusher,
+        }
+    }
+
+    /// Also publish every individual resource event to `detail`.
+    pub fn with_detail(mut self, detail: Arc<dyn EventBus>) -> Self {
+        self.detail = Some(detail);
+        self
+    }
+
+    /// The wrapped bus.
+    pub fn inner(&self) -> &B {
+        &self.shared.inner
+    }
+
+    /// Publish the summaries of the current window now.
+    ///
+    /// Returns the number of summaries published.
+    pub fn flush(&self) -> Result<usize> {
+        self.shared.flush()
+    }
+
+    /// Counters since the bus was created.
+    pub fn stats(&self) -> CoalescingStats {
+        CoalescingStats {
+            coalesced: self.shared.coalesced.load(Ordering::Relaxed),
+            summaries: self.shared.summaries.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Whether `event` is rolled up rather than published.
+    pub fn coalesces(event: &KernelEvent) -> bool {
+        matches!(
+            event,
+            KernelEvent::MemoryAllocated { .. } | KernelEvent::CPUUtilization { .. } | KernelEvent::IOOperation { .. }
+        )
+    }
+}
+
+impl<B: EventBus + 'static> EventBus for CoalescingBus<B> {
+    fn publish(&self, event: &KernelEvent) -> Result<()> {
+        if !Self::coalesces(event) {
+            return self.shared.inner.publish(event);
+        }
+        // Reject invalid events here, as the wrapped bus never sees them
+        event.validate().map_err(BusError::PublishFailed)?;
+        if let Some(detail) = &self.detail {
+            detail.publish(event)?;
+        }
+
+        let agent = event.agent().expect("resource events concern an agent");
+        self.shared
+            .rollups
+            .lock()
+            .expect("rollups poisoned")
+            .entry(agent)
+            .or_default()
+            .add(event);
+        self.shared.coalesced.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<KernelEvent> {
+        self.shared.inner.subscribe()
+    }
+
+    fn subscribe_filtered(&self, filter: EventFilter) -> FilteredReceiver {
+        self.shared.inner.subscribe_filtered(filter)
+    }
+}
+
+impl<B: EventBus + 'static> Drop for CoalescingBus<B> {
+    fn drop(&mut self) {
+        self.flusher.abort();
+        let _ = self.shared.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IOOperationType, InMemoryBus};
+
+    #[tokio::test]
+    async fn test_resource_events_are_rolled_up_per_agent() {
+        let detail: Arc<dyn EventBus> = Arc::new(InMemoryBus::new(64));
+        let mut all_detail = detail.subscribe();
+        let bus = CoalescingBus::new(InMemoryBus::new(64), Duration::from_secs(3600)).with_detail(detail);
+        let mut rx = bus.subscribe();
+
+        for (agent, cpu) in [(1, 20.0), (1, 60.0), (2, 5.0)] {
+            bus.publish(&KernelEvent::CPUUtilization {
+                agent: EntityId(agent),
+                cpu_percent: cpu,
+                duration_ms: 100,
+                timestamp: Utc::now(),
+            })
+            .unwrap();
+        }
+        bus.publish(&KernelEvent::MemoryAllocated {
+            agent: EntityId(1),
+            amount: 512,
+            total_allocated: 4096,
+            timestamp: Utc::now(),
+        })
+        .unwrap();
+        bus.publish(&KernelEvent::IOOperation {
+            agent: EntityId(1),
+            operation_type: IOOperationType::FileRead,
+            bytes: 1000,
+            duration_ms: 3,
+            timestamp: Utc::now(),
+        })
+        .unwrap();
+        let invalid = KernelEvent::CPUUtilization {
+            agent: EntityId(1),
+            cpu_percent: 250.0,
+            duration_ms: 100,
+            timestamp: Utc::now(),
+        };
+        assert!(bus.publish(&invalid).is_err());
+
+        // Nothing reached subscribers of the wrapped bus yet
+        assert!(rx.try_recv().is_err());
+        assert_eq!(bus.stats(), CoalescingStats { coalesced: 5, summaries: 0 });
+        for _ in 0..5 {
+            assert!(CoalescingBus::<InMemoryBus>::coalesces(&all_detail.recv().await.unwrap()));
+        }
+
+        assert_eq!(bus.flush().unwrap(), 2);
+        let mut summaries = [rx.recv().await.unwrap(), rx.recv().await.unwrap()];
+        summaries.sort_by_key(|event| event.agent().map(|agent| agent.0));
+        match &summaries[0] {
+            KernelEvent::ResourceSummary {
+                agent,
+                events,
+                memory_allocated,
+                peak_memory,
+                avg_cpu_percent,
+                max_cpu_percent,
+                io_bytes,
+                io_operations,
+                ..
+            } => {
+                assert_eq!(*agent, EntityId(1));
+                assert_eq!((*events, *memory_allocated, *peak_memory), (4, 512, 4096));
+                assert_eq!((*avg_cpu_percent, *max_cpu_percent), (40.0, 60.0));
+                assert_eq!((*io_bytes, *io_operations), (1000, 1));
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(summaries[1].validate().is_ok());
+        assert_eq!(bus.flush().unwrap(), 0);
+        assert_eq!(bus.stats().summaries, 2);
+    }
+}
@@ -0,0 +1,285 @@
+//! Dead-letter queue for events the bus could not deliver.
+//!
+//! Events failing [`KernelEvent::validate`] are rejected by `publish`, and
+//! events a [`BackpressureBus`](crate::BackpressureBus) discards for a
+//! lagging subscriber are gone for good. Attaching a [`DeadLetterQueue`] to a
+//! bus (see [`InMemoryBus::with_dead_letters`](crate::InMemoryBus::with_dead_letters)
+//! and [`BackpressureBus::with_dead_letters`](crate::BackpressureBus::with_dead_letters))
+//! captures those events together with a [`DeadLetterReason`], so operators
+//! can inspect them, replay them once the cause is fixed, or purge them.
+//!
+//! The queue is bounded; once full, the oldest letter is evicted to make room
+//! and counted in [`DeadLetterQueue::evicted`].
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{EventBus, KernelEvent, OverflowPolicy};
+
+/// Why an event ended up in the dead-letter queue.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeadLetterReason {
+    /// The event failed validation on publish
+    Invalid(String),
+    /// A bounded subscriber's overflow policy discarded the event
+    Overflow {
+        /// Identifier of the subscription, see
+        /// [`SubscriberMetrics::id`](crate::SubscriberMetrics::id)
+        subscriber: u64,
+        /// Policy that discarded the event
+        policy: OverflowPolicy,
+    },
+}
+
+/// An undeliverable event with its reason.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    /// Identifier of the letter within its queue
+    pub id: u64,
+    /// The undeliverable event
+    pub event: KernelEvent,
+    /// Why the event could not be delivered
+    pub reason: DeadLetterReason,
+    /// When the event was dead-lettered
+    pub dead_at: DateTime<Utc>,
+    /// Failed replay attempts
+    pub attempts: u32,
+    /// Error of the last failed replay
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug)]
+struct DeadLetters {
+    letters: VecDeque<DeadLetter>,
+    capacity: usize,
+    next_id: u64,
+    evicted: u64,
+}
+
+/// Bounded store of [`DeadLetter`]s.
+///
+/// Cloning is cheap; clones share the same letters.
+#[derive(Debug, Clone)]
+pub struct DeadLetterQueue {
+    inner: Arc<Mutex<DeadLetters>>,
+}
+
+impl Default for DeadLetterQueue {
+    fn default() -> Self {
+        Self::new(1024)
+    }
+}
+
+impl DeadLetterQueue {
+    /// Create a queue keeping at most `capacity` letters.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(DeadLetters {
+                letters: VecDeque::new(),
+                capacity: capacity.max(1),
+                next_id: 1,
+                evicted: 0,
+            })),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, DeadLetters> {
+        self.inner.lock().expect("dead letters poisoned")
+    }
+
+    /// Record `event` as undeliverable and return the letter's id.
+    pub fn push(&self, event: KernelEvent, reason: DeadLetterReason) -> u64 {
+        let mut inner = self.lock();
+        let id = inner.next_id;
+        inner.next_id += 1;
+        if inner.letters.len() >= inner.capacity {
+            inner.letters.pop_front();
+            inner.evicted += 1;
+        }
+        inner.letters.push_back(DeadLetter {
+            id,
+            event,
+            reason,
+            dead_at: Utc::now(),
+            attempts: 0,
+            last_error: None,
+        });
+        id
+    }
+
+    /// Number of letters held.
+    pub fn len(&self) -> usize {
+        self.lock().letters.len()
+    }
+
+    /// Whether no letters are held.
+    pub fn is_empty(&self) -> bool {
+        self.lock().letters.is_empty()
+    }
+
+    /// Letters evicted because the queue was full.
+    pub fn evicted(&self) -> u64 {
+        self.lock().evicted
+    }
+
+    /// All letters, oldest first.
+    pub fn list(&self) -> Vec<DeadLetter> {
+        self.lock().letters.iter().cloned().collect()
+    }
+
+    /// The letter `id`, if it is still held.
+    pub fn get(&self, id: u64) -> Option<DeadLetter> {
+        self.lock().letters.iter().find(|letter| letter.id == id).cloned()
+    }
+
+    /// Remove the letter `id` without replaying it.
+    pub fn purge(&self, id: u64) -> Option<DeadLetter> {
+        let mut inner = self.lock();
+        let index = inner.letters.iter().position(|letter| letter.id == id)?;
+        inner.letters.remove(index)
+    }
+
+    /// Remove every letter matching `predicate`, returning how many were
+    /// removed.
+    pub fn purge_where(&self, predicate: impl Fn(&DeadLetter) -> bool) -> usize {
+        let mut inner = self.lock();
+        let before = inner.letters.len();
+        inner.letters.retain(|letter| !predicate(letter));
+        before - inner.letters.len()
+    }
+
+    /// Remove every letter, returning how many were removed.
+    pub fn purge_all(&self) -> usize {
+        self.purge_where(|_| true)
+    }
+
+    /// Publish the letter `id` on `bus` again and remove it on success.
+    ///
+    /// The event goes to every subscriber of `bus`, not only the one that
+    /// missed it. On failure the letter stays queued with its attempt count
+    /// incremented and the error returned. If `bus` dead-letters the event
+    /// again into this queue, the new letter takes over the attempt count
+    /// instead. Returns `Ok(false)` if no letter `id` is held.
+    pub fn replay<B: EventBus + ?Sized>(&self, id: u64, bus: &B) -> Result<bool> {
+        let (letter, next_id) = {
+            let mut inner = self.lock();
+            let Some(index) = inner.letters.iter().position(|letter| letter.id == id) else {
+                return Ok(false);
+            };
+            let letter = inner.letters.remove(index).expect("index found above");
+            (letter, inner.next_id)
+        };
+
+        let Err(error) = bus.publish(&letter.event) else {
+            return Ok(true);
+        };
+
+        let mut inner = self.lock();
+        let requeued = inner
+            .letters
+            .iter_mut()
+            .find(|requeued| requeued.id >= next_id && requeued.event == letter.event);
+        match requeued {
+            Some(requeued) => {
+                requeued.attempts = letter.attempts + 1;
+                requeued.last_error = Some(error.to_string());
+            }
+            None => {
+                // Back at its place in age order
+                let index = inner.letters.partition_point(|other| other.id < letter.id);
+                inner.letters.insert(
+                    index,
+                    DeadLetter {
+                        attempts: letter.attempts + 1,
+                        last_error: Some(error.to_string()),
+                        ..letter
+                    },
+                );
+            }
+        }
+        Err(error)
+    }
+
+    /// Replay every letter in age order, returning how many were delivered.
+    ///
+    /// Letters failing again stay queued; replay continues with the next
+    /// one.
+    pub fn replay_all<B: EventBus + ?Sized>(&self, bus: &B) -> usize {
+        let ids: Vec<u64> = self.lock().letters.iter().map(|letter| letter.id).collect();
+        ids.into_iter()
+            .filter(|id| matches!(self.replay(*id, bus), Ok(true)))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BackpressureBus, InMemoryBus};
+    use toka_types::EntityId;
+
+    fn observation(n: u8, timestamp: DateTime<Utc>) -> KernelEvent {
+        KernelEvent::ObservationEmitted {
+            agent: EntityId(1),
+            data: vec![n].into(),
+            timestamp,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejected_and_dropped_events_are_captured() {
+        let dlq = DeadLetterQueue::new(8);
+        let bus = InMemoryBus::new(16).with_dead_letters(dlq.clone());
+        let far_future = Utc::now() + chrono::Duration::days(2);
+        assert!(bus.publish(&observation(0, far_future)).is_err());
+
+        let bounded = BackpressureBus::new(16).with_dead_letters(dlq.clone());
+        let mut slow = bounded.subscribe_bounded(1, OverflowPolicy::DropNewest);
+        bounded.publish(&observation(1, Utc::now())).unwrap();
+        bounded.publish(&observation(2, Utc::now())).unwrap();
+
+        let letters = dlq.list();
+        assert_eq!(letters.len(), 2);
+        assert!(matches!(letters[0].reason, DeadLetterReason::Invalid(_)));
+        assert_eq!(
+            letters[1].reason,
+            DeadLetterReason::Overflow {
+                subscriber: slow.metrics().id,
+                policy: OverflowPolicy::DropNewest
+            }
+        );
+
+        // The invalid event is rejected again and requeued by the bus
+        assert!(dlq.replay(letters[0].id, &bus).is_err());
+        assert_eq!(dlq.len(), 2);
+        let requeued = dlq.list().pop().unwrap();
+        assert_eq!(requeued.event, letters[0].event);
+        assert_eq!(requeued.attempts, 1);
+        assert!(requeued.last_error.is_some());
+
+        // Once the subscriber caught up the dropped event can be replayed
+        slow.recv().await.unwrap();
+        assert!(dlq.replay(letters[1].id, &bounded).unwrap());
+        assert_eq!(slow.recv().await, Some(letters[1].event.clone()));
+        assert!(!dlq.replay(letters[1].id, &bounded).unwrap());
+
+        assert_eq!(dlq.purge_all(), 1);
+        assert!(dlq.is_empty());
+    }
+
+    #[test]
+    fn test_full_queue_evicts_oldest() {
+        let dlq = DeadLetterQueue::new(2);
+        for n in 0..3 {
+            dlq.push(observation(n, Utc::now()), DeadLetterReason::Invalid("bad".to_string()));
+        }
+        assert_eq!(dlq.evicted(), 1);
+        assert_eq!(dlq.list().iter().map(|letter| letter.id).collect::<Vec<_>>(), vec![2, 3]);
+        assert!(dlq.purge(2).is_some());
+        assert_eq!(dlq.purge_where(|letter| letter.id == 3), 1);
+    }
+}
@@ -0,0 +1,440 @@
+//! Versioned event envelopes and the registry of custom event types.
+//!
+//! An [`EventEnvelope`] carries an event as its name, the schema version of
+//! its payload and the JSON payload itself. It serves two purposes:
+//!
+//! - **Wire versioning.** [`EventEnvelope::wrap`] stamps a [`KernelEvent`]
+//!   with [`KERNEL_EVENT_SCHEMA_VERSION`], and [`EventEnvelope::unwrap_kernel`]
+//!   refuses envelopes written by a newer schema with a clear error instead
+//!   of misreading them. Transports that cross process or release
+//!   boundaries should send envelopes rather than bare events.
+//! - **Custom events.** Crates define their own event types by implementing
+//!   [`CustomEvent`] and registering them in an [`EventSchemaRegistry`]. The
+//!   registry turns them into [`KernelEvent::Custom`] events that travel
+//!   through any [`EventBus`](crate::EventBus) and decodes them back on the
+//!   subscriber side, so the kernel enum does not need a variant per
+//!   integration.
+//!
+//! Payloads written with an older schema version than the registered one are
+//! decoded as is; fields added since then must therefore carry serde
+//! defaults.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use toka_types::EntityId;
+
+use crate::KernelEvent;
+
+/// Version of the envelope format itself.
+pub const ENVELOPE_VERSION: u32 = 1;
+
+/// Schema version of [`KernelEvent`] payloads, bumped on incompatible
+/// changes to existing variants.
+pub const KERNEL_EVENT_SCHEMA_VERSION: u32 = 3;
+
+/// Longest allowed event name.
+const MAX_EVENT_NAME_LEN: usize = 256;
+
+/// Errors raised while wrapping, registering or decoding events.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum EnvelopeError {
+    /// The envelope format is newer than this build understands
+    #[error("envelope version {found} is newer than the supported {supported}")]
+    UnsupportedEnvelope {
+        /// Version of the received envelope
+        found: u32,
+        /// Newest supported envelope version
+        supported: u32,
+    },
+    /// The payload was written with a newer schema than this build knows
+    #[error("{name} schema version {found} is newer than the supported {supported}")]
+    UnsupportedSchema {
+        /// Event name
+        name: String,
+        /// Schema version of the payload
+        found: u32,
+        /// Newest schema version known for the event
+        supported: u32,
+    },
+    /// No custom event type with this name is registered
+    #[error("event type {0} is not registered")]
+    Unregistered(String),
+    /// Another type is already registered under this name
+    #[error("event type {0} is already registered")]
+    AlreadyRegistered(String),
+    /// The payload does not match the event type
+    #[error("invalid {name} payload: {reason}")]
+    InvalidPayload {
+        /// Event name
+        name: String,
+        /// Why decoding or validation failed
+        reason: String,
+    },
+}
+
+/// An event with its name and the schema version of its payload.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventEnvelope {
+    /// Version of the envelope format, see [`ENVELOPE_VERSION`]
+    pub envelope_version: u32,
+    /// Event name: the [`KernelEvent::kind`] of kernel events, the
+    /// [`CustomEvent::NAME`] of custom ones
+    pub name: String,
+    /// Schema version the payload was written with
+    pub schema_version: u32,
+    /// The event itself
+    pub payload: serde_json::Value,
+}
+
+impl EventEnvelope {
+    /// Envelope of `payload` named `name`, written with `schema_version`.
+    pub fn new(name: impl Into<String>, schema_version: u32, payload: serde_json::Value) -> Self {
+        Self {
+            envelope_version: ENVELOPE_VERSION,
+            name: name.into(),
+            schema_version,
+            payload,
+        }
+    }
+
+    /// Wrap a kernel event for the wire.
+    pub fn wrap(event: &KernelEvent) -> Result<Self, EnvelopeError> {
+        let payload = serde_json::to_value(event).map_err(|e| EnvelopeError::InvalidPayload {
+            name: event.kind().to_string(),
+            reason: e.to_string(),
+        })?;
+        Ok(Self::new(event.kind(), KERNEL_EVENT_SCHEMA_VERSION, payload))
+    }
+
+    /// Unwrap a kernel event received from the wire.
+    pub fn unwrap_kernel(&self) -> Result<KernelEvent, EnvelopeError> {
+        self.check_versions(KERNEL_EVENT_SCHEMA_VERSION)?;
+        let event: KernelEvent = self.decode_payload()?;
+        if event.kind() != self.name {
+            return Err(self.invalid(format!("envelope names {} but carries {}", self.name, event.kind())));
+        }
+        Ok(event)
+    }
+
+    /// Check the limits every envelope on the bus must respect.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.envelope_version > ENVELOPE_VERSION {
+            return Err(format!(
+                "Envelope version {} is newer than the supported {}",
+                self.envelope_version, ENVELOPE_VERSION
+            ));
+        }
+        if self.name.is_empty() || self.name.len() > MAX_EVENT_NAME_LEN {
+            return Err("Event name must be 1-256 characters".to_string());
+        }
+        // SECURITY: Custom payloads share the observation size limit
+        let size = serde_json::to_vec(&self.payload).map_err(|e| e.to_string())?.len();
+        if size > toka_types::MAX_OBSERVATION_DATA_LEN {
+            return Err(format!(
+                "Event payload too large: {} > {}",
+                size,
+                toka_types::MAX_OBSERVATION_DATA_LEN
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_versions(&self, supported: u32) -> Result<(), EnvelopeError> {
+        if self.envelope_version > ENVELOPE_VERSION {
+            return Err(EnvelopeError::UnsupportedEnvelope {
+                found: self.envelope_version,
+                supported: ENVELOPE_VERSION,
+            });
+        }
+        if self.schema_version > supported {
+            return Err(EnvelopeError::UnsupportedSchema {
+                name: self.name.clone(),
+                found: self.schema_version,
+                supported,
+            });
+        }
+        Ok(())
+    }
+
+    fn decode_payload<T: DeserializeOwned>(&self) -> Result<T, EnvelopeError> {
+        serde_json::from_value(self.payload.clone()).map_err(|e| self.invalid(e.to_string()))
+    }
+
+    fn invalid(&self, reason: String) -> EnvelopeError {
+        EnvelopeError::InvalidPayload {
+            name: self.name.clone(),
+            reason,
+        }
+    }
+}
+
+/// An event type defined outside this crate.
+///
+/// # Examples
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use toka_bus_core::CustomEvent;
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct InvoicePaid {
+///     invoice: String,
+///     cents: u64,
+/// }
+///
+/// impl CustomEvent for InvoicePaid {
+///     const NAME: &'static str = "billing.invoice_paid";
+///     const SCHEMA_VERSION: u32 = 1;
+/// }
+/// ```
+pub trait CustomEvent: Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// Globally unique event name, conventionally prefixed with the crate
+    /// or domain, e.g. `billing.invoice_paid`
+    const NAME: &'static str;
+
+    /// Schema version of the payload; bump it whenever a field is added
+    const SCHEMA_VERSION: u32;
+
+    /// Domain checks run before the event is published and after it is
+    /// decoded.
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Registration {
+    type_name: &'static str,
+    schema_version: u32,
+    validate: fn(&serde_json::Value) -> Result<(), String>,
+}
+
+fn validate_payload<E: CustomEvent>(payload: &serde_json::Value) -> Result<(), String> {
+    E::deserialize(payload).map_err(|e| e.to_string())?.validate()
+}
+
+/// Registry of the custom event types a process publishes or consumes.
+///
+/// Registering is what ties an event name to its Rust type: encoding and
+/// decoding refuse unregistered names, and two crates claiming the same
+/// name fail at registration rather than corrupting each other's events.
+#[derive(Debug, Clone, Default)]
+pub struct EventSchemaRegistry {
+    types: HashMap<String, Registration>,
+}
+
+impl EventSchemaRegistry {
+    /// Empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `E` under [`CustomEvent::NAME`].
+    ///
+    /// Registering the same type again is a no-op.
+    pub fn register<E: CustomEvent>(&mut self) -> Result<(), EnvelopeError> {
+        let type_name = std::any::type_name::<E>();
+        if let Some(existing) = self.types.get(E::NAME) {
+            if existing.type_name == type_name {
+                return Ok(());
+            }
+            return Err(EnvelopeError::AlreadyRegistered(E::NAME.to_string()));
+        }
+        self.types.insert(
+            E::NAME.to_string(),
+            Registration {
+                type_name,
+                schema_version: E::SCHEMA_VERSION,
+                validate: validate_payload::<E>,
+            },
+        );
+        Ok(())
+    }
+
+    /// Whether an event type is registered under `name`.
+    pub fn is_registered(&self, name: &str) -> bool {
+        self.types.contains_key(name)
+    }
+
+    /// Schema version registered for `name`.
+    pub fn schema_version(&self, name: &str) -> Option<u32> {
+        self.types.get(name).map(|registration| registration.schema_version)
+    }
+
+    /// Registered event names, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.types.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Build the bus event carrying `event`, published by `source`.
+    #[cfg(feature = "clock")]
+    pub fn event<E: CustomEvent>(&self, source: Option<EntityId>, event: &E) -> Result<KernelEvent, EnvelopeError> {
+        self.event_at(source, event, Utc::now())
+    }
+
+    /// Build the bus event carrying `event` with an explicit timestamp.
+    pub fn event_at<E: CustomEvent>(
+        &self,
+        source: Option<EntityId>,
+        event: &E,
+        timestamp: DateTime<Utc>,
+    ) -> Result<KernelEvent, EnvelopeError> {
+        if !self.is_registered(E::NAME) {
+            return Err(EnvelopeError::Unregistered(E::NAME.to_string()));
+        }
+        let invalid = |reason: String| EnvelopeError::InvalidPayload {
+            name: E::NAME.to_string(),
+            reason,
+        };
+        event.validate().map_err(invalid)?;
+        let payload = serde_json::to_value(event).map_err(|e| invalid(e.to_string()))?;
+        Ok(KernelEvent::Custom {
+            source,
+            envelope: EventEnvelope::new(E::NAME, E::SCHEMA_VERSION, payload),
+            timestamp,
+        })
+    }
+
+    /// Decode `event` as `E`.
+    ///
+    /// Returns `None` for events that are not custom events named
+    /// [`CustomEvent::NAME`], so subscribers can try their types in turn.
+    pub fn decode<E: CustomEvent>(&self, event: &KernelEvent) -> Result<Option<E>, EnvelopeError> {
+        let KernelEvent::Custom { envelope, .. } = event else {
+            return Ok(None);
+        };
+        if envelope.name != E::NAME {
+            return Ok(None);
+        }
+        if !self.is_registered(E::NAME) {
+            return Err(EnvelopeError::Unregistered(E::NAME.to_string()));
+        }
+        envelope.check_versions(E::SCHEMA_VERSION)?;
+        let decoded: E = envelope.decode_payload()?;
+        decoded.validate().map_err(|reason| envelope.invalid(reason))?;
+        Ok(Some(decoded))
+    }
+
+    /// Check a received custom event against its registered type.
+    ///
+    /// Kernel events pass unchecked; custom events must be registered, not
+    /// newer than their registration and decode into the registered type.
+    pub fn validate(&self, event: &KernelEvent) -> Result<(), EnvelopeError> {
+        let KernelEvent::Custom { envelope, .. } = event else {
+            return Ok(());
+        };
+        let registration = self
+            .types
+            .get(&envelope.name)
+            .ok_or_else(|| EnvelopeError::Unregistered(envelope.name.clone()))?;
+        envelope.check_versions(registration.schema_version)?;
+        (registration.validate)(&envelope.payload).map_err(|reason| envelope.invalid(reason))
+    }
+}
+
+#[cfg(all(test, feature = "clock"))]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct InvoicePaid {
+        invoice: String,
+        cents: u64,
+    }
+
+    impl CustomEvent for InvoicePaid {
+        const NAME: &'static str = "billing.invoice_paid";
+        const SCHEMA_VERSION: u32 = 2;
+
+        fn validate(&self) -> Result<(), String> {
+            if self.cents == 0 {
+                return Err("invoice must not be free".to_string());
+            }
+            Ok(())
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Impostor {}
+
+    impl CustomEvent for Impostor {
+        const NAME: &'static str = "billing.invoice_paid";
+        const SCHEMA_VERSION: u32 = 1;
+    }
+
+    #[test]
+    fn test_custom_events_round_trip_through_the_registry() {
+        let mut registry = EventSchemaRegistry::new();
+        let paid = InvoicePaid {
+            invoice: "INV-1".to_string(),
+            cents: 1200,
+        };
+        assert_eq!(
+            registry.event(None, &paid),
+            Err(EnvelopeError::Unregistered(InvoicePaid::NAME.to_string()))
+        );
+
+        registry.register::<InvoicePaid>().unwrap();
+        registry.register::<InvoicePaid>().unwrap();
+        assert_eq!(
+            registry.register::<Impostor>(),
+            Err(EnvelopeError::AlreadyRegistered(InvoicePaid::NAME.to_string()))
+        );
+
+        let event = registry.event(Some(EntityId(4)), &paid).unwrap();
+        assert_eq!(event.kind(), "custom.event");
+        assert_eq!(event.agent(), Some(EntityId(4)));
+        assert!(event.validate().is_ok());
+        registry.validate(&event).unwrap();
+        assert_eq!(registry.decode::<InvoicePaid>(&event).unwrap(), Some(paid));
+
+        // A payload from a newer release of the publishing crate
+        let KernelEvent::Custom { source, mut envelope, timestamp } = event else {
+            unreachable!()
+        };
+        envelope.schema_version = 3;
+        let newer = KernelEvent::Custom { source, envelope, timestamp };
+        assert!(matches!(
+            registry.decode::<InvoicePaid>(&newer),
+            Err(EnvelopeError::UnsupportedSchema { found: 3, supported: 2, .. })
+        ));
+
+        let free = InvoicePaid {
+            invoice: "INV-2".to_string(),
+            cents: 0,
+        };
+        assert!(matches!(
+            registry.event(None, &free),
+            Err(EnvelopeError::InvalidPayload { .. })
+        ));
+    }
+
+    #[test]
+    fn test_kernel_events_are_versioned_on_the_wire() {
+        let event = KernelEvent::RequestCancelled {
+            request_id: "req-1".to_string(),
+            timestamp: Utc::now(),
+        };
+        let envelope = EventEnvelope::wrap(&event).unwrap();
+        assert_eq!(envelope.name, "rpc.cancelled");
+        assert_eq!(envelope.unwrap_kernel().unwrap(), event);
+
+        let future = EventEnvelope {
+            schema_version: KERNEL_EVENT_SCHEMA_VERSION + 1,
+            ..envelope.clone()
+        };
+        assert!(matches!(future.unwrap_kernel(), Err(EnvelopeError::UnsupportedSchema { .. })));
+
+        let mislabeled = EventEnvelope {
+            name: "task.completed".to_string(),
+            ..envelope
+        };
+        assert!(matches!(mislabeled.unwrap_kernel(), Err(EnvelopeError::InvalidPayload { .. })));
+    }
+}
@@ -0,0 +1,370 @@
+//! Health tracking of [`InMemoryBus`](crate::InMemoryBus) subscribers.
+//!
+//! A plain subscription to an `InMemoryBus` that falls behind silently loses
+//! the events overwritten in its ring buffer. Subscriptions made with
+//! [`subscribe_monitored`](crate::InMemoryBus::subscribe_monitored) are
+//! tracked instead: [`subscriber_stats`](crate::InMemoryBus::subscriber_stats)
+//! reports how far each one lags, how many events it lost and when it last
+//! received one. A [`LagPolicy`] attached with
+//! [`with_lag_policy`](crate::InMemoryBus::with_lag_policy) acts on
+//! subscribers that stay behind for too long, either by publishing an alert
+//! or by evicting them.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+
+use crate::{ErrorCategory, ErrorContext, ErrorSeverity, EventFilter, KernelEvent};
+
+/// Error code of the alerts published for lagging subscribers.
+pub const SUBSCRIBER_LAGGING: &str = "BUS_SUBSCRIBER_LAGGING";
+
+/// What a [`LagPolicy`] does about a chronically lagging subscriber.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LagAction {
+    /// Publish a [`SystemError`](KernelEvent::SystemError) with error code
+    /// [`SUBSCRIBER_LAGGING`], once per lagging episode
+    Alert,
+    /// Stop delivering to the subscriber; it receives what is buffered,
+    /// then `Closed`
+    Evict,
+}
+
+/// When a subscriber counts as chronically lagging and what to do about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LagPolicy {
+    /// Lag, in events, above which a subscriber is behind
+    pub max_lag: u64,
+    /// How long a subscriber may stay behind before `action` is taken
+    pub patience: Duration,
+    /// Action taken on subscribers behind for longer than `patience`
+    pub action: LagAction,
+}
+
+impl LagPolicy {
+    /// Alert on subscribers more than `max_lag` events behind for `patience`.
+    pub fn alert(max_lag: u64, patience: Duration) -> Self {
+        Self { max_lag, patience, action: LagAction::Alert }
+    }
+
+    /// Evict subscribers more than `max_lag` events behind for `patience`.
+    pub fn evict(max_lag: u64, patience: Duration) -> Self {
+        Self { max_lag, patience, action: LagAction::Evict }
+    }
+}
+
+/// Delivery statistics of one monitored subscriber.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubscriberStats {
+    /// Identifier of the subscription on its bus
+    pub id: u64,
+    /// Events delivered to the subscriber but not yet received or lost
+    pub lag: u64,
+    /// Events received by the subscriber
+    pub received: u64,
+    /// Events overwritten before the subscriber received them
+    pub dropped: u64,
+    /// When the subscriber last received an event
+    pub last_received: Option<DateTime<Utc>>,
+    /// Since when the subscriber lags more than the bus's [`LagPolicy`] allows
+    pub lagging_since: Option<DateTime<Utc>>,
+    /// Whether the subscriber was evicted by the bus's [`LagPolicy`]
+    pub evicted: bool,
+}
+
+#[derive(Debug)]
+struct MonitorState {
+    stats: SubscriberStats,
+    /// Events the bus delivered to the subscriber
+    sent: u64,
+    /// The policy already acted on the current lagging episode
+    acted: bool,
+}
+
+impl MonitorState {
+    fn refresh_lag(&mut self) {
+        self.stats.lag = self.sent.saturating_sub(self.stats.received + self.stats.dropped);
+    }
+}
+
+/// Buffer of one monitored subscriber, shared with its receiver.
+#[derive(Debug)]
+struct MonitoredRoute {
+    filter: Option<EventFilter>,
+    /// `None` once the subscriber was evicted
+    tx: Option<broadcast::Sender<KernelEvent>>,
+    state: Arc<Mutex<MonitorState>>,
+}
+
+/// Monitored subscriptions of an [`InMemoryBus`](crate::InMemoryBus).
+#[derive(Debug, Default)]
+pub(crate) struct Monitors {
+    routes: Vec<MonitoredRoute>,
+    next_id: u64,
+}
+
+impl Monitors {
+    /// Register a subscription with a buffer of `capacity` events.
+    pub(crate) fn subscribe(&mut self, capacity: usize, filter: Option<EventFilter>) -> MonitoredReceiver {
+        let (tx, rx) = broadcast::channel(capacity);
+        self.next_id += 1;
+        let state = Arc::new(Mutex::new(MonitorState {
+            stats: SubscriberStats {
+                id: self.next_id,
+                lag: 0,
+                received: 0,
+                dropped: 0,
+                last_received: None,
+                lagging_since: None,
+                evicted: false,
+            },
+            sent: 0,
+            acted: false,
+        }));
+        self.routes.push(MonitoredRoute { filter, tx: Some(tx), state: Arc::clone(&state) });
+        MonitoredReceiver { rx, state }
+    }
+
+    pub(crate) fn stats(&self) -> Vec<SubscriberStats> {
+        self.routes
+            .iter()
+            .filter(|route| Arc::strong_count(&route.state) > 1)
+            .map(|route| {
+                let mut state = lock(&route.state);
+                state.refresh_lag();
+                state.stats.clone()
+            })
+            .collect()
+    }
+
+    /// Deliver `event` and apply `policy`, returning the alerts to publish.
+    pub(crate) fn deliver(&mut self, event: &KernelEvent, policy: Option<&LagPolicy>) -> Vec<KernelEvent> {
+        let now = Utc::now();
+        let mut alerts = Vec::new();
+        // Forget subscribers whose receiver was dropped
+        self.routes.retain(|route| Arc::strong_count(&route.state) > 1);
+        for route in &mut self.routes {
+            let Some(tx) = &route.tx else { continue };
+            if !route.filter.as_ref().is_none_or(|filter| filter.matches(event)) {
+                continue;
+            }
+            let _ = tx.send(event.clone());
+
+            let mut state = lock(&route.state);
+            state.sent += 1;
+            state.refresh_lag();
+            let Some(policy) = policy else { continue };
+            if state.stats.lag <= policy.max_lag {
+                state.stats.lagging_since = None;
+                state.acted = false;
+                continue;
+            }
+            let since = *state.stats.lagging_since.get_or_insert(now);
+            let out_of_patience = now
+                .signed_duration_since(since)
+                .to_std()
+                .is_ok_and(|behind| behind >= policy.patience);
+            if state.acted || !out_of_patience {
+                continue;
+            }
+            state.acted = true;
+            match policy.action {
+                LagAction::Alert => alerts.push(lag_alert(&state.stats, now)),
+                LagAction::Evict => {
+                    state.stats.evicted = true;
+                    drop(state);
+                    route.tx = None;
+                }
+            }
+        }
+        alerts
+    }
+}
+
+fn lock(state: &Mutex<MonitorState>) -> std::sync::MutexGuard<'_, MonitorState> {
+    state.lock().expect("subscriber monitor poisoned")
+}
+
+fn lag_alert(stats: &SubscriberStats, now: DateTime<Utc>) -> KernelEvent {
+    let mut metadata = HashMap::new();
+    metadata.insert("subscriber".to_string(), stats.id.to_string());
+    metadata.insert("lag".to_string(), stats.lag.to_string());
+    metadata.insert("dropped".to_string(), stats.dropped.to_string());
+    if let Some(last) = stats.last_received {
+        metadata.insert("last_received".to_string(), last.to_rfc3339());
+    }
+    KernelEvent::SystemError {
+        error_category: ErrorCategory::Resource,
+        error_code: SUBSCRIBER_LAGGING.to_string(),
+        context: ErrorContext { component: "event-bus".to_string(), metadata },
+        severity: ErrorSeverity::Warning,
+        timestamp: now,
+    }
+}
+
+/// Receiving end of a monitored subscription.
+///
+/// Returned by [`InMemoryBus::subscribe_monitored`](crate::InMemoryBus::subscribe_monitored).
+/// Dropping it removes the subscription from the bus's statistics.
+#[derive(Debug)]
+pub struct MonitoredReceiver {
+    rx: broadcast::Receiver<KernelEvent>,
+    state: Arc<Mutex<MonitorState>>,
+}
+
+impl MonitoredReceiver {
+    /// Receive the next event.
+    ///
+    /// Errors like [`broadcast::Receiver::recv`]; after an eviction the
+    /// buffered events are still returned, then
+    /// [`RecvError::Closed`].
+    pub async fn recv(&mut self) -> Result<KernelEvent, RecvError> {
+        let received = self.rx.recv().await;
+        self.record(received.as_ref().map_err(|e| match e {
+            RecvError::Lagged(missed) => Some(*missed),
+            RecvError::Closed => None,
+        }));
+        received
+    }
+
+    /// Receive the next event if one is already buffered.
+    pub fn try_recv(&mut self) -> Result<KernelEvent, TryRecvError> {
+        let received = self.rx.try_recv();
+        self.record(received.as_ref().map_err(|e| match e {
+            TryRecvError::Lagged(missed) => Some(*missed),
+            TryRecvError::Empty | TryRecvError::Closed => None,
+        }));
+        received
+    }
+
+    /// Current statistics of this subscription.
+    pub fn stats(&self) -> SubscriberStats {
+        let mut state = lock(&self.state);
+        state.refresh_lag();
+        state.stats.clone()
+    }
+
+    /// Count a received event or, with `Err(Some(n))`, `n` lost ones.
+    fn record(&self, outcome: Result<&KernelEvent, Option<u64>>) {
+        let mut state = lock(&self.state);
+        match outcome {
+            Ok(_) => {
+                state.stats.received += 1;
+                state.stats.last_received = Some(Utc::now());
+            }
+            Err(Some(missed)) => state.stats.dropped += missed,
+            Err(None) => {}
+        }
+        state.refresh_lag();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EventBus, InMemoryBus};
+    use toka_types::EntityId;
+
+    fn observation(agent: u128) -> KernelEvent {
+        KernelEvent::ObservationEmitted {
+            agent: EntityId(agent),
+            data: vec![1].into(),
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stats_track_lag_drops_and_receipt() {
+        let bus = InMemoryBus::new(4);
+        let mut rx = bus.subscribe_monitored();
+
+        for _ in 0..6 {
+            bus.publish(&observation(1)).unwrap();
+        }
+        assert_eq!(bus.subscriber_stats()[0].lag, 6);
+
+        // The two oldest events were overwritten in the ring buffer
+        assert!(matches!(rx.recv().await, Err(RecvError::Lagged(2))));
+        rx.recv().await.unwrap();
+
+        let stats = bus.subscriber_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!((stats[0].received, stats[0].dropped, stats[0].lag), (1, 2, 3));
+        assert!(stats[0].last_received.is_some());
+        assert_eq!(stats[0], rx.stats());
+
+        drop(rx);
+        bus.publish(&observation(1)).unwrap();
+        assert!(bus.subscriber_stats().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_filtered_subscriber_only_counts_matching_events() {
+        let bus = InMemoryBus::new(16);
+        let mut rx = bus.subscribe_monitored_filtered(EventFilter::new().agent(EntityId(2)));
+
+        bus.publish(&observation(1)).unwrap();
+        bus.publish(&observation(2)).unwrap();
+
+        assert_eq!(rx.stats().lag, 1);
+        assert_eq!(rx.recv().await.unwrap().agent(), Some(EntityId(2)));
+        assert_eq!(rx.stats().lag, 0);
+    }
+
+    #[tokio::test]
+    async fn test_evict_policy_closes_lagging_subscriber() {
+        let bus = InMemoryBus::new(16).with_lag_policy(LagPolicy::evict(2, Duration::ZERO));
+        let mut slow = bus.subscribe_monitored();
+        let mut fast = bus.subscribe_monitored();
+
+        for _ in 0..5 {
+            bus.publish(&observation(1)).unwrap();
+            fast.recv().await.unwrap();
+        }
+
+        let stats = bus.subscriber_stats();
+        assert!(stats[0].evicted);
+        assert!(!stats[1].evicted);
+        assert_eq!(stats[1].received, 5);
+
+        // The evicted subscriber drains what was buffered, then is closed
+        for _ in 0..3 {
+            slow.recv().await.unwrap();
+        }
+        assert!(matches!(slow.recv().await, Err(RecvError::Closed)));
+    }
+
+    #[tokio::test]
+    async fn test_alert_policy_publishes_one_alert_per_episode() {
+        let bus = InMemoryBus::new(16).with_lag_policy(LagPolicy::alert(1, Duration::ZERO));
+        let mut alerts = bus.subscribe_filtered(EventFilter::new().kind("error"));
+        let mut slow = bus.subscribe_monitored_filtered(EventFilter::new().kind("agent"));
+
+        for _ in 0..4 {
+            bus.publish(&observation(1)).unwrap();
+        }
+
+        let alert = alerts.recv().await.unwrap();
+        match &alert {
+            KernelEvent::SystemError { error_code, context, .. } => {
+                assert_eq!(error_code, SUBSCRIBER_LAGGING);
+                assert_eq!(context.metadata["lag"], "2");
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(alerts.try_recv().is_err());
+
+        // Catching up ends the episode; falling behind again alerts again
+        while slow.try_recv().is_ok() {}
+        for _ in 0..2 {
+            bus.publish(&observation(1)).unwrap();
+        }
+        assert!(alerts.recv().await.is_ok());
+        assert!(!bus.subscriber_stats()[0].evicted);
+    }
+}
@@ -9,12 +9,32 @@
 //!
 //! The bus abstraction allows different components to communicate via typed events
 //! while maintaining loose coupling and testability.
+//!
+//! # Features
+//!
+//! - `runtime` (default): the Tokio-backed [`EventBus`], [`InMemoryBus`]
+//!   with subscriber health tracking, [`BackpressureBus`], [`PriorityBus`], [`CoalescingBus`],
+//!   [`DeadLetterQueue`], request/response through [`RpcClient`] and
+//!   [`RpcResponder`], and glob subscriptions to event topics through
+//!   [`TopicRouter`].
+//! - `clock` (enabled by `runtime`): [`KernelEvent::validate`] against the
+//!   system clock.
+//!
+//! Without default features only the event types remain, and the crate
+//! compiles for `wasm32-unknown-unknown`, so browser dashboards and WASM
+//! tools can share them. Use [`KernelEvent::validate_at`] there with a time
+//! supplied by the host.
 
+#[cfg(feature = "runtime")]
 use std::sync::Arc;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "runtime")]
 use tokio::sync::broadcast;
 
+/// Shared byte buffer carrying observation data.
+pub use bytes::Bytes;
+
 use toka_types::{EntityId, TaskSpec, AgentSpec};
 use chrono::{DateTime, Utc};
 
@@ -61,8 +81,11 @@ pub enum KernelEvent {
     ObservationEmitted {
         /// The agent that made the observation
         agent: EntityId,
-        /// The observation data
-        data: Vec<u8>,
+        /// The observation data.
+        ///
+        /// Cloning the event, e.g. once per bus subscriber, shares the
+        /// buffer instead of copying it.
+        data: Bytes,
         /// Event timestamp
         timestamp: DateTime<Utc>,
     },
@@ -117,6 +140,10 @@ pub enum KernelEvent {
         result: TaskResult,
         /// Time taken to execute the task (milliseconds)
         execution_time_ms: u64,
+        /// Correlation identifier of the request that scheduled the task,
+        /// copied from [`TaskSpec::correlation_id`]
+        #[serde(default)]
+        correlation_id: Option<String>,
         /// Event timestamp
         timestamp: DateTime<Utc>,
     },
@@ -130,6 +157,10 @@ pub enum KernelEvent {
         error: String,
         /// Categorized failure reason
         failure_reason: FailureReason,
+        /// Correlation identifier of the request that scheduled the task,
+        /// copied from [`TaskSpec::correlation_id`]
+        #[serde(default)]
+        correlation_id: Option<String>,
         /// Event timestamp
         timestamp: DateTime<Utc>,
     },
@@ -141,6 +172,10 @@ pub enum KernelEvent {
         agent: EntityId,
         /// Configured timeout duration (milliseconds)
         timeout_duration_ms: u64,
+        /// Correlation identifier of the request that scheduled the task,
+        /// copied from [`TaskSpec::correlation_id`]
+        #[serde(default)]
+        correlation_id: Option<String>,
         /// Event timestamp
         timestamp: DateTime<Utc>,
     },
@@ -226,6 +261,130 @@ pub enum KernelEvent {
         /// Event timestamp
         timestamp: DateTime<Utc>,
     },
+    /// Resource usage of an agent rolled up over a window by a
+    /// `CoalescingBus`
+    ResourceSummary {
+        /// Agent the usage belongs to
+        agent: EntityId,
+        /// Timestamp of the first event rolled up
+        window_start: DateTime<Utc>,
+        /// Timestamp of the last event rolled up
+        window_end: DateTime<Utc>,
+        /// Number of events rolled up
+        events: u64,
+        /// Memory allocated during the window (bytes)
+        memory_allocated: u64,
+        /// Highest total allocation reported (bytes)
+        peak_memory: u64,
+        /// Mean of the reported CPU percentages
+        avg_cpu_percent: f64,
+        /// Highest reported CPU percentage
+        max_cpu_percent: f64,
+        /// Bytes transferred by I/O operations
+        io_bytes: u64,
+        /// Number of I/O operations
+        io_operations: u64,
+        /// Event timestamp
+        timestamp: DateTime<Utc>,
+    },
+
+    //─────────────────────────────
+    //  Reporting Events (v0.3)
+    //─────────────────────────────
+
+    /// A periodic progress report was generated
+    ReportGenerated {
+        /// Report identifier
+        report_id: String,
+        /// Agent the report covers (`None` for workstream reports)
+        agent: Option<EntityId>,
+        /// Workstream the report covers
+        workstream: String,
+        /// Rendered report content (markdown or JSON)
+        content: String,
+        /// Event timestamp
+        timestamp: DateTime<Utc>,
+    },
+
+    //─────────────────────────────
+    //  Storage Events (v0.3)
+    //─────────────────────────────
+
+    /// Periodic health report of a store's write-ahead log
+    WalHealthReported {
+        /// Name of the reporting store
+        store: String,
+        /// Transactions begun but neither committed nor rolled back
+        active_transactions: u64,
+        /// WAL entries not yet committed
+        pending_entries: u64,
+        /// Committed WAL entries not yet checkpointed
+        committed_entries: u64,
+        /// Rolled back WAL entries still retained
+        rolled_back_entries: u64,
+        /// Checkpointed WAL entries awaiting compaction
+        checkpointed_entries: u64,
+        /// Age of the oldest pending entry (milliseconds)
+        oldest_pending_age_ms: Option<u64>,
+        /// Bytes used by retained WAL entries
+        bytes_used: u64,
+        /// Whether the WAL exceeds the reporter's backlog thresholds
+        backlogged: bool,
+        /// Event timestamp
+        timestamp: DateTime<Utc>,
+    },
+
+    //─────────────────────────────
+    //  RPC Events (v0.3)
+    //─────────────────────────────
+
+    /// A request awaiting a [`ResponseSent`](KernelEvent::ResponseSent)
+    RequestIssued {
+        /// Correlation identifier echoed by the response
+        request_id: String,
+        /// Agent asking, if any
+        requester: Option<EntityId>,
+        /// Method the request is addressed to, e.g. `planner.estimate`
+        method: String,
+        /// Request arguments
+        payload: Bytes,
+        /// Time after which the requester no longer waits for an answer
+        deadline: Option<DateTime<Utc>>,
+        /// Event timestamp
+        timestamp: DateTime<Utc>,
+    },
+    /// Answer to a [`RequestIssued`](KernelEvent::RequestIssued)
+    ResponseSent {
+        /// Identifier of the answered request
+        request_id: String,
+        /// Agent answering, if any
+        responder: Option<EntityId>,
+        /// Response body, or the error the handler failed with
+        outcome: Result<Bytes, String>,
+        /// Event timestamp
+        timestamp: DateTime<Utc>,
+    },
+    /// The requester stopped waiting for an answer
+    RequestCancelled {
+        /// Identifier of the abandoned request
+        request_id: String,
+        /// Event timestamp
+        timestamp: DateTime<Utc>,
+    },
+
+    //─────────────────────────────
+    //  Custom Events (v0.3)
+    //─────────────────────────────
+
+    /// Event type defined outside this crate, see [`EventSchemaRegistry`]
+    Custom {
+        /// Agent that published the event, if any
+        source: Option<EntityId>,
+        /// Name, schema version and payload of the event
+        envelope: EventEnvelope,
+        /// Event timestamp
+        timestamp: DateTime<Utc>,
+    },
 }
 
 //─────────────────────────────
@@ -332,8 +491,8 @@ pub struct ErrorContext {
     pub metadata: std::collections::HashMap<String, String>,
 }
 
-/// Error severity levels
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Error severity levels, ordered from least to most severe
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ErrorSeverity {
     /// Informational - no action required
     Info,
@@ -399,14 +558,115 @@ pub enum IOOperationType {
 }
 
 impl KernelEvent {
+    /// Stable dotted name of the event variant, e.g. `task.completed`.
+    ///
+    /// The first segment names the event family, so consumers can select
+    /// families by prefix.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            KernelEvent::TaskScheduled { .. } => "task.scheduled",
+            KernelEvent::AgentSpawned { .. } => "agent.spawned",
+            KernelEvent::ObservationEmitted { .. } => "agent.observation",
+            KernelEvent::AgentTerminated { .. } => "agent.terminated",
+            KernelEvent::AgentSuspended { .. } => "agent.suspended",
+            KernelEvent::AgentResumed { .. } => "agent.resumed",
+            KernelEvent::TaskCompleted { .. } => "task.completed",
+            KernelEvent::TaskFailed { .. } => "task.failed",
+            KernelEvent::TaskTimeout { .. } => "task.timeout",
+            KernelEvent::SystemError { .. } => "error.system",
+            KernelEvent::ValidationError { .. } => "error.validation",
+            KernelEvent::ResourceError { .. } => "error.resource",
+            KernelEvent::MemoryAllocated { .. } => "resource.memory",
+            KernelEvent::CPUUtilization { .. } => "resource.cpu",
+            KernelEvent::IOOperation { .. } => "resource.io",
+            KernelEvent::ResourceSummary { .. } => "resource.summary",
+            KernelEvent::ReportGenerated { .. } => "report.generated",
+            KernelEvent::WalHealthReported { .. } => "storage.wal_health",
+            KernelEvent::RequestIssued { .. } => "rpc.request",
+            KernelEvent::ResponseSent { .. } => "rpc.response",
+            KernelEvent::RequestCancelled { .. } => "rpc.cancelled",
+            KernelEvent::Custom { .. } => "custom.event",
+        }
+    }
+
+    /// Agent the event concerns, if any.
+    ///
+    /// For [`AgentSpawned`](KernelEvent::AgentSpawned) this is the parent
+    /// that spawned the new agent.
+    pub fn agent(&self) -> Option<EntityId> {
+        match self {
+            KernelEvent::TaskScheduled { agent, .. }
+            | KernelEvent::ObservationEmitted { agent, .. }
+            | KernelEvent::AgentTerminated { agent, .. }
+            | KernelEvent::AgentSuspended { agent, .. }
+            | KernelEvent::AgentResumed { agent, .. }
+            | KernelEvent::TaskCompleted { agent, .. }
+            | KernelEvent::TaskFailed { agent, .. }
+            | KernelEvent::TaskTimeout { agent, .. }
+            | KernelEvent::MemoryAllocated { agent, .. }
+            | KernelEvent::CPUUtilization { agent, .. }
+            | KernelEvent::IOOperation { agent, .. }
+            | KernelEvent::ResourceSummary { agent, .. } => Some(*agent),
+            KernelEvent::AgentSpawned { parent, .. } => Some(*parent),
+            KernelEvent::ResourceError { agent, .. } | KernelEvent::ReportGenerated { agent, .. } => *agent,
+            KernelEvent::RequestIssued { requester, .. } => *requester,
+            KernelEvent::ResponseSent { responder, .. } => *responder,
+            KernelEvent::Custom { source, .. } => *source,
+            KernelEvent::SystemError { .. }
+            | KernelEvent::ValidationError { .. }
+            | KernelEvent::WalHealthReported { .. }
+            | KernelEvent::RequestCancelled { .. } => None,
+        }
+    }
+
+    /// Task the event concerns, if any.
+    pub fn task_id(&self) -> Option<&str> {
+        match self {
+            KernelEvent::TaskCompleted { task_id, .. }
+            | KernelEvent::TaskFailed { task_id, .. }
+            | KernelEvent::TaskTimeout { task_id, .. } => Some(task_id),
+            _ => None,
+        }
+    }
+
+    /// Correlation identifier of the request the event belongs to, if any.
+    ///
+    /// Follows a request from its submission through
+    /// [`TaskScheduled`](KernelEvent::TaskScheduled) to the task's outcome.
+    pub fn correlation_id(&self) -> Option<&str> {
+        match self {
+            KernelEvent::TaskScheduled { task, .. } => task.correlation_id.as_deref(),
+            KernelEvent::TaskCompleted { correlation_id, .. }
+            | KernelEvent::TaskFailed { correlation_id, .. }
+            | KernelEvent::TaskTimeout { correlation_id, .. } => correlation_id.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Severity of the event, if it carries one.
+    pub fn severity(&self) -> Option<&ErrorSeverity> {
+        match self {
+            KernelEvent::SystemError { severity, .. } => Some(severity),
+            _ => None,
+        }
+    }
+
     /// Validate the kernel event to ensure it meets security constraints.
     /// 
     /// # Security
     /// Validates all event parameters to prevent various attack vectors.
     /// Enhanced in v0.3.0 to validate new event types and timestamp constraints.
+    #[cfg(feature = "clock")]
     pub fn validate(&self) -> Result<(), String> {
+        self.validate_at(Utc::now())
+    }
+
+    /// Validate the kernel event, checking timestamps against `now`.
+    ///
+    /// Same checks as [`validate`](Self::validate), for targets without a
+    /// system clock.
+    pub fn validate_at(&self, now: DateTime<Utc>) -> Result<(), String> {
         // Common timestamp validation
-        let now = Utc::now();
         let max_timestamp_drift = chrono::Duration::hours(24); // Allow 24-hour drift
         
         match self {
@@ -544,6 +804,98 @@ impl KernelEvent {
                 self.validate_timestamp(*timestamp, now, max_timestamp_drift)?;
                 Ok(())
             }
+            KernelEvent::ResourceSummary {
+                window_start,
+                window_end,
+                peak_memory,
+                avg_cpu_percent,
+                max_cpu_percent,
+                timestamp,
+                ..
+            } => {
+                const MAX_MEMORY: u64 = 1_000_000_000_000; // 1TB
+                if window_start > window_end {
+                    return Err("Summary window ends before it starts".to_string());
+                }
+                if *peak_memory > MAX_MEMORY {
+                    return Err("Memory amount exceeds maximum limit".to_string());
+                }
+                if !(0.0..=100.0).contains(avg_cpu_percent) || !(0.0..=100.0).contains(max_cpu_percent) {
+                    return Err("CPU percentage must be between 0 and 100".to_string());
+                }
+                self.validate_timestamp(*timestamp, now, max_timestamp_drift)?;
+                Ok(())
+            }
+
+            // Reporting Events (v0.3)
+            KernelEvent::ReportGenerated { content, timestamp, .. } => {
+                // SECURITY: Reports share the observation size limit
+                if content.len() > toka_types::MAX_OBSERVATION_DATA_LEN {
+                    return Err(format!(
+                        "Report content too large: {} > {}",
+                        content.len(),
+                        toka_types::MAX_OBSERVATION_DATA_LEN
+                    ));
+                }
+                self.validate_timestamp(*timestamp, now, max_timestamp_drift)?;
+                Ok(())
+            }
+
+            // Storage Events (v0.3)
+            KernelEvent::WalHealthReported { store, timestamp, .. } => {
+                const MAX_STORE_NAME_LEN: usize = 256;
+                if store.is_empty() || store.len() > MAX_STORE_NAME_LEN {
+                    return Err("Store name must be 1-256 characters".to_string());
+                }
+                self.validate_timestamp(*timestamp, now, max_timestamp_drift)?;
+                Ok(())
+            }
+
+            // RPC Events (v0.3)
+            KernelEvent::RequestIssued { request_id, method, payload, timestamp, .. } => {
+                self.validate_request_id(request_id)?;
+                if method.is_empty() || method.len() > 256 {
+                    return Err("Method name must be 1-256 characters".to_string());
+                }
+                // SECURITY: Requests share the observation size limit
+                if payload.len() > toka_types::MAX_OBSERVATION_DATA_LEN {
+                    return Err(format!(
+                        "Request payload too large: {} > {}",
+                        payload.len(),
+                        toka_types::MAX_OBSERVATION_DATA_LEN
+                    ));
+                }
+                self.validate_timestamp(*timestamp, now, max_timestamp_drift)?;
+                Ok(())
+            }
+            KernelEvent::ResponseSent { request_id, outcome, timestamp, .. } => {
+                self.validate_request_id(request_id)?;
+                let len = match outcome {
+                    Ok(payload) => payload.len(),
+                    Err(error) => error.len(),
+                };
+                if len > toka_types::MAX_OBSERVATION_DATA_LEN {
+                    return Err(format!(
+                        "Response too large: {} > {}",
+                        len,
+                        toka_types::MAX_OBSERVATION_DATA_LEN
+                    ));
+                }
+                self.validate_timestamp(*timestamp, now, max_timestamp_drift)?;
+                Ok(())
+            }
+            KernelEvent::RequestCancelled { request_id, timestamp } => {
+                self.validate_request_id(request_id)?;
+                self.validate_timestamp(*timestamp, now, max_timestamp_drift)?;
+                Ok(())
+            }
+
+            // Custom Events (v0.3)
+            KernelEvent::Custom { envelope, timestamp, .. } => {
+                envelope.validate()?;
+                self.validate_timestamp(*timestamp, now, max_timestamp_drift)?;
+                Ok(())
+            }
         }
     }
 
@@ -566,6 +918,14 @@ impl KernelEvent {
         Ok(())
     }
 
+    /// Validate RPC request ID length
+    fn validate_request_id(&self, request_id: &str) -> Result<(), String> {
+        if request_id.is_empty() || request_id.len() > 256 {
+            return Err("Request ID must be 1-256 characters".to_string());
+        }
+        Ok(())
+    }
+
     /// Validate execution time is reasonable
     fn validate_execution_time(&self, execution_time_ms: u64) -> Result<(), String> {
         const MAX_EXECUTION_TIME_MS: u64 = 24 * 60 * 60 * 1000; // 24 hours
@@ -658,6 +1018,163 @@ impl KernelEvent {
     }
 }
 
+//─────────────────────────────
+//  Event filters
+//─────────────────────────────
+
+/// Selection of kernel events for [`EventBus::subscribe_filtered`].
+///
+/// Each criterion that is set must match, and within a criterion any of the
+/// listed values may match. The default filter matches every event.
+///
+/// ```rust
+/// use toka_bus_core::{ErrorSeverity, EventFilter, TopicPattern};
+/// use toka_types::EntityId;
+///
+/// // Task outcomes of agent 7, plus serious errors from anywhere
+/// let tasks = EventFilter::new().kind("task").agent(EntityId(7));
+/// let errors = EventFilter::new().min_severity(ErrorSeverity::Error);
+/// // Task failures of every agent
+/// let failures = EventFilter::new().topic(TopicPattern::parse("agents.*.task.failed").unwrap());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EventFilter {
+    /// Event kinds (`task.completed`) or families (`task`) to accept
+    #[serde(default)]
+    pub kinds: Vec<String>,
+    /// Agents whose events to accept
+    #[serde(default)]
+    pub agents: Vec<EntityId>,
+    /// Tasks whose events to accept
+    #[serde(default)]
+    pub task_ids: Vec<String>,
+    /// Lowest severity to accept; events without a severity are rejected
+    #[serde(default)]
+    pub min_severity: Option<ErrorSeverity>,
+    /// Topic patterns to accept, see [`topic`]
+    #[serde(default)]
+    pub topics: Vec<TopicPattern>,
+}
+
+impl EventFilter {
+    /// Filter matching every event.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also accept events of the kind or family `kind`.
+    pub fn kind(mut self, kind: impl Into<String>) -> Self {
+        self.kinds.push(kind.into());
+        self
+    }
+
+    /// Also accept events concerning `agent`.
+    pub fn agent(mut self, agent: EntityId) -> Self {
+        self.agents.push(agent);
+        self
+    }
+
+    /// Also accept events concerning the task `task_id`.
+    pub fn task(mut self, task_id: impl Into<String>) -> Self {
+        self.task_ids.push(task_id.into());
+        self
+    }
+
+    /// Only accept events at least as severe as `severity`.
+    pub fn min_severity(mut self, severity: ErrorSeverity) -> Self {
+        self.min_severity = Some(severity);
+        self
+    }
+
+    /// Also accept events whose topic matches `pattern`.
+    pub fn topic(mut self, pattern: TopicPattern) -> Self {
+        self.topics.push(pattern);
+        self
+    }
+
+    /// Whether `event` passes the filter.
+    pub fn matches(&self, event: &KernelEvent) -> bool {
+        if !self.kinds.is_empty() {
+            let kind = event.kind();
+            let family = kind.split('.').next().unwrap_or(kind);
+            if !self.kinds.iter().any(|k| k == kind || k == family) {
+                return false;
+            }
+        }
+        if !self.agents.is_empty() && !event.agent().is_some_and(|agent| self.agents.contains(&agent)) {
+            return false;
+        }
+        if !self.task_ids.is_empty() && !event.task_id().is_some_and(|task| self.task_ids.iter().any(|t| t == task)) {
+            return false;
+        }
+        if let Some(min) = &self.min_severity {
+            if event.severity().is_none_or(|severity| severity < min) {
+                return false;
+            }
+        }
+        if !self.topics.is_empty() {
+            let topic = topic_of(event);
+            if !self.topics.iter().any(|pattern| pattern.matches(&topic)) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Receiver of the events passing an [`EventFilter`].
+///
+/// Returned by [`EventBus::subscribe_filtered`]. Depending on the bus, the
+/// filter is applied before events are queued for the subscriber or as they
+/// are received; either way only matching events are returned.
+#[cfg(feature = "runtime")]
+#[derive(Debug)]
+pub struct FilteredReceiver {
+    rx: broadcast::Receiver<KernelEvent>,
+    filter: Option<EventFilter>,
+}
+
+#[cfg(feature = "runtime")]
+impl FilteredReceiver {
+    /// Receive from `rx` the events passing `filter`.
+    pub fn new(rx: broadcast::Receiver<KernelEvent>, filter: EventFilter) -> Self {
+        Self { rx, filter: Some(filter) }
+    }
+
+    /// Wrap `rx`, which only ever receives matching events.
+    pub fn prefiltered(rx: broadcast::Receiver<KernelEvent>) -> Self {
+        Self { rx, filter: None }
+    }
+
+    /// Receive the next matching event.
+    ///
+    /// Errors like [`broadcast::Receiver::recv`]: a lagging subscriber gets
+    /// [`RecvError::Lagged`](broadcast::error::RecvError::Lagged) and then
+    /// continues with the oldest event still buffered.
+    pub async fn recv(&mut self) -> Result<KernelEvent, broadcast::error::RecvError> {
+        loop {
+            let event = self.rx.recv().await?;
+            if self.accepts(&event) {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// Receive the next matching event if one is already buffered.
+    pub fn try_recv(&mut self) -> Result<KernelEvent, broadcast::error::TryRecvError> {
+        loop {
+            let event = self.rx.try_recv()?;
+            if self.accepts(&event) {
+                return Ok(event);
+            }
+        }
+    }
+
+    fn accepts(&self, event: &KernelEvent) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter.matches(event))
+    }
+}
+
 //─────────────────────────────
 //  Event bus trait
 //─────────────────────────────
@@ -667,6 +1184,7 @@ impl KernelEvent {
 /// The bus provides a simple publish-subscribe mechanism that allows different
 /// components to communicate asynchronously while maintaining loose coupling.
 /// All implementations must be thread-safe and support multiple subscribers.
+#[cfg(feature = "runtime")]
 pub trait EventBus: Send + Sync {
     /// Publish an event to all subscribers.
     ///
@@ -681,6 +1199,15 @@ pub trait EventBus: Send + Sync {
     /// after the subscription was created. Subscribers that fall behind
     /// may miss events if the bus buffer overflows.
     fn subscribe(&self) -> broadcast::Receiver<KernelEvent>;
+
+    /// Subscribe to the live events passing `filter`.
+    ///
+    /// The default implementation filters a plain subscription as events
+    /// are received. Buses that can route events should override it so that
+    /// non-matching events are never queued for the subscriber.
+    fn subscribe_filtered(&self, filter: EventFilter) -> FilteredReceiver {
+        FilteredReceiver::new(self.subscribe(), filter)
+    }
 }
 
 //─────────────────────────────
@@ -692,17 +1219,41 @@ pub trait EventBus: Send + Sync {
 /// This implementation uses a ring buffer to store recent events and broadcasts
 /// them to all active subscribers. It provides good performance for scenarios
 /// where events don't need persistence.
+///
+/// Filtered subscribers get a buffer of their own, and each event is matched
+/// once per filter at publish time, so events are only cloned into the
+/// buffers of subscribers that want them.
+///
+/// Subscribers made with [`subscribe_monitored`](Self::subscribe_monitored)
+/// also get a buffer of their own, and their lag and losses are reported by
+/// [`subscriber_stats`](Self::subscriber_stats).
+#[cfg(feature = "runtime")]
 #[derive(Debug, Clone)]
 pub struct InMemoryBus {
     tx: Arc<broadcast::Sender<KernelEvent>>,
+    routes: Arc<std::sync::Mutex<Vec<FilteredRoute>>>,
+    monitors: Arc<std::sync::Mutex<health::Monitors>>,
+    capacity: usize,
+    dead_letters: Option<DeadLetterQueue>,
+    lag_policy: Option<LagPolicy>,
+}
+
+/// Buffer of one filtered subscriber of an [`InMemoryBus`].
+#[cfg(feature = "runtime")]
+#[derive(Debug)]
+struct FilteredRoute {
+    filter: EventFilter,
+    tx: broadcast::Sender<KernelEvent>,
 }
 
+#[cfg(feature = "runtime")]
 impl Default for InMemoryBus {
     fn default() -> Self {
         Self::new(1024)
     }
 }
 
+#[cfg(feature = "runtime")]
 impl InMemoryBus {
     /// Create a new in-memory bus with the specified ring buffer capacity.
     ///
@@ -710,28 +1261,240 @@ impl InMemoryBus {
     /// subscribers before older events are dropped.
     pub fn new(capacity: usize) -> Self {
         let (tx, _rx) = broadcast::channel(capacity);
-        Self { tx: Arc::new(tx) }
+        Self {
+            tx: Arc::new(tx),
+            routes: Arc::new(std::sync::Mutex::new(Vec::new())),
+            monitors: Arc::new(std::sync::Mutex::new(health::Monitors::default())),
+            capacity,
+            dead_letters: None,
+            lag_policy: None,
+        }
+    }
+
+    /// Capture events rejected by validation in `dead_letters`.
+    pub fn with_dead_letters(mut self, dead_letters: DeadLetterQueue) -> Self {
+        self.dead_letters = Some(dead_letters);
+        self
+    }
+
+    /// Act on monitored subscribers that keep lagging as `policy` says.
+    pub fn with_lag_policy(mut self, policy: LagPolicy) -> Self {
+        self.lag_policy = Some(policy);
+        self
+    }
+
+    /// Subscribe to the live event stream with health tracking.
+    pub fn subscribe_monitored(&self) -> MonitoredReceiver {
+        self.lock_monitors().subscribe(self.capacity, None)
+    }
+
+    /// Subscribe to the live events passing `filter` with health tracking.
+    pub fn subscribe_monitored_filtered(&self, filter: EventFilter) -> MonitoredReceiver {
+        self.lock_monitors().subscribe(self.capacity, Some(filter))
+    }
+
+    /// Statistics of the monitored subscribers, oldest subscription first.
+    ///
+    /// Evicted subscribers are listed until their receiver is dropped.
+    pub fn subscriber_stats(&self) -> Vec<SubscriberStats> {
+        self.lock_monitors().stats()
     }
 
     /// Get the current number of active subscribers.
     pub fn subscriber_count(&self) -> usize {
-        self.tx.receiver_count()
+        let filtered: usize = self
+            .routes
+            .lock()
+            .expect("filtered routes poisoned")
+            .iter()
+            .map(|route| route.tx.receiver_count())
+            .sum();
+        let monitored = self.lock_monitors().stats().iter().filter(|stats| !stats.evicted).count();
+        self.tx.receiver_count() + filtered + monitored
+    }
+
+    fn lock_monitors(&self) -> std::sync::MutexGuard<'_, health::Monitors> {
+        self.monitors.lock().expect("subscriber monitors poisoned")
     }
 }
 
+#[cfg(feature = "runtime")]
 impl EventBus for InMemoryBus {
     fn publish(&self, event: &KernelEvent) -> Result<()> {
         // SECURITY: Validate event before publishing
-        event.validate().map_err(|e| BusError::PublishFailed(e))?;
-        
+        if let Err(e) = event.validate() {
+            if let Some(dead_letters) = &self.dead_letters {
+                dead_letters.push(event.clone(), DeadLetterReason::Invalid(e.clone()));
+            }
+            return Err(BusError::PublishFailed(e).into());
+        }
+
         // Ignore lagging receiver errors - subscribers must handle missed events
         let _ = self.tx.send(event.clone());
+
+        let mut routes = self.routes.lock().expect("filtered routes poisoned");
+        // Drop the buffers of subscribers that went away
+        routes.retain(|route| route.tx.receiver_count() > 0);
+        for route in routes.iter().filter(|route| route.filter.matches(event)) {
+            let _ = route.tx.send(event.clone());
+        }
+        drop(routes);
+
+        let alerts = self.lock_monitors().deliver(event, self.lag_policy.as_ref());
+        for alert in &alerts {
+            self.publish(alert)?;
+        }
         Ok(())
     }
 
     fn subscribe(&self) -> broadcast::Receiver<KernelEvent> {
         self.tx.subscribe()
     }
+
+    fn subscribe_filtered(&self, filter: EventFilter) -> FilteredReceiver {
+        let (tx, rx) = broadcast::channel(self.capacity);
+        self.routes
+            .lock()
+            .expect("filtered routes poisoned")
+            .push(FilteredRoute { filter, tx });
+        FilteredReceiver::prefiltered(rx)
+    }
+}
+
+//─────────────────────────────
+//  Topic routing
+//─────────────────────────────
+
+/// Hierarchical event topics and glob subscriptions.
+pub mod topic;
+
+pub use topic::{topic_of, TopicPattern, TopicPatternError};
+#[cfg(feature = "runtime")]
+pub use topic::TopicRouter;
+
+//─────────────────────────────
+//  Versioned envelopes
+//─────────────────────────────
+
+/// Versioned wire envelopes and the registry of custom event types.
+pub mod envelope;
+
+pub use envelope::{
+    CustomEvent, EnvelopeError, EventEnvelope, EventSchemaRegistry, ENVELOPE_VERSION, KERNEL_EVENT_SCHEMA_VERSION,
+};
+
+//─────────────────────────────
+//  Bounded bus implementation
+//─────────────────────────────
+
+/// Bounded event bus with per-subscriber overflow policies.
+#[cfg(feature = "runtime")]
+pub mod backpressure;
+
+#[cfg(feature = "runtime")]
+pub use backpressure::{BackpressureBus, BoundedSubscriber, OverflowPolicy, SubscriberMetrics};
+
+//─────────────────────────────
+//  Subscriber health
+//─────────────────────────────
+
+/// Lag tracking and eviction of in-memory bus subscribers.
+#[cfg(feature = "runtime")]
+pub mod health;
+
+#[cfg(feature = "runtime")]
+pub use health::{LagAction, LagPolicy, MonitoredReceiver, SubscriberStats, SUBSCRIBER_LAGGING};
+
+//─────────────────────────────
+//  Priority lanes
+//─────────────────────────────
+
+/// Event bus delivering urgent events ahead of telemetry.
+#[cfg(feature = "runtime")]
+pub mod priority;
+
+#[cfg(feature = "runtime")]
+pub use priority::{EventPriority, LaneCapacities, LaneMetrics, PriorityBus, PrioritySubscriber};
+
+//─────────────────────────────
+//  Telemetry coalescing
+//─────────────────────────────
+
+/// Per-agent rollup of high-frequency resource events.
+#[cfg(feature = "runtime")]
+pub mod coalesce;
+
+#[cfg(feature = "runtime")]
+pub use coalesce::{CoalescingBus, CoalescingStats, DEFAULT_COALESCING_WINDOW};
+
+//─────────────────────────────
+//  Dead-letter queue
+//─────────────────────────────
+
+/// Capture of rejected and dropped events for inspection and replay.
+#[cfg(feature = "runtime")]
+pub mod dlq;
+
+#[cfg(feature = "runtime")]
+pub use dlq::{DeadLetter, DeadLetterQueue, DeadLetterReason};
+
+//─────────────────────────────
+//  Request/response
+//─────────────────────────────
+
+/// Correlated requests and responses over the event bus.
+#[cfg(feature = "runtime")]
+pub mod rpc;
+
+#[cfg(feature = "runtime")]
+pub use rpc::{PendingResponse, RpcCall, RpcClient, RpcError, RpcRequest, RpcResponder, RpcResponse};
+
+//─────────────────────────────
+//  Fault injection
+//─────────────────────────────
+
+/// Event bus wrapper that silently drops events for resilience testing.
+///
+/// Each publish consults the [`FaultInjector`](toka_types::FaultInjector);
+/// when it decides to fail, the event is discarded and `publish` still
+/// returns `Ok`, just as a lossy transport would. Publishing is synchronous,
+/// so configured latency is ignored here.
+#[cfg(feature = "fault-injection")]
+pub struct FaultyBus<B> {
+    inner: B,
+    injector: Arc<toka_types::FaultInjector>,
+}
+
+#[cfg(feature = "fault-injection")]
+impl<B: EventBus> FaultyBus<B> {
+    /// Wrap `inner`, dropping events as decided by `injector`.
+    pub fn new(inner: B, injector: Arc<toka_types::FaultInjector>) -> Self {
+        Self { inner, injector }
+    }
+
+    /// The injector deciding which events are dropped.
+    pub fn injector(&self) -> &Arc<toka_types::FaultInjector> {
+        &self.injector
+    }
+}
+
+#[cfg(feature = "fault-injection")]
+impl<B: EventBus> EventBus for FaultyBus<B> {
+    fn publish(&self, event: &KernelEvent) -> Result<()> {
+        let (_, drop_event) = self.injector.next_fault();
+        if drop_event {
+            return Ok(());
+        }
+        self.inner.publish(event)
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<KernelEvent> {
+        self.inner.subscribe()
+    }
+
+    fn subscribe_filtered(&self, filter: EventFilter) -> FilteredReceiver {
+        self.inner.subscribe_filtered(filter)
+    }
 }
 
 //─────────────────────────────
@@ -747,9 +1510,12 @@ pub enum BusError {
     /// Subscription failed
     #[error("failed to create subscription: {0}")]
     SubscriptionFailed(String),
+    /// A subscriber cannot take more events until it catches up
+    #[error("subscriber applies backpressure: {0}")]
+    Backpressure(String),
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "runtime"))]
 mod tests {
     use super::*;
     use tokio::sync::broadcast::error::RecvError;
@@ -763,6 +1529,7 @@ mod tests {
             agent: EntityId(123),
             task: TaskSpec {
                 description: "test task".to_string(),
+                correlation_id: None,
             },
             timestamp: Utc::now(),
         };
@@ -807,7 +1574,7 @@ mod tests {
         for i in 0..5 {
             let event = KernelEvent::ObservationEmitted {
                 agent: EntityId(i as u128),
-                data: vec![i as u8],
+                data: vec![i as u8].into(),
                 timestamp: Utc::now(),
             };
             bus.publish(&event).unwrap();
@@ -831,4 +1598,62 @@ mod tests {
             }
         }
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_filtered_subscriptions_only_receive_matches() {
+        let bus = InMemoryBus::new(16);
+        let mut agent_tasks = bus.subscribe_filtered(EventFilter::new().kind("task").agent(EntityId(7)));
+        let mut serious = bus.subscribe_filtered(EventFilter::new().min_severity(ErrorSeverity::Error));
+        assert_eq!(bus.subscriber_count(), 2);
+
+        let completed = |agent| KernelEvent::TaskCompleted {
+            task_id: "task-1".to_string(),
+            agent: EntityId(agent),
+            result: TaskResult::SuccessEmpty,
+            execution_time_ms: 10,
+            correlation_id: None,
+            timestamp: Utc::now(),
+        };
+        let error = |severity| KernelEvent::SystemError {
+            error_category: ErrorCategory::Storage,
+            error_code: "E1".to_string(),
+            context: ErrorContext {
+                component: "store".to_string(),
+                metadata: std::collections::HashMap::new(),
+            },
+            severity,
+            timestamp: Utc::now(),
+        };
+
+        for event in [completed(3), error(ErrorSeverity::Warning), completed(7), error(ErrorSeverity::Critical)] {
+            bus.publish(&event).unwrap();
+        }
+
+        assert_eq!(agent_tasks.recv().await.unwrap().agent(), Some(EntityId(7)));
+        assert!(agent_tasks.try_recv().is_err());
+        assert!(matches!(
+            serious.recv().await.unwrap(),
+            KernelEvent::SystemError { severity: ErrorSeverity::Critical, .. }
+        ));
+        assert!(serious.try_recv().is_err());
+
+        // Dropped subscribers stop receiving copies
+        drop(agent_tasks);
+        bus.publish(&completed(7)).unwrap();
+        assert_eq!(bus.subscriber_count(), 1);
+    }
+
+    #[test]
+    fn test_validate_at_supplied_time() {
+        let sent = Utc::now();
+        let event = KernelEvent::TaskScheduled {
+            agent: EntityId(1),
+            task: TaskSpec::new("wasm dashboard".to_string()).unwrap(),
+            timestamp: sent,
+        };
+
+        assert!(event.validate_at(sent + chrono::Duration::hours(1)).is_ok());
+        assert!(event.validate_at(sent + chrono::Duration::hours(25)).is_err());
+        assert_eq!(event.validate(), event.validate_at(Utc::now()));
+    }
+}
@@ -0,0 +1,463 @@
+//! Priority lanes for event delivery.
+//!
+//! On a plain ring buffer a flood of telemetry such as
+//! [`CPUUtilization`](KernelEvent::CPUUtilization) reports can overwrite a
+//! critical [`SystemError`](KernelEvent::SystemError) before anyone reads
+//! it. [`PriorityBus`] classifies every event into an [`EventPriority`] and
+//! gives each subscriber one ring buffer per class:
+//!
+//! - Subscribers always receive the highest-priority event queued, so
+//!   errors overtake a backlog of telemetry. Within a class, events arrive
+//!   in publish order.
+//! - When a lane is full its oldest event is dropped; lanes never evict
+//!   each other, so telemetry can only crowd out telemetry.
+//! - The [`Critical`](EventPriority::Critical) lane is unbounded: critical
+//!   events are never dropped and are received in publish order before
+//!   anything else.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, Notify};
+
+use crate::{BusError, DeadLetterQueue, DeadLetterReason, ErrorSeverity, EventBus, EventFilter, KernelEvent, OverflowPolicy};
+
+/// Delivery class of an event, from least to most urgent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventPriority {
+    /// Telemetry: observations, resource usage and storage health
+    Low,
+    /// Lifecycle, task, reporting and RPC events
+    Normal,
+    /// Failures and errors below critical severity
+    High,
+    /// System errors of [`ErrorSeverity::Critical`]
+    Critical,
+}
+
+impl EventPriority {
+    /// Every class, from least to most urgent.
+    pub const ALL: [EventPriority; 4] = [
+        EventPriority::Low,
+        EventPriority::Normal,
+        EventPriority::High,
+        EventPriority::Critical,
+    ];
+
+    /// Class of `event`.
+    pub fn of(event: &KernelEvent) -> Self {
+        match event {
+            KernelEvent::SystemError { severity, .. } if *severity == ErrorSeverity::Critical => EventPriority::Critical,
+            KernelEvent::SystemError { .. }
+            | KernelEvent::ValidationError { .. }
+            | KernelEvent::ResourceError { .. }
+            | KernelEvent::TaskFailed { .. }
+            | KernelEvent::TaskTimeout { .. }
+            | KernelEvent::AgentTerminated { .. } => EventPriority::High,
+            KernelEvent::ObservationEmitted { .. }
+            | KernelEvent::MemoryAllocated { .. }
+            | KernelEvent::CPUUtilization { .. }
+            | KernelEvent::IOOperation { .. }
+            | KernelEvent::ResourceSummary { .. }
+            | KernelEvent::WalHealthReported { .. } => EventPriority::Low,
+            _ => EventPriority::Normal,
+        }
+    }
+
+    fn lane(self) -> usize {
+        self as usize
+    }
+}
+
+/// Ring buffer sizes of the bounded lanes of each subscriber.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LaneCapacities {
+    /// Capacity of the [`Low`](EventPriority::Low) lane
+    pub low: usize,
+    /// Capacity of the [`Normal`](EventPriority::Normal) lane
+    pub normal: usize,
+    /// Capacity of the [`High`](EventPriority::High) lane
+    pub high: usize,
+}
+
+impl Default for LaneCapacities {
+    fn default() -> Self {
+        Self {
+            low: 256,
+            normal: 1024,
+            high: 1024,
+        }
+    }
+}
+
+impl LaneCapacities {
+    /// Capacity of the lane of `priority`, `None` for the unbounded
+    /// critical lane.
+    pub fn of(&self, priority: EventPriority) -> Option<usize> {
+        match priority {
+            EventPriority::Low => Some(self.low.max(1)),
+            EventPriority::Normal => Some(self.normal.max(1)),
+            EventPriority::High => Some(self.high.max(1)),
+            EventPriority::Critical => None,
+        }
+    }
+}
+
+/// Delivery statistics of one lane of a subscriber.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LaneMetrics {
+    /// Class of the lane
+    pub priority: EventPriority,
+    /// Ring buffer size, `None` if unbounded
+    pub capacity: Option<usize>,
+    /// Events queued but not yet received
+    pub queued: usize,
+    /// Events received by the subscriber
+    pub delivered: u64,
+    /// Events dropped because the lane was full
+    pub dropped: u64,
+}
+
+struct LaneState {
+    events: [VecDeque<KernelEvent>; 4],
+    metrics: [LaneMetrics; 4],
+    /// The subscriber was dropped
+    closed: bool,
+    /// The bus was dropped
+    bus_closed: bool,
+}
+
+/// Lanes of one subscriber, shared with the bus.
+struct PriorityQueue {
+    id: u64,
+    filter: Option<EventFilter>,
+    state: Mutex<LaneState>,
+    readable: Notify,
+}
+
+impl PriorityQueue {
+    fn lock(&self) -> std::sync::MutexGuard<'_, LaneState> {
+        self.state.lock().expect("priority queue poisoned")
+    }
+
+    fn accepts(&self, event: &KernelEvent) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter.matches(event))
+    }
+
+    /// Queue `event`, returning the event evicted to make room, if any.
+    fn offer(&self, event: &KernelEvent) -> Option<KernelEvent> {
+        let lane = EventPriority::of(event).lane();
+        let mut state = self.lock();
+        let evicted = match state.metrics[lane].capacity {
+            Some(capacity) if state.events[lane].len() >= capacity => {
+                state.metrics[lane].dropped += 1;
+                state.events[lane].pop_front()
+            }
+            _ => None,
+        };
+        state.events[lane].push_back(event.clone());
+        state.metrics[lane].queued = state.events[lane].len();
+        drop(state);
+        self.readable.notify_one();
+        evicted
+    }
+}
+
+struct BusShared {
+    subscribers: Mutex<Vec<Arc<PriorityQueue>>>,
+    next_id: AtomicU64,
+}
+
+impl Drop for BusShared {
+    fn drop(&mut self) {
+        let subscribers = self.subscribers.get_mut().expect("subscribers poisoned");
+        for queue in subscribers.drain(..) {
+            queue.lock().bus_closed = true;
+            queue.readable.notify_one();
+        }
+    }
+}
+
+/// Event bus delivering events to each subscriber by priority.
+///
+/// Prioritized subscribers are created with
+/// [`subscribe_prioritized`](Self::subscribe_prioritized). Plain
+/// [`EventBus::subscribe`] receivers are served from a shared broadcast ring
+/// buffer in publish order, like on an `InMemoryBus`.
+#[derive(Clone)]
+pub struct PriorityBus {
+    shared: Arc<BusShared>,
+    tx: Arc<broadcast::Sender<KernelEvent>>,
+    capacities: LaneCapacities,
+    dead_letters: Option<DeadLetterQueue>,
+}
+
+impl Default for PriorityBus {
+    fn default() -> Self {
+        Self::new(LaneCapacities::default())
+    }
+}
+
+impl std::fmt::Debug for PriorityBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PriorityBus")
+            .field("capacities", &self.capacities)
+            .field("subscribers", &self.subscriber_count())
+            .finish()
+    }
+}
+
+impl PriorityBus {
+    /// Create a bus whose prioritized subscribers get lanes of
+    /// `capacities`.
+    ///
+    /// Plain subscribers share a ring buffer as large as all bounded lanes
+    /// together.
+    pub fn new(capacities: LaneCapacities) -> Self {
+        let (tx, _rx) = broadcast::channel((capacities.low + capacities.normal + capacities.high).max(1));
+        Self {
+            shared: Arc::new(BusShared {
+                subscribers: Mutex::new(Vec::new()),
+                next_id: AtomicU64::new(1),
+            }),
+            tx: Arc::new(tx),
+            capacities,
+            dead_letters: None,
+        }
+    }
+
+    /// Capture events rejected by validation or dropped from a full lane in
+    /// `dead_letters`.
+    pub fn with_dead_letters(mut self, dead_letters: DeadLetterQueue) -> Self {
+        self.dead_letters = Some(dead_letters);
+        self
+    }
+
+    /// Lane sizes of new subscribers.
+    pub fn capacities(&self) -> LaneCapacities {
+        self.capacities
+    }
+
+    /// Subscribe to every event, receiving the most urgent first.
+    pub fn subscribe_prioritized(&self) -> PrioritySubscriber {
+        self.add_subscriber(None)
+    }
+
+    /// Subscribe to the events passing `filter`, receiving the most urgent
+    /// first.
+    pub fn subscribe_prioritized_filtered(&self, filter: EventFilter) -> PrioritySubscriber {
+        self.add_subscriber(Some(filter))
+    }
+
+    /// Number of connected prioritized subscribers.
+    pub fn subscriber_count(&self) -> usize {
+        self.shared.subscribers.lock().expect("subscribers poisoned").len()
+    }
+
+    fn add_subscriber(&self, filter: Option<EventFilter>) -> PrioritySubscriber {
+        let metrics = EventPriority::ALL.map(|priority| LaneMetrics {
+            priority,
+            capacity: self.capacities.of(priority),
+            queued: 0,
+            delivered: 0,
+            dropped: 0,
+        });
+        let queue = Arc::new(PriorityQueue {
+            id: self.shared.next_id.fetch_add(1, Ordering::Relaxed),
+            filter,
+            state: Mutex::new(LaneState {
+                events: Default::default(),
+                metrics,
+                closed: false,
+                bus_closed: false,
+            }),
+            readable: Notify::new(),
+        });
+        self.shared
+            .subscribers
+            .lock()
+            .expect("subscribers poisoned")
+            .push(queue.clone());
+        PrioritySubscriber { queue }
+    }
+
+    fn validate(&self, event: &KernelEvent) -> Result<()> {
+        event.validate().map_err(|e| {
+            if let Some(dead_letters) = &self.dead_letters {
+                dead_letters.push(event.clone(), DeadLetterReason::Invalid(e.clone()));
+            }
+            BusError::PublishFailed(e).into()
+        })
+    }
+}
+
+impl EventBus for PriorityBus {
+    fn publish(&self, event: &KernelEvent) -> Result<()> {
+        self.validate(event)?;
+
+        // Holding the list lock serializes publishers, which keeps every
+        // lane, and the critical lane in particular, in publish order
+        let mut subscribers = self.shared.subscribers.lock().expect("subscribers poisoned");
+        subscribers.retain(|queue| !queue.lock().closed);
+        let _ = self.tx.send(event.clone());
+        for queue in subscribers.iter().filter(|queue| queue.accepts(event)) {
+            if let (Some(evicted), Some(dead_letters)) = (queue.offer(event), &self.dead_letters) {
+                dead_letters.push(
+                    evicted,
+                    DeadLetterReason::Overflow {
+                        subscriber: queue.id,
+                        policy: OverflowPolicy::DropOldest,
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<KernelEvent> {
+        self.tx.subscribe()
+    }
+}
+
+/// Receiving end of a prioritized subscription.
+pub struct PrioritySubscriber {
+    queue: Arc<PriorityQueue>,
+}
+
+impl PrioritySubscriber {
+    /// Identifier of the subscription on its bus.
+    pub fn id(&self) -> u64 {
+        self.queue.id
+    }
+
+    /// Receive the most urgent queued event, waiting for one if needed.
+    ///
+    /// Returns `None` once the bus is gone and every queued event has been
+    /// received.
+    pub async fn recv(&mut self) -> Option<KernelEvent> {
+        loop {
+            let queue = Arc::clone(&self.queue);
+            let readable = queue.readable.notified();
+            match self.try_recv() {
+                Ok(event) => return Some(event),
+                Err(true) => return None,
+                Err(false) => readable.await,
+            }
+        }
+    }
+
+    /// Receive the most urgent queued event, if any.
+    ///
+    /// `Err(true)` means no event will ever arrive again, `Err(false)` that
+    /// none is queued right now.
+    pub fn try_recv(&mut self) -> std::result::Result<KernelEvent, bool> {
+        let mut state = self.queue.lock();
+        for lane in (0..EventPriority::ALL.len()).rev() {
+            if let Some(event) = state.events[lane].pop_front() {
+                state.metrics[lane].delivered += 1;
+                state.metrics[lane].queued = state.events[lane].len();
+                return Ok(event);
+            }
+        }
+        Err(state.bus_closed)
+    }
+
+    /// Delivery statistics of each lane, from least to most urgent.
+    pub fn metrics(&self) -> Vec<LaneMetrics> {
+        self.queue.lock().metrics.to_vec()
+    }
+}
+
+impl Drop for PrioritySubscriber {
+    fn drop(&mut self) {
+        self.queue.lock().closed = true;
+    }
+}
+
+impl std::fmt::Debug for PrioritySubscriber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrioritySubscriber")
+            .field("id", &self.queue.id)
+            .field("lanes", &self.metrics())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ErrorCategory, ErrorContext};
+    use chrono::Utc;
+    use toka_types::EntityId;
+
+    fn cpu(n: u64) -> KernelEvent {
+        KernelEvent::CPUUtilization {
+            agent: EntityId(1),
+            cpu_percent: 50.0,
+            duration_ms: n,
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn error(code: &str, severity: ErrorSeverity) -> KernelEvent {
+        KernelEvent::SystemError {
+            error_category: ErrorCategory::Storage,
+            error_code: code.to_string(),
+            context: ErrorContext {
+                component: "store".to_string(),
+                metadata: std::collections::HashMap::new(),
+            },
+            severity,
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn code(event: Option<KernelEvent>) -> String {
+        match event {
+            Some(KernelEvent::SystemError { error_code, .. }) => error_code,
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_critical_events_survive_telemetry_flood() {
+        let dead_letters = DeadLetterQueue::new(16);
+        let bus = PriorityBus::new(LaneCapacities {
+            low: 4,
+            normal: 4,
+            high: 4,
+        })
+        .with_dead_letters(dead_letters.clone());
+        let mut subscriber = bus.subscribe_prioritized();
+
+        bus.publish(&error("C1", ErrorSeverity::Critical)).unwrap();
+        for n in 0..50 {
+            bus.publish(&cpu(n)).unwrap();
+            if n == 25 {
+                bus.publish(&error("E1", ErrorSeverity::Error)).unwrap();
+                bus.publish(&error("C2", ErrorSeverity::Critical)).unwrap();
+            }
+        }
+        bus.publish(&error("C3", ErrorSeverity::Critical)).unwrap();
+
+        // Critical events first and in order, then errors, then telemetry
+        for expected in ["C1", "C2", "C3", "E1"] {
+            assert_eq!(code(subscriber.recv().await), expected);
+        }
+        let mut telemetry = Vec::new();
+        while let Ok(KernelEvent::CPUUtilization { duration_ms, .. }) = subscriber.try_recv() {
+            telemetry.push(duration_ms);
+        }
+        assert_eq!(telemetry, vec![46, 47, 48, 49]);
+
+        let metrics = subscriber.metrics();
+        assert_eq!(metrics[EventPriority::Low.lane()].dropped, 46);
+        assert_eq!(metrics[EventPriority::Critical.lane()].delivered, 3);
+        assert_eq!(metrics[EventPriority::Critical.lane()].dropped, 0);
+        assert_eq!(dead_letters.len(), 16);
+    }
+}
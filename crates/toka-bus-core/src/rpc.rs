@@ -0,0 +1,549 @@
+//! Request/response on top of the broadcast bus.
+//!
+//! An [`RpcClient`] publishes a [`KernelEvent::RequestIssued`] carrying a
+//! fresh correlation identifier and returns a [`PendingResponse`] that
+//! resolves once a [`KernelEvent::ResponseSent`] with the same identifier
+//! arrives. Handlers are registered per method on an [`RpcResponder`], which
+//! answers matching requests from its own task.
+//!
+//! A pending response gives up after its timeout. Timing out, calling
+//! [`PendingResponse::cancel`] or dropping the pending response publishes a
+//! [`KernelEvent::RequestCancelled`], and responders abort the handler still
+//! working on it. Requests also carry their deadline, so responders skip
+//! requests nobody waits for any more.
+//!
+//! Several responders may serve the same method; the first answer wins and
+//! later ones are ignored.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use toka_types::EntityId;
+
+use crate::{Bytes, EventBus, EventFilter, KernelEvent};
+
+/// Time a request waits for its response unless configured otherwise.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Why a request did not produce a response.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RpcError {
+    /// No response arrived in time
+    #[error("no response within {0:?}")]
+    Timeout(Duration),
+    /// The client shut down before a response arrived
+    #[error("request was cancelled")]
+    Cancelled,
+    /// The responder's handler failed
+    #[error("responder failed: {0}")]
+    Remote(String),
+    /// The bus rejected the request
+    #[error("failed to publish request: {0}")]
+    Publish(String),
+}
+
+/// A request to send with [`RpcClient::publish_request`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcRequest {
+    /// Method the request is addressed to
+    pub method: String,
+    /// Request arguments
+    pub payload: Bytes,
+    /// Agent asking, if any
+    pub requester: Option<EntityId>,
+    /// Time to wait for the response, the client's default if `None`
+    pub timeout: Option<Duration>,
+}
+
+impl RpcRequest {
+    /// Call `method` with `payload`.
+    pub fn new(method: impl Into<String>, payload: impl Into<Bytes>) -> Self {
+        Self {
+            method: method.into(),
+            payload: payload.into(),
+            requester: None,
+            timeout: None,
+        }
+    }
+
+    /// Ask on behalf of `agent`.
+    pub fn from_agent(mut self, agent: EntityId) -> Self {
+        self.requester = Some(agent);
+        self
+    }
+
+    /// Wait at most `timeout` for the response.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+/// Successful answer to a request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcResponse {
+    /// Identifier of the answered request
+    pub request_id: String,
+    /// Agent that answered, if any
+    pub responder: Option<EntityId>,
+    /// Response body
+    pub payload: Bytes,
+}
+
+type Waiter = oneshot::Sender<Result<RpcResponse, RpcError>>;
+type Waiters = Arc<Mutex<HashMap<String, Waiter>>>;
+
+/// Sends requests and routes responses back to their callers.
+///
+/// A single background task listens for responses on behalf of every
+/// request of the client, so one client should be shared rather than
+/// created per request.
+pub struct RpcClient {
+    bus: Arc<dyn EventBus>,
+    waiters: Waiters,
+    default_timeout: Duration,
+    listener: JoinHandle<()>,
+}
+
+impl std::fmt::Debug for RpcClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RpcClient")
+            .field("default_timeout", &self.default_timeout)
+            .field("pending", &self.pending())
+            .finish()
+    }
+}
+
+impl RpcClient {
+    /// Create a client sending requests over `bus`.
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn new(bus: Arc<dyn EventBus>) -> Self {
+        let waiters = Waiters::default();
+        let mut responses = bus.subscribe_filtered(EventFilter::new().kind("rpc.response"));
+        let listener = {
+            let waiters = waiters.clone();
+            tokio::spawn(async move {
+                loop {
+                    match responses.recv().await {
+                        Ok(KernelEvent::ResponseSent {
+                            request_id,
+                            responder,
+                            outcome,
+                            ..
+                        }) => {
+                            let waiter = lock(&waiters).remove(&request_id);
+                            if let Some(waiter) = waiter {
+                                let result = outcome
+                                    .map(|payload| RpcResponse {
+                                        request_id,
+                                        responder,
+                                        payload,
+                                    })
+                                    .map_err(RpcError::Remote);
+                                let _ = waiter.send(result);
+                            }
+                        }
+                        // Responses missed while lagging surface as timeouts
+                        Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+            })
+        };
+        Self {
+            bus,
+            waiters,
+            default_timeout: DEFAULT_REQUEST_TIMEOUT,
+            listener,
+        }
+    }
+
+    /// Wait `timeout` for responses to requests that don't set their own.
+    pub fn with_default_timeout(mut self, timeout: Duration) -> Self {
+        self.default_timeout = timeout;
+        self
+    }
+
+    /// Number of requests still awaiting a response.
+    pub fn pending(&self) -> usize {
+        lock(&self.waiters).len()
+    }
+
+    /// Publish `request` and return its pending response.
+    ///
+    /// Fails only if the bus rejects the request, e.g. because its payload
+    /// is too large.
+    pub fn publish_request(&self, request: RpcRequest) -> Result<PendingResponse, RpcError> {
+        let request_id = Uuid::new_v4().to_string();
+        let timeout = request.timeout.unwrap_or(self.default_timeout);
+        let (tx, rx) = oneshot::channel();
+        // Register before publishing so that a fast responder is not missed
+        lock(&self.waiters).insert(request_id.clone(), tx);
+
+        let now = Utc::now();
+        let event = KernelEvent::RequestIssued {
+            request_id: request_id.clone(),
+            requester: request.requester,
+            method: request.method,
+            payload: request.payload,
+            deadline: chrono::Duration::from_std(timeout).ok().map(|timeout| now + timeout),
+            timestamp: now,
+        };
+        if let Err(e) = self.bus.publish(&event) {
+            lock(&self.waiters).remove(&request_id);
+            return Err(RpcError::Publish(e.to_string()));
+        }
+
+        Ok(PendingResponse {
+            request_id,
+            rx,
+            timeout,
+            expiry: Box::pin(tokio::time::sleep(timeout)),
+            bus: self.bus.clone(),
+            waiters: self.waiters.clone(),
+            finished: false,
+        })
+    }
+
+    /// Publish `request` and wait for its response.
+    pub async fn request(&self, request: RpcRequest) -> Result<RpcResponse, RpcError> {
+        self.publish_request(request)?.await
+    }
+}
+
+impl Drop for RpcClient {
+    fn drop(&mut self) {
+        self.listener.abort();
+        // Resolves outstanding requests with `RpcError::Cancelled`
+        lock(&self.waiters).clear();
+    }
+}
+
+/// Response of a published request, resolved by awaiting it.
+///
+/// Dropping it before it resolved cancels the request.
+pub struct PendingResponse {
+    request_id: String,
+    rx: oneshot::Receiver<Result<RpcResponse, RpcError>>,
+    timeout: Duration,
+    expiry: Pin<Box<tokio::time::Sleep>>,
+    bus: Arc<dyn EventBus>,
+    waiters: Waiters,
+    finished: bool,
+}
+
+impl std::fmt::Debug for PendingResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PendingResponse")
+            .field("request_id", &self.request_id)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl PendingResponse {
+    /// Correlation identifier of the request.
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+
+    /// Stop waiting and tell responders to abandon the request.
+    pub fn cancel(mut self) {
+        self.abandon();
+    }
+
+    fn abandon(&mut self) {
+        if std::mem::replace(&mut self.finished, true) {
+            return;
+        }
+        if lock(&self.waiters).remove(&self.request_id).is_some() {
+            let _ = self.bus.publish(&KernelEvent::RequestCancelled {
+                request_id: self.request_id.clone(),
+                timestamp: Utc::now(),
+            });
+        }
+    }
+}
+
+impl Future for PendingResponse {
+    type Output = Result<RpcResponse, RpcError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if let Poll::Ready(result) = Pin::new(&mut this.rx).poll(cx) {
+            this.finished = true;
+            return Poll::Ready(result.unwrap_or(Err(RpcError::Cancelled)));
+        }
+        if this.expiry.as_mut().poll(cx).is_ready() {
+            this.abandon();
+            return Poll::Ready(Err(RpcError::Timeout(this.timeout)));
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for PendingResponse {
+    fn drop(&mut self) {
+        self.abandon();
+    }
+}
+
+/// A request delivered to a registered handler.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcCall {
+    /// Correlation identifier of the request
+    pub request_id: String,
+    /// Agent asking, if any
+    pub requester: Option<EntityId>,
+    /// Method the request is addressed to
+    pub method: String,
+    /// Request arguments
+    pub payload: Bytes,
+    /// Time after which the requester no longer waits
+    pub deadline: Option<DateTime<Utc>>,
+}
+
+type HandlerFuture = Pin<Box<dyn Future<Output = Result<Bytes, String>> + Send>>;
+type Handler = Arc<dyn Fn(RpcCall) -> HandlerFuture + Send + Sync>;
+
+/// Answers requests with the handlers registered per method.
+///
+/// Clones share their handlers, so methods can be registered and removed
+/// while [`spawn`](Self::spawn)ed. Requests for methods without a handler
+/// are left to other responders.
+#[derive(Clone)]
+pub struct RpcResponder {
+    bus: Arc<dyn EventBus>,
+    identity: Option<EntityId>,
+    handlers: Arc<Mutex<HashMap<String, Handler>>>,
+}
+
+impl std::fmt::Debug for RpcResponder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RpcResponder")
+            .field("identity", &self.identity)
+            .field("methods", &self.methods())
+            .finish()
+    }
+}
+
+impl RpcResponder {
+    /// Create a responder answering requests on `bus`.
+    pub fn new(bus: Arc<dyn EventBus>) -> Self {
+        Self {
+            bus,
+            identity: None,
+            handlers: Arc::default(),
+        }
+    }
+
+    /// Answer on behalf of `agent`.
+    pub fn with_identity(mut self, agent: EntityId) -> Self {
+        self.identity = Some(agent);
+        self
+    }
+
+    /// Answer requests for `method` with `handler`, replacing any previous
+    /// handler of the method.
+    ///
+    /// An `Err` returned by the handler is sent to the requester as
+    /// [`RpcError::Remote`].
+    pub fn register<F, Fut>(&self, method: impl Into<String>, handler: F)
+    where
+        F: Fn(RpcCall) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Bytes, String>> + Send + 'static,
+    {
+        let handler: Handler = Arc::new(move |call| Box::pin(handler(call)));
+        lock(&self.handlers).insert(method.into(), handler);
+    }
+
+    /// Stop answering `method`, returning whether it had a handler.
+    pub fn unregister(&self, method: &str) -> bool {
+        lock(&self.handlers).remove(method).is_some()
+    }
+
+    /// Methods with a registered handler, sorted.
+    pub fn methods(&self) -> Vec<String> {
+        let mut methods: Vec<String> = lock(&self.handlers).keys().cloned().collect();
+        methods.sort();
+        methods
+    }
+
+    /// Answer requests until the bus closes or the task is aborted.
+    ///
+    /// Each request is handled on a task of its own, so a slow handler does
+    /// not hold up other requests.
+    pub fn spawn(&self) -> JoinHandle<()> {
+        let responder = self.clone();
+        let mut requests = self
+            .bus
+            .subscribe_filtered(EventFilter::new().kind("rpc.request").kind("rpc.cancelled"));
+        let in_flight: Arc<Mutex<HashMap<String, oneshot::Sender<()>>>> = Arc::default();
+
+        tokio::spawn(async move {
+            loop {
+                match requests.recv().await {
+                    Ok(KernelEvent::RequestIssued {
+                        request_id,
+                        requester,
+                        method,
+                        payload,
+                        deadline,
+                        ..
+                    }) => {
+                        let remaining = match deadline {
+                            Some(deadline) => match (deadline - Utc::now()).to_std() {
+                                Ok(remaining) => Some(remaining),
+                                // Nobody waits for the answer any more
+                                Err(_) => continue,
+                            },
+                            None => None,
+                        };
+                        let handler = lock(&responder.handlers).get(&method).cloned();
+                        let Some(handler) = handler else {
+                            continue;
+                        };
+
+                        let (cancel, cancelled) = oneshot::channel();
+                        lock(&in_flight).insert(request_id.clone(), cancel);
+                        let call = RpcCall {
+                            request_id: request_id.clone(),
+                            requester,
+                            method,
+                            payload,
+                            deadline,
+                        };
+                        let responder = responder.clone();
+                        let in_flight = in_flight.clone();
+                        tokio::spawn(async move {
+                            let expired = async move {
+                                match remaining {
+                                    Some(remaining) => tokio::time::sleep(remaining).await,
+                                    None => std::future::pending().await,
+                                }
+                            };
+                            let outcome = tokio::select! {
+                                outcome = handler(call) => Some(outcome),
+                                _ = cancelled => None,
+                                _ = expired => None,
+                            };
+                            lock(&in_flight).remove(&request_id);
+                            if let Some(outcome) = outcome {
+                                responder.respond(request_id, outcome);
+                            }
+                        });
+                    }
+                    Ok(KernelEvent::RequestCancelled { request_id, .. }) => {
+                        if let Some(cancel) = lock(&in_flight).remove(&request_id) {
+                            let _ = cancel.send(());
+                        }
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    fn respond(&self, request_id: String, outcome: Result<Bytes, String>) {
+        let response = |outcome| KernelEvent::ResponseSent {
+            request_id: request_id.clone(),
+            responder: self.identity,
+            outcome,
+            timestamp: Utc::now(),
+        };
+        // A response the bus rejects, e.g. for its size, still gets an answer
+        if let Err(e) = self.bus.publish(&response(outcome)) {
+            let _ = self.bus.publish(&response(Err(format!("invalid response: {}", e))));
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().expect("rpc state poisoned")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+    use crate::InMemoryBus;
+
+    #[tokio::test]
+    async fn test_requests_are_answered_by_registered_handlers() {
+        let bus: Arc<dyn EventBus> = Arc::new(InMemoryBus::new(64));
+        let responder = RpcResponder::new(bus.clone()).with_identity(EntityId(9));
+        responder.register("math.double", |call: RpcCall| async move {
+            match call.payload.first() {
+                Some(n) => Ok(Bytes::from(vec![n * 2])),
+                None => Err("empty request".to_string()),
+            }
+        });
+        let server = responder.spawn();
+        let client = RpcClient::new(bus.clone()).with_default_timeout(Duration::from_millis(200));
+
+        let response = client
+            .request(RpcRequest::new("math.double", vec![21u8]).from_agent(EntityId(1)))
+            .await
+            .unwrap();
+        assert_eq!(response.payload, Bytes::from(vec![42]));
+        assert_eq!(response.responder, Some(EntityId(9)));
+
+        assert_eq!(
+            client.request(RpcRequest::new("math.double", Bytes::new())).await,
+            Err(RpcError::Remote("empty request".to_string()))
+        );
+
+        // Nobody answers unregistered methods
+        assert!(responder.unregister("math.double"));
+        assert_eq!(
+            client.request(RpcRequest::new("math.double", vec![1u8])).await,
+            Err(RpcError::Timeout(Duration::from_millis(200)))
+        );
+        assert_eq!(client.pending(), 0);
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_requests_abort_their_handler() {
+        let bus: Arc<dyn EventBus> = Arc::new(InMemoryBus::new(64));
+        let finished = Arc::new(AtomicBool::new(false));
+        let responder = RpcResponder::new(bus.clone());
+        {
+            let finished = finished.clone();
+            responder.register("slow", move |_| {
+                let finished = finished.clone();
+                async move {
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    finished.store(true, Ordering::SeqCst);
+                    Ok(Bytes::new())
+                }
+            });
+        }
+        let server = responder.spawn();
+        let client = RpcClient::new(bus.clone());
+        let mut responses = bus.subscribe_filtered(EventFilter::new().kind("rpc.response"));
+
+        let pending = client.publish_request(RpcRequest::new("slow", Bytes::new())).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        pending.cancel();
+        assert_eq!(client.pending(), 0);
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(!finished.load(Ordering::SeqCst));
+        assert!(responses.try_recv().is_err());
+        server.abort();
+    }
+}
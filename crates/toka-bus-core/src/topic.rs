@@ -0,0 +1,280 @@
+//! Hierarchical topics and glob patterns over kernel events.
+//!
+//! Every [`KernelEvent`] maps to a dot-separated topic: events concerning an
+//! agent live under `agents.<id>`, all others under `system`, followed by
+//! the event kind. A task failure of agent 1234 is `agents.1234.task.failed`,
+//! a system error is `system.error.system`.
+//!
+//! Subscribers select topics with a [`TopicPattern`], where `*` matches one
+//! segment and `**` any number of segments, including none:
+//!
+//! - `agents.*.task.failed` – all task failures
+//! - `agents.1234.**` – everything concerning agent 1234
+//! - `**.error.*` – every error, with or without an agent
+//!
+//! Patterns are part of [`EventFilter`](crate::EventFilter), so buses that
+//! route filtered subscriptions match them once at publish time and
+//! subscribers never see, let alone decode, other events.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::KernelEvent;
+
+/// First segment of the topics of events concerning an agent.
+pub const AGENT_TOPIC_PREFIX: &str = "agents";
+
+/// First segment of the topics of events without an agent.
+pub const SYSTEM_TOPIC_PREFIX: &str = "system";
+
+/// Topic of `event`, e.g. `agents.1234.task.failed`.
+pub fn topic_of(event: &KernelEvent) -> String {
+    match event.agent() {
+        Some(agent) => format!("{}.{}.{}", AGENT_TOPIC_PREFIX, agent.0, event.kind()),
+        None => format!("{}.{}", SYSTEM_TOPIC_PREFIX, event.kind()),
+    }
+}
+
+/// Why a topic pattern could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TopicPatternError {
+    /// The pattern is empty or has an empty segment, e.g. `agents..task`
+    #[error("topic pattern '{0}' has an empty segment")]
+    EmptySegment(String),
+    /// A segment mixes wildcards with other characters, e.g. `task*`
+    #[error("topic pattern '{pattern}' has invalid segment '{segment}'; wildcards must fill a whole segment")]
+    InvalidSegment {
+        /// The whole pattern
+        pattern: String,
+        /// The offending segment
+        segment: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    /// `*`
+    One,
+    /// `**`
+    Any,
+}
+
+/// Glob pattern over event topics.
+///
+/// Serialized as its textual form, e.g. `"agents.*.task.failed"`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TopicPattern {
+    pattern: String,
+    segments: Vec<Segment>,
+}
+
+impl TopicPattern {
+    /// Parse `pattern`.
+    pub fn parse(pattern: &str) -> Result<Self, TopicPatternError> {
+        let segments = pattern
+            .split('.')
+            .map(|segment| match segment {
+                "" => Err(TopicPatternError::EmptySegment(pattern.to_string())),
+                "*" => Ok(Segment::One),
+                "**" => Ok(Segment::Any),
+                literal if literal.contains('*') => Err(TopicPatternError::InvalidSegment {
+                    pattern: pattern.to_string(),
+                    segment: literal.to_string(),
+                }),
+                literal => Ok(Segment::Literal(literal.to_string())),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            pattern: pattern.to_string(),
+            segments,
+        })
+    }
+
+    /// Pattern matching every topic.
+    pub fn all() -> Self {
+        Self {
+            pattern: "**".to_string(),
+            segments: vec![Segment::Any],
+        }
+    }
+
+    /// Textual form of the pattern.
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Whether `topic` matches the pattern.
+    pub fn matches(&self, topic: &str) -> bool {
+        let topic: Vec<&str> = topic.split('.').collect();
+        matches_segments(&self.segments, &topic)
+    }
+
+    /// Whether the topic of `event` matches the pattern.
+    pub fn matches_event(&self, event: &KernelEvent) -> bool {
+        self.matches(&topic_of(event))
+    }
+}
+
+fn matches_segments(pattern: &[Segment], topic: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => topic.is_empty(),
+        Some((Segment::Any, rest)) => (0..=topic.len()).any(|skip| matches_segments(rest, &topic[skip..])),
+        Some((segment, rest)) => match topic.split_first() {
+            Some((first, remaining)) => {
+                let matched = match segment {
+                    Segment::Literal(literal) => literal == first,
+                    _ => true,
+                };
+                matched && matches_segments(rest, remaining)
+            }
+            None => false,
+        },
+    }
+}
+
+impl FromStr for TopicPattern {
+    type Err = TopicPatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<String> for TopicPattern {
+    type Error = TopicPatternError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse(&value)
+    }
+}
+
+impl From<TopicPattern> for String {
+    fn from(pattern: TopicPattern) -> Self {
+        pattern.pattern
+    }
+}
+
+impl fmt::Display for TopicPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+/// Topic-based subscriptions on top of an [`EventBus`](crate::EventBus).
+///
+/// Subscriptions are [`EventFilter`](crate::EventFilter)s holding the
+/// patterns, so the bus decides where they are matched.
+#[cfg(feature = "runtime")]
+#[derive(Clone)]
+pub struct TopicRouter {
+    bus: std::sync::Arc<dyn crate::EventBus>,
+}
+
+#[cfg(feature = "runtime")]
+impl TopicRouter {
+    /// Route subscriptions of `bus`.
+    pub fn new(bus: std::sync::Arc<dyn crate::EventBus>) -> Self {
+        Self { bus }
+    }
+
+    /// Receive the events whose topic matches `pattern`.
+    pub fn subscribe(&self, pattern: &str) -> Result<crate::FilteredReceiver, TopicPatternError> {
+        self.subscribe_any([pattern])
+    }
+
+    /// Receive the events whose topic matches any of `patterns`.
+    pub fn subscribe_any<'a>(
+        &self,
+        patterns: impl IntoIterator<Item = &'a str>,
+    ) -> Result<crate::FilteredReceiver, TopicPatternError> {
+        let mut filter = crate::EventFilter::new();
+        for pattern in patterns {
+            filter = filter.topic(TopicPattern::parse(pattern)?);
+        }
+        Ok(self.bus.subscribe_filtered(filter))
+    }
+}
+
+#[cfg(all(test, feature = "runtime"))]
+mod tests {
+    use super::*;
+    use crate::{ErrorCategory, ErrorContext, ErrorSeverity, EventBus, FailureReason, InMemoryBus, TaskResult};
+    use chrono::Utc;
+    use std::sync::Arc;
+    use toka_types::EntityId;
+
+    #[test]
+    fn test_pattern_matching() {
+        let pattern = |p: &str| TopicPattern::parse(p).unwrap();
+        assert!(pattern("agents.*.task.failed").matches("agents.1234.task.failed"));
+        assert!(!pattern("agents.*.task.failed").matches("agents.1234.task.completed"));
+        assert!(pattern("agents.1234.**").matches("agents.1234.task.failed"));
+        assert!(pattern("agents.1234.**").matches("agents.1234"));
+        assert!(!pattern("agents.1234.**").matches("agents.12345.task.failed"));
+        assert!(pattern("**.error.*").matches("system.error.validation"));
+        assert!(pattern("**.error.*").matches("agents.7.error.resource"));
+        assert!(!pattern("*.error.*").matches("agents.7.error.resource"));
+        assert!(TopicPattern::all().matches("system.rpc.cancelled"));
+
+        assert_eq!(
+            TopicPattern::parse("agents..task"),
+            Err(TopicPatternError::EmptySegment("agents..task".to_string()))
+        );
+        assert!(matches!(TopicPattern::parse("task*"), Err(TopicPatternError::InvalidSegment { .. })));
+        let json = serde_json::to_string(&pattern("agents.*.task.*")).unwrap();
+        assert_eq!(json, "\"agents.*.task.*\"");
+        assert_eq!(serde_json::from_str::<TopicPattern>(&json).unwrap(), pattern("agents.*.task.*"));
+    }
+
+    #[tokio::test]
+    async fn test_router_subscriptions() {
+        let bus: Arc<dyn EventBus> = Arc::new(InMemoryBus::default());
+        let router = TopicRouter::new(bus.clone());
+        let mut failures = router.subscribe("agents.*.task.failed").unwrap();
+        let mut agent = router.subscribe("agents.1234.**").unwrap();
+        let mut errors = router.subscribe_any(["**.error.*", "agents.*.task.timeout"]).unwrap();
+
+        let completed = KernelEvent::TaskCompleted {
+            task_id: "t-1".to_string(),
+            agent: EntityId(1234),
+            result: TaskResult::SuccessEmpty,
+            execution_time_ms: 10,
+            correlation_id: None,
+            timestamp: Utc::now(),
+        };
+        let failed = KernelEvent::TaskFailed {
+            task_id: "t-2".to_string(),
+            agent: EntityId(99),
+            error: "boom".to_string(),
+            failure_reason: FailureReason::InvalidInput,
+            correlation_id: None,
+            timestamp: Utc::now(),
+        };
+        let system = KernelEvent::SystemError {
+            error_category: ErrorCategory::Storage,
+            error_code: "E1".to_string(),
+            context: ErrorContext {
+                component: "store".to_string(),
+                metadata: std::collections::HashMap::new(),
+            },
+            severity: ErrorSeverity::Error,
+            timestamp: Utc::now(),
+        };
+        assert_eq!(topic_of(&completed), "agents.1234.task.completed");
+        assert_eq!(topic_of(&system), "system.error.system");
+        for event in [&completed, &failed, &system] {
+            bus.publish(event).unwrap();
+        }
+
+        assert_eq!(failures.try_recv().unwrap(), failed);
+        assert!(failures.try_recv().is_err());
+        assert_eq!(agent.try_recv().unwrap(), completed);
+        assert!(agent.try_recv().is_err());
+        assert_eq!(errors.try_recv().unwrap(), system);
+        assert!(errors.try_recv().is_err());
+    }
+}
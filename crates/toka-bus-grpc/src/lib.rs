@@ -0,0 +1,169 @@
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+
+//! **toka-bus-grpc** – gRPC facade of the Toka OS event bus.
+//!
+//! [`EventStreamService`] exposes any [`EventBus`] over gRPC, so dashboards
+//! and consumers written in other languages can publish kernel events and
+//! subscribe to them. The service is described in `proto/toka_bus.proto`.
+//!
+//! - Every call is authenticated with a capability token sent in the
+//!   `authorization` metadata as `Bearer <token>`. Publishing and
+//!   subscribing each require a permission, see [`GrpcBusConfig`].
+//! - Subscriptions carry a [`Filter`](proto::Filter) applied on the server,
+//!   so clients only receive the events they asked for.
+//! - Events travel as JSON [`EventEnvelope`]s, which carry their schema
+//!   version, next to their kind and topic for clients that only route.
+//!
+//! [`EventBus`]: toka_bus_core::EventBus
+
+use serde::{Deserialize, Serialize};
+
+use toka_bus_core::{topic_of, ErrorSeverity, EventEnvelope, EventFilter, KernelEvent, TopicPattern};
+use toka_types::EntityId;
+
+/// Messages and service stubs generated from `proto/toka_bus.proto`.
+#[allow(missing_docs)]
+pub mod proto {
+    tonic::include_proto!("toka.bus.v1");
+}
+
+//─────────────────────────────
+//  gRPC service
+//─────────────────────────────
+
+/// Event bus service served over gRPC.
+pub mod server;
+
+pub use server::EventStreamService;
+
+//─────────────────────────────
+//  Configuration
+//─────────────────────────────
+
+/// Metadata key carrying the capability token.
+pub const AUTHORIZATION_METADATA: &str = "authorization";
+
+/// Permission required to publish, see [`GrpcBusConfig::default`].
+pub const DEFAULT_PUBLISH_PERMISSION: &str = "bus:publish";
+
+/// Permission required to subscribe, see [`GrpcBusConfig::default`].
+pub const DEFAULT_SUBSCRIBE_PERMISSION: &str = "bus:subscribe";
+
+/// Configuration of an [`EventStreamService`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GrpcBusConfig {
+    /// Permission a token must hold to publish events
+    pub publish_permission: String,
+    /// Permission a token must hold to subscribe to events
+    pub subscribe_permission: String,
+    /// Events buffered per subscriber while the client is slow to read
+    pub stream_buffer: usize,
+}
+
+impl Default for GrpcBusConfig {
+    fn default() -> Self {
+        Self {
+            publish_permission: DEFAULT_PUBLISH_PERMISSION.to_string(),
+            subscribe_permission: DEFAULT_SUBSCRIBE_PERMISSION.to_string(),
+            stream_buffer: 256,
+        }
+    }
+}
+
+//─────────────────────────────
+//  Wire conversions
+//─────────────────────────────
+
+/// Filter selected by a wire `filter`.
+pub fn filter_from_proto(filter: &proto::Filter) -> Result<EventFilter, String> {
+    let agents = filter
+        .agents
+        .iter()
+        .map(|agent| {
+            agent
+                .parse::<u128>()
+                .map(EntityId)
+                .map_err(|_| format!("invalid agent id {:?}", agent))
+        })
+        .collect::<Result<_, _>>()?;
+    let min_severity = filter
+        .min_severity
+        .as_deref()
+        .map(|severity| match severity {
+            "info" => Ok(ErrorSeverity::Info),
+            "warning" => Ok(ErrorSeverity::Warning),
+            "error" => Ok(ErrorSeverity::Error),
+            "critical" => Ok(ErrorSeverity::Critical),
+            other => Err(format!("unknown severity {:?}", other)),
+        })
+        .transpose()?;
+    let topics = filter
+        .topics
+        .iter()
+        .map(|pattern| TopicPattern::parse(pattern).map_err(|e| e.to_string()))
+        .collect::<Result<_, _>>()?;
+
+    Ok(EventFilter {
+        kinds: filter.kinds.clone(),
+        agents,
+        task_ids: filter.task_ids.clone(),
+        min_severity,
+        topics,
+    })
+}
+
+/// Wire form of `event`.
+pub fn event_to_proto(event: &KernelEvent) -> anyhow::Result<proto::Event> {
+    let envelope = EventEnvelope::wrap(event)?;
+    Ok(proto::Event {
+        kind: event.kind().to_string(),
+        topic: topic_of(event),
+        envelope_json: serde_json::to_string(&envelope)?,
+    })
+}
+
+/// Event carried by a wire envelope.
+pub fn event_from_envelope_json(envelope_json: &str) -> anyhow::Result<KernelEvent> {
+    let envelope: EventEnvelope = serde_json::from_str(envelope_json)?;
+    Ok(envelope.unwrap_kernel()?)
+}
+
+/// Commonly used types.
+pub mod prelude {
+    pub use super::proto::event_stream_client::EventStreamClient;
+    pub use super::{EventStreamService, GrpcBusConfig};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn test_wire_filter_and_event_round_trip() {
+        let filter = filter_from_proto(&proto::Filter {
+            kinds: vec!["task".to_string()],
+            agents: vec!["7".to_string()],
+            min_severity: Some("error".to_string()),
+            topics: vec!["agents.*.task.failed".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(filter.agents, vec![EntityId(7)]);
+        assert_eq!(filter.min_severity, Some(ErrorSeverity::Error));
+
+        let bad_agent = proto::Filter { agents: vec!["seven".to_string()], ..Default::default() };
+        assert!(filter_from_proto(&bad_agent).is_err());
+
+        let event = KernelEvent::ObservationEmitted {
+            agent: EntityId(7),
+            data: vec![1, 2, 3].into(),
+            timestamp: Utc::now(),
+        };
+        let wire = event_to_proto(&event).unwrap();
+        assert_eq!(wire.kind, "agent.observation");
+        assert_eq!(wire.topic, "agents.7.agent.observation");
+        assert_eq!(event_from_envelope_json(&wire.envelope_json).unwrap(), event);
+    }
+}
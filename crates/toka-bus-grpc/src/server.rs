@@ -0,0 +1,250 @@
+#![forbid(unsafe_code)]
+
+//! [`EventStream`] service backed by an [`EventBus`].
+//!
+//! Each call validates the caller's capability token before touching the
+//! bus. A subscription forwards the events passing its filter from a
+//! background task until the client hangs up, the bus closes or the token
+//! expires; the stream then ends, with `UNAUTHENTICATED` in the last case.
+//! Events a slow client misses because its buffer overflowed are skipped.
+
+use std::sync::Arc;
+
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+use tracing::{debug, warn};
+
+use toka_auth::{Claims, TokenValidator};
+use toka_bus_core::EventBus;
+
+use crate::proto::event_stream_server::{EventStream, EventStreamServer};
+use crate::proto::{Event, PublishRequest, PublishResponse, SubscribeRequest};
+use crate::{event_from_envelope_json, event_to_proto, filter_from_proto, GrpcBusConfig, AUTHORIZATION_METADATA};
+
+/// gRPC service publishing to and streaming from an [`EventBus`].
+pub struct EventStreamService {
+    bus: Arc<dyn EventBus>,
+    auth: Arc<dyn TokenValidator>,
+    config: GrpcBusConfig,
+}
+
+impl std::fmt::Debug for EventStreamService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventStreamService").field("config", &self.config).finish()
+    }
+}
+
+impl EventStreamService {
+    /// Serve `bus`, authenticating callers with `auth`.
+    pub fn new(bus: Arc<dyn EventBus>, auth: Arc<dyn TokenValidator>) -> Self {
+        Self { bus, auth, config: GrpcBusConfig::default() }
+    }
+
+    /// Use `config` instead of the default configuration.
+    pub fn with_config(mut self, config: GrpcBusConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Wrap the service for a `tonic` server.
+    pub fn into_server(self) -> EventStreamServer<Self> {
+        EventStreamServer::new(self)
+    }
+
+    /// Validate the caller's token and check it holds `permission`.
+    async fn authorize<T>(&self, request: &Request<T>, permission: &str) -> Result<Claims, Status> {
+        let token = request
+            .metadata()
+            .get(AUTHORIZATION_METADATA)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| Status::unauthenticated("missing bearer capability token"))?;
+        let claims = self.auth.validate(token).await.map_err(|e| {
+            warn!("Rejected gRPC bus caller: {}", e);
+            Status::unauthenticated("invalid capability token")
+        })?;
+        if claims.is_expired() {
+            return Err(Status::unauthenticated("capability token expired"));
+        }
+        if !claims.permissions.iter().any(|held| held == permission) {
+            return Err(Status::permission_denied(format!("{} requires permission {}", claims.sub, permission)));
+        }
+        Ok(claims)
+    }
+}
+
+#[tonic::async_trait]
+impl EventStream for EventStreamService {
+    async fn publish(&self, request: Request<PublishRequest>) -> Result<Response<PublishResponse>, Status> {
+        let claims = self.authorize(&request, &self.config.publish_permission).await?;
+        let event = event_from_envelope_json(&request.get_ref().envelope_json)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        self.bus
+            .publish(&event)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        debug!("{} published {} over gRPC", claims.sub, event.kind());
+        Ok(Response::new(PublishResponse {}))
+    }
+
+    type SubscribeStream = ReceiverStream<Result<Event, Status>>;
+
+    async fn subscribe(&self, request: Request<SubscribeRequest>) -> Result<Response<Self::SubscribeStream>, Status> {
+        let claims = self.authorize(&request, &self.config.subscribe_permission).await?;
+        let filter = filter_from_proto(&request.get_ref().filter.clone().unwrap_or_default())
+            .map_err(Status::invalid_argument)?;
+
+        let mut events = self.bus.subscribe_filtered(filter);
+        let (tx, rx) = mpsc::channel(self.config.stream_buffer.max(1));
+        debug!("{} subscribed over gRPC", claims.sub);
+        tokio::spawn(async move {
+            loop {
+                let event = match events.recv().await {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!("gRPC subscriber {} missed {} events", claims.sub, missed);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if claims.is_expired() {
+                    let _ = tx.send(Err(Status::unauthenticated("capability token expired"))).await;
+                    break;
+                }
+                let message = match event_to_proto(&event) {
+                    Ok(message) => message,
+                    Err(e) => {
+                        warn!("Cannot encode {} for gRPC: {}", event.kind(), e);
+                        continue;
+                    }
+                };
+                if tx.send(Ok(message)).await.is_err() {
+                    // The client hung up
+                    break;
+                }
+            }
+            debug!("gRPC subscription of {} ended", claims.sub);
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use chrono::Utc;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::{Channel, Server};
+    use tonic::Code;
+
+    use toka_bus_core::{InMemoryBus, KernelEvent};
+    use toka_types::EntityId;
+
+    use crate::proto::event_stream_client::EventStreamClient;
+    use crate::proto::Filter;
+
+    /// Accepts tokens of the form `subject:permission,permission`.
+    struct PermissionsInToken;
+
+    #[async_trait]
+    impl TokenValidator for PermissionsInToken {
+        async fn validate(&self, raw: &str) -> toka_auth::Result<Claims> {
+            let (sub, permissions) = raw.split_once(':').ok_or_else(|| toka_auth::Error::new("malformed"))?;
+            Ok(Claims {
+                sub: sub.to_string(),
+                vault: "test".to_string(),
+                permissions: permissions.split(',').map(str::to_string).collect(),
+                iat: 0,
+                exp: u64::MAX,
+                jti: "fixed".to_string(),
+            })
+        }
+    }
+
+    async fn serve(bus: Arc<dyn EventBus>) -> EventStreamClient<Channel> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let service = EventStreamService::new(bus, Arc::new(PermissionsInToken));
+        tokio::spawn(
+            Server::builder()
+                .add_service(service.into_server())
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        EventStreamClient::connect(format!("http://{}", addr)).await.unwrap()
+    }
+
+    fn authorized<T>(message: T, token: &str) -> Request<T> {
+        let mut request = Request::new(message);
+        request
+            .metadata_mut()
+            .insert(AUTHORIZATION_METADATA, format!("Bearer {}", token).parse().unwrap());
+        request
+    }
+
+    fn observation(agent: u128) -> KernelEvent {
+        KernelEvent::ObservationEmitted {
+            agent: EntityId(agent),
+            data: vec![agent as u8].into(),
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscriber_receives_only_matching_events() {
+        let bus: Arc<dyn EventBus> = Arc::new(InMemoryBus::default());
+        let mut client = serve(Arc::clone(&bus)).await;
+
+        let filter = Filter { agents: vec!["2".to_string()], ..Default::default() };
+        let mut stream = client
+            .subscribe(authorized(SubscribeRequest { filter: Some(filter) }, "dashboard:bus:subscribe"))
+            .await
+            .unwrap()
+            .into_inner();
+
+        bus.publish(&observation(1)).unwrap();
+        bus.publish(&observation(2)).unwrap();
+
+        let received = stream.message().await.unwrap().unwrap();
+        assert_eq!(received.topic, "agents.2.agent.observation");
+        assert_eq!(event_from_envelope_json(&received.envelope_json).unwrap().agent(), Some(EntityId(2)));
+    }
+
+    #[tokio::test]
+    async fn test_remote_publish_reaches_local_subscribers() {
+        let bus: Arc<dyn EventBus> = Arc::new(InMemoryBus::default());
+        let mut local = bus.subscribe();
+        let mut client = serve(Arc::clone(&bus)).await;
+
+        let event = observation(3);
+        let wire = event_to_proto(&event).unwrap();
+        client
+            .publish(authorized(PublishRequest { envelope_json: wire.envelope_json }, "agent-3:bus:publish"))
+            .await
+            .unwrap();
+
+        assert_eq!(local.recv().await.unwrap(), event);
+    }
+
+    #[tokio::test]
+    async fn test_calls_require_token_and_permission() {
+        let bus: Arc<dyn EventBus> = Arc::new(InMemoryBus::default());
+        let mut client = serve(bus).await;
+
+        let missing = client.subscribe(Request::new(SubscribeRequest::default())).await.unwrap_err();
+        assert_eq!(missing.code(), Code::Unauthenticated);
+
+        let invalid = client
+            .subscribe(authorized(SubscribeRequest::default(), "no-permissions"))
+            .await
+            .unwrap_err();
+        assert_eq!(invalid.code(), Code::Unauthenticated);
+
+        let wire = event_to_proto(&observation(1)).unwrap();
+        let denied = client
+            .publish(authorized(PublishRequest { envelope_json: wire.envelope_json }, "dashboard:bus:subscribe"))
+            .await
+            .unwrap_err();
+        assert_eq!(denied.code(), Code::PermissionDenied);
+    }
+}
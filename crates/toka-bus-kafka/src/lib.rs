@@ -0,0 +1,187 @@
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+
+//! **toka-bus-kafka** – Kafka/Redpanda sink for Toka OS kernel events.
+//!
+//! Analytics pipelines usually consume from Kafka rather than from an
+//! in-process bus. This crate mirrors every published [`KernelEvent`] into a
+//! Kafka topic (Redpanda speaks the same protocol):
+//!
+//! - [`KafkaSink`] sends single events as JSON and awaits the broker's
+//!   delivery confirmation, returning the partition and offset.
+//! - [`KafkaMirrorBus`] wraps any [`EventBus`](toka_bus_core::EventBus):
+//!   publishing goes to the wrapped bus as usual and each published event is
+//!   mirrored to Kafka in the background, counting confirmed and failed
+//!   deliveries.
+//!
+//! Records are keyed by the agent `EntityId` of the
+//! event by default, so all events of one agent land on the same partition
+//! and stay ordered; see [`PartitionStrategy`] for the alternatives.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use toka_bus_core::KernelEvent;
+
+//─────────────────────────────
+//  Kafka sink
+//─────────────────────────────
+
+/// Producer sending kernel events to Kafka.
+pub mod sink;
+
+pub use sink::{DeliveryReceipt, KafkaMirrorBus, KafkaSink, MirrorStats};
+
+//─────────────────────────────
+//  Configuration
+//─────────────────────────────
+
+/// Record key of events that do not concern an agent under
+/// [`PartitionStrategy::ByAgent`].
+pub const SYSTEM_KEY: &str = "system";
+
+/// How records are spread over the topic's partitions.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum PartitionStrategy {
+    /// Key records by the agent the event concerns, [`SYSTEM_KEY`] for
+    /// events without one
+    #[default]
+    ByAgent,
+    /// Key records by event kind, e.g. `task.completed`
+    ByKind,
+    /// No key; the producer spreads records over all partitions
+    Unkeyed,
+    /// Send every record to one partition, preserving global order
+    Fixed {
+        /// Target partition
+        partition: i32,
+    },
+}
+
+impl PartitionStrategy {
+    /// Record key of `event`, if the strategy uses keys.
+    pub fn key(&self, event: &KernelEvent) -> Option<String> {
+        match self {
+            PartitionStrategy::ByAgent => Some(
+                event
+                    .agent()
+                    .map(|agent| agent.0.to_string())
+                    .unwrap_or_else(|| SYSTEM_KEY.to_string()),
+            ),
+            PartitionStrategy::ByKind => Some(event.kind().to_string()),
+            PartitionStrategy::Unkeyed | PartitionStrategy::Fixed { .. } => None,
+        }
+    }
+
+    /// Explicit partition of every record, if the strategy fixes one.
+    pub fn partition(&self) -> Option<i32> {
+        match self {
+            PartitionStrategy::Fixed { partition } => Some(*partition),
+            _ => None,
+        }
+    }
+}
+
+/// Broker acknowledgement required before a delivery is confirmed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Acks {
+    /// Confirm once the partition leader wrote the record
+    Leader,
+    /// Confirm once all in-sync replicas wrote the record
+    #[default]
+    All,
+}
+
+/// Configuration of a [`KafkaSink`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KafkaSinkConfig {
+    /// Comma-separated bootstrap servers, e.g. `localhost:9092`
+    pub brokers: String,
+    /// Topic events are written to
+    pub topic: String,
+    /// How records are assigned to partitions
+    #[serde(default)]
+    pub partitioning: PartitionStrategy,
+    /// Acknowledgement required for a confirmed delivery
+    #[serde(default)]
+    pub acks: Acks,
+    /// Time allowed for a delivery, including retries
+    #[serde(default = "default_delivery_timeout")]
+    pub delivery_timeout: Duration,
+    /// Events a [`KafkaMirrorBus`] queues before dropping mirrored events
+    #[serde(default = "default_capacity")]
+    pub capacity: usize,
+    /// Additional librdkafka producer properties, e.g. `compression.type`
+    #[serde(default)]
+    pub producer_properties: BTreeMap<String, String>,
+}
+
+fn default_delivery_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_capacity() -> usize {
+    1024
+}
+
+impl KafkaSinkConfig {
+    /// Write to `topic` on `brokers`, keeping the other defaults.
+    pub fn new(brokers: impl Into<String>, topic: impl Into<String>) -> Self {
+        Self {
+            brokers: brokers.into(),
+            topic: topic.into(),
+            partitioning: PartitionStrategy::default(),
+            acks: Acks::default(),
+            delivery_timeout: default_delivery_timeout(),
+            capacity: default_capacity(),
+            producer_properties: BTreeMap::new(),
+        }
+    }
+
+    /// Use `partitioning` to assign records to partitions.
+    pub fn with_partitioning(mut self, partitioning: PartitionStrategy) -> Self {
+        self.partitioning = partitioning;
+        self
+    }
+}
+
+/// Commonly used types.
+pub mod prelude {
+    pub use super::{Acks, KafkaMirrorBus, KafkaSink, KafkaSinkConfig, PartitionStrategy};
+    pub use toka_bus_core::EventBus;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use toka_types::EntityId;
+
+    #[test]
+    fn test_partition_keys() {
+        let observation = KernelEvent::ObservationEmitted {
+            agent: EntityId(42),
+            data: vec![1].into(),
+            timestamp: Utc::now(),
+        };
+        let report = KernelEvent::ReportGenerated {
+            report_id: "report-1".to_string(),
+            agent: None,
+            workstream: "storage".to_string(),
+            content: "ok".to_string(),
+            timestamp: Utc::now(),
+        };
+
+        assert_eq!(PartitionStrategy::ByAgent.key(&observation).as_deref(), Some("42"));
+        assert_eq!(PartitionStrategy::ByAgent.key(&report).as_deref(), Some(SYSTEM_KEY));
+        assert_eq!(PartitionStrategy::ByKind.key(&observation).as_deref(), Some("agent.observation"));
+        assert_eq!(PartitionStrategy::Unkeyed.key(&observation), None);
+
+        let fixed = PartitionStrategy::Fixed { partition: 3 };
+        assert_eq!((fixed.key(&observation), fixed.partition()), (None, Some(3)));
+    }
+}
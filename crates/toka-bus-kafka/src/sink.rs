@@ -0,0 +1,241 @@
+#![forbid(unsafe_code)]
+
+//! Kafka producer for kernel events.
+//!
+//! Each record carries the event as JSON in its value and the event kind in
+//! a `toka-kind` header, so consumers can filter without decoding. A record
+//! counts as delivered once the broker acknowledged it as configured by
+//! [`Acks`](crate::Acks).
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use rdkafka::config::ClientConfig;
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use toka_bus_core::{EventBus, EventFilter, FilteredReceiver, KernelEvent};
+
+use crate::{Acks, KafkaSinkConfig};
+
+/// Header carrying the event kind of a record.
+pub const KIND_HEADER: &str = "toka-kind";
+
+/// Broker confirmation of a delivered event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeliveryReceipt {
+    /// Partition the record was written to
+    pub partition: i32,
+    /// Offset of the record within the partition
+    pub offset: i64,
+}
+
+/// Producer writing kernel events to the configured topic.
+#[derive(Clone)]
+pub struct KafkaSink {
+    config: KafkaSinkConfig,
+    producer: FutureProducer,
+}
+
+impl std::fmt::Debug for KafkaSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KafkaSink").field("config", &self.config).finish()
+    }
+}
+
+impl KafkaSink {
+    /// Create a producer for `config`.
+    ///
+    /// Brokers are contacted lazily, so an unreachable cluster only shows up
+    /// as failed deliveries.
+    pub fn new(config: KafkaSinkConfig) -> Result<Self> {
+        let mut client = ClientConfig::new();
+        client
+            .set("bootstrap.servers", &config.brokers)
+            .set("message.timeout.ms", config.delivery_timeout.as_millis().to_string())
+            .set(
+                "acks",
+                match config.acks {
+                    Acks::Leader => "1",
+                    Acks::All => "all",
+                },
+            );
+        for (key, value) in &config.producer_properties {
+            client.set(key, value);
+        }
+        let producer = client.create().context("failed to create Kafka producer")?;
+        Ok(Self { config, producer })
+    }
+
+    /// Configuration of the sink.
+    pub fn config(&self) -> &KafkaSinkConfig {
+        &self.config
+    }
+
+    /// Send `event` and wait for the broker to confirm it.
+    pub async fn send(&self, event: &KernelEvent) -> Result<DeliveryReceipt> {
+        let payload = serde_json::to_vec(event)?;
+        let key = self.config.partitioning.key(event);
+        let headers = OwnedHeaders::new().insert(Header {
+            key: KIND_HEADER,
+            value: Some(event.kind()),
+        });
+
+        let mut record = FutureRecord::<str, [u8]>::to(&self.config.topic)
+            .payload(&payload)
+            .headers(headers);
+        if let Some(key) = &key {
+            record = record.key(key.as_str());
+        }
+        if let Some(partition) = self.config.partitioning.partition() {
+            record = record.partition(partition);
+        }
+
+        let (partition, offset) = self
+            .producer
+            .send(record, self.config.delivery_timeout)
+            .await
+            .map_err(|(e, _)| e)
+            .with_context(|| format!("failed to deliver {} event to {}", event.kind(), self.config.topic))?;
+        Ok(DeliveryReceipt { partition, offset })
+    }
+}
+
+/// Delivery counters of a [`KafkaMirrorBus`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MirrorStats {
+    /// Events confirmed by the broker
+    pub delivered: u64,
+    /// Events the broker did not confirm in time
+    pub failed: u64,
+    /// Events not mirrored because the queue was full
+    pub dropped: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    delivered: AtomicU64,
+    failed: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// Event bus wrapper mirroring every published event to Kafka.
+///
+/// Publishing, validation and subscriptions are handled by the wrapped bus;
+/// only events it accepted are mirrored. Mirroring happens in order on a
+/// background task, so a slow cluster never blocks publishers. When more
+/// than `capacity` events wait for delivery, further events are dropped
+/// from the mirror and counted in [`MirrorStats::dropped`].
+pub struct KafkaMirrorBus<B> {
+    inner: B,
+    outgoing: mpsc::Sender<KernelEvent>,
+    counters: Arc<Counters>,
+    mirror: JoinHandle<()>,
+}
+
+impl<B: EventBus> KafkaMirrorBus<B> {
+    /// Mirror the events published on `inner` through `sink`.
+    pub fn new(inner: B, sink: KafkaSink) -> Self {
+        let counters = Arc::new(Counters::default());
+        let (outgoing, mut queued) = mpsc::channel::<KernelEvent>(sink.config().capacity.max(1));
+        let mirror = {
+            let counters = counters.clone();
+            tokio::spawn(async move {
+                while let Some(event) = queued.recv().await {
+                    match sink.send(&event).await {
+                        Ok(_) => {
+                            counters.delivered.fetch_add(1, Ordering::Relaxed);
+                        }
+                        Err(e) => {
+                            warn!("Failed to mirror event to Kafka: {:#}", e);
+                            counters.failed.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                }
+            })
+        };
+        Self {
+            inner,
+            outgoing,
+            counters,
+            mirror,
+        }
+    }
+
+    /// The wrapped bus.
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    /// Delivery counters since the mirror started.
+    pub fn stats(&self) -> MirrorStats {
+        MirrorStats {
+            delivered: self.counters.delivered.load(Ordering::Relaxed),
+            failed: self.counters.failed.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl<B: EventBus> EventBus for KafkaMirrorBus<B> {
+    fn publish(&self, event: &KernelEvent) -> Result<()> {
+        self.inner.publish(event)?;
+        if let Err(e) = self.outgoing.try_send(event.clone()) {
+            warn!("Not mirroring {} event to Kafka: {}", event.kind(), e);
+            self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<KernelEvent> {
+        self.inner.subscribe()
+    }
+
+    fn subscribe_filtered(&self, filter: EventFilter) -> FilteredReceiver {
+        self.inner.subscribe_filtered(filter)
+    }
+}
+
+impl<B> Drop for KafkaMirrorBus<B> {
+    fn drop(&mut self) {
+        self.mirror.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::Utc;
+    use toka_bus_core::InMemoryBus;
+    use toka_types::EntityId;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unreachable_cluster_counts_failed_deliveries() {
+        let mut config = KafkaSinkConfig::new("127.0.0.1:1", "toka-events");
+        config.delivery_timeout = Duration::from_millis(200);
+        let bus = KafkaMirrorBus::new(InMemoryBus::default(), KafkaSink::new(config).unwrap());
+        let mut rx = bus.subscribe();
+
+        let event = KernelEvent::ObservationEmitted {
+            agent: EntityId(1),
+            data: vec![7].into(),
+            timestamp: Utc::now(),
+        };
+        bus.publish(&event).unwrap();
+        // Local delivery does not wait for Kafka
+        assert_eq!(rx.recv().await.unwrap(), event);
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(30);
+        while bus.stats().failed == 0 && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(bus.stats(), MirrorStats { delivered: 0, failed: 1, dropped: 0 });
+    }
+}
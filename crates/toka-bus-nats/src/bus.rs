@@ -0,0 +1,261 @@
+#![forbid(unsafe_code)]
+
+//! [`EventBus`] implementation bridged over NATS.
+//!
+//! Publishing is synchronous in [`EventBus`], so [`NatsBus::publish`] hands
+//! events to the embedded [`InMemoryBus`] for local subscribers and queues
+//! them for a background task that forwards them to NATS. A second task
+//! feeds events published by other processes into the local bus. Events the
+//! bus published itself carry its [`ORIGIN_HEADER`] and are not delivered a
+//! second time when NATS echoes them back.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use toka_bus_core::{EventBus, EventFilter, FilteredReceiver, InMemoryBus, KernelEvent};
+
+use crate::{NatsBusConfig, Serialization, CONTENT_TYPE_HEADER, ORIGIN_HEADER};
+
+/// Traffic counters of a [`NatsBus`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NatsBusStats {
+    /// Events forwarded to NATS
+    pub published_remote: u64,
+    /// Events received from other processes
+    pub received_remote: u64,
+    /// Events delivered locally only, because the server was unreachable or
+    /// the outgoing queue was full
+    pub local_only: u64,
+    /// Messages from NATS that could not be decoded or failed validation
+    pub rejected: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    published_remote: AtomicU64,
+    received_remote: AtomicU64,
+    local_only: AtomicU64,
+    rejected: AtomicU64,
+}
+
+/// Event bus distributing kernel events over NATS.
+///
+/// Local subscribers see every event published in this process immediately,
+/// whether or not the server is reachable, plus the events published by
+/// other processes on the same subjects.
+pub struct NatsBus {
+    config: NatsBusConfig,
+    local: InMemoryBus,
+    client: async_nats::Client,
+    outgoing: mpsc::Sender<KernelEvent>,
+    counters: Arc<Counters>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl std::fmt::Debug for NatsBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NatsBus")
+            .field("config", &self.config)
+            .field("connected", &self.is_connected())
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl NatsBus {
+    /// Connect to the server named in `config`.
+    ///
+    /// An unreachable server is not an error: the bus starts in local-only
+    /// mode and keeps reconnecting in the background. Only an invalid
+    /// configuration, such as a malformed URL, fails.
+    pub async fn connect(config: NatsBusConfig) -> Result<Self> {
+        let client = async_nats::ConnectOptions::new()
+            .connection_timeout(config.connect_timeout)
+            .retry_on_initial_connect()
+            .event_callback(|event| async move {
+                match event {
+                    async_nats::Event::Connected => info!("Connected to NATS"),
+                    async_nats::Event::Disconnected => warn!("Disconnected from NATS, delivering events locally"),
+                    other => debug!("NATS event: {}", other),
+                }
+            })
+            .connect(config.url.as_str())
+            .await
+            .with_context(|| format!("invalid NATS server address {}", config.url))?;
+
+        let mut subscriber = client
+            .subscribe(config.wildcard_subject())
+            .await
+            .context("failed to subscribe to NATS subjects")?;
+
+        let local = InMemoryBus::new(config.capacity);
+        let counters = Arc::new(Counters::default());
+        let origin = Uuid::new_v4().to_string();
+        let (outgoing, mut queued) = mpsc::channel::<KernelEvent>(config.capacity.max(1));
+
+        let forwarder = {
+            let client = client.clone();
+            let config = config.clone();
+            let counters = counters.clone();
+            let origin = origin.clone();
+            tokio::spawn(async move {
+                while let Some(event) = queued.recv().await {
+                    if client.connection_state() != async_nats::connection::State::Connected {
+                        counters.local_only.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+                    match forward(&client, &config, &origin, &event).await {
+                        Ok(()) => {
+                            counters.published_remote.fetch_add(1, Ordering::Relaxed);
+                        }
+                        Err(e) => {
+                            warn!("Failed to forward {} event to NATS: {:#}", event.kind(), e);
+                            counters.local_only.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                }
+            })
+        };
+
+        let receiver = {
+            let local = local.clone();
+            let default_format = config.serialization;
+            let counters = counters.clone();
+            tokio::spawn(async move {
+                while let Some(message) = subscriber.next().await {
+                    let headers = message.headers.as_ref();
+                    let header = |name: &str| headers.and_then(|h| h.get(name)).map(|v| v.as_str().to_string());
+                    if header(ORIGIN_HEADER).as_deref() == Some(origin.as_str()) {
+                        continue;
+                    }
+                    let format = header(CONTENT_TYPE_HEADER)
+                        .and_then(|content_type| Serialization::from_content_type(&content_type))
+                        .unwrap_or(default_format);
+                    let delivered = format
+                        .decode(&message.payload)
+                        .and_then(|event| local.publish(&event));
+                    match delivered {
+                        Ok(()) => {
+                            counters.received_remote.fetch_add(1, Ordering::Relaxed);
+                        }
+                        Err(e) => {
+                            warn!("Rejected event from NATS subject {}: {:#}", message.subject, e);
+                            counters.rejected.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                }
+            })
+        };
+
+        Ok(Self {
+            config,
+            local,
+            client,
+            outgoing,
+            counters,
+            tasks: vec![forwarder, receiver],
+        })
+    }
+
+    /// Configuration the bus was connected with.
+    pub fn config(&self) -> &NatsBusConfig {
+        &self.config
+    }
+
+    /// Whether the server is currently reachable.
+    pub fn is_connected(&self) -> bool {
+        self.client.connection_state() == async_nats::connection::State::Connected
+    }
+
+    /// Traffic counters since the bus was connected.
+    pub fn stats(&self) -> NatsBusStats {
+        NatsBusStats {
+            published_remote: self.counters.published_remote.load(Ordering::Relaxed),
+            received_remote: self.counters.received_remote.load(Ordering::Relaxed),
+            local_only: self.counters.local_only.load(Ordering::Relaxed),
+            rejected: self.counters.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Publish `event` on its family subject.
+async fn forward(client: &async_nats::Client, config: &NatsBusConfig, origin: &str, event: &KernelEvent) -> Result<()> {
+    let payload = config.serialization.encode(event)?;
+    let mut headers = async_nats::HeaderMap::new();
+    headers.insert(ORIGIN_HEADER, origin);
+    headers.insert(CONTENT_TYPE_HEADER, config.serialization.content_type());
+    client
+        .publish_with_headers(config.subject_for(event), headers, payload.into())
+        .await?;
+    Ok(())
+}
+
+impl EventBus for NatsBus {
+    fn publish(&self, event: &KernelEvent) -> Result<()> {
+        // Validates the event and serves local subscribers
+        self.local.publish(event)?;
+
+        if let Err(e) = self.outgoing.try_send(event.clone()) {
+            warn!("Delivering {} event locally only: {}", event.kind(), e);
+            self.counters.local_only.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<KernelEvent> {
+        self.local.subscribe()
+    }
+
+    fn subscribe_filtered(&self, filter: EventFilter) -> FilteredReceiver {
+        self.local.subscribe_filtered(filter)
+    }
+}
+
+impl Drop for NatsBus {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::Utc;
+    use toka_types::EntityId;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unreachable_server_falls_back_to_local_delivery() {
+        let config = NatsBusConfig {
+            connect_timeout: Duration::from_millis(100),
+            ..NatsBusConfig::new("nats://127.0.0.1:1")
+        };
+        let bus = NatsBus::connect(config).await.unwrap();
+        assert!(!bus.is_connected());
+
+        let mut rx = bus.subscribe();
+        let event = KernelEvent::ObservationEmitted {
+            agent: EntityId(1),
+            data: vec![42].into(),
+            timestamp: Utc::now(),
+        };
+        bus.publish(&event).unwrap();
+        assert_eq!(rx.recv().await.unwrap(), event);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let stats = bus.stats();
+        assert_eq!(stats.local_only, 1);
+        assert_eq!(stats.published_remote, 0);
+    }
+}
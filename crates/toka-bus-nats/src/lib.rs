@@ -0,0 +1,174 @@
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+
+//! **toka-bus-nats** – NATS bridge for the Toka OS event bus.
+//!
+//! The [`InMemoryBus`](toka_bus_core::InMemoryBus) only reaches subscribers
+//! in the same process. [`NatsBus`] implements [`EventBus`] on top of a NATS
+//! server so that kernel events published in one process reach subscribers
+//! in every other process connected to the same server.
+//!
+//! - Each event family (the first segment of [`KernelEvent::kind`]) gets a
+//!   subject of its own, e.g. `toka.events.task`, so remote consumers can
+//!   subscribe to just the families they need.
+//! - Events travel as JSON or MessagePack, see [`Serialization`]. The format
+//!   is announced in a header, so processes using different formats can
+//!   share a server.
+//! - Local subscribers are always served by an embedded `InMemoryBus`. While
+//!   the server is unreachable, events keep flowing locally and the client
+//!   reconnects in the background; events published meanwhile are not
+//!   forwarded.
+//!
+//! [`EventBus`]: toka_bus_core::EventBus
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use toka_bus_core::KernelEvent;
+
+//─────────────────────────────
+//  NATS bus
+//─────────────────────────────
+
+/// Event bus bridged over NATS.
+pub mod bus;
+
+pub use bus::{NatsBus, NatsBusStats};
+
+//─────────────────────────────
+//  Configuration
+//─────────────────────────────
+
+/// Subject prefix used by [`NatsBusConfig::default`].
+pub const DEFAULT_SUBJECT_PREFIX: &str = "toka.events";
+
+/// Header naming the process that published an event.
+pub const ORIGIN_HEADER: &str = "Toka-Origin";
+
+/// Header naming the [`Serialization`] of an event.
+pub const CONTENT_TYPE_HEADER: &str = "Content-Type";
+
+/// Wire format of events on NATS.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Serialization {
+    /// JSON, readable with the `nats` CLI
+    #[default]
+    Json,
+    /// MessagePack, more compact
+    MsgPack,
+}
+
+impl Serialization {
+    /// MIME type announced in the [`CONTENT_TYPE_HEADER`].
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Serialization::Json => "application/json",
+            Serialization::MsgPack => "application/msgpack",
+        }
+    }
+
+    /// Format announced by `content_type`, if it is known.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        match content_type {
+            "application/json" => Some(Serialization::Json),
+            "application/msgpack" => Some(Serialization::MsgPack),
+            _ => None,
+        }
+    }
+
+    /// Serialize `event` in this format.
+    pub fn encode(&self, event: &KernelEvent) -> anyhow::Result<Vec<u8>> {
+        Ok(match self {
+            Serialization::Json => serde_json::to_vec(event)?,
+            Serialization::MsgPack => rmp_serde::to_vec_named(event)?,
+        })
+    }
+
+    /// Deserialize an event in this format.
+    pub fn decode(&self, bytes: &[u8]) -> anyhow::Result<KernelEvent> {
+        Ok(match self {
+            Serialization::Json => serde_json::from_slice(bytes)?,
+            Serialization::MsgPack => rmp_serde::from_slice(bytes)?,
+        })
+    }
+}
+
+/// Configuration of a [`NatsBus`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NatsBusConfig {
+    /// Server URL, e.g. `nats://localhost:4222`
+    pub url: String,
+    /// Prefix of the per-family subjects
+    pub subject_prefix: String,
+    /// Format of published events
+    pub serialization: Serialization,
+    /// Buffer size of local subscribers and of the outgoing queue
+    pub capacity: usize,
+    /// Time allowed for each connection attempt
+    pub connect_timeout: Duration,
+}
+
+impl Default for NatsBusConfig {
+    fn default() -> Self {
+        Self {
+            url: "nats://localhost:4222".to_string(),
+            subject_prefix: DEFAULT_SUBJECT_PREFIX.to_string(),
+            serialization: Serialization::default(),
+            capacity: 1024,
+            connect_timeout: Duration::from_secs(2),
+        }
+    }
+}
+
+impl NatsBusConfig {
+    /// Connect to the server at `url`, keeping the other defaults.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Subject `event` is published on.
+    pub fn subject_for(&self, event: &KernelEvent) -> String {
+        let family = event.kind().split('.').next().unwrap_or_default();
+        format!("{}.{}", self.subject_prefix, family)
+    }
+
+    /// Wildcard subject matching every event family.
+    pub fn wildcard_subject(&self) -> String {
+        format!("{}.>", self.subject_prefix)
+    }
+}
+
+/// Commonly used types.
+pub mod prelude {
+    pub use super::{NatsBus, NatsBusConfig, NatsBusStats, Serialization};
+    pub use toka_bus_core::EventBus;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use toka_types::EntityId;
+
+    #[test]
+    fn test_subjects_and_codecs() {
+        let config = NatsBusConfig::default();
+        let event = KernelEvent::ObservationEmitted {
+            agent: EntityId(7),
+            data: vec![1, 2, 3].into(),
+            timestamp: Utc::now(),
+        };
+        assert_eq!(config.subject_for(&event), "toka.events.agent");
+        assert_eq!(config.wildcard_subject(), "toka.events.>");
+
+        for serialization in [Serialization::Json, Serialization::MsgPack] {
+            let bytes = serialization.encode(&event).unwrap();
+            assert_eq!(serialization.decode(&bytes).unwrap(), event);
+            assert_eq!(Serialization::from_content_type(serialization.content_type()), Some(serialization));
+        }
+    }
+}
@@ -0,0 +1,394 @@
+#![forbid(unsafe_code)]
+
+//! [`EventBus`] that persists events before broadcasting them.
+//!
+//! Every event becomes one stored event of kind [`BUS_EVENT_KIND`] under the
+//! stream's intent (see [`stream_intent`]), committed in its own WAL
+//! transaction and chained to the previous one as its causal parent. Only
+//! once the transaction has committed is the event handed to subscribers,
+//! so anything a subscriber sees can be replayed later.
+//!
+//! [`EventBus::publish`] cannot wait for storage, so it queues the event for
+//! a background writer and returns. Use [`PersistentBus::publish_durable`]
+//! when the caller must know that the event was persisted.
+
+use std::sync::Arc;
+
+use futures::stream::{self, BoxStream};
+use futures::{future, StreamExt, TryStreamExt};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc, oneshot, Mutex};
+use tracing::warn;
+
+use toka_bus_core::{BusError, EventBus, KernelEvent};
+use toka_store_core::{
+    create_event_header, decode_payload, CausalDigest, EventHeader, IntentId, QueryableStorage,
+    StorageError, WalStorageBackend,
+};
+
+use crate::consumer::DurableConsumer;
+use crate::{stream_intent, PersistedEvent, BUS_EVENT_KIND, DEFAULT_STREAM};
+
+/// Replayed and then live events of a stream, in sequence order.
+///
+/// If the subscriber falls so far behind that the live buffer overflows,
+/// the stream yields an error; resubscribe from the last processed sequence
+/// to recover.
+pub type DurableStream = BoxStream<'static, anyhow::Result<PersistedEvent>>;
+
+/// Settings of a [`PersistentBus`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PersistentBusConfig {
+    /// Name of the stream the events are recorded under
+    pub stream: String,
+    /// Events buffered for slow live subscribers
+    pub capacity: usize,
+}
+
+impl Default for PersistentBusConfig {
+    fn default() -> Self {
+        Self {
+            stream: DEFAULT_STREAM.to_string(),
+            capacity: 1024,
+        }
+    }
+}
+
+/// Work for the background writer.
+enum Command {
+    Publish(KernelEvent),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Position of the stream's writer.
+struct Writer {
+    next_sequence: u64,
+    head: Option<EventHeader>,
+}
+
+/// State shared by the bus, its background writer and its consumers.
+pub(crate) struct Shared<S: ?Sized> {
+    pub(crate) store: Arc<S>,
+    pub(crate) stream: String,
+    intent: IntentId,
+    writer: Mutex<Writer>,
+    live: broadcast::Sender<KernelEvent>,
+    durable: broadcast::Sender<PersistedEvent>,
+}
+
+impl<S> Shared<S>
+where
+    S: WalStorageBackend + QueryableStorage + ?Sized,
+{
+    /// Persist `event` as the next one of the stream, then broadcast it.
+    async fn persist(&self, event: &KernelEvent) -> anyhow::Result<PersistedEvent> {
+        let mut writer = self.writer.lock().await;
+        let record = PersistedEvent {
+            sequence: writer.next_sequence,
+            event: event.clone(),
+            persisted_at: chrono::Utc::now(),
+        };
+        let header = create_event_header(writer.head.as_slice(), self.intent, BUS_EVENT_KIND.to_string(), &record)
+            .map_err(|e| StorageError::SerializationFailed(e.to_string()))?;
+        let payload = rmp_serde::to_vec_named(&record)
+            .map_err(|e| StorageError::SerializationFailed(e.to_string()))?;
+
+        let transaction = self.store.begin_transaction().await?;
+        if let Err(e) = self.store.commit_with_wal(transaction, &header, &payload).await {
+            let _ = self.store.rollback_transaction(transaction).await;
+            return Err(e);
+        }
+        self.store.commit_transaction(transaction).await?;
+
+        writer.next_sequence += 1;
+        writer.head = Some(header);
+        // Broadcast under the writer lock so subscribers see sequence order.
+        // Nobody listening is not an error.
+        let _ = self.durable.send(record.clone());
+        let _ = self.live.send(record.event.clone());
+        Ok(record)
+    }
+
+    /// Stored events with a sequence of at least `from`.
+    pub(crate) async fn replay(&self, from: u64) -> anyhow::Result<Vec<PersistedEvent>> {
+        let records = read_stream(self.store.as_ref(), &self.intent).await?;
+        Ok(records
+            .into_iter()
+            .map(|(_, record)| record)
+            .filter(|record| record.sequence >= from)
+            .collect())
+    }
+
+    /// Replay from `from`, then follow the live stream.
+    pub(crate) async fn subscribe_from(&self, from: u64) -> anyhow::Result<DurableStream> {
+        // Subscribe before reading the history so nothing persisted in
+        // between is missed; whatever shows up in both is filtered below
+        let receiver = self.durable.subscribe();
+        let history = self.replay(from).await?;
+        let next = history.last().map_or(from, |record| record.sequence + 1);
+
+        let live = stream::unfold(receiver, |mut receiver| async move {
+            match receiver.recv().await {
+                Ok(record) => Some((Ok(record), receiver)),
+                Err(RecvError::Lagged(skipped)) => Some((
+                    Err(anyhow::anyhow!("Durable subscriber lagged behind by {} events", skipped)),
+                    receiver,
+                )),
+                Err(RecvError::Closed) => None,
+            }
+        })
+        .try_filter(move |record| future::ready(record.sequence >= next));
+
+        Ok(stream::iter(history.into_iter().map(Ok)).chain(live).boxed())
+    }
+}
+
+/// Event bus recording every event in a storage backend before broadcasting.
+///
+/// The backend needs a write-ahead log for durable commits and queries to
+/// replay the stream. Must be created inside a Tokio runtime, which runs
+/// the background writer behind [`EventBus::publish`].
+pub struct PersistentBus<S: ?Sized> {
+    shared: Arc<Shared<S>>,
+    queue: mpsc::UnboundedSender<Command>,
+}
+
+impl<S> PersistentBus<S>
+where
+    S: WalStorageBackend + QueryableStorage + ?Sized + 'static,
+{
+    /// Open the stream of `config` in `store`, continuing after its last
+    /// stored event.
+    pub async fn open(store: Arc<S>, config: PersistentBusConfig) -> anyhow::Result<Self> {
+        let intent = stream_intent(&config.stream);
+        let writer = match read_last_record(store.as_ref(), &intent).await? {
+            Some((header, record)) => Writer {
+                next_sequence: record.sequence + 1,
+                head: Some(header),
+            },
+            None => Writer {
+                next_sequence: 1,
+                head: None,
+            },
+        };
+
+        let (live, _) = broadcast::channel(config.capacity);
+        let (durable, _) = broadcast::channel(config.capacity);
+        let shared = Arc::new(Shared {
+            store,
+            stream: config.stream,
+            intent,
+            writer: Mutex::new(writer),
+            live,
+            durable,
+        });
+
+        let (queue, mut commands) = mpsc::unbounded_channel();
+        let background = shared.clone();
+        tokio::spawn(async move {
+            while let Some(command) = commands.recv().await {
+                match command {
+                    Command::Publish(event) => {
+                        if let Err(e) = background.persist(&event).await {
+                            warn!("Dropping {} event on stream {}: {}", event.kind(), background.stream, e);
+                        }
+                    }
+                    Command::Flush(done) => {
+                        let _ = done.send(());
+                    }
+                }
+            }
+        });
+
+        Ok(Self { shared, queue })
+    }
+
+    /// Name of the stream events are recorded under.
+    pub fn stream(&self) -> &str {
+        &self.shared.stream
+    }
+
+    /// Sequence of the last persisted event, 0 if there is none.
+    pub async fn last_sequence(&self) -> u64 {
+        self.shared.writer.lock().await.next_sequence - 1
+    }
+
+    /// Persist and broadcast `event`, returning once it is durable.
+    pub async fn publish_durable(&self, event: &KernelEvent) -> anyhow::Result<PersistedEvent> {
+        event.validate().map_err(BusError::PublishFailed)?;
+        self.shared.persist(event).await
+    }
+
+    /// Wait until every event queued by [`EventBus::publish`] so far has
+    /// been persisted or dropped.
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        let (done, wait) = oneshot::channel();
+        self.queue
+            .send(Command::Flush(done))
+            .map_err(|_| anyhow::anyhow!("Writer of stream {} stopped", self.shared.stream))?;
+        wait.await?;
+        Ok(())
+    }
+
+    /// Stored events with a sequence of at least `from`.
+    pub async fn replay(&self, from: u64) -> anyhow::Result<Vec<PersistedEvent>> {
+        self.shared.replay(from).await
+    }
+
+    /// Replay stored events from the sequence `from`, then continue with
+    /// live events.
+    pub async fn subscribe_from(&self, from: u64) -> anyhow::Result<DurableStream> {
+        self.shared.subscribe_from(from).await
+    }
+
+    /// Open the durable consumer `name`, resuming after its last
+    /// acknowledged event.
+    pub async fn consumer(&self, name: impl Into<String>) -> anyhow::Result<DurableConsumer<S>> {
+        DurableConsumer::open(self.shared.clone(), name.into()).await
+    }
+}
+
+impl<S> EventBus for PersistentBus<S>
+where
+    S: WalStorageBackend + QueryableStorage + ?Sized + 'static,
+{
+    /// Queue `event` for persistence; it is broadcast once durable.
+    ///
+    /// Events published this way are persisted in publication order. A
+    /// storage failure drops the event and is logged.
+    fn publish(&self, event: &KernelEvent) -> anyhow::Result<()> {
+        event.validate().map_err(BusError::PublishFailed)?;
+        self.queue
+            .send(Command::Publish(event.clone()))
+            .map_err(|_| BusError::PublishFailed(format!("writer of stream {} stopped", self.shared.stream)))?;
+        Ok(())
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<KernelEvent> {
+        self.shared.live.subscribe()
+    }
+}
+
+/// Stored events of the stream `intent` with their headers, by sequence.
+async fn read_stream<S>(store: &S, intent: &IntentId) -> anyhow::Result<Vec<(EventHeader, PersistedEvent)>>
+where
+    S: QueryableStorage + ?Sized,
+{
+    let headers: Vec<EventHeader> = store
+        .events_by_intent(intent)
+        .await?
+        .try_filter(|header| future::ready(header.kind == BUS_EVENT_KIND))
+        .try_collect()
+        .await?;
+    let digests: Vec<CausalDigest> = headers.iter().map(|header| header.digest).collect();
+    let payloads = store.payloads_bulk(&digests).await?;
+
+    let mut records = Vec::with_capacity(headers.len());
+    for header in headers {
+        let bytes = payloads
+            .get(&header.digest)
+            .ok_or_else(|| StorageError::EventNotFound(format!("bus record {}", header.id)))?;
+        let record: PersistedEvent = decode_payload(&header, bytes)?;
+        records.push((header, record));
+    }
+    records.sort_by_key(|(_, record)| record.sequence);
+    Ok(records)
+}
+
+/// Last stored event of the stream `intent`, decoding only the newest records.
+async fn read_last_record<S>(store: &S, intent: &IntentId) -> anyhow::Result<Option<(EventHeader, PersistedEvent)>>
+where
+    S: QueryableStorage + ?Sized,
+{
+    // Headers come oldest first; only those sharing the newest timestamp can
+    // hold the last sequence
+    let mut newest: Vec<EventHeader> = Vec::new();
+    let mut headers = store
+        .events_by_intent(intent)
+        .await?
+        .try_filter(|header| future::ready(header.kind == BUS_EVENT_KIND));
+    while let Some(header) = headers.try_next().await? {
+        if newest.first().is_some_and(|first| first.timestamp < header.timestamp) {
+            newest.clear();
+        }
+        newest.push(header);
+    }
+    drop(headers);
+
+    let digests: Vec<CausalDigest> = newest.iter().map(|header| header.digest).collect();
+    let payloads = store.payloads_bulk(&digests).await?;
+    let mut last: Option<(EventHeader, PersistedEvent)> = None;
+    for header in newest {
+        let bytes = payloads
+            .get(&header.digest)
+            .ok_or_else(|| StorageError::EventNotFound(format!("bus record {}", header.id)))?;
+        let record: PersistedEvent = decode_payload(&header, bytes)?;
+        if last.as_ref().is_none_or(|(_, last)| last.sequence < record.sequence) {
+            last = Some((header, record));
+        }
+    }
+    Ok(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use toka_store_memory::MemoryBackend;
+    use toka_types::{EntityId, TaskSpec};
+
+    fn scheduled(description: &str) -> KernelEvent {
+        KernelEvent::TaskScheduled {
+            agent: EntityId(1),
+            task: TaskSpec {
+                description: description.to_string(),
+                correlation_id: None,
+            },
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_events_survive_reopening() {
+        let backend = Arc::new(MemoryBackend::new());
+        let bus = PersistentBus::open(backend.clone(), PersistentBusConfig::default()).await.unwrap();
+        let mut live = bus.subscribe();
+
+        let first = bus.publish_durable(&scheduled("first")).await.unwrap();
+        assert_eq!(first.sequence, 1);
+        assert_eq!(live.recv().await.unwrap(), first.event);
+        bus.publish(&scheduled("second")).unwrap();
+        bus.flush().await.unwrap();
+        assert_eq!(bus.last_sequence().await, 2);
+        drop(bus);
+
+        let reopened = PersistentBus::open(backend, PersistentBusConfig::default()).await.unwrap();
+        let history = reopened.replay(1).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0], first);
+        assert_eq!(history[1].event, live.recv().await.unwrap());
+        assert_eq!(reopened.publish_durable(&scheduled("third")).await.unwrap().sequence, 3);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_from_replays_then_follows_live() {
+        let backend = Arc::new(MemoryBackend::new());
+        let bus = PersistentBus::open(backend, PersistentBusConfig::default()).await.unwrap();
+        for description in ["one", "two", "three"] {
+            bus.publish(&scheduled(description)).unwrap();
+        }
+        bus.flush().await.unwrap();
+
+        let mut events = bus.subscribe_from(2).await.unwrap();
+        bus.publish_durable(&scheduled("four")).await.unwrap();
+
+        let sequences: Vec<u64> = events
+            .by_ref()
+            .take(3)
+            .map_ok(|record| record.sequence)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(sequences, vec![2, 3, 4]);
+    }
+}
@@ -0,0 +1,199 @@
+#![forbid(unsafe_code)]
+
+//! Durable consumers with at-least-once delivery.
+//!
+//! A [`DurableConsumer`] reads a stream through
+//! [`subscribe_from`](crate::PersistentBus::subscribe_from) and records each
+//! acknowledged sequence as a [`BUS_OFFSET_KIND`] event under its own intent
+//! (see [`consumer_intent`]). Reopening the consumer resumes right after the
+//! last acknowledged event, so events that were delivered but not
+//! acknowledged before a crash are delivered again.
+//!
+//! Every acknowledgement appends an event. Consumers acknowledging each
+//! event of a busy stream should apply a retention policy to
+//! [`BUS_OFFSET_KIND`], or acknowledge in batches.
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use futures::{future, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+
+use toka_store_core::{
+    create_event_header, decode_payload, EventHeader, IntentId, QueryableStorage, StorageError,
+    WalStorageBackend,
+};
+
+use crate::bus::{DurableStream, Shared};
+use crate::{consumer_intent, PersistedEvent, BUS_OFFSET_KIND};
+
+/// Acknowledgement recorded by a [`DurableConsumer`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsumerOffset {
+    /// Name of the consumer
+    pub consumer: String,
+    /// Highest sequence processed by the consumer
+    pub sequence: u64,
+    /// When the acknowledgement was recorded
+    pub committed_at: DateTime<Utc>,
+}
+
+/// Named reader of a persistent stream that resumes where it left off.
+///
+/// Only one consumer of a given name should run at a time.
+pub struct DurableConsumer<S: ?Sized> {
+    shared: Arc<Shared<S>>,
+    name: String,
+    intent: IntentId,
+    committed: u64,
+    head: Option<EventHeader>,
+    events: Option<DurableStream>,
+}
+
+impl<S> DurableConsumer<S>
+where
+    S: WalStorageBackend + QueryableStorage + ?Sized,
+{
+    /// Open the consumer `name`, reading its last offset from the store.
+    pub(crate) async fn open(shared: Arc<Shared<S>>, name: String) -> anyhow::Result<Self> {
+        let intent = consumer_intent(&shared.stream, &name);
+        let headers: Vec<EventHeader> = shared
+            .store
+            .events_by_intent(&intent)
+            .await?
+            .try_filter(|header| future::ready(header.kind == BUS_OFFSET_KIND))
+            .try_collect()
+            .await?;
+
+        let mut committed = 0;
+        let mut head = None;
+        for header in headers {
+            let bytes = shared
+                .store
+                .payload_bytes(&header.digest)
+                .await?
+                .ok_or_else(|| StorageError::EventNotFound(format!("consumer offset {}", header.id)))?;
+            let offset: ConsumerOffset = decode_payload(&header, &bytes)?;
+            if head.is_none() || offset.sequence > committed {
+                committed = offset.sequence;
+                head = Some(header);
+            }
+        }
+
+        Ok(Self {
+            shared,
+            name,
+            intent,
+            committed,
+            head,
+            events: None,
+        })
+    }
+
+    /// Name of the consumer.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Highest acknowledged sequence, 0 if nothing was acknowledged yet.
+    pub fn committed(&self) -> u64 {
+        self.committed
+    }
+
+    /// Next event of the stream, waiting for one to be published if needed.
+    ///
+    /// Returns `None` once the bus has shut down. If the consumer fell so far
+    /// behind that live events were lost, an error is returned and the next
+    /// call redelivers everything after the committed offset.
+    pub async fn next(&mut self) -> anyhow::Result<Option<PersistedEvent>> {
+        if self.events.is_none() {
+            self.events = Some(self.shared.subscribe_from(self.committed + 1).await?);
+        }
+        let events = self.events.as_mut().expect("subscribed above");
+        match events.next().await {
+            Some(Ok(record)) => Ok(Some(record)),
+            Some(Err(e)) => {
+                self.events = None;
+                Err(e)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Record that every event up to `sequence` has been processed.
+    ///
+    /// Acknowledging a sequence at or below the committed offset is a no-op.
+    pub async fn ack(&mut self, sequence: u64) -> anyhow::Result<()> {
+        if sequence <= self.committed {
+            return Ok(());
+        }
+        let offset = ConsumerOffset {
+            consumer: self.name.clone(),
+            sequence,
+            committed_at: Utc::now(),
+        };
+        let header = create_event_header(self.head.as_slice(), self.intent, BUS_OFFSET_KIND.to_string(), &offset)
+            .map_err(|e| StorageError::SerializationFailed(e.to_string()))?;
+        let payload = rmp_serde::to_vec_named(&offset)
+            .map_err(|e| StorageError::SerializationFailed(e.to_string()))?;
+        self.shared.store.commit(&header, &payload).await?;
+
+        self.committed = sequence;
+        self.head = Some(header);
+        Ok(())
+    }
+
+    /// Deliver every event after the committed offset again, e.g. after
+    /// processing failed.
+    pub fn rewind(&mut self) {
+        self.events = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use toka_bus_core::KernelEvent;
+    use toka_store_memory::MemoryBackend;
+    use toka_types::{AgentSpec, EntityId};
+
+    use crate::{PersistentBus, PersistentBusConfig};
+
+    use super::*;
+
+    fn spawned(name: &str) -> KernelEvent {
+        KernelEvent::AgentSpawned {
+            parent: EntityId(1),
+            spec: AgentSpec {
+                name: name.to_string(),
+            },
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unacknowledged_events_are_redelivered() {
+        let backend = Arc::new(MemoryBackend::new());
+        let bus = PersistentBus::open(backend.clone(), PersistentBusConfig::default()).await.unwrap();
+        for name in ["a", "b", "c"] {
+            bus.publish_durable(&spawned(name)).await.unwrap();
+        }
+
+        let mut consumer = bus.consumer("indexer").await.unwrap();
+        assert_eq!(consumer.next().await.unwrap().unwrap().sequence, 1);
+        consumer.ack(1).await.unwrap();
+        assert_eq!(consumer.next().await.unwrap().unwrap().sequence, 2);
+
+        // Crash before acknowledging 2: the next run starts over at 2
+        drop(consumer);
+        let mut consumer = bus.consumer("indexer").await.unwrap();
+        assert_eq!(consumer.committed(), 1);
+        assert_eq!(consumer.next().await.unwrap().unwrap().sequence, 2);
+        consumer.ack(2).await.unwrap();
+        consumer.rewind();
+        assert_eq!(consumer.next().await.unwrap().unwrap().sequence, 3);
+
+        // Offsets are per consumer
+        let mut other = bus.consumer("auditor").await.unwrap();
+        assert_eq!(other.next().await.unwrap().unwrap().sequence, 1);
+    }
+}
@@ -0,0 +1,108 @@
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+
+//! **toka-bus-persist** – Durable kernel event bus for Toka OS.
+//!
+//! The [`InMemoryBus`](toka_bus_core::InMemoryBus) forgets every event once
+//! it has been broadcast: a subscriber that is down, restarting or lagging
+//! simply misses it. This crate provides an [`EventBus`] that records every
+//! published [`KernelEvent`] in a storage backend first and only then
+//! broadcasts it.
+//!
+//! - [`PersistentBus`] writes each event through the backend's write-ahead
+//!   log and assigns it a per-stream sequence number. Live subscribers only
+//!   ever see events that are already durable.
+//! - [`PersistentBus::subscribe_from`] replays the stored events from a
+//!   sequence number and then follows the live stream without gaps or
+//!   duplicates.
+//! - [`DurableConsumer`] remembers, in the same backend, the last sequence a
+//!   named consumer acknowledged. After a restart it resumes right after it,
+//!   so every event is delivered **at least once**; consumers should be
+//!   idempotent.
+//! - [`BusRecorder`] and [`BusReplayer`] capture the traffic of any bus to a
+//!   file and publish it back, in real time or accelerated, to reproduce
+//!   incidents in integration tests.
+//!
+//! Each stream must have a single writing [`PersistentBus`] at a time, since
+//! sequence numbers are assigned by the bus that opened it.
+//!
+//! [`EventBus`]: toka_bus_core::EventBus
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use toka_bus_core::KernelEvent;
+use toka_store_core::{causal_hash, IntentId};
+
+//─────────────────────────────
+//  Persistent bus
+//─────────────────────────────
+
+/// Event bus persisting events before broadcasting them.
+pub mod bus;
+/// Named consumers with committed offsets.
+pub mod consumer;
+
+pub use bus::{DurableStream, PersistentBus, PersistentBusConfig};
+pub use consumer::{ConsumerOffset, DurableConsumer};
+
+//─────────────────────────────
+//  Recording and replay
+//─────────────────────────────
+
+/// Recording bus traffic to a file and replaying it into any bus.
+pub mod recorder;
+
+pub use recorder::{
+    BusRecorder, BusReplayer, RecordedEvent, RecordingSummary, ReplayOptions, ReplaySpeed, ReplaySummary,
+};
+
+//─────────────────────────────
+//  Stored records
+//─────────────────────────────
+
+/// Event kind of persisted kernel events.
+pub const BUS_EVENT_KIND: &str = "bus.event";
+
+/// Event kind of committed consumer offsets.
+pub const BUS_OFFSET_KIND: &str = "bus.offset";
+
+/// Stream used by [`PersistentBusConfig::default`].
+pub const DEFAULT_STREAM: &str = "kernel";
+
+/// A kernel event as recorded by a [`PersistentBus`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PersistedEvent {
+    /// Position in the stream, starting at 1
+    pub sequence: u64,
+    /// The published event
+    pub event: KernelEvent,
+    /// When the event was persisted
+    pub persisted_at: DateTime<Utc>,
+}
+
+/// Deterministic intent ID grouping the events of the stream `stream`.
+pub fn stream_intent(stream: &str) -> IntentId {
+    derive_intent(&format!("bus:{}", stream))
+}
+
+/// Deterministic intent ID grouping the offsets of `consumer` on `stream`.
+pub fn consumer_intent(stream: &str, consumer: &str) -> IntentId {
+    derive_intent(&format!("bus:{}/consumer:{}", stream, consumer))
+}
+
+fn derive_intent(name: &str) -> IntentId {
+    let digest = causal_hash(name.as_bytes(), &[]);
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    Uuid::from_bytes(bytes)
+}
+
+/// Convenient re-exports for callers.
+pub mod prelude {
+    pub use super::{
+        BusRecorder, BusReplayer, ConsumerOffset, DurableConsumer, DurableStream, PersistedEvent,
+        PersistentBus, PersistentBusConfig, ReplayOptions, ReplaySpeed,
+    };
+}
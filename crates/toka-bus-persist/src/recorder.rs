@@ -0,0 +1,362 @@
+//! Recording bus traffic to a file and replaying it.
+//!
+//! A [`BusRecorder`] subscribes to any [`EventBus`] and appends every event
+//! it sees, with the time it saw it, to a JSON Lines file. A [`BusReplayer`]
+//! reads such a file, or the history of a [`PersistentBus`], and publishes
+//! the events into another bus with their original spacing, accelerated or
+//! as fast as possible. Together they turn a production incident into an
+//! integration test.
+//!
+//! Kernel events older than a day fail validation, so by default the
+//! replayer moves every timestamp of a recording forward by the time
+//! between the start of the recording and the start of the replay.
+//!
+//! [`PersistentBus`]: crate::PersistentBus
+
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+use tokio::sync::{broadcast, oneshot};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use toka_bus_core::{EventBus, KernelEvent};
+
+use crate::PersistedEvent;
+
+/// Event fields holding points in time, shifted when rebasing.
+const TIME_FIELDS: &[&str] = &["timestamp", "deadline", "window_start", "window_end"];
+
+/// One line of a recording.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// When the recorder received the event
+    pub recorded_at: DateTime<Utc>,
+    /// The event
+    pub event: KernelEvent,
+}
+
+/// Outcome of a recording.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RecordingSummary {
+    /// Events written
+    pub events: u64,
+    /// Events missed because the recorder lagged behind the bus
+    pub missed: u64,
+}
+
+/// Records the events of a bus to a JSON Lines file.
+///
+/// Recording stops when [`finish`](Self::finish) is called or the recorder
+/// is dropped; events published before either are still written.
+pub struct BusRecorder {
+    stop: Option<oneshot::Sender<()>>,
+    task: JoinHandle<anyhow::Result<RecordingSummary>>,
+}
+
+impl BusRecorder {
+    /// Record the events of `bus` to a new file at `path`, replacing any
+    /// existing one.
+    ///
+    /// Must be called inside a Tokio runtime.
+    pub async fn start(bus: &dyn EventBus, path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = File::create(path.as_ref()).await?;
+        let (stop, stopped) = oneshot::channel();
+        let task = tokio::spawn(record(bus.subscribe(), file, stopped));
+        Ok(Self {
+            stop: Some(stop),
+            task,
+        })
+    }
+
+    /// Stop recording and wait until every received event is written.
+    pub async fn finish(mut self) -> anyhow::Result<RecordingSummary> {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        (&mut self.task).await?
+    }
+}
+
+impl Drop for BusRecorder {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+    }
+}
+
+async fn record(
+    mut events: broadcast::Receiver<KernelEvent>,
+    mut file: File,
+    mut stopped: oneshot::Receiver<()>,
+) -> anyhow::Result<RecordingSummary> {
+    let mut summary = RecordingSummary::default();
+    loop {
+        let received = tokio::select! {
+            received = events.recv() => received,
+            // A dropped recorder stops recording as well
+            _ = &mut stopped => break,
+        };
+        match received {
+            Ok(event) => write_event(&mut file, event, &mut summary).await?,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Bus recorder missed {} events", skipped);
+                summary.missed += skipped;
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+
+    // Write what was published before recording stopped
+    loop {
+        match events.try_recv() {
+            Ok(event) => write_event(&mut file, event, &mut summary).await?,
+            Err(TryRecvError::Lagged(skipped)) => summary.missed += skipped,
+            Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+        }
+    }
+    file.flush().await?;
+    Ok(summary)
+}
+
+async fn write_event(file: &mut File, event: KernelEvent, summary: &mut RecordingSummary) -> anyhow::Result<()> {
+    let record = RecordedEvent {
+        recorded_at: Utc::now(),
+        event,
+    };
+    let mut line = serde_json::to_vec(&record)?;
+    line.push(b'\n');
+    // One write per event, so a crash loses at most the event in flight
+    file.write_all(&line).await?;
+    summary.events += 1;
+    Ok(())
+}
+
+/// Pace of a replay.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ReplaySpeed {
+    /// Publish without delays
+    #[default]
+    Instant,
+    /// Keep the recorded spacing between events
+    RealTime,
+    /// Divide the recorded spacing by this factor
+    Accelerated(f64),
+}
+
+/// Settings of a replay.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReplayOptions {
+    /// Pace of the replay
+    pub speed: ReplaySpeed,
+    /// Move event timestamps forward so the recording starts now
+    pub rebase_timestamps: bool,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        Self {
+            speed: ReplaySpeed::Instant,
+            rebase_timestamps: true,
+        }
+    }
+}
+
+/// Outcome of a replay.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReplaySummary {
+    /// Events the bus accepted
+    pub published: u64,
+    /// Events the bus refused, e.g. because they no longer validate
+    pub rejected: u64,
+}
+
+/// Publishes recorded events into a bus.
+#[derive(Clone, Debug, Default)]
+pub struct BusReplayer {
+    events: Vec<RecordedEvent>,
+}
+
+impl BusReplayer {
+    /// Replay `events`, in the given order.
+    pub fn new(events: Vec<RecordedEvent>) -> Self {
+        Self { events }
+    }
+
+    /// Read a recording written by a [`BusRecorder`].
+    pub async fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let mut lines = BufReader::new(File::open(path).await?).lines();
+        let mut events = Vec::new();
+        let mut number = 0;
+        while let Some(line) = lines.next_line().await? {
+            number += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = serde_json::from_str(&line)
+                .map_err(|e| anyhow::anyhow!("Invalid recording {} at line {}: {}", path.display(), number, e))?;
+            events.push(record);
+        }
+        Ok(Self { events })
+    }
+
+    /// Replay the history of a [`PersistentBus`](crate::PersistentBus).
+    pub fn from_persisted(records: impl IntoIterator<Item = PersistedEvent>) -> Self {
+        Self::new(
+            records
+                .into_iter()
+                .map(|record| RecordedEvent {
+                    recorded_at: record.persisted_at,
+                    event: record.event,
+                })
+                .collect(),
+        )
+    }
+
+    /// The recorded events.
+    pub fn events(&self) -> &[RecordedEvent] {
+        &self.events
+    }
+
+    /// Publish the recorded events into `bus`.
+    pub async fn replay(&self, bus: &dyn EventBus, options: ReplayOptions) -> anyhow::Result<ReplaySummary> {
+        let mut summary = ReplaySummary::default();
+        let Some(first) = self.events.first() else {
+            return Ok(summary);
+        };
+        let shift = Utc::now().signed_duration_since(first.recorded_at);
+
+        let mut previous = first.recorded_at;
+        for record in &self.events {
+            if let Some(delay) = delay(options.speed, record.recorded_at.signed_duration_since(previous)) {
+                tokio::time::sleep(delay).await;
+            }
+            previous = record.recorded_at;
+
+            let event = if options.rebase_timestamps {
+                rebase(&record.event, shift)?
+            } else {
+                record.event.clone()
+            };
+            match bus.publish(&event) {
+                Ok(()) => summary.published += 1,
+                Err(e) => {
+                    warn!("Replayed {} event was rejected: {}", event.kind(), e);
+                    summary.rejected += 1;
+                }
+            }
+        }
+        Ok(summary)
+    }
+}
+
+/// Wait before an event recorded `gap` after its predecessor.
+fn delay(speed: ReplaySpeed, gap: chrono::Duration) -> Option<Duration> {
+    let gap = gap.to_std().ok()?;
+    let delay = match speed {
+        ReplaySpeed::Instant => return None,
+        ReplaySpeed::RealTime => gap,
+        ReplaySpeed::Accelerated(factor) if factor > 0.0 => gap.div_f64(factor),
+        ReplaySpeed::Accelerated(_) => return None,
+    };
+    (!delay.is_zero()).then_some(delay)
+}
+
+/// `event` with every point in time moved by `shift`.
+fn rebase(event: &KernelEvent, shift: chrono::Duration) -> anyhow::Result<KernelEvent> {
+    let mut value = serde_json::to_value(event)?;
+    // Events serialize as `{ "<Variant>": { fields } }`
+    if let Some(fields) = value
+        .as_object_mut()
+        .and_then(|variant| variant.values_mut().next())
+        .and_then(|fields| fields.as_object_mut())
+    {
+        for name in TIME_FIELDS {
+            let Some(field) = fields.get_mut(*name) else {
+                continue;
+            };
+            if let Some(Ok(time)) = field.as_str().map(|time| time.parse::<DateTime<Utc>>()) {
+                *field = serde_json::to_value(time + shift)?;
+            }
+        }
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toka_bus_core::InMemoryBus;
+    use toka_types::{EntityId, TaskSpec};
+
+    fn scheduled(description: &str, timestamp: DateTime<Utc>) -> KernelEvent {
+        KernelEvent::TaskScheduled {
+            agent: EntityId(1),
+            task: TaskSpec {
+                description: description.to_string(),
+                correlation_id: None,
+            },
+            timestamp,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("incident.jsonl");
+
+        let production = InMemoryBus::new(16);
+        let recorder = BusRecorder::start(&production, &path).await.unwrap();
+        for description in ["one", "two", "three"] {
+            production.publish(&scheduled(description, Utc::now())).unwrap();
+        }
+        let summary = recorder.finish().await.unwrap();
+        assert_eq!(summary, RecordingSummary { events: 3, missed: 0 });
+
+        let replayer = BusReplayer::open(&path).await.unwrap();
+        let test = InMemoryBus::new(16);
+        let mut received = test.subscribe();
+        let options = ReplayOptions {
+            speed: ReplaySpeed::Accelerated(1000.0),
+            rebase_timestamps: false,
+        };
+        let summary = replayer.replay(&test, options).await.unwrap();
+        assert_eq!(summary, ReplaySummary { published: 3, rejected: 0 });
+        for record in replayer.events() {
+            assert_eq!(received.recv().await.unwrap(), record.event);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_rebases_old_recordings() {
+        let recorded_at = Utc::now() - chrono::Duration::days(30);
+        let replayer = BusReplayer::new(vec![RecordedEvent {
+            recorded_at,
+            event: scheduled("stale", recorded_at - chrono::Duration::seconds(1)),
+        }]);
+        let bus = InMemoryBus::new(16);
+        let mut received = bus.subscribe();
+
+        let verbatim = ReplayOptions {
+            rebase_timestamps: false,
+            ..ReplayOptions::default()
+        };
+        assert_eq!(replayer.replay(&bus, verbatim).await.unwrap().rejected, 1);
+
+        assert_eq!(replayer.replay(&bus, ReplayOptions::default()).await.unwrap().published, 1);
+        match received.recv().await.unwrap() {
+            KernelEvent::TaskScheduled { timestamp, .. } => {
+                let age = Utc::now().signed_duration_since(timestamp);
+                assert!(age >= chrono::Duration::seconds(1) && age < chrono::Duration::minutes(1));
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+}
@@ -153,7 +153,7 @@ async fn main() -> Result<()> {
 
 async fn handle_schedule_task(runtime: &Runtime, agent_id: u128, description: String, token: Option<String>) -> Result<()> {
     let agent = EntityId(agent_id);
-    let task = TaskSpec { description: description.clone() };
+    let task = TaskSpec { description: description.clone(), correlation_id: None };
 
     let capability = match token {
         Some(token) => token,
@@ -172,6 +172,7 @@ async fn handle_schedule_task(runtime: &Runtime, agent_id: u128, description: St
         origin,
         capability,
         op: Operation::ScheduleAgentTask { agent, task },
+        correlation_id: None,
     };
 
     info!("Scheduling task for agent {}: {}", agent_id, description);
@@ -201,6 +202,7 @@ async fn handle_spawn_agent(runtime: &Runtime, name: String, token: Option<Strin
         origin: parent,
         capability,
         op: Operation::SpawnSubAgent { parent, spec },
+        correlation_id: None,
     };
 
     info!("Spawning agent: {}", name);
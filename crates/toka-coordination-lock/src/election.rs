@@ -0,0 +1,299 @@
+#![forbid(unsafe_code)]
+
+//! Lease-based leader election.
+//!
+//! Every candidate node runs a [`LeaderElection`] for the same lock name.
+//! Each round, the leader renews its lease and followers try to acquire it;
+//! a follower only succeeds once the leader's lease has expired, e.g.
+//! because the leader crashed or lost its connection to the store. A node
+//! that can no longer renew steps down as soon as its own lease lapses, so
+//! two nodes never consider themselves leader under a live lease.
+//!
+//! With the default [`ElectionConfig`] a new leader takes over at most
+//! `lease_ttl + renew_interval` after the old one stopped renewing.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::{Lease, LeaseStore};
+
+/// Timing of a [`LeaderElection`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ElectionConfig {
+    /// How long a lease lasts without renewal
+    pub lease_ttl: Duration,
+    /// How often the leader renews and followers retry
+    pub renew_interval: Duration,
+}
+
+impl Default for ElectionConfig {
+    fn default() -> Self {
+        Self {
+            lease_ttl: Duration::from_secs(15),
+            renew_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Role of a node in an election.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LeadershipState {
+    /// Another node (or nobody) leads
+    Follower {
+        /// Holder of the live lease, if known
+        leader: Option<String>,
+    },
+    /// This node leads under the given lease
+    Leader(Lease),
+}
+
+impl LeadershipState {
+    /// Whether both states describe the same leader, ignoring renewals.
+    fn same_role(&self, other: &LeadershipState) -> bool {
+        match (self, other) {
+            (LeadershipState::Leader(a), LeadershipState::Leader(b)) => a.token == b.token,
+            (a, b) => a == b,
+        }
+    }
+}
+
+/// Name of the lock electing the leader of an orchestration session.
+pub fn session_lock_name(session_id: &str) -> String {
+    format!("orchestration/session/{}", session_id)
+}
+
+/// Leader election for one lock among the nodes sharing a [`LeaseStore`].
+///
+/// Drive it with [`spawn`](Self::spawn), or call [`step`](Self::step)
+/// directly to run single rounds.
+pub struct LeaderElection {
+    store: Arc<dyn LeaseStore>,
+    name: String,
+    node: String,
+    config: ElectionConfig,
+    state: watch::Sender<LeadershipState>,
+}
+
+impl LeaderElection {
+    /// Campaign as `node` for the lock `name`.
+    ///
+    /// Fails if the renew interval is zero or not shorter than the lease TTL,
+    /// since the leader would then lose its lease between renewals.
+    pub fn new(
+        store: Arc<dyn LeaseStore>,
+        name: impl Into<String>,
+        node: impl Into<String>,
+        config: ElectionConfig,
+    ) -> anyhow::Result<Self> {
+        if config.renew_interval.is_zero() || config.renew_interval >= config.lease_ttl {
+            anyhow::bail!(
+                "Renew interval {:?} must be non-zero and shorter than the lease TTL {:?}",
+                config.renew_interval,
+                config.lease_ttl
+            );
+        }
+        let (state, _) = watch::channel(LeadershipState::Follower { leader: None });
+        Ok(Self {
+            store,
+            name: name.into(),
+            node: node.into(),
+            config,
+            state,
+        })
+    }
+
+    /// Name of the contested lock.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Identifier this node campaigns under.
+    pub fn node(&self) -> &str {
+        &self.node
+    }
+
+    /// Current role of this node.
+    pub fn state(&self) -> LeadershipState {
+        self.state.borrow().clone()
+    }
+
+    /// Whether this node leads under a lease that has not lapsed.
+    pub fn is_leader(&self) -> bool {
+        self.lease().is_some()
+    }
+
+    /// The live lease this node leads under.
+    pub fn lease(&self) -> Option<Lease> {
+        match &*self.state.borrow() {
+            LeadershipState::Leader(lease) if !lease.is_expired() => Some(lease.clone()),
+            _ => None,
+        }
+    }
+
+    /// Watch role changes. Lease renewals do not count as changes.
+    pub fn subscribe(&self) -> watch::Receiver<LeadershipState> {
+        self.state.subscribe()
+    }
+
+    /// Run one round: renew the lease if leading, otherwise try to take it.
+    ///
+    /// On a store error the node keeps leading only while its lease has not
+    /// lapsed, and the error is returned.
+    pub async fn step(&self) -> anyhow::Result<LeadershipState> {
+        let held = self.lease();
+        let result = match &held {
+            Some(lease) => self.store.renew(lease, self.config.lease_ttl).await,
+            None => {
+                self.store
+                    .try_acquire(&self.name, &self.node, self.config.lease_ttl)
+                    .await
+            }
+        };
+
+        let next = match result {
+            Ok(Some(lease)) => LeadershipState::Leader(lease),
+            Ok(None) => {
+                let current = self.store.current(&self.name).await?;
+                LeadershipState::Follower {
+                    leader: current.map(|lease| lease.holder),
+                }
+            }
+            Err(e) => {
+                if held.is_none() {
+                    self.transition(LeadershipState::Follower { leader: None });
+                }
+                return Err(e);
+            }
+        };
+        self.transition(next.clone());
+        Ok(next)
+    }
+
+    /// Run rounds every renew interval until the task is aborted.
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = self.step().await {
+                    warn!("Election round for {} on {} failed: {}", self.name, self.node, e);
+                }
+                tokio::time::sleep(self.config.renew_interval).await;
+            }
+        })
+    }
+
+    /// Wait until this node leads, returning its lease.
+    pub async fn wait_for_leadership(&self) -> anyhow::Result<Lease> {
+        let mut rx = self.subscribe();
+        loop {
+            if let Some(lease) = self.lease() {
+                return Ok(lease);
+            }
+            rx.changed().await?;
+        }
+    }
+
+    /// Step down, releasing the lease so another node can take over at once.
+    pub async fn resign(&self) -> anyhow::Result<()> {
+        if let LeadershipState::Leader(lease) = self.state() {
+            self.store.release(&lease).await?;
+        }
+        self.transition(LeadershipState::Follower { leader: None });
+        Ok(())
+    }
+
+    fn transition(&self, next: LeadershipState) {
+        let name = &self.name;
+        let node = &self.node;
+        self.state.send_if_modified(|state| {
+            let changed = !state.same_role(&next);
+            if changed {
+                match &next {
+                    LeadershipState::Leader(lease) => {
+                        info!("{} became leader of {} (token {})", node, name, lease.token)
+                    }
+                    LeadershipState::Follower { .. } if matches!(state, LeadershipState::Leader(_)) => {
+                        info!("{} is no longer leader of {}", node, name)
+                    }
+                    LeadershipState::Follower { .. } => {}
+                }
+            }
+            *state = next;
+            changed
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryLeaseStore;
+
+    fn config() -> ElectionConfig {
+        ElectionConfig {
+            lease_ttl: Duration::from_millis(60),
+            renew_interval: Duration::from_millis(10),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failover_after_leader_stops_renewing() {
+        let store: Arc<dyn LeaseStore> = Arc::new(MemoryLeaseStore::new());
+        let lock = session_lock_name("session-1");
+        let a = LeaderElection::new(store.clone(), lock.clone(), "node-a", config()).unwrap();
+        let b = LeaderElection::new(store.clone(), lock, "node-b", config()).unwrap();
+
+        assert!(matches!(a.step().await.unwrap(), LeadershipState::Leader(_)));
+        assert_eq!(
+            b.step().await.unwrap(),
+            LeadershipState::Follower { leader: Some("node-a".into()) }
+        );
+
+        // node-a stops renewing; node-b takes over once the lease lapses
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert!(!a.is_leader());
+        let LeadershipState::Leader(lease) = b.step().await.unwrap() else {
+            panic!("node-b should lead after node-a's lease expired");
+        };
+        assert_eq!(lease.token, 2);
+        assert!(matches!(a.step().await.unwrap(), LeadershipState::Follower { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_background_election_and_resign() {
+        let store: Arc<dyn LeaseStore> = Arc::new(MemoryLeaseStore::new());
+        let a = Arc::new(LeaderElection::new(store.clone(), "lock", "node-a", config()).unwrap());
+        let b = Arc::new(LeaderElection::new(store.clone(), "lock", "node-b", config()).unwrap());
+        assert!(LeaderElection::new(
+            store,
+            "lock",
+            "node-c",
+            ElectionConfig {
+                lease_ttl: Duration::from_millis(10),
+                renew_interval: Duration::from_millis(10),
+            },
+        )
+        .is_err());
+
+        let first = a.clone().spawn();
+        a.wait_for_leadership().await.unwrap();
+        let second = b.clone().spawn();
+
+        // Renewals keep node-a in charge past several lease lifetimes
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(a.is_leader());
+        assert!(!b.is_leader());
+
+        first.abort();
+        a.resign().await.unwrap();
+        let lease = tokio::time::timeout(Duration::from_secs(1), b.wait_for_leadership())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(lease.holder, "node-b");
+        second.abort();
+    }
+}
@@ -0,0 +1,212 @@
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+
+//! **toka-coordination-lock** – Leases and leader election for Toka OS.
+//!
+//! High-availability deployments run several orchestration engine instances
+//! against the same store, but only one of them may drive a given session.
+//! This crate provides the primitive they coordinate through: a named,
+//! time-limited [`Lease`] that at most one holder owns at a time.
+//!
+//! - [`LeaseStore`] is the contract: atomic acquire, renew and release of a
+//!   lease by name.
+//! - [`MemoryLeaseStore`] keeps leases in process, for tests and single-node
+//!   setups.
+//! - [`StorageLeaseStore`] records every lease change as an event in a shared
+//!   [`ConditionalStorage`](toka_store_core::ConditionalStorage) backend, using
+//!   compare-and-swap commits so that concurrent nodes cannot both win.
+//! - [`LeaderElection`] keeps renewing a lease in the background, publishes
+//!   leadership changes and takes over automatically once the previous
+//!   leader's lease expires.
+//!
+//! Every acquisition increments the lease's fencing [`token`](Lease::token).
+//! Writers that act on behalf of a leader should pass the token along so that
+//! work from a deposed leader, still running after its lease expired, can be
+//! told apart from work of the current one.
+//!
+//! Expiry is judged against wall-clock time on each node, so lease TTLs must
+//! be comfortably larger than the clock skew between nodes.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+//─────────────────────────────
+//  Storage-backed leases
+//─────────────────────────────
+
+/// In-process lease store.
+pub mod memory;
+/// Lease store recording lease changes in an event store.
+pub mod store;
+
+pub use memory::MemoryLeaseStore;
+pub use store::{lock_intent, StorageLeaseStore, LOCK_KIND_PREFIX};
+
+//─────────────────────────────
+//  Leader election
+//─────────────────────────────
+
+/// Lease-based leader election with automatic failover.
+pub mod election;
+
+pub use election::{session_lock_name, ElectionConfig, LeaderElection, LeadershipState};
+
+//─────────────────────────────
+//  Lease primitive
+//─────────────────────────────
+
+/// A time-limited claim on a named lock.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lease {
+    /// Name of the lock
+    pub name: String,
+    /// Identifier of the node holding the lease
+    pub holder: String,
+    /// Fencing token, incremented every time the lock changes hands
+    pub token: u64,
+    /// When the holder first acquired the lock
+    pub acquired_at: DateTime<Utc>,
+    /// When the lease lapses unless renewed
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Lease {
+    /// Whether the lease has lapsed at `now`.
+    pub fn is_expired_at(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at <= now
+    }
+
+    /// Whether the lease has lapsed.
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(Utc::now())
+    }
+
+    /// Time left until the lease lapses, zero if it already has.
+    pub fn remaining(&self) -> Duration {
+        (self.expires_at - Utc::now()).to_std().unwrap_or(Duration::ZERO)
+    }
+
+    fn extended(&self, ttl: Duration, now: DateTime<Utc>) -> anyhow::Result<Lease> {
+        Ok(Lease {
+            expires_at: now + chrono::Duration::from_std(ttl)?,
+            ..self.clone()
+        })
+    }
+}
+
+/// Atomic acquire, renew and release of named leases.
+///
+/// Implementations must make each operation atomic with respect to the same
+/// operation from other nodes sharing the store: of two concurrent
+/// [`try_acquire`](Self::try_acquire) calls on a free lock, exactly one
+/// succeeds.
+#[async_trait]
+pub trait LeaseStore: Send + Sync {
+    /// Acquire the lock `name` for `holder` for `ttl`.
+    ///
+    /// Succeeds if the lock is free, its lease has expired, or `holder`
+    /// already holds it (in which case the lease is extended and keeps its
+    /// token). Returns `None` while another holder's lease is live.
+    async fn try_acquire(&self, name: &str, holder: &str, ttl: Duration) -> anyhow::Result<Option<Lease>>;
+
+    /// Extend `lease` by `ttl` from now.
+    ///
+    /// Returns `None` if the lease is no longer current: it expired, was
+    /// released, or the lock changed hands.
+    async fn renew(&self, lease: &Lease, ttl: Duration) -> anyhow::Result<Option<Lease>>;
+
+    /// Give up `lease` before it expires, returning whether it was current.
+    async fn release(&self, lease: &Lease) -> anyhow::Result<bool>;
+
+    /// The live lease on `name`, if any.
+    async fn current(&self, name: &str) -> anyhow::Result<Option<Lease>>;
+}
+
+/// Latest state of a lock, as kept by the stores.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum LeaseRecord {
+    /// The lock is (or was, if expired) held under this lease
+    Held(Lease),
+    /// The holder of `token` released the lock
+    Released {
+        /// Token of the released lease
+        token: u64,
+    },
+}
+
+impl LeaseRecord {
+    fn token(&self) -> u64 {
+        match self {
+            LeaseRecord::Held(lease) => lease.token,
+            LeaseRecord::Released { token } => *token,
+        }
+    }
+
+    /// The lease if it is still live at `now`.
+    pub(crate) fn live(&self, now: DateTime<Utc>) -> Option<&Lease> {
+        match self {
+            LeaseRecord::Held(lease) if !lease.is_expired_at(now) => Some(lease),
+            _ => None,
+        }
+    }
+
+    /// Lease resulting from `holder` acquiring the lock, if allowed.
+    pub(crate) fn acquire(
+        record: Option<&LeaseRecord>,
+        name: &str,
+        holder: &str,
+        ttl: Duration,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Option<Lease>> {
+        match record.and_then(|record| record.live(now)) {
+            Some(lease) if lease.holder == holder => lease.extended(ttl, now).map(Some),
+            Some(_) => Ok(None),
+            None => Ok(Some(Lease {
+                name: name.to_string(),
+                holder: holder.to_string(),
+                token: record.map_or(0, LeaseRecord::token) + 1,
+                acquired_at: now,
+                expires_at: now + chrono::Duration::from_std(ttl)?,
+            })),
+        }
+    }
+
+    /// Lease resulting from renewing `lease`, if it is still current.
+    pub(crate) fn renew(
+        record: Option<&LeaseRecord>,
+        lease: &Lease,
+        ttl: Duration,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Option<Lease>> {
+        match record.and_then(|record| record.live(now)) {
+            Some(current) if current.token == lease.token && current.holder == lease.holder => {
+                current.extended(ttl, now).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Record resulting from releasing `lease`, if it is still current.
+    pub(crate) fn release(record: Option<&LeaseRecord>, lease: &Lease) -> Option<LeaseRecord> {
+        match record {
+            Some(LeaseRecord::Held(current))
+                if current.token == lease.token && current.holder == lease.holder =>
+            {
+                Some(LeaseRecord::Released { token: current.token })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Commonly used types.
+pub mod prelude {
+    pub use super::{
+        session_lock_name, ElectionConfig, LeaderElection, Lease, LeaseStore, LeadershipState,
+        MemoryLeaseStore, StorageLeaseStore,
+    };
+}
@@ -0,0 +1,114 @@
+#![forbid(unsafe_code)]
+
+//! In-process [`LeaseStore`].
+//!
+//! Leases live in a map behind a mutex, so they only coordinate tasks within
+//! one process. Use it in tests and single-node deployments; nodes that must
+//! agree on a leader need a shared store such as
+//! [`StorageLeaseStore`](crate::StorageLeaseStore).
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use tokio::sync::Mutex;
+
+use crate::{Lease, LeaseRecord, LeaseStore};
+
+/// [`LeaseStore`] keeping leases in memory.
+#[derive(Debug, Default)]
+pub struct MemoryLeaseStore {
+    records: Mutex<HashMap<String, LeaseRecord>>,
+}
+
+impl MemoryLeaseStore {
+    /// Create a store without leases.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl LeaseStore for MemoryLeaseStore {
+    async fn try_acquire(&self, name: &str, holder: &str, ttl: Duration) -> anyhow::Result<Option<Lease>> {
+        let mut records = self.records.lock().await;
+        let lease = LeaseRecord::acquire(records.get(name), name, holder, ttl, Utc::now())?;
+        if let Some(lease) = &lease {
+            records.insert(name.to_string(), LeaseRecord::Held(lease.clone()));
+        }
+        Ok(lease)
+    }
+
+    async fn renew(&self, lease: &Lease, ttl: Duration) -> anyhow::Result<Option<Lease>> {
+        let mut records = self.records.lock().await;
+        let renewed = LeaseRecord::renew(records.get(&lease.name), lease, ttl, Utc::now())?;
+        if let Some(renewed) = &renewed {
+            records.insert(lease.name.clone(), LeaseRecord::Held(renewed.clone()));
+        }
+        Ok(renewed)
+    }
+
+    async fn release(&self, lease: &Lease) -> anyhow::Result<bool> {
+        let mut records = self.records.lock().await;
+        match LeaseRecord::release(records.get(&lease.name), lease) {
+            Some(released) => {
+                records.insert(lease.name.clone(), released);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn current(&self, name: &str) -> anyhow::Result<Option<Lease>> {
+        let records = self.records.lock().await;
+        Ok(records.get(name).and_then(|record| record.live(Utc::now())).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_lease_lifecycle() {
+        let store = MemoryLeaseStore::new();
+        let ttl = Duration::from_secs(30);
+
+        let lease = store.try_acquire("session", "node-a", ttl).await.unwrap().unwrap();
+        assert_eq!(lease.token, 1);
+        assert!(store.try_acquire("session", "node-b", ttl).await.unwrap().is_none());
+
+        // Re-acquiring as the holder extends the same lease
+        let again = store.try_acquire("session", "node-a", ttl).await.unwrap().unwrap();
+        assert_eq!(again.token, 1);
+        let renewed = store.renew(&again, ttl).await.unwrap().unwrap();
+        assert_eq!(store.current("session").await.unwrap(), Some(renewed.clone()));
+
+        assert!(store.release(&renewed).await.unwrap());
+        assert!(!store.release(&renewed).await.unwrap());
+        assert!(store.renew(&renewed, ttl).await.unwrap().is_none());
+
+        let next = store.try_acquire("session", "node-b", ttl).await.unwrap().unwrap();
+        assert_eq!(next.token, 2);
+    }
+
+    #[tokio::test]
+    async fn test_expired_lease_changes_hands() {
+        let store = MemoryLeaseStore::new();
+        let short = Duration::from_millis(20);
+
+        let stale = store.try_acquire("session", "node-a", short).await.unwrap().unwrap();
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert!(store.current("session").await.unwrap().is_none());
+
+        let taken = store
+            .try_acquire("session", "node-b", Duration::from_secs(30))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(taken.token, stale.token + 1);
+        assert!(store.renew(&stale, short).await.unwrap().is_none());
+        assert!(!store.release(&stale).await.unwrap());
+    }
+}
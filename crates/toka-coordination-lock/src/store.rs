@@ -0,0 +1,228 @@
+#![forbid(unsafe_code)]
+
+//! [`LeaseStore`] backed by a shared event store.
+//!
+//! Each lock gets its own intent (see [`lock_intent`]); every acquisition,
+//! renewal and release appends an event holding the new state of the lock.
+//! The latest event of the intent is the current state. Updates are
+//! committed with [`ConditionalStorage::commit_if`] on the condition that
+//! the intent head has not moved since it was read, so when two nodes race
+//! for a lock exactly one commit lands and the other re-reads and loses.
+//!
+//! Renewals append an event per lease interval. Long-running deployments
+//! should apply a retention policy to the `lock.*` event kinds.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use futures::TryStreamExt;
+use tracing::debug;
+use uuid::Uuid;
+
+use toka_store_core::{
+    as_conflict, causal_hash, create_event_header, decode_payload, intent_head, ConditionalStorage,
+    EventHeader, IntentId, Precondition, QueryableStorage, StorageError,
+};
+
+use crate::{Lease, LeaseRecord, LeaseStore};
+
+/// Prefix of the event kinds written for locks.
+pub const LOCK_KIND_PREFIX: &str = "lock";
+
+/// Attempts at a compare-and-swap update before giving up.
+const MAX_UPDATE_ATTEMPTS: usize = 8;
+
+/// [`LeaseStore`] recording lease changes in a [`ConditionalStorage`] backend.
+///
+/// Nodes sharing the backend share the locks. The backend must make
+/// conditional commits atomic across all of them, which holds for a single
+/// SQLite database or any backend serving every node.
+pub struct StorageLeaseStore<S: ?Sized> {
+    store: Arc<S>,
+}
+
+impl<S> StorageLeaseStore<S>
+where
+    S: ConditionalStorage + QueryableStorage + ?Sized,
+{
+    /// Keep leases in `store`.
+    pub fn new(store: Arc<S>) -> Self {
+        Self { store }
+    }
+
+    /// Latest event of the lock `name` and the state it records.
+    async fn read(&self, name: &str) -> anyhow::Result<(Option<EventHeader>, Option<LeaseRecord>)> {
+        let intent = lock_intent(name);
+        let headers: Vec<EventHeader> = self.store.events_by_intent(&intent).await?.try_collect().await?;
+        let Some(head_id) = intent_head(&headers) else {
+            return Ok((None, None));
+        };
+        let Some(head) = headers.into_iter().find(|header| header.id == head_id) else {
+            return Ok((None, None));
+        };
+        if !head.kind.starts_with(LOCK_KIND_PREFIX) {
+            anyhow::bail!("Intent of lock {} holds a foreign event of kind {}", name, head.kind);
+        }
+
+        let bytes = self
+            .store
+            .payload_bytes(&head.digest)
+            .await?
+            .ok_or_else(|| StorageError::EventNotFound(format!("lock record {}", head.id)))?;
+        let record: LeaseRecord = decode_payload(&head, &bytes)?;
+        Ok((Some(head), Some(record)))
+    }
+
+    /// Compare-and-swap the state of the lock `name`.
+    ///
+    /// `update` computes the next state from the current one, or `None` to
+    /// leave the lock untouched. It is re-run on the fresh state whenever a
+    /// concurrent update gets in between.
+    async fn update<T>(
+        &self,
+        name: &str,
+        update: impl Fn(Option<&LeaseRecord>) -> anyhow::Result<Option<(LeaseRecord, T)>> + Send,
+    ) -> anyhow::Result<Option<T>>
+    where
+        T: Send,
+    {
+        let intent = lock_intent(name);
+        for _ in 0..MAX_UPDATE_ATTEMPTS {
+            let (head, record) = self.read(name).await?;
+            let Some((next, output)) = update(record.as_ref())? else {
+                return Ok(None);
+            };
+
+            let mut header = create_event_header(head.as_slice(), intent, kind(&next), &next)
+                .map_err(|e| StorageError::SerializationFailed(e.to_string()))?;
+            // The head is chosen by timestamp, so never fall behind it even
+            // if this node's clock lags the previous writer's
+            if let Some(head) = &head {
+                if header.timestamp <= head.timestamp {
+                    header.timestamp = head.timestamp + chrono::Duration::microseconds(1);
+                }
+            }
+            let payload = rmp_serde::to_vec_named(&next)
+                .map_err(|e| StorageError::SerializationFailed(e.to_string()))?;
+
+            let precondition = Precondition::intent_head(intent, head.as_ref().map(|h| h.id));
+            match self.store.commit_if(&header, &payload, &precondition).await {
+                Ok(()) => return Ok(Some(output)),
+                Err(e) if as_conflict(&e).is_some() => {
+                    debug!("Lock {} changed concurrently, retrying", name);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        anyhow::bail!("Lock {} is too contended to update", name)
+    }
+}
+
+#[async_trait]
+impl<S> LeaseStore for StorageLeaseStore<S>
+where
+    S: ConditionalStorage + QueryableStorage + ?Sized,
+{
+    async fn try_acquire(&self, name: &str, holder: &str, ttl: Duration) -> anyhow::Result<Option<Lease>> {
+        self.update(name, |record| {
+            let lease = LeaseRecord::acquire(record, name, holder, ttl, Utc::now())?;
+            Ok(lease.map(|lease| (LeaseRecord::Held(lease.clone()), lease)))
+        })
+        .await
+    }
+
+    async fn renew(&self, lease: &Lease, ttl: Duration) -> anyhow::Result<Option<Lease>> {
+        self.update(&lease.name, |record| {
+            let renewed = LeaseRecord::renew(record, lease, ttl, Utc::now())?;
+            Ok(renewed.map(|renewed| (LeaseRecord::Held(renewed.clone()), renewed)))
+        })
+        .await
+    }
+
+    async fn release(&self, lease: &Lease) -> anyhow::Result<bool> {
+        let released = self
+            .update(&lease.name, |record| Ok(LeaseRecord::release(record, lease).map(|next| (next, ()))))
+            .await?;
+        Ok(released.is_some())
+    }
+
+    async fn current(&self, name: &str) -> anyhow::Result<Option<Lease>> {
+        let (_, record) = self.read(name).await?;
+        Ok(record.and_then(|record| record.live(Utc::now()).cloned()))
+    }
+}
+
+fn kind(record: &LeaseRecord) -> String {
+    let action = match record {
+        LeaseRecord::Held(_) => "held",
+        LeaseRecord::Released { .. } => "released",
+    };
+    format!("{}.{}", LOCK_KIND_PREFIX, action)
+}
+
+/// Deterministic intent ID grouping all records of the lock `name`.
+pub fn lock_intent(name: &str) -> IntentId {
+    let digest = causal_hash(format!("{}:{}", LOCK_KIND_PREFIX, name).as_bytes(), &[]);
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    Uuid::from_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toka_store_memory::MemoryBackend;
+
+    #[tokio::test]
+    async fn test_nodes_share_locks_through_the_store() {
+        let backend = Arc::new(MemoryBackend::new());
+        let node_a = StorageLeaseStore::new(backend.clone());
+        let node_b = StorageLeaseStore::new(backend.clone());
+        let ttl = Duration::from_secs(30);
+
+        let (a, b) = tokio::join!(
+            node_a.try_acquire("session-1", "node-a", ttl),
+            node_b.try_acquire("session-1", "node-b", ttl),
+        );
+        let winners: Vec<Lease> = [a.unwrap(), b.unwrap()].into_iter().flatten().collect();
+        assert_eq!(winners.len(), 1);
+        let lease = winners[0].clone();
+        assert_eq!(node_a.current("session-1").await.unwrap(), Some(lease.clone()));
+
+        // Another lock is independent
+        assert!(node_b.try_acquire("session-2", "node-b", ttl).await.unwrap().is_some());
+
+        let renewed = node_b.renew(&lease, ttl).await.unwrap().unwrap();
+        assert_eq!(renewed.token, lease.token);
+        assert!(node_a.release(&renewed).await.unwrap());
+        assert!(node_b.current("session-1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_expired_lease_fails_over() {
+        let backend = Arc::new(MemoryBackend::new());
+        let store = StorageLeaseStore::new(backend);
+
+        let stale = store
+            .try_acquire("session", "node-a", Duration::from_millis(20))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(store
+            .try_acquire("session", "node-b", Duration::from_secs(30))
+            .await
+            .unwrap()
+            .is_none());
+
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        let taken = store
+            .try_acquire("session", "node-b", Duration::from_secs(30))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(taken.token, stale.token + 1);
+        assert!(store.renew(&stale, Duration::from_secs(30)).await.unwrap().is_none());
+    }
+}
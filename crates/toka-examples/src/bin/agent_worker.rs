@@ -0,0 +1,94 @@
+#![forbid(unsafe_code)]
+
+//! **toka-agent-worker** – Example agent worker processing queued work items.
+//!
+//! ```bash
+//! # Process work until interrupted
+//! toka-agent-worker --name worker-1
+//!
+//! # Drain the queue and exit
+//! toka-agent-worker --name worker-1 --once
+//! ```
+
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::Parser;
+use tracing::info;
+
+use toka_examples::{Node, NodeConfig, Worker, DEFAULT_DB_PATH, DEFAULT_JWT_SECRET, DEFAULT_QUEUE};
+
+#[derive(Parser)]
+#[command(name = "toka-agent-worker")]
+#[command(about = "Example Toka agent worker - runs queued work items through the tool registry")]
+#[command(version)]
+struct Cli {
+    /// Worker name recorded with claimed tasks
+    #[arg(long, default_value = "worker-1")]
+    name: String,
+
+    /// Database path of the shared store
+    #[arg(long, default_value = DEFAULT_DB_PATH)]
+    db_path: String,
+
+    /// Name of the work queue
+    #[arg(long, default_value = DEFAULT_QUEUE)]
+    queue: String,
+
+    /// Seconds a claimed task stays leased to this worker
+    #[arg(long, default_value = "60")]
+    lease_secs: u64,
+
+    /// Milliseconds between polls when the queue is empty
+    #[arg(long, default_value = "500")]
+    poll_ms: u64,
+
+    /// Exit once no work is available instead of polling
+    #[arg(long)]
+    once: bool,
+
+    /// Log level (trace, debug, info, warn, error)
+    #[arg(long, default_value = "info")]
+    log_level: String,
+
+    /// JWT secret for capability tokens
+    #[arg(long, env = "JWT_SECRET", default_value = DEFAULT_JWT_SECRET)]
+    jwt_secret: String,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::new(&cli.log_level))
+        .init();
+
+    let node = Node::start(
+        NodeConfig::new(&cli.db_path)
+            .with_queue(&cli.queue)
+            .with_jwt_secret(&cli.jwt_secret),
+    )
+    .await?;
+    let worker = Worker::new(&node, &cli.name, Duration::from_secs(cli.lease_secs));
+    info!("{} processing queue {}", cli.name, cli.queue);
+
+    let processed = if cli.once {
+        let mut processed = 0;
+        while let Some(outcome) = worker.process_next().await? {
+            info!("Task {} ({}) is {:?}", outcome.task, outcome.tool, outcome.status);
+            processed += 1;
+        }
+        processed
+    } else {
+        let shutdown = async {
+            let _ = tokio::signal::ctrl_c().await;
+        };
+        worker.run_until(Duration::from_millis(cli.poll_ms), shutdown).await?
+    };
+
+    info!("{} processed {} work items", cli.name, processed);
+    let tap = node.tap_stats();
+    info!("Persisted {} kernel events to the store", tap.persisted);
+    node.shutdown().await;
+    Ok(())
+}
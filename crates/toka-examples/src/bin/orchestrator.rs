@@ -0,0 +1,87 @@
+#![forbid(unsafe_code)]
+
+//! **toka-orchestrator** – Example orchestrator queuing work for agent workers.
+//!
+//! ```bash
+//! # Queue a plan and exit
+//! toka-orchestrator --plan crates/toka-examples/plans/hello.json
+//!
+//! # Queue a plan and wait until workers finished it
+//! toka-orchestrator --plan crates/toka-examples/plans/hello.json --wait
+//! ```
+
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::Parser;
+use tracing::info;
+
+use toka_examples::{
+    enqueue_plan, load_plan, wait_for_tasks, Node, NodeConfig, DEFAULT_DB_PATH, DEFAULT_JWT_SECRET,
+    DEFAULT_QUEUE,
+};
+
+#[derive(Parser)]
+#[command(name = "toka-orchestrator")]
+#[command(about = "Example Toka orchestrator - queues work items for agent workers")]
+#[command(version)]
+struct Cli {
+    /// Plan file: a JSON array of work items
+    #[arg(long)]
+    plan: String,
+
+    /// Database path of the shared store
+    #[arg(long, default_value = DEFAULT_DB_PATH)]
+    db_path: String,
+
+    /// Name of the work queue
+    #[arg(long, default_value = DEFAULT_QUEUE)]
+    queue: String,
+
+    /// Wait until every queued item is completed or dead-lettered
+    #[arg(long)]
+    wait: bool,
+
+    /// Seconds to wait before giving up
+    #[arg(long, default_value = "300")]
+    timeout_secs: u64,
+
+    /// Log level (trace, debug, info, warn, error)
+    #[arg(long, default_value = "info")]
+    log_level: String,
+
+    /// JWT secret for capability tokens
+    #[arg(long, env = "JWT_SECRET", default_value = DEFAULT_JWT_SECRET)]
+    jwt_secret: String,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::new(&cli.log_level))
+        .init();
+
+    let plan = load_plan(&cli.plan)?;
+    let node = Node::start(
+        NodeConfig::new(&cli.db_path)
+            .with_queue(&cli.queue)
+            .with_jwt_secret(&cli.jwt_secret),
+    )
+    .await?;
+
+    let tasks = enqueue_plan(&node, &plan).await?;
+    info!("Queued {} work items on queue {}", tasks.len(), cli.queue);
+
+    if cli.wait {
+        let timeout = Duration::from_secs(cli.timeout_secs);
+        let stats = wait_for_tasks(&node, &tasks, Duration::from_millis(500), timeout).await?;
+        info!(
+            "Plan finished: {} completed, {} dead-lettered",
+            stats.completed, stats.dead_lettered
+        );
+    }
+
+    node.shutdown().await;
+    Ok(())
+}
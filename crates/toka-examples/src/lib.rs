@@ -0,0 +1,459 @@
+#![forbid(unsafe_code)]
+
+//! **toka-examples** – Runnable example services for Toka OS.
+//!
+//! Two binaries stand up a working system from the real crates and double as
+//! deployment templates:
+//!
+//! - **`toka-orchestrator`** loads a plan of [`WorkItem`]s and enqueues them in
+//!   a durable queue, optionally waiting until workers have processed them.
+//! - **`toka-agent-worker`** claims work items, schedules them with its
+//!   kernel, runs the requested tool from the tool registry and reports the
+//!   output as an agent observation.
+//!
+//! Both processes share one SQLite store. The queue lives in it, so work
+//! survives restarts of either side, and each process persists its kernel
+//! bus events into it through a [`BusTap`], so the store holds the full trail
+//! of what happened.
+//!
+//! ## Usage
+//!
+//! ```bash
+//! # Queue the sample plan and wait for it to be processed
+//! toka-orchestrator --plan crates/toka-examples/plans/hello.json --wait
+//!
+//! # In another terminal, process queued work until interrupted
+//! toka-agent-worker --name worker-1
+//! ```
+//!
+//! Run one worker per queue: queue instances only see each other's changes
+//! when reopened, which the worker does before every claim.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use toka_auth::{CapabilityToken, JwtHs256Token, JwtHs256Validator};
+use toka_bus_core::{EventBus, InMemoryBus, KernelEvent};
+use toka_kernel::{Kernel, WorldState};
+use toka_store_core::{
+    BusTap, DurableQueue, QueueConfig, QueueStats, TapConfig, TapStats, TaskId, TaskStatus,
+    WriteAheadLog,
+};
+use toka_store_sqlite::SqliteBackend;
+use toka_tools::{ToolParams, ToolRegistry};
+use toka_types::{EntityId, Message, Operation, TaskSpec};
+
+/// Default path of the shared SQLite store.
+pub const DEFAULT_DB_PATH: &str = "data/toka-example.db";
+
+/// Default name of the work queue.
+pub const DEFAULT_QUEUE: &str = "work";
+
+/// Secret used when `JWT_SECRET` is not set; change it in production.
+pub const DEFAULT_JWT_SECRET: &str = "toka-example-secret-change-in-production";
+
+/// Lifetime of the capability tokens a node mints for its agents.
+const CAPABILITY_TTL_SECS: u64 = 300;
+
+/// Capacity of each node's in-memory event bus.
+const BUS_CAPACITY: usize = 1024;
+
+/// Unit of work handed from the orchestrator to a worker.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkItem {
+    /// Agent the task is scheduled for
+    pub agent: EntityId,
+    /// Task description recorded with the kernel
+    pub description: String,
+    /// Registered tool to run
+    pub tool: String,
+    /// Tool arguments
+    #[serde(default)]
+    pub args: HashMap<String, String>,
+}
+
+impl WorkItem {
+    /// Run `tool` for `agent`.
+    pub fn new(agent: EntityId, tool: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            agent,
+            description: description.into(),
+            tool: tool.into(),
+            args: HashMap::new(),
+        }
+    }
+
+    /// Add a tool argument.
+    pub fn with_arg(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.args.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// Load a plan: a JSON array of [`WorkItem`]s.
+pub fn load_plan(path: impl AsRef<std::path::Path>) -> Result<Vec<WorkItem>> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read plan {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("Invalid plan {}", path.display()))
+}
+
+/// Settings shared by both example services.
+#[derive(Debug, Clone)]
+pub struct NodeConfig {
+    /// Path of the shared SQLite store
+    pub db_path: PathBuf,
+    /// Name of the work queue
+    pub queue: String,
+    /// Secret for minting and validating capability tokens
+    pub jwt_secret: String,
+    /// Retry policy of the work queue
+    pub queue_config: QueueConfig,
+}
+
+impl NodeConfig {
+    /// Defaults with the store at `db_path`.
+    pub fn new(db_path: impl Into<PathBuf>) -> Self {
+        Self {
+            db_path: db_path.into(),
+            queue: DEFAULT_QUEUE.to_string(),
+            jwt_secret: DEFAULT_JWT_SECRET.to_string(),
+            queue_config: QueueConfig::default(),
+        }
+    }
+
+    /// Use the queue `name`.
+    pub fn with_queue(mut self, name: impl Into<String>) -> Self {
+        self.queue = name.into();
+        self
+    }
+
+    /// Use `secret` for capability tokens.
+    pub fn with_jwt_secret(mut self, secret: impl Into<String>) -> Self {
+        self.jwt_secret = secret.into();
+        self
+    }
+
+    /// Use `config` for the work queue.
+    pub fn with_queue_config(mut self, config: QueueConfig) -> Self {
+        self.queue_config = config;
+        self
+    }
+}
+
+/// A running kernel with its bus, tool registry and SQLite store.
+pub struct Node {
+    config: NodeConfig,
+    store: Arc<SqliteBackend>,
+    kernel: Kernel,
+    tools: ToolRegistry,
+    tap: Arc<BusTap<SqliteBackend>>,
+    tap_task: JoinHandle<()>,
+}
+
+impl Node {
+    /// Open the store, recover its WAL and start the kernel.
+    pub async fn start(config: NodeConfig) -> Result<Self> {
+        if let Some(parent) = config.db_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let store = Arc::new(
+            SqliteBackend::open(&config.db_path)
+                .await
+                .with_context(|| format!("Failed to open store {}", config.db_path.display()))?,
+        );
+        let recovery = store.recover().await.context("WAL recovery failed")?;
+        if recovery.transactions_rolled_back > 0 {
+            warn!("Rolled back {} unfinished transactions", recovery.transactions_rolled_back);
+        }
+
+        let bus: Arc<dyn EventBus> = Arc::new(InMemoryBus::new(BUS_CAPACITY));
+        let auth = Arc::new(JwtHs256Validator::new(config.jwt_secret.clone()));
+        let kernel = Kernel::new(WorldState::default(), auth, bus.clone());
+
+        let tools = ToolRegistry::new().await?;
+        toka_tools::tools::register_essential_tools(&tools).await?;
+
+        let tap = Arc::new(BusTap::new(
+            store.clone(),
+            TapConfig::new().capture("task").capture("agent").capture("error"),
+        ));
+        let events = bus.subscribe();
+        let tap_task = tokio::spawn({
+            let tap = tap.clone();
+            async move { tap.run_from(events).await }
+        });
+
+        info!("Node started with store {}", config.db_path.display());
+        Ok(Self {
+            config,
+            store,
+            kernel,
+            tools,
+            tap,
+            tap_task,
+        })
+    }
+
+    /// The node configuration.
+    pub fn config(&self) -> &NodeConfig {
+        &self.config
+    }
+
+    /// The shared store.
+    pub fn store(&self) -> &Arc<SqliteBackend> {
+        &self.store
+    }
+
+    /// The tool registry, with the essential tools registered.
+    pub fn tools(&self) -> &ToolRegistry {
+        &self.tools
+    }
+
+    /// Counters of the tap persisting bus events.
+    pub fn tap_stats(&self) -> TapStats {
+        self.tap.stats()
+    }
+
+    /// Open the work queue, picking up changes made by other processes.
+    pub async fn queue(&self) -> Result<DurableQueue<WorkItem>> {
+        let config = self.config.queue_config.clone();
+        DurableQueue::open(self.store.clone(), &self.config.queue, config).await
+    }
+
+    /// Submit `op` to the kernel on behalf of `agent`.
+    pub async fn submit(&self, agent: EntityId, op: Operation) -> Result<KernelEvent> {
+        let token = JwtHs256Token::new(
+            &agent.0.to_string(),
+            "toka-examples",
+            vec!["agent".to_string()],
+            &self.config.jwt_secret,
+            CAPABILITY_TTL_SECS,
+        )
+        .map_err(|e| anyhow::anyhow!("Failed to mint capability: {}", e))?;
+        let message =
+            Message::new(agent, token.as_str().to_string(), op).map_err(anyhow::Error::msg)?;
+        self.kernel.submit(message).await
+    }
+
+    /// Stop the kernel, let the tap persist what is left on the bus and
+    /// close the store.
+    pub async fn shutdown(self) {
+        let Self { store, kernel, tap_task, .. } = self;
+        // Dropping the kernel closes the bus, which ends the tap
+        drop(kernel);
+        if let Err(e) = tap_task.await {
+            warn!("Bus tap stopped abnormally: {}", e);
+        }
+        store.close().await;
+    }
+}
+
+/// Queue every item of `plan`.
+pub async fn enqueue_plan(node: &Node, plan: &[WorkItem]) -> Result<Vec<TaskId>> {
+    let queue = node.queue().await?;
+    let mut tasks = Vec::with_capacity(plan.len());
+    for item in plan {
+        tasks.push(queue.enqueue(item.clone()).await?);
+    }
+    Ok(tasks)
+}
+
+/// Poll the queue until none of `tasks` is pending or claimed.
+///
+/// Returns the final queue statistics, or an error once `timeout` elapsed.
+pub async fn wait_for_tasks(
+    node: &Node,
+    tasks: &[TaskId],
+    poll: Duration,
+    timeout: Duration,
+) -> Result<QueueStats> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let queue = node.queue().await?;
+        let mut finished = 0;
+        for task in tasks {
+            if let Some(task) = queue.task(*task).await {
+                if matches!(task.status, TaskStatus::Completed | TaskStatus::DeadLettered) {
+                    finished += 1;
+                }
+            }
+        }
+        if finished == tasks.len() {
+            return Ok(queue.stats().await);
+        }
+        if tokio::time::Instant::now() >= deadline {
+            anyhow::bail!("Timed out with {} of {} tasks finished", finished, tasks.len());
+        }
+        tokio::time::sleep(poll).await;
+    }
+}
+
+/// What a worker did with one claimed work item.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkOutcome {
+    /// The queued task
+    pub task: TaskId,
+    /// Tool that was run
+    pub tool: String,
+    /// Task status afterwards
+    pub status: TaskStatus,
+    /// Bytes of tool output reported as an observation
+    pub output_bytes: usize,
+    /// Error of a failed attempt
+    pub error: Option<String>,
+}
+
+/// Processes work items from a node's queue.
+pub struct Worker<'a> {
+    node: &'a Node,
+    name: String,
+    lease: Duration,
+}
+
+impl<'a> Worker<'a> {
+    /// Worker `name` claiming tasks under a lease of `lease`.
+    pub fn new(node: &'a Node, name: impl Into<String>, lease: Duration) -> Self {
+        Self {
+            node,
+            name: name.into(),
+            lease,
+        }
+    }
+
+    /// Claim and process the next available work item, if any.
+    pub async fn process_next(&self) -> Result<Option<WorkOutcome>> {
+        let queue = self.node.queue().await?;
+        let Some(task) = queue.claim(&self.name, self.lease).await? else {
+            return Ok(None);
+        };
+        let item = task.payload;
+        info!("{} claimed task {} ({})", self.name, task.id, item.tool);
+
+        match self.run(&item).await {
+            Ok(output_bytes) => {
+                queue.complete(task.id, &self.name).await?;
+                Ok(Some(WorkOutcome {
+                    task: task.id,
+                    tool: item.tool,
+                    status: TaskStatus::Completed,
+                    output_bytes,
+                    error: None,
+                }))
+            }
+            Err(e) => {
+                let error = format!("{:#}", e);
+                warn!("{} failed task {}: {}", self.name, task.id, error);
+                let status = queue.fail(task.id, &self.name, &error).await?;
+                Ok(Some(WorkOutcome {
+                    task: task.id,
+                    tool: item.tool,
+                    status,
+                    output_bytes: 0,
+                    error: Some(error),
+                }))
+            }
+        }
+    }
+
+    /// Process work items until `shutdown` resolves, polling when idle.
+    pub async fn run_until<F>(&self, poll: Duration, shutdown: F) -> Result<usize>
+    where
+        F: std::future::Future<Output = ()>,
+    {
+        tokio::pin!(shutdown);
+        let mut processed = 0;
+        loop {
+            match self.process_next().await? {
+                Some(_) => processed += 1,
+                None => tokio::select! {
+                    _ = &mut shutdown => return Ok(processed),
+                    _ = tokio::time::sleep(poll) => {}
+                },
+            }
+        }
+    }
+
+    /// Schedule the item with the kernel, run its tool and report the output.
+    async fn run(&self, item: &WorkItem) -> Result<usize> {
+        let task = TaskSpec::new(item.description.clone()).map_err(anyhow::Error::msg)?;
+        self.node
+            .submit(item.agent, Operation::ScheduleAgentTask { agent: item.agent, task })
+            .await?;
+
+        let params = ToolParams {
+            name: item.tool.clone(),
+            args: item.args.clone(),
+        };
+        let result = self.node.tools().execute_tool(&item.tool, &params).await?;
+        if !result.success {
+            anyhow::bail!("Tool {} reported failure: {}", item.tool, result.output);
+        }
+
+        let data = result.output.into_bytes();
+        let output_bytes = data.len();
+        self.node
+            .submit(item.agent, Operation::EmitObservation { agent: item.agent, data })
+            .await?;
+        Ok(output_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use toka_store_core::QueryableStorage;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_orchestrator_and_worker_end_to_end() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "hello from the worker").unwrap();
+        let config = NodeConfig::new(dir.path().join("store.db")).with_queue_config(QueueConfig {
+            max_attempts: 1,
+            ..QueueConfig::default()
+        });
+
+        // The orchestrator queues the plan and shuts down
+        let orchestrator = Node::start(config.clone()).await.unwrap();
+        let plan = vec![
+            WorkItem::new(EntityId(1), "file-reader", "Read notes")
+                .with_arg("path", dir.path().join("notes.txt").display().to_string()),
+            WorkItem::new(EntityId(2), "file-reader", "Read a missing file")
+                .with_arg("path", dir.path().join("missing.txt").display().to_string()),
+        ];
+        let tasks = enqueue_plan(&orchestrator, &plan).await.unwrap();
+        orchestrator.shutdown().await;
+
+        // A worker started later picks the work up from the store
+        let node = Node::start(config.clone()).await.unwrap();
+        let worker = Worker::new(&node, "worker-1", Duration::from_secs(30));
+        let first = worker.process_next().await.unwrap().unwrap();
+        assert_eq!(first.task, tasks[0]);
+        assert_eq!((first.status, first.output_bytes), (TaskStatus::Completed, 21));
+        let second = worker.process_next().await.unwrap().unwrap();
+        assert_eq!(second.status, TaskStatus::DeadLettered);
+        assert!(worker.process_next().await.unwrap().is_none());
+
+        let stats = wait_for_tasks(&node, &tasks, Duration::from_millis(10), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!((stats.completed, stats.dead_lettered), (1, 1));
+
+        // Both schedules and the observation reach the store through the tap
+        while node.tap_stats().persisted < 3 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let scheduled = node.store().events_by_kind("bus.task.scheduled").await.unwrap();
+        assert_eq!(scheduled.count().await, 2);
+        node.shutdown().await;
+    }
+}
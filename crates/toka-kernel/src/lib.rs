@@ -83,6 +83,10 @@ pub struct Kernel {
 
 impl Kernel {
     /// Create a new kernel backed by `state`, `auth` validator and `bus`.
+    ///
+    /// Every submission validates its token; wrap `auth` in a
+    /// [`toka_auth::CachingValidator`] when agents reuse tokens across many
+    /// submissions.
     pub fn new(state: WorldState, auth: Arc<dyn TokenValidator>, bus: Arc<dyn EventBus>) -> Self {
         Self { state: Arc::new(RwLock::new(state)), auth, bus }
     }
@@ -100,6 +104,11 @@ impl Kernel {
     /// - Capability token authentication
     /// - Operation parameter validation
     /// - Rate limiting (future enhancement)
+    ///
+    /// # Correlation
+    /// Scheduled tasks carry the message's `correlation_id`, or a fresh one
+    /// when the message has none, so the task's outcome events can be traced
+    /// back to this submission.
     pub async fn submit(&self, msg: Message) -> Result<KernelEvent> {
         // SECURITY: Validate message structure first
         msg.validate().map_err(|e| KernelError::InvalidOperation(e))?;
@@ -146,7 +155,13 @@ impl Kernel {
         let evt = match &msg.op {
             // ───────── core system ops ─────────
             Operation::ScheduleAgentTask { agent, task } => {
-                self.handle_schedule_task(agent.clone(), task.clone()).await?
+                let mut task = task.clone();
+                task.correlation_id = msg
+                    .correlation_id
+                    .clone()
+                    .or(task.correlation_id)
+                    .or_else(|| Some(uuid::Uuid::new_v4().to_string()));
+                self.handle_schedule_task(agent.clone(), task).await?
             }
             Operation::SpawnSubAgent { parent, spec } => {
                 self.handle_spawn_agent(parent.clone(), spec.clone()).await?
@@ -219,7 +234,7 @@ impl Kernel {
         
         Ok(KernelEvent::ObservationEmitted { 
             agent, 
-            data, 
+            data: data.into(), 
             timestamp: Utc::now(),
         })
     }
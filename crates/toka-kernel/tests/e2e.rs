@@ -33,18 +33,18 @@ async fn e2e_kernel_happy_flow() -> Result<()> {
 
     // 1. Schedule task
     let agent = EntityId(10);
-    let task = TaskSpec { description: "demo".into() };
-    let msg = Message { origin: agent, capability: "cap".into(), op: Operation::ScheduleAgentTask { agent, task: task.clone() } };
+    let task = TaskSpec { description: "demo".into(), correlation_id: None };
+    let msg = Message { origin: agent, capability: "cap".into(), op: Operation::ScheduleAgentTask { agent, task: task.clone() }, correlation_id: None };
     let evt1 = kernel.submit(msg).await?;
 
     // 2. Spawn sub agent
     let child_spec = AgentSpec { name: "child".into() };
-    let msg2 = Message { origin: agent, capability: "cap".into(), op: Operation::SpawnSubAgent { parent: agent, spec: child_spec.clone() } };
+    let msg2 = Message { origin: agent, capability: "cap".into(), op: Operation::SpawnSubAgent { parent: agent, spec: child_spec.clone() }, correlation_id: None };
     let evt2 = kernel.submit(msg2).await?;
 
     // 3. Emit observation
     let data = vec![1,2,3];
-    let msg3 = Message { origin: agent, capability: "cap".into(), op: Operation::EmitObservation { agent, data: data.clone() } };
+    let msg3 = Message { origin: agent, capability: "cap".into(), op: Operation::EmitObservation { agent, data: data.clone() }, correlation_id: None };
     let evt3 = kernel.submit(msg3).await?;
 
     // Collect three events from bus (order preserved).
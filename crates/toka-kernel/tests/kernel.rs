@@ -63,10 +63,11 @@ impl OpcodeHandler for ObservationHandler {
                 .or_default()
                 .push(TaskSpec {
                     description: "generated from observation".into(),
+                    correlation_id: None,
                 });
             return Ok(Some(KernelEvent::ObservationEmitted {
                 agent: *agent,
-                data: data.clone(),
+                data: data.clone().into(),
                 timestamp: Utc::now(),
             }));
         }
@@ -86,6 +87,7 @@ async fn test_kernel_schedule_task_happy_path() -> Result<()> {
     let agent = EntityId(42);
     let task = TaskSpec {
         description: "demo task".into(),
+        correlation_id: None,
     };
     let msg = Message {
         origin: agent,
@@ -94,26 +96,51 @@ async fn test_kernel_schedule_task_happy_path() -> Result<()> {
             agent,
             task: task.clone(),
         },
+        correlation_id: None,
     };
 
     let evt = kernel.submit(msg).await?;
     // Validate event type and core fields (timestamp will vary)
-    match evt {
+    let scheduled = match evt {
         KernelEvent::TaskScheduled { agent: evt_agent, task: evt_task, timestamp } => {
             assert_eq!(evt_agent, agent);
-            assert_eq!(evt_task, task);
+            assert_eq!(evt_task.description, task.description);
+            // The kernel assigns a correlation id when the message has none
+            assert!(evt_task.correlation_id.is_some());
             // Validate timestamp is recent (within last 5 seconds)
             let now = Utc::now();
             let age = now.signed_duration_since(timestamp);
             assert!(age.num_seconds() < 5, "Event timestamp too old: {:?}", age);
+            evt_task
         }
         _ => panic!("Expected TaskScheduled event, got: {:?}", evt),
-    }
+    };
 
     // World-state must reflect the queued task.
     let state_arc = kernel.state_ptr();
     let state = state_arc.read().await;
-    assert_eq!(state.agent_tasks.get(&agent).unwrap(), &vec![task]);
+    assert_eq!(state.agent_tasks.get(&agent).unwrap(), &vec![scheduled]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_correlation_id_follows_task_into_queue() -> Result<()> {
+    let bus: Arc<dyn EventBus> = Arc::new(InMemoryBus::default());
+    let mut events = bus.subscribe();
+    let kernel = Kernel::new(WorldState::default(), Arc::new(AllowAllValidator), bus);
+
+    let agent = EntityId(43);
+    let task = TaskSpec::new("traced task".into()).unwrap();
+    let msg = Message::new(agent, "43".into(), Operation::ScheduleAgentTask { agent, task })
+        .unwrap()
+        .with_correlation_id("req-1");
+    kernel.submit(msg).await?;
+
+    // Published and queued alike, so whoever executes the task can tag its outcome
+    assert_eq!(events.recv().await?.correlation_id(), Some("req-1"));
+    let state_arc = kernel.state_ptr();
+    let queued = state_arc.read().await.agent_tasks[&agent][0].clone();
+    assert_eq!(queued.correlation_id.as_deref(), Some("req-1"));
     Ok(())
 }
 
@@ -130,6 +157,7 @@ async fn test_kernel_capability_denied() {
             agent,
             data: vec![],
         },
+        correlation_id: None,
     };
 
     let err = kernel.submit(msg).await.unwrap_err();
@@ -159,6 +187,7 @@ async fn test_external_opcode_handler_intercepts_operation() -> Result<()> {
             agent,
             data: payload.clone(),
         },
+        correlation_id: None,
     };
 
     let evt = kernel.submit(msg).await?;
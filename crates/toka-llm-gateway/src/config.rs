@@ -168,6 +168,18 @@ impl Drop for EnvLoader {
 }
 
 impl Config {
+    /// Configuration for `provider` with the default rate limit, a 30 second
+    /// timeout and debug mode off.
+    pub fn new(provider: ProviderConfig) -> Self {
+        Self {
+            provider,
+            rate_limit: DEFAULT_RATE_LIMIT,
+            timeout_seconds: 30,
+            debug_mode: false,
+            additional_settings: HashMap::new(),
+        }
+    }
+
     /// Load configuration from environment variables.
     ///
     /// # Environment Variables
@@ -13,8 +13,10 @@
 //! - **Zero-copy configuration**: Environment variables are securely loaded without copies
 //! - **Automatic cleanup**: All sensitive data is zeroized on drop
 //! - **Rate limiting**: Built-in protection against abuse
+//! - **Fair queuing**: Interactive requests go first and sessions take turns
 //! - **Request sanitization**: Prevents injection attacks
 //! - **Response validation**: Ensures safe outputs
+//! - **Structured output**: Schema-validated JSON replies with repair and retry
 //!
 //! ## Usage
 //!
@@ -50,12 +52,18 @@ use tracing::{debug, error, info, warn};
 
 pub mod config;
 pub mod providers;
+pub mod queue;
 pub mod sanitizer;
+pub mod scheduler;
+pub mod structured;
 pub mod validator;
 
-pub use config::{Config, EnvLoader};
+pub use config::{Config, EnvLoader, ProviderConfig};
 pub use providers::{LlmProvider, AnthropicProvider, OpenAiProvider};
+pub use queue::{QueueStats, QueueTicket, RequestPriority, RequestQueue};
 pub use sanitizer::RequestSanitizer;
+pub use scheduler::{LlmScheduler, ProviderLimits, ProviderScheduleStats, SchedulePermit};
+pub use structured::{OutputSchema, StructuredResponse, DEFAULT_STRUCTURED_ATTEMPTS};
 pub use validator::ResponseValidator;
 
 /// Maximum allowed prompt length to prevent memory exhaustion
@@ -67,6 +75,9 @@ pub const MAX_RESPONSE_LENGTH: usize = 1_048_576; // 1MB
 /// Default rate limit: 60 requests per minute
 pub const DEFAULT_RATE_LIMIT: u32 = 60;
 
+/// Completion tokens assumed for scheduling when a request sets no `max_tokens`
+pub const DEFAULT_COMPLETION_ESTIMATE: u32 = 1024;
+
 /// Request to an LLM provider with security constraints.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LlmRequest {
@@ -78,6 +89,12 @@ pub struct LlmRequest {
     temperature: Option<f32>,
     /// Request metadata for auditing
     metadata: RequestMetadata,
+    /// Scheduling class in the gateway's request queue
+    #[serde(default)]
+    priority: RequestPriority,
+    /// JSON Schema the reply must conform to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    response_schema: Option<OutputSchema>,
 }
 
 /// Metadata attached to LLM requests for security and auditing.
@@ -164,6 +181,10 @@ pub struct LlmGateway {
     validator: ResponseValidator,
     config: Arc<Config>,
     metrics: Arc<RwLock<GatewayMetrics>>,
+    scheduler: Option<Arc<LlmScheduler>>,
+    queue: Option<RequestQueue>,
+    #[cfg(feature = "fault-injection")]
+    fault_injector: Option<Arc<toka_types::FaultInjector>>,
 }
 
 /// Metrics collected by the gateway for monitoring.
@@ -179,6 +200,8 @@ pub struct GatewayMetrics {
     pub total_tokens: u64,
     /// Average response time in milliseconds
     pub avg_response_time_ms: f64,
+    /// Request queue depth and admissions, when a queue is configured
+    pub queue: Option<QueueStats>,
 }
 
 impl LlmRequest {
@@ -216,6 +239,8 @@ impl LlmRequest {
                     .as_secs(),
                 request_id: uuid::Uuid::new_v4().to_string(),
             },
+            priority: RequestPriority::default(),
+            response_schema: None,
         })
     }
     
@@ -234,6 +259,22 @@ impl LlmRequest {
         Ok(self)
     }
     
+    /// Set the scheduling class used by the gateway's request queue.
+    pub fn with_priority(mut self, priority: RequestPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Require the reply to be JSON conforming to `schema`.
+    ///
+    /// Providers with a native JSON mode enforce the schema themselves; use
+    /// [`LlmGateway::complete_structured`] to validate and retry regardless
+    /// of provider.
+    pub fn with_response_schema(mut self, schema: OutputSchema) -> Self {
+        self.response_schema = Some(schema);
+        self
+    }
+
     /// Get the prompt text.
     pub fn prompt(&self) -> &str {
         &self.prompt
@@ -253,6 +294,34 @@ impl LlmRequest {
     pub fn metadata(&self) -> &RequestMetadata {
         &self.metadata
     }
+
+    /// Get the scheduling class.
+    pub fn priority(&self) -> RequestPriority {
+        self.priority
+    }
+
+    /// Get the schema the reply must conform to.
+    pub fn response_schema(&self) -> Option<&OutputSchema> {
+        self.response_schema.as_ref()
+    }
+
+    /// Same request with a different prompt, validated like [`LlmRequest::new`].
+    pub(crate) fn with_prompt(&self, prompt: impl Into<String>) -> Result<Self> {
+        let checked = Self::new(prompt)?;
+        Ok(Self {
+            prompt: checked.prompt,
+            ..self.clone()
+        })
+    }
+
+    /// Rough token estimate (prompt + completion) used for scheduling.
+    ///
+    /// Uses the common ~4 characters per token heuristic for the prompt and
+    /// `max_tokens` (or [`DEFAULT_COMPLETION_ESTIMATE`]) for the completion.
+    pub fn estimated_tokens(&self) -> u32 {
+        let prompt_tokens = u32::try_from(self.prompt.len()).unwrap_or(u32::MAX).div_ceil(4);
+        prompt_tokens.saturating_add(self.max_tokens.unwrap_or(DEFAULT_COMPLETION_ESTIMATE))
+    }
 }
 
 impl LlmResponse {
@@ -335,13 +404,48 @@ impl LlmGateway {
             validator,
             config: Arc::new(config),
             metrics,
+            scheduler: None,
+            queue: None,
+            #[cfg(feature = "fault-injection")]
+            fault_injector: None,
         })
     }
+
+    /// Route requests through a shared cost-aware scheduler.
+    ///
+    /// The scheduler is keyed by provider name, so sharing one instance across
+    /// gateways keeps all agents within the same provider limits.
+    pub fn with_scheduler(mut self, scheduler: Arc<LlmScheduler>) -> Self {
+        self.scheduler = Some(scheduler);
+        self
+    }
     
+    /// Admit requests through a bounded queue with priority classes.
+    ///
+    /// Requests wait for a slot by [`RequestPriority`], and sessions (one per
+    /// agent) take turns within a class. Share a clone of the queue between
+    /// gateways to bound their combined concurrency.
+    pub fn with_request_queue(mut self, queue: RequestQueue) -> Self {
+        self.queue = Some(queue);
+        self
+    }
+
+    /// Inject provider latency and failures for resilience testing.
+    ///
+    /// Injected failures are counted as failed requests, exactly like real
+    /// provider errors.
+    #[cfg(feature = "fault-injection")]
+    pub fn with_fault_injector(mut self, injector: Arc<toka_types::FaultInjector>) -> Self {
+        self.fault_injector = Some(injector);
+        self
+    }
+
     /// Complete an LLM request with full security validation.
     ///
     /// # Security
     /// - Rate limiting per agent
+    /// - Fair admission by priority and session (when a queue is configured)
+    /// - Provider-wide scheduling (when a scheduler is configured)
     /// - Request sanitization
     /// - Response validation
     /// - Audit logging
@@ -369,6 +473,36 @@ impl LlmGateway {
             request.metadata.workstream
         );
         
+        // Wait for a slot if a request queue is configured; the ticket is
+        // held until the provider has answered
+        let _ticket = match &self.queue {
+            Some(queue) => Some(queue.acquire(request.priority, &rate_key).await?),
+            None => None,
+        };
+
+        // Wait for provider capacity if a shared scheduler is configured
+        let permit = match &self.scheduler {
+            Some(scheduler) => Some(
+                scheduler
+                    .acquire(self.config.provider_name(), request.estimated_tokens())
+                    .await?,
+            ),
+            None => None,
+        };
+
+        #[cfg(feature = "fault-injection")]
+        if let Some(injector) = &self.fault_injector {
+            let (delay, fail) = injector.next_fault();
+            if let Some(delay) = delay {
+                tokio::time::sleep(delay).await;
+            }
+            if fail {
+                warn!("Injected LLM provider failure");
+                self.increment_failed_requests().await;
+                anyhow::bail!("Injected LLM provider failure");
+            }
+        }
+
         // Make request to provider
         let response = match self.provider.complete(&request).await {
             Ok(response) => response,
@@ -378,6 +512,10 @@ impl LlmGateway {
                 return Err(e);
             }
         };
+
+        if let (Some(scheduler), Some(permit)) = (&self.scheduler, &permit) {
+            scheduler.record_usage(permit, response.usage());
+        }
         
         // Validate response
         let validated_response = self.validator.validate(response)
@@ -396,6 +534,72 @@ impl LlmGateway {
         Ok(validated_response)
     }
     
+    /// Complete a request whose reply must be JSON conforming to `schema`.
+    ///
+    /// Providers with a native JSON mode receive the schema with the request;
+    /// others get it as prompt instructions. Each reply is repaired if the
+    /// JSON is wrapped in prose or code fences, validated against the schema
+    /// and deserialized into `T`. Invalid replies are retried with the
+    /// validation errors appended to the prompt, up to
+    /// [`DEFAULT_STRUCTURED_ATTEMPTS`] attempts in total.
+    pub async fn complete_structured<T: serde::de::DeserializeOwned>(
+        &self,
+        request: LlmRequest,
+        schema: OutputSchema,
+    ) -> Result<StructuredResponse<T>> {
+        let request = request.with_response_schema(schema.clone());
+        let base_prompt = if self.provider.supports_json_schema() {
+            request.prompt().to_string()
+        } else {
+            format!("{}{}", request.prompt(), schema.instructions())
+        };
+
+        let mut errors = Vec::new();
+        for attempt in 1..=DEFAULT_STRUCTURED_ATTEMPTS {
+            let prompt = if errors.is_empty() {
+                base_prompt.clone()
+            } else {
+                format!("{}{}", base_prompt, structured::retry_feedback(&errors))
+            };
+            let response = self.complete(request.with_prompt(prompt)?).await?;
+
+            errors = match structured::parse_reply(response.content()) {
+                Some((value, repaired)) => {
+                    let mut violations = schema.validate(&value);
+                    if violations.is_empty() {
+                        match serde_json::from_value(value) {
+                            Ok(value) => {
+                                return Ok(StructuredResponse {
+                                    value,
+                                    response,
+                                    attempts: attempt,
+                                    repaired,
+                                })
+                            }
+                            Err(e) => violations
+                                .push(format!("$ does not match the expected shape: {}", e)),
+                        }
+                    }
+                    violations
+                }
+                None => vec!["reply is not valid JSON".to_string()],
+            };
+            warn!(
+                "Structured reply for schema {} rejected on attempt {}: {}",
+                schema.name,
+                attempt,
+                errors.join("; ")
+            );
+        }
+
+        anyhow::bail!(
+            "No valid {} reply after {} attempts: {}",
+            schema.name,
+            DEFAULT_STRUCTURED_ATTEMPTS,
+            errors.join("; ")
+        )
+    }
+
     /// Get current gateway metrics.
     pub async fn metrics(&self) -> GatewayMetrics {
         let metrics_guard = self.metrics.read().await;
@@ -405,6 +609,7 @@ impl LlmGateway {
             failed_requests: metrics_guard.failed_requests,
             total_tokens: metrics_guard.total_tokens,
             avg_response_time_ms: metrics_guard.avg_response_time_ms,
+            queue: self.queue.as_ref().map(RequestQueue::stats),
         }
     }
     
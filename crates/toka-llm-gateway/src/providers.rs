@@ -32,6 +32,14 @@ pub trait LlmProvider: Send + Sync {
     
     /// Get maximum token limit for this provider/model.
     fn max_tokens(&self) -> u32;
+
+    /// Whether the provider enforces [`LlmRequest::response_schema`] natively.
+    ///
+    /// Providers without a JSON mode receive the schema as prompt
+    /// instructions instead.
+    fn supports_json_schema(&self) -> bool {
+        false
+    }
     
     /// Health check for the provider.
     async fn health_check(&self) -> Result<()>;
@@ -101,6 +109,8 @@ struct OpenAiRequest {
     max_tokens: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize)]
@@ -403,6 +413,16 @@ impl LlmProvider for OpenAiProvider {
             }],
             max_tokens: request.max_tokens().map(|t| t.min(self.max_tokens)),
             temperature: request.temperature(),
+            response_format: request.response_schema().map(|schema| {
+                serde_json::json!({
+                    "type": "json_schema",
+                    "json_schema": {
+                        "name": schema.name,
+                        "schema": schema.schema,
+                        "strict": true,
+                    },
+                })
+            }),
         };
         
         // Create headers
@@ -473,6 +493,10 @@ impl LlmProvider for OpenAiProvider {
     fn max_tokens(&self) -> u32 {
         self.max_tokens
     }
+
+    fn supports_json_schema(&self) -> bool {
+        true
+    }
     
     async fn health_check(&self) -> Result<()> {
         // Simple health check by making a minimal request
@@ -0,0 +1,371 @@
+//! Priority- and session-aware admission of LLM requests.
+//!
+//! Provider rate limits make requests wait, and a plain FIFO lets whoever
+//! submits the most work take every freed slot: one agent's batch of
+//! summarization jobs can starve interactive sessions for minutes. The
+//! [`RequestQueue`] bounds the number of requests in flight and hands freed
+//! slots out by [`RequestPriority`] class first, then round-robin across the
+//! sessions waiting in that class, so each session gets its turn.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+use tracing::debug;
+
+/// Default number of requests allowed in flight at once.
+pub const DEFAULT_MAX_CONCURRENT: usize = 8;
+
+/// Scheduling class of an LLM request; earlier classes are served first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestPriority {
+    /// An agent turn someone is waiting on
+    Interactive,
+    /// Regular agent work
+    #[default]
+    Normal,
+    /// Batch work such as summarization
+    Background,
+}
+
+impl RequestPriority {
+    /// All classes, highest priority first.
+    pub const ALL: [RequestPriority; 3] = [
+        RequestPriority::Interactive,
+        RequestPriority::Normal,
+        RequestPriority::Background,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Snapshot of the queue for monitoring.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QueueStats {
+    /// Requests allowed in flight at once
+    pub max_concurrent: usize,
+    /// Requests currently in flight
+    pub in_flight: usize,
+    /// Requests waiting for a slot
+    pub depth: usize,
+    /// Waiting requests per class
+    pub depth_by_priority: BTreeMap<RequestPriority, usize>,
+    /// Sessions with at least one waiting request
+    pub sessions_waiting: usize,
+    /// Requests admitted since the queue was created
+    pub admitted: u64,
+    /// Average time admitted requests spent waiting, in milliseconds
+    pub avg_wait_ms: f64,
+}
+
+/// A request waiting for a slot.
+struct Waiter {
+    id: u64,
+    tx: oneshot::Sender<QueueTicket>,
+    enqueued_at: Instant,
+}
+
+/// Waiting requests of one class, grouped by session.
+#[derive(Default)]
+struct ClassQueue {
+    /// Sessions in the order they get their next turn
+    rotation: VecDeque<String>,
+    waiting: HashMap<String, VecDeque<Waiter>>,
+}
+
+impl ClassQueue {
+    fn push(&mut self, session: &str, waiter: Waiter) {
+        let queue = self.waiting.entry(session.to_string()).or_default();
+        if queue.is_empty() {
+            self.rotation.push_back(session.to_string());
+        }
+        queue.push_back(waiter);
+    }
+
+    /// Next waiter in round-robin order across sessions.
+    fn pop(&mut self) -> Option<Waiter> {
+        let session = self.rotation.pop_front()?;
+        let queue = self.waiting.get_mut(&session)?;
+        let waiter = queue.pop_front();
+        if queue.is_empty() {
+            self.waiting.remove(&session);
+        } else {
+            self.rotation.push_back(session);
+        }
+        waiter
+    }
+
+    fn remove(&mut self, session: &str, id: u64) {
+        if let Some(queue) = self.waiting.get_mut(session) {
+            queue.retain(|waiter| waiter.id != id);
+            if queue.is_empty() {
+                self.waiting.remove(session);
+                self.rotation.retain(|s| s != session);
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.waiting.values().map(VecDeque::len).sum()
+    }
+}
+
+#[derive(Default)]
+struct QueueState {
+    classes: [ClassQueue; 3],
+    in_flight: usize,
+    next_id: u64,
+    admitted: u64,
+    total_wait: Duration,
+}
+
+impl QueueState {
+    fn depth(&self) -> usize {
+        self.classes.iter().map(ClassQueue::len).sum()
+    }
+}
+
+struct Shared {
+    max_concurrent: usize,
+    state: Mutex<QueueState>,
+}
+
+impl Shared {
+    /// Hand the slot freed by a finished request to the next waiter.
+    fn release(self: &Arc<Self>) {
+        let mut state = self.state.lock();
+        state.in_flight -= 1;
+        self.dispatch(&mut state);
+    }
+
+    fn dispatch(self: &Arc<Self>, state: &mut QueueState) {
+        while state.in_flight < self.max_concurrent {
+            let Some(waiter) = state.classes.iter_mut().find_map(ClassQueue::pop) else {
+                return;
+            };
+            state.in_flight += 1;
+            let ticket = QueueTicket {
+                shared: Some(self.clone()),
+            };
+            match waiter.tx.send(ticket) {
+                Ok(()) => {
+                    state.admitted += 1;
+                    state.total_wait += waiter.enqueued_at.elapsed();
+                }
+                Err(mut ticket) => {
+                    // The waiter gave up; reuse the slot without re-locking
+                    ticket.shared = None;
+                    state.in_flight -= 1;
+                }
+            }
+        }
+    }
+}
+
+/// A slot in the queue, held while the request is in flight.
+///
+/// Dropping the ticket frees the slot for the next waiting request.
+pub struct QueueTicket {
+    shared: Option<Arc<Shared>>,
+}
+
+impl Drop for QueueTicket {
+    fn drop(&mut self) {
+        if let Some(shared) = self.shared.take() {
+            shared.release();
+        }
+    }
+}
+
+/// Removes a waiter whose request was cancelled before it got a slot.
+struct WaitGuard<'a> {
+    shared: &'a Arc<Shared>,
+    priority: RequestPriority,
+    session: &'a str,
+    id: u64,
+}
+
+impl Drop for WaitGuard<'_> {
+    fn drop(&mut self) {
+        self.shared.state.lock().classes[self.priority.index()].remove(self.session, self.id);
+    }
+}
+
+/// Bounded request queue with priority classes and per-session fairness.
+///
+/// Cloning the queue yields a handle to the same slots, so one queue can be
+/// shared by every gateway talking to the same provider.
+#[derive(Clone)]
+pub struct RequestQueue {
+    shared: Arc<Shared>,
+}
+
+impl Default for RequestQueue {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CONCURRENT)
+    }
+}
+
+impl std::fmt::Debug for RequestQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestQueue")
+            .field("max_concurrent", &self.shared.max_concurrent)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RequestQueue {
+    /// Create a queue admitting at most `max_concurrent` requests at once.
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                max_concurrent: max_concurrent.max(1),
+                state: Mutex::new(QueueState::default()),
+            }),
+        }
+    }
+
+    /// Wait for a slot for a request of `session` in class `priority`.
+    ///
+    /// Requests are admitted immediately while slots are free and nobody is
+    /// waiting. Cancelling the returned future gives up the place in line.
+    pub async fn acquire(&self, priority: RequestPriority, session: &str) -> Result<QueueTicket> {
+        let (id, rx) = {
+            let mut state = self.shared.state.lock();
+            if state.in_flight < self.shared.max_concurrent && state.depth() == 0 {
+                state.in_flight += 1;
+                state.admitted += 1;
+                return Ok(QueueTicket {
+                    shared: Some(self.shared.clone()),
+                });
+            }
+
+            let id = state.next_id;
+            state.next_id += 1;
+            let (tx, rx) = oneshot::channel();
+            state.classes[priority.index()].push(
+                session,
+                Waiter {
+                    id,
+                    tx,
+                    enqueued_at: Instant::now(),
+                },
+            );
+            debug!("Queued {:?} LLM request for session {} (depth {})", priority, session, state.depth());
+            (id, rx)
+        };
+
+        let _guard = WaitGuard {
+            shared: &self.shared,
+            priority,
+            session,
+            id,
+        };
+        rx.await
+            .map_err(|_| anyhow::anyhow!("Request queue dropped the waiting request"))
+    }
+
+    /// Current queue depth and admission statistics.
+    pub fn stats(&self) -> QueueStats {
+        let state = self.shared.state.lock();
+        let depth_by_priority = RequestPriority::ALL
+            .iter()
+            .map(|priority| (*priority, state.classes[priority.index()].len()))
+            .collect();
+        QueueStats {
+            max_concurrent: self.shared.max_concurrent,
+            in_flight: state.in_flight,
+            depth: state.depth(),
+            depth_by_priority,
+            sessions_waiting: state.classes.iter().map(|class| class.waiting.len()).sum(),
+            admitted: state.admitted,
+            avg_wait_ms: state.total_wait.as_secs_f64() * 1000.0 / state.admitted.max(1) as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Queue `requests` one after another and return the order they are admitted in.
+    async fn admission_order(queue: &RequestQueue, requests: &[(RequestPriority, &str)]) -> Vec<usize> {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut handles = Vec::new();
+        for (index, (priority, session)) in requests.iter().enumerate() {
+            let queue = queue.clone();
+            let tx = tx.clone();
+            let (priority, session) = (*priority, session.to_string());
+            handles.push(tokio::spawn(async move {
+                let ticket = queue.acquire(priority, &session).await.unwrap();
+                tx.send(index).unwrap();
+                drop(ticket);
+            }));
+            // Let the task enqueue before the next one
+            tokio::task::yield_now().await;
+        }
+        drop(tx);
+
+        let mut order = Vec::new();
+        while let Some(index) = rx.recv().await {
+            order.push(index);
+        }
+        order
+    }
+
+    #[tokio::test]
+    async fn test_priority_and_session_fairness() {
+        use RequestPriority::*;
+
+        let queue = RequestQueue::new(1);
+        let blocker = queue.acquire(Normal, "blocker").await.unwrap();
+
+        let requests = [
+            (Background, "batch"),
+            (Background, "batch"),
+            (Background, "batch"),
+            (Background, "other"),
+            (Interactive, "chat"),
+        ];
+        let order = tokio::spawn({
+            let queue = queue.clone();
+            async move { admission_order(&queue, &requests).await }
+        });
+        while queue.stats().depth < requests.len() {
+            tokio::task::yield_now().await;
+        }
+        let stats = queue.stats();
+        assert_eq!(stats.depth_by_priority[&Background], 4);
+        assert_eq!(stats.sessions_waiting, 3);
+
+        drop(blocker);
+        // Interactive first, then the background sessions take turns
+        assert_eq!(order.await.unwrap(), vec![4, 0, 3, 1, 2]);
+        assert_eq!(queue.stats().in_flight, 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter_releases_its_place() {
+        let queue = RequestQueue::new(1);
+        let blocker = queue.acquire(RequestPriority::Normal, "a").await.unwrap();
+
+        let waiting = tokio::time::timeout(
+            Duration::from_millis(10),
+            queue.acquire(RequestPriority::Normal, "b"),
+        )
+        .await;
+        assert!(waiting.is_err());
+        assert_eq!(queue.stats().depth, 0);
+
+        drop(blocker);
+        let _ticket = queue.acquire(RequestPriority::Background, "c").await.unwrap();
+        assert_eq!(queue.stats().in_flight, 1);
+    }
+}
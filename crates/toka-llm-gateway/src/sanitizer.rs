@@ -156,7 +156,12 @@ impl RequestSanitizer {
         
         // Create new request with sanitized prompt
         let sanitized_request = LlmRequest::new(sanitized_prompt)?
-            .with_max_tokens(request.max_tokens().unwrap_or(4096));
+            .with_max_tokens(request.max_tokens().unwrap_or(4096))
+            .with_priority(request.priority());
+        let sanitized_request = match request.response_schema() {
+            Some(schema) => sanitized_request.with_response_schema(schema.clone()),
+            None => sanitized_request,
+        };
         
         let sanitized_request = if let Some(temp) = request.temperature() {
             sanitized_request.with_temperature(temp)?
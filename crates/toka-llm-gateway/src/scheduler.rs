@@ -0,0 +1,384 @@
+//! Cost-aware scheduling of LLM requests across agents.
+//!
+//! The per-agent rate limiter in [`LlmGateway`](crate::LlmGateway) protects the
+//! gateway from a single noisy agent, but it does nothing to stop many agents
+//! from hitting the same provider at once. This module provides a shared
+//! [`LlmScheduler`] holding one token bucket per provider (requests and tokens
+//! per minute) plus an optional cost budget. Requests wait for capacity instead
+//! of being sent immediately, which spreads parallel agent work over time and
+//! keeps the fleet under the provider's limits.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::TokenUsage;
+
+/// Longest single wait before re-evaluating bucket state.
+const MAX_WAIT_SLICE: Duration = Duration::from_secs(5);
+
+/// Rate and cost limits for a single provider.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderLimits {
+    /// Maximum requests per minute across all agents
+    pub requests_per_minute: u32,
+    /// Maximum tokens (prompt + completion) per minute across all agents
+    pub tokens_per_minute: u32,
+    /// Cost in USD per 1,000 tokens, used for budget accounting
+    pub cost_per_1k_tokens: f64,
+    /// Total spend allowed for this provider, in USD (`None` = unlimited)
+    pub cost_budget: Option<f64>,
+}
+
+impl Default for ProviderLimits {
+    fn default() -> Self {
+        Self {
+            requests_per_minute: crate::DEFAULT_RATE_LIMIT,
+            tokens_per_minute: 90_000,
+            cost_per_1k_tokens: 0.0,
+            cost_budget: None,
+        }
+    }
+}
+
+/// Snapshot of a provider's scheduling state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderScheduleStats {
+    /// Requests currently available without waiting
+    pub available_requests: f64,
+    /// Tokens currently available without waiting
+    pub available_tokens: f64,
+    /// Total tokens recorded against this provider
+    pub tokens_used: u64,
+    /// Total spend recorded against this provider, in USD
+    pub cost_spent: f64,
+    /// Number of times a request had to wait for capacity
+    pub throttled_requests: u64,
+}
+
+/// Proof that capacity was reserved for a request.
+///
+/// Pass the permit back to [`LlmScheduler::record_usage`] once the provider has
+/// responded so the scheduler can reconcile the estimate with actual usage.
+#[derive(Debug, Clone)]
+pub struct SchedulePermit {
+    provider: String,
+    estimated_tokens: u32,
+    waited: Duration,
+}
+
+impl SchedulePermit {
+    /// Provider the permit was issued for.
+    pub fn provider(&self) -> &str {
+        &self.provider
+    }
+
+    /// Tokens reserved when the permit was issued.
+    pub fn estimated_tokens(&self) -> u32 {
+        self.estimated_tokens
+    }
+
+    /// Time spent waiting for capacity.
+    pub fn waited(&self) -> Duration {
+        self.waited
+    }
+}
+
+/// Classic token bucket refilled continuously at a fixed rate.
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    available: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn per_minute(capacity: u32) -> Self {
+        let capacity = capacity.max(1) as f64;
+        Self {
+            capacity,
+            available: capacity,
+            refill_per_sec: capacity / 60.0,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.available = (self.available + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    /// Time until `amount` units are available (zero if available now).
+    fn wait_for(&self, amount: f64) -> Duration {
+        if self.available >= amount {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((amount - self.available) / self.refill_per_sec)
+        }
+    }
+
+    /// Adjust the bucket by `delta` units. Negative balances are allowed so that
+    /// under-estimated requests are paid back by later callers.
+    fn adjust(&mut self, delta: f64) {
+        self.available = (self.available + delta).min(self.capacity);
+    }
+}
+
+/// Per-provider scheduling state.
+#[derive(Debug)]
+struct ProviderState {
+    limits: ProviderLimits,
+    requests: TokenBucket,
+    tokens: TokenBucket,
+    tokens_used: u64,
+    cost_spent: f64,
+    throttled_requests: u64,
+}
+
+impl ProviderState {
+    fn new(limits: ProviderLimits) -> Self {
+        Self {
+            requests: TokenBucket::per_minute(limits.requests_per_minute),
+            tokens: TokenBucket::per_minute(limits.tokens_per_minute),
+            limits,
+            tokens_used: 0,
+            cost_spent: 0.0,
+            throttled_requests: 0,
+        }
+    }
+
+    fn cost_of(&self, tokens: u32) -> f64 {
+        tokens as f64 / 1000.0 * self.limits.cost_per_1k_tokens
+    }
+}
+
+/// Shared scheduler enforcing provider rate limits and cost budgets.
+///
+/// A single scheduler is meant to be shared (via `Arc`) by every gateway and
+/// agent talking to the same providers. Providers without registered limits
+/// are not throttled.
+#[derive(Debug, Default)]
+pub struct LlmScheduler {
+    providers: Mutex<HashMap<String, ProviderState>>,
+}
+
+impl LlmScheduler {
+    /// Create a scheduler with no registered providers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register (or replace) the limits for a provider.
+    pub fn with_provider(self, provider: impl Into<String>, limits: ProviderLimits) -> Self {
+        self.set_limits(provider, limits);
+        self
+    }
+
+    /// Register or replace the limits for a provider at runtime.
+    ///
+    /// Replacing limits resets the buckets but keeps recorded usage and spend.
+    pub fn set_limits(&self, provider: impl Into<String>, limits: ProviderLimits) {
+        let mut providers = self.providers.lock();
+        let provider = provider.into();
+        let mut state = ProviderState::new(limits);
+        if let Some(previous) = providers.get(&provider) {
+            state.tokens_used = previous.tokens_used;
+            state.cost_spent = previous.cost_spent;
+            state.throttled_requests = previous.throttled_requests;
+        }
+        providers.insert(provider, state);
+    }
+
+    /// Reserve capacity for a request of roughly `estimated_tokens` tokens.
+    ///
+    /// Waits until both the request and token buckets for `provider` have
+    /// capacity. Fails immediately if the request can never be satisfied
+    /// (larger than the per-minute token limit) or if it would exceed the
+    /// provider's cost budget.
+    pub async fn acquire(&self, provider: &str, estimated_tokens: u32) -> Result<SchedulePermit> {
+        let started = Instant::now();
+        let mut throttled = false;
+
+        loop {
+            let wait = {
+                let mut providers = self.providers.lock();
+                let Some(state) = providers.get_mut(provider) else {
+                    return Ok(SchedulePermit {
+                        provider: provider.to_string(),
+                        estimated_tokens,
+                        waited: started.elapsed(),
+                    });
+                };
+
+                if estimated_tokens as f64 > state.tokens.capacity {
+                    anyhow::bail!(
+                        "Request of {} tokens exceeds {} tokens/minute limit for provider {}",
+                        estimated_tokens,
+                        state.limits.tokens_per_minute,
+                        provider
+                    );
+                }
+
+                if let Some(budget) = state.limits.cost_budget {
+                    let projected = state.cost_spent + state.cost_of(estimated_tokens);
+                    if projected > budget {
+                        warn!("Cost budget exhausted for provider {}", provider);
+                        anyhow::bail!(
+                            "Cost budget exceeded for provider {}: ${:.4} spent of ${:.4}",
+                            provider,
+                            state.cost_spent,
+                            budget
+                        );
+                    }
+                }
+
+                let now = Instant::now();
+                state.requests.refill(now);
+                state.tokens.refill(now);
+
+                let wait = state
+                    .requests
+                    .wait_for(1.0)
+                    .max(state.tokens.wait_for(estimated_tokens as f64));
+
+                if wait.is_zero() {
+                    state.requests.adjust(-1.0);
+                    state.tokens.adjust(-(estimated_tokens as f64));
+                    return Ok(SchedulePermit {
+                        provider: provider.to_string(),
+                        estimated_tokens,
+                        waited: started.elapsed(),
+                    });
+                }
+
+                if !throttled {
+                    state.throttled_requests += 1;
+                    throttled = true;
+                }
+                wait
+            };
+
+            debug!("Throttling request to {} for {:?}", provider, wait);
+            tokio::time::sleep(wait.min(MAX_WAIT_SLICE)).await;
+        }
+    }
+
+    /// Reconcile a permit with the provider's reported token usage.
+    ///
+    /// Tokens are charged to the bucket for the difference between the
+    /// estimate and the actual usage, and the cost is added to the spend.
+    pub fn record_usage(&self, permit: &SchedulePermit, usage: &TokenUsage) {
+        let mut providers = self.providers.lock();
+        if let Some(state) = providers.get_mut(&permit.provider) {
+            let delta = permit.estimated_tokens as f64 - usage.total_tokens as f64;
+            state.tokens.adjust(delta);
+            state.tokens_used += usage.total_tokens as u64;
+            state.cost_spent += state.cost_of(usage.total_tokens);
+        }
+    }
+
+    /// Return the current scheduling state for `provider`, if registered.
+    pub fn stats(&self, provider: &str) -> Option<ProviderScheduleStats> {
+        let mut providers = self.providers.lock();
+        let state = providers.get_mut(provider)?;
+        let now = Instant::now();
+        state.requests.refill(now);
+        state.tokens.refill(now);
+
+        Some(ProviderScheduleStats {
+            available_requests: state.requests.available,
+            available_tokens: state.tokens.available,
+            tokens_used: state.tokens_used,
+            cost_spent: state.cost_spent,
+            throttled_requests: state.throttled_requests,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(total_tokens: u32) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens: total_tokens / 2,
+            completion_tokens: total_tokens - total_tokens / 2,
+            total_tokens,
+        }
+    }
+
+    #[test]
+    fn test_estimated_tokens_saturates() {
+        let request = crate::LlmRequest::new("Explain Rust ownership").unwrap().with_max_tokens(u32::MAX);
+        assert_eq!(request.estimated_tokens(), u32::MAX);
+    }
+
+    #[tokio::test]
+    async fn test_unregistered_provider_is_not_throttled() {
+        let scheduler = LlmScheduler::new();
+        let permit = scheduler.acquire("unknown", 1_000_000).await.unwrap();
+        assert_eq!(permit.provider(), "unknown");
+        assert!(scheduler.stats("unknown").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_requests_wait_when_bucket_is_empty() {
+        let scheduler = LlmScheduler::new().with_provider(
+            "openai",
+            ProviderLimits {
+                requests_per_minute: 600, // one every 100ms once drained
+                ..ProviderLimits::default()
+            },
+        );
+
+        // Drain the bucket
+        for _ in 0..600 {
+            scheduler.acquire("openai", 1).await.unwrap();
+        }
+
+        let permit = scheduler.acquire("openai", 1).await.unwrap();
+        assert!(permit.waited() >= Duration::from_millis(50));
+        assert_eq!(scheduler.stats("openai").unwrap().throttled_requests, 1);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_request_is_rejected() {
+        let scheduler = LlmScheduler::new().with_provider(
+            "anthropic",
+            ProviderLimits {
+                tokens_per_minute: 1_000,
+                ..ProviderLimits::default()
+            },
+        );
+
+        assert!(scheduler.acquire("anthropic", 5_000).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cost_budget_is_enforced() {
+        let scheduler = LlmScheduler::new().with_provider(
+            "openai",
+            ProviderLimits {
+                cost_per_1k_tokens: 1.0,
+                cost_budget: Some(2.0),
+                ..ProviderLimits::default()
+            },
+        );
+
+        let permit = scheduler.acquire("openai", 1_000).await.unwrap();
+        scheduler.record_usage(&permit, &usage(1_500));
+        assert!((scheduler.stats("openai").unwrap().cost_spent - 1.5).abs() < f64::EPSILON);
+
+        let err = scheduler.acquire("openai", 1_000).await.unwrap_err();
+        assert!(err.to_string().contains("Cost budget exceeded"));
+
+        // Smaller requests that fit the remaining budget still go through
+        scheduler.acquire("openai", 100).await.unwrap();
+    }
+}
@@ -0,0 +1,325 @@
+//! Schema-constrained (JSON mode) generation.
+//!
+//! Agents that need data rather than prose attach an [`OutputSchema`] to a
+//! request and call
+//! [`LlmGateway::complete_structured`](crate::LlmGateway::complete_structured).
+//! Providers with a native JSON mode receive the schema directly; for the
+//! others the schema is spelled out in the prompt. Replies are parsed,
+//! repaired when the model wrapped the JSON in prose or code fences,
+//! validated against the schema and deserialized. Invalid replies are retried
+//! with the validation errors fed back to the model.
+//!
+//! Validation covers the commonly used subset of JSON Schema: `type`,
+//! `properties`, `required`, `additionalProperties: false`, `items`, `enum`,
+//! `minimum`/`maximum` and `minLength`/`maxLength`. Other keywords are
+//! accepted and ignored.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::LlmResponse;
+
+/// Default number of attempts before structured generation gives up.
+pub const DEFAULT_STRUCTURED_ATTEMPTS: u32 = 3;
+
+/// Most validation errors fed back to the model on a retry.
+const MAX_FEEDBACK_ERRORS: usize = 5;
+
+/// JSON Schema a reply must conform to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputSchema {
+    /// Schema name, passed to providers that require one
+    pub name: String,
+    /// The JSON Schema document
+    pub schema: Value,
+}
+
+impl OutputSchema {
+    /// Create a named schema.
+    pub fn new(name: impl Into<String>, schema: Value) -> Self {
+        Self {
+            name: name.into(),
+            schema,
+        }
+    }
+
+    /// Check `value` against the schema, returning every violation found.
+    pub fn validate(&self, value: &Value) -> Vec<String> {
+        let mut errors = Vec::new();
+        validate_value(value, &self.schema, "$", &mut errors);
+        errors
+    }
+
+    /// Prompt suffix instructing providers without a native JSON mode.
+    pub fn instructions(&self) -> String {
+        format!(
+            "\n\nRespond only with a single JSON value, without prose or code fences, \
+             that conforms to this JSON Schema:\n{}",
+            self.schema
+        )
+    }
+}
+
+/// Typed result of a structured completion.
+#[derive(Debug, Clone)]
+pub struct StructuredResponse<T> {
+    /// The deserialized reply
+    pub value: T,
+    /// The provider response the value was taken from
+    pub response: LlmResponse,
+    /// Attempts made, including the successful one
+    pub attempts: u32,
+    /// Whether the reply had to be repaired before it parsed
+    pub repaired: bool,
+}
+
+/// Parse a model reply as JSON, repairing common formatting mistakes.
+///
+/// Returns the value and whether repair was needed. Repairs strip Markdown
+/// code fences and surrounding prose and drop trailing commas.
+pub fn parse_reply(content: &str) -> Option<(Value, bool)> {
+    let trimmed = content.trim();
+    if let Ok(value) = serde_json::from_str(trimmed) {
+        return Some((value, false));
+    }
+
+    let candidate = strip_code_fence(trimmed).unwrap_or(trimmed);
+    let candidate = extract_json_block(candidate).unwrap_or(candidate);
+    if let Ok(value) = serde_json::from_str(candidate) {
+        return Some((value, true));
+    }
+    serde_json::from_str(&remove_trailing_commas(candidate))
+        .ok()
+        .map(|value| (value, true))
+}
+
+/// Feedback appended to the prompt after an invalid reply.
+pub fn retry_feedback(errors: &[String]) -> String {
+    let listed: Vec<&str> = errors
+        .iter()
+        .take(MAX_FEEDBACK_ERRORS)
+        .map(String::as_str)
+        .collect();
+    format!(
+        "\n\nYour previous reply was rejected: {}. Reply again with only the corrected JSON.",
+        listed.join("; ")
+    )
+}
+
+fn strip_code_fence(text: &str) -> Option<&str> {
+    let start = text.find("```")?;
+    let body = &text[start + 3..];
+    // Skip the language tag on the opening fence
+    let body = &body[body.find('\n')? + 1..];
+    let end = body.find("```")?;
+    Some(body[..end].trim())
+}
+
+/// The first balanced `{...}` or `[...]` block in `text`.
+fn extract_json_block(text: &str) -> Option<&str> {
+    let start = text.find(['{', '['])?;
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (offset, c) in text[start..].char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&text[start..start + offset + 1]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn remove_trailing_commas(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    let chars: Vec<char> = text.chars().collect();
+    for (i, &c) in chars.iter().enumerate() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            let next = chars[i + 1..].iter().find(|c| !c.is_whitespace());
+            if matches!(next, Some('}') | Some(']')) {
+                continue;
+            }
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn type_matches(value: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        // Unknown types are not enforced
+        _ => true,
+    }
+}
+
+fn validate_value(value: &Value, schema: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        return;
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| type_matches(value, t)) {
+            errors.push(format!("{} should be of type {}", path, allowed.join(" or ")));
+            return;
+        }
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        if !options.contains(value) {
+            errors.push(format!("{} should be one of {}", path, Value::Array(options.clone())));
+        }
+    }
+
+    if let Some(number) = value.as_f64() {
+        if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64) {
+            if number < minimum {
+                errors.push(format!("{} should be at least {}", path, minimum));
+            }
+        }
+        if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64) {
+            if number > maximum {
+                errors.push(format!("{} should be at most {}", path, maximum));
+            }
+        }
+    }
+
+    if let Some(text) = value.as_str() {
+        let length = text.chars().count() as u64;
+        if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+            if length < min {
+                errors.push(format!("{} should be at least {} characters", path, min));
+            }
+        }
+        if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+            if length > max {
+                errors.push(format!("{} should be at most {} characters", path, max));
+            }
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for field in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(field) {
+                    errors.push(format!("{} is missing required field \"{}\"", path, field));
+                }
+            }
+        }
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (field, field_value) in object {
+            let field_path = format!("{}.{}", path, field);
+            match properties.and_then(|properties| properties.get(field)) {
+                Some(field_schema) => {
+                    validate_value(field_value, field_schema, &field_path, errors)
+                }
+                None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                    errors.push(format!("{} is not an allowed field", field_path));
+                }
+                None => {}
+            }
+        }
+    }
+
+    if let (Some(items), Some(item_schema)) = (value.as_array(), schema.get("items")) {
+        for (index, item) in items.iter().enumerate() {
+            validate_value(item, item_schema, &format!("{}[{}]", path, index), errors);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn task_schema() -> OutputSchema {
+        OutputSchema::new(
+            "task",
+            json!({
+                "type": "object",
+                "properties": {
+                    "title": { "type": "string", "minLength": 1 },
+                    "priority": { "enum": ["low", "medium", "high"] },
+                    "estimate_hours": { "type": "number", "minimum": 0 },
+                    "tags": { "type": "array", "items": { "type": "string" } }
+                },
+                "required": ["title", "priority"],
+                "additionalProperties": false
+            }),
+        )
+    }
+
+    #[test]
+    fn test_schema_validation() {
+        let schema = task_schema();
+        assert!(schema
+            .validate(&json!({ "title": "Write docs", "priority": "high", "tags": ["docs"] }))
+            .is_empty());
+
+        let errors = schema.validate(&json!({
+            "title": "",
+            "priority": "urgent",
+            "estimate_hours": -1,
+            "tags": ["docs", 3],
+            "owner": "sam"
+        }));
+        assert_eq!(errors.len(), 5, "{:?}", errors);
+        assert!(errors.iter().any(|e| e.contains("$.tags[1] should be of type string")));
+        assert!(errors.iter().any(|e| e.contains("$.owner is not an allowed field")));
+        assert_eq!(schema.validate(&json!({ "title": "x" })).len(), 1);
+        assert_eq!(schema.validate(&json!([])).len(), 1);
+    }
+
+    #[test]
+    fn test_reply_repair() {
+        assert_eq!(parse_reply(r#" {"a": 1} "#), Some((json!({ "a": 1 }), false)));
+        assert_eq!(
+            parse_reply("Here you go:\n```json\n{\"a\": [1, 2,],}\n```\nAnything else?"),
+            Some((json!({ "a": [1, 2] }), true))
+        );
+        assert_eq!(
+            parse_reply(r#"The answer is {"text": "braces } and, ] inside"} as requested."#),
+            Some((json!({ "text": "braces } and, ] inside" }), true))
+        );
+        assert_eq!(parse_reply("no json here"), None);
+    }
+}
@@ -30,7 +30,7 @@ use std::sync::Arc;
 
 use anyhow::{Context, Result};
 use axum::{
-    extract::State,
+    extract::{Path, State},
     http::StatusCode,
     response::Json,
     routing::get,
@@ -45,8 +45,8 @@ use tracing::{error, info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 use toka_auth::JwtHs256Validator;
-use toka_llm_gateway::{Config as LlmConfig, LlmGateway};
-use toka_orchestration::{OrchestrationConfig, OrchestrationEngine};
+use toka_llm_gateway::{Config as LlmConfig, LlmGateway, LlmScheduler, ProviderLimits};
+use toka_orchestration::{OrchestrationConfig, OrchestrationEngine, SessionProgress, WorkstreamProgress};
 use toka_runtime::RuntimeManager;
 use toka_kernel;
 use toka_bus_core;
@@ -164,14 +164,23 @@ async fn main() -> Result<()> {
     let event_bus = Arc::new(toka_bus_core::InMemoryBus::new(1024));
     let kernel = toka_kernel::Kernel::new(world_state, auth, event_bus);
     let runtime_kernel = toka_runtime::RuntimeKernel::new(kernel);
-    let runtime = Arc::new(RuntimeManager::new(runtime_kernel).await?);
+    let runtime = Arc::new(RuntimeManager::with_default_engines(runtime_kernel).await?);
     info!("Toka runtime initialized");
 
     // Initialize LLM gateway
     let llm_gateway = match LlmConfig::from_env() {
         Ok(llm_config) => {
             info!("Initializing LLM gateway with provider: {}", llm_config.provider_name());
-            Some(Arc::new(LlmGateway::new(llm_config).await?))
+            // Share provider limits across all agents so parallel workstreams
+            // are spread over time instead of tripping provider 429s together
+            let scheduler = Arc::new(LlmScheduler::new().with_provider(
+                llm_config.provider_name(),
+                ProviderLimits {
+                    requests_per_minute: llm_config.rate_limit(),
+                    ..ProviderLimits::default()
+                },
+            ));
+            Some(Arc::new(LlmGateway::new(llm_config).await?.with_scheduler(scheduler)))
         }
         Err(e) => {
             warn!("Failed to initialize LLM gateway: {}. Continuing without LLM integration.", e);
@@ -253,6 +262,8 @@ fn create_app(state: ServiceState) -> Router {
         .route("/health", get(health_check))
         .route("/status", get(orchestration_status))
         .route("/agents", get(list_agents))
+        .route("/progress", get(session_progress))
+        .route("/progress/:workstream", get(workstream_progress))
         .with_state(state)
         .layer(
             ServiceBuilder::new()
@@ -292,6 +303,21 @@ async fn orchestration_status(State(state): State<ServiceState>) -> Result<Json<
     Ok(Json(response))
 }
 
+async fn session_progress(State(state): State<ServiceState>) -> Result<Json<SessionProgress>, StatusCode> {
+    Ok(Json(state.orchestration_engine.session_progress().await))
+}
+
+async fn workstream_progress(
+    State(state): State<ServiceState>,
+    Path(workstream): Path<String>,
+) -> Result<Json<WorkstreamProgress>, StatusCode> {
+    state.orchestration_engine
+        .workstream_progress(&workstream)
+        .await
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
 async fn list_agents(State(state): State<ServiceState>) -> Result<Json<Vec<String>>, StatusCode> {
     let agent_names: Vec<String> = state.config.agents
         .iter()
@@ -12,8 +12,15 @@ use tracing::{error, info, warn};
 use tracing_subscriber;
 
 // MockTokenValidator is defined locally in the mock_auth module below
+use toka_bus_core::InMemoryBus;
+use toka_kernel::{Kernel, WorldState};
 use toka_orchestration::{OrchestrationConfig, OrchestrationEngine};
-use toka_runtime::{Runtime, RuntimeConfig};
+use toka_runtime::{RuntimeBuilder, RuntimeKernel};
+use toka_types::{
+    AgentCapabilities, AgentConfig, AgentDependencies, AgentMetadata, AgentObjective, AgentPriority,
+    AgentSpecConfig, AgentTasks, ReportingConfig, ReportingFrequency, ResourceLimits, SecurityConfig,
+    TaskConfig, TaskPriority,
+};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -25,13 +32,19 @@ async fn main() -> Result<()> {
     // Create mock configuration for demonstration
     let config = create_demo_configuration()?;
 
-    // Initialize Toka runtime
+    // Initialize Toka runtime; agents report progress over its event bus
+    let bus = Arc::new(InMemoryBus::new(1024));
+    let kernel = Kernel::new(
+        WorldState::default(),
+        Arc::new(mock_auth::MockTokenValidator::new()),
+        bus.clone(),
+    );
     let runtime = Arc::new(
-        Runtime::new(
-            RuntimeConfig::default(),
-            Arc::new(mock_auth::MockTokenValidator::new()),
-        )
-        .await?,
+        RuntimeBuilder::new(RuntimeKernel::new(kernel))
+            .with_default_engines()
+            .with_event_bus(bus)
+            .build()
+            .await?,
     );
 
     info!("Toka runtime initialized");
@@ -279,6 +292,7 @@ fn create_demo_configuration() -> Result<OrchestrationConfig> {
                     timeout: "30m".to_string(),
                 },
             },
+            placement: Default::default(),
         },
         // Testing infrastructure agent (depends on build system)
         AgentConfig {
@@ -340,6 +354,7 @@ fn create_demo_configuration() -> Result<OrchestrationConfig> {
                     timeout: "1h".to_string(),
                 },
             },
+            placement: Default::default(),
         },
         // Parallel development agents
         AgentConfig {
@@ -398,6 +413,7 @@ fn create_demo_configuration() -> Result<OrchestrationConfig> {
                     timeout: "45m".to_string(),
                 },
             },
+            placement: Default::default(),
         },
     ];
 
@@ -405,6 +421,7 @@ fn create_demo_configuration() -> Result<OrchestrationConfig> {
         agents,
         global_timeout: Duration::from_secs(1800), // 30 minutes
         max_concurrent_agents: 5,
+        budget: Default::default(),
     })
 }
 
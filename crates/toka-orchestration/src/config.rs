@@ -3,6 +3,10 @@
 //! This module provides functionality to load agent configurations from YAML files
 //! and validate them against the expected schema. It handles both individual agent
 //! configurations and bulk loading from directories.
+//!
+//! A loader can target an environment: overlays from
+//! `overlays/<environment>/` are merged over the base files and `${VAR}`
+//! references are substituted before parsing, see [`crate::overlay`].
 
 use std::collections::HashMap;
 use std::fs;
@@ -13,7 +17,10 @@ use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use tracing::{debug, info, warn};
 
+use crate::overlay::{interpolate, merge_yaml, overlay_path};
+use crate::signing::{ConfigVerifier, SignatureStatus};
 use crate::AgentConfig;
+use toka_agent_runtime::SessionBudget;
 
 /// Main orchestration configuration.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -24,6 +31,9 @@ pub struct OrchestrationConfig {
     pub global_timeout: Duration,
     /// Maximum number of concurrent agents
     pub max_concurrent_agents: usize,
+    /// Token, time and cost limits exposed to agents as planning hints
+    #[serde(default)]
+    pub budget: SessionBudget,
 }
 
 /// Agent configuration loader.
@@ -32,6 +42,12 @@ pub struct AgentConfigLoader {
     base_dir: PathBuf,
     /// Loaded configurations cache
     cache: HashMap<String, AgentConfig>,
+    /// Signature verification, if enabled
+    verifier: Option<ConfigVerifier>,
+    /// Environment whose overlays are applied, if any
+    environment: Option<String>,
+    /// Variables taking precedence over the process environment
+    variables: HashMap<String, String>,
 }
 
 impl AgentConfigLoader {
@@ -40,9 +56,36 @@ impl AgentConfigLoader {
         Self {
             base_dir: base_dir.as_ref().to_path_buf(),
             cache: HashMap::new(),
+            verifier: None,
+            environment: None,
+            variables: HashMap::new(),
         }
     }
 
+    /// Verify detached signatures before accepting any configuration.
+    pub fn with_verifier(mut self, verifier: ConfigVerifier) -> Self {
+        self.verifier = Some(verifier);
+        self
+    }
+
+    /// Apply the overlays of `environment`, e.g. `dev` or `prod`.
+    pub fn with_environment(mut self, environment: impl Into<String>) -> Self {
+        self.environment = Some(environment.into());
+        self
+    }
+
+    /// Resolve `${VAR}` references from `variables` before the process
+    /// environment.
+    pub fn with_variables(mut self, variables: HashMap<String, String>) -> Self {
+        self.variables = variables;
+        self
+    }
+
+    /// Environment whose overlays are applied, if any.
+    pub fn environment(&self) -> Option<&str> {
+        self.environment.as_deref()
+    }
+
     /// Load all agent configurations from the base directory.
     pub fn load_all(&mut self) -> Result<Vec<AgentConfig>> {
         info!("Loading agent configurations from: {}", self.base_dir.display());
@@ -94,10 +137,24 @@ impl AgentConfigLoader {
     pub fn load_config_file(&mut self, path: &Path) -> Result<AgentConfig> {
         debug!("Loading agent configuration file: {}", path.display());
 
-        let contents = fs::read_to_string(path)
-            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        let mut document = self.read_verified(path)?;
+
+        if let Some(overlay) = self
+            .environment
+            .as_deref()
+            .and_then(|environment| overlay_path(path, environment))
+            .filter(|overlay| overlay.exists())
+        {
+            debug!("Applying overlay {} to {}", overlay.display(), path.display());
+            let patch = self.read_verified(&overlay)?;
+            merge_yaml(&mut document, patch);
+        }
+
+        let lookup = |name: &str| self.variables.get(name).cloned().or_else(|| std::env::var(name).ok());
+        interpolate(&mut document, &lookup)
+            .with_context(|| format!("Failed to resolve variables in: {}", path.display()))?;
 
-        let config: AgentConfig = serde_yaml::from_str(&contents)
+        let config: AgentConfig = serde_yaml::from_value(document)
             .with_context(|| format!("Failed to parse YAML file: {}", path.display()))?;
 
         // Validate the configuration
@@ -110,6 +167,28 @@ impl AgentConfigLoader {
         Ok(config)
     }
 
+    /// Read `path` as YAML after verifying its signature.
+    fn read_verified(&self, path: &Path) -> Result<serde_yaml::Value> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+
+        // Verify the exact bytes on disk before trusting anything in them
+        if let Some(verifier) = &self.verifier {
+            match verifier.verify(path, contents.as_bytes())? {
+                SignatureStatus::Verified { key_index } => {
+                    debug!("Verified signature of {} with trusted key {}", path.display(), key_index);
+                }
+                SignatureStatus::Unsigned => {
+                    warn!("Loading unsigned agent configuration: {}", path.display());
+                }
+                SignatureStatus::Skipped => {}
+            }
+        }
+
+        serde_yaml::from_str(&contents)
+            .with_context(|| format!("Failed to parse YAML file: {}", path.display()))
+    }
+
     /// Get a cached configuration by name.
     pub fn get_config(&self, name: &str) -> Option<&AgentConfig> {
         self.cache.get(name)
@@ -201,6 +280,38 @@ impl OrchestrationConfig {
             agents,
             global_timeout: Duration::from_secs(3600), // 1 hour default
             max_concurrent_agents: 10,
+            budget: SessionBudget::default(),
+        })
+    }
+
+    /// Load orchestration configuration from a directory, verifying signatures.
+    ///
+    /// Configs that fail verification are skipped like any other invalid config.
+    pub fn from_directory_with_verifier(
+        dir: impl AsRef<Path>,
+        verifier: ConfigVerifier,
+    ) -> Result<Self> {
+        let mut loader = AgentConfigLoader::new(dir).with_verifier(verifier);
+        let agents = loader.load_all()?;
+
+        Ok(Self {
+            agents,
+            ..Self::default()
+        })
+    }
+
+    /// Load orchestration configuration from a directory for `environment`,
+    /// applying its overlays.
+    pub fn from_directory_for_environment(
+        dir: impl AsRef<Path>,
+        environment: impl Into<String>,
+    ) -> Result<Self> {
+        let mut loader = AgentConfigLoader::new(dir).with_environment(environment);
+        let agents = loader.load_all()?;
+
+        Ok(Self {
+            agents,
+            ..Self::default()
         })
     }
 
@@ -217,6 +328,7 @@ impl OrchestrationConfig {
             agents,
             global_timeout,
             max_concurrent_agents,
+            budget: SessionBudget::default(),
         })
     }
 
@@ -265,6 +377,7 @@ impl Default for OrchestrationConfig {
             agents: Vec::new(),
             global_timeout: Duration::from_secs(3600),
             max_concurrent_agents: 10,
+            budget: SessionBudget::default(),
         }
     }
 }
@@ -327,6 +440,7 @@ mod tests {
                     timeout: "1h".to_string(),
                 },
             },
+            placement: Default::default(),
         };
 
         assert!(loader.validate_config(&invalid_config).is_err());
@@ -401,4 +515,74 @@ security:
         assert_eq!(config.agents.len(), 1);
         assert_eq!(config.agents[0].metadata.name, "test-agent");
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_environment_overlay_and_variables() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_dir = temp_dir.path();
+        fs::create_dir_all(config_dir.join("overlays/prod")).unwrap();
+
+        let base_yaml = r#"
+metadata:
+  name: "overlay-agent"
+  version: "v1.0"
+  created: "2024-01-01"
+  workstream: "testing"
+  branch: "${AGENT_BRANCH}"
+spec:
+  name: "Overlay Agent"
+  domain: "testing"
+  priority: "medium"
+capabilities:
+  primary: ["testing"]
+  secondary: []
+objectives:
+  - description: "Test objective"
+    deliverable: "Test deliverable"
+    validation: "Test validation"
+tasks:
+  default:
+    - description: "Test task"
+      priority: "medium"
+dependencies:
+  required: {}
+  optional: {}
+reporting:
+  frequency: "daily"
+  channels: ["test"]
+  metrics: {}
+security:
+  sandbox: true
+  capabilities_required: ["test"]
+  resource_limits:
+    max_memory: "${MAX_MEMORY:-100MB}"
+    max_cpu: "50%"
+    timeout: "1h"
+"#;
+        let path = config_dir.join("overlay-agent.yaml");
+        fs::write(&path, base_yaml).unwrap();
+        fs::write(
+            config_dir.join("overlays/prod/overlay-agent.yaml"),
+            "security:\n  resource_limits:\n    max_cpu: \"90%\"\n",
+        )
+        .unwrap();
+
+        let mut loader = AgentConfigLoader::new(config_dir).with_environment("prod");
+        let err = loader.load_config_file(&path).unwrap_err();
+        assert!(format!("{:#}", err).contains("AGENT_BRANCH"));
+
+        let variables = HashMap::from([("AGENT_BRANCH".to_string(), "release".to_string())]);
+        let mut loader = loader.with_variables(variables.clone());
+        let config = loader.load_config_file(&path).unwrap();
+        assert_eq!(config.metadata.branch, "release");
+        assert_eq!(config.security.resource_limits.max_cpu, "90%");
+        assert_eq!(config.security.resource_limits.max_memory, "100MB");
+
+        // Without an environment the overlay is ignored
+        let config = AgentConfigLoader::new(config_dir)
+            .with_variables(variables)
+            .load_config_file(&path)
+            .unwrap();
+        assert_eq!(config.security.resource_limits.max_cpu, "50%");
+    }
+}
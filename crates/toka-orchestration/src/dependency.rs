@@ -489,6 +489,7 @@ mod tests {
                     timeout: "1h".to_string(),
                 },
             },
+            placement: Default::default(),
         }
     }
 
@@ -9,12 +9,12 @@ use std::sync::Arc;
 use anyhow::Result;
 use tracing::{info, warn, error};
 
-use toka_agent_runtime::{AgentProcessManager, process::ProcessResult};
+use toka_agent_runtime::{AgentProcessManager, OrchestrationBackend, OrchestrationStatus, process::ProcessResult};
 use toka_llm_gateway::LlmGateway;
 use toka_runtime::RuntimeManager;
 use toka_types::EntityId;
 
-use crate::{OrchestrationEngine, AgentConfig};
+use crate::{OrchestrationEngine, OrchestrationSession, AgentConfig};
 
 /// Integration service that connects orchestration with agent runtime
 pub struct RuntimeIntegration {
@@ -109,6 +109,26 @@ impl OrchestrationRuntimeExt for OrchestrationEngine {
     }
 }
 
+#[async_trait::async_trait]
+impl OrchestrationBackend for OrchestrationEngine {
+    type Session = OrchestrationSession;
+
+    async fn start_orchestration(self: Arc<Self>) -> Result<OrchestrationSession> {
+        OrchestrationEngine::start_orchestration(self).await
+    }
+
+    async fn orchestration_status(&self) -> OrchestrationStatus {
+        let state = self.get_session_state().await;
+        OrchestrationStatus {
+            session_id: state.session_id,
+            phase: format!("{:?}", state.current_phase),
+            progress: state.progress,
+            spawned_agents: self.get_spawned_agents().len(),
+            completed: state.completed,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
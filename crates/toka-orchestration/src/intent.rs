@@ -0,0 +1,262 @@
+//! Hierarchical intent management.
+//!
+//! Every event in the store carries the intent it was emitted under. The
+//! [`IntentRegistry`] arranges intents into trees (an epic with its tasks,
+//! a workstream with its agents) so that work can be opened and closed as a
+//! unit and the events of a whole subtree can be rolled up in one query.
+//!
+//! The [`WorkstreamCoordinator`](crate::WorkstreamCoordinator) creates one
+//! tree per orchestration session: a root intent for the session, a child
+//! per workstream and a grandchild per agent.
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use toka_store_core::{IntentId, QueryableStorage};
+
+/// Lifecycle state of an intent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IntentState {
+    /// Work under the intent is ongoing
+    Open,
+    /// The intent is finished and accepts no new children
+    Closed,
+}
+
+/// A registered intent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Intent {
+    /// Intent identifier, as recorded in event headers
+    pub id: IntentId,
+    /// Parent intent, `None` for roots
+    pub parent: Option<IntentId>,
+    /// Human-readable title
+    pub title: String,
+    /// Lifecycle state
+    pub state: IntentState,
+    /// When the intent was created
+    pub created_at: DateTime<Utc>,
+    /// When the intent was closed
+    pub closed_at: Option<DateTime<Utc>>,
+}
+
+/// Events recorded under an intent subtree.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntentRollup {
+    /// Root of the subtree
+    pub intent: IntentId,
+    /// Intents in the subtree, including the root
+    pub intents: usize,
+    /// Intents in the subtree still open
+    pub open_intents: usize,
+    /// Events recorded under any intent of the subtree
+    pub events: usize,
+    /// Event counts by kind
+    pub events_by_kind: BTreeMap<String, usize>,
+    /// Event counts per direct child subtree
+    pub events_by_child: BTreeMap<IntentId, usize>,
+    /// Timestamp of the earliest event
+    pub first_event: Option<DateTime<Utc>>,
+    /// Timestamp of the latest event
+    pub last_event: Option<DateTime<Utc>>,
+}
+
+/// Registry of parent/child intents.
+#[derive(Debug, Default)]
+pub struct IntentRegistry {
+    intents: RwLock<HashMap<IntentId, Intent>>,
+    children: RwLock<HashMap<IntentId, Vec<IntentId>>>,
+}
+
+impl IntentRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an open intent, optionally below `parent`.
+    ///
+    /// Fails if the parent is unknown or already closed.
+    pub async fn create_intent(&self, title: impl Into<String>, parent: Option<IntentId>) -> Result<IntentId> {
+        let mut intents = self.intents.write().await;
+        if let Some(parent) = parent {
+            match intents.get(&parent) {
+                Some(intent) if intent.state == IntentState::Closed => {
+                    return Err(anyhow::anyhow!("Parent intent is closed: {}", parent));
+                }
+                Some(_) => {}
+                None => return Err(anyhow::anyhow!("Parent intent not found: {}", parent)),
+            }
+        }
+
+        let id = Uuid::new_v4();
+        intents.insert(
+            id,
+            Intent {
+                id,
+                parent,
+                title: title.into(),
+                state: IntentState::Open,
+                created_at: Utc::now(),
+                closed_at: None,
+            },
+        );
+        if let Some(parent) = parent {
+            self.children.write().await.entry(parent).or_default().push(id);
+        }
+        Ok(id)
+    }
+
+    /// Close an intent.
+    ///
+    /// Fails if any child is still open. Closing a closed intent is a no-op.
+    pub async fn close_intent(&self, id: IntentId) -> Result<()> {
+        let mut intents = self.intents.write().await;
+        let children = self.children.read().await;
+        let open_child = children
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .find(|child| intents.get(child).is_some_and(|c| c.state == IntentState::Open));
+        if let Some(child) = open_child {
+            return Err(anyhow::anyhow!("Intent {} has open child {}", id, child));
+        }
+
+        let intent = intents
+            .get_mut(&id)
+            .ok_or_else(|| anyhow::anyhow!("Intent not found: {}", id))?;
+        if intent.state == IntentState::Open {
+            intent.state = IntentState::Closed;
+            intent.closed_at = Some(Utc::now());
+        }
+        Ok(())
+    }
+
+    /// Close an intent and all of its descendants.
+    pub async fn close_subtree(&self, id: IntentId) -> Result<()> {
+        let subtree = self.subtree(id).await?;
+        // Descendants come after their parents, so close in reverse
+        for intent in subtree.into_iter().rev() {
+            self.close_intent(intent).await?;
+        }
+        Ok(())
+    }
+
+    /// Look up an intent.
+    pub async fn get(&self, id: IntentId) -> Option<Intent> {
+        self.intents.read().await.get(&id).cloned()
+    }
+
+    /// Direct children of an intent, in creation order.
+    pub async fn children(&self, id: IntentId) -> Vec<IntentId> {
+        self.children.read().await.get(&id).cloned().unwrap_or_default()
+    }
+
+    /// Ancestors of an intent, nearest first.
+    pub async fn ancestors(&self, id: IntentId) -> Vec<IntentId> {
+        let intents = self.intents.read().await;
+        let mut ancestors = Vec::new();
+        let mut current = intents.get(&id).and_then(|intent| intent.parent);
+        while let Some(parent) = current {
+            ancestors.push(parent);
+            current = intents.get(&parent).and_then(|intent| intent.parent);
+        }
+        ancestors
+    }
+
+    /// An intent followed by all of its descendants, parents before children.
+    pub async fn subtree(&self, id: IntentId) -> Result<Vec<IntentId>> {
+        if !self.intents.read().await.contains_key(&id) {
+            return Err(anyhow::anyhow!("Intent not found: {}", id));
+        }
+        let children = self.children.read().await;
+        let mut subtree = vec![id];
+        let mut next = 0;
+        while next < subtree.len() {
+            if let Some(kids) = children.get(&subtree[next]) {
+                subtree.extend(kids.iter().copied());
+            }
+            next += 1;
+        }
+        Ok(subtree)
+    }
+
+    /// Roll up the events recorded in `storage` under the subtree of `id`.
+    pub async fn rollup<S: QueryableStorage + ?Sized>(&self, storage: &S, id: IntentId) -> Result<IntentRollup> {
+        let subtree = self.subtree(id).await?;
+        let open_intents = {
+            let intents = self.intents.read().await;
+            subtree
+                .iter()
+                .filter(|intent| intents.get(intent).is_some_and(|i| i.state == IntentState::Open))
+                .count()
+        };
+
+        // Attribute every intent to the direct child of `id` it descends from
+        let mut branch = HashMap::new();
+        for child in self.children(id).await {
+            for intent in self.subtree(child).await? {
+                branch.insert(intent, child);
+            }
+        }
+
+        let mut rollup = IntentRollup {
+            intent: id,
+            intents: subtree.len(),
+            open_intents,
+            events: 0,
+            events_by_kind: BTreeMap::new(),
+            events_by_child: BTreeMap::new(),
+            first_event: None,
+            last_event: None,
+        };
+        for intent in &subtree {
+            let mut events = storage.events_by_intent(intent).await?;
+            while let Some(header) = events.next().await {
+                let header = header?;
+                rollup.events += 1;
+                *rollup.events_by_kind.entry(header.kind.clone()).or_default() += 1;
+                if let Some(child) = branch.get(intent) {
+                    *rollup.events_by_child.entry(*child).or_default() += 1;
+                }
+                rollup.first_event = Some(rollup.first_event.map_or(header.timestamp, |t| t.min(header.timestamp)));
+                rollup.last_event = Some(rollup.last_event.map_or(header.timestamp, |t| t.max(header.timestamp)));
+            }
+        }
+        Ok(rollup)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_intent_hierarchy() {
+        let registry = IntentRegistry::new();
+        let epic = registry.create_intent("epic", None).await.unwrap();
+        let task = registry.create_intent("task", Some(epic)).await.unwrap();
+        let subtask = registry.create_intent("subtask", Some(task)).await.unwrap();
+
+        assert_eq!(registry.children(epic).await, vec![task]);
+        assert_eq!(registry.ancestors(subtask).await, vec![task, epic]);
+        assert_eq!(registry.subtree(epic).await.unwrap(), vec![epic, task, subtask]);
+        assert!(registry.create_intent("orphan", Some(Uuid::new_v4())).await.is_err());
+
+        // Open children keep their parent open
+        assert!(registry.close_intent(task).await.is_err());
+        registry.close_intent(subtask).await.unwrap();
+        registry.close_intent(task).await.unwrap();
+        assert!(registry.create_intent("late", Some(task)).await.is_err());
+
+        registry.create_intent("second task", Some(epic)).await.unwrap();
+        registry.close_subtree(epic).await.unwrap();
+        assert_eq!(registry.get(epic).await.unwrap().state, IntentState::Closed);
+    }
+}
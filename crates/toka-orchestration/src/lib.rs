@@ -12,23 +12,44 @@
 //! The orchestration system consists of:
 //!
 //! - **OrchestrationEngine**: Main coordinator that manages agent lifecycles
-//! - **AgentConfigLoader**: Loads and validates agent configurations from YAML
+//! - **AgentConfigLoader**: Loads and validates agent configurations from YAML,
+//!   optionally verifying detached signatures
 //! - **DependencyResolver**: Resolves spawn order based on agent dependencies
 //! - **ProgressMonitor**: Tracks agent progress and coordinates phases
 //! - **WorkstreamCoordinator**: Manages workstream-specific coordination
+//! - **IntentRegistry**: Tracks the session → workstream → agent intent
+//!   hierarchy and rolls up stored events per intent subtree
+//! - **Notifier**: Delivers webhook, email and command notifications for
+//!   selected kernel events
+//! - **NodeRegistry**: Places agents on remote runtime worker nodes according
+//!   to their placement constraints
+//! - **Reporter**: Generates periodic progress reports from each agent's
+//!   reporting configuration
+//! - **PlatformWatchdog**: Built-in agent preset correlating platform errors
+//!   and timeouts into incident observations and webhook notifications
 //!
 //! ## Usage
 //!
 //! ```rust,no_run
+//! use std::sync::Arc;
+//! use toka_auth::hs256::JwtHs256Validator;
+//! use toka_bus_core::InMemoryBus;
+//! use toka_kernel::{Kernel, WorldState};
 //! use toka_orchestration::{OrchestrationEngine, OrchestrationConfig};
-//! use toka_runtime::Runtime;
+//! use toka_runtime::{RuntimeBuilder, RuntimeKernel};
 //!
 //! # #[tokio::main]
 //! # async fn main() -> anyhow::Result<()> {
 //! let config = OrchestrationConfig::from_directory("agents/v0.3.0/workstreams")?;
-//! let runtime = Runtime::new(Default::default(), Default::default()).await?;
-//! 
-//! let engine = OrchestrationEngine::new(config, runtime).await?;
+//! let bus = Arc::new(InMemoryBus::new(1024));
+//! let kernel = Kernel::new(WorldState::default(), Arc::new(JwtHs256Validator::new("secret")), bus.clone());
+//! let runtime = RuntimeBuilder::new(RuntimeKernel::new(kernel))
+//!     .with_default_engines()
+//!     .with_event_bus(bus)
+//!     .build()
+//!     .await?;
+//!
+//! let engine = Arc::new(OrchestrationEngine::new(config, Arc::new(runtime)).await?);
 //! let session = engine.start_orchestration().await?;
 //!
 //! // Wait for completion
@@ -55,10 +76,12 @@ use chrono::{DateTime, Utc};
 use dashmap::DashMap;
 use futures::future::join_all;
 use serde::{Deserialize, Serialize};
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{broadcast, mpsc, watch, RwLock};
+use tokio::task::JoinHandle;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+use toka_agent_runtime::{AgentProgress, DeadLetterId, DeadLetterQueue, ProgressAggregator};
 use toka_llm_gateway::LlmGateway;
 use toka_runtime::RuntimeManager;
 use toka_types::{
@@ -68,20 +91,48 @@ use toka_types::{
     ReportingConfig, ReportingFrequency, SecurityConfig, ResourceLimits
 };
 use toka_bus_core::KernelEvent;
+use toka_coordination_lock::LeaderElection;
+use toka_store_core::{DurableQueue, TaskId};
 
 pub mod config;
 pub mod dependency;
 pub mod monitor;
 pub mod workstream;
+pub mod intent;
 pub mod llm_integration;
 pub mod integration;
+pub mod overlay;
+pub mod signing;
+pub mod notifier;
+pub mod placement;
+pub mod reporting;
+pub mod watchdog;
 
 pub use config::{AgentConfigLoader, OrchestrationConfig};
+pub use overlay::{interpolate, merge_yaml, InterpolationError};
+pub use signing::{ConfigSigningConfig, ConfigVerifier, SignaturePolicy};
+pub use notifier::{
+    ChannelConfig, EventFilter, Notification, NotificationRule, NotificationSink,
+    NotificationTemplate, Notifier, NotifierConfig,
+};
+pub use placement::{
+    HttpWorkerDispatcher, NodeRegistry, NodeStatus, PlacementError, WorkerDispatcher, WorkerNode,
+};
+pub use reporting::{
+    ProgressReport, ReportFormat, ReportScope, ReportSink, ReportTemplate, Reporter,
+    ReporterConfig, WebhookReportSink,
+};
+pub use watchdog::{
+    watchdog_agent_config, Incident, PlatformWatchdog, WatchdogConfig, WATCHDOG_AGENT_NAME,
+};
 pub use dependency::DependencyResolver;
 pub use monitor::ProgressMonitor;
 pub use workstream::WorkstreamCoordinator;
+pub use intent::{Intent, IntentRegistry, IntentRollup, IntentState};
 pub use llm_integration::{LlmOrchestrationIntegrator, TaskExecutionResult, CoordinationPlan};
 pub use integration::{RuntimeIntegration, OrchestrationRuntimeExt};
+pub use toka_agent_runtime::{SessionProgress, WorkstreamProgress};
+pub use toka_agent_runtime::{BudgetPressure, BudgetTracker, RemainingBudget, SessionBudget};
 
 /// Maximum number of agents that can be spawned simultaneously
 pub const MAX_CONCURRENT_AGENTS: usize = 10;
@@ -132,6 +183,8 @@ pub struct SpawnedAgent {
     pub tasks: Vec<TaskSpec>,
     /// Completion metrics
     pub metrics: AgentMetrics,
+    /// Worker node the agent was dispatched to, if placed remotely
+    pub node: Option<String>,
 }
 
 /// Metrics tracked for each agent.
@@ -149,6 +202,17 @@ pub struct AgentMetrics {
     pub last_progress: Option<DateTime<Utc>>,
 }
 
+/// A task assignment persisted in the durable task queue.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentTaskAssignment {
+    /// Agent the task is assigned to
+    pub agent: EntityId,
+    /// Configuration name of the agent
+    pub agent_name: String,
+    /// The task specification
+    pub task: TaskSpec,
+}
+
 /// Main orchestration engine for managing agent lifecycles.
 pub struct OrchestrationEngine {
     /// Orchestration configuration
@@ -171,6 +235,24 @@ pub struct OrchestrationEngine {
     agent_states: Arc<DashMap<String, AgentState>>,
     /// Orchestration session state
     session_state: Arc<RwLock<SessionState>>,
+    /// Workstream and session progress rollup
+    progress_rollup: Arc<RwLock<ProgressAggregator>>,
+    /// Watch channel publishing the latest session progress
+    progress_tx: watch::Sender<SessionProgress>,
+    /// Durable queue recording task assignments before they are scheduled
+    task_queue: Option<Arc<DurableQueue<AgentTaskAssignment>>>,
+    /// Worker node registry used to place agents remotely
+    node_registry: Option<Arc<NodeRegistry>>,
+    /// Dispatcher starting placed agents on their worker node
+    worker_dispatcher: Option<Arc<dyn WorkerDispatcher>>,
+    /// Progress report generator
+    reporter: Option<Arc<Reporter>>,
+    /// Election deciding which engine instance drives the session
+    leader_election: Option<Arc<LeaderElection>>,
+    /// Consumption of the session budget, shared with agent executors
+    budget: Arc<BudgetTracker>,
+    /// Task feeding agent progress reports into the rollups
+    progress_follower: std::sync::Mutex<Option<JoinHandle<()>>>,
 }
 
 /// Orchestration session state.
@@ -238,6 +320,7 @@ impl OrchestrationEngine {
 
         // Initialize workstream coordinator
         let workstream_coordinator = Arc::new(WorkstreamCoordinator::new());
+        workstream_coordinator.initialize_workstreams(&config.agents).await?;
 
         // Initialize agent state tracking
         let agent_states = Arc::new(DashMap::new());
@@ -255,6 +338,11 @@ impl OrchestrationEngine {
             error: None,
         }));
 
+        let progress_rollup = ProgressAggregator::new();
+        let (progress_tx, _) = watch::channel(progress_rollup.session());
+
+        let budget = Arc::new(BudgetTracker::new(config.budget.clone()));
+
         info!("Orchestration engine initialized successfully");
 
         Ok(Self {
@@ -267,6 +355,15 @@ impl OrchestrationEngine {
             spawned_agents: Arc::new(DashMap::new()),
             agent_states,
             session_state,
+            progress_rollup: Arc::new(RwLock::new(progress_rollup)),
+            progress_tx,
+            task_queue: None,
+            node_registry: None,
+            worker_dispatcher: None,
+            reporter: None,
+            leader_election: None,
+            budget,
+            progress_follower: std::sync::Mutex::new(None),
         })
     }
 
@@ -276,6 +373,106 @@ impl OrchestrationEngine {
         self
     }
 
+    /// Persist task assignments in a durable queue.
+    ///
+    /// Every assignment is enqueued before it is scheduled on the runtime, so
+    /// a crash between scheduling and execution leaves the task in the queue
+    /// for agents to claim instead of losing it.
+    pub fn with_task_queue(mut self, queue: Arc<DurableQueue<AgentTaskAssignment>>) -> Self {
+        self.task_queue = Some(queue);
+        self
+    }
+
+    /// Place agents on remote runtime worker nodes.
+    ///
+    /// Each spawned agent is assigned a node from `registry` that satisfies
+    /// its placement constraints and is started there through `dispatcher`.
+    /// Agents that cannot be placed fail to spawn.
+    pub fn with_placement(
+        mut self,
+        registry: Arc<NodeRegistry>,
+        dispatcher: Arc<dyn WorkerDispatcher>,
+    ) -> Self {
+        self.node_registry = Some(registry);
+        self.worker_dispatcher = Some(dispatcher);
+        self
+    }
+
+    /// Generate progress reports with `reporter`.
+    ///
+    /// Every configured agent is registered with the reporter, and due
+    /// reports are generated whenever an agent reports progress. Use
+    /// [`Reporter::spawn`] with [`watch_progress`](Self::watch_progress) to
+    /// also produce time-based reports while no progress arrives.
+    pub async fn with_reporter(mut self, reporter: Arc<Reporter>) -> Result<Self> {
+        for agent in &self.config.agents {
+            reporter.register_agent(agent).await?;
+        }
+        self.reporter = Some(reporter);
+        Ok(self)
+    }
+
+    /// Only drive the session while leading `election`.
+    ///
+    /// In high-availability deployments every engine instance campaigns for
+    /// the same lock, usually named with
+    /// [`session_lock_name`](toka_coordination_lock::session_lock_name).
+    /// [`start_orchestration`](Self::start_orchestration) then waits until
+    /// this instance is elected, so a standby takes over once the leader's
+    /// lease expires. The election must be running, e.g. through
+    /// [`LeaderElection::spawn`].
+    pub fn with_leader_election(mut self, election: Arc<LeaderElection>) -> Self {
+        self.leader_election = Some(election);
+        self
+    }
+
+    /// Whether this instance may drive the session.
+    ///
+    /// Always true without leader election.
+    pub fn is_leader(&self) -> bool {
+        self.leader_election
+            .as_ref()
+            .is_none_or(|election| election.is_leader())
+    }
+
+    /// Worker node registry, if placement is configured.
+    pub fn node_registry(&self) -> Option<Arc<NodeRegistry>> {
+        self.node_registry.clone()
+    }
+
+    /// Durable task queue agents claim their work from, if configured.
+    pub fn task_queue(&self) -> Option<Arc<DurableQueue<AgentTaskAssignment>>> {
+        self.task_queue.clone()
+    }
+
+    /// Send a dead-lettered task back to the durable task queue.
+    ///
+    /// The task is re-assigned to the agent that owned it, with any
+    /// parameter edits made in the dead-letter queue applied. Fails if no
+    /// task queue is configured.
+    pub async fn requeue_dead_letter(
+        &self,
+        dead_letters: &DeadLetterQueue,
+        id: DeadLetterId,
+    ) -> Result<TaskId> {
+        let queue = self
+            .task_queue
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("No task queue configured to requeue dead letters into"))?;
+
+        dead_letters
+            .requeue_into(id, queue, |entry| {
+                let task = TaskSpec::new(entry.task.description.clone())
+                    .map_err(|e| anyhow::anyhow!("Failed to create task spec: {}", e))?;
+                Ok(AgentTaskAssignment {
+                    agent: entry.agent_id,
+                    agent_name: entry.agent_name.clone(),
+                    task,
+                })
+            })
+            .await
+    }
+
     /// Start orchestration session.
     ///
     /// This begins the agent spawning and coordination process according to the
@@ -288,12 +485,23 @@ impl OrchestrationEngine {
 
         info!("Starting orchestration session: {}", session_id);
 
+        if !self.follow_agent_progress() {
+            warn!("Runtime has no event bus; agent progress will not be rolled up");
+        }
+
         // Create completion channel
         let (completion_tx, completion_rx) = mpsc::channel(1);
 
         // Spawn orchestration task
         let engine = self.clone();
         tokio::spawn(async move {
+            if let Some(election) = &engine.leader_election {
+                info!("Waiting to be elected leader of {}", election.name());
+                if let Err(e) = election.wait_for_leadership().await {
+                    let _ = completion_tx.send(Err(e)).await;
+                    return;
+                }
+            }
             let result = engine.run_orchestration().await;
             let _ = completion_tx.send(result).await;
         });
@@ -462,6 +670,15 @@ impl OrchestrationEngine {
         let spec = AgentSpec::new(agent_config.spec.name.clone())
             .map_err(|e| anyhow::anyhow!("Failed to create agent spec: {}", e))?;
 
+        // Place the agent on a worker node before anything is scheduled
+        let node = match self.place_agent(agent_config).await {
+            Ok(node) => node,
+            Err(e) => {
+                self.agent_states.insert(agent_config.metadata.name.clone(), AgentState::Failed);
+                return Err(e);
+            }
+        };
+
         // Create spawn operation
         let main_agent_id = EntityId(uuid::Uuid::new_v4().as_u128());
         let spawn_message = Message {
@@ -471,6 +688,7 @@ impl OrchestrationEngine {
                 parent: main_agent_id,
                 spec: spec.clone(),
             },
+            correlation_id: None,
         };
 
         // Submit spawn operation
@@ -496,6 +714,7 @@ impl OrchestrationEngine {
             last_activity: Utc::now(),
             tasks: Vec::new(),
             metrics: AgentMetrics::default(),
+            node,
         };
 
         // Store spawned agent
@@ -510,6 +729,26 @@ impl OrchestrationEngine {
         Ok(())
     }
 
+    /// Choose a worker node for an agent and dispatch it there.
+    ///
+    /// Returns `None` when no placement is configured and the agent runs on
+    /// the local runtime.
+    async fn place_agent(&self, agent_config: &AgentConfig) -> Result<Option<String>> {
+        let (registry, dispatcher) = match (&self.node_registry, &self.worker_dispatcher) {
+            (Some(registry), Some(dispatcher)) => (registry, dispatcher),
+            _ => return Ok(None),
+        };
+
+        let node = registry.place(agent_config)?;
+        debug!("Agent {} placed on worker node {}", agent_config.metadata.name, node.id);
+
+        if let Err(e) = dispatcher.dispatch(&node, agent_config).await {
+            registry.release(&agent_config.metadata.name);
+            return Err(e);
+        }
+        Ok(Some(node.id))
+    }
+
     /// Assign default tasks to an agent.
     async fn assign_default_tasks(&self, agent_id: EntityId, agent_config: &AgentConfig) -> Result<()> {
         debug!("Assigning default tasks to agent: {}", agent_config.metadata.name);
@@ -517,6 +756,17 @@ impl OrchestrationEngine {
         for task_config in &agent_config.tasks.default {
             let task = TaskSpec::new(task_config.description.clone())
                 .map_err(|e| anyhow::anyhow!("Failed to create task spec: {}", e))?;
+
+            if let Some(queue) = &self.task_queue {
+                let task_id = queue
+                    .enqueue(AgentTaskAssignment {
+                        agent: agent_id,
+                        agent_name: agent_config.metadata.name.clone(),
+                        task: task.clone(),
+                    })
+                    .await?;
+                debug!("Task {} persisted for agent {}", task_id, agent_config.metadata.name);
+            }
             
             let task_message = Message {
                 origin: EntityId(uuid::Uuid::new_v4().as_u128()),
@@ -525,6 +775,7 @@ impl OrchestrationEngine {
                     agent: agent_id,
                     task: task.clone(),
                 },
+                correlation_id: None,
             };
 
             self.runtime.submit(task_message).await?;
@@ -576,6 +827,16 @@ impl OrchestrationEngine {
         Ok(())
     }
 
+    /// Intent hierarchy of the session, its workstreams and their agents.
+    pub fn intents(&self) -> Arc<IntentRegistry> {
+        self.workstream_coordinator.intents()
+    }
+
+    /// Intent an agent's events are recorded under.
+    pub fn agent_intent(&self, agent_name: &str) -> Option<toka_store_core::IntentId> {
+        self.workstream_coordinator.get_agent_intent(agent_name)
+    }
+
     /// Get current session state.
     pub async fn get_session_state(&self) -> SessionState {
         self.session_state.read().await.clone()
@@ -585,6 +846,119 @@ impl OrchestrationEngine {
     pub fn get_spawned_agents(&self) -> Vec<SpawnedAgent> {
         self.spawned_agents.iter().map(|entry| entry.value().clone()).collect()
     }
+
+    /// Record a progress report from an agent.
+    ///
+    /// The report is folded into the workstream and session rollups and the
+    /// updated session summary is published to all progress watchers.
+    pub async fn record_agent_progress(&self, report: AgentProgress) {
+        if let Some(reporter) = &self.reporter {
+            reporter.observe(&report).await;
+        }
+        let summary = {
+            let mut rollup = self.progress_rollup.write().await;
+            rollup.record(report);
+            rollup.session()
+        };
+
+        self.session_state.write().await.progress = summary.overall.progress;
+        if let Some(reporter) = &self.reporter {
+            reporter.run_due(&summary, Utc::now()).await;
+        }
+        self.progress_tx.send_replace(summary);
+    }
+
+    /// Feed the progress reports agents submit through the runtime into the
+    /// rollups.
+    ///
+    /// Agents' [`ProgressReporter`](toka_agent_runtime::ProgressReporter)s
+    /// publish each report as an observation; a single task records every
+    /// observation carrying an [`AgentProgress`] until the engine is shut
+    /// down or dropped, however often this is called. Returns `false` when
+    /// the runtime has no event bus to observe.
+    pub fn follow_agent_progress(self: &Arc<Self>) -> bool {
+        let mut follower = self.progress_follower.lock().expect("progress follower lock poisoned");
+        if follower.is_some() {
+            return true;
+        }
+        let Some(bus) = self.runtime.event_bus() else {
+            return false;
+        };
+        let mut events = bus.subscribe();
+        let engine = Arc::downgrade(self);
+        *follower = Some(tokio::spawn(async move {
+            loop {
+                let data = match events.recv().await {
+                    Ok(KernelEvent::ObservationEmitted { data, .. }) => data,
+                    Ok(_) => continue,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!("Missed {} events while following agent progress", missed);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                // Task and completion reports share the observation channel
+                let Ok(report) = serde_json::from_slice::<AgentProgress>(&data) else {
+                    continue;
+                };
+                let Some(engine) = engine.upgrade() else {
+                    break;
+                };
+                engine.record_agent_progress(report).await;
+            }
+        }));
+        true
+    }
+
+    /// Stop following agent progress.
+    ///
+    /// Rollups keep their last state; a later
+    /// [`start_orchestration`](Self::start_orchestration) follows progress
+    /// again.
+    pub async fn shutdown(&self) -> Result<()> {
+        info!("Shutting down orchestration engine");
+        if let Some(follower) = self.progress_follower.lock().expect("progress follower lock poisoned").take() {
+            follower.abort();
+        }
+        Ok(())
+    }
+
+    /// Tracker of the session budget.
+    ///
+    /// Pass it to each agent through
+    /// [`AgentExecutor::with_budget`](toka_agent_runtime::AgentExecutor::with_budget)
+    /// so agents see the remaining budget and charge their token usage to it.
+    pub fn budget(&self) -> Arc<BudgetTracker> {
+        self.budget.clone()
+    }
+
+    /// Budget left in the session right now.
+    pub fn remaining_budget(&self) -> RemainingBudget {
+        self.budget.remaining()
+    }
+
+    /// Get the current session-level progress rollup.
+    pub async fn session_progress(&self) -> SessionProgress {
+        self.progress_rollup.read().await.session()
+    }
+
+    /// Get the current progress rollup for a single workstream.
+    pub async fn workstream_progress(&self, workstream: &str) -> Option<WorkstreamProgress> {
+        self.progress_rollup.read().await.workstream(workstream)
+    }
+
+    /// Watch session progress as agents report in.
+    pub fn watch_progress(&self) -> watch::Receiver<SessionProgress> {
+        self.progress_tx.subscribe()
+    }
+}
+
+impl Drop for OrchestrationEngine {
+    fn drop(&mut self) {
+        if let Some(follower) = self.progress_follower.get_mut().expect("progress follower lock poisoned").take() {
+            follower.abort();
+        }
+    }
 }
 
 impl OrchestrationSession {
@@ -611,6 +985,21 @@ impl OrchestrationSession {
     pub fn get_spawned_agents(&self) -> Vec<SpawnedAgent> {
         self.engine.get_spawned_agents()
     }
+
+    /// Get the current session-level progress rollup.
+    pub async fn progress(&self) -> SessionProgress {
+        self.engine.session_progress().await
+    }
+
+    /// Get the current progress rollup for a single workstream.
+    pub async fn workstream_progress(&self, workstream: &str) -> Option<WorkstreamProgress> {
+        self.engine.workstream_progress(workstream).await
+    }
+
+    /// Watch session progress as agents report in.
+    pub fn watch_progress(&self) -> watch::Receiver<SessionProgress> {
+        self.engine.watch_progress()
+    }
 }
 
 impl Default for SessionState {
@@ -629,53 +1018,81 @@ impl Default for SessionState {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use toka_runtime::RuntimeConfig;
-    use toka_auth::{TokenValidator, Claims};
-    use anyhow::Result;
-    use std::future::Future;
-    use std::pin::Pin;
-
-    struct MockTokenValidator;
-
-    impl TokenValidator for MockTokenValidator {
-        fn validate<'life0, 'life1, 'async_trait>(
-            &'life0 self,
-            _token: &'life1 str,
-        ) -> Pin<Box<dyn Future<Output = Result<Claims, toka_auth::Error>> + Send + 'async_trait>>
-        where
-            'life0: 'async_trait,
-            'life1: 'async_trait,
-            Self: 'async_trait,
-        {
-            Box::pin(async move {
-                let now = chrono::Utc::now().timestamp() as u64;
-                Ok(Claims {
-                    sub: "test-user".to_string(),
-                    vault: "test-vault".to_string(),
-                    permissions: vec!["test-permission".to_string()],
-                    iat: now,
-                    exp: now + 3600,
-                    jti: uuid::Uuid::new_v4().to_string(),
-                })
-            })
-        }
+    use toka_agent_runtime::{AgentContext, AgentExecutionState, AgentMetrics as RuntimeAgentMetrics, ProgressReporter};
+    use toka_auth::JwtHs256Validator;
+    use toka_bus_core::InMemoryBus;
+    use toka_kernel::{Kernel, WorldState};
+    use toka_runtime::{RuntimeBuilder, RuntimeKernel};
+
+    async fn runtime(bus: Arc<InMemoryBus>) -> Arc<RuntimeManager> {
+        let kernel = Kernel::new(WorldState::default(), Arc::new(JwtHs256Validator::new("secret")), bus.clone());
+        let runtime = RuntimeBuilder::new(RuntimeKernel::new(kernel))
+            .with_event_bus(bus)
+            .build()
+            .await
+            .expect("Failed to create runtime");
+        Arc::new(runtime)
     }
 
-    #[tokio::test]
-    async fn test_orchestration_engine_creation() {
-        let config = OrchestrationConfig {
-            agents: vec![],
+    fn config(agents: Vec<AgentConfig>) -> OrchestrationConfig {
+        OrchestrationConfig {
+            agents,
             global_timeout: Duration::from_secs(3600),
             max_concurrent_agents: 5,
-        };
+            budget: SessionBudget::default(),
+        }
+    }
 
-        let runtime = Arc::new(
-            Runtime::new(RuntimeConfig::default(), Arc::new(MockTokenValidator))
-                .await
-                .expect("Failed to create runtime")
-        );
+    #[tokio::test]
+    async fn test_orchestration_engine_creation() {
+        let runtime = runtime(Arc::new(InMemoryBus::new(16))).await;
 
-        let engine = OrchestrationEngine::new(config, runtime).await;
+        let engine = OrchestrationEngine::new(config(vec![]), runtime).await;
         assert!(engine.is_ok());
     }
-} 
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_agent_progress_reports_reach_rollup() {
+        let runtime = runtime(Arc::new(InMemoryBus::new(16))).await;
+        let agent = watchdog_agent_config();
+        let workstream = agent.metadata.workstream.clone();
+        let engine = Arc::new(OrchestrationEngine::new(config(vec![agent.clone()]), runtime.clone()).await.unwrap());
+        assert!(engine.follow_agent_progress());
+        let mut progress = engine.watch_progress();
+
+        let context = AgentContext {
+            agent_id: EntityId(9),
+            config: agent,
+            state: AgentExecutionState::Ready,
+            started_at: Utc::now(),
+            last_activity: Utc::now(),
+            metrics: RuntimeAgentMetrics::default(),
+            environment: HashMap::new(),
+            budget: None,
+        };
+        let mut reporter = ProgressReporter::new(context, runtime);
+        reporter.report_progress(0.5, Some("halfway".to_string())).await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), progress.changed()).await.unwrap().unwrap();
+        let rollup = engine.workstream_progress(&workstream).await.unwrap().rollup;
+        assert_eq!((rollup.agents, rollup.progress), (1, 0.5));
+        assert_eq!(engine.session_progress().await.overall.progress, 0.5);
+        assert_eq!(engine.get_session_state().await.progress, 0.5);
+        engine.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sessions_share_one_progress_follower() {
+        let bus = Arc::new(InMemoryBus::new(16));
+        let engine = Arc::new(OrchestrationEngine::new(config(vec![]), runtime(bus.clone()).await).await.unwrap());
+
+        engine.clone().start_orchestration().await.unwrap();
+        engine.clone().start_orchestration().await.unwrap();
+        assert!(engine.follow_agent_progress());
+        assert_eq!(bus.subscriber_count(), 1);
+
+        engine.shutdown().await.unwrap();
+        tokio::task::yield_now().await;
+        assert_eq!(bus.subscriber_count(), 0);
+    }
+}
@@ -532,6 +532,7 @@ mod tests {
                     timeout: "1h".to_string(),
                 },
             },
+            placement: Default::default(),
         }
     }
 
@@ -0,0 +1,787 @@
+//! Kernel-event notifications for operators.
+//!
+//! This module provides a notifier component that subscribes to the kernel
+//! event bus and turns selected events into notifications delivered over
+//! webhooks, email (SMTP) or local command hooks. Each [`NotificationRule`]
+//! combines an [`EventFilter`], a [`NotificationTemplate`] and a set of
+//! channels; repeated notifications for the same condition are suppressed
+//! within the rule's dedup window so a crash loop does not page anyone fifty
+//! times a minute.
+//!
+//! Templates use `{{name}}` placeholders. The available variables are
+//! `event`, `timestamp`, `summary` and, when present on the event, `agent`,
+//! `reason`, `exit_code`, `task_id`, `error`, `error_code`, `component`,
+//! `severity`, `category`, `resource`, `requested` and `available`.
+
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{broadcast, Mutex};
+use tracing::{debug, warn};
+
+use toka_bus_core::{ErrorSeverity, EventBus, KernelEvent, TerminationReason};
+
+/// Default window within which identical notifications are suppressed
+pub const DEFAULT_DEDUP_WINDOW_SECS: u64 = 300;
+
+/// Maximum time a command hook may run before it is killed
+pub const COMMAND_HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default dedup key: one notification per event type, agent and error code
+const DEFAULT_DEDUP_KEY: &str = "{{event}}:{{agent}}:{{error_code}}";
+
+/// Notifier configuration, typically loaded from YAML alongside agent configs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotifierConfig {
+    /// Notification rules, evaluated independently for every event
+    #[serde(default)]
+    pub rules: Vec<NotificationRule>,
+}
+
+/// A rule describing which events to notify about and where.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationRule {
+    /// Rule name, included in every notification
+    pub name: String,
+    /// Events this rule applies to
+    pub filter: EventFilter,
+    /// Subject and body templates
+    #[serde(default)]
+    pub template: NotificationTemplate,
+    /// Delivery channels
+    #[serde(default)]
+    pub channels: Vec<ChannelConfig>,
+    /// Seconds during which notifications with the same dedup key are suppressed
+    #[serde(default = "default_dedup_window")]
+    pub dedup_window_secs: u64,
+    /// Template producing the dedup key (defaults to event, agent and error code)
+    #[serde(default)]
+    pub dedup_key: Option<String>,
+}
+
+fn default_dedup_window() -> u64 {
+    DEFAULT_DEDUP_WINDOW_SECS
+}
+
+impl NotificationRule {
+    /// Create a rule with the default template and dedup window.
+    pub fn new(name: impl Into<String>, filter: EventFilter) -> Self {
+        Self {
+            name: name.into(),
+            filter,
+            template: NotificationTemplate::default(),
+            channels: Vec::new(),
+            dedup_window_secs: DEFAULT_DEDUP_WINDOW_SECS,
+            dedup_key: None,
+        }
+    }
+
+    /// Use the given template.
+    pub fn with_template(mut self, template: NotificationTemplate) -> Self {
+        self.template = template;
+        self
+    }
+
+    /// Set the dedup window (zero disables deduplication).
+    pub fn with_dedup_window(mut self, window: Duration) -> Self {
+        self.dedup_window_secs = window.as_secs();
+        self
+    }
+
+    /// Add a delivery channel.
+    pub fn with_channel(mut self, channel: ChannelConfig) -> Self {
+        self.channels.push(channel);
+        self
+    }
+}
+
+/// Selects the kernel events a rule reacts to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventFilter {
+    /// System errors at or above a severity, optionally from one component
+    SystemError {
+        /// Minimum severity to notify about
+        #[serde(default = "default_min_severity")]
+        min_severity: ErrorSeverity,
+        /// Only errors reported by this component
+        #[serde(default)]
+        component: Option<String>,
+    },
+    /// Agent terminations, optionally restricted to certain reasons
+    AgentTerminated {
+        /// Reasons to notify about (empty matches every termination)
+        #[serde(default)]
+        reasons: Vec<TerminationReason>,
+    },
+    /// Failed tasks
+    TaskFailed,
+    /// Tasks that exceeded their time limit
+    TaskTimeout,
+    /// Resource allocation errors
+    ResourceError,
+}
+
+fn default_min_severity() -> ErrorSeverity {
+    ErrorSeverity::Error
+}
+
+impl EventFilter {
+    /// Whether `event` is selected by this filter.
+    pub fn matches(&self, event: &KernelEvent) -> bool {
+        match (self, event) {
+            (
+                EventFilter::SystemError { min_severity, component },
+                KernelEvent::SystemError { severity, context, .. },
+            ) => {
+                severity_rank(severity) >= severity_rank(min_severity)
+                    && component.as_ref().is_none_or(|c| *c == context.component)
+            }
+            (EventFilter::AgentTerminated { reasons }, KernelEvent::AgentTerminated { reason, .. }) => {
+                reasons.is_empty() || reasons.contains(reason)
+            }
+            (EventFilter::TaskFailed, KernelEvent::TaskFailed { .. }) => true,
+            (EventFilter::TaskTimeout, KernelEvent::TaskTimeout { .. }) => true,
+            (EventFilter::ResourceError, KernelEvent::ResourceError { .. }) => true,
+            _ => false,
+        }
+    }
+}
+
+fn severity_rank(severity: &ErrorSeverity) -> u8 {
+    match severity {
+        ErrorSeverity::Info => 0,
+        ErrorSeverity::Warning => 1,
+        ErrorSeverity::Error => 2,
+        ErrorSeverity::Critical => 3,
+    }
+}
+
+/// Subject and body templates for a notification.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotificationTemplate {
+    /// Subject line (email subject, webhook `subject` field)
+    pub subject: String,
+    /// Message body
+    pub body: String,
+}
+
+impl Default for NotificationTemplate {
+    fn default() -> Self {
+        Self {
+            subject: "[toka] {{event}}".to_string(),
+            body: "{{summary}}".to_string(),
+        }
+    }
+}
+
+/// Substitute `{{name}}` placeholders in `template` with `vars`.
+///
+/// Unknown variables render as an empty string; an unterminated placeholder
+/// is kept verbatim.
+pub fn render_template(template: &str, vars: &HashMap<&str, String>) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find("}}") {
+            Some(end) => {
+                if let Some(value) = vars.get(after[..end].trim()) {
+                    out.push_str(value);
+                }
+                rest = &after[end + 2..];
+            }
+            None => {
+                out.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Template variables describing `event`.
+pub fn event_variables(event: &KernelEvent) -> HashMap<&'static str, String> {
+    let mut vars = HashMap::new();
+    vars.insert("event", event_name(event));
+
+    let summary = match event {
+        KernelEvent::SystemError { error_category, error_code, context, severity, timestamp } => {
+            vars.insert("error_code", error_code.clone());
+            vars.insert("component", context.component.clone());
+            vars.insert("severity", format!("{:?}", severity));
+            vars.insert("category", format!("{:?}", error_category));
+            vars.insert("timestamp", timestamp.to_rfc3339());
+            format!("{:?} system error {} in {}", severity, error_code, context.component)
+        }
+        KernelEvent::AgentTerminated { agent, reason, exit_code, timestamp } => {
+            vars.insert("agent", agent.0.to_string());
+            vars.insert("reason", format!("{:?}", reason));
+            vars.insert("exit_code", exit_code.to_string());
+            vars.insert("timestamp", timestamp.to_rfc3339());
+            format!("agent {} terminated ({:?}, exit code {})", agent.0, reason, exit_code)
+        }
+        KernelEvent::TaskFailed { task_id, agent, error, failure_reason, correlation_id, timestamp } => {
+            vars.insert("agent", agent.0.to_string());
+            if let Some(correlation_id) = correlation_id {
+                vars.insert("correlation_id", correlation_id.clone());
+            }
+            vars.insert("task_id", task_id.clone());
+            vars.insert("error", error.clone());
+            vars.insert("reason", format!("{:?}", failure_reason));
+            vars.insert("timestamp", timestamp.to_rfc3339());
+            format!("task {} failed on agent {}: {}", task_id, agent.0, error)
+        }
+        KernelEvent::TaskTimeout { task_id, agent, timeout_duration_ms, correlation_id, timestamp } => {
+            vars.insert("agent", agent.0.to_string());
+            if let Some(correlation_id) = correlation_id {
+                vars.insert("correlation_id", correlation_id.clone());
+            }
+            vars.insert("task_id", task_id.clone());
+            vars.insert("timestamp", timestamp.to_rfc3339());
+            format!("task {} on agent {} timed out after {}ms", task_id, agent.0, timeout_duration_ms)
+        }
+        KernelEvent::ResourceError { resource_type, requested, available, agent, timestamp } => {
+            if let Some(agent) = agent {
+                vars.insert("agent", agent.0.to_string());
+            }
+            vars.insert("resource", format!("{:?}", resource_type));
+            vars.insert("requested", requested.to_string());
+            vars.insert("available", available.to_string());
+            vars.insert("timestamp", timestamp.to_rfc3339());
+            format!("{:?} exhausted: requested {}, available {}", resource_type, requested, available)
+        }
+        _ => vars["event"].clone(),
+    };
+    vars.insert("summary", summary);
+    vars
+}
+
+/// Variant name of a kernel event, e.g. `AgentTerminated`.
+fn event_name(event: &KernelEvent) -> String {
+    // KernelEvent is externally tagged, so the variant name is the only key
+    match serde_json::to_value(event) {
+        Ok(serde_json::Value::Object(map)) => map.keys().next().cloned().unwrap_or_default(),
+        _ => "KernelEvent".to_string(),
+    }
+}
+
+/// A rendered notification ready for delivery.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    /// Name of the rule that produced the notification
+    pub rule: String,
+    /// Rendered subject
+    pub subject: String,
+    /// Rendered body
+    pub body: String,
+    /// The triggering event
+    pub event: KernelEvent,
+    /// When the notification was produced
+    pub created_at: DateTime<Utc>,
+}
+
+/// A delivery channel for notifications.
+#[async_trait]
+pub trait NotificationSink: Send + Sync {
+    /// Short channel description used in logs.
+    fn name(&self) -> &str;
+
+    /// Deliver a notification.
+    async fn deliver(&self, notification: &Notification) -> Result<()>;
+}
+
+/// Delivery channel configuration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChannelConfig {
+    /// POST the notification as JSON to a URL
+    Webhook {
+        /// Target URL
+        url: String,
+        /// Extra request headers (e.g. authorization)
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+    /// Send an email through an SMTP relay
+    Email {
+        /// SMTP relay host
+        smtp_host: String,
+        /// SMTP port (defaults to 587)
+        #[serde(default = "default_smtp_port")]
+        smtp_port: u16,
+        /// SMTP username
+        #[serde(default)]
+        username: Option<String>,
+        /// Environment variable holding the SMTP password
+        #[serde(default)]
+        password_env: Option<String>,
+        /// Sender address
+        from: String,
+        /// Recipient addresses
+        to: Vec<String>,
+    },
+    /// Run a local command with the body on stdin
+    Command {
+        /// Program to execute
+        program: String,
+        /// Program arguments
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+impl ChannelConfig {
+    /// Build the sink for this channel.
+    pub fn build(&self) -> Result<Arc<dyn NotificationSink>> {
+        Ok(match self {
+            ChannelConfig::Webhook { url, headers } => Arc::new(WebhookSink::new(url, headers.clone())?),
+            ChannelConfig::Email { smtp_host, smtp_port, username, password_env, from, to } => {
+                let credentials = match (username, password_env) {
+                    (Some(user), Some(var)) => {
+                        let password = std::env::var(var)
+                            .with_context(|| format!("SMTP password variable {} is not set", var))?;
+                        Some((user.clone(), password))
+                    }
+                    (Some(user), None) => Some((user.clone(), String::new())),
+                    _ => None,
+                };
+                Arc::new(EmailSink::new(smtp_host, *smtp_port, credentials, from, to)?)
+            }
+            ChannelConfig::Command { program, args } => Arc::new(CommandSink::new(program, args.clone())),
+        })
+    }
+}
+
+/// Delivers notifications as JSON `POST` requests.
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: reqwest::Url,
+    headers: HashMap<String, String>,
+}
+
+impl WebhookSink {
+    /// Create a webhook sink for `url`.
+    pub fn new(url: &str, headers: HashMap<String, String>) -> Result<Self> {
+        let url = reqwest::Url::parse(url).with_context(|| format!("invalid webhook URL {}", url))?;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        Ok(Self { client, url, headers })
+    }
+}
+
+#[async_trait]
+impl NotificationSink for WebhookSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn deliver(&self, notification: &Notification) -> Result<()> {
+        let mut request = self.client.post(self.url.clone()).json(notification);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Delivers notifications as email through an SMTP relay.
+pub struct EmailSink {
+    transport: lettre::AsyncSmtpTransport<lettre::Tokio1Executor>,
+    from: lettre::message::Mailbox,
+    to: Vec<lettre::message::Mailbox>,
+}
+
+impl EmailSink {
+    /// Create an email sink using STARTTLS on `host:port`.
+    pub fn new(
+        host: &str,
+        port: u16,
+        credentials: Option<(String, String)>,
+        from: &str,
+        to: &[String],
+    ) -> Result<Self> {
+        use lettre::transport::smtp::authentication::Credentials;
+
+        let mut builder = lettre::AsyncSmtpTransport::<lettre::Tokio1Executor>::starttls_relay(host)?.port(port);
+        if let Some((user, password)) = credentials {
+            builder = builder.credentials(Credentials::new(user, password));
+        }
+        let to = to
+            .iter()
+            .map(|addr| addr.parse::<lettre::message::Mailbox>().with_context(|| format!("invalid recipient {}", addr)))
+            .collect::<Result<Vec<_>>>()?;
+        if to.is_empty() {
+            anyhow::bail!("email channel needs at least one recipient");
+        }
+        Ok(Self {
+            transport: builder.build(),
+            from: from.parse::<lettre::message::Mailbox>().with_context(|| format!("invalid sender {}", from))?,
+            to,
+        })
+    }
+}
+
+#[async_trait]
+impl NotificationSink for EmailSink {
+    fn name(&self) -> &str {
+        "email"
+    }
+
+    async fn deliver(&self, notification: &Notification) -> Result<()> {
+        use lettre::AsyncTransport;
+
+        let mut message = lettre::Message::builder()
+            .from(self.from.clone())
+            .subject(notification.subject.clone());
+        for recipient in &self.to {
+            message = message.to(recipient.clone());
+        }
+        self.transport.send(message.body(notification.body.clone())?).await?;
+        Ok(())
+    }
+}
+
+/// Runs a local command for each notification.
+///
+/// The body is written to the command's stdin; the rule name, subject and
+/// JSON-encoded event are passed in `TOKA_NOTIFY_RULE`,
+/// `TOKA_NOTIFY_SUBJECT` and `TOKA_NOTIFY_EVENT`.
+pub struct CommandSink {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandSink {
+    /// Create a command hook running `program` with `args`.
+    pub fn new(program: impl Into<String>, args: Vec<String>) -> Self {
+        Self { program: program.into(), args }
+    }
+}
+
+#[async_trait]
+impl NotificationSink for CommandSink {
+    fn name(&self) -> &str {
+        &self.program
+    }
+
+    async fn deliver(&self, notification: &Notification) -> Result<()> {
+        let mut child = tokio::process::Command::new(&self.program)
+            .args(&self.args)
+            .env("TOKA_NOTIFY_RULE", &notification.rule)
+            .env("TOKA_NOTIFY_SUBJECT", &notification.subject)
+            .env("TOKA_NOTIFY_EVENT", serde_json::to_string(&notification.event)?)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("failed to start {}", self.program))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(notification.body.as_bytes()).await?;
+        }
+        let status = tokio::time::timeout(COMMAND_HOOK_TIMEOUT, child.wait())
+            .await
+            .with_context(|| format!("{} timed out", self.program))??;
+        if !status.success() {
+            anyhow::bail!("{} exited with {}", self.program, status);
+        }
+        Ok(())
+    }
+}
+
+struct RegisteredRule {
+    rule: NotificationRule,
+    sinks: Vec<Arc<dyn NotificationSink>>,
+}
+
+/// Evaluates notification rules against kernel events and delivers the results.
+pub struct Notifier {
+    rules: Vec<RegisteredRule>,
+    /// Last delivery time per dedup key
+    last_sent: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Notifier {
+    /// Create a notifier without rules.
+    pub fn new() -> Self {
+        Self {
+            rules: Vec::new(),
+            last_sent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Create a notifier from configuration, building every channel.
+    pub fn from_config(config: &NotifierConfig) -> Result<Self> {
+        let mut notifier = Self::new();
+        for rule in &config.rules {
+            let sinks = rule
+                .channels
+                .iter()
+                .map(ChannelConfig::build)
+                .collect::<Result<Vec<_>>>()
+                .with_context(|| format!("invalid channel in notification rule {}", rule.name))?;
+            notifier.add_rule(rule.clone(), sinks);
+        }
+        Ok(notifier)
+    }
+
+    /// Register a rule delivering to `sinks`.
+    ///
+    /// The rule's `channels` are not consulted; use [`from_config`](Self::from_config)
+    /// to build sinks from configuration.
+    pub fn add_rule(&mut self, rule: NotificationRule, sinks: Vec<Arc<dyn NotificationSink>>) {
+        self.rules.push(RegisteredRule { rule, sinks });
+    }
+
+    /// Number of registered rules.
+    pub fn rule_count(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Evaluate all rules against `event` and deliver resulting notifications.
+    ///
+    /// Returns the notifications that were produced; suppressed duplicates are
+    /// not included. Delivery failures are logged and do not stop delivery to
+    /// the remaining channels.
+    pub async fn handle(&self, event: &KernelEvent, now: DateTime<Utc>) -> Vec<Notification> {
+        let mut produced = Vec::new();
+        let vars = event_variables(event);
+
+        for registered in &self.rules {
+            let rule = &registered.rule;
+            if !rule.filter.matches(event) {
+                continue;
+            }
+
+            let dedup_key = format!(
+                "{}/{}",
+                rule.name,
+                render_template(rule.dedup_key.as_deref().unwrap_or(DEFAULT_DEDUP_KEY), &vars)
+            );
+            if !self.claim(dedup_key, rule.dedup_window_secs, now).await {
+                debug!("Suppressed duplicate notification for rule {}", rule.name);
+                continue;
+            }
+
+            let notification = Notification {
+                rule: rule.name.clone(),
+                subject: render_template(&rule.template.subject, &vars),
+                body: render_template(&rule.template.body, &vars),
+                event: event.clone(),
+                created_at: now,
+            };
+            for sink in &registered.sinks {
+                if let Err(e) = sink.deliver(&notification).await {
+                    warn!("Notification {} via {} failed: {}", rule.name, sink.name(), e);
+                }
+            }
+            produced.push(notification);
+        }
+
+        produced
+    }
+
+    /// Record a delivery for `key` unless one happened within the window.
+    async fn claim(&self, key: String, window_secs: u64, now: DateTime<Utc>) -> bool {
+        let window = chrono::Duration::seconds(window_secs.min(i64::MAX as u64) as i64);
+        let mut last_sent = self.last_sent.lock().await;
+
+        // Forget keys older than the longest window so the map stays bounded
+        let longest = self.rules.iter().map(|r| r.rule.dedup_window_secs).max().unwrap_or(0);
+        let horizon = chrono::Duration::seconds(longest.min(i64::MAX as u64) as i64);
+        last_sent.retain(|_, sent| now.signed_duration_since(*sent) < horizon);
+
+        if let Some(sent) = last_sent.get(&key) {
+            if now.signed_duration_since(*sent) < window {
+                return false;
+            }
+        }
+        last_sent.insert(key, now);
+        true
+    }
+
+    /// Subscribe to `bus` and process events until the bus is closed.
+    pub fn spawn(self: Arc<Self>, bus: &dyn EventBus) -> tokio::task::JoinHandle<()> {
+        let mut rx = bus.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => {
+                        self.handle(&event, Utc::now()).await;
+                    }
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!("Notifier lagged behind the event bus; {} events skipped", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toka_bus_core::{ErrorCategory, ErrorContext, InMemoryBus};
+    use toka_types::EntityId;
+
+    #[derive(Default)]
+    struct RecordingSink {
+        delivered: std::sync::Mutex<Vec<Notification>>,
+    }
+
+    #[async_trait]
+    impl NotificationSink for RecordingSink {
+        fn name(&self) -> &str {
+            "recording"
+        }
+
+        async fn deliver(&self, notification: &Notification) -> Result<()> {
+            self.delivered.lock().unwrap().push(notification.clone());
+            Ok(())
+        }
+    }
+
+    fn system_error(severity: ErrorSeverity) -> KernelEvent {
+        KernelEvent::SystemError {
+            error_category: ErrorCategory::Storage,
+            error_code: "E_DISK".to_string(),
+            context: ErrorContext {
+                component: "store".to_string(),
+                metadata: HashMap::new(),
+            },
+            severity,
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn terminated(agent: u128, reason: TerminationReason) -> KernelEvent {
+        KernelEvent::AgentTerminated {
+            agent: EntityId(agent),
+            reason,
+            exit_code: 1,
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_filters() {
+        let errors = EventFilter::SystemError { min_severity: ErrorSeverity::Error, component: None };
+        assert!(errors.matches(&system_error(ErrorSeverity::Critical)));
+        assert!(!errors.matches(&system_error(ErrorSeverity::Warning)));
+
+        let crashes = EventFilter::AgentTerminated { reasons: vec![TerminationReason::Crashed] };
+        assert!(crashes.matches(&terminated(1, TerminationReason::Crashed)));
+        assert!(!crashes.matches(&terminated(1, TerminationReason::Completed)));
+        assert!(!crashes.matches(&system_error(ErrorSeverity::Critical)));
+    }
+
+    #[test]
+    fn test_render_template() {
+        let vars = event_variables(&terminated(7, TerminationReason::Crashed));
+        assert_eq!(
+            render_template("{{event}}: agent {{ agent }} ({{reason}}){{missing}}", &vars),
+            "AgentTerminated: agent 7 (Crashed)"
+        );
+        assert_eq!(render_template("broken {{agent", &vars), "broken {{agent");
+    }
+
+    #[tokio::test]
+    async fn test_dedup_window() {
+        let sink = Arc::new(RecordingSink::default());
+        let mut notifier = Notifier::new();
+        notifier.add_rule(
+            NotificationRule::new(
+                "crashes",
+                EventFilter::AgentTerminated { reasons: vec![TerminationReason::Crashed] },
+            )
+            .with_dedup_window(Duration::from_secs(60)),
+            vec![sink.clone()],
+        );
+
+        let now = Utc::now();
+        assert_eq!(notifier.handle(&terminated(1, TerminationReason::Crashed), now).await.len(), 1);
+        // Same agent within the window is suppressed, another agent is not
+        assert!(notifier.handle(&terminated(1, TerminationReason::Crashed), now).await.is_empty());
+        assert_eq!(notifier.handle(&terminated(2, TerminationReason::Crashed), now).await.len(), 1);
+        // After the window the same condition notifies again
+        let later = now + chrono::Duration::seconds(61);
+        assert_eq!(notifier.handle(&terminated(1, TerminationReason::Crashed), later).await.len(), 1);
+
+        let delivered = sink.delivered.lock().unwrap();
+        assert_eq!(delivered.len(), 3);
+        assert_eq!(delivered[0].subject, "[toka] AgentTerminated");
+    }
+
+    #[tokio::test]
+    async fn test_spawned_notifier_reacts_to_bus() {
+        let bus = InMemoryBus::new(16);
+        let sink = Arc::new(RecordingSink::default());
+        let mut notifier = Notifier::new();
+        notifier.add_rule(
+            NotificationRule::new(
+                "errors",
+                EventFilter::SystemError { min_severity: ErrorSeverity::Error, component: None },
+            ),
+            vec![sink.clone()],
+        );
+        let handle = Arc::new(notifier).spawn(&bus);
+
+        bus.publish(&system_error(ErrorSeverity::Info)).unwrap();
+        bus.publish(&system_error(ErrorSeverity::Critical)).unwrap();
+
+        for _ in 0..50 {
+            if !sink.delivered.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        handle.abort();
+
+        let delivered = sink.delivered.lock().unwrap();
+        assert_eq!(delivered.len(), 1);
+        assert!(delivered[0].body.contains("E_DISK"));
+    }
+
+    #[test]
+    fn test_config_from_yaml() {
+        let config: NotifierConfig = serde_yaml::from_str(
+            r#"
+rules:
+  - name: crashes
+    filter:
+      event: agent_terminated
+      reasons: [Crashed]
+    channels:
+      - type: command
+        program: /usr/local/bin/page
+"#,
+        )
+        .unwrap();
+        let notifier = Notifier::from_config(&config).unwrap();
+        assert_eq!(notifier.rule_count(), 1);
+        assert_eq!(config.rules[0].dedup_window_secs, DEFAULT_DEDUP_WINDOW_SECS);
+    }
+}
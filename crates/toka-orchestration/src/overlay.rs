@@ -0,0 +1,204 @@
+//! Environment overlays and variable interpolation for agent configurations.
+//!
+//! A base configuration is shared by every environment. An overlay for an
+//! environment lives at `overlays/<environment>/<file>` next to the base
+//! file and only states what differs; [`merge_yaml`] patches it into the
+//! base. Afterwards [`interpolate`] substitutes variables in string values:
+//!
+//! - `${NAME}` is required; a configuration referencing unset required
+//!   variables is rejected, naming all of them at once.
+//! - `${NAME:-default}` falls back to `default` when `NAME` is unset.
+//! - `$$` produces a literal `$`.
+
+use std::path::{Path, PathBuf};
+
+use serde_yaml::Value;
+
+/// Directory below the configuration directory holding overlays, one
+/// subdirectory per environment.
+pub const OVERLAY_DIR: &str = "overlays";
+
+/// Path of the overlay of `base` for `environment`.
+pub fn overlay_path(base: &Path, environment: &str) -> Option<PathBuf> {
+    let file_name = base.file_name()?;
+    let dir = base.parent().unwrap_or_else(|| Path::new("."));
+    Some(dir.join(OVERLAY_DIR).join(environment).join(file_name))
+}
+
+/// Patch `overlay` into `base`.
+///
+/// Mappings are merged key by key, recursively. Any other overlay value,
+/// including a sequence, replaces the base value, and a `null` removes the
+/// key from the base.
+pub fn merge_yaml(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                if value.is_null() {
+                    base.remove(&key);
+                } else if let Some(existing) = base.get_mut(&key) {
+                    merge_yaml(existing, value);
+                } else {
+                    base.insert(key, value);
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Why variables could not be substituted.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum InterpolationError {
+    /// Required variables are not set
+    #[error("missing required variables: {}", .0.join(", "))]
+    Missing(Vec<String>),
+    /// A value contains an unterminated or empty `${...}` reference
+    #[error("malformed variable reference in '{0}'")]
+    Malformed(String),
+}
+
+/// Substitute variables in every string value of `value`.
+///
+/// `lookup` resolves variable names. All string values are processed before
+/// failing, so the error lists every missing variable, sorted and without
+/// duplicates.
+pub fn interpolate(value: &mut Value, lookup: &dyn Fn(&str) -> Option<String>) -> Result<(), InterpolationError> {
+    let mut missing = Vec::new();
+    interpolate_value(value, lookup, &mut missing)?;
+    if missing.is_empty() {
+        return Ok(());
+    }
+    missing.sort();
+    missing.dedup();
+    Err(InterpolationError::Missing(missing))
+}
+
+fn interpolate_value(
+    value: &mut Value,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    missing: &mut Vec<String>,
+) -> Result<(), InterpolationError> {
+    match value {
+        Value::String(s) if s.contains('$') => *s = interpolate_str(s, lookup, missing)?,
+        Value::Sequence(items) => {
+            for item in items {
+                interpolate_value(item, lookup, missing)?;
+            }
+        }
+        Value::Mapping(map) => {
+            for (_, item) in map.iter_mut() {
+                interpolate_value(item, lookup, missing)?;
+            }
+        }
+        Value::Tagged(tagged) => interpolate_value(&mut tagged.value, lookup, missing)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+fn interpolate_str(
+    s: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    missing: &mut Vec<String>,
+) -> Result<String, InterpolationError> {
+    let malformed = || InterpolationError::Malformed(s.to_string());
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("$$") {
+            out.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let end = after.find('}').ok_or_else(malformed)?;
+            let reference = &after[..end];
+            let (name, default) = match reference.split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (reference, None),
+            };
+            if !is_variable_name(name) {
+                return Err(malformed());
+            }
+            match (lookup(name), default) {
+                (Some(resolved), _) => out.push_str(&resolved),
+                (None, Some(default)) => out.push_str(default),
+                (None, None) => missing.push(name.to_string()),
+            }
+            rest = &after[end + 1..];
+        } else {
+            out.push('$');
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_and_interpolate() {
+        let mut base: Value = serde_yaml::from_str(
+            r#"
+security:
+  sandbox: true
+  resource_limits: { max_memory: "${MAX_MEMORY:-100MB}", max_cpu: "50%" }
+reporting:
+  channels: ["${CHANNEL}", "cost: $$5"]
+debug: { verbose: true }
+"#,
+        )
+        .unwrap();
+        let overlay: Value = serde_yaml::from_str(
+            r#"
+security:
+  resource_limits: { max_cpu: "90%" }
+reporting:
+  channels: ["${CHANNEL}", "${PAGER}"]
+debug: ~
+"#,
+        )
+        .unwrap();
+        merge_yaml(&mut base, overlay);
+
+        let lookup = |name: &str| (name == "CHANNEL").then(|| "ops".to_string());
+        assert_eq!(
+            interpolate(&mut base.clone(), &lookup),
+            Err(InterpolationError::Missing(vec!["PAGER".to_string()]))
+        );
+
+        let lookup = |name: &str| match name {
+            "CHANNEL" => Some("ops".to_string()),
+            "PAGER" => Some("pagerduty".to_string()),
+            _ => None,
+        };
+        interpolate(&mut base, &lookup).unwrap();
+        let expected: Value = serde_yaml::from_str(
+            r#"
+security:
+  sandbox: true
+  resource_limits: { max_memory: "100MB", max_cpu: "90%" }
+reporting:
+  channels: ["ops", "pagerduty"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(base, expected);
+
+        let mut unterminated = Value::String("${OOPS".to_string());
+        assert!(matches!(interpolate(&mut unterminated, &lookup), Err(InterpolationError::Malformed(_))));
+        let mut literal = Value::String("cost: $$5 or $3".to_string());
+        interpolate(&mut literal, &lookup).unwrap();
+        assert_eq!(literal, Value::String("cost: $5 or $3".to_string()));
+    }
+}
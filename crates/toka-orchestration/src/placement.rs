@@ -0,0 +1,478 @@
+//! Worker node registry and agent placement.
+//!
+//! Agents do not have to run on the orchestrating host. Runtime workers in
+//! other regions or on GPU machines register themselves as [`WorkerNode`]s
+//! with a set of labels, a GPU count and a capacity. When the
+//! [`OrchestrationEngine`](crate::OrchestrationEngine) spawns an agent, the
+//! [`NodeRegistry`] picks a node that satisfies the agent's
+//! [`PlacementConstraints`] and a [`WorkerDispatcher`] hands the agent to it.
+//!
+//! A node is eligible for an agent when:
+//!
+//! - every entry of `node_selector` is present in the node's labels with the
+//!   same value;
+//! - the node has at least `min_gpus` GPUs;
+//! - no agent on the node lists the new agent in its `anti_affinity`, and the
+//!   new agent lists none of them (anti-affinity is symmetric);
+//! - the node still has free capacity.
+//!
+//! Among eligible nodes the least loaded one wins, ties broken by node id so
+//! placement is deterministic.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use toka_types::{AgentConfig, PlacementConstraints};
+
+/// Conventional label holding a node's region.
+pub const REGION_LABEL: &str = "region";
+
+/// Number of agents a node accepts when no capacity is given.
+pub const DEFAULT_NODE_CAPACITY: usize = 4;
+
+/// A runtime worker node agents can be placed on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkerNode {
+    /// Unique node identifier
+    pub id: String,
+    /// Base URL of the node's runtime worker API
+    pub endpoint: String,
+    /// Node labels (region, hardware, ...)
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    /// Number of GPUs available on the node
+    #[serde(default)]
+    pub gpus: u32,
+    /// Maximum number of agents the node runs at once
+    #[serde(default = "default_capacity")]
+    pub capacity: usize,
+}
+
+fn default_capacity() -> usize {
+    DEFAULT_NODE_CAPACITY
+}
+
+impl WorkerNode {
+    /// Create a node without labels or GPUs.
+    pub fn new(id: impl Into<String>, endpoint: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            endpoint: endpoint.into(),
+            labels: HashMap::new(),
+            gpus: 0,
+            capacity: DEFAULT_NODE_CAPACITY,
+        }
+    }
+
+    /// Add a label.
+    pub fn with_label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.labels.insert(key.into(), value.into());
+        self
+    }
+
+    /// Set the node's region label.
+    pub fn with_region(self, region: impl Into<String>) -> Self {
+        self.with_label(REGION_LABEL, region)
+    }
+
+    /// Set the number of GPUs.
+    pub fn with_gpus(mut self, gpus: u32) -> Self {
+        self.gpus = gpus;
+        self
+    }
+
+    /// Set the maximum number of concurrent agents.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Region of the node, if labelled.
+    pub fn region(&self) -> Option<&str> {
+        self.labels.get(REGION_LABEL).map(String::as_str)
+    }
+
+    /// Whether the node's labels and hardware satisfy `constraints`.
+    ///
+    /// Anti-affinity and capacity depend on the other agents placed on the
+    /// node and are checked by the [`NodeRegistry`].
+    pub fn matches(&self, constraints: &PlacementConstraints) -> bool {
+        self.gpus >= constraints.min_gpus
+            && constraints
+                .node_selector
+                .iter()
+                .all(|(key, value)| self.labels.get(key) == Some(value))
+    }
+}
+
+/// Errors raised while placing an agent.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PlacementError {
+    /// No registered node satisfies the agent's constraints
+    #[error("no worker node satisfies the placement constraints of agent {0}")]
+    NoMatchingNode(String),
+    /// Matching nodes exist but all of them are full
+    #[error("all worker nodes eligible for agent {0} are at capacity")]
+    NoCapacity(String),
+    /// The node is not registered
+    #[error("unknown worker node {0}")]
+    UnknownNode(String),
+}
+
+/// Load of a registered node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeStatus {
+    /// The node
+    pub node: WorkerNode,
+    /// Names of the agents placed on the node, sorted
+    pub agents: Vec<String>,
+    /// Time of the last registration or heartbeat
+    pub last_heartbeat: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+struct NodeEntry {
+    node: WorkerNode,
+    last_heartbeat: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+struct Placement {
+    node_id: String,
+    anti_affinity: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct RegistryState {
+    nodes: BTreeMap<String, NodeEntry>,
+    placements: HashMap<String, Placement>,
+}
+
+impl RegistryState {
+    fn agents_on(&self, node_id: &str) -> impl Iterator<Item = (&String, &Placement)> {
+        let node_id = node_id.to_string();
+        self.placements
+            .iter()
+            .filter(move |(_, placement)| placement.node_id == node_id)
+    }
+}
+
+/// Registry of worker nodes and the agents placed on them.
+#[derive(Debug, Default)]
+pub struct NodeRegistry {
+    state: Mutex<RegistryState>,
+}
+
+impl NodeRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, RegistryState> {
+        // Placement bookkeeping stays consistent even if a holder panicked
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Register a node, replacing any node with the same id.
+    ///
+    /// Agents already placed on a replaced node stay assigned to it.
+    pub fn register(&self, node: WorkerNode) {
+        let mut state = self.lock();
+        state.nodes.insert(
+            node.id.clone(),
+            NodeEntry {
+                node,
+                last_heartbeat: Utc::now(),
+            },
+        );
+    }
+
+    /// Remove a node and return the names of the agents that were placed on it.
+    pub fn deregister(&self, node_id: &str) -> Vec<String> {
+        let mut state = self.lock();
+        state.nodes.remove(node_id);
+        let mut orphaned: Vec<String> = state.agents_on(node_id).map(|(agent, _)| agent.clone()).collect();
+        for agent in &orphaned {
+            state.placements.remove(agent);
+        }
+        orphaned.sort();
+        orphaned
+    }
+
+    /// Record a heartbeat from a node.
+    pub fn heartbeat(&self, node_id: &str) -> Result<(), PlacementError> {
+        let mut state = self.lock();
+        let entry = state
+            .nodes
+            .get_mut(node_id)
+            .ok_or_else(|| PlacementError::UnknownNode(node_id.to_string()))?;
+        entry.last_heartbeat = Utc::now();
+        Ok(())
+    }
+
+    /// Deregister nodes whose last heartbeat is older than `ttl`.
+    ///
+    /// Returns the ids of the removed nodes.
+    pub fn expire_stale(&self, ttl: Duration) -> Vec<String> {
+        let cutoff = chrono::Duration::from_std(ttl)
+            .ok()
+            .and_then(|ttl| Utc::now().checked_sub_signed(ttl))
+            .unwrap_or(DateTime::<Utc>::MIN_UTC);
+        let stale: Vec<String> = self
+            .lock()
+            .nodes
+            .values()
+            .filter(|entry| entry.last_heartbeat < cutoff)
+            .map(|entry| entry.node.id.clone())
+            .collect();
+        for node_id in &stale {
+            self.deregister(node_id);
+        }
+        stale
+    }
+
+    /// Status of every registered node, sorted by id.
+    pub fn nodes(&self) -> Vec<NodeStatus> {
+        let state = self.lock();
+        state
+            .nodes
+            .values()
+            .map(|entry| {
+                let mut agents: Vec<String> = state.agents_on(&entry.node.id).map(|(agent, _)| agent.clone()).collect();
+                agents.sort();
+                NodeStatus {
+                    node: entry.node.clone(),
+                    agents,
+                    last_heartbeat: entry.last_heartbeat,
+                }
+            })
+            .collect()
+    }
+
+    /// Node an agent is currently placed on.
+    pub fn placement_of(&self, agent_name: &str) -> Option<WorkerNode> {
+        let state = self.lock();
+        let placement = state.placements.get(agent_name)?;
+        state.nodes.get(&placement.node_id).map(|entry| entry.node.clone())
+    }
+
+    /// Choose a node for `agent` and reserve a slot on it.
+    ///
+    /// Placing an agent that already has a node returns that node.
+    pub fn place(&self, agent: &AgentConfig) -> Result<WorkerNode, PlacementError> {
+        let name = &agent.metadata.name;
+        let constraints = &agent.placement;
+        let mut state = self.lock();
+
+        if let Some(existing) = state.placements.get(name) {
+            if let Some(entry) = state.nodes.get(&existing.node_id) {
+                return Ok(entry.node.clone());
+            }
+        }
+
+        let mut matching = false;
+        let mut best: Option<(usize, &WorkerNode)> = None;
+        for entry in state.nodes.values() {
+            let node = &entry.node;
+            if !node.matches(constraints) {
+                continue;
+            }
+            let conflicts = state.agents_on(&node.id).any(|(other, placement)| {
+                constraints.anti_affinity.contains(other) || placement.anti_affinity.contains(name)
+            });
+            if conflicts {
+                continue;
+            }
+            matching = true;
+
+            let load = state.agents_on(&node.id).count();
+            if load >= node.capacity {
+                continue;
+            }
+            // Nodes are visited in id order, so the first least-loaded node wins ties
+            if best.is_none_or(|(best_load, _)| load < best_load) {
+                best = Some((load, node));
+            }
+        }
+
+        let node = match best {
+            Some((_, node)) => node.clone(),
+            None if matching => return Err(PlacementError::NoCapacity(name.clone())),
+            None => return Err(PlacementError::NoMatchingNode(name.clone())),
+        };
+        state.placements.insert(
+            name.clone(),
+            Placement {
+                node_id: node.id.clone(),
+                anti_affinity: constraints.anti_affinity.clone(),
+            },
+        );
+        Ok(node)
+    }
+
+    /// Free the slot held by an agent.
+    pub fn release(&self, agent_name: &str) -> Option<String> {
+        self.lock()
+            .placements
+            .remove(agent_name)
+            .map(|placement| placement.node_id)
+    }
+}
+
+/// Hands agents to the runtime worker on a remote node.
+#[async_trait]
+pub trait WorkerDispatcher: Send + Sync {
+    /// Start `agent` on `node`.
+    async fn dispatch(&self, node: &WorkerNode, agent: &AgentConfig) -> Result<()>;
+}
+
+/// Dispatches agents by posting their configuration to the node's
+/// `<endpoint>/v1/agents` route.
+#[derive(Debug, Clone, Default)]
+pub struct HttpWorkerDispatcher {
+    client: reqwest::Client,
+}
+
+impl HttpWorkerDispatcher {
+    /// Create a dispatcher with a default HTTP client.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl WorkerDispatcher for HttpWorkerDispatcher {
+    async fn dispatch(&self, node: &WorkerNode, agent: &AgentConfig) -> Result<()> {
+        let url = format!("{}/v1/agents", node.endpoint.trim_end_matches('/'));
+        self.client
+            .post(&url)
+            .json(agent)
+            .send()
+            .await
+            .with_context(|| format!("failed to reach worker node {}", node.id))?
+            .error_for_status()
+            .with_context(|| format!("worker node {} rejected agent {}", node.id, agent.metadata.name))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        AgentCapabilities, AgentDependencies, AgentMetadata, AgentPriority, AgentSpecConfig, AgentTasks,
+        ReportingConfig, ReportingFrequency, ResourceLimits, SecurityConfig,
+    };
+
+    fn agent(name: &str, placement: PlacementConstraints) -> AgentConfig {
+        AgentConfig {
+            metadata: AgentMetadata {
+                name: name.to_string(),
+                version: "v1.0".to_string(),
+                created: "2024-01-01".to_string(),
+                workstream: "test".to_string(),
+                branch: "main".to_string(),
+            },
+            spec: AgentSpecConfig {
+                name: name.to_string(),
+                domain: "test".to_string(),
+                priority: AgentPriority::Medium,
+            },
+            capabilities: AgentCapabilities {
+                primary: vec!["test".to_string()],
+                secondary: vec![],
+            },
+            objectives: vec![],
+            tasks: AgentTasks { default: vec![] },
+            dependencies: AgentDependencies {
+                required: HashMap::new(),
+                optional: HashMap::new(),
+            },
+            reporting: ReportingConfig {
+                frequency: ReportingFrequency::Daily,
+                channels: vec![],
+                metrics: HashMap::new(),
+            },
+            security: SecurityConfig {
+                sandbox: true,
+                capabilities_required: vec![],
+                resource_limits: ResourceLimits {
+                    max_memory: "100MB".to_string(),
+                    max_cpu: "50%".to_string(),
+                    timeout: "1h".to_string(),
+                },
+            },
+            placement,
+        }
+    }
+
+    #[test]
+    fn test_node_selector_and_gpus() {
+        let registry = NodeRegistry::new();
+        registry.register(WorkerNode::new("cpu-eu", "http://cpu-eu").with_region("eu-west"));
+        registry.register(WorkerNode::new("gpu-us", "http://gpu-us").with_region("us-east").with_gpus(2));
+
+        let mut eu = PlacementConstraints::default();
+        eu.node_selector.insert(REGION_LABEL.to_string(), "eu-west".to_string());
+        assert_eq!(registry.place(&agent("eu", eu)).unwrap().id, "cpu-eu");
+
+        let gpu = PlacementConstraints { min_gpus: 1, ..Default::default() };
+        assert_eq!(registry.place(&agent("trainer", gpu)).unwrap().id, "gpu-us");
+
+        let mut impossible = PlacementConstraints::default();
+        impossible.node_selector.insert(REGION_LABEL.to_string(), "ap-south".to_string());
+        assert_eq!(
+            registry.place(&agent("nowhere", impossible)),
+            Err(PlacementError::NoMatchingNode("nowhere".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_anti_affinity_is_symmetric() {
+        let registry = NodeRegistry::new();
+        registry.register(WorkerNode::new("a", "http://a"));
+        registry.register(WorkerNode::new("b", "http://b"));
+
+        let primary = PlacementConstraints {
+            anti_affinity: vec!["replica".to_string()],
+            ..Default::default()
+        };
+        let first = registry.place(&agent("primary", primary)).unwrap();
+        // The replica declares nothing but is still kept apart from the primary
+        let second = registry.place(&agent("replica", PlacementConstraints::default())).unwrap();
+        assert_ne!(first.id, second.id);
+
+        registry.register(WorkerNode::new("a", "http://a").with_capacity(0));
+        registry.register(WorkerNode::new("b", "http://b").with_capacity(1));
+        assert_eq!(
+            registry.place(&agent("other", PlacementConstraints::default())),
+            Err(PlacementError::NoCapacity("other".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_release_and_deregister() {
+        let registry = NodeRegistry::new();
+        registry.register(WorkerNode::new("only", "http://only").with_capacity(1));
+
+        let config = agent("worker", PlacementConstraints::default());
+        assert_eq!(registry.place(&config).unwrap().id, "only");
+        // Placing again is idempotent
+        assert_eq!(registry.place(&config).unwrap().id, "only");
+        assert!(registry.place(&agent("second", PlacementConstraints::default())).is_err());
+
+        assert_eq!(registry.release("worker"), Some("only".to_string()));
+        assert!(registry.place(&agent("second", PlacementConstraints::default())).is_ok());
+
+        assert_eq!(registry.deregister("only"), vec!["second".to_string()]);
+        assert!(registry.placement_of("second").is_none());
+        assert!(registry.heartbeat("only").is_err());
+    }
+}
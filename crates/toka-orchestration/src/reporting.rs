@@ -0,0 +1,754 @@
+//! Periodic progress reports driven by agent reporting configuration.
+//!
+//! Every agent config carries a [`ReportingConfig`] naming how often it
+//! should report and which metrics matter. The [`Reporter`] keeps the latest
+//! [`AgentProgress`] per agent, decides when a report is due and renders it
+//! from a [`ReportTemplate`] as markdown or JSON. Reports can optionally be
+//! summarized by the LLM gateway, and are published as
+//! [`KernelEvent::ReportGenerated`] events and delivered to report sinks.
+//!
+//! Besides per-agent reports, each workstream gets a report on the most
+//! frequent schedule of its agents. `daily` and `weekly` reports are due once
+//! the period has elapsed since the previous one; `on-milestone` reports are
+//! due whenever progress crosses a quarter (25%, 50%, 75%, 100%).
+//!
+//! Reporting channels that are `http://` or `https://` URLs receive reports
+//! as JSON webhook payloads. Other channel names (e.g. `kernel-events`) are
+//! served by the event bus.
+//!
+//! Templates use the `{{name}}` placeholders of
+//! [`render_template`](crate::notifier::render_template). The available
+//! variables are `title`, `scope`, `name`, `workstream`, `frequency`,
+//! `progress`, `agents`, `agents_finished`, `tasks_attempted`,
+//! `tasks_completed`, `tasks_failed`, `llm_tokens`, `eta`, `generated_at`,
+//! `metrics` and, for agent reports, `agent`, `state` and `message`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{watch, Mutex};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use toka_agent_runtime::{AgentProgress, ProgressAggregator, ProgressRollup, SessionProgress};
+use toka_bus_core::{EventBus, KernelEvent};
+use toka_llm_gateway::{LlmGateway, LlmRequest};
+use toka_types::{AgentConfig, EntityId, ReportingConfig, ReportingFrequency};
+
+use crate::notifier::render_template;
+
+/// Number of milestones progress is divided into for `on-milestone` reports
+pub const MILESTONES: u8 = 4;
+
+/// Maximum number of characters of a report sent to the LLM for summarization
+const MAX_SUMMARY_INPUT: usize = 8000;
+
+/// Output format of a report.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    /// Markdown rendered from the body template
+    #[default]
+    Markdown,
+    /// JSON object with every template variable
+    Json,
+}
+
+/// What a report covers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "scope", rename_all = "snake_case")]
+pub enum ReportScope {
+    /// A single agent
+    Agent {
+        /// Agent entity ID
+        agent_id: EntityId,
+        /// Agent name
+        agent_name: String,
+        /// Workstream the agent belongs to
+        workstream: String,
+    },
+    /// All agents of a workstream
+    Workstream {
+        /// Workstream name
+        workstream: String,
+    },
+}
+
+impl ReportScope {
+    /// Workstream the report belongs to.
+    pub fn workstream(&self) -> &str {
+        match self {
+            ReportScope::Agent { workstream, .. } | ReportScope::Workstream { workstream } => workstream,
+        }
+    }
+
+    /// Name of the agent or workstream covered.
+    pub fn name(&self) -> &str {
+        match self {
+            ReportScope::Agent { agent_name, .. } => agent_name,
+            ReportScope::Workstream { workstream } => workstream,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            ReportScope::Agent { .. } => "agent",
+            ReportScope::Workstream { .. } => "workstream",
+        }
+    }
+}
+
+/// Title and body templates for reports.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportTemplate {
+    /// Report title
+    pub title: String,
+    /// Markdown body
+    pub body: String,
+}
+
+impl Default for ReportTemplate {
+    fn default() -> Self {
+        Self {
+            title: "{{frequency}} progress report: {{name}}".to_string(),
+            body: "# {{title}}\n\n\
+                   _{{scope}} `{{name}}` in workstream `{{workstream}}`, generated {{generated_at}}_\n\n\
+                   - Progress: {{progress}}%\n\
+                   - Agents finished: {{agents_finished}}/{{agents}}\n\
+                   - Tasks: {{tasks_completed}} completed, {{tasks_failed}} failed of {{tasks_attempted}} attempted\n\
+                   - LLM tokens: {{llm_tokens}}\n\
+                   - ETA: {{eta}}\n\n\
+                   ## Tracked metrics\n\n\
+                   {{metrics}}\n"
+                .to_string(),
+        }
+    }
+}
+
+/// Reporter configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReporterConfig {
+    /// Output format
+    #[serde(default)]
+    pub format: ReportFormat,
+    /// Title and body templates
+    #[serde(default)]
+    pub template: ReportTemplate,
+    /// Ask the LLM gateway for a short summary of each report
+    #[serde(default)]
+    pub llm_summary: bool,
+}
+
+/// A generated progress report.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProgressReport {
+    /// Report identifier
+    pub id: Uuid,
+    /// What the report covers
+    pub scope: ReportScope,
+    /// Schedule that produced the report
+    pub frequency: ReportingFrequency,
+    /// Format of `content`
+    pub format: ReportFormat,
+    /// Rendered title
+    pub title: String,
+    /// Rendered report
+    pub content: String,
+    /// LLM-written summary, if enabled and successful
+    pub summary: Option<String>,
+    /// Progress figures the report was rendered from
+    pub rollup: ProgressRollup,
+    /// When the report was generated
+    pub generated_at: DateTime<Utc>,
+}
+
+/// A destination for generated reports.
+#[async_trait]
+pub trait ReportSink: Send + Sync {
+    /// Short sink description used in logs.
+    fn name(&self) -> &str;
+
+    /// Deliver a report.
+    async fn deliver(&self, report: &ProgressReport) -> Result<()>;
+}
+
+/// Delivers reports as JSON `POST` requests.
+pub struct WebhookReportSink {
+    client: reqwest::Client,
+    url: reqwest::Url,
+}
+
+impl WebhookReportSink {
+    /// Create a webhook sink for `url`.
+    pub fn new(url: &str) -> Result<Self> {
+        let url = reqwest::Url::parse(url).with_context(|| format!("invalid webhook URL {}", url))?;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        Ok(Self { client, url })
+    }
+}
+
+#[async_trait]
+impl ReportSink for WebhookReportSink {
+    fn name(&self) -> &str {
+        self.url.as_str()
+    }
+
+    async fn deliver(&self, report: &ProgressReport) -> Result<()> {
+        self.client
+            .post(self.url.clone())
+            .json(report)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// When a schedule last produced a report.
+#[derive(Debug, Clone, Default)]
+struct ScheduleState {
+    last_report: Option<DateTime<Utc>>,
+    last_milestone: u8,
+}
+
+impl ScheduleState {
+    fn is_due(&self, frequency: &ReportingFrequency, progress: f64, now: DateTime<Utc>) -> bool {
+        let period = match frequency {
+            ReportingFrequency::Daily => chrono::Duration::days(1),
+            ReportingFrequency::Weekly => chrono::Duration::weeks(1),
+            ReportingFrequency::OnMilestone => return milestone(progress) > self.last_milestone,
+        };
+        self.last_report.is_none_or(|last| now.signed_duration_since(last) >= period)
+    }
+
+    fn mark(&mut self, progress: f64, now: DateTime<Utc>) {
+        self.last_report = Some(now);
+        self.last_milestone = self.last_milestone.max(milestone(progress));
+    }
+}
+
+/// Number of milestones reached at `progress`.
+fn milestone(progress: f64) -> u8 {
+    (progress.clamp(0.0, 1.0) * MILESTONES as f64).floor() as u8
+}
+
+/// Rank of a frequency; lower reports more often.
+fn frequency_rank(frequency: &ReportingFrequency) -> u8 {
+    match frequency {
+        ReportingFrequency::Daily => 0,
+        ReportingFrequency::Weekly => 1,
+        ReportingFrequency::OnMilestone => 2,
+    }
+}
+
+struct RegisteredAgent {
+    workstream: String,
+    config: ReportingConfig,
+    sinks: Vec<Arc<dyn ReportSink>>,
+    latest: Option<AgentProgress>,
+    schedule: ScheduleState,
+}
+
+#[derive(Default)]
+struct ReporterState {
+    agents: HashMap<String, RegisteredAgent>,
+    workstreams: HashMap<String, ScheduleState>,
+}
+
+/// Generates and publishes progress reports for registered agents.
+pub struct Reporter {
+    config: ReporterConfig,
+    state: Mutex<ReporterState>,
+    sinks: Vec<Arc<dyn ReportSink>>,
+    bus: Option<Arc<dyn EventBus>>,
+    llm_gateway: Option<Arc<LlmGateway>>,
+}
+
+impl Reporter {
+    /// Create a reporter without agents or sinks.
+    pub fn new(config: ReporterConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(ReporterState::default()),
+            sinks: Vec::new(),
+            bus: None,
+            llm_gateway: None,
+        }
+    }
+
+    /// Publish [`KernelEvent::ReportGenerated`] events on `bus`.
+    pub fn with_event_bus(mut self, bus: Arc<dyn EventBus>) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    /// Summarize reports with `gateway` when `llm_summary` is enabled.
+    pub fn with_llm_gateway(mut self, gateway: Arc<LlmGateway>) -> Self {
+        self.llm_gateway = Some(gateway);
+        self
+    }
+
+    /// Deliver every report to `sink`, regardless of the agents' channels.
+    pub fn with_sink(mut self, sink: Arc<dyn ReportSink>) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    /// Register an agent's reporting configuration.
+    ///
+    /// Webhook channels are validated here so a bad URL fails at startup
+    /// rather than at the first report.
+    pub async fn register_agent(&self, agent: &AgentConfig) -> Result<()> {
+        let mut sinks: Vec<Arc<dyn ReportSink>> = Vec::new();
+        for channel in &agent.reporting.channels {
+            if channel.starts_with("http://") || channel.starts_with("https://") {
+                let sink = WebhookReportSink::new(channel)
+                    .with_context(|| format!("invalid reporting channel for agent {}", agent.metadata.name))?;
+                sinks.push(Arc::new(sink));
+            }
+        }
+
+        let mut state = self.state.lock().await;
+        state.agents.insert(
+            agent.metadata.name.clone(),
+            RegisteredAgent {
+                workstream: agent.metadata.workstream.clone(),
+                config: agent.reporting.clone(),
+                sinks,
+                latest: None,
+                schedule: ScheduleState::default(),
+            },
+        );
+        state.workstreams.entry(agent.metadata.workstream.clone()).or_default();
+        Ok(())
+    }
+
+    /// Number of registered agents.
+    pub async fn agent_count(&self) -> usize {
+        self.state.lock().await.agents.len()
+    }
+
+    /// Record the latest progress of an agent.
+    ///
+    /// Reports from agents that were never registered are ignored.
+    pub async fn observe(&self, report: &AgentProgress) {
+        let mut state = self.state.lock().await;
+        match state.agents.get_mut(&report.agent_name) {
+            Some(agent) => agent.latest = Some(report.clone()),
+            None => debug!("Ignoring progress from unregistered agent {}", report.agent_name),
+        }
+    }
+
+    /// Generate and publish every report that is due at `now`.
+    ///
+    /// Returns the published reports. Delivery failures are logged and do
+    /// not stop delivery to the remaining sinks.
+    pub async fn run_due(&self, session: &SessionProgress, now: DateTime<Utc>) -> Vec<ProgressReport> {
+        let mut due = Vec::new();
+        {
+            let mut state = self.state.lock().await;
+
+            for agent in state.agents.values_mut() {
+                let Some(latest) = &agent.latest else {
+                    continue;
+                };
+                if !agent.schedule.is_due(&agent.config.frequency, latest.progress, now) {
+                    continue;
+                }
+                agent.schedule.mark(latest.progress, now);
+
+                let mut single = ProgressAggregator::new();
+                single.record(latest.clone());
+                let rollup = single.session().overall;
+                let scope = ReportScope::Agent {
+                    agent_id: latest.agent_id,
+                    agent_name: latest.agent_name.clone(),
+                    workstream: agent.workstream.clone(),
+                };
+                due.push((scope, agent.config.clone(), rollup, Some(latest.clone()), agent.sinks.clone()));
+            }
+
+            let ReporterState { agents, workstreams } = &mut *state;
+            for (name, schedule) in workstreams.iter_mut() {
+                let Some(progress) = session.workstreams.get(name) else {
+                    continue;
+                };
+                let members: Vec<&RegisteredAgent> = agents.values().filter(|a| a.workstream == *name).collect();
+                let Some(config) = workstream_config(&members) else {
+                    continue;
+                };
+                if !schedule.is_due(&config.frequency, progress.rollup.progress, now) {
+                    continue;
+                }
+                schedule.mark(progress.rollup.progress, now);
+
+                let sinks = members.iter().flat_map(|a| a.sinks.iter().cloned()).collect();
+                let scope = ReportScope::Workstream { workstream: name.clone() };
+                due.push((scope, config, progress.rollup.clone(), None, sinks));
+            }
+        }
+
+        let mut published = Vec::new();
+        for (scope, config, rollup, latest, sinks) in due {
+            let mut report = self.render(scope, &config, rollup, latest.as_ref(), now);
+            if self.config.llm_summary {
+                report.summary = self.summarize(&report).await;
+            }
+            self.publish(&report, &sinks).await;
+            published.push(report);
+        }
+        published
+    }
+
+    /// Render a report without publishing it.
+    pub fn render(
+        &self,
+        scope: ReportScope,
+        config: &ReportingConfig,
+        rollup: ProgressRollup,
+        latest: Option<&AgentProgress>,
+        now: DateTime<Utc>,
+    ) -> ProgressReport {
+        let mut vars = report_variables(&scope, config, &rollup, latest, now);
+        let title = render_template(&self.config.template.title, &vars);
+        vars.insert("title", title.clone());
+
+        let content = match self.config.format {
+            ReportFormat::Markdown => render_template(&self.config.template.body, &vars),
+            ReportFormat::Json => {
+                let mut object: serde_json::Map<String, serde_json::Value> = vars
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), serde_json::Value::String(value.clone())))
+                    .collect();
+                object.insert("metrics".to_string(), serde_json::json!(config.metrics));
+                serde_json::Value::Object(object).to_string()
+            }
+        };
+
+        ProgressReport {
+            id: Uuid::new_v4(),
+            scope,
+            frequency: config.frequency.clone(),
+            format: self.config.format,
+            title,
+            content,
+            summary: None,
+            rollup,
+            generated_at: now,
+        }
+    }
+
+    /// Ask the LLM gateway for a short summary of `report`.
+    async fn summarize(&self, report: &ProgressReport) -> Option<String> {
+        let gateway = self.llm_gateway.as_ref()?;
+        let content: String = report.content.chars().take(MAX_SUMMARY_INPUT).collect();
+        let prompt = format!(
+            "Summarize the following progress report in two or three sentences for a project lead. \
+             Mention blockers and failed tasks if there are any.\n\n{}",
+            content
+        );
+
+        let result = async {
+            let request = LlmRequest::new(prompt)?;
+            let response = gateway.complete(request).await?;
+            Ok::<_, anyhow::Error>(response.content().trim().to_string())
+        }
+        .await;
+        match result {
+            Ok(summary) => Some(summary),
+            Err(e) => {
+                warn!("LLM summary for report {} failed: {}", report.title, e);
+                None
+            }
+        }
+    }
+
+    /// Publish `report` on the event bus and deliver it to every sink.
+    async fn publish(&self, report: &ProgressReport, channel_sinks: &[Arc<dyn ReportSink>]) {
+        if let Some(bus) = &self.bus {
+            let agent = match &report.scope {
+                ReportScope::Agent { agent_id, .. } => Some(*agent_id),
+                ReportScope::Workstream { .. } => None,
+            };
+            let content = match &report.summary {
+                Some(summary) => format!("{}\n\n{}", summary, report.content),
+                None => report.content.clone(),
+            };
+            let event = KernelEvent::ReportGenerated {
+                report_id: report.id.to_string(),
+                agent,
+                workstream: report.scope.workstream().to_string(),
+                content,
+                timestamp: report.generated_at,
+            };
+            if let Err(e) = bus.publish(&event) {
+                warn!("Failed to publish report {}: {}", report.title, e);
+            }
+        }
+
+        for sink in self.sinks.iter().chain(channel_sinks) {
+            if let Err(e) = sink.deliver(report).await {
+                warn!("Report {} via {} failed: {}", report.title, sink.name(), e);
+            }
+        }
+    }
+
+    /// Generate due reports on every progress update and every `interval`.
+    ///
+    /// The task ends when the progress channel is closed.
+    pub fn spawn(
+        self: Arc<Self>,
+        mut progress: watch::Receiver<SessionProgress>,
+        interval: Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    changed = progress.changed() => {
+                        if changed.is_err() {
+                            break;
+                        }
+                    }
+                    _ = ticker.tick() => {}
+                }
+                let session = progress.borrow().clone();
+                self.run_due(&session, Utc::now()).await;
+            }
+        })
+    }
+}
+
+/// Reporting configuration of a workstream: the most frequent schedule of its
+/// agents, with the union of their tracked metrics.
+fn workstream_config(members: &[&RegisteredAgent]) -> Option<ReportingConfig> {
+    let frequency = members
+        .iter()
+        .map(|agent| &agent.config.frequency)
+        .min_by_key(|frequency| frequency_rank(frequency))?
+        .clone();
+    let mut metrics = HashMap::new();
+    let mut channels = Vec::new();
+    for agent in members {
+        metrics.extend(agent.config.metrics.iter().map(|(k, v)| (k.clone(), v.clone())));
+        channels.extend(agent.config.channels.iter().cloned());
+    }
+    channels.sort();
+    channels.dedup();
+    Some(ReportingConfig { frequency, channels, metrics })
+}
+
+/// Template variables describing a report.
+fn report_variables(
+    scope: &ReportScope,
+    config: &ReportingConfig,
+    rollup: &ProgressRollup,
+    latest: Option<&AgentProgress>,
+    now: DateTime<Utc>,
+) -> HashMap<&'static str, String> {
+    let mut vars = HashMap::new();
+    vars.insert("scope", scope.kind().to_string());
+    vars.insert("name", scope.name().to_string());
+    vars.insert("workstream", scope.workstream().to_string());
+    vars.insert(
+        "frequency",
+        match config.frequency {
+            ReportingFrequency::Daily => "Daily",
+            ReportingFrequency::Weekly => "Weekly",
+            ReportingFrequency::OnMilestone => "Milestone",
+        }
+        .to_string(),
+    );
+    vars.insert("progress", format!("{:.0}", rollup.progress * 100.0));
+    vars.insert("agents", rollup.agents.to_string());
+    vars.insert("agents_finished", rollup.agents_finished.to_string());
+    vars.insert("tasks_attempted", rollup.tasks_attempted.to_string());
+    vars.insert("tasks_completed", rollup.tasks_completed.to_string());
+    vars.insert("tasks_failed", rollup.tasks_failed.to_string());
+    vars.insert("llm_tokens", rollup.llm_tokens_consumed.to_string());
+    vars.insert(
+        "eta",
+        rollup
+            .eta
+            .map(|eta| format!("{}m", eta.as_secs() / 60))
+            .unwrap_or_else(|| "unknown".to_string()),
+    );
+    vars.insert("generated_at", now.to_rfc3339());
+
+    let mut metrics: Vec<String> = config
+        .metrics
+        .iter()
+        .map(|(name, target)| format!("- {}: {}", name, target))
+        .collect();
+    metrics.sort();
+    vars.insert(
+        "metrics",
+        if metrics.is_empty() { "_none_".to_string() } else { metrics.join("\n") },
+    );
+
+    if let Some(latest) = latest {
+        vars.insert("agent", latest.agent_id.0.to_string());
+        vars.insert("state", format!("{:?}", latest.state));
+        vars.insert("message", latest.message.clone().unwrap_or_default());
+    }
+    vars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toka_agent_runtime::{AgentExecutionState, AgentMetrics};
+    use toka_bus_core::InMemoryBus;
+    use toka_types::{
+        AgentCapabilities, AgentDependencies, AgentMetadata, AgentPriority, AgentSpecConfig,
+        AgentTasks, ResourceLimits, SecurityConfig,
+    };
+
+    #[derive(Default)]
+    struct RecordingSink {
+        delivered: std::sync::Mutex<Vec<ProgressReport>>,
+    }
+
+    #[async_trait]
+    impl ReportSink for RecordingSink {
+        fn name(&self) -> &str {
+            "recording"
+        }
+
+        async fn deliver(&self, report: &ProgressReport) -> Result<()> {
+            self.delivered.lock().unwrap().push(report.clone());
+            Ok(())
+        }
+    }
+
+    fn agent_config(name: &str, frequency: ReportingFrequency) -> AgentConfig {
+        AgentConfig {
+            metadata: AgentMetadata {
+                name: name.to_string(),
+                version: "v1.0".to_string(),
+                created: "2024-01-01".to_string(),
+                workstream: "storage".to_string(),
+                branch: "main".to_string(),
+            },
+            spec: AgentSpecConfig {
+                name: name.to_string(),
+                domain: "test".to_string(),
+                priority: AgentPriority::Medium,
+            },
+            capabilities: AgentCapabilities {
+                primary: vec!["test".to_string()],
+                secondary: vec![],
+            },
+            objectives: vec![],
+            tasks: AgentTasks { default: vec![] },
+            dependencies: AgentDependencies {
+                required: HashMap::new(),
+                optional: HashMap::new(),
+            },
+            reporting: ReportingConfig {
+                frequency,
+                channels: vec!["kernel-events".to_string()],
+                metrics: HashMap::from([("coverage".to_string(), "80%".to_string())]),
+            },
+            security: SecurityConfig {
+                sandbox: true,
+                capabilities_required: vec![],
+                resource_limits: ResourceLimits {
+                    max_memory: "100MB".to_string(),
+                    max_cpu: "50%".to_string(),
+                    timeout: "1h".to_string(),
+                },
+            },
+            placement: Default::default(),
+        }
+    }
+
+    fn progress(name: &str, progress: f64) -> AgentProgress {
+        AgentProgress {
+            agent_id: EntityId(7),
+            agent_name: name.to_string(),
+            workstream: "storage".to_string(),
+            progress,
+            message: Some("indexing".to_string()),
+            state: AgentExecutionState::Ready,
+            timestamp: Utc::now(),
+            metrics: AgentMetrics::default(),
+            tool_invocations: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_daily_schedule() {
+        let sink = Arc::new(RecordingSink::default());
+        let reporter = Reporter::new(ReporterConfig::default()).with_sink(sink.clone());
+        reporter.register_agent(&agent_config("indexer", ReportingFrequency::Daily)).await.unwrap();
+
+        let report = progress("indexer", 0.3);
+        reporter.observe(&report).await;
+        let mut aggregator = ProgressAggregator::new();
+        aggregator.record(report);
+        let session = aggregator.session();
+
+        // First run reports on the agent and its workstream
+        let now = Utc::now();
+        let reports = reporter.run_due(&session, now).await;
+        assert_eq!(reports.len(), 2);
+        let agent_report = reports.iter().find(|r| matches!(r.scope, ReportScope::Agent { .. })).unwrap();
+        assert_eq!(agent_report.title, "Daily progress report: indexer");
+        assert!(agent_report.content.contains("Progress: 30%"));
+        assert!(agent_report.content.contains("- coverage: 80%"));
+
+        // Nothing is due again until a day has passed
+        assert!(reporter.run_due(&session, now + chrono::Duration::hours(23)).await.is_empty());
+        assert_eq!(reporter.run_due(&session, now + chrono::Duration::days(1)).await.len(), 2);
+        assert_eq!(sink.delivered.lock().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_milestone_reports_published_as_events() {
+        let bus = Arc::new(InMemoryBus::new(16));
+        let mut events = bus.subscribe();
+        let reporter = Reporter::new(ReporterConfig {
+            format: ReportFormat::Json,
+            ..Default::default()
+        })
+        .with_event_bus(bus.clone());
+        reporter.register_agent(&agent_config("indexer", ReportingFrequency::OnMilestone)).await.unwrap();
+
+        let session = SessionProgress {
+            overall: ProgressAggregator::new().session().overall,
+            workstreams: HashMap::new(),
+        };
+        let now = Utc::now();
+
+        // Below the first milestone nothing is reported
+        reporter.observe(&progress("indexer", 0.1)).await;
+        assert!(reporter.run_due(&session, now).await.is_empty());
+
+        reporter.observe(&progress("indexer", 0.5)).await;
+        let reports = reporter.run_due(&session, now).await;
+        assert_eq!(reports.len(), 1);
+        let json: serde_json::Value = serde_json::from_str(&reports[0].content).unwrap();
+        assert_eq!(json["progress"], "50");
+        assert_eq!(json["metrics"]["coverage"], "80%");
+
+        // The same milestone is not reported twice
+        reporter.observe(&progress("indexer", 0.6)).await;
+        assert!(reporter.run_due(&session, now).await.is_empty());
+
+        match events.recv().await.unwrap() {
+            KernelEvent::ReportGenerated { agent, workstream, .. } => {
+                assert_eq!(agent, Some(EntityId(7)));
+                assert_eq!(workstream, "storage");
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! Signed agent configuration bundles.
+//!
+//! Agent YAML files decide which capabilities agents receive, so a tampered
+//! file is as dangerous as a tampered binary. Configs can be signed with an
+//! Ed25519 key; the signature is stored *detached* next to the file
+//! (`agent.yaml` → `agent.yaml.sig`, base64-encoded) so the YAML itself stays
+//! untouched and human-readable.
+//!
+//! [`AgentConfigLoader`](crate::AgentConfigLoader) verifies signatures with a
+//! [`ConfigVerifier`] built from the trusted public keys in
+//! [`ConfigSigningConfig`]. Under [`SignaturePolicy::Required`] (production
+//! mode) unsigned configs are refused as well as badly signed ones.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Extension appended to a config path to locate its detached signature.
+pub const SIGNATURE_EXTENSION: &str = "sig";
+
+/// How strictly configuration signatures are enforced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignaturePolicy {
+    /// Signatures are ignored
+    #[default]
+    Disabled,
+    /// Signatures are checked when present; unsigned configs are accepted
+    VerifyIfPresent,
+    /// Every config must carry a valid signature (production mode)
+    Required,
+}
+
+/// Signature settings as they appear in orchestration configuration.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigSigningConfig {
+    /// Enforcement policy
+    #[serde(default)]
+    pub policy: SignaturePolicy,
+    /// Trusted Ed25519 public keys, base64-encoded
+    #[serde(default)]
+    pub trusted_keys: Vec<String>,
+}
+
+/// Errors raised while verifying configuration signatures.
+#[derive(Debug, Error)]
+pub enum ConfigSignatureError {
+    /// A signature is required but the config has none
+    #[error("configuration {0} is not signed")]
+    MissingSignature(PathBuf),
+    /// The signature does not match any trusted key
+    #[error("configuration {0} has an invalid signature")]
+    InvalidSignature(PathBuf),
+    /// The signature file could not be decoded
+    #[error("malformed signature for {path}: {reason}")]
+    MalformedSignature {
+        /// Config the signature belongs to
+        path: PathBuf,
+        /// What was wrong with it
+        reason: String,
+    },
+    /// A trusted key could not be decoded
+    #[error("invalid trusted key: {0}")]
+    InvalidKey(String),
+    /// Signatures are required but no trusted keys are configured
+    #[error("signatures are required but no trusted keys are configured")]
+    NoTrustedKeys,
+}
+
+/// Outcome of verifying one configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureStatus {
+    /// Signed by the trusted key at this index
+    Verified {
+        /// Index into the trusted keys
+        key_index: usize,
+    },
+    /// No signature present (only under [`SignaturePolicy::VerifyIfPresent`])
+    Unsigned,
+    /// Verification is disabled
+    Skipped,
+}
+
+/// Verifies detached configuration signatures against trusted keys.
+#[derive(Debug, Clone)]
+pub struct ConfigVerifier {
+    policy: SignaturePolicy,
+    trusted_keys: Vec<VerifyingKey>,
+}
+
+impl ConfigVerifier {
+    /// Build a verifier from configuration.
+    pub fn from_config(config: &ConfigSigningConfig) -> Result<Self, ConfigSignatureError> {
+        let trusted_keys = config
+            .trusted_keys
+            .iter()
+            .map(|key| decode_verifying_key(key))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(config.policy, trusted_keys)
+    }
+
+    /// Build a verifier from already-decoded keys.
+    pub fn new(
+        policy: SignaturePolicy,
+        trusted_keys: Vec<VerifyingKey>,
+    ) -> Result<Self, ConfigSignatureError> {
+        if policy == SignaturePolicy::Required && trusted_keys.is_empty() {
+            return Err(ConfigSignatureError::NoTrustedKeys);
+        }
+        Ok(Self {
+            policy,
+            trusted_keys,
+        })
+    }
+
+    /// The enforcement policy.
+    pub fn policy(&self) -> SignaturePolicy {
+        self.policy
+    }
+
+    /// Verify `contents` read from `path` against its detached signature.
+    pub fn verify(&self, path: &Path, contents: &[u8]) -> Result<SignatureStatus, ConfigSignatureError> {
+        if self.policy == SignaturePolicy::Disabled {
+            return Ok(SignatureStatus::Skipped);
+        }
+
+        let signature_path = signature_path(path);
+        let encoded = match fs::read_to_string(&signature_path) {
+            Ok(encoded) => encoded,
+            Err(_) if self.policy == SignaturePolicy::VerifyIfPresent => {
+                return Ok(SignatureStatus::Unsigned)
+            }
+            Err(_) => return Err(ConfigSignatureError::MissingSignature(path.to_path_buf())),
+        };
+
+        let malformed = |reason: String| ConfigSignatureError::MalformedSignature {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let bytes = BASE64
+            .decode(encoded.trim())
+            .map_err(|e| malformed(e.to_string()))?;
+        let bytes: [u8; 64] = bytes
+            .try_into()
+            .map_err(|_| malformed("expected 64 signature bytes".to_string()))?;
+        let signature = Signature::from_bytes(&bytes);
+
+        self.trusted_keys
+            .iter()
+            .position(|key| key.verify_strict(contents, &signature).is_ok())
+            .map(|key_index| SignatureStatus::Verified { key_index })
+            .ok_or_else(|| ConfigSignatureError::InvalidSignature(path.to_path_buf()))
+    }
+}
+
+/// Path of the detached signature for `config_path`.
+pub fn signature_path(config_path: &Path) -> PathBuf {
+    let mut path = config_path.as_os_str().to_owned();
+    path.push(".");
+    path.push(SIGNATURE_EXTENSION);
+    PathBuf::from(path)
+}
+
+/// Sign config `contents`, returning the base64-encoded detached signature.
+pub fn sign_config(contents: &[u8], key: &SigningKey) -> String {
+    BASE64.encode(key.sign(contents).to_bytes())
+}
+
+/// Sign the config file at `path` and write its detached signature next to it.
+pub fn write_signature(path: &Path, key: &SigningKey) -> Result<PathBuf> {
+    let contents = fs::read(path).with_context(|| format!("Failed to read file: {}", path.display()))?;
+    let signature_path = signature_path(path);
+    fs::write(&signature_path, sign_config(&contents, key))
+        .with_context(|| format!("Failed to write signature: {}", signature_path.display()))?;
+    Ok(signature_path)
+}
+
+/// Base64-encode a public key for use in [`ConfigSigningConfig::trusted_keys`].
+pub fn encode_verifying_key(key: &VerifyingKey) -> String {
+    BASE64.encode(key.to_bytes())
+}
+
+fn decode_verifying_key(encoded: &str) -> Result<VerifyingKey, ConfigSignatureError> {
+    let bytes = BASE64
+        .decode(encoded.trim())
+        .map_err(|e| ConfigSignatureError::InvalidKey(e.to_string()))?;
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| ConfigSignatureError::InvalidKey("expected 32 key bytes".to_string()))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| ConfigSignatureError::InvalidKey(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn verifier(policy: SignaturePolicy, key: &SigningKey) -> ConfigVerifier {
+        ConfigVerifier::from_config(&ConfigSigningConfig {
+            policy,
+            trusted_keys: vec![encode_verifying_key(&key.verifying_key())],
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_signed_config_verifies() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("agent.yaml");
+        fs::write(&path, "metadata:\n  name: agent\n").unwrap();
+
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        write_signature(&path, &key).unwrap();
+
+        let contents = fs::read(&path).unwrap();
+        let status = verifier(SignaturePolicy::Required, &key).verify(&path, &contents).unwrap();
+        assert_eq!(status, SignatureStatus::Verified { key_index: 0 });
+
+        // Any change to the YAML invalidates the signature
+        let tampered = b"metadata:\n  name: evil\n";
+        assert!(matches!(
+            verifier(SignaturePolicy::Required, &key).verify(&path, tampered),
+            Err(ConfigSignatureError::InvalidSignature(_))
+        ));
+
+        // So does a signature from an untrusted key
+        let other = SigningKey::from_bytes(&[9u8; 32]);
+        assert!(verifier(SignaturePolicy::Required, &other).verify(&path, &contents).is_err());
+    }
+
+    #[test]
+    fn test_unsigned_config_policy() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("agent.yaml");
+        fs::write(&path, "metadata: {}\n").unwrap();
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+
+        assert!(matches!(
+            verifier(SignaturePolicy::Required, &key).verify(&path, b"metadata: {}\n"),
+            Err(ConfigSignatureError::MissingSignature(_))
+        ));
+        assert_eq!(
+            verifier(SignaturePolicy::VerifyIfPresent, &key).verify(&path, b"metadata: {}\n").unwrap(),
+            SignatureStatus::Unsigned
+        );
+        assert!(matches!(
+            ConfigVerifier::new(SignaturePolicy::Required, Vec::new()),
+            Err(ConfigSignatureError::NoTrustedKeys)
+        ));
+    }
+}
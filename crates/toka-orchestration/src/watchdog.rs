@@ -0,0 +1,464 @@
+//! Built-in platform watchdog agent.
+//!
+//! The watchdog dogfoods the agent system for Toka's own operational health.
+//! [`watchdog_agent_config`] is the agent template to orchestrate alongside
+//! other agents, and [`PlatformWatchdog`] does the work behind its tasks: it
+//! subscribes to `SystemError`, `ResourceError` and `TaskTimeout` events,
+//! correlates the ones concerning the same component, agent or resource
+//! within a time window, and files an [`Incident`] once enough of them pile
+//! up (or immediately for critical system errors).
+//!
+//! Incidents are published back on the bus as structured
+//! `ObservationEmitted` events from the watchdog agent, with the incident as
+//! JSON in the observation data, and delivered as [`Notification`]s to the
+//! configured channels, typically webhooks.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use toka_bus_core::{ErrorSeverity, EventBus, KernelEvent};
+use toka_types::{
+    AgentCapabilities, AgentConfig, AgentDependencies, AgentMetadata, AgentObjective, AgentPriority,
+    AgentSpecConfig, AgentTasks, EntityId, PlacementConstraints, ReportingConfig, ReportingFrequency,
+    ResourceLimits, SecurityConfig, TaskConfig, TaskPriority,
+};
+
+use crate::notifier::{event_variables, ChannelConfig, Notification, NotificationSink};
+
+/// Name of the watchdog agent template.
+pub const WATCHDOG_AGENT_NAME: &str = "platform-watchdog";
+
+/// Most events kept as evidence in one incident.
+pub const MAX_INCIDENT_EVENTS: usize = 20;
+
+/// Event kinds the watchdog subscribes to.
+pub const WATCHED_EVENT_KINDS: [&str; 3] = ["error.system", "error.resource", "task.timeout"];
+
+/// Agent template of the platform watchdog.
+pub fn watchdog_agent_config() -> AgentConfig {
+    let task = |description: &str, priority| TaskConfig {
+        description: description.to_string(),
+        priority,
+    };
+    AgentConfig {
+        metadata: AgentMetadata {
+            name: WATCHDOG_AGENT_NAME.to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            created: "2026-10-16".to_string(),
+            workstream: "platform-operations".to_string(),
+            branch: "main".to_string(),
+        },
+        spec: AgentSpecConfig {
+            name: "Platform Watchdog".to_string(),
+            domain: "operations".to_string(),
+            priority: AgentPriority::High,
+        },
+        capabilities: AgentCapabilities {
+            primary: vec![
+                "event-monitoring".to_string(),
+                "incident-correlation".to_string(),
+                "incident-reporting".to_string(),
+            ],
+            secondary: vec!["webhook-notification".to_string()],
+        },
+        objectives: vec![AgentObjective {
+            description: "Detect and report operational problems of the Toka platform".to_string(),
+            deliverable: "Structured incident observations for correlated failures".to_string(),
+            validation: "Every burst of related errors or timeouts yields exactly one incident".to_string(),
+        }],
+        tasks: AgentTasks {
+            default: vec![
+                task(
+                    "Watch SystemError, ResourceError and TaskTimeout events on the kernel bus",
+                    TaskPriority::High,
+                ),
+                task(
+                    "Correlate failures concerning the same component, agent or resource within the correlation window",
+                    TaskPriority::High,
+                ),
+                task(
+                    "File structured incident observations and notify operators through the configured webhooks",
+                    TaskPriority::Medium,
+                ),
+            ],
+        },
+        dependencies: AgentDependencies {
+            required: HashMap::new(),
+            optional: HashMap::new(),
+        },
+        reporting: ReportingConfig {
+            frequency: ReportingFrequency::OnMilestone,
+            channels: vec!["kernel-events".to_string()],
+            metrics: HashMap::from([(
+                "incidents_filed".to_string(),
+                "Incidents filed since the watchdog started".to_string(),
+            )]),
+        },
+        security: SecurityConfig {
+            sandbox: true,
+            capabilities_required: vec![
+                "event-subscribe".to_string(),
+                "observation-emit".to_string(),
+                "network-webhook".to_string(),
+            ],
+            resource_limits: ResourceLimits {
+                max_memory: "64MB".to_string(),
+                max_cpu: "10%".to_string(),
+                timeout: "24h".to_string(),
+            },
+        },
+        placement: PlacementConstraints::default(),
+    }
+}
+
+/// Watchdog settings, typically loaded from YAML alongside the notifier's.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchdogConfig {
+    /// Seconds within which related events are correlated
+    #[serde(default = "default_correlation_window")]
+    pub correlation_window_secs: u64,
+    /// Related events within the window that make an incident
+    #[serde(default = "default_incident_threshold")]
+    pub incident_threshold: usize,
+    /// Channels notified of every incident
+    #[serde(default)]
+    pub channels: Vec<ChannelConfig>,
+}
+
+fn default_correlation_window() -> u64 {
+    300
+}
+
+fn default_incident_threshold() -> usize {
+    3
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            correlation_window_secs: default_correlation_window(),
+            incident_threshold: default_incident_threshold(),
+            channels: Vec::new(),
+        }
+    }
+}
+
+impl WatchdogConfig {
+    /// Correlation window as a duration.
+    pub fn correlation_window(&self) -> Duration {
+        Duration::from_secs(self.correlation_window_secs)
+    }
+}
+
+/// Correlated failures filed by the watchdog.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Incident {
+    /// Unique incident identifier
+    pub id: Uuid,
+    /// What the events have in common, e.g. `component:store` or `agent:7`
+    pub correlation_key: String,
+    /// Highest severity among the events; timeouts and resource errors
+    /// count as errors
+    pub severity: ErrorSeverity,
+    /// One-line description
+    pub summary: String,
+    /// The correlated events, oldest first
+    pub events: Vec<KernelEvent>,
+    /// Time of the first correlated event
+    pub first_seen: DateTime<Utc>,
+    /// Time of the last correlated event
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Related events seen within the correlation window.
+#[derive(Debug, Default)]
+struct Bucket {
+    events: Vec<(DateTime<Utc>, KernelEvent)>,
+}
+
+/// Correlates failure events into incidents and reports them.
+pub struct PlatformWatchdog {
+    config: WatchdogConfig,
+    agent: EntityId,
+    sinks: Vec<Arc<dyn NotificationSink>>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    incidents_filed: std::sync::atomic::AtomicU64,
+}
+
+impl PlatformWatchdog {
+    /// Create a watchdog filing observations as `agent`, building the
+    /// configured notification channels.
+    pub fn new(config: WatchdogConfig, agent: EntityId) -> Result<Self> {
+        let sinks = config
+            .channels
+            .iter()
+            .map(ChannelConfig::build)
+            .collect::<Result<Vec<_>>>()
+            .context("invalid watchdog notification channel")?;
+        Ok(Self {
+            config,
+            agent,
+            sinks,
+            buckets: Mutex::new(HashMap::new()),
+            incidents_filed: std::sync::atomic::AtomicU64::new(0),
+        })
+    }
+
+    /// Also notify `sink` of every incident.
+    pub fn with_sink(mut self, sink: Arc<dyn NotificationSink>) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    /// Number of incidents filed so far.
+    pub fn incidents_filed(&self) -> u64 {
+        self.incidents_filed.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Correlate `event`, seen at `now`, returning an incident once one is
+    /// due.
+    ///
+    /// Events the watchdog does not watch are ignored. The correlated
+    /// events are cleared when an incident is filed, so a continuing
+    /// problem files a new incident every `incident_threshold` events.
+    pub fn observe(&self, event: &KernelEvent, now: DateTime<Utc>) -> Option<Incident> {
+        let key = correlation_key(event)?;
+        let window = chrono::Duration::seconds(self.config.correlation_window_secs.min(i64::MAX as u64) as i64);
+
+        let mut buckets = self.buckets.lock().expect("watchdog buckets poisoned");
+        buckets.retain(|_, bucket| {
+            bucket.events.retain(|(seen, _)| now.signed_duration_since(*seen) < window);
+            !bucket.events.is_empty()
+        });
+
+        let bucket = buckets.entry(key.clone()).or_default();
+        bucket.events.push((now, event.clone()));
+        let critical = matches!(
+            event,
+            KernelEvent::SystemError { severity: ErrorSeverity::Critical, .. }
+        );
+        if !critical && bucket.events.len() < self.config.incident_threshold.max(1) {
+            return None;
+        }
+
+        let events = std::mem::take(&mut bucket.events);
+        buckets.remove(&key);
+        Some(incident(key, events))
+    }
+
+    /// Correlate `event` and, if an incident is due, file it on `bus` and
+    /// notify the configured channels.
+    ///
+    /// Notification failures are logged and do not fail the call.
+    pub async fn handle(&self, bus: &dyn EventBus, event: &KernelEvent, now: DateTime<Utc>) -> Result<Option<Incident>> {
+        let Some(incident) = self.observe(event, now) else {
+            return Ok(None);
+        };
+        self.incidents_filed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        info!("Watchdog filed incident {}: {}", incident.id, incident.summary);
+
+        bus.publish(&KernelEvent::ObservationEmitted {
+            agent: self.agent,
+            data: serde_json::to_vec(&incident)?.into(),
+            timestamp: now,
+        })?;
+
+        let notification = Notification {
+            rule: WATCHDOG_AGENT_NAME.to_string(),
+            subject: format!("[{:?}] Incident on {}", incident.severity, incident.correlation_key),
+            body: incident_body(&incident),
+            event: event.clone(),
+            created_at: now,
+        };
+        for sink in &self.sinks {
+            if let Err(e) = sink.deliver(&notification).await {
+                warn!("Incident {} via {} failed: {}", incident.id, sink.name(), e);
+            }
+        }
+        Ok(Some(incident))
+    }
+
+    /// Subscribe to the watched events on `bus` and process them until the
+    /// bus is closed.
+    pub fn spawn(self: Arc<Self>, bus: Arc<dyn EventBus>) -> tokio::task::JoinHandle<()> {
+        let filter = WATCHED_EVENT_KINDS
+            .iter()
+            .fold(toka_bus_core::EventFilter::new(), |filter, kind| filter.kind(*kind));
+        let mut rx = bus.subscribe_filtered(filter);
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => {
+                        if let Err(e) = self.handle(bus.as_ref(), &event, Utc::now()).await {
+                            warn!("Watchdog failed to file incident: {}", e);
+                        }
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!("Watchdog lagged behind the event bus; {} events skipped", missed);
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+}
+
+/// What a watched event concerns, or `None` if it is not watched.
+fn correlation_key(event: &KernelEvent) -> Option<String> {
+    match event {
+        KernelEvent::SystemError { context, .. } => Some(format!("component:{}", context.component)),
+        KernelEvent::TaskTimeout { agent, .. } => Some(format!("agent:{}", agent.0)),
+        KernelEvent::ResourceError { agent: Some(agent), .. } => Some(format!("agent:{}", agent.0)),
+        KernelEvent::ResourceError { resource_type, .. } => Some(format!("resource:{:?}", resource_type)),
+        _ => None,
+    }
+}
+
+fn incident(correlation_key: String, events: Vec<(DateTime<Utc>, KernelEvent)>) -> Incident {
+    let severity = events
+        .iter()
+        .map(|(_, event)| match event {
+            KernelEvent::SystemError { severity, .. } => severity.clone(),
+            _ => ErrorSeverity::Error,
+        })
+        .max()
+        .unwrap_or(ErrorSeverity::Error);
+    let first_seen = events.first().map(|(seen, _)| *seen).unwrap_or_else(Utc::now);
+    let last_seen = events.last().map(|(seen, _)| *seen).unwrap_or(first_seen);
+    let latest = events
+        .last()
+        .map(|(_, event)| event_variables(event)["summary"].clone())
+        .unwrap_or_default();
+    let summary = format!("{} related event(s) on {}; latest: {}", events.len(), correlation_key, latest);
+
+    let skip = events.len().saturating_sub(MAX_INCIDENT_EVENTS);
+    Incident {
+        id: Uuid::new_v4(),
+        correlation_key,
+        severity,
+        summary,
+        events: events.into_iter().skip(skip).map(|(_, event)| event).collect(),
+        first_seen,
+        last_seen,
+    }
+}
+
+fn incident_body(incident: &Incident) -> String {
+    let mut body = format!(
+        "{}\nSeverity: {:?}\nFirst seen: {}\nLast seen: {}\nEvents:",
+        incident.summary,
+        incident.severity,
+        incident.first_seen.to_rfc3339(),
+        incident.last_seen.to_rfc3339()
+    );
+    for event in &incident.events {
+        body.push_str(&format!("\n- {}", event_variables(event)["summary"]));
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use toka_bus_core::{ErrorCategory, ErrorContext, InMemoryBus};
+
+    #[derive(Default)]
+    struct RecordingSink {
+        delivered: Mutex<Vec<Notification>>,
+    }
+
+    #[async_trait]
+    impl NotificationSink for RecordingSink {
+        fn name(&self) -> &str {
+            "recording"
+        }
+
+        async fn deliver(&self, notification: &Notification) -> Result<()> {
+            self.delivered.lock().unwrap().push(notification.clone());
+            Ok(())
+        }
+    }
+
+    fn timeout(agent: u128) -> KernelEvent {
+        KernelEvent::TaskTimeout {
+            task_id: "task-1".to_string(),
+            agent: EntityId(agent),
+            timeout_duration_ms: 1000,
+            correlation_id: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_correlated_failures_file_one_incident() {
+        let sink = Arc::new(RecordingSink::default());
+        let watchdog = PlatformWatchdog::new(WatchdogConfig::default(), EntityId(99))
+            .unwrap()
+            .with_sink(sink.clone());
+        let bus = InMemoryBus::default();
+        let mut rx = bus.subscribe();
+        let now = Utc::now();
+
+        // Two timeouts on agent 1 and one on agent 2 stay below the threshold
+        for agent in [1, 2, 1] {
+            assert!(watchdog.handle(&bus, &timeout(agent), now).await.unwrap().is_none());
+        }
+        // A resource error of agent 1 completes the burst
+        let exhausted = KernelEvent::ResourceError {
+            resource_type: toka_bus_core::ResourceType::Memory,
+            requested: 2048,
+            available: 1024,
+            agent: Some(EntityId(1)),
+            timestamp: now,
+        };
+        let incident = watchdog.handle(&bus, &exhausted, now).await.unwrap().unwrap();
+        assert_eq!(incident.correlation_key, "agent:1");
+        assert_eq!(incident.events.len(), 3);
+        assert_eq!(watchdog.incidents_filed(), 1);
+
+        match rx.recv().await.unwrap() {
+            KernelEvent::ObservationEmitted { agent, data, .. } => {
+                assert_eq!(agent, EntityId(99));
+                let filed: Incident = serde_json::from_slice(&data).unwrap();
+                assert_eq!(filed, incident);
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert_eq!(sink.delivered.lock().unwrap()[0].subject, "[Error] Incident on agent:1");
+
+        // Events outside the window are forgotten
+        let later = now + chrono::Duration::seconds(600);
+        assert!(watchdog.observe(&timeout(2), later).is_none());
+        assert!(watchdog.observe(&timeout(2), later).is_none());
+
+        // Critical system errors are filed immediately
+        let critical = KernelEvent::SystemError {
+            error_category: ErrorCategory::Storage,
+            error_code: "E_DISK".to_string(),
+            context: ErrorContext {
+                component: "store".to_string(),
+                metadata: HashMap::new(),
+            },
+            severity: ErrorSeverity::Critical,
+            timestamp: now,
+        };
+        let incident = watchdog.observe(&critical, later).unwrap();
+        assert_eq!(incident.severity, ErrorSeverity::Critical);
+        assert_eq!(incident.correlation_key, "component:store");
+    }
+
+    #[test]
+    fn test_template_names_the_watchdog() {
+        let config = watchdog_agent_config();
+        assert_eq!(config.metadata.name, WATCHDOG_AGENT_NAME);
+        assert_eq!(config.tasks.default.len(), 3);
+    }
+}
@@ -14,6 +14,9 @@ use dashmap::DashMap;
 use tokio::sync::{mpsc, RwLock};
 use tracing::{debug, info, warn};
 
+use toka_store_core::IntentId;
+
+use crate::intent::IntentRegistry;
 use crate::{AgentConfig, AgentState};
 
 /// Workstream coordinator for managing agent coordination within workstreams.
@@ -28,6 +31,12 @@ pub struct WorkstreamCoordinator {
     communication_channels: Arc<RwLock<HashMap<String, mpsc::UnboundedSender<WorkstreamMessage>>>>,
     /// Coordination events
     event_listeners: Arc<RwLock<Vec<mpsc::UnboundedSender<CoordinationEvent>>>>,
+    /// Intent hierarchy of the session, its workstreams and their agents
+    intents: Arc<IntentRegistry>,
+    /// Root intent of the session
+    session_intent: Arc<RwLock<Option<IntentId>>>,
+    /// Agent to intent mapping
+    agent_intents: Arc<DashMap<String, IntentId>>,
 }
 
 /// Workstream definition and state.
@@ -51,6 +60,8 @@ pub struct Workstream {
     pub deliverables: Vec<WorkstreamDeliverable>,
     /// Communication preferences
     pub communication: WorkstreamCommunication,
+    /// Intent events of the workstream are recorded under
+    pub intent: IntentId,
 }
 
 /// Workstream state tracking.
@@ -255,12 +266,19 @@ impl WorkstreamCoordinator {
             workstream_dependencies: Arc::new(RwLock::new(HashMap::new())),
             communication_channels: Arc::new(RwLock::new(HashMap::new())),
             event_listeners: Arc::new(RwLock::new(Vec::new())),
+            intents: Arc::new(IntentRegistry::new()),
+            session_intent: Arc::new(RwLock::new(None)),
+            agent_intents: Arc::new(DashMap::new()),
         }
     }
 
     /// Initialize workstreams from agent configurations.
+    ///
+    /// Each workstream gets an intent below the session intent, and each
+    /// agent an intent below its workstream's.
     pub async fn initialize_workstreams(&self, agents: &[AgentConfig]) -> Result<()> {
         info!("Initializing workstreams from {} agent configurations", agents.len());
+        let session_intent = self.session_intent().await?;
 
         let mut workstreams = self.workstreams.write().await;
         let mut workstream_dependencies = self.workstream_dependencies.write().await;
@@ -276,6 +294,18 @@ impl WorkstreamCoordinator {
 
         // Create workstream definitions
         for (workstream_name, agent_names) in workstream_agents {
+            let intent = self
+                .intents
+                .create_intent(format!("workstream {}", workstream_name), Some(session_intent))
+                .await?;
+            for agent_name in &agent_names {
+                let agent_intent = self
+                    .intents
+                    .create_intent(format!("agent {}", agent_name), Some(intent))
+                    .await?;
+                self.agent_intents.insert(agent_name.clone(), agent_intent);
+            }
+
             let workstream = Workstream {
                 name: workstream_name.clone(),
                 description: format!("Workstream for {}", workstream_name),
@@ -290,6 +320,7 @@ impl WorkstreamCoordinator {
                     channels: vec!["default".to_string()],
                     notifications: Vec::new(),
                 },
+                intent,
             };
 
             workstreams.insert(workstream_name.clone(), workstream);
@@ -319,12 +350,12 @@ impl WorkstreamCoordinator {
 
     /// Start a workstream.
     pub async fn start_workstream(&self, workstream_name: &str) -> Result<()> {
+        // Check dependencies before taking the write lock, which the check
+        // would otherwise wait on forever
+        let dependencies_satisfied = self.check_workstream_dependencies(workstream_name).await?;
         let mut workstreams = self.workstreams.write().await;
         
         if let Some(workstream) = workstreams.get_mut(workstream_name) {
-            // Check if dependencies are satisfied
-            let dependencies_satisfied = self.check_workstream_dependencies(workstream_name).await?;
-            
             if dependencies_satisfied {
                 let old_state = workstream.state.clone();
                 workstream.state = WorkstreamState::Active;
@@ -369,6 +400,11 @@ impl WorkstreamCoordinator {
                     new_state: WorkstreamState::Completed,
                 }).await;
 
+                // The workstream's agents have nothing left to record
+                if let Err(e) = self.intents.close_subtree(workstream.intent).await {
+                    warn!("Failed to close intents of workstream {}: {}", workstream_name, e);
+                }
+
                 // Notify dependent workstreams
                 self.notify_dependent_workstreams(workstream_name).await?;
 
@@ -462,6 +498,32 @@ impl WorkstreamCoordinator {
         self.agent_workstreams.get(agent_name).map(|w| w.value().clone())
     }
 
+    /// Intent registry holding the session's intent hierarchy.
+    pub fn intents(&self) -> Arc<IntentRegistry> {
+        self.intents.clone()
+    }
+
+    /// Root intent of the session, created on first use.
+    pub async fn session_intent(&self) -> Result<IntentId> {
+        let mut session_intent = self.session_intent.write().await;
+        if let Some(intent) = *session_intent {
+            return Ok(intent);
+        }
+        let intent = self.intents.create_intent("orchestration session", None).await?;
+        *session_intent = Some(intent);
+        Ok(intent)
+    }
+
+    /// Get the intent of a workstream.
+    pub async fn get_workstream_intent(&self, workstream_name: &str) -> Option<IntentId> {
+        self.workstreams.read().await.get(workstream_name).map(|w| w.intent)
+    }
+
+    /// Get the intent of an agent.
+    pub fn get_agent_intent(&self, agent_name: &str) -> Option<IntentId> {
+        self.agent_intents.get(agent_name).map(|i| *i.value())
+    }
+
     /// Check if workstream dependencies are satisfied.
     async fn check_workstream_dependencies(&self, workstream_name: &str) -> Result<bool> {
         let workstream_dependencies = self.workstream_dependencies.read().await;
@@ -601,6 +663,7 @@ mod tests {
                     timeout: "1h".to_string(),
                 },
             },
+            placement: Default::default(),
         }
     }
 
@@ -648,4 +711,31 @@ mod tests {
         assert_eq!(workstream.state, WorkstreamState::Completed);
         assert!(workstream.completed_at.is_some());
     }
+
+    #[tokio::test]
+    async fn test_workstream_intent_hierarchy() {
+        use crate::intent::IntentState;
+
+        let coordinator = WorkstreamCoordinator::new();
+        let agents = vec![
+            create_test_agent("agent1", "workstream1"),
+            create_test_agent("agent2", "workstream1"),
+            create_test_agent("agent3", "workstream2"),
+        ];
+        coordinator.initialize_workstreams(&agents).await.unwrap();
+
+        let intents = coordinator.intents();
+        let session = coordinator.session_intent().await.unwrap();
+        let workstream = coordinator.get_workstream_intent("workstream1").await.unwrap();
+        let agent = coordinator.get_agent_intent("agent1").unwrap();
+        assert_eq!(intents.ancestors(agent).await, vec![workstream, session]);
+        assert_eq!(intents.children(workstream).await.len(), 2);
+        assert_eq!(intents.subtree(session).await.unwrap().len(), 6);
+
+        coordinator.start_workstream("workstream1").await.unwrap();
+        coordinator.update_workstream_progress("workstream1", 1.0).await.unwrap();
+        assert_eq!(intents.get(agent).await.unwrap().state, IntentState::Closed);
+        assert_eq!(intents.get(workstream).await.unwrap().state, IntentState::Closed);
+        assert_eq!(intents.get(session).await.unwrap().state, IntentState::Open);
+    }
 } 
\ No newline at end of file
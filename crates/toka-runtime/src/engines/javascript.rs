@@ -0,0 +1,577 @@
+//! JavaScript engine with a Node.js subprocess or embedded QuickJS backend.
+//!
+//! Both backends run the script as the body of a function receiving
+//! `require`, `module`, `exports` and the request inputs as `inputs`, with
+//! the request's environment variables in `process.env`. Anything logged
+//! through `console` becomes the execution output; a value returned by the
+//! script, or resolved by a returned promise, is appended as JSON.
+//!
+//! - **Node** ([`JavaScriptBackend::Node`]): every execution starts a fresh
+//!   `node` process with a cleared environment under Node's permission
+//!   model, so the filesystem, child processes and workers are off limits
+//!   except for the directories in [`JavaScriptEngineConfig::fs_read`].
+//!   `require` only resolves the built-in modules listed in
+//!   [`JavaScriptEngineConfig::allowed_modules`]; packages and relative
+//!   paths are refused. Unless disabled, the process also runs under the
+//!   [`SandboxPolicy`] of the request's security level.
+//! - **QuickJS** ([`JavaScriptBackend::QuickJs`], `quickjs` feature): the
+//!   script runs in-process on an embedded interpreter without any host
+//!   APIs. `require` refuses every module.
+//!
+//! Output is collected while the script runs, so a script killed at its
+//! timeout or on cancellation still returns what it printed until then.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::Result;
+use tokio::process::Command;
+
+use super::process::{self, RunOptions};
+use crate::sandbox::{SandboxGuard, SandboxPolicy};
+use crate::{
+    Capability, CapabilitySet, CodeType, EngineMetadata, ExecutionContext, ExecutionEngine, ExecutionRequest,
+    ExecutionResult, RuntimeMetadata, RuntimeResourceUsage, StopReason, ToolKernel,
+};
+
+/// Runs the script read from stdin after a line of JSON inputs.
+///
+/// Takes the JSON list of allowed modules as its only argument.
+const NODE_BOOTSTRAP: &str = r#"
+const nodeRequire = require;
+for (const name of ['require', 'module', 'exports', '__filename', '__dirname']) delete globalThis[name];
+const allowed = new Set(JSON.parse(process.argv[1]));
+const toolRequire = (name) => {
+  const bare = name.startsWith('node:') ? name.slice(5) : name;
+  if (!allowed.has(bare)) throw new Error(`module '${name}' is not allowed`);
+  return nodeRequire(bare);
+};
+let data = '';
+process.stdin.setEncoding('utf8');
+process.stdin.on('data', (chunk) => { data += chunk; });
+process.stdin.on('end', () => {
+  const newline = data.indexOf('\n');
+  const inputs = JSON.parse(data.slice(0, newline));
+  const tool = { exports: {} };
+  const result = new Function('require', 'module', 'exports', 'inputs', data.slice(newline + 1))(
+    toolRequire, tool, tool.exports, inputs);
+  Promise.resolve(result).then((value) => {
+    if (value !== undefined) process.stdout.write(JSON.stringify(value));
+  });
+});
+"#;
+
+/// Checks the script read from stdin parses as a function body.
+const NODE_SYNTAX_CHECK: &str = r#"
+let source = '';
+process.stdin.setEncoding('utf8');
+process.stdin.on('data', (chunk) => { source += chunk; });
+process.stdin.on('end', () => new Function('require', 'module', 'exports', 'inputs', source));
+"#;
+
+/// Where scripts run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JavaScriptBackend {
+    /// A `node` process per execution
+    Node {
+        /// Node executable, looked up on `PATH` unless absolute
+        executable: PathBuf,
+    },
+    /// An embedded QuickJS interpreter per execution
+    #[cfg(feature = "quickjs")]
+    QuickJs,
+}
+
+impl Default for JavaScriptBackend {
+    fn default() -> Self {
+        JavaScriptBackend::Node { executable: PathBuf::from("node") }
+    }
+}
+
+/// Settings applied to every JavaScript execution.
+#[derive(Debug, Clone)]
+pub struct JavaScriptEngineConfig {
+    /// Backend running the scripts
+    pub backend: JavaScriptBackend,
+    /// Time a script may run unless the request overrides it
+    pub timeout: Duration,
+    /// Node built-in modules scripts may `require`, without the `node:` prefix
+    pub allowed_modules: Vec<String>,
+    /// Directories Node scripts may read
+    pub fs_read: Vec<PathBuf>,
+    /// Heap limit of the interpreter in bytes
+    pub memory_limit_bytes: usize,
+    /// Maximum bytes kept of stdout and of stderr
+    pub max_output_bytes: usize,
+    /// Whether Node scripts run under the sandbox policy of their security level
+    pub sandbox: bool,
+}
+
+impl Default for JavaScriptEngineConfig {
+    fn default() -> Self {
+        Self {
+            backend: JavaScriptBackend::default(),
+            timeout: Duration::from_secs(30),
+            allowed_modules: ["assert", "buffer", "crypto", "path", "querystring", "string_decoder", "url", "util"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            fs_read: Vec::new(),
+            memory_limit_bytes: 256 * 1024 * 1024,
+            max_output_bytes: 16 * 1024 * 1024,
+            sandbox: true,
+        }
+    }
+}
+
+/// Output of a finished or killed script.
+struct Outcome {
+    stdout: String,
+    stderr: String,
+    exit_code: Option<i32>,
+    success: bool,
+    stopped: Option<StopReason>,
+    peak_memory_bytes: u64,
+}
+
+/// Execution engine running JavaScript on Node.js or QuickJS.
+#[derive(Debug, Clone, Default)]
+pub struct JavaScriptEngine {
+    config: JavaScriptEngineConfig,
+}
+
+impl JavaScriptEngine {
+    /// Create an engine running `node` with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an engine with custom settings.
+    pub fn with_config(config: JavaScriptEngineConfig) -> Self {
+        Self { config }
+    }
+
+    /// Engine settings.
+    pub fn config(&self) -> &JavaScriptEngineConfig {
+        &self.config
+    }
+
+    /// Node command under the permission model with a cleared environment.
+    fn node_command(&self, executable: &PathBuf, environment: Option<&HashMap<String, String>>) -> Command {
+        let mut command = Command::new(executable);
+        command
+            .arg("--experimental-permission")
+            .arg("--disable-warning=ExperimentalWarning")
+            .arg(format!("--max-old-space-size={}", (self.config.memory_limit_bytes / (1024 * 1024)).max(1)))
+            .args(self.config.fs_read.iter().map(|dir| format!("--allow-fs-read={}", dir.display())))
+            .env_clear();
+        if let Some(path) = std::env::var_os("PATH") {
+            command.env("PATH", path);
+        }
+        if let Some(environment) = environment {
+            command.envs(environment);
+        }
+        command
+    }
+
+    /// Run a node `command` feeding it `stdin`.
+    async fn run_node(&self, command: Command, stdin: String, options: RunOptions<'_>) -> Result<Outcome> {
+        let timeout = options.timeout;
+        let outcome = process::run_collecting(command, Some(stdin), options).await?;
+        Ok(Outcome {
+            stopped: outcome.stop_reason(timeout),
+            stdout: outcome.stdout,
+            stderr: outcome.stderr,
+            exit_code: outcome.status.and_then(|status| status.code()),
+            success: outcome.status.is_some_and(|status| status.success()),
+            peak_memory_bytes: 0,
+        })
+    }
+
+    /// Run a request until it finishes, times out or is cancelled.
+    async fn run(&self, context: &ExecutionContext, request: &ExecutionRequest) -> Result<ExecutionResult> {
+        let start = Instant::now();
+        let timeout = request.timeout_override.unwrap_or(self.config.timeout);
+
+        let mut sandbox: Option<SandboxGuard> = None;
+        let (outcome, engine_version) = match &self.config.backend {
+            JavaScriptBackend::Node { executable } => {
+                let mut command = self.node_command(executable, request.environment.as_ref());
+                command
+                    .arg("-e")
+                    .arg(NODE_BOOTSTRAP)
+                    .arg(serde_json::to_string(&self.config.allowed_modules)?);
+                if self.config.sandbox {
+                    sandbox = Some(SandboxPolicy::for_level(request.security_level).apply(&mut command)?);
+                }
+                let stdin = format!("{}\n{}", serde_json::to_string(&request.inputs)?, request.code);
+                let options = RunOptions::new(timeout, self.config.max_output_bytes).in_context(context);
+                (self.run_node(command, stdin, options).await?, "node".to_string())
+            }
+            #[cfg(feature = "quickjs")]
+            JavaScriptBackend::QuickJs => {
+                let config = self.config.clone();
+                let (context, request) = (context.clone(), request.clone());
+                let run = move || quickjs::run(&config, &context, &request, timeout);
+                let outcome = tokio::task::spawn_blocking(run).await??;
+                (outcome, "quickjs".to_string())
+            }
+        };
+
+        let duration = start.elapsed();
+        let mut resource_usage = RuntimeResourceUsage {
+            peak_memory_mb: outcome.peak_memory_bytes / (1024 * 1024),
+            cpu_time_ms: duration.as_millis() as u64,
+            syscall_count: 0,
+            files_accessed: Vec::new(),
+            network_attempts: 0,
+        };
+        if let Some(sandbox) = &sandbox {
+            sandbox.record_usage(&mut resource_usage);
+        }
+        Ok(ExecutionResult {
+            success: outcome.success,
+            output: outcome.stdout,
+            error: outcome.stderr,
+            exit_code: outcome.exit_code,
+            metadata: RuntimeMetadata {
+                code_type: CodeType::JavaScript,
+                session_id: request.session_id.clone(),
+                duration,
+                resource_usage,
+                security_level: request.security_level,
+                engine_version,
+                executed_at: SystemTime::now(),
+            },
+            artifacts: Vec::new(),
+            stopped: outcome.stopped,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl ExecutionEngine for JavaScriptEngine {
+    fn metadata(&self) -> EngineMetadata {
+        let (version, description) = match &self.config.backend {
+            JavaScriptBackend::Node { .. } => ("node", "Node.js subprocess under the permission model"),
+            #[cfg(feature = "quickjs")]
+            JavaScriptBackend::QuickJs => ("quickjs", "Embedded QuickJS interpreter without host APIs"),
+        };
+        EngineMetadata {
+            name: "javascript".to_string(),
+            version: version.to_string(),
+            code_type: CodeType::JavaScript,
+            description: description.to_string(),
+            supported_features: vec![
+                "timeout".to_string(),
+                "module_allow_list".to_string(),
+                "environment".to_string(),
+                "json_inputs".to_string(),
+            ],
+        }
+    }
+
+    async fn validate_code(&self, code: &str) -> Result<()> {
+        match &self.config.backend {
+            JavaScriptBackend::Node { executable } => {
+                let mut command = self.node_command(executable, None);
+                command.arg("-e").arg(NODE_SYNTAX_CHECK);
+                let options = RunOptions::new(self.config.timeout, self.config.max_output_bytes);
+                let outcome = self.run_node(command, code.to_string(), options).await?;
+                if !outcome.success {
+                    let reason = outcome
+                        .stderr
+                        .lines()
+                        .find(|line| line.starts_with("SyntaxError"))
+                        .unwrap_or("syntax check failed")
+                        .to_string();
+                    anyhow::bail!("Invalid JavaScript code: {}", reason);
+                }
+                Ok(())
+            }
+            #[cfg(feature = "quickjs")]
+            JavaScriptBackend::QuickJs => {
+                let code = code.to_string();
+                tokio::task::spawn_blocking(move || quickjs::check_syntax(&code)).await?
+            }
+        }
+    }
+
+    async fn execute(
+        &self,
+        context: &ExecutionContext,
+        request: &ExecutionRequest,
+        _kernel: &ToolKernel,
+    ) -> Result<ExecutionResult> {
+        self.run(context, request).await
+    }
+
+    fn supports_capabilities(&self, capabilities: &CapabilitySet) -> bool {
+        // Node reads the configured directories; neither backend can write or spawn
+        capabilities
+            .capabilities
+            .iter()
+            .all(|capability| *capability == Capability::FileSystem)
+    }
+
+    fn required_capabilities(&self) -> CapabilitySet {
+        match &self.config.backend {
+            JavaScriptBackend::Node { .. } if !self.config.fs_read.is_empty() => {
+                CapabilitySet::with_capabilities(vec![Capability::Process, Capability::FileSystem])
+            }
+            JavaScriptBackend::Node { .. } => CapabilitySet::with_capabilities(vec![Capability::Process]),
+            #[cfg(feature = "quickjs")]
+            JavaScriptBackend::QuickJs => CapabilitySet::with_capabilities(Vec::new()),
+        }
+    }
+}
+
+/// Embedded QuickJS backend.
+#[cfg(feature = "quickjs")]
+mod quickjs {
+    use std::time::{Duration, Instant};
+
+    use anyhow::Result;
+    use rquickjs::{Context, Function, Runtime};
+
+    use super::{JavaScriptEngineConfig, Outcome};
+    use crate::engines::process::OutputBuffer;
+    use crate::streaming::OutputStream;
+    use crate::StopReason;
+    use crate::{ExecutionContext, ExecutionRequest};
+
+    /// Sets up `console` and `process.env`, then runs the script.
+    const RUNNER: &str = r#"
+    (() => {
+      const write = __toka_write, fail = __toka_fail, source = __toka_source;
+      const inputs = JSON.parse(__toka_inputs), env = JSON.parse(__toka_env);
+      for (const name of ['__toka_write', '__toka_fail', '__toka_source', '__toka_inputs', '__toka_env']) {
+        delete globalThis[name];
+      }
+      const format = (args) => args.map((arg) => typeof arg === 'string' ? arg : JSON.stringify(arg)).join(' ') + '\n';
+      const describe = (error) => error && error.stack ? `${error}\n${error.stack}` : `${error}\n`;
+      globalThis.console = {
+        log: (...args) => write(1, format(args)),
+        info: (...args) => write(1, format(args)),
+        warn: (...args) => write(2, format(args)),
+        error: (...args) => write(2, format(args)),
+      };
+      globalThis.process = { env };
+      const require = (name) => { throw new Error(`module '${name}' is not allowed`); };
+      const tool = { exports: {} };
+      try {
+        const result = new Function('require', 'module', 'exports', 'inputs', source)(require, tool, tool.exports, inputs);
+        Promise.resolve(result).then(
+          (value) => { if (value !== undefined) write(1, JSON.stringify(value)); },
+          (error) => fail(describe(error)));
+      } catch (error) {
+        fail(describe(error));
+      }
+    })();
+    "#;
+
+    /// Run a request on a fresh interpreter.
+    pub(super) fn run(
+        config: &JavaScriptEngineConfig,
+        execution: &ExecutionContext,
+        request: &ExecutionRequest,
+        timeout: Duration,
+    ) -> Result<Outcome> {
+        let runtime = Runtime::new()?;
+        runtime.set_memory_limit(config.memory_limit_bytes);
+        let deadline = Instant::now() + timeout;
+        let cancelled = execution.cancellation.clone();
+        runtime.set_interrupt_handler(Some(Box::new(move || {
+            Instant::now() > deadline || cancelled.is_cancelled()
+        })));
+        let context = Context::full(&runtime)?;
+
+        let sink = execution.output.as_ref();
+        let stdout = OutputBuffer::new(config.max_output_bytes).streaming_to(sink, OutputStream::Stdout);
+        let stderr = OutputBuffer::new(config.max_output_bytes).streaming_to(sink, OutputStream::Stderr);
+        let failure = OutputBuffer::new(config.max_output_bytes).streaming_to(sink, OutputStream::Stderr);
+
+        let evaluated = context.with(|ctx| -> rquickjs::Result<()> {
+            let globals = ctx.globals();
+            let (out, err) = (stdout.clone(), stderr.clone());
+            globals.set(
+                "__toka_write",
+                Function::new(ctx.clone(), move |stream: i32, text: String| {
+                    let buffer = if stream == 2 { &err } else { &out };
+                    buffer.push(text.as_bytes());
+                })?,
+            )?;
+            let failed = failure.clone();
+            globals.set(
+                "__toka_fail",
+                Function::new(ctx.clone(), move |text: String| failed.push(text.as_bytes()))?,
+            )?;
+            globals.set("__toka_source", request.code.as_str())?;
+            globals.set("__toka_inputs", request.inputs.to_string())?;
+            globals.set(
+                "__toka_env",
+                serde_json::to_string(&request.environment.clone().unwrap_or_default()).unwrap_or_default(),
+            )?;
+            ctx.eval::<(), _>(RUNNER)
+        });
+        // Settle promises returned by the script
+        let mut settled = evaluated.map_err(|e| e.to_string());
+        while settled.is_ok() && runtime.is_job_pending() {
+            settled = runtime.execute_pending_job().map(|_| ()).map_err(|e| e.to_string());
+        }
+
+        for buffer in [&stdout, &stderr, &failure] {
+            buffer.finish();
+        }
+        let mut error = stderr.contents();
+        let failure = failure.contents();
+        error.push_str(&failure);
+        let stopped = if execution.cancellation.is_cancelled() {
+            Some(StopReason::Cancelled)
+        } else if Instant::now() > deadline {
+            Some(StopReason::TimedOut(timeout))
+        } else {
+            None
+        };
+        let success = match settled {
+            _ if stopped.is_some() => false,
+            Err(e) => {
+                error.push_str(&e);
+                false
+            }
+            Ok(()) => failure.is_empty(),
+        };
+
+        Ok(Outcome {
+            stdout: stdout.contents(),
+            stderr: error,
+            exit_code: None,
+            success,
+            stopped,
+            peak_memory_bytes: runtime.memory_usage().malloc_size.max(0) as u64,
+        })
+    }
+
+    /// Check `code` parses as a function body.
+    pub(super) fn check_syntax(code: &str) -> Result<()> {
+        let runtime = Runtime::new()?;
+        let context = Context::full(&runtime)?;
+        context.with(|ctx| {
+            ctx.globals().set("__toka_source", code)?;
+            ctx.eval::<(), _>("new Function('require', 'module', 'exports', 'inputs', __toka_source);")
+                .map_err(|_| {
+                    let reason = ctx
+                        .catch()
+                        .get::<rquickjs::Coerced<String>>()
+                        .map(|reason| reason.0)
+                        .unwrap_or_else(|_| "syntax check failed".to_string());
+                    anyhow::anyhow!("Invalid JavaScript code: {}", reason)
+                })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SecurityLevel;
+    use tokio_util::sync::CancellationToken;
+
+    fn context() -> ExecutionContext {
+        ExecutionContext {
+            session_id: "test".to_string(),
+            security_level: SecurityLevel::Restricted,
+            capabilities: CapabilitySet::with_capabilities(vec![Capability::Process]),
+            cached_artifact: None,
+            cancellation: CancellationToken::new(),
+            output: None,
+        }
+    }
+
+    fn request(code: &str, inputs: serde_json::Value) -> ExecutionRequest {
+        ExecutionRequest {
+            code_type: CodeType::JavaScript,
+            code: code.to_string(),
+            session_id: "test".to_string(),
+            security_level: SecurityLevel::Restricted,
+            inputs,
+            timeout_override: None,
+            environment: None,
+            agent: None,
+            correlation_id: None,
+        }
+    }
+
+    fn backends() -> Vec<JavaScriptEngine> {
+        #[allow(unused_mut)]
+        let mut engines = vec![JavaScriptEngine::new()];
+        #[cfg(feature = "quickjs")]
+        engines.push(JavaScriptEngine::with_config(JavaScriptEngineConfig {
+            backend: JavaScriptBackend::QuickJs,
+            ..JavaScriptEngineConfig::default()
+        }));
+        engines
+    }
+
+    #[tokio::test]
+    async fn test_console_inputs_environment_and_result() {
+        for engine in backends() {
+            let mut greet = request(
+                "console.log('hello', inputs.name); console.error(process.env.GREETING); return { total: inputs.a + inputs.b };",
+                serde_json::json!({"name": "toka", "a": 2, "b": 3}),
+            );
+            greet.environment = Some(HashMap::from([("GREETING".to_string(), "hi".to_string())]));
+            let result = engine.run(&context(), &greet).await.unwrap();
+
+            assert!(result.success, "{}: {}", engine.metadata().version, result.error);
+            assert_eq!(result.output, "hello toka\n{\"total\":5}");
+            assert_eq!(result.error, "hi\n");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_module_resolution_is_restricted() {
+        for engine in backends() {
+            let forbidden = request("require('child_process')", serde_json::json!({}));
+            let result = engine.run(&context(), &forbidden).await.unwrap();
+            assert!(!result.success);
+            assert!(result.error.contains("module 'child_process' is not allowed"), "{}", result.error);
+        }
+
+        let node = JavaScriptEngine::new();
+        let allowed = request("return require('node:path').join('a', 'b')", serde_json::json!({}));
+        let result = node.run(&context(), &allowed).await.unwrap();
+        assert_eq!(result.output, "\"a/b\"");
+
+        // Dynamic imports bypass `require` but not the permission model
+        let import = request(
+            "return import('fs').then((fs) => fs.readFileSync('/etc/hostname'))",
+            serde_json::json!({}),
+        );
+        let result = node.run(&context(), &import).await.unwrap();
+        assert!(!result.success);
+        assert!(result.error.contains("ERR_ACCESS_DENIED"), "{}", result.error);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_keeps_partial_output() {
+        for engine in backends() {
+            let mut spin = request("console.log('started'); while (true) {}", serde_json::json!({}));
+            spin.timeout_override = Some(Duration::from_millis(500));
+            let result = engine.run(&context(), &spin).await.unwrap();
+
+            assert!(!result.success);
+            assert_eq!(result.stopped, Some(StopReason::TimedOut(Duration::from_millis(500))));
+            assert_eq!(result.output, "started\n");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validate_code() {
+        for engine in backends() {
+            assert!(engine.validate_code("return 1 + 1").await.is_ok());
+            assert!(engine.validate_code("function (").await.is_err());
+        }
+    }
+}
@@ -0,0 +1,305 @@
+//! Embedded Lua engine for lightweight tool scripts.
+//!
+//! Many tool scripts are a handful of lines of string or table manipulation.
+//! Spawning a Python process or compiling a WebAssembly module for them costs
+//! far more than the script itself, so this engine runs them in-process on an
+//! embedded Lua 5.4 interpreter.
+//!
+//! Every execution gets a fresh interpreter with a sandboxed standard library:
+//! only `table`, `string`, `math`, `utf8` and `coroutine` are loaded, and the
+//! base functions that can reach the filesystem or load arbitrary chunks are
+//! removed. Runaway scripts are stopped by an instruction-count hook and a
+//! memory limit.
+//!
+//! Scripts see their inputs as the global `inputs` table and environment
+//! variables as `env`. Anything passed to `print` becomes the execution
+//! output; a non-nil return value is appended as JSON.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
+
+use anyhow::Result;
+use mlua::{HookTriggers, Lua, LuaOptions, LuaSerdeExt, StdLib, Value, Variadic};
+use serde_json::Value as JsonValue;
+
+use crate::{
+    CapabilitySet, CodeType, EngineMetadata, ExecutionContext, ExecutionEngine, ExecutionRequest,
+    ExecutionResult, RuntimeMetadata, RuntimeResourceUsage, ToolKernel,
+};
+
+/// Base library functions removed from the sandbox.
+const BLOCKED_GLOBALS: &[&str] = &["dofile", "loadfile", "load", "collectgarbage"];
+
+/// Limits applied to every Lua execution.
+#[derive(Debug, Clone)]
+pub struct LuaEngineConfig {
+    /// Maximum number of VM instructions a script may execute
+    pub max_instructions: u64,
+    /// Number of instructions between limit checks
+    pub hook_interval: u32,
+    /// Maximum interpreter memory in bytes
+    pub memory_limit_bytes: usize,
+}
+
+impl Default for LuaEngineConfig {
+    fn default() -> Self {
+        Self {
+            max_instructions: 10_000_000,
+            hook_interval: 1_000,
+            memory_limit_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// Execution engine running scripts on an embedded, sandboxed Lua interpreter.
+#[derive(Debug, Clone, Default)]
+pub struct LuaEngine {
+    config: LuaEngineConfig,
+}
+
+impl LuaEngine {
+    /// Create an engine with default limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an engine with custom limits.
+    pub fn with_config(config: LuaEngineConfig) -> Self {
+        Self { config }
+    }
+
+    /// Engine limits.
+    pub fn config(&self) -> &LuaEngineConfig {
+        &self.config
+    }
+
+    /// Create a fresh sandboxed interpreter.
+    fn sandbox(&self) -> Result<Lua> {
+        let lua = Lua::new_with(
+            StdLib::TABLE | StdLib::STRING | StdLib::MATH | StdLib::UTF8 | StdLib::COROUTINE,
+            LuaOptions::default(),
+        )?;
+        lua.set_memory_limit(self.config.memory_limit_bytes)?;
+
+        {
+            let globals = lua.globals();
+            for name in BLOCKED_GLOBALS {
+                globals.set(*name, Value::Nil)?;
+            }
+            // `string.dump` exposes bytecode, which the sandbox never accepts back
+            let string: mlua::Table = globals.get("string")?;
+            string.set("dump", Value::Nil)?;
+        }
+
+        Ok(lua)
+    }
+
+    /// Run a request to completion on the calling thread.
+    fn run(&self, request: &ExecutionRequest) -> Result<ExecutionResult> {
+        let start = Instant::now();
+        let lua = self.sandbox()?;
+
+        let executed = Arc::new(AtomicU64::new(0));
+        let interval = self.config.hook_interval.max(1);
+        let limit = self.config.max_instructions;
+        let counter = executed.clone();
+        lua.set_hook(
+            HookTriggers::new().every_nth_instruction(interval),
+            move |_lua, _debug| {
+                let total = counter.fetch_add(u64::from(interval), Ordering::Relaxed) + u64::from(interval);
+                if total > limit {
+                    return Err(mlua::Error::RuntimeError(format!(
+                        "instruction limit of {} exceeded",
+                        limit
+                    )));
+                }
+                Ok(())
+            },
+        );
+
+        let output = Arc::new(Mutex::new(String::new()));
+        let sink = output.clone();
+        let print = lua.create_function(move |lua, args: Variadic<Value>| {
+            let tostring: mlua::Function = lua.globals().get("tostring")?;
+            let mut parts = Vec::with_capacity(args.len());
+            for arg in args {
+                parts.push(tostring.call::<_, String>(arg)?);
+            }
+            let mut sink = sink.lock().expect("lua output lock poisoned");
+            sink.push_str(&parts.join("\t"));
+            sink.push('\n');
+            Ok(())
+        })?;
+
+        let globals = lua.globals();
+        globals.set("print", print)?;
+        globals.set("inputs", lua.to_value(&request.inputs)?)?;
+        globals.set("env", lua.to_value(&request.environment.clone().unwrap_or_default())?)?;
+        let engine_version: String = globals.get("_VERSION")?;
+
+        let outcome = lua
+            .load(&request.code)
+            .set_name("tool")
+            .eval::<Value>()
+            .and_then(|value| match value {
+                Value::Nil => Ok(None),
+                value => lua.from_value::<JsonValue>(value).map(Some),
+            });
+
+        let mut stdout = output.lock().expect("lua output lock poisoned").clone();
+        let (success, error) = match outcome {
+            Ok(Some(value)) => {
+                stdout.push_str(&value.to_string());
+                (true, String::new())
+            }
+            Ok(None) => (true, String::new()),
+            Err(e) => (false, e.to_string()),
+        };
+
+        let duration = start.elapsed();
+        Ok(ExecutionResult {
+            success,
+            output: stdout,
+            error,
+            exit_code: None,
+            metadata: RuntimeMetadata {
+                code_type: CodeType::Lua,
+                session_id: request.session_id.clone(),
+                duration,
+                resource_usage: RuntimeResourceUsage {
+                    peak_memory_mb: (lua.used_memory() / (1024 * 1024)) as u64,
+                    cpu_time_ms: duration.as_millis() as u64,
+                    syscall_count: 0,
+                    files_accessed: Vec::new(),
+                    network_attempts: 0,
+                },
+                security_level: request.security_level,
+                engine_version,
+                executed_at: SystemTime::now(),
+            },
+            artifacts: Vec::new(),
+            stopped: None,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl ExecutionEngine for LuaEngine {
+    fn metadata(&self) -> EngineMetadata {
+        EngineMetadata {
+            name: "lua".to_string(),
+            version: "5.4".to_string(),
+            code_type: CodeType::Lua,
+            description: "Embedded Lua interpreter with a sandboxed standard library".to_string(),
+            supported_features: vec![
+                "instruction_limit".to_string(),
+                "memory_limit".to_string(),
+                "json_inputs".to_string(),
+            ],
+        }
+    }
+
+    async fn validate_code(&self, code: &str) -> Result<()> {
+        let lua = self.sandbox()?;
+        lua.load(code)
+            .set_name("tool")
+            .into_function()
+            .map_err(|e| anyhow::anyhow!("Invalid Lua code: {}", e))?;
+        Ok(())
+    }
+
+    async fn execute(
+        &self,
+        _context: &ExecutionContext,
+        request: &ExecutionRequest,
+        _kernel: &ToolKernel,
+    ) -> Result<ExecutionResult> {
+        // The interpreter is synchronous; keep it off the async worker threads
+        let engine = self.clone();
+        let request = request.clone();
+        tokio::task::spawn_blocking(move || engine.run(&request)).await?
+    }
+
+    fn supports_capabilities(&self, capabilities: &CapabilitySet) -> bool {
+        // The sandbox has no filesystem, network or process access to grant
+        capabilities.capabilities.is_empty()
+    }
+
+    fn required_capabilities(&self) -> CapabilitySet {
+        CapabilitySet::with_capabilities(Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SecurityLevel;
+
+    fn request(code: &str, inputs: JsonValue) -> ExecutionRequest {
+        ExecutionRequest {
+            code_type: CodeType::Lua,
+            code: code.to_string(),
+            session_id: "test".to_string(),
+            security_level: SecurityLevel::Restricted,
+            inputs,
+            timeout_override: None,
+            environment: None,
+            agent: None,
+            correlation_id: None,
+        }
+    }
+
+    #[test]
+    fn test_print_and_inputs() {
+        let engine = LuaEngine::new();
+        let result = engine
+            .run(&request(
+                "print('hello', inputs.name)\nreturn { total = inputs.a + inputs.b }",
+                serde_json::json!({"name": "toka", "a": 2, "b": 3}),
+            ))
+            .unwrap();
+
+        assert!(result.success, "{}", result.error);
+        assert_eq!(result.output, "hello\ttoka\n{\"total\":5}");
+        assert_eq!(result.metadata.code_type, CodeType::Lua);
+    }
+
+    #[test]
+    fn test_sandboxed_stdlib() {
+        let engine = LuaEngine::new();
+        let result = engine
+            .run(&request(
+                "return { io = io == nil, os = os == nil, load = load == nil, dofile = dofile == nil }",
+                serde_json::json!({}),
+            ))
+            .unwrap();
+
+        assert!(result.success, "{}", result.error);
+        assert_eq!(
+            result.output,
+            serde_json::json!({"io": true, "os": true, "load": true, "dofile": true}).to_string()
+        );
+    }
+
+    #[test]
+    fn test_instruction_limit() {
+        let engine = LuaEngine::with_config(LuaEngineConfig {
+            max_instructions: 10_000,
+            ..LuaEngineConfig::default()
+        });
+        let result = engine
+            .run(&request("while true do end", serde_json::json!({})))
+            .unwrap();
+
+        assert!(!result.success);
+        assert!(result.error.contains("instruction limit"));
+    }
+
+    #[tokio::test]
+    async fn test_validate_code() {
+        let engine = LuaEngine::new();
+        assert!(engine.validate_code("return 1 + 1").await.is_ok());
+        assert!(engine.validate_code("return (").await.is_err());
+    }
+}
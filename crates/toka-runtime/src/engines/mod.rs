@@ -0,0 +1,77 @@
+//! Built-in execution engines.
+//!
+//! Each engine lives behind its own cargo feature so that heavyweight
+//! interpreters are only compiled in when requested.
+
+#[cfg(feature = "javascript")]
+pub mod javascript;
+#[cfg(feature = "lua")]
+pub mod lua;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "shell")]
+pub mod shell;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(any(feature = "javascript", feature = "python", feature = "shell"))]
+mod process;
+
+use crate::{CodeType, ExecutionEngine};
+
+/// Every built-in engine compiled into this build, keyed by the code type it runs.
+///
+/// Engines whose cargo feature is disabled are left out; the feature names
+/// match [`CodeType::engine_key`].
+#[allow(clippy::vec_init_then_push)]
+pub fn default_engines() -> Vec<(CodeType, Box<dyn ExecutionEngine + Send + Sync>)> {
+    #[allow(unused_mut)]
+    let mut engines: Vec<(CodeType, Box<dyn ExecutionEngine + Send + Sync>)> = Vec::new();
+
+    #[cfg(feature = "python")]
+    engines.push((CodeType::Python, Box::new(python::PythonEngine::new())));
+
+    #[cfg(feature = "javascript")]
+    engines.push((CodeType::JavaScript, Box::new(javascript::JavaScriptEngine::new())));
+
+    #[cfg(feature = "wasm")]
+    match wasm::WasmEngine::new() {
+        Ok(engine) => engines.push((CodeType::WebAssembly, Box::new(engine))),
+        Err(e) => tracing::warn!("WebAssembly engine unavailable: {}", e),
+    }
+
+    #[cfg(feature = "shell")]
+    engines.push((CodeType::Shell, Box::new(shell::ShellEngine::new())));
+
+    #[cfg(feature = "lua")]
+    engines.push((CodeType::Lua, Box::new(lua::LuaEngine::new())));
+
+    engines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_engines_match_their_keys() {
+        let engines = default_engines();
+        for (code_type, engine) in &engines {
+            assert_eq!(&engine.metadata().code_type, code_type);
+        }
+
+        let registered: Vec<CodeType> = engines.into_iter().map(|(code_type, _)| code_type).collect();
+        for code_type in CodeType::ALL {
+            let enabled = match code_type {
+                CodeType::Python => cfg!(feature = "python"),
+                CodeType::JavaScript => cfg!(feature = "javascript"),
+                CodeType::WebAssembly => cfg!(feature = "wasm"),
+                CodeType::Shell => cfg!(feature = "shell"),
+                CodeType::Lua => cfg!(feature = "lua"),
+                // Not implemented yet, so never registered
+                _ => false,
+            };
+            assert_eq!(registered.contains(&code_type), enabled, "{:?}", code_type);
+        }
+    }
+}
@@ -0,0 +1,283 @@
+//! Plumbing shared by the engines running scripts in a child process.
+
+use std::process::{ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, Command};
+use tokio_util::sync::CancellationToken;
+
+use crate::streaming::{decode_complete, OutputSink, OutputStream};
+use crate::{ExecutionContext, StopReason};
+
+/// Stream output collected while a script runs, capped at a size.
+#[derive(Clone)]
+pub(crate) struct OutputBuffer {
+    state: Arc<Mutex<BufferState>>,
+    limit: usize,
+    sink: Option<(OutputSink, OutputStream)>,
+}
+
+#[derive(Default)]
+struct BufferState {
+    bytes: Vec<u8>,
+    /// Bytes already sent to the sink
+    streamed: usize,
+}
+
+impl OutputBuffer {
+    pub(crate) fn new(limit: usize) -> Self {
+        Self { state: Arc::default(), limit, sink: None }
+    }
+
+    /// Also send the output kept to `sink`, if any, as written to `stream`.
+    pub(crate) fn streaming_to(mut self, sink: Option<&OutputSink>, stream: OutputStream) -> Self {
+        self.sink = sink.map(|sink| (sink.clone(), stream));
+        self
+    }
+
+    /// Append `chunk`, dropping whatever does not fit.
+    pub(crate) fn push(&self, chunk: &[u8]) {
+        let mut state = self.state.lock().expect("output buffer lock poisoned");
+        let room = self.limit.saturating_sub(state.bytes.len());
+        state.bytes.extend_from_slice(&chunk[..chunk.len().min(room)]);
+        self.stream(&mut state, false);
+    }
+
+    /// Send the output not yet streamed, up to the last complete character
+    /// unless the output is `complete`.
+    fn stream(&self, state: &mut BufferState, complete: bool) {
+        if let Some((sink, stream)) = &self.sink {
+            let (text, len) = decode_complete(&state.bytes[state.streamed..], complete);
+            state.streamed += len;
+            sink.send(*stream, text);
+        }
+    }
+
+    /// Send whatever output is left to the sink.
+    pub(crate) fn finish(&self) {
+        let mut state = self.state.lock().expect("output buffer lock poisoned");
+        self.stream(&mut state, true);
+    }
+
+    /// Copy `reader` into the buffer until it closes.
+    pub(crate) async fn collect(self, mut reader: impl AsyncRead + Unpin) {
+        let mut chunk = [0u8; 8192];
+        while let Ok(read) = reader.read(&mut chunk).await {
+            if read == 0 {
+                break;
+            }
+            self.push(&chunk[..read]);
+        }
+        self.finish();
+    }
+
+    pub(crate) fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.state.lock().expect("output buffer lock poisoned").bytes).into_owned()
+    }
+}
+
+/// Output of a finished or killed child.
+pub(crate) struct ProcessOutcome {
+    pub(crate) stdout: String,
+    pub(crate) stderr: String,
+    /// Exit status, `None` if the child was killed
+    pub(crate) status: Option<ExitStatus>,
+    /// Whether the child was killed because the execution was cancelled
+    pub(crate) cancelled: bool,
+}
+
+impl ProcessOutcome {
+    /// Why the child was killed, if it was.
+    pub(crate) fn stop_reason(&self, timeout: Duration) -> Option<StopReason> {
+        match (self.status, self.cancelled) {
+            (Some(_), _) => None,
+            (None, true) => Some(StopReason::Cancelled),
+            (None, false) => Some(StopReason::TimedOut(timeout)),
+        }
+    }
+}
+
+/// Process group led by a child, killed when dropped.
+///
+/// It must be dropped before the leader is reaped: the group ID is the
+/// leader's PID, which may be reused once the leader is gone.
+struct ProcessGroup(u32);
+
+#[cfg(unix)]
+impl ProcessGroup {
+    /// Wait until the leader exited, without reaping it.
+    async fn leader_exited(&self) -> std::io::Result<()> {
+        let leader = self.0 as libc::id_t;
+        tokio::task::spawn_blocking(move || loop {
+            // SAFETY: siginfo_t is plain data, valid when zeroed.
+            let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+            // SAFETY: `info` is a valid siginfo_t to fill in.
+            if unsafe { libc::waitid(libc::P_PID, leader, &mut info, libc::WEXITED | libc::WNOWAIT) } == 0 {
+                return Ok(());
+            }
+            let error = std::io::Error::last_os_error();
+            if error.kind() != std::io::ErrorKind::Interrupted {
+                return Err(error);
+            }
+        })
+        .await?
+    }
+}
+
+#[cfg(unix)]
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        // SAFETY: killpg has no memory safety requirements.
+        if unsafe { libc::killpg(self.0 as libc::pid_t, libc::SIGKILL) } != 0 {
+            let error = std::io::Error::last_os_error();
+            if error.raw_os_error() != Some(libc::ESRCH) {
+                tracing::warn!("Failed to kill process group {}: {}", self.0, error);
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+impl ProcessGroup {
+    async fn leader_exited(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Wait for `child` to exit, killing what is left of its `group` before the
+/// child is reaped.
+async fn wait_for_exit(child: &mut Child, group: &mut Option<ProcessGroup>) -> std::io::Result<ExitStatus> {
+    if let Some(group) = group {
+        group.leader_exited().await?;
+    }
+    drop(group.take());
+    child.wait().await
+}
+
+/// How [`run_collecting`] runs a command.
+pub(crate) struct RunOptions<'a> {
+    /// Time the command may run
+    pub(crate) timeout: Duration,
+    /// Maximum bytes kept of stdout and of stderr
+    pub(crate) max_output_bytes: usize,
+    /// Whether the command leads its own process group, which is killed once
+    /// the command exits, or when the run is dropped, so background jobs
+    /// cannot hold the output pipes open
+    pub(crate) group: bool,
+    /// Kills the command once cancelled
+    pub(crate) cancellation: Option<&'a CancellationToken>,
+    /// Receives output as it arrives
+    pub(crate) output: Option<&'a OutputSink>,
+}
+
+impl<'a> RunOptions<'a> {
+    pub(crate) fn new(timeout: Duration, max_output_bytes: usize) -> Self {
+        Self {
+            timeout,
+            max_output_bytes,
+            group: false,
+            cancellation: None,
+            output: None,
+        }
+    }
+
+    /// Stop on cancellation of, and stream output to, the execution `context`.
+    pub(crate) fn in_context(mut self, context: &'a ExecutionContext) -> Self {
+        self.cancellation = Some(&context.cancellation);
+        self.output = context.output.as_ref();
+        self
+    }
+}
+
+/// Run `command` feeding it `stdin`, collecting output until it exits, its
+/// timeout expires or it is cancelled.
+pub(crate) async fn run_collecting(
+    mut command: Command,
+    stdin: Option<String>,
+    options: RunOptions<'_>,
+) -> Result<ProcessOutcome> {
+    command
+        .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("Failed to start {:?}", command.as_std().get_program()))?;
+    let mut group = child.id().filter(|_| options.group).map(ProcessGroup);
+
+    let stdout = OutputBuffer::new(options.max_output_bytes).streaming_to(options.output, OutputStream::Stdout);
+    let stderr = OutputBuffer::new(options.max_output_bytes).streaming_to(options.output, OutputStream::Stderr);
+    let readers = [
+        tokio::spawn(stdout.clone().collect(child.stdout.take().expect("stdout is piped"))),
+        tokio::spawn(stderr.clone().collect(child.stderr.take().expect("stderr is piped"))),
+    ];
+
+    // Written concurrently, so a script that never reads its input still
+    // times out and can be cancelled
+    let writer = stdin.zip(child.stdin.take()).map(|(input, mut pipe)| {
+        tokio::spawn(async move {
+            // A script exiting without reading its input closes the pipe early
+            let _ = pipe.write_all(input.as_bytes()).await;
+        })
+    });
+
+    let cancelled = async {
+        match options.cancellation {
+            Some(cancellation) => cancellation.cancelled().await,
+            None => std::future::pending().await,
+        }
+    };
+    let (status, cancelled) = tokio::select! {
+        status = tokio::time::timeout(options.timeout, wait_for_exit(&mut child, &mut group)) => match status {
+            Ok(status) => (Some(status?), false),
+            Err(_) => {
+                drop(group.take());
+                child.kill().await?;
+                (None, false)
+            }
+        },
+        _ = cancelled => {
+            drop(group.take());
+            child.kill().await?;
+            (None, true)
+        }
+    };
+    if let Some(writer) = writer {
+        writer.abort();
+    }
+    for reader in readers {
+        reader.await?;
+    }
+
+    Ok(ProcessOutcome {
+        stdout: stdout.contents(),
+        stderr: stderr.contents(),
+        status,
+        cancelled,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unread_stdin_does_not_block_timeout() {
+        // Far more input than a pipe buffers, for a command that never reads it
+        let input = "x".repeat(4 * 1024 * 1024);
+        let mut command = Command::new("sleep");
+        command.arg("30");
+
+        let started = std::time::Instant::now();
+        let outcome = run_collecting(command, Some(input), RunOptions::new(Duration::from_millis(200), 1024))
+            .await
+            .unwrap();
+        assert!(outcome.status.is_none());
+        assert!(!outcome.cancelled);
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,364 @@
+//! Python engine running scripts in a `python3` subprocess.
+//!
+//! Every execution starts a fresh interpreter in isolated mode (`-I`), so
+//! `PYTHON*` environment variables and the user site directory are ignored.
+//! The child environment is cleared; it only sees `PATH` and the variables
+//! of the request. A small bootstrap reads the request inputs and the script
+//! from stdin, exposes the inputs as the global `inputs` and runs the script
+//! as `__main__`.
+//!
+//! On exit the bootstrap writes the CPU time and peak memory of the
+//! interpreter to a report file, which the engine turns into
+//! [`RuntimeResourceUsage`]. Output is unbuffered and collected, and
+//! streamed, while the script runs, so a script killed at its timeout or on
+//! cancellation still returns what it printed until then.
+//!
+//! Unless disabled, the interpreter runs under the [`SandboxPolicy`] of the
+//! request's security level; its cgroup, when one could be created, gives
+//! the usage of the script and everything it started.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::Result;
+use serde::Deserialize;
+use tokio::process::Command;
+
+use super::process::{self, RunOptions};
+use crate::sandbox::SandboxPolicy;
+use crate::{
+    Capability, CapabilitySet, CodeType, EngineMetadata, ExecutionContext, ExecutionEngine, ExecutionRequest,
+    ExecutionResult, RuntimeMetadata, RuntimeResourceUsage, ToolKernel,
+};
+
+/// Distinguishes the usage reports of concurrent executions.
+static REPORT_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Runs the script read from stdin after a line of JSON inputs, writing the
+/// usage report to the file named by its argument.
+const BOOTSTRAP: &str = r#"
+import atexit, json, platform, resource, sys
+
+_toka_report_path = sys.argv.pop(1)
+
+def _toka_report():
+    usage = resource.getrusage(resource.RUSAGE_SELF)
+    maxrss = usage.ru_maxrss if sys.platform == "darwin" else usage.ru_maxrss * 1024
+    with open(_toka_report_path, "w") as report:
+        json.dump({
+            "cpu_time_ms": int((usage.ru_utime + usage.ru_stime) * 1000),
+            "peak_memory_bytes": maxrss,
+            "version": platform.python_version(),
+        }, report)
+
+atexit.register(_toka_report)
+_toka_inputs = json.loads(sys.stdin.readline())
+_toka_source = sys.stdin.read()
+exec(compile(_toka_source, "<tool>", "exec"), {"__name__": "__main__", "inputs": _toka_inputs})
+"#;
+
+/// Checks the script read from stdin compiles.
+const SYNTAX_CHECK: &str = "import sys; compile(sys.stdin.read(), '<tool>', 'exec')";
+
+/// Settings applied to every Python execution.
+#[derive(Debug, Clone)]
+pub struct PythonEngineConfig {
+    /// Interpreter to run, looked up on `PATH` unless absolute
+    pub interpreter: PathBuf,
+    /// Time a script may run unless the request overrides it
+    pub timeout: Duration,
+    /// Working directory of the interpreter, the runtime's own if unset
+    pub working_dir: Option<PathBuf>,
+    /// Maximum bytes kept of stdout and of stderr
+    pub max_output_bytes: usize,
+    /// Whether scripts run under the sandbox policy of their security level
+    pub sandbox: bool,
+}
+
+impl Default for PythonEngineConfig {
+    fn default() -> Self {
+        Self {
+            interpreter: PathBuf::from("python3"),
+            timeout: Duration::from_secs(30),
+            working_dir: None,
+            max_output_bytes: 16 * 1024 * 1024,
+            sandbox: true,
+        }
+    }
+}
+
+/// Usage reported by the bootstrap on exit.
+#[derive(Debug, Deserialize)]
+struct UsageReport {
+    cpu_time_ms: u64,
+    peak_memory_bytes: u64,
+    version: String,
+}
+
+/// Usage report file, removed when dropped.
+struct ReportFile(PathBuf);
+
+impl ReportFile {
+    fn new() -> Self {
+        Self(std::env::temp_dir().join(format!(
+            "toka-python-{}-{}.json",
+            std::process::id(),
+            REPORT_COUNTER.fetch_add(1, Ordering::Relaxed)
+        )))
+    }
+
+    /// The report, if the interpreter exited normally.
+    fn read(&self) -> Option<UsageReport> {
+        serde_json::from_slice(&std::fs::read(&self.0).ok()?).ok()
+    }
+}
+
+impl Drop for ReportFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Execution engine running scripts in a `python3` subprocess.
+#[derive(Debug, Clone, Default)]
+pub struct PythonEngine {
+    config: PythonEngineConfig,
+}
+
+impl PythonEngine {
+    /// Create an engine running `python3` with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an engine with custom settings.
+    pub fn with_config(config: PythonEngineConfig) -> Self {
+        Self { config }
+    }
+
+    /// Engine settings.
+    pub fn config(&self) -> &PythonEngineConfig {
+        &self.config
+    }
+
+    /// Unbuffered interpreter command in isolated mode with a cleared environment.
+    fn command(&self, environment: Option<&HashMap<String, String>>) -> Command {
+        let mut command = Command::new(&self.config.interpreter);
+        command.args(["-I", "-u"]).env_clear();
+        if let Some(path) = std::env::var_os("PATH") {
+            command.env("PATH", path);
+        }
+        if let Some(environment) = environment {
+            command.envs(environment);
+        }
+        if let Some(dir) = &self.config.working_dir {
+            command.current_dir(dir);
+        }
+        command
+    }
+
+    /// Run a request until it finishes, times out or is cancelled.
+    async fn run(&self, context: &ExecutionContext, request: &ExecutionRequest) -> Result<ExecutionResult> {
+        let start = Instant::now();
+        let timeout = request.timeout_override.unwrap_or(self.config.timeout);
+
+        let report = ReportFile::new();
+        let mut command = self.command(request.environment.as_ref());
+        command.arg("-c").arg(BOOTSTRAP).arg(&report.0).process_group(0);
+        let sandbox = self
+            .config
+            .sandbox
+            .then(|| SandboxPolicy::for_level(request.security_level).apply(&mut command))
+            .transpose()?;
+        let stdin = format!("{}\n{}", serde_json::to_string(&request.inputs)?, request.code);
+        let options = RunOptions {
+            group: true,
+            ..RunOptions::new(timeout, self.config.max_output_bytes).in_context(context)
+        };
+        let outcome = process::run_collecting(command, Some(stdin), options).await?;
+        let duration = start.elapsed();
+
+        let usage = report.read();
+        let stopped = outcome.stop_reason(timeout);
+        let mut resource_usage = RuntimeResourceUsage {
+            peak_memory_mb: usage.as_ref().map_or(0, |usage| usage.peak_memory_bytes / (1024 * 1024)),
+            cpu_time_ms: usage.as_ref().map_or(duration.as_millis() as u64, |usage| usage.cpu_time_ms),
+            syscall_count: 0,
+            files_accessed: Vec::new(),
+            network_attempts: 0,
+        };
+        if let Some(sandbox) = &sandbox {
+            sandbox.record_usage(&mut resource_usage);
+        }
+
+        Ok(ExecutionResult {
+            success: outcome.status.is_some_and(|status| status.success()),
+            output: outcome.stdout,
+            error: outcome.stderr,
+            exit_code: outcome.status.and_then(|status| status.code()),
+            metadata: RuntimeMetadata {
+                code_type: CodeType::Python,
+                session_id: request.session_id.clone(),
+                duration,
+                resource_usage,
+                security_level: request.security_level,
+                engine_version: usage.map_or_else(|| "3".to_string(), |usage| usage.version),
+                executed_at: SystemTime::now(),
+            },
+            artifacts: Vec::new(),
+            stopped,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl ExecutionEngine for PythonEngine {
+    fn metadata(&self) -> EngineMetadata {
+        EngineMetadata {
+            name: "python".to_string(),
+            version: "3".to_string(),
+            code_type: CodeType::Python,
+            description: "Python 3 interpreter in an isolated subprocess".to_string(),
+            supported_features: vec![
+                "timeout".to_string(),
+                "resource_usage".to_string(),
+                "json_inputs".to_string(),
+            ],
+        }
+    }
+
+    async fn validate_code(&self, code: &str) -> Result<()> {
+        let mut command = self.command(None);
+        command.arg("-c").arg(SYNTAX_CHECK);
+        let options = RunOptions::new(self.config.timeout, self.config.max_output_bytes);
+        let outcome = process::run_collecting(command, Some(code.to_string()), options).await?;
+        let status = outcome.status.ok_or_else(|| anyhow::anyhow!("Python syntax check timed out"))?;
+        if !status.success() {
+            let reason = outcome.stderr.lines().last().unwrap_or("syntax check failed");
+            anyhow::bail!("Invalid Python code: {}", reason);
+        }
+        Ok(())
+    }
+
+    async fn execute(
+        &self,
+        context: &ExecutionContext,
+        request: &ExecutionRequest,
+        _kernel: &ToolKernel,
+    ) -> Result<ExecutionResult> {
+        self.run(context, request).await
+    }
+
+    fn supports_capabilities(&self, _capabilities: &CapabilitySet) -> bool {
+        // The interpreter is an ordinary process and can use any capability
+        true
+    }
+
+    fn required_capabilities(&self) -> CapabilitySet {
+        CapabilitySet::with_capabilities(vec![Capability::Process])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SecurityLevel, StopReason};
+    use tokio_util::sync::CancellationToken;
+
+    fn context() -> ExecutionContext {
+        ExecutionContext {
+            session_id: "test".to_string(),
+            security_level: SecurityLevel::Restricted,
+            capabilities: CapabilitySet::with_capabilities(vec![Capability::Process]),
+            cached_artifact: None,
+            cancellation: CancellationToken::new(),
+            output: None,
+        }
+    }
+
+    fn request(code: &str, inputs: serde_json::Value) -> ExecutionRequest {
+        ExecutionRequest {
+            code_type: CodeType::Python,
+            code: code.to_string(),
+            session_id: "test".to_string(),
+            security_level: SecurityLevel::Restricted,
+            inputs,
+            timeout_override: None,
+            environment: None,
+            agent: None,
+            correlation_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_output_inputs_and_usage() {
+        let engine = PythonEngine::new();
+        let greet = request(
+            "import sys\nprint('hello', inputs['name'])\nprint(inputs['a'] + inputs['b'], file=sys.stderr)",
+            serde_json::json!({"name": "toka", "a": 2, "b": 3}),
+        );
+        let result = engine.run(&context(), &greet).await.unwrap();
+
+        assert!(result.success, "{}", result.error);
+        assert_eq!(result.output, "hello toka\n");
+        assert_eq!(result.error, "5\n");
+        assert_eq!(result.exit_code, Some(0));
+        assert!(result.metadata.resource_usage.peak_memory_mb > 0);
+        assert!(result.metadata.engine_version.starts_with("3."));
+    }
+
+    #[tokio::test]
+    async fn test_failure_exit_code_and_environment() {
+        let engine = PythonEngine::new();
+        let mut failing = request("import os, sys\nprint(os.environ.get('GREETING'))\nsys.exit(3)", serde_json::json!({}));
+        failing.environment = Some(HashMap::from([("GREETING".to_string(), "hi".to_string())]));
+        let result = engine.run(&context(), &failing).await.unwrap();
+
+        assert!(!result.success);
+        assert_eq!(result.exit_code, Some(3));
+        assert_eq!(result.output, "hi\n");
+
+        let raising = request("raise ValueError('boom')", serde_json::json!({}));
+        let result = engine.run(&context(), &raising).await.unwrap();
+        assert_eq!(result.exit_code, Some(1));
+        assert!(result.error.contains("ValueError: boom"));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_kills_script() {
+        let engine = PythonEngine::new();
+        let mut sleeper = request("import time\ntime.sleep(30)", serde_json::json!({}));
+        sleeper.timeout_override = Some(Duration::from_millis(300));
+        let result = engine.run(&context(), &sleeper).await.unwrap();
+
+        assert!(!result.success);
+        assert_eq!(result.exit_code, None);
+        assert_eq!(result.stopped, Some(StopReason::TimedOut(Duration::from_millis(300))));
+        assert!(result.metadata.duration < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_kills_background_children() {
+        let engine = PythonEngine::new();
+        let mut spawner = request(
+            "import subprocess, time\nsubprocess.Popen(['sleep', '600'])\nprint('started', flush=True)\ntime.sleep(30)",
+            serde_json::json!({}),
+        );
+        spawner.timeout_override = Some(Duration::from_millis(500));
+        let result = engine.run(&context(), &spawner).await.unwrap();
+
+        assert!(!result.success);
+        assert_eq!(result.output, "started\n");
+        assert_eq!(result.stopped, Some(StopReason::TimedOut(Duration::from_millis(500))));
+        assert!(result.metadata.duration < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_validate_code() {
+        let engine = PythonEngine::new();
+        assert!(engine.validate_code("x = 1 + 1").await.is_ok());
+        assert!(engine.validate_code("def broken(:").await.is_err());
+    }
+}
@@ -0,0 +1,551 @@
+//! Shell engine running scripts in a restricted bash.
+//!
+//! Scripts run under `bash --restricted` with a sandbox policy:
+//!
+//! - **Allow-listed binaries**: `PATH` points at a private directory holding
+//!   only the binaries in [`ShellEngineConfig::allowed_binaries`], and a
+//!   restricted shell can neither change `PATH` nor run commands by path.
+//! - **Working-directory jail**: the restricted shell cannot `cd` out of its
+//!   working directory. Scripts get a fresh, empty directory unless the
+//!   execution holds the `FileSystem` capability, in which case they run in
+//!   [`ShellEngineConfig::working_dir`].
+//! - **No network**: scripts run in their own network namespace unless the
+//!   engine allows networking and the execution holds the `Network`
+//!   capability.
+//! - **OS sandbox**: the [`SandboxPolicy`] of the request's security level
+//!   limits memory, CPU and processes and filters syscalls.
+//!
+//! Executions without the `Process` capability are refused, as are requests
+//! setting dynamic loader variables such as `LD_PRELOAD`. Inputs are
+//! passed as JSON in the `TOKA_INPUTS` environment variable, next to the
+//! request's environment variables. Scripts running longer than the timeout,
+//! or whose execution is cancelled, are killed together with everything they
+//! started.
+//!
+//! The policy confines what a script can start, not what those binaries
+//! read; the filesystem is not isolated.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{Context, Result};
+use tokio::process::Command;
+
+use super::process::{self, RunOptions};
+use crate::sandbox::SandboxPolicy;
+use crate::{
+    Capability, CapabilitySet, CodeType, EngineMetadata, ExecutionContext, ExecutionEngine, ExecutionRequest,
+    ExecutionResult, RuntimeMetadata, RuntimeResourceUsage, ToolKernel,
+};
+
+/// Environment variables a request may not set, as they change how the
+/// shell starts or where it finds commands.
+const BLOCKED_VARIABLES: &[&str] = &["PATH", "HOME", "SHELL", "ENV", "BASH_ENV", "SHELLOPTS", "BASHOPTS", "CDPATH", "IFS"];
+
+/// Prefixes of environment variables the dynamic loader and libc read to
+/// load code or data from arbitrary paths, into bash and every allowed
+/// binary alike. Requests setting one are refused.
+const LOADER_VARIABLES: &[&str] = &[
+    "LD_",
+    "GCONV_PATH",
+    "GETCONF_DIR",
+    "GLIBC_TUNABLES",
+    "HOSTALIASES",
+    "LOCPATH",
+    "MALLOC_",
+    "NLSPATH",
+    "RESOLV_HOST_CONF",
+];
+
+/// Longest script accepted, as it is passed as a single argument.
+pub const MAX_SCRIPT_BYTES: usize = 128 * 1024;
+
+/// Sandbox policy applied to every shell execution.
+#[derive(Debug, Clone)]
+pub struct ShellEngineConfig {
+    /// Shell to run, looked up on `PATH` unless absolute
+    pub shell: PathBuf,
+    /// Binaries scripts may run, looked up on the runtime's `PATH`.
+    ///
+    /// Binaries that start other programs, such as `awk` (`system()`), GNU
+    /// `sed` (the `e` command), `sort` (`--compress-program`), `find` or
+    /// `xargs`, run them outside the restricted shell and defeat the list.
+    pub allowed_binaries: Vec<String>,
+    /// Working directory of executions holding the `FileSystem` capability
+    pub working_dir: Option<PathBuf>,
+    /// Whether executions holding the `Network` capability may use the network
+    pub allow_network: bool,
+    /// Time a script may run unless the request overrides it
+    pub timeout: Duration,
+    /// Maximum bytes kept of stdout and of stderr
+    pub max_output_bytes: usize,
+}
+
+impl Default for ShellEngineConfig {
+    fn default() -> Self {
+        Self {
+            shell: PathBuf::from("bash"),
+            allowed_binaries: [
+                "basename", "cat", "cut", "date", "dirname", "grep", "head", "ls", "tail", "tr", "uniq", "wc",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+            working_dir: None,
+            allow_network: false,
+            timeout: Duration::from_secs(30),
+            max_output_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// Resolve `program` on the runtime's `PATH`.
+fn which(program: &Path) -> Option<PathBuf> {
+    if program.components().count() > 1 {
+        return Some(program.to_path_buf());
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Execution engine running shell scripts under a sandbox policy.
+#[derive(Debug, Clone, Default)]
+pub struct ShellEngine {
+    config: ShellEngineConfig,
+}
+
+impl ShellEngine {
+    /// Create an engine with the default policy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an engine with a custom policy.
+    pub fn with_config(config: ShellEngineConfig) -> Self {
+        Self { config }
+    }
+
+    /// Engine policy.
+    pub fn config(&self) -> &ShellEngineConfig {
+        &self.config
+    }
+
+    /// Absolute path of the shell.
+    fn shell(&self) -> Result<PathBuf> {
+        which(&self.config.shell).with_context(|| format!("Shell {} not found", self.config.shell.display()))
+    }
+
+    /// Link the allowed binaries into `bin`.
+    fn link_binaries(&self, bin: &Path) -> Result<()> {
+        std::fs::create_dir(bin)?;
+        for name in &self.config.allowed_binaries {
+            match which(Path::new(name)) {
+                Some(target) => std::os::unix::fs::symlink(&target, bin.join(name))?,
+                None => tracing::debug!("Allowed binary {} not found", name),
+            }
+        }
+        Ok(())
+    }
+
+    /// Run a request to completion.
+    async fn run(&self, context: &ExecutionContext, request: &ExecutionRequest) -> Result<ExecutionResult> {
+        let start = Instant::now();
+        let held = &context.capabilities.capabilities;
+        if !held.contains(&Capability::Process) {
+            anyhow::bail!("Shell execution requires the Process capability");
+        }
+        if request.code.len() > MAX_SCRIPT_BYTES {
+            anyhow::bail!("Shell script exceeds {} bytes", MAX_SCRIPT_BYTES);
+        }
+        if let Some(name) = request.environment.iter().flatten().map(|(name, _)| name).find(|name| {
+            LOADER_VARIABLES.iter().any(|prefix| name.starts_with(prefix))
+        }) {
+            anyhow::bail!("Shell execution may not set the loader variable {}", name);
+        }
+        let timeout = request.timeout_override.unwrap_or(self.config.timeout);
+
+        // A fresh, randomly named directory only this user can enter, so no
+        // one can plant binaries in `bin` ahead of the execution
+        let scratch = tempfile::Builder::new()
+            .prefix("toka-shell-")
+            .permissions(std::os::unix::fs::PermissionsExt::from_mode(0o700))
+            .tempdir()?;
+        let bin = scratch.path().join("bin");
+        self.link_binaries(&bin)?;
+        let working_dir = match &self.config.working_dir {
+            Some(dir) if held.contains(&Capability::FileSystem) => dir.clone(),
+            _ => {
+                let dir = scratch.path().join("work");
+                std::fs::create_dir(&dir)?;
+                dir
+            }
+        };
+
+        let shell = self.shell()?;
+        let network = self.config.allow_network && held.contains(&Capability::Network);
+        let mut command = Command::new(&shell);
+        command
+            .args(["--restricted", "--noprofile", "--norc", "-c"])
+            .arg(&request.code)
+            .current_dir(&working_dir)
+            .env_clear()
+            .process_group(0);
+        if let Some(environment) = &request.environment {
+            command.envs(environment.iter().filter(|(name, _)| {
+                !BLOCKED_VARIABLES.contains(&name.as_str()) && !name.starts_with("BASH_FUNC_")
+            }));
+        }
+        command
+            .env("PATH", &bin)
+            .env("HOME", &working_dir)
+            .env("TOKA_INPUTS", serde_json::to_string(&request.inputs)?);
+        let sandbox = SandboxPolicy::for_level(request.security_level)
+            .with_network(network)
+            .apply(&mut command)?;
+        if !network && !sandbox.network_namespace {
+            anyhow::bail!("Shell execution without network requires network namespaces");
+        }
+
+        let options = RunOptions {
+            group: true,
+            ..RunOptions::new(timeout, self.config.max_output_bytes).in_context(context)
+        };
+        let outcome = process::run_collecting(command, None, options).await?;
+        if let Err(e) = scratch.close() {
+            tracing::warn!("Failed to remove shell scratch directory: {}", e);
+        }
+
+        let stopped = outcome.stop_reason(timeout);
+        let duration = start.elapsed();
+        let mut resource_usage = RuntimeResourceUsage {
+            peak_memory_mb: 0,
+            cpu_time_ms: duration.as_millis() as u64,
+            syscall_count: 0,
+            files_accessed: Vec::new(),
+            network_attempts: 0,
+        };
+        sandbox.record_usage(&mut resource_usage);
+        Ok(ExecutionResult {
+            success: outcome.status.is_some_and(|status| status.success()),
+            output: outcome.stdout,
+            error: outcome.stderr,
+            exit_code: outcome.status.and_then(|status| status.code()),
+            metadata: RuntimeMetadata {
+                code_type: CodeType::Shell,
+                session_id: request.session_id.clone(),
+                duration,
+                resource_usage,
+                security_level: request.security_level,
+                engine_version: "bash".to_string(),
+                executed_at: SystemTime::now(),
+            },
+            artifacts: Vec::new(),
+            stopped,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl ExecutionEngine for ShellEngine {
+    fn metadata(&self) -> EngineMetadata {
+        EngineMetadata {
+            name: "shell".to_string(),
+            version: "bash".to_string(),
+            code_type: CodeType::Shell,
+            description: "Restricted bash with allow-listed binaries and no network".to_string(),
+            supported_features: vec![
+                "timeout".to_string(),
+                "binary_allow_list".to_string(),
+                "working_dir_jail".to_string(),
+                "network_isolation".to_string(),
+            ],
+        }
+    }
+
+    async fn validate_code(&self, code: &str) -> Result<()> {
+        if code.len() > MAX_SCRIPT_BYTES {
+            anyhow::bail!("Shell script exceeds {} bytes", MAX_SCRIPT_BYTES);
+        }
+        let mut command = Command::new(self.shell()?);
+        command.args(["--noprofile", "--norc", "-n", "-c"]).arg(code).env_clear();
+        let options = RunOptions::new(self.config.timeout, self.config.max_output_bytes);
+        let outcome = process::run_collecting(command, None, options).await?;
+        if !outcome.status.is_some_and(|status| status.success()) {
+            let reason = outcome.stderr.lines().next().unwrap_or("syntax check failed").to_string();
+            anyhow::bail!("Invalid shell script: {}", reason);
+        }
+        Ok(())
+    }
+
+    async fn execute(
+        &self,
+        context: &ExecutionContext,
+        request: &ExecutionRequest,
+        _kernel: &ToolKernel,
+    ) -> Result<ExecutionResult> {
+        self.run(context, request).await
+    }
+
+    fn supports_capabilities(&self, capabilities: &CapabilitySet) -> bool {
+        capabilities.capabilities.iter().all(|capability| match capability {
+            Capability::Process | Capability::FileSystem => true,
+            Capability::Network => self.config.allow_network,
+            Capability::CodeGeneration => false,
+        })
+    }
+
+    fn required_capabilities(&self) -> CapabilitySet {
+        let mut capabilities = vec![Capability::Process];
+        if self.config.working_dir.is_some() {
+            capabilities.push(Capability::FileSystem);
+        }
+        if self.config.allow_network {
+            capabilities.push(Capability::Network);
+        }
+        CapabilitySet::with_capabilities(capabilities)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{OutputSink, OutputStream, SecurityLevel, StopReason};
+    use std::collections::HashMap;
+    use tokio_util::sync::CancellationToken;
+
+    fn context(capabilities: Vec<Capability>) -> ExecutionContext {
+        ExecutionContext {
+            session_id: "test".to_string(),
+            security_level: SecurityLevel::Restricted,
+            capabilities: CapabilitySet::with_capabilities(capabilities),
+            cached_artifact: None,
+            cancellation: CancellationToken::new(),
+            output: None,
+        }
+    }
+
+    fn request(code: &str) -> ExecutionRequest {
+        ExecutionRequest {
+            code_type: CodeType::Shell,
+            code: code.to_string(),
+            session_id: "test".to_string(),
+            security_level: SecurityLevel::Restricted,
+            inputs: serde_json::json!({"name": "toka"}),
+            timeout_override: None,
+            environment: None,
+            agent: None,
+            correlation_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_allowed_binaries_and_inputs() {
+        let engine = ShellEngine::new();
+        let mut script = request("echo \"$TOKA_INPUTS\" | tr -d '{}'; echo \"$GREETING\"; exit 4");
+        script.environment = Some(HashMap::from([
+            ("GREETING".to_string(), "hi".to_string()),
+            ("PATH".to_string(), "/usr/bin:/bin".to_string()),
+        ]));
+        let result = engine.run(&context(vec![Capability::Process]), &script).await.unwrap();
+
+        assert_eq!(result.output, "\"name\":\"toka\"\nhi\n");
+        assert_eq!(result.exit_code, Some(4));
+        assert!(!result.success);
+
+        // Neither binaries off the list nor absolute paths run
+        for script in ["id", "/usr/bin/id", "PATH=/usr/bin; id"] {
+            let result = engine.run(&context(vec![Capability::Process]), &request(script)).await.unwrap();
+            assert!(!result.success, "{} ran: {}", script, result.output);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_loader_variables_are_refused() {
+        let engine = ShellEngine::new();
+        for name in ["LD_PRELOAD", "LD_LIBRARY_PATH", "LD_AUDIT", "GCONV_PATH"] {
+            let mut script = request("echo ran");
+            script.environment = Some(HashMap::from([(name.to_string(), "/tmp/evil.so".to_string())]));
+            let error = engine.run(&context(vec![Capability::Process]), &script).await.unwrap_err();
+            assert!(error.to_string().contains(name), "{}: {}", name, error);
+        }
+    }
+
+    /// Programs allowed binaries could start, with the script reaching them.
+    const EXEC_ESCAPES: &[(&str, &str)] = &[
+        ("awk", "awk 'BEGIN { system(ENVIRON[\"PROBE\"]) }'"),
+        ("sed", "echo | sed \"1e $PROBE\""),
+        ("sort", "printf '%s\\n' {1..100000} | sort -S 1K --compress-program=\"$PROBE\""),
+    ];
+
+    /// Escape attempt through each default binary.
+    const DEFAULT_ESCAPES: &[(&str, &str)] = &[
+        ("basename", "basename \"$PROBE\""),
+        ("cat", "cat \"$PROBE\""),
+        ("cut", "cut -c1- \"$PROBE\""),
+        ("date", "date -f \"$PROBE\""),
+        ("dirname", "dirname \"$PROBE\""),
+        ("grep", "grep -f \"$PROBE\" \"$PROBE\""),
+        ("head", "head \"$PROBE\""),
+        ("ls", "ls \"$PROBE\""),
+        ("tail", "tail --pid=1 \"$PROBE\""),
+        ("tr", "tr a b < \"$PROBE\""),
+        ("uniq", "uniq \"$PROBE\""),
+        ("wc", "wc \"$PROBE\""),
+    ];
+
+    /// Script that creates `marker` when run.
+    fn probe(dir: &Path, marker: &Path) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let probe = dir.join("probe");
+        std::fs::write(&probe, format!("#!/bin/sh\n/usr/bin/touch {}\nexec /bin/cat\n", marker.display())).unwrap();
+        std::fs::set_permissions(&probe, std::fs::Permissions::from_mode(0o755)).unwrap();
+        probe
+    }
+
+    #[tokio::test]
+    async fn test_default_binaries_cannot_start_programs() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("escaped");
+        let probe = probe(dir.path(), &marker);
+        let run = |engine: ShellEngine, script: &str| {
+            let mut script = request(script);
+            script.environment = Some(HashMap::from([("PROBE".to_string(), probe.display().to_string())]));
+            async move { engine.run(&context(vec![Capability::Process]), &script).await.unwrap() }
+        };
+
+        // Each escape works once its binary is allowed
+        for (binary, script) in EXEC_ESCAPES {
+            let engine = ShellEngine::with_config(ShellEngineConfig {
+                allowed_binaries: vec![binary.to_string()],
+                ..ShellEngineConfig::default()
+            });
+            run(engine, script).await;
+            assert!(marker.exists(), "{} did not start the probe", binary);
+            std::fs::remove_file(&marker).unwrap();
+        }
+
+        let defaults = ShellEngineConfig::default().allowed_binaries;
+        let mut audited: Vec<&str> = DEFAULT_ESCAPES.iter().map(|(binary, _)| *binary).collect();
+        audited.sort_unstable();
+        assert_eq!(defaults, audited, "every default binary needs an escape attempt");
+        for (binary, script) in DEFAULT_ESCAPES.iter().chain(EXEC_ESCAPES) {
+            run(ShellEngine::new(), script).await;
+            assert!(!marker.exists(), "{} started the probe", binary);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_working_directory_jail_and_capabilities() {
+        let shared = tempfile::tempdir().unwrap();
+        std::fs::write(shared.path().join("shared.txt"), "data").unwrap();
+        let engine = ShellEngine::with_config(ShellEngineConfig {
+            working_dir: Some(shared.path().to_path_buf()),
+            ..ShellEngineConfig::default()
+        });
+
+        let granted = engine
+            .run(&context(vec![Capability::Process, Capability::FileSystem]), &request("ls; cd /"))
+            .await
+            .unwrap();
+        assert_eq!(granted.output, "shared.txt\n");
+        assert!(granted.error.contains("restricted"), "{}", granted.error);
+
+        let scratch = engine.run(&context(vec![Capability::Process]), &request("ls")).await.unwrap();
+        assert!(scratch.success, "{}", scratch.error);
+        assert_eq!(scratch.output, "");
+
+        assert!(engine.run(&context(Vec::new()), &request("true")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scratch_directory_is_private() {
+        let engine = ShellEngine::new();
+        let result = engine.run(&context(vec![Capability::Process]), &request("ls -ld ..")).await.unwrap();
+
+        assert!(result.success, "{}", result.error);
+        assert!(result.output.starts_with("drwx------"), "{}", result.output);
+    }
+
+    #[tokio::test]
+    async fn test_network_is_isolated() {
+        let engine = ShellEngine::new();
+        let result = engine
+            .run(&context(vec![Capability::Process]), &request("echo $(wc -l < /proc/net/dev)"))
+            .await
+            .unwrap();
+
+        // Only the loopback interface below the two header lines
+        assert!(result.success, "{}", result.error);
+        assert_eq!(result.output, "3\n");
+    }
+
+    #[tokio::test]
+    async fn test_timeout_kills_background_jobs() {
+        let engine = ShellEngine::with_config(ShellEngineConfig {
+            allowed_binaries: vec!["sleep".to_string()],
+            ..ShellEngineConfig::default()
+        });
+        let mut script = request("sleep 30 & echo started; sleep 30");
+        script.timeout_override = Some(Duration::from_millis(500));
+        let result = engine.run(&context(vec![Capability::Process]), &script).await.unwrap();
+
+        assert!(!result.success);
+        assert_eq!(result.output, "started\n");
+        assert_eq!(result.stopped, Some(StopReason::TimedOut(Duration::from_millis(500))));
+        assert!(result.metadata.duration < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_kills_script() {
+        let engine = ShellEngine::with_config(ShellEngineConfig {
+            allowed_binaries: vec!["sleep".to_string()],
+            ..ShellEngineConfig::default()
+        });
+        let context = context(vec![Capability::Process]);
+        let cancellation = context.cancellation.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            cancellation.cancel();
+        });
+        let result = engine.run(&context, &request("sleep 30 & echo started; wait")).await.unwrap();
+
+        assert!(!result.success);
+        assert_eq!(result.output, "started\n");
+        assert_eq!(result.stopped, Some(StopReason::Cancelled));
+        assert!(result.metadata.duration < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_output_streams_while_running() {
+        let engine = ShellEngine::with_config(ShellEngineConfig {
+            allowed_binaries: vec!["sleep".to_string()],
+            ..ShellEngineConfig::default()
+        });
+        let (sink, mut chunks) = OutputSink::channel();
+        let mut context = context(vec![Capability::Process]);
+        context.output = Some(sink);
+
+        let script = request("echo one; sleep 1; echo two >&2");
+        let execution = engine.run(&context, &script);
+        tokio::pin!(execution);
+        let first = tokio::select! {
+            chunk = chunks.recv() => chunk.unwrap(),
+            _ = &mut execution => panic!("execution finished before streaming output"),
+        };
+        assert_eq!((first.stream, first.data.as_str()), (OutputStream::Stdout, "one\n"));
+
+        let result = execution.await.unwrap();
+        let second = chunks.recv().await.unwrap();
+        assert_eq!((second.stream, second.data.as_str()), (OutputStream::Stderr, "two\n"));
+        assert_eq!(result.output, "one\n");
+    }
+
+    #[tokio::test]
+    async fn test_validate_code() {
+        let engine = ShellEngine::new();
+        assert!(engine.validate_code("echo ok").await.is_ok());
+        assert!(engine.validate_code("if true; then").await.is_err());
+    }
+}
@@ -0,0 +1,549 @@
+//! WebAssembly engine running WASI command modules on wasmtime.
+//!
+//! Code is a module in the WebAssembly text format exporting `_start`, as
+//! produced by any WASI command toolchain. Every execution instantiates the
+//! module in a fresh store with:
+//!
+//! - **Fuel metering**: each instruction burns fuel, so runaway modules trap
+//!   once [`WasmEngineConfig::fuel`] is used up.
+//! - **Memory limits**: linear memory may not grow past the limit of the
+//!   request's [`SecurityLevel`], see [`memory_limit_bytes`].
+//! - **Scoped WASI**: the module reads its inputs as JSON from stdin and sees
+//!   the request's environment variables. Directories are only preopened
+//!   when the execution context holds the `FileSystem` capability, and stay
+//!   read-only from [`SecurityLevel::High`] up.
+//! - **Cancellation**: cancelling [`ExecutionContext::cancellation`]
+//!   interrupts the module through wasmtime's epoch interruption.
+//!
+//! Compiled modules are serialized next to the other cached modules and
+//! returned as a [`WASM_MODULE_ARTIFACT_TYPE`] artifact, which the runtime
+//! keeps in its code cache. Executions of the same code hand the artifact
+//! back in [`ExecutionContext::cached_artifact`] and skip compilation.
+
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime};
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use tokio_util::sync::{CancellationToken, DropGuard};
+use wasmtime::{Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, Trap, UpdateDeadline};
+use wasmtime_wasi::pipe::{MemoryInputPipe, MemoryOutputPipe};
+use wasmtime_wasi::preview1::WasiP1Ctx;
+use wasmtime_wasi::{DirPerms, FilePerms, I32Exit, WasiCtxBuilder};
+
+use crate::{
+    Artifact, Capability, CapabilitySet, CodeType, EngineMetadata, ExecutionContext, ExecutionEngine,
+    ExecutionRequest, ExecutionResult, RuntimeMetadata, RuntimeResourceUsage, SecurityLevel, StopReason,
+    ToolKernel,
+};
+
+/// Artifact type of a serialized, compiled module.
+pub const WASM_MODULE_ARTIFACT_TYPE: &str = "wasm-module";
+
+/// Major version of the wasmtime runtime compiled in.
+const WASMTIME_VERSION: &str = "29";
+
+/// Size of a WebAssembly page.
+const PAGE_SIZE: u64 = 64 * 1024;
+
+/// Linear memory a module may use at `level`.
+pub fn memory_limit_bytes(level: SecurityLevel) -> usize {
+    const MIB: usize = 1024 * 1024;
+    match level {
+        SecurityLevel::Low => 512 * MIB,
+        SecurityLevel::Medium => 256 * MIB,
+        SecurityLevel::High => 128 * MIB,
+        SecurityLevel::Restricted => 64 * MIB,
+    }
+}
+
+/// Host directory made available to modules.
+#[derive(Debug, Clone)]
+pub struct WasmPreopen {
+    /// Directory on the host
+    pub host: PathBuf,
+    /// Path the module opens it under
+    pub guest: String,
+    /// Whether modules below [`SecurityLevel::High`] may write to it
+    pub writable: bool,
+}
+
+/// Limits and WASI setup applied to every WebAssembly execution.
+#[derive(Debug, Clone)]
+pub struct WasmEngineConfig {
+    /// Fuel available to each execution
+    pub fuel: u64,
+    /// Maximum bytes kept of stdout and of stderr
+    pub max_output_bytes: usize,
+    /// Directories preopened for executions holding the `FileSystem` capability
+    pub preopens: Vec<WasmPreopen>,
+    /// Directory compiled modules are serialized to
+    pub module_dir: PathBuf,
+}
+
+impl Default for WasmEngineConfig {
+    fn default() -> Self {
+        Self {
+            fuel: 1_000_000_000,
+            max_output_bytes: 16 * 1024 * 1024,
+            preopens: Vec::new(),
+            module_dir: std::env::temp_dir().join("toka-wasm-modules"),
+        }
+    }
+}
+
+/// Per-execution store state.
+struct WasmState {
+    wasi: WasiP1Ctx,
+    limits: StoreLimits,
+}
+
+/// Execution engine running WASI modules on wasmtime.
+#[derive(Clone)]
+pub struct WasmEngine {
+    engine: Engine,
+    config: WasmEngineConfig,
+}
+
+impl std::fmt::Debug for WasmEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmEngine").field("config", &self.config).finish()
+    }
+}
+
+impl WasmEngine {
+    /// Create an engine with default limits.
+    pub fn new() -> Result<Self> {
+        Self::with_config(WasmEngineConfig::default())
+    }
+
+    /// Create an engine with custom limits.
+    pub fn with_config(config: WasmEngineConfig) -> Result<Self> {
+        let mut wasmtime_config = Config::new();
+        wasmtime_config.consume_fuel(true).epoch_interruption(true);
+        let engine = Engine::new(&wasmtime_config)?;
+        Ok(Self { engine, config })
+    }
+
+    /// Engine limits.
+    pub fn config(&self) -> &WasmEngineConfig {
+        &self.config
+    }
+
+    /// Interrupt running modules of executions once `cancellation` is
+    /// cancelled, until the returned guard is dropped.
+    ///
+    /// The epoch is shared by every execution on the engine; each store
+    /// checks its own cancellation when the epoch advances.
+    fn interrupt_on_cancel(&self, cancellation: &CancellationToken) -> DropGuard {
+        let finished = CancellationToken::new();
+        let (engine, cancellation, done) = (self.engine.clone(), cancellation.clone(), finished.clone());
+        tokio::spawn(async move {
+            tokio::select! {
+                biased;
+                _ = cancellation.cancelled() => engine.increment_epoch(),
+                _ = done.cancelled() => {}
+            }
+        });
+        finished.drop_guard()
+    }
+
+    /// Module for `code`, from the cached artifact when it is still valid.
+    ///
+    /// Returns the artifact describing the serialized module.
+    fn load_module(&self, code: &str, cached: Option<&Artifact>) -> Result<(Module, Artifact)> {
+        if let Some(artifact) = cached.filter(|artifact| artifact.artifact_type == WASM_MODULE_ARTIFACT_TYPE) {
+            match self.deserialize(artifact) {
+                Ok(module) => return Ok((module, artifact.clone())),
+                Err(e) => tracing::debug!("Recompiling cached module {}: {}", artifact.path, e),
+            }
+        }
+
+        let binary = wat::parse_str(code)?;
+        let module = Module::new(&self.engine, &binary)?;
+        let serialized = module.serialize()?;
+        std::fs::create_dir_all(&self.config.module_dir)?;
+        let path = self
+            .config
+            .module_dir
+            .join(format!("{:x}.cwasm", Sha256::digest(code.as_bytes())));
+        std::fs::write(&path, &serialized)?;
+
+        let artifact = Artifact {
+            artifact_type: WASM_MODULE_ARTIFACT_TYPE.to_string(),
+            path: path.display().to_string(),
+            size_bytes: serialized.len() as u64,
+            checksum: format!("{:x}", Sha256::digest(&serialized)),
+        };
+        Ok((module, artifact))
+    }
+
+    /// Load a module this engine serialized earlier.
+    fn deserialize(&self, artifact: &Artifact) -> Result<Module> {
+        let path = Path::new(&artifact.path);
+        let serialized = std::fs::read(path)?;
+        if format!("{:x}", Sha256::digest(&serialized)) != artifact.checksum {
+            anyhow::bail!("checksum mismatch");
+        }
+        // SAFETY: the bytes were produced by `Module::serialize` of this
+        // engine, which the checksum recorded at that time confirms.
+        // Modules from an incompatible engine are rejected by wasmtime.
+        unsafe { Module::deserialize(&self.engine, &serialized) }
+    }
+
+    /// WASI context of an execution, scoped by its capabilities.
+    fn wasi(
+        &self,
+        context: &ExecutionContext,
+        request: &ExecutionRequest,
+        stdout: &MemoryOutputPipe,
+        stderr: &MemoryOutputPipe,
+    ) -> Result<WasiP1Ctx> {
+        let mut wasi = WasiCtxBuilder::new();
+        wasi.args(&["tool"])
+            .stdin(MemoryInputPipe::new(serde_json::to_vec(&request.inputs)?))
+            .stdout(stdout.clone())
+            .stderr(stderr.clone());
+        if let Some(environment) = &request.environment {
+            let vars: Vec<(&String, &String)> = environment.iter().collect();
+            wasi.envs(&vars);
+        }
+
+        if context.capabilities.capabilities.contains(&Capability::FileSystem) {
+            let may_write = matches!(context.security_level, SecurityLevel::Low | SecurityLevel::Medium);
+            for preopen in &self.config.preopens {
+                let (dir_perms, file_perms) = if preopen.writable && may_write {
+                    (DirPerms::all(), FilePerms::all())
+                } else {
+                    (DirPerms::READ, FilePerms::READ)
+                };
+                wasi.preopened_dir(&preopen.host, &preopen.guest, dir_perms, file_perms)
+                    .with_context(|| format!("Failed to preopen {}", preopen.host.display()))?;
+            }
+        }
+        Ok(wasi.build_p1())
+    }
+
+    /// Run a request to completion on the calling thread.
+    fn run(&self, context: &ExecutionContext, request: &ExecutionRequest) -> Result<ExecutionResult> {
+        let start = Instant::now();
+        let (module, artifact) = self.load_module(&request.code, context.cached_artifact.as_ref())?;
+
+        let stdout = MemoryOutputPipe::new(self.config.max_output_bytes);
+        let stderr = MemoryOutputPipe::new(self.config.max_output_bytes);
+        let state = WasmState {
+            wasi: self.wasi(context, request, &stdout, &stderr)?,
+            limits: StoreLimitsBuilder::new()
+                .memory_size(memory_limit_bytes(request.security_level))
+                .build(),
+        };
+        let mut store = Store::new(&self.engine, state);
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(self.config.fuel)?;
+        let cancellation = context.cancellation.clone();
+        store.epoch_deadline_callback(move |_| {
+            if cancellation.is_cancelled() {
+                Err(Trap::Interrupt.into())
+            } else {
+                Ok(UpdateDeadline::Continue(1))
+            }
+        });
+        store.set_epoch_deadline(1);
+
+        let mut linker: Linker<WasmState> = Linker::new(&self.engine);
+        wasmtime_wasi::preview1::add_to_linker_sync(&mut linker, |state| &mut state.wasi)?;
+
+        let outcome = linker
+            .instantiate(&mut store, &module)
+            .and_then(|instance| {
+                let memory = instance.get_memory(&mut store, "memory");
+                let run = instance.get_typed_func::<(), ()>(&mut store, "_start")?.call(&mut store, ());
+                let memory_bytes = memory.map_or(0, |memory| memory.size(&store) * PAGE_SIZE);
+                Ok((run, memory_bytes))
+            });
+
+        let mut stopped = None;
+        let (exit_code, trap, memory_bytes) = match outcome {
+            Ok((Ok(()), memory_bytes)) => (Some(0), None, memory_bytes),
+            Ok((Err(e), memory_bytes)) => match e.downcast_ref::<I32Exit>() {
+                Some(exit) => (Some(exit.0), None, memory_bytes),
+                None if matches!(e.downcast_ref::<Trap>(), Some(Trap::Interrupt)) => {
+                    stopped = Some(StopReason::Cancelled);
+                    (None, None, memory_bytes)
+                }
+                None => (None, Some(describe_trap(&e)), memory_bytes),
+            },
+            Err(e) => (None, Some(format!("instantiation failed: {:#}", e)), 0),
+        };
+        let fuel_used = self.config.fuel - store.get_fuel().unwrap_or(0);
+        drop(store);
+
+        let mut error = String::from_utf8_lossy(&stderr.contents()).into_owned();
+        if let Some(trap) = trap {
+            error.push_str(&trap);
+        }
+        tracing::debug!("WebAssembly execution used {} fuel", fuel_used);
+
+        let duration = start.elapsed();
+        Ok(ExecutionResult {
+            success: exit_code == Some(0),
+            output: String::from_utf8_lossy(&stdout.contents()).into_owned(),
+            error,
+            exit_code,
+            metadata: RuntimeMetadata {
+                code_type: CodeType::WebAssembly,
+                session_id: request.session_id.clone(),
+                duration,
+                resource_usage: RuntimeResourceUsage {
+                    peak_memory_mb: memory_bytes / (1024 * 1024),
+                    cpu_time_ms: duration.as_millis() as u64,
+                    syscall_count: 0,
+                    files_accessed: Vec::new(),
+                    network_attempts: 0,
+                },
+                security_level: request.security_level,
+                engine_version: format!("wasmtime {}", WASMTIME_VERSION),
+                executed_at: SystemTime::now(),
+            },
+            artifacts: vec![artifact],
+            stopped,
+        })
+    }
+}
+
+/// Human-readable reason a module stopped.
+fn describe_trap(error: &anyhow::Error) -> String {
+    match error.downcast_ref::<Trap>() {
+        Some(Trap::OutOfFuel) => "fuel exhausted".to_string(),
+        _ => format!("{:#}", error),
+    }
+}
+
+#[async_trait::async_trait]
+impl ExecutionEngine for WasmEngine {
+    fn metadata(&self) -> EngineMetadata {
+        EngineMetadata {
+            name: "wasm".to_string(),
+            version: WASMTIME_VERSION.to_string(),
+            code_type: CodeType::WebAssembly,
+            description: "WASI modules on wasmtime with fuel and memory limits".to_string(),
+            supported_features: vec![
+                "fuel_metering".to_string(),
+                "memory_limit".to_string(),
+                "wasi_preview1".to_string(),
+                "module_cache".to_string(),
+            ],
+        }
+    }
+
+    async fn validate_code(&self, code: &str) -> Result<()> {
+        let binary = wat::parse_str(code).map_err(|e| anyhow::anyhow!("Invalid WebAssembly text: {}", e))?;
+        Module::validate(&self.engine, &binary).map_err(|e| anyhow::anyhow!("Invalid WebAssembly module: {}", e))
+    }
+
+    async fn execute(
+        &self,
+        context: &ExecutionContext,
+        request: &ExecutionRequest,
+        _kernel: &ToolKernel,
+    ) -> Result<ExecutionResult> {
+        // Compilation and execution are synchronous; keep them off the async worker threads
+        let engine = self.clone();
+        let context = context.clone();
+        let request = request.clone();
+        let _interrupt = self.interrupt_on_cancel(&context.cancellation);
+        tokio::task::spawn_blocking(move || engine.run(&context, &request)).await?
+    }
+
+    fn supports_capabilities(&self, capabilities: &CapabilitySet) -> bool {
+        // Only preopened directories can be granted to a module
+        capabilities
+            .capabilities
+            .iter()
+            .all(|capability| *capability == Capability::FileSystem)
+    }
+
+    fn required_capabilities(&self) -> CapabilitySet {
+        if self.config.preopens.is_empty() {
+            CapabilitySet::with_capabilities(Vec::new())
+        } else {
+            CapabilitySet::with_capabilities(vec![Capability::FileSystem])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Writes its input back, then exits with the code of the first input byte.
+    const ECHO: &str = r#"
+        (module
+          (import "wasi_snapshot_preview1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+          (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+          (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+          (memory (export "memory") 1)
+          (func (export "_start")
+            (i32.store (i32.const 0) (i32.const 64))
+            (i32.store (i32.const 4) (i32.const 1024))
+            (drop (call $fd_read (i32.const 0) (i32.const 0) (i32.const 1) (i32.const 8)))
+            (i32.store (i32.const 4) (i32.load (i32.const 8)))
+            (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))
+            (call $proc_exit (i32.sub (i32.load8_u (i32.const 64)) (i32.const 48)))))
+    "#;
+
+    fn context(capabilities: Vec<Capability>, cached_artifact: Option<Artifact>) -> ExecutionContext {
+        ExecutionContext {
+            session_id: "test".to_string(),
+            security_level: SecurityLevel::Restricted,
+            capabilities: CapabilitySet::with_capabilities(capabilities),
+            cached_artifact,
+            cancellation: CancellationToken::new(),
+            output: None,
+        }
+    }
+
+    fn request(code: &str, inputs: serde_json::Value) -> ExecutionRequest {
+        ExecutionRequest {
+            code_type: CodeType::WebAssembly,
+            code: code.to_string(),
+            session_id: "test".to_string(),
+            security_level: SecurityLevel::Restricted,
+            inputs,
+            timeout_override: None,
+            environment: None,
+            agent: None,
+            correlation_id: None,
+        }
+    }
+
+    fn engine(module_dir: &Path) -> WasmEngine {
+        WasmEngine::with_config(WasmEngineConfig {
+            fuel: 1_000_000,
+            module_dir: module_dir.to_path_buf(),
+            ..WasmEngineConfig::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_inputs_output_and_exit_code() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = engine(dir.path());
+
+        let result = engine.run(&context(Vec::new(), None), &request(ECHO, serde_json::json!(3))).unwrap();
+        assert_eq!(result.output, "3");
+        assert_eq!(result.exit_code, Some(3));
+        assert!(!result.success);
+        assert_eq!(result.artifacts[0].artifact_type, WASM_MODULE_ARTIFACT_TYPE);
+
+        let result = engine.run(&context(Vec::new(), None), &request(ECHO, serde_json::json!(0))).unwrap();
+        assert!(result.success, "{}", result.error);
+    }
+
+    #[test]
+    fn test_fuel_and_memory_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = engine(dir.path());
+
+        let spin = r#"(module (func (export "_start") (loop (br 0))))"#;
+        let result = engine.run(&context(Vec::new(), None), &request(spin, serde_json::json!({}))).unwrap();
+        assert!(!result.success);
+        assert!(result.error.contains("fuel exhausted"), "{}", result.error);
+
+        // 2048 pages are 128 MiB, over the limit of restricted executions
+        let hog = r#"(module (memory 2048) (func (export "_start")))"#;
+        let result = engine.run(&context(Vec::new(), None), &request(hog, serde_json::json!({}))).unwrap();
+        assert!(!result.success);
+        assert!(result.error.contains("instantiation failed"), "{}", result.error);
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_interrupts_module() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = WasmEngine::with_config(WasmEngineConfig {
+            fuel: u64::MAX,
+            module_dir: dir.path().to_path_buf(),
+            ..WasmEngineConfig::default()
+        })
+        .unwrap();
+        let context = context(Vec::new(), None);
+        let _interrupt = engine.interrupt_on_cancel(&context.cancellation);
+        let cancellation = context.cancellation.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            cancellation.cancel();
+        });
+
+        let spin = request(r#"(module (func (export "_start") (loop (br 0))))"#, serde_json::json!({}));
+        let result = tokio::task::spawn_blocking(move || engine.run(&context, &spin)).await.unwrap().unwrap();
+        assert!(!result.success);
+        assert_eq!(result.stopped, Some(StopReason::Cancelled));
+        assert!(result.metadata.duration < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_cached_module_is_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = engine(dir.path());
+
+        let first = engine.run(&context(Vec::new(), None), &request(ECHO, serde_json::json!(0))).unwrap();
+        let artifact = first.artifacts[0].clone();
+        assert!(Path::new(&artifact.path).exists());
+
+        // A cached module is used even when the code would no longer compile
+        let cached = context(Vec::new(), Some(artifact.clone()));
+        let result = engine.run(&cached, &request("(module", serde_json::json!(0))).unwrap();
+        assert!(result.success, "{}", result.error);
+        assert_eq!(result.artifacts[0].checksum, artifact.checksum);
+
+        // A tampered module is recompiled
+        std::fs::write(&artifact.path, b"tampered").unwrap();
+        let result = engine.run(&cached, &request(ECHO, serde_json::json!(0))).unwrap();
+        assert!(result.success, "{}", result.error);
+        assert_ne!(result.artifacts[0].checksum, "tampered");
+    }
+
+    #[test]
+    fn test_preopens_require_filesystem_capability() {
+        let modules = tempfile::tempdir().unwrap();
+        let shared = tempfile::tempdir().unwrap();
+        let engine = WasmEngine::with_config(WasmEngineConfig {
+            preopens: vec![WasmPreopen {
+                host: shared.path().to_path_buf(),
+                guest: "/data".to_string(),
+                writable: false,
+            }],
+            module_dir: modules.path().to_path_buf(),
+            ..WasmEngineConfig::default()
+        })
+        .unwrap();
+        assert_eq!(engine.required_capabilities().capabilities, vec![Capability::FileSystem]);
+
+        // Exits with the errno of fd_prestat_get on the first preopen slot
+        let probe = r#"
+            (module
+              (import "wasi_snapshot_preview1" "fd_prestat_get" (func $prestat (param i32 i32) (result i32)))
+              (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+              (memory (export "memory") 1)
+              (func (export "_start") (call $proc_exit (call $prestat (i32.const 3) (i32.const 0)))))
+        "#;
+        let granted = engine
+            .run(&context(vec![Capability::FileSystem], None), &request(probe, serde_json::json!({})))
+            .unwrap();
+        assert_eq!(granted.exit_code, Some(0));
+
+        let denied = engine.run(&context(Vec::new(), None), &request(probe, serde_json::json!({}))).unwrap();
+        assert_ne!(denied.exit_code, Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_validate_code() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = engine(dir.path());
+        assert!(engine.validate_code(ECHO).await.is_ok());
+        assert!(engine.validate_code("(module (func (result i32)))").await.is_err());
+        assert!(engine.validate_code("(module").await.is_err());
+    }
+}
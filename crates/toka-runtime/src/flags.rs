@@ -0,0 +1,527 @@
+//! Feature flags for gradual rollout of risky capabilities.
+//!
+//! A flag is a named switch, e.g. `engine.python` or `tool.http-fetch`. Its
+//! state is the fold of the [`FlagEvent`]s recorded for it: rules set per
+//! [`FlagScope`], plus a kill switch that turns the flag off everywhere
+//! regardless of rules. Rules are evaluated against a [`FlagContext`]; the
+//! most specific matching scope wins (session, then agent, then tenant, then
+//! global), and percentage rules bucket callers deterministically so a
+//! caller keeps its answer while a rollout widens.
+//!
+//! Events are kept in a [`FlagStore`]. [`InMemoryFlagStore`] suits a single
+//! process; [`StorageFlagStore`] appends them to a shared event store.
+//! [`FeatureFlags`] caches folded flags for [`DEFAULT_FLAG_CACHE_TTL`], so a
+//! change made by another process is seen within that time, while changes
+//! made through the same [`FeatureFlags`] apply immediately.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::RwLock;
+
+use toka_store_core::{
+    create_event_header_with_format, decode_payload, EventHeader, IntentId, PayloadFormat, QueryableStorage,
+};
+use toka_types::traits::Claims;
+
+/// Event kind of flag changes in a [`StorageFlagStore`].
+pub const FLAG_EVENT_KIND: &str = "runtime.flag.change";
+
+/// How long [`FeatureFlags`] reuses a folded flag before reloading it.
+pub const DEFAULT_FLAG_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// Flag gating execution of a code type, e.g. `engine.python`.
+///
+/// Undefined engine flags leave the engine enabled.
+pub fn engine_flag(code_type: &crate::CodeType) -> String {
+    format!("engine.{}", code_type.engine_key())
+}
+
+/// Flag gating execution of a tool, e.g. `tool.file-reader`.
+///
+/// Undefined tool flags leave the tool enabled.
+pub fn tool_flag(tool_name: &str) -> String {
+    format!("tool.{}", tool_name)
+}
+
+/// Who a rule applies to, from least to most specific.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(tag = "scope", content = "id", rename_all = "snake_case")]
+pub enum FlagScope {
+    /// Everyone
+    Global,
+    /// One tenant (the `vault` of a capability token)
+    Tenant(String),
+    /// One agent (the `sub` of a capability token)
+    Agent(String),
+    /// One runtime session
+    Session(String),
+}
+
+/// Value of a rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", content = "percent", rename_all = "snake_case")]
+pub enum FlagState {
+    /// Enabled for everyone in scope
+    On,
+    /// Disabled for everyone in scope
+    Off,
+    /// Enabled for this percentage (0–100) of callers in scope
+    Percentage(u8),
+}
+
+/// A recorded change to one flag.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum FlagChange {
+    /// Set the rule of a scope
+    Set {
+        /// Scope of the rule
+        scope: FlagScope,
+        /// New value
+        state: FlagState,
+    },
+    /// Remove the rule of a scope
+    Clear {
+        /// Scope of the rule
+        scope: FlagScope,
+    },
+    /// Disable the flag everywhere, overriding all rules
+    Kill,
+    /// Lift a previous [`FlagChange::Kill`], restoring the rules
+    Revive,
+}
+
+/// Change event stored for a flag.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlagEvent {
+    /// Flag name
+    pub flag: String,
+    /// What changed
+    pub change: FlagChange,
+    /// When the change was made
+    pub at: DateTime<Utc>,
+}
+
+impl FlagEvent {
+    /// Change `flag` now.
+    pub fn new(flag: impl Into<String>, change: FlagChange) -> Self {
+        Self {
+            flag: flag.into(),
+            change,
+            at: Utc::now(),
+        }
+    }
+}
+
+/// Identity a flag is evaluated for.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlagContext {
+    /// Runtime session
+    pub session: Option<String>,
+    /// Agent, usually the token subject
+    pub agent: Option<String>,
+    /// Tenant, usually the token vault
+    pub tenant: Option<String>,
+}
+
+impl FlagContext {
+    /// Context of a runtime session.
+    pub fn session(session: impl Into<String>) -> Self {
+        Self {
+            session: Some(session.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Context of the holder of a capability token.
+    pub fn from_claims(claims: &Claims) -> Self {
+        Self {
+            session: None,
+            agent: Some(claims.sub.clone()),
+            tenant: Some(claims.vault.clone()),
+        }
+    }
+
+    /// Also evaluate for `session`.
+    pub fn with_session(mut self, session: impl Into<String>) -> Self {
+        self.session = Some(session.into());
+        self
+    }
+
+    /// Whether a rule for `scope` applies to this context.
+    pub fn matches(&self, scope: &FlagScope) -> bool {
+        match scope {
+            FlagScope::Global => true,
+            FlagScope::Tenant(id) => self.tenant.as_ref() == Some(id),
+            FlagScope::Agent(id) => self.agent.as_ref() == Some(id),
+            FlagScope::Session(id) => self.session.as_ref() == Some(id),
+        }
+    }
+
+    /// Most specific identity, used to bucket percentage rollouts.
+    fn bucket_key(&self) -> &str {
+        self.session
+            .as_deref()
+            .or(self.agent.as_deref())
+            .or(self.tenant.as_deref())
+            .unwrap_or_default()
+    }
+}
+
+/// Current state of a flag, folded from its events.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlagDefinition {
+    /// Whether the kill switch is engaged
+    pub killed: bool,
+    /// Rules by scope
+    pub rules: BTreeMap<FlagScope, FlagState>,
+}
+
+impl FlagDefinition {
+    /// Fold `events`, oldest first.
+    pub fn from_events<'a>(events: impl IntoIterator<Item = &'a FlagEvent>) -> Self {
+        let mut definition = Self::default();
+        for event in events {
+            definition.apply(&event.change);
+        }
+        definition
+    }
+
+    /// Apply one change.
+    pub fn apply(&mut self, change: &FlagChange) {
+        match change {
+            FlagChange::Set { scope, state } => {
+                self.rules.insert(scope.clone(), *state);
+            }
+            FlagChange::Clear { scope } => {
+                self.rules.remove(scope);
+            }
+            FlagChange::Kill => self.killed = true,
+            FlagChange::Revive => self.killed = false,
+        }
+    }
+
+    /// Whether the flag has any rule or is killed.
+    pub fn is_defined(&self) -> bool {
+        self.killed || !self.rules.is_empty()
+    }
+
+    /// Evaluate `flag` for `context`; `default` applies when no rule matches.
+    pub fn evaluate(&self, flag: &str, context: &FlagContext, default: bool) -> bool {
+        if self.killed {
+            return false;
+        }
+        // Rules are ordered least specific first
+        let Some(state) = self
+            .rules
+            .iter()
+            .rev()
+            .find(|(scope, _)| context.matches(scope))
+            .map(|(_, state)| *state)
+        else {
+            return default;
+        };
+        match state {
+            FlagState::On => true,
+            FlagState::Off => false,
+            FlagState::Percentage(percent) => rollout_bucket(flag, context.bucket_key()) < u32::from(percent),
+        }
+    }
+}
+
+/// Stable bucket in `0..100` of `key` for `flag`.
+fn rollout_bucket(flag: &str, key: &str) -> u32 {
+    let digest = Sha256::digest(format!("{}/{}", flag, key).as_bytes());
+    u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) % 100
+}
+
+/// Error returned when a flag gates an operation off.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("feature flag '{flag}' is disabled")]
+pub struct FlagDisabled {
+    /// Flag that is off
+    pub flag: String,
+}
+
+/// Storage of flag change events.
+#[async_trait]
+pub trait FlagStore: Send + Sync {
+    /// Record a change.
+    async fn append(&self, event: FlagEvent) -> Result<()>;
+
+    /// Every change of `flag`, oldest first.
+    async fn events(&self, flag: &str) -> Result<Vec<FlagEvent>>;
+}
+
+/// Flag events kept in process memory.
+#[derive(Debug, Default)]
+pub struct InMemoryFlagStore {
+    events: RwLock<HashMap<String, Vec<FlagEvent>>>,
+}
+
+#[async_trait]
+impl FlagStore for InMemoryFlagStore {
+    async fn append(&self, event: FlagEvent) -> Result<()> {
+        self.events.write().await.entry(event.flag.clone()).or_default().push(event);
+        Ok(())
+    }
+
+    async fn events(&self, flag: &str) -> Result<Vec<FlagEvent>> {
+        Ok(self.events.read().await.get(flag).cloned().unwrap_or_default())
+    }
+}
+
+/// Intent holding the [`StorageFlagStore`] events of `flag`.
+pub fn flag_intent(flag: &str) -> IntentId {
+    let digest = Sha256::digest(format!("toka-runtime/flag/{}", flag).as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    IntentId::from_bytes(bytes)
+}
+
+/// Flag events kept in an event store shared by several processes.
+///
+/// Each flag's changes are a chain of [`FLAG_EVENT_KIND`] events under an
+/// intent derived from the flag name.
+pub struct StorageFlagStore<S: ?Sized> {
+    store: Arc<S>,
+}
+
+impl<S: QueryableStorage + ?Sized> StorageFlagStore<S> {
+    /// Keep flag events in `store`.
+    pub fn new(store: Arc<S>) -> Self {
+        Self { store }
+    }
+
+    /// Headers of the events of `intent`, oldest first.
+    async fn headers(&self, intent: &IntentId) -> Result<Vec<EventHeader>> {
+        let mut headers: Vec<EventHeader> = self.store.events_by_intent(intent).await?.try_collect().await?;
+        headers.retain(|header| header.kind == FLAG_EVENT_KIND);
+        headers.sort_by_key(|header| (header.timestamp, header.id));
+        Ok(headers)
+    }
+}
+
+#[async_trait]
+impl<S: QueryableStorage + ?Sized> FlagStore for StorageFlagStore<S> {
+    async fn append(&self, event: FlagEvent) -> Result<()> {
+        let intent = flag_intent(&event.flag);
+        let parents: Vec<EventHeader> = self.headers(&intent).await?.pop().into_iter().collect();
+        let (header, payload) = create_event_header_with_format(
+            &parents,
+            intent,
+            FLAG_EVENT_KIND.to_string(),
+            &event,
+            PayloadFormat::MessagePack,
+        )?;
+        self.store.commit(&header, &payload).await
+    }
+
+    async fn events(&self, flag: &str) -> Result<Vec<FlagEvent>> {
+        let mut events = Vec::new();
+        for header in self.headers(&flag_intent(flag)).await? {
+            let bytes = self
+                .store
+                .payload_bytes(&header.digest)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("flag event {} has no payload", header.id))?;
+            let event: FlagEvent = decode_payload(&header, &bytes)?;
+            // Guard against intent collisions between flag names
+            if event.flag == flag {
+                events.push(event);
+            }
+        }
+        Ok(events)
+    }
+}
+
+/// Cached flag evaluation on top of a [`FlagStore`].
+pub struct FeatureFlags {
+    store: Arc<dyn FlagStore>,
+    ttl: Duration,
+    cache: RwLock<HashMap<String, (Instant, FlagDefinition)>>,
+}
+
+impl std::fmt::Debug for FeatureFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FeatureFlags").field("ttl", &self.ttl).finish()
+    }
+}
+
+impl Default for FeatureFlags {
+    fn default() -> Self {
+        Self::new(Arc::new(InMemoryFlagStore::default()))
+    }
+}
+
+impl FeatureFlags {
+    /// Evaluate flags kept in `store`.
+    pub fn new(store: Arc<dyn FlagStore>) -> Self {
+        Self {
+            store,
+            ttl: DEFAULT_FLAG_CACHE_TTL,
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Reuse folded flags for `ttl`; zero disables caching.
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Current definition of `flag`.
+    pub async fn definition(&self, flag: &str) -> Result<FlagDefinition> {
+        if let Some((loaded_at, definition)) = self.cache.read().await.get(flag) {
+            if loaded_at.elapsed() < self.ttl {
+                return Ok(definition.clone());
+            }
+        }
+        let definition = FlagDefinition::from_events(&self.store.events(flag).await?);
+        self.cache
+            .write()
+            .await
+            .insert(flag.to_string(), (Instant::now(), definition.clone()));
+        Ok(definition)
+    }
+
+    /// Whether `flag` is on for `context`, `default` if no rule matches.
+    ///
+    /// Fails closed: if the store cannot be read the flag is off.
+    pub async fn is_enabled(&self, flag: &str, context: &FlagContext, default: bool) -> bool {
+        match self.definition(flag).await {
+            Ok(definition) => definition.evaluate(flag, context, default),
+            Err(e) => {
+                tracing::warn!("Failed to load feature flag '{}', treating it as off: {}", flag, e);
+                false
+            }
+        }
+    }
+
+    /// Fail with [`FlagDisabled`] unless `flag` is on for `context`.
+    pub async fn ensure_enabled(&self, flag: &str, context: &FlagContext, default: bool) -> Result<()> {
+        if self.is_enabled(flag, context, default).await {
+            Ok(())
+        } else {
+            Err(FlagDisabled { flag: flag.to_string() }.into())
+        }
+    }
+
+    /// Set the rule of `scope` for `flag`.
+    pub async fn set(&self, flag: &str, scope: FlagScope, state: FlagState) -> Result<()> {
+        if let FlagState::Percentage(percent) = state {
+            anyhow::ensure!(percent <= 100, "rollout percentage {} exceeds 100", percent);
+        }
+        self.record(flag, FlagChange::Set { scope, state }).await
+    }
+
+    /// Remove the rule of `scope` for `flag`.
+    pub async fn clear(&self, flag: &str, scope: FlagScope) -> Result<()> {
+        self.record(flag, FlagChange::Clear { scope }).await
+    }
+
+    /// Turn `flag` off everywhere.
+    pub async fn kill(&self, flag: &str) -> Result<()> {
+        self.record(flag, FlagChange::Kill).await
+    }
+
+    /// Lift the kill switch of `flag`.
+    pub async fn revive(&self, flag: &str) -> Result<()> {
+        self.record(flag, FlagChange::Revive).await
+    }
+
+    /// Drop cached definitions so the next evaluation reloads them.
+    pub async fn invalidate(&self) {
+        self.cache.write().await.clear();
+    }
+
+    async fn record(&self, flag: &str, change: FlagChange) -> Result<()> {
+        self.store.append(FlagEvent::new(flag, change)).await?;
+        self.cache.write().await.remove(flag);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_scopes_rollout_and_kill_switch() {
+        let flags = FeatureFlags::default();
+        let alice = FlagContext::session("s-1");
+        let bob = FlagContext {
+            session: Some("s-2".to_string()),
+            agent: Some("bob".to_string()),
+            tenant: Some("acme".to_string()),
+        };
+
+        assert!(flags.is_enabled("engine.python", &alice, true).await);
+        assert!(!flags.is_enabled("engine.python", &alice, false).await);
+
+        flags.set("engine.python", FlagScope::Global, FlagState::Off).await.unwrap();
+        flags
+            .set("engine.python", FlagScope::Tenant("acme".to_string()), FlagState::On)
+            .await
+            .unwrap();
+        assert!(!flags.is_enabled("engine.python", &alice, true).await);
+        assert!(flags.is_enabled("engine.python", &bob, false).await);
+
+        // The more specific agent rule beats the tenant rule
+        flags
+            .set("engine.python", FlagScope::Agent("bob".to_string()), FlagState::Off)
+            .await
+            .unwrap();
+        assert!(!flags.is_enabled("engine.python", &bob, true).await);
+
+        flags.kill("engine.python").await.unwrap();
+        flags
+            .clear("engine.python", FlagScope::Agent("bob".to_string()))
+            .await
+            .unwrap();
+        let err = flags.ensure_enabled("engine.python", &bob, true).await.unwrap_err();
+        assert_eq!(err.downcast_ref::<FlagDisabled>().unwrap().flag, "engine.python");
+        flags.revive("engine.python").await.unwrap();
+        assert!(flags.is_enabled("engine.python", &bob, false).await);
+
+        // Percentage rollouts are stable per caller and roughly proportional
+        flags.set("tool.fetch", FlagScope::Global, FlagState::Percentage(30)).await.unwrap();
+        let mut enabled = 0;
+        for session in 0..1000 {
+            let context = FlagContext::session(session.to_string());
+            let first = flags.is_enabled("tool.fetch", &context, false).await;
+            assert_eq!(first, flags.is_enabled("tool.fetch", &context, false).await);
+            enabled += usize::from(first);
+        }
+        assert!((200..400).contains(&enabled), "{} of 1000 enabled", enabled);
+        assert!(flags.set("tool.fetch", FlagScope::Global, FlagState::Percentage(101)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_storage_flags_shared_between_processes() {
+        let backend = Arc::new(toka_store_memory::MemoryBackend::new());
+        let admin = FeatureFlags::new(Arc::new(StorageFlagStore::new(backend.clone())));
+        let worker = FeatureFlags::new(Arc::new(StorageFlagStore::new(backend))).with_cache_ttl(Duration::ZERO);
+        let context = FlagContext::session("s-1");
+
+        admin.set("engine.wasm", FlagScope::Global, FlagState::On).await.unwrap();
+        assert!(worker.is_enabled("engine.wasm", &context, false).await);
+        admin.kill("engine.wasm").await.unwrap();
+        assert!(!worker.is_enabled("engine.wasm", &context, true).await);
+        assert_eq!(
+            worker.definition("engine.wasm").await.unwrap(),
+            FlagDefinition {
+                killed: true,
+                rules: BTreeMap::from([(FlagScope::Global, FlagState::On)]),
+            }
+        );
+    }
+}
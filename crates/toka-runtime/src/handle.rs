@@ -0,0 +1,346 @@
+//! Handles to executions running in the background.
+//!
+//! [`RuntimeManager::execute_with_handle`] runs an execution on its own task
+//! and returns an [`ExecutionHandle`]:
+//!
+//! - [`cancel`](ExecutionHandle::cancel) asks the engine to stop. Process
+//!   engines kill their child and the WebAssembly engine interrupts the
+//!   module; the result carries whatever the execution produced until then.
+//! - [`kill`](ExecutionHandle::kill) also cancels, but drops the execution
+//!   right away instead of waiting for the engine, so it works for engines
+//!   that ignore cancellation. The result is an error.
+//!
+//! Executions stopped either way publish [`KernelEvent::AgentTerminated`] and
+//! [`KernelEvent::TaskFailed`] for the agent named in the request, if any.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::Utc;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use toka_bus_core::{FailureReason, KernelEvent, TerminationReason};
+
+use crate::{ExecutionRequest, ExecutionResult, FlagContext, OutputSink, RuntimeManager};
+
+/// Distinguishes the handles of executions in the same session.
+static EXECUTION_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Exit code reported for executions killed before they returned one.
+const KILLED_EXIT_CODE: i32 = -9;
+
+/// Identifier of a new execution in `session_id`, used as task id in events.
+pub(crate) fn execution_id(session_id: &str) -> String {
+    format!("{}-{}", session_id, EXECUTION_COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Handle to an execution running in the background.
+///
+/// Dropping the handle detaches the execution, which keeps running.
+#[derive(Debug)]
+pub struct ExecutionHandle {
+    id: String,
+    cancellation: CancellationToken,
+    killed: CancellationToken,
+    task: JoinHandle<Result<ExecutionResult>>,
+}
+
+impl ExecutionHandle {
+    /// Start executing `request` on `runtime`, sending its output to `output`.
+    pub(crate) fn spawn(runtime: Arc<RuntimeManager>, request: ExecutionRequest, output: Option<OutputSink>) -> Self {
+        let id = execution_id(&request.session_id);
+        let cancellation = CancellationToken::new();
+        let killed = CancellationToken::new();
+        let task = tokio::spawn(run(runtime, request, output, id.clone(), cancellation.clone(), killed.clone()));
+        Self { id, cancellation, killed, task }
+    }
+
+    /// Identifier of the execution, used as task id in events.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Ask the engine to stop the execution.
+    pub fn cancel(&self) {
+        self.cancellation.cancel();
+    }
+
+    /// Stop the execution without waiting for the engine.
+    pub fn kill(&self) {
+        self.cancellation.cancel();
+        self.killed.cancel();
+    }
+
+    /// Whether the execution was cancelled or killed.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// Whether the execution has finished.
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Wait for the execution to finish.
+    pub async fn await_result(self) -> Result<ExecutionResult> {
+        self.task.await?
+    }
+}
+
+/// Run an execution until it finishes or is killed.
+async fn run(
+    runtime: Arc<RuntimeManager>,
+    request: ExecutionRequest,
+    output: Option<OutputSink>,
+    id: String,
+    cancellation: CancellationToken,
+    killed: CancellationToken,
+) -> Result<ExecutionResult> {
+    let agent = request.agent;
+    let correlation_id = request.correlation_id.clone();
+    let flag_context = FlagContext::session(request.session_id.clone());
+    let execution = runtime.execute_code_in(request, &flag_context, &id, cancellation.clone(), output);
+    let result = tokio::select! {
+        biased;
+        _ = killed.cancelled() => Err(anyhow::anyhow!("Execution {} was killed", id)),
+        result = execution => result,
+    };
+
+    let stopped = match &result {
+        Ok(result) => !result.success,
+        Err(_) => true,
+    };
+    if let (true, true, Some(agent)) = (cancellation.is_cancelled(), stopped, agent) {
+        let (exit_code, error) = match &result {
+            Ok(result) => (result.exit_code.unwrap_or(KILLED_EXIT_CODE), result.error.clone()),
+            Err(e) => (KILLED_EXIT_CODE, e.to_string()),
+        };
+        let reason = if killed.is_cancelled() { "killed" } else { "cancelled" };
+        runtime.publish_event(KernelEvent::AgentTerminated {
+            agent,
+            reason: TerminationReason::Killed,
+            exit_code,
+            timestamp: Utc::now(),
+        });
+        runtime.publish_event(KernelEvent::TaskFailed {
+            task_id: id,
+            agent,
+            error,
+            failure_reason: FailureReason::Other(reason.to_string()),
+            correlation_id,
+            timestamp: Utc::now(),
+        });
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use toka_bus_core::{EventBus, InMemoryBus};
+    use toka_auth::JwtHs256Validator;
+    use toka_types::EntityId;
+
+    use super::*;
+    use crate::{
+        CapabilitySet, CodeType, EngineMetadata, ExecutionContext, ExecutionEngine, Kernel, OutputStream,
+        RuntimeBuilder, RuntimeKernel, RuntimeMetadata, RuntimeResourceUsage, SecurityLevel, StopReason,
+        ToolKernel, TIMEOUT_GRACE_PERIOD,
+    };
+
+    /// Engine printing and streaming a line per tick until cancelled, or
+    /// forever when it ignores cancellation.
+    struct Ticker {
+        honours_cancellation: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl ExecutionEngine for Ticker {
+        fn metadata(&self) -> EngineMetadata {
+            EngineMetadata {
+                name: "ticker".to_string(),
+                version: "1".to_string(),
+                code_type: CodeType::Shell,
+                description: "Ticks until cancelled".to_string(),
+                supported_features: Vec::new(),
+            }
+        }
+
+        async fn validate_code(&self, _code: &str) -> Result<()> {
+            Ok(())
+        }
+
+        async fn execute(
+            &self,
+            context: &ExecutionContext,
+            request: &ExecutionRequest,
+            _kernel: &ToolKernel,
+        ) -> Result<ExecutionResult> {
+            let mut output = String::new();
+            loop {
+                output.push_str("tick\n");
+                if let Some(sink) = &context.output {
+                    sink.send(OutputStream::Stdout, "tick\n");
+                }
+                let cancelled = context.cancellation.cancelled();
+                tokio::select! {
+                    _ = cancelled, if self.honours_cancellation => break,
+                    _ = tokio::time::sleep(Duration::from_millis(10)) => {}
+                }
+            }
+            Ok(ExecutionResult {
+                success: false,
+                output,
+                error: String::new(),
+                exit_code: None,
+                metadata: RuntimeMetadata {
+                    code_type: CodeType::Shell,
+                    session_id: request.session_id.clone(),
+                    duration: Duration::ZERO,
+                    resource_usage: RuntimeResourceUsage {
+                        peak_memory_mb: 0,
+                        cpu_time_ms: 0,
+                        syscall_count: 0,
+                        files_accessed: Vec::new(),
+                        network_attempts: 0,
+                    },
+                    security_level: request.security_level,
+                    engine_version: "1".to_string(),
+                    executed_at: std::time::SystemTime::now(),
+                },
+                artifacts: Vec::new(),
+                stopped: Some(StopReason::Cancelled),
+            })
+        }
+
+        fn supports_capabilities(&self, _capabilities: &CapabilitySet) -> bool {
+            true
+        }
+
+        fn required_capabilities(&self) -> CapabilitySet {
+            CapabilitySet::default()
+        }
+    }
+
+    async fn runtime(honours_cancellation: bool, bus: Arc<InMemoryBus>) -> Arc<RuntimeManager> {
+        let kernel = Kernel::new(Default::default(), Arc::new(JwtHs256Validator::new("secret")), bus.clone());
+        let runtime = RuntimeBuilder::new(RuntimeKernel::new(kernel))
+            .with_engine(CodeType::Shell, Box::new(Ticker { honours_cancellation }))
+            .with_event_bus(bus)
+            .build()
+            .await
+            .unwrap();
+        Arc::new(runtime)
+    }
+
+    fn request(agent: Option<EntityId>) -> ExecutionRequest {
+        ExecutionRequest {
+            code_type: CodeType::Shell,
+            code: "tick".to_string(),
+            session_id: "session".to_string(),
+            security_level: SecurityLevel::Restricted,
+            inputs: serde_json::json!({}),
+            timeout_override: None,
+            environment: None,
+            agent,
+            correlation_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancel_returns_partial_result_and_publishes_events() {
+        let bus = Arc::new(InMemoryBus::new(16));
+        let mut events = bus.subscribe();
+        let runtime = runtime(true, bus).await;
+
+        let mut request = request(Some(EntityId(3)));
+        request.correlation_id = Some("order-7".to_string());
+        let handle = runtime.execute_with_handle(request);
+        let id = handle.id().to_string();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!handle.is_finished());
+        handle.cancel();
+        let result = handle.await_result().await.unwrap();
+        assert!(!result.success);
+        assert!(result.output.starts_with("tick\n"));
+        assert_eq!(result.error, "execution cancelled");
+
+        match events.recv().await.unwrap() {
+            KernelEvent::AgentTerminated { agent, reason, .. } => {
+                assert_eq!(agent, EntityId(3));
+                assert_eq!(reason, TerminationReason::Killed);
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        match events.recv().await.unwrap() {
+            KernelEvent::TaskFailed { task_id, failure_reason, correlation_id, .. } => {
+                assert_eq!(task_id, id);
+                assert_eq!(failure_reason, FailureReason::Other("cancelled".to_string()));
+                assert_eq!(correlation_id.as_deref(), Some("order-7"));
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timeout_override_is_enforced() {
+        let bus = Arc::new(InMemoryBus::new(16));
+        let mut events = bus.subscribe();
+        let runtime = runtime(true, bus).await;
+
+        let mut request = request(Some(EntityId(4)));
+        request.timeout_override = Some(Duration::from_millis(100));
+        request.correlation_id = Some("order-8".to_string());
+        let handle = runtime.execute_with_handle(request);
+        let id = handle.id().to_string();
+        let result = handle.await_result().await.unwrap();
+        assert!(!result.success);
+        assert!(result.output.starts_with("tick\n"));
+        assert_eq!(result.error, "execution timed out after 100ms");
+
+        // A timeout is not a cancellation by the caller
+        match events.recv().await.unwrap() {
+            KernelEvent::TaskTimeout { task_id, agent, timeout_duration_ms, correlation_id, .. } => {
+                assert_eq!((task_id, agent, timeout_duration_ms), (id, EntityId(4), 100));
+                assert_eq!(correlation_id.as_deref(), Some("order-8"));
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_timeout_drops_engines_ignoring_cancellation() {
+        let bus = Arc::new(InMemoryBus::new(16));
+        let runtime = runtime(false, bus).await;
+
+        let mut request = request(None);
+        request.timeout_override = Some(Duration::from_millis(100));
+        let start = Instant::now();
+        let result = runtime.execute_code(request).await.unwrap();
+        assert!(!result.success);
+        assert!(result.output.starts_with("tick\n"));
+        assert_eq!(result.error, "execution timed out after 100ms");
+        assert!(start.elapsed() < TIMEOUT_GRACE_PERIOD * 2);
+    }
+
+    #[tokio::test]
+    async fn test_kill_stops_engines_ignoring_cancellation() {
+        let bus = Arc::new(InMemoryBus::new(16));
+        let mut events = bus.subscribe();
+        let runtime = runtime(false, bus).await;
+
+        let handle = runtime.execute_with_handle(request(None));
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        let start = Instant::now();
+        handle.kill();
+        let error = handle.await_result().await.unwrap_err();
+        assert!(error.to_string().contains("was killed"));
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        // Without an agent there is nobody to report the termination of
+        assert!(events.try_recv().is_err());
+    }
+}
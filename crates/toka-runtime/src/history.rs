@@ -0,0 +1,255 @@
+//! Execution history storage and queries.
+//!
+//! Every execution result is recorded in an [`ExecutionHistoryStore`].
+//! Consumers such as dashboards query it with an [`ExecutionHistoryQuery`]
+//! and receive one [`ExecutionHistoryPage`] at a time instead of cloning the
+//! entire history on every refresh.
+//!
+//! [`InMemoryHistoryStore`] is the default store; persistent stores implement
+//! the same trait and are plugged in with
+//! [`RuntimeBuilder::with_history_store`](crate::RuntimeBuilder::with_history_store).
+
+use std::collections::VecDeque;
+use std::time::SystemTime;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{CodeType, ExecutionResult};
+
+/// Default number of results kept by [`InMemoryHistoryStore`].
+pub const DEFAULT_HISTORY_CAPACITY: usize = 1000;
+
+/// Default page size for history queries.
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+
+/// Filters and pagination for execution history queries.
+///
+/// Results are returned newest first. All filters are optional and combine
+/// with AND.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionHistoryQuery {
+    /// Only executions from this session
+    pub session_id: Option<String>,
+    /// Only successful (`true`) or failed (`false`) executions
+    pub success: Option<bool>,
+    /// Only executions of this code type
+    pub code_type: Option<CodeType>,
+    /// Only executions at or after this time
+    pub since: Option<SystemTime>,
+    /// Only executions before this time
+    pub until: Option<SystemTime>,
+    /// Number of matching results to skip
+    pub offset: usize,
+    /// Maximum number of results to return
+    pub limit: usize,
+}
+
+impl Default for ExecutionHistoryQuery {
+    fn default() -> Self {
+        Self {
+            session_id: None,
+            success: None,
+            code_type: None,
+            since: None,
+            until: None,
+            offset: 0,
+            limit: DEFAULT_PAGE_SIZE,
+        }
+    }
+}
+
+impl ExecutionHistoryQuery {
+    /// Whether `result` passes every filter.
+    pub fn matches(&self, result: &ExecutionResult) -> bool {
+        let metadata = &result.metadata;
+        self.session_id.as_ref().is_none_or(|id| &metadata.session_id == id)
+            && self.success.is_none_or(|success| result.success == success)
+            && self.code_type.as_ref().is_none_or(|code_type| &metadata.code_type == code_type)
+            && self.since.is_none_or(|since| metadata.executed_at >= since)
+            && self.until.is_none_or(|until| metadata.executed_at < until)
+    }
+}
+
+/// One page of history query results.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionHistoryPage {
+    /// Matching results, newest first
+    pub results: Vec<ExecutionResult>,
+    /// Total number of matching results across all pages
+    pub total: usize,
+    /// Offset of the next page, if there is one
+    pub next_offset: Option<usize>,
+}
+
+impl ExecutionHistoryPage {
+    /// Build a page from the full list of matches (newest first).
+    pub fn from_matches<'a>(
+        matches: impl Iterator<Item = &'a ExecutionResult>,
+        query: &ExecutionHistoryQuery,
+    ) -> Self {
+        let mut total = 0;
+        let mut results = Vec::new();
+        for result in matches {
+            if total >= query.offset && results.len() < query.limit {
+                results.push(result.clone());
+            }
+            total += 1;
+        }
+
+        let end = query.offset + results.len();
+        Self {
+            results,
+            total,
+            next_offset: (end < total).then_some(end),
+        }
+    }
+}
+
+/// Storage for execution results.
+#[async_trait]
+pub trait ExecutionHistoryStore: Send + Sync {
+    /// Record a completed execution.
+    async fn record(&self, result: ExecutionResult) -> Result<()>;
+
+    /// Return one page of results matching `query`.
+    async fn query(&self, query: &ExecutionHistoryQuery) -> Result<ExecutionHistoryPage>;
+
+    /// Remove every recorded result.
+    async fn clear(&self) -> Result<()>;
+}
+
+/// Bounded in-memory history that drops the oldest results when full.
+#[derive(Debug)]
+pub struct InMemoryHistoryStore {
+    capacity: usize,
+    results: RwLock<VecDeque<ExecutionResult>>,
+}
+
+impl Default for InMemoryHistoryStore {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_HISTORY_CAPACITY)
+    }
+}
+
+impl InMemoryHistoryStore {
+    /// Create a store keeping at most `capacity` results.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            results: RwLock::new(VecDeque::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl ExecutionHistoryStore for InMemoryHistoryStore {
+    async fn record(&self, result: ExecutionResult) -> Result<()> {
+        let mut results = self.results.write().await;
+        results.push_back(result);
+        while results.len() > self.capacity {
+            results.pop_front();
+        }
+        Ok(())
+    }
+
+    async fn query(&self, query: &ExecutionHistoryQuery) -> Result<ExecutionHistoryPage> {
+        let results = self.results.read().await;
+        let matches = results.iter().rev().filter(|result| query.matches(result));
+        Ok(ExecutionHistoryPage::from_matches(matches, query))
+    }
+
+    async fn clear(&self) -> Result<()> {
+        self.results.write().await.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::{RuntimeMetadata, RuntimeResourceUsage, SecurityLevel};
+
+    fn result(session: &str, success: bool, code_type: CodeType) -> ExecutionResult {
+        ExecutionResult {
+            success,
+            output: String::new(),
+            error: String::new(),
+            exit_code: None,
+            metadata: RuntimeMetadata {
+                code_type,
+                session_id: session.to_string(),
+                duration: Duration::from_millis(1),
+                resource_usage: RuntimeResourceUsage {
+                    peak_memory_mb: 0,
+                    cpu_time_ms: 0,
+                    syscall_count: 0,
+                    files_accessed: Vec::new(),
+                    network_attempts: 0,
+                },
+                security_level: SecurityLevel::Low,
+                engine_version: "test".to_string(),
+                executed_at: SystemTime::now(),
+            },
+            artifacts: Vec::new(),
+            stopped: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_filtered_pagination() {
+        let store = InMemoryHistoryStore::default();
+        for i in 0..5 {
+            let mut entry = result("alpha", i % 2 == 0, CodeType::Python);
+            entry.output = i.to_string();
+            store.record(entry).await.unwrap();
+        }
+        store.record(result("beta", true, CodeType::Shell)).await.unwrap();
+
+        let query = ExecutionHistoryQuery {
+            session_id: Some("alpha".to_string()),
+            success: Some(true),
+            limit: 2,
+            ..Default::default()
+        };
+        let page = store.query(&query).await.unwrap();
+        let outputs: Vec<_> = page.results.iter().map(|r| r.output.as_str()).collect();
+        assert_eq!(outputs, vec!["4", "2"]);
+        assert_eq!(page.total, 3);
+        assert_eq!(page.next_offset, Some(2));
+
+        let page = store
+            .query(&ExecutionHistoryQuery { offset: 2, ..query })
+            .await
+            .unwrap();
+        assert_eq!(page.results.len(), 1);
+        assert_eq!(page.results[0].output, "0");
+        assert_eq!(page.next_offset, None);
+
+        let page = store
+            .query(&ExecutionHistoryQuery {
+                code_type: Some(CodeType::Shell),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.results[0].metadata.session_id, "beta");
+    }
+
+    #[tokio::test]
+    async fn test_capacity_drops_oldest() {
+        let store = InMemoryHistoryStore::with_capacity(2);
+        for session in ["a", "b", "c"] {
+            store.record(result(session, true, CodeType::Python)).await.unwrap();
+        }
+
+        let page = store.query(&ExecutionHistoryQuery::default()).await.unwrap();
+        let sessions: Vec<_> = page.results.iter().map(|r| r.metadata.session_id.as_str()).collect();
+        assert_eq!(sessions, vec!["c", "b"]);
+    }
+}
@@ -2,8 +2,8 @@
 //!
 //! This crate provides the runtime layer for dynamic code generation and execution
 //! while maintaining security through the toka-kernel enforcement layer. It supports
-//! multiple execution environments including WebAssembly, Python scripting, and
-//! sandboxed native code execution.
+//! multiple execution environments including WebAssembly, Python scripting, embedded
+//! Lua, and sandboxed native code execution.
 //!
 //! # Architecture
 //!
@@ -14,26 +14,39 @@
 //! - **Code Generation**: Dynamic code creation with validation
 //! - **Security Integration**: Full kernel enforcement for all operations
 //! - **Resource Management**: Memory, CPU, and I/O tracking per execution
+//! - **Feature Flags**: Store-backed flags gating engines for gradual rollout and instant kill
+//! - **Output Limits**: Per-engine caps on result output with overflow kept in a blob store
+//! - **Cancellation**: Handles to cancel or kill executions that are still running
+//! - **Streaming**: Output delivered in chunks while an execution runs
+//! - **Timeouts**: Per-request timeouts stopping executions cooperatively and keeping their partial output
 //!
 //! # Usage
 //!
-//! ```rust
-//! use toka_runtime::{RuntimeManager, ExecutionRequest, CodeType};
-//! use toka_kernel::{ToolKernel, SecurityLevel};
+//! ```rust,no_run
+//! use std::sync::Arc;
+//! use toka_auth::hs256::JwtHs256Validator;
+//! use toka_bus_core::InMemoryBus;
+//! use toka_kernel::{Kernel, WorldState};
+//! use toka_runtime::{CodeType, ExecutionRequest, RuntimeManager, RuntimeKernel, SecurityLevel};
 //!
 //! #[tokio::main]
 //! async fn main() -> anyhow::Result<()> {
 //!     // Initialize kernel and runtime
-//!     let kernel = ToolKernel::new().await?;
-//!     let runtime = RuntimeManager::new(kernel).await?;
+//!     let bus = Arc::new(InMemoryBus::new(1024));
+//!     let kernel = Kernel::new(WorldState::default(), Arc::new(JwtHs256Validator::new("secret")), bus);
+//!     let runtime = RuntimeManager::with_default_engines(RuntimeKernel::new(kernel)).await?;
 //!     
 //!     // Execute Python code dynamically
 //!     let request = ExecutionRequest {
 //!         code_type: CodeType::Python,
 //!         code: "print('Hello from dynamic execution!')".to_string(),
 //!         session_id: "user_session".to_string(),
-//!         security_level: SecurityLevel::Sandboxed,
+//!         security_level: SecurityLevel::Restricted,
 //!         inputs: serde_json::json!({}),
+//!         timeout_override: None,
+//!         environment: None,
+//!         agent: None,
+//!         correlation_id: None,
 //!     };
 //!     
 //!     let result = runtime.execute_code(request).await?;
@@ -44,26 +57,58 @@
 
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::RwLock;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
+use tokio_util::sync::CancellationToken;
 
 // Re-export kernel types
 pub use toka_kernel::{Kernel, KernelError};
 
 // Import toka-types for Message handling
 use toka_types::{Message, Operation};
+use toka_types::traits::Claims;
+use toka_types::EntityId;
+use toka_bus_core::{EventBus, KernelEvent};
+
+pub mod engines;
+pub mod flags;
+pub mod handle;
+pub mod history;
+pub mod output;
+pub mod quota;
+pub mod sandbox;
+pub mod streaming;
+pub mod timeout;
+pub mod workspace;
+
+pub use flags::{
+    FeatureFlags, FlagContext, FlagDisabled, FlagScope, FlagState, FlagStore, InMemoryFlagStore, StorageFlagStore,
+};
+pub use handle::ExecutionHandle;
+pub use history::{
+    ExecutionHistoryPage, ExecutionHistoryQuery, ExecutionHistoryStore, InMemoryHistoryStore,
+};
+pub use output::{OutputLimiter, OutputLimits, OutputOverflow, TruncationPolicy};
+pub use quota::{
+    ExecutionQuota, InMemoryQuotaStore, QuotaError, QuotaPolicy, QuotaStore, QuotaUsage, StorageQuotaStore,
+};
+pub use sandbox::{SandboxGuard, SandboxPolicy};
+pub use streaming::{ExecutionStream, OutputChunk, OutputSink, OutputStream};
+pub use timeout::TIMEOUT_GRACE_PERIOD;
+
+use quota::QuotaLease;
+use timeout::Completion;
+pub use workspace::{CleanupReport, WorkspaceConfig, WorkspaceError, WorkspaceManager, WorkspaceUsage};
 
 // TODO: Create these module files when implementing the engines
-// pub mod engines;
-// pub mod sandbox;
 // pub mod generation;
 // pub mod validation;
 
 // TODO: These types need to be implemented in toka-kernel or defined here
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum SecurityLevel {
     Low,
     Medium,
@@ -71,7 +116,7 @@ pub enum SecurityLevel {
     Restricted,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Capability {
     CodeGeneration,
     FileSystem,
@@ -79,7 +124,7 @@ pub enum Capability {
     Process,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CapabilitySet {
     pub capabilities: Vec<Capability>,
 }
@@ -95,6 +140,13 @@ pub struct ExecutionContext {
     pub session_id: String,
     pub security_level: SecurityLevel,
     pub capabilities: CapabilitySet,
+    /// Artifact a previous execution of the same code left in the code cache
+    pub cached_artifact: Option<Artifact>,
+    /// Cancelled when the execution should stop early; engines then end it
+    /// and return what it produced so far
+    pub cancellation: CancellationToken,
+    /// Receives output while the execution runs, for streaming executions
+    pub output: Option<OutputSink>,
 }
 
 // Removed duplicate definition - using the one below
@@ -121,6 +173,9 @@ impl RuntimeKernel {
             session_id: session_id.to_string(),
             security_level,
             capabilities: capabilities.clone(),
+            cached_artifact: None,
+            cancellation: CancellationToken::new(),
+            output: None,
         })
     }
     
@@ -149,10 +204,18 @@ pub struct ExecutionRequest {
     pub security_level: SecurityLevel,
     /// Input data for the code
     pub inputs: JsonValue,
-    /// Optional timeout override
+    /// Optional timeout override, enforced by the runtime on top of the
+    /// engine's own limits
     pub timeout_override: Option<Duration>,
     /// Environment variables
     pub environment: Option<HashMap<String, String>>,
+    /// Agent the code runs on behalf of, named in lifecycle events
+    #[serde(default)]
+    pub agent: Option<EntityId>,
+    /// Correlation ID of the task the code runs for, copied to its
+    /// lifecycle events
+    #[serde(default)]
+    pub correlation_id: Option<String>,
 }
 
 /// Supported code execution types
@@ -168,6 +231,40 @@ pub enum CodeType {
     Shell,
     /// Rust code compilation and execution
     Rust,
+    /// Embedded Lua script execution
+    Lua,
+}
+
+impl CodeType {
+    /// Every supported code type
+    pub const ALL: [CodeType; 6] = [
+        CodeType::Python,
+        CodeType::JavaScript,
+        CodeType::WebAssembly,
+        CodeType::Shell,
+        CodeType::Rust,
+        CodeType::Lua,
+    ];
+
+    /// Stable key of the engine running this code type
+    ///
+    /// Keys match the cargo feature enabling the engine and name the
+    /// execution context created for it (`runtime_<key>`).
+    pub fn engine_key(&self) -> &'static str {
+        match self {
+            CodeType::Python => "python",
+            CodeType::JavaScript => "javascript",
+            CodeType::WebAssembly => "wasm",
+            CodeType::Shell => "shell",
+            CodeType::Rust => "rust",
+            CodeType::Lua => "lua",
+        }
+    }
+
+    /// Code type whose engine key is `key`
+    pub fn from_engine_key(key: &str) -> Option<CodeType> {
+        Self::ALL.into_iter().find(|code_type| code_type.engine_key() == key)
+    }
 }
 
 /// Runtime execution result
@@ -185,6 +282,29 @@ pub struct ExecutionResult {
     pub metadata: RuntimeMetadata,
     /// Generated artifacts (compiled binaries, etc.)
     pub artifacts: Vec<Artifact>,
+    /// Why the execution was stopped before it finished, if it was
+    ///
+    /// [`RuntimeManager`] appends the reason to `error`.
+    #[serde(default)]
+    pub stopped: Option<StopReason>,
+}
+
+/// Why an execution was stopped before it finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StopReason {
+    /// The execution was cancelled
+    Cancelled,
+    /// The timeout of the execution elapsed
+    TimedOut(Duration),
+}
+
+impl std::fmt::Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StopReason::Cancelled => f.write_str("execution cancelled"),
+            StopReason::TimedOut(timeout) => f.write_str(&timeout::timeout_message(*timeout)),
+        }
+    }
 }
 
 /// Runtime execution metadata
@@ -238,8 +358,14 @@ pub struct Artifact {
 pub struct RuntimeManager {
     kernel: Arc<RuntimeKernel>,
     engines: RwLock<HashMap<CodeType, Box<dyn ExecutionEngine + Send + Sync>>>,
-    execution_history: RwLock<Vec<ExecutionResult>>,
+    execution_history: Arc<dyn ExecutionHistoryStore>,
     code_cache: RwLock<HashMap<String, CachedExecution>>,
+    workspaces: Option<Arc<WorkspaceManager>>,
+    quota_policy: QuotaPolicy,
+    quota_store: Arc<dyn QuotaStore>,
+    feature_flags: Option<Arc<FeatureFlags>>,
+    output_limiter: Option<Arc<OutputLimiter>>,
+    event_bus: Option<Arc<dyn EventBus>>,
 }
 
 /// Cached execution for performance optimization
@@ -286,28 +412,82 @@ pub struct EngineMetadata {
 }
 
 impl RuntimeManager {
-    /// Create new runtime manager with kernel
+    /// Create new runtime manager with kernel and no engines registered
+    ///
+    /// Use [`with_default_engines`](Self::with_default_engines) for the
+    /// built-in engines, or [`register_engine`](Self::register_engine) for
+    /// custom ones.
     pub async fn new(kernel: ToolKernel) -> Result<Self> {
-        let engines: HashMap<CodeType, Box<dyn ExecutionEngine + Send + Sync>> = HashMap::new();
-        
-        // TODO: Register default engines when engine modules are implemented
-        // engines.insert(CodeType::Python, Box::new(engines::PythonEngine::new()));
-        // engines.insert(CodeType::JavaScript, Box::new(engines::JavaScriptEngine::new()));
-        // engines.insert(CodeType::WebAssembly, Box::new(engines::WasmEngine::new()));
-        // engines.insert(CodeType::Shell, Box::new(engines::ShellEngine::new()));
-        // engines.insert(CodeType::Rust, Box::new(engines::RustEngine::new()));
-        
         Ok(Self {
             kernel: Arc::new(kernel),
-            engines: RwLock::new(engines),
-            execution_history: RwLock::new(Vec::new()),
+            engines: RwLock::new(HashMap::new()),
+            execution_history: Arc::new(InMemoryHistoryStore::default()),
             code_cache: RwLock::new(HashMap::new()),
+            workspaces: None,
+            quota_policy: QuotaPolicy::default(),
+            quota_store: Arc::new(InMemoryQuotaStore::default()),
+            feature_flags: None,
+            output_limiter: None,
+            event_bus: None,
         })
     }
-    
+
+    /// Create new runtime manager with every built-in engine enabled by
+    /// cargo features registered
+    pub async fn with_default_engines(kernel: ToolKernel) -> Result<Self> {
+        let runtime = Self::new(kernel).await?;
+        for (code_type, engine) in engines::default_engines() {
+            runtime.register_engine(code_type, engine).await?;
+        }
+        Ok(runtime)
+    }
+
     /// Execute code dynamically with kernel enforcement
     pub async fn execute_code(&self, request: ExecutionRequest) -> Result<ExecutionResult> {
+        let context = FlagContext::session(request.session_id.clone());
+        let id = handle::execution_id(&request.session_id);
+        self.execute_code_in(request, &context, &id, CancellationToken::new(), None).await
+    }
+
+    /// Start executing code in the background, returning a handle to cancel
+    /// or kill the execution and to await its result
+    ///
+    /// When the execution is stopped through the handle and the request
+    /// names an agent, [`KernelEvent::AgentTerminated`] and
+    /// [`KernelEvent::TaskFailed`] are published on the event bus.
+    pub fn execute_with_handle(self: &Arc<Self>, request: ExecutionRequest) -> ExecutionHandle {
+        ExecutionHandle::spawn(Arc::clone(self), request, None)
+    }
+
+    /// Start executing code in the background, streaming its output
+    ///
+    /// The returned stream yields stdout and stderr chunks as the engine
+    /// produces them and ends when the execution finishes; its
+    /// [`handle`](ExecutionStream::handle) cancels the execution.
+    pub fn execute_streaming(self: &Arc<Self>, request: ExecutionRequest) -> ExecutionStream {
+        ExecutionStream::spawn(Arc::clone(self), request)
+    }
+
+    /// Execute code, evaluating feature flags for `flag_context`
+    ///
+    /// Fails with [`FlagDisabled`] when the engine flag of the code type
+    /// (see [`flags::engine_flag`]) is off for the caller. Engines stop the
+    /// execution early once `cancellation` is cancelled or the timeout of
+    /// the request elapses, and send output to `output` while it runs.
+    /// Timeouts are reported as [`KernelEvent::TaskTimeout`] for task `id`.
+    async fn execute_code_in(
+        &self,
+        request: ExecutionRequest,
+        flag_context: &FlagContext,
+        id: &str,
+        cancellation: CancellationToken,
+        output: Option<OutputSink>,
+    ) -> Result<ExecutionResult> {
         let start_time = Instant::now();
+
+        if let Some(flags) = &self.feature_flags {
+            flags.ensure_enabled(&flags::engine_flag(&request.code_type), flag_context, true).await?;
+        }
         
         // Get appropriate execution engine
         let engines = self.engines.read().await;
@@ -318,43 +498,168 @@ impl RuntimeManager {
         let required_capabilities = engine.required_capabilities();
         
         // Create execution context with kernel
-        let context = self.kernel.create_execution_context(
-            &format!("runtime_{:?}", request.code_type),
+        let mut context = self.kernel.create_execution_context(
+            &format!("runtime_{}", request.code_type.engine_key()),
             &request.session_id,
             &required_capabilities,
-            request.security_level.clone(),
+            request.security_level,
         ).await?;
         
         // Validate code before execution
         engine.validate_code(&request.code).await?;
+
+        // Refuse to run once the session's workspace is over quota
+        if let Some(workspaces) = &self.workspaces {
+            workspaces.open_workspace(&request.session_id, None).await?;
+            workspaces.check_quota(&request.session_id, 0).await?;
+        }
         
-        // Check cache for previously compiled code
+        // Hand previously compiled code to the engine
         let code_hash = self.calculate_code_hash(&request.code);
-        let cached_artifact = self.get_cached_execution(&code_hash).await;
+        context.cached_artifact = self.get_cached_execution(&code_hash).await;
         
+        if cancellation.is_cancelled() {
+            anyhow::bail!("Execution was cancelled before it started");
+        }
+
+        // Cancel a child token on timeout, so it is not taken for a
+        // cancellation by the caller; engine output is relayed to `output`
+        // and kept in case the engine has to be dropped
+        context.cancellation = cancellation.child_token();
+        let (sink, mut chunks) = OutputSink::channel();
+        context.output = Some(sink);
+        let executed_at = SystemTime::now();
+
         // Execute with kernel enforcement
-        let result = self.kernel.enforce_execution(&context, async {
+        let execution = self.kernel.enforce_execution(&context, async {
             // Execute through the appropriate engine
             engine.execute(&context, &request, &self.kernel).await
-        }).await?;
+        });
+        let completion = timeout::run_with_timeout(
+            execution,
+            &mut chunks,
+            output.as_ref(),
+            request.timeout_override,
+            &context.cancellation,
+        )
+        .await;
+        let mut result = match completion {
+            Completion::Finished(result) => result?,
+            Completion::TimedOut { timeout, result, partial } => {
+                let mut result = match result {
+                    Some(Ok(result)) => result,
+                    stopped => {
+                        if let Some(Err(e)) = stopped {
+                            tracing::debug!("Execution {} failed after timing out: {}", id, e);
+                        }
+                        partial.into_result(&request, engine.metadata().version, executed_at, start_time.elapsed())
+                    }
+                };
+                self.report_timeout(&mut result, id, &request, timeout);
+                result
+            }
+        };
+        if let Some(reason) = result.stopped {
+            result.error.push_str(&reason.to_string());
+        }
         
+        if let Some(output) = &output {
+            output.replay(&result);
+        }
+
         // Update cache if compilation occurred
         if let Some(artifact) = result.artifacts.first() {
             self.update_cache(code_hash, artifact.clone()).await;
         }
+
+        // Cap output before it reaches history, events and prompts
+        if let Some(limiter) = &self.output_limiter {
+            let engine = request.code_type.engine_key();
+            for stream in [&mut result.output, &mut result.error] {
+                if let Some(blob) = limiter.apply(engine, stream).await?.and_then(|overflow| overflow.blob) {
+                    result.artifacts.push(Artifact {
+                        artifact_type: output::OVERFLOW_ARTIFACT_TYPE.to_string(),
+                        path: format!("blob:{}", blob.hex()),
+                        size_bytes: blob.size,
+                        checksum: blob.hex(),
+                    });
+                }
+            }
+        }
         
         // Store execution history
-        let mut history = self.execution_history.write().await;
-        history.push(result.clone());
-        
-        // Keep only recent executions
-        if history.len() > 1000 {
-            history.drain(0..100);
-        }
+        self.execution_history.record(result.clone()).await?;
         
         Ok(result)
     }
     
+    /// Execute code on behalf of a capability token, enforcing the execution
+    /// quota of its subject
+    ///
+    /// The execution counts against the quota of `claims.sub` until it
+    /// finishes, whether it succeeds or not. Fails with a [`QuotaError`] when
+    /// the subject has too many executions running or used up its daily
+    /// allowance.
+    pub async fn execute_code_with_claims(
+        &self,
+        request: ExecutionRequest,
+        claims: &Claims,
+    ) -> Result<ExecutionResult> {
+        let flag_context = FlagContext::from_claims(claims).with_session(request.session_id.clone());
+        let quota = self.quota_policy.quota_for(&claims.sub);
+        let id = handle::execution_id(&request.session_id);
+        if quota.is_unlimited() {
+            return self.execute_code_in(request, &flag_context, &id, CancellationToken::new(), None).await;
+        }
+
+        let lease = QuotaLease::acquire(self.quota_store.clone(), &claims.sub, &quota, chrono::Utc::now()).await?;
+        let result = self.execute_code_in(request, &flag_context, &id, CancellationToken::new(), None).await;
+        lease.release().await;
+        result
+    }
+
+    /// Feature flags gating engines, if configured
+    pub fn feature_flags(&self) -> Option<&Arc<FeatureFlags>> {
+        self.feature_flags.as_ref()
+    }
+
+    /// Event bus lifecycle events are published on, if configured
+    pub fn event_bus(&self) -> Option<&Arc<dyn EventBus>> {
+        self.event_bus.as_ref()
+    }
+
+    /// Publish `event` on the event bus, if configured
+    fn publish_event(&self, event: KernelEvent) {
+        if let Some(bus) = &self.event_bus {
+            if let Err(e) = bus.publish(&event) {
+                tracing::warn!("Failed to publish {} event: {}", event.kind(), e);
+            }
+        }
+    }
+
+    /// Fail `result` of task `id` as timed out and publish
+    /// [`KernelEvent::TaskTimeout`] for the agent of `request`, if any
+    fn report_timeout(&self, result: &mut ExecutionResult, id: &str, request: &ExecutionRequest, timeout: Duration) {
+        // Replaces the cancellation engines report once the timeout cancelled them
+        result.success = false;
+        result.stopped = Some(StopReason::TimedOut(timeout));
+
+        if let Some(agent) = request.agent {
+            self.publish_event(KernelEvent::TaskTimeout {
+                task_id: id.to_string(),
+                agent,
+                timeout_duration_ms: timeout.as_millis() as u64,
+                correlation_id: request.correlation_id.clone(),
+                timestamp: chrono::Utc::now(),
+            });
+        }
+    }
+
+    /// Current quota usage of a token subject
+    pub async fn quota_usage(&self, subject: &str) -> Result<QuotaUsage> {
+        self.quota_store.usage(subject, chrono::Utc::now()).await
+    }
+
     /// Generate code dynamically based on requirements
     pub async fn generate_code(
         &self,
@@ -385,6 +690,7 @@ impl RuntimeManager {
                           CodeType::WebAssembly => "WebAssembly",
                           CodeType::Shell => "Shell",
                           CodeType::Rust => "Rust",
+                          CodeType::Lua => "Lua",
                       }, prompt))
         }).await
     }
@@ -400,6 +706,11 @@ impl RuntimeManager {
         Ok(())
     }
     
+    /// Whether an engine is registered for `code_type`
+    pub async fn has_engine(&self, code_type: &CodeType) -> bool {
+        self.engines.read().await.contains_key(code_type)
+    }
+
     /// List available execution engines
     pub async fn list_engines(&self) -> Vec<EngineMetadata> {
         let engines = self.engines.read().await;
@@ -409,11 +720,66 @@ impl RuntimeManager {
     }
     
     /// Get execution history
+    ///
+    /// This clones every stored result, oldest first; prefer
+    /// [`query_execution_history`](Self::query_execution_history) for anything
+    /// that refreshes periodically.
     pub async fn get_execution_history(&self) -> Vec<ExecutionResult> {
-        let history = self.execution_history.read().await;
-        history.clone()
+        let query = ExecutionHistoryQuery {
+            limit: usize::MAX,
+            ..Default::default()
+        };
+        match self.execution_history.query(&query).await {
+            Ok(page) => page.results.into_iter().rev().collect(),
+            Err(e) => {
+                tracing::warn!("Failed to read execution history: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Get one page of execution history matching `query`, newest first
+    pub async fn query_execution_history(
+        &self,
+        query: &ExecutionHistoryQuery,
+    ) -> Result<ExecutionHistoryPage> {
+        self.execution_history.query(query).await
+    }
+
+    /// Stream every execution matching `query`, newest first, one page at a time
+    ///
+    /// Pages are fetched lazily as the stream is polled, starting at
+    /// `query.offset` and using `query.limit` as the page size. Executions
+    /// recorded while streaming shift later pages, so a result may be
+    /// yielded twice.
+    pub fn stream_execution_history(
+        &self,
+        query: ExecutionHistoryQuery,
+    ) -> impl futures::Stream<Item = Result<ExecutionResult>> + Send + '_ {
+        use futures::StreamExt;
+
+        let pages = futures::stream::unfold(Some(query), move |query| async move {
+            let query = query?;
+            match self.execution_history.query(&query).await {
+                Ok(page) => {
+                    let next = page.next_offset.map(|offset| ExecutionHistoryQuery {
+                        offset,
+                        ..query
+                    });
+                    let results: Vec<Result<ExecutionResult>> = page.results.into_iter().map(Ok).collect();
+                    Some((futures::stream::iter(results), next))
+                }
+                Err(e) => Some((futures::stream::iter(vec![Err(e)]), None)),
+            }
+        });
+        pages.flatten()
     }
     
+    /// Workspace manager enforcing disk quotas, if configured
+    pub fn workspace_manager(&self) -> Option<&Arc<WorkspaceManager>> {
+        self.workspaces.as_ref()
+    }
+
     /// Clear execution cache
     pub async fn clear_cache(&self) {
         let mut cache = self.code_cache.write().await;
@@ -438,9 +804,11 @@ impl RuntimeManager {
                 })
             }
             Operation::ScheduleAgentTask { agent, task } => {
+                let mut task = task.clone();
+                task.correlation_id = message.correlation_id.clone().or(task.correlation_id);
                 Ok(KernelEvent::TaskScheduled {
                     agent: *agent,
-                    task: task.clone(),
+                    task,
                     timestamp: Utc::now(),
                 })
             }
@@ -449,6 +817,7 @@ impl RuntimeManager {
                     agent: *agent,
                     task: toka_types::TaskSpec {
                         description: "Mock observation task".to_string(),
+                        correlation_id: message.correlation_id.clone(),
                     },
                     timestamp: Utc::now(),
                 })
@@ -500,6 +869,14 @@ impl RuntimeManager {
 pub struct RuntimeBuilder {
     kernel: RuntimeKernel,
     engines: HashMap<CodeType, Box<dyn ExecutionEngine + Send + Sync>>,
+    default_engines: bool,
+    history_store: Option<Arc<dyn ExecutionHistoryStore>>,
+    workspaces: Option<Arc<WorkspaceManager>>,
+    quota_policy: QuotaPolicy,
+    quota_store: Option<Arc<dyn QuotaStore>>,
+    feature_flags: Option<Arc<FeatureFlags>>,
+    output_limiter: Option<Arc<OutputLimiter>>,
+    event_bus: Option<Arc<dyn EventBus>>,
 }
 
 impl RuntimeBuilder {
@@ -508,6 +885,14 @@ impl RuntimeBuilder {
         Self {
             kernel,
             engines: HashMap::new(),
+            default_engines: false,
+            history_store: None,
+            workspaces: None,
+            quota_policy: QuotaPolicy::default(),
+            quota_store: None,
+            feature_flags: None,
+            output_limiter: None,
+            event_bus: None,
         }
     }
     
@@ -521,9 +906,79 @@ impl RuntimeBuilder {
         self
     }
     
+    /// Register the built-in engines enabled by cargo features
+    ///
+    /// Engines added with [`with_engine`](Self::with_engine) replace the
+    /// built-in engine for their code type.
+    pub fn with_default_engines(mut self) -> Self {
+        self.default_engines = true;
+        self
+    }
+    
+    /// Use a custom (e.g. persistent) execution history store
+    pub fn with_history_store(mut self, store: Arc<dyn ExecutionHistoryStore>) -> Self {
+        self.history_store = Some(store);
+        self
+    }
+    
+    /// Enforce workspace disk quotas on every execution
+    pub fn with_workspace_manager(mut self, workspaces: Arc<WorkspaceManager>) -> Self {
+        self.workspaces = Some(workspaces);
+        self
+    }
+    
+    /// Enforce per-subject execution quotas on token-authorized executions
+    pub fn with_quota_policy(mut self, policy: QuotaPolicy) -> Self {
+        self.quota_policy = policy;
+        self
+    }
+    
+    /// Keep quota usage in `store`, e.g. a [`StorageQuotaStore`] shared by
+    /// several runtime processes
+    pub fn with_quota_store(mut self, store: Arc<dyn QuotaStore>) -> Self {
+        self.quota_store = Some(store);
+        self
+    }
+    
+    /// Gate engines behind feature flags, so an engine can be rolled out
+    /// gradually or killed without a redeploy
+    pub fn with_feature_flags(mut self, flags: Arc<FeatureFlags>) -> Self {
+        self.feature_flags = Some(flags);
+        self
+    }
+    
+    /// Cap stdout and stderr of each execution per engine, keeping the
+    /// complete streams as [`output::OVERFLOW_ARTIFACT_TYPE`] artifacts if
+    /// the limiter has a blob store
+    pub fn with_output_limiter(mut self, limiter: Arc<OutputLimiter>) -> Self {
+        self.output_limiter = Some(limiter);
+        self
+    }
+    
+    /// Publish lifecycle events of executions, e.g. when one is cancelled
+    pub fn with_event_bus(mut self, bus: Arc<dyn EventBus>) -> Self {
+        self.event_bus = Some(bus);
+        self
+    }
+    
     /// Build runtime manager
     pub async fn build(self) -> Result<RuntimeManager> {
-        let runtime = RuntimeManager::new(self.kernel).await?;
+        let mut runtime = if self.default_engines {
+            RuntimeManager::with_default_engines(self.kernel).await?
+        } else {
+            RuntimeManager::new(self.kernel).await?
+        };
+        if let Some(store) = self.history_store {
+            runtime.execution_history = store;
+        }
+        runtime.workspaces = self.workspaces;
+        runtime.quota_policy = self.quota_policy;
+        if let Some(store) = self.quota_store {
+            runtime.quota_store = store;
+        }
+        runtime.feature_flags = self.feature_flags;
+        runtime.output_limiter = self.output_limiter;
+        runtime.event_bus = self.event_bus;
         
         // Register custom engines
         for (code_type, engine) in self.engines {
@@ -569,6 +1024,8 @@ mod tests {
             inputs: serde_json::json!({}),
             timeout_override: None,
             environment: None,
+            agent: None,
+            correlation_id: None,
         };
         
         // For this test, we'd need to implement the actual Python engine
@@ -605,4 +1062,16 @@ mod tests {
         assert!(python_type != js_type);
         assert!(python_type == CodeType::Python);
     }
+
+    #[test]
+    fn test_engine_keys() {
+        let keys: Vec<&str> = CodeType::ALL.iter().map(CodeType::engine_key).collect();
+        assert_eq!(keys, ["python", "javascript", "wasm", "shell", "rust", "lua"]);
+
+        for code_type in CodeType::ALL {
+            assert_eq!(CodeType::from_engine_key(code_type.engine_key()), Some(code_type));
+        }
+        assert_eq!(CodeType::from_engine_key("code_python"), None);
+        assert_eq!(CodeType::from_engine_key("Python"), None);
+    }
 }
\ No newline at end of file
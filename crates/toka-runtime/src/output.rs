@@ -0,0 +1,279 @@
+//! Size limits for tool and execution output.
+//!
+//! A single verbose tool can print hundreds of megabytes, and that output
+//! would otherwise travel on in results, bus events and LLM prompts. An
+//! [`OutputLimiter`] caps it per tool or engine: output over the limit is
+//! cut at a UTF-8 character boundary, following the [`TruncationPolicy`],
+//! and an explicit marker states how much was omitted. With a
+//! [`BlobStore`] configured, the complete output is stored there first and
+//! the marker names the blob, so nothing is lost.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use toka_store_core::{BlobRef, BlobStore};
+
+/// Default cap on output kept inline (1 MiB).
+pub const DEFAULT_MAX_OUTPUT_BYTES: usize = 1024 * 1024;
+
+/// Artifact type of complete outputs stored by the runtime.
+pub const OVERFLOW_ARTIFACT_TYPE: &str = "output-overflow";
+
+/// Which part of an oversized output is kept inline.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TruncationPolicy {
+    /// Keep the beginning
+    #[default]
+    Head,
+    /// Keep the end, where errors usually are
+    Tail,
+    /// Keep half the limit from each end
+    HeadAndTail,
+}
+
+/// Output caps by tool name or engine key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputLimits {
+    /// Cap of names without an override, `None` for unlimited
+    pub default_max_bytes: Option<usize>,
+    /// Caps of individual tools or engines
+    #[serde(default)]
+    pub overrides: HashMap<String, usize>,
+    /// Part of the output kept inline
+    #[serde(default)]
+    pub policy: TruncationPolicy,
+}
+
+impl Default for OutputLimits {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_OUTPUT_BYTES)
+    }
+}
+
+impl OutputLimits {
+    /// Cap every output at `max_bytes`.
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            default_max_bytes: Some(max_bytes),
+            overrides: HashMap::new(),
+            policy: TruncationPolicy::default(),
+        }
+    }
+
+    /// No caps unless overridden.
+    pub fn unlimited() -> Self {
+        Self {
+            default_max_bytes: None,
+            ..Self::new(0)
+        }
+    }
+
+    /// Cap the output of tool or engine `name` at `max_bytes`.
+    pub fn with_limit(mut self, name: impl Into<String>, max_bytes: usize) -> Self {
+        self.overrides.insert(name.into(), max_bytes);
+        self
+    }
+
+    /// Keep the part selected by `policy`.
+    pub fn with_policy(mut self, policy: TruncationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Cap applying to `name`.
+    pub fn limit_for(&self, name: &str) -> Option<usize> {
+        self.overrides.get(name).copied().or(self.default_max_bytes)
+    }
+}
+
+/// Details of an output that exceeded its cap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputOverflow {
+    /// Size of the complete output in bytes
+    pub original_bytes: usize,
+    /// Bytes kept inline, excluding the marker
+    pub kept_bytes: usize,
+    /// Complete output, if a blob store is configured
+    pub blob: Option<BlobRef>,
+}
+
+impl OutputOverflow {
+    /// Marker inserted where output was cut.
+    pub fn marker(&self) -> String {
+        let omitted = self.original_bytes - self.kept_bytes;
+        match &self.blob {
+            Some(blob) => format!(
+                "[output truncated: {} of {} bytes omitted; full output in blob {}]",
+                omitted,
+                self.original_bytes,
+                blob.hex()
+            ),
+            None => format!("[output truncated: {} of {} bytes omitted]", omitted, self.original_bytes),
+        }
+    }
+}
+
+/// Largest char boundary of `s` at or below `index`.
+fn floor_boundary(s: &str, mut index: usize) -> usize {
+    index = index.min(s.len());
+    while !s.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// Smallest char boundary of `s` at or above `index`.
+fn ceil_boundary(s: &str, mut index: usize) -> usize {
+    while !s.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+/// Cut `output` to at most `max_bytes` plus a marker line.
+///
+/// Returns `None` if the output fits.
+pub fn truncate_output(
+    output: &str,
+    max_bytes: usize,
+    policy: TruncationPolicy,
+    blob: Option<BlobRef>,
+) -> Option<(String, OutputOverflow)> {
+    if output.len() <= max_bytes {
+        return None;
+    }
+    let (head, tail) = match policy {
+        TruncationPolicy::Head => (&output[..floor_boundary(output, max_bytes)], ""),
+        TruncationPolicy::Tail => ("", &output[ceil_boundary(output, output.len() - max_bytes)..]),
+        TruncationPolicy::HeadAndTail => {
+            let head = &output[..floor_boundary(output, max_bytes / 2)];
+            let tail_start = ceil_boundary(output, output.len() - (max_bytes - max_bytes / 2));
+            (head, &output[tail_start..])
+        }
+    };
+    let overflow = OutputOverflow {
+        original_bytes: output.len(),
+        kept_bytes: head.len() + tail.len(),
+        blob,
+    };
+
+    let marker = overflow.marker();
+    let mut truncated = String::with_capacity(overflow.kept_bytes + marker.len() + 2);
+    truncated.push_str(head);
+    if !head.is_empty() && !head.ends_with('\n') {
+        truncated.push('\n');
+    }
+    truncated.push_str(&marker);
+    if !tail.is_empty() {
+        truncated.push('\n');
+        truncated.push_str(tail);
+    }
+    Some((truncated, overflow))
+}
+
+/// Applies [`OutputLimits`], keeping complete outputs in a blob store.
+#[derive(Clone, Default)]
+pub struct OutputLimiter {
+    limits: OutputLimits,
+    blobs: Option<Arc<dyn BlobStore>>,
+}
+
+impl std::fmt::Debug for OutputLimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutputLimiter")
+            .field("limits", &self.limits)
+            .field("blobs", &self.blobs.is_some())
+            .finish()
+    }
+}
+
+impl OutputLimiter {
+    /// Enforce `limits`, discarding what is cut.
+    pub fn new(limits: OutputLimits) -> Self {
+        Self { limits, blobs: None }
+    }
+
+    /// Store complete oversized outputs in `blobs`.
+    pub fn with_blob_store(mut self, blobs: Arc<dyn BlobStore>) -> Self {
+        self.blobs = Some(blobs);
+        self
+    }
+
+    /// Limits being enforced.
+    pub fn limits(&self) -> &OutputLimits {
+        &self.limits
+    }
+
+    /// Cap `output` of tool or engine `name` in place.
+    ///
+    /// Returns the overflow details if the output was cut.
+    pub async fn apply(&self, name: &str, output: &mut String) -> Result<Option<OutputOverflow>> {
+        let Some(max_bytes) = self.limits.limit_for(name) else {
+            return Ok(None);
+        };
+        if output.len() <= max_bytes {
+            return Ok(None);
+        }
+        let blob = match &self.blobs {
+            Some(blobs) => Some(blobs.put_bytes(output.as_bytes()).await?),
+            None => None,
+        };
+        let Some((truncated, overflow)) = truncate_output(output, max_bytes, self.limits.policy, blob) else {
+            return Ok(None);
+        };
+        tracing::debug!(
+            "Truncated output of '{}' from {} to {} bytes",
+            name,
+            overflow.original_bytes,
+            overflow.kept_bytes
+        );
+        *output = truncated;
+        Ok(Some(overflow))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toka_store_core::FsBlobStore;
+
+    #[test]
+    fn test_truncation_policies() {
+        let output = "héllo wörld, this is a long line";
+        assert!(truncate_output(output, output.len(), TruncationPolicy::Head, None).is_none());
+
+        // The cut never splits a multi-byte character
+        let (head, overflow) = truncate_output(output, 2, TruncationPolicy::Head, None).unwrap();
+        assert_eq!(overflow.kept_bytes, 1);
+        assert!(head.starts_with("h\n[output truncated: "));
+
+        let (tail, _) = truncate_output(output, 9, TruncationPolicy::Tail, None).unwrap();
+        assert!(tail.ends_with("\nlong line"));
+
+        let (both, overflow) = truncate_output(output, 10, TruncationPolicy::HeadAndTail, None).unwrap();
+        assert!(both.starts_with("héll\n") && both.ends_with("\n line"));
+        assert_eq!(overflow.original_bytes - overflow.kept_bytes, output.len() - 10);
+    }
+
+    #[tokio::test]
+    async fn test_limiter_stores_complete_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let blobs = Arc::new(FsBlobStore::open(dir.path()).unwrap());
+        let limiter = OutputLimiter::new(OutputLimits::unlimited().with_limit("verbose-tool", 16))
+            .with_blob_store(blobs.clone());
+
+        let complete = "x".repeat(100);
+        let mut output = complete.clone();
+        assert_eq!(limiter.apply("other-tool", &mut output).await.unwrap(), None);
+
+        let overflow = limiter.apply("verbose-tool", &mut output).await.unwrap().unwrap();
+        let blob = overflow.blob.unwrap();
+        assert!(output.starts_with(&"x".repeat(16)));
+        assert!(output.contains(&blob.hex()));
+        assert_eq!(blobs.get_bytes(&blob.digest).await.unwrap().unwrap(), complete.into_bytes());
+    }
+}
@@ -0,0 +1,444 @@
+//! Execution quotas per capability token subject.
+//!
+//! Delegated tokens are handed to integrations that are only partly trusted,
+//! so the runtime caps how much each token subject (`Claims.sub`) may run:
+//! how many executions may be in flight at once and how many may start per
+//! UTC day. Quotas are enforced by
+//! [`RuntimeManager::execute_code_with_claims`](crate::RuntimeManager::execute_code_with_claims).
+//!
+//! Usage is kept in a [`QuotaStore`]. [`InMemoryQuotaStore`] suits a single
+//! process; [`StorageQuotaStore`] keeps usage in a shared event store and
+//! updates it with conditional commits, so several runtime processes can
+//! enforce the same quota.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+
+use toka_store_core::{
+    as_conflict, create_event_header_with_format, decode_payload, ConditionalStorage, EventHeader,
+    IntentId, PayloadFormat, Precondition,
+};
+
+/// Event kind of quota usage records in a [`StorageQuotaStore`].
+pub const QUOTA_EVENT_KIND: &str = "runtime.quota.usage";
+
+/// Attempts before a contended [`StorageQuotaStore`] update gives up.
+pub const MAX_QUOTA_RETRIES: usize = 16;
+
+/// Limits applied to one token subject.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionQuota {
+    /// Maximum number of executions running at the same time
+    pub max_concurrent: Option<u32>,
+    /// Maximum number of executions started per UTC day
+    pub max_daily: Option<u32>,
+}
+
+impl ExecutionQuota {
+    /// Quota without limits.
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Limit concurrent executions.
+    pub fn with_max_concurrent(mut self, limit: u32) -> Self {
+        self.max_concurrent = Some(limit);
+        self
+    }
+
+    /// Limit executions per UTC day.
+    pub fn with_max_daily(mut self, limit: u32) -> Self {
+        self.max_daily = Some(limit);
+        self
+    }
+
+    /// Whether the quota limits anything.
+    pub fn is_unlimited(&self) -> bool {
+        self.max_concurrent.is_none() && self.max_daily.is_none()
+    }
+}
+
+/// Default quota plus per-subject overrides.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaPolicy {
+    /// Quota of subjects without an override
+    pub default_quota: ExecutionQuota,
+    /// Quotas of individual subjects
+    pub overrides: HashMap<String, ExecutionQuota>,
+}
+
+impl QuotaPolicy {
+    /// Policy applying `quota` to every subject.
+    pub fn new(quota: ExecutionQuota) -> Self {
+        Self {
+            default_quota: quota,
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Apply `quota` to `subject` instead of the default.
+    pub fn with_override(mut self, subject: impl Into<String>, quota: ExecutionQuota) -> Self {
+        self.overrides.insert(subject.into(), quota);
+        self
+    }
+
+    /// Quota that applies to `subject`.
+    pub fn quota_for(&self, subject: &str) -> ExecutionQuota {
+        self.overrides.get(subject).copied().unwrap_or(self.default_quota)
+    }
+}
+
+/// Errors returned when a quota is exhausted.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum QuotaError {
+    /// Too many executions are already running
+    #[error("subject '{subject}' already has {limit} concurrent executions")]
+    ConcurrentLimit {
+        /// Token subject
+        subject: String,
+        /// Configured limit
+        limit: u32,
+    },
+    /// The daily allowance is used up
+    #[error("subject '{subject}' reached its limit of {limit} executions per day")]
+    DailyLimit {
+        /// Token subject
+        subject: String,
+        /// Configured limit
+        limit: u32,
+    },
+}
+
+/// Quota usage of one subject.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaUsage {
+    /// UTC day `executions_today` refers to
+    pub day: Option<NaiveDate>,
+    /// Executions started on `day`
+    pub executions_today: u32,
+    /// Executions currently running
+    pub active: u32,
+}
+
+impl QuotaUsage {
+    /// Usage as seen at `now`, with the daily count reset on a new day.
+    pub fn at(&self, now: DateTime<Utc>) -> Self {
+        let today = now.date_naive();
+        if self.day == Some(today) {
+            *self
+        } else {
+            Self {
+                day: Some(today),
+                executions_today: 0,
+                active: self.active,
+            }
+        }
+    }
+
+    /// Usage after starting one more execution, if `quota` allows it.
+    pub fn acquire(&self, subject: &str, quota: &ExecutionQuota, now: DateTime<Utc>) -> Result<Self, QuotaError> {
+        let usage = self.at(now);
+        if let Some(limit) = quota.max_concurrent {
+            if usage.active >= limit {
+                return Err(QuotaError::ConcurrentLimit {
+                    subject: subject.to_string(),
+                    limit,
+                });
+            }
+        }
+        if let Some(limit) = quota.max_daily {
+            if usage.executions_today >= limit {
+                return Err(QuotaError::DailyLimit {
+                    subject: subject.to_string(),
+                    limit,
+                });
+            }
+        }
+        Ok(Self {
+            executions_today: usage.executions_today + 1,
+            active: usage.active + 1,
+            ..usage
+        })
+    }
+
+    /// Usage after an execution finished.
+    pub fn release(&self) -> Self {
+        Self {
+            active: self.active.saturating_sub(1),
+            ..*self
+        }
+    }
+}
+
+/// Shared record of quota usage.
+#[async_trait]
+pub trait QuotaStore: Send + Sync {
+    /// Count one execution start for `subject`, failing with a
+    /// [`QuotaError`] if `quota` does not allow it.
+    async fn acquire(&self, subject: &str, quota: &ExecutionQuota, now: DateTime<Utc>) -> Result<QuotaUsage>;
+
+    /// Record that one execution of `subject` finished.
+    async fn release(&self, subject: &str) -> Result<()>;
+
+    /// Current usage of `subject`.
+    async fn usage(&self, subject: &str, now: DateTime<Utc>) -> Result<QuotaUsage>;
+}
+
+/// One execution of a subject counted against its quota.
+///
+/// The execution is released when the lease is dropped, also when the future
+/// running it is dropped mid-flight; the release then runs in a spawned task.
+pub(crate) struct QuotaLease {
+    store: Arc<dyn QuotaStore>,
+    subject: Option<String>,
+}
+
+impl QuotaLease {
+    /// Count one execution start for `subject` in `store`.
+    pub(crate) async fn acquire(
+        store: Arc<dyn QuotaStore>,
+        subject: &str,
+        quota: &ExecutionQuota,
+        now: DateTime<Utc>,
+    ) -> Result<Self> {
+        store.acquire(subject, quota, now).await?;
+        Ok(Self {
+            store,
+            subject: Some(subject.to_string()),
+        })
+    }
+
+    /// Record that the execution finished.
+    pub(crate) async fn release(mut self) {
+        if let Some(subject) = self.subject.take() {
+            release_logged(self.store.as_ref(), &subject).await;
+        }
+    }
+}
+
+impl Drop for QuotaLease {
+    fn drop(&mut self) {
+        let Some(subject) = self.subject.take() else {
+            return;
+        };
+        let store = self.store.clone();
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(async move { release_logged(store.as_ref(), &subject).await });
+            }
+            Err(_) => tracing::warn!("Execution quota of '{}' not released: no Tokio runtime", subject),
+        }
+    }
+}
+
+async fn release_logged(store: &dyn QuotaStore, subject: &str) {
+    if let Err(e) = store.release(subject).await {
+        tracing::warn!("Failed to release execution quota of '{}': {}", subject, e);
+    }
+}
+
+/// Quota usage kept in process memory.
+#[derive(Debug, Default)]
+pub struct InMemoryQuotaStore {
+    usage: Mutex<HashMap<String, QuotaUsage>>,
+}
+
+#[async_trait]
+impl QuotaStore for InMemoryQuotaStore {
+    async fn acquire(&self, subject: &str, quota: &ExecutionQuota, now: DateTime<Utc>) -> Result<QuotaUsage> {
+        let mut usage = self.usage.lock().await;
+        let current = usage.get(subject).copied().unwrap_or_default();
+        let next = current.acquire(subject, quota, now)?;
+        usage.insert(subject.to_string(), next);
+        Ok(next)
+    }
+
+    async fn release(&self, subject: &str) -> Result<()> {
+        if let Some(usage) = self.usage.lock().await.get_mut(subject) {
+            *usage = usage.release();
+        }
+        Ok(())
+    }
+
+    async fn usage(&self, subject: &str, now: DateTime<Utc>) -> Result<QuotaUsage> {
+        Ok(self.usage.lock().await.get(subject).copied().unwrap_or_default().at(now))
+    }
+}
+
+/// Intent holding the [`StorageQuotaStore`] usage records of `subject`.
+pub fn quota_intent(subject: &str) -> IntentId {
+    let digest = Sha256::digest(format!("toka-runtime/quota/{}", subject).as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    IntentId::from_bytes(bytes)
+}
+
+/// Quota usage kept in an event store shared by several processes.
+///
+/// Each subject's usage is a chain of [`QUOTA_EVENT_KIND`] events under an
+/// intent derived from the subject. Updates are committed with
+/// [`Precondition::intent_head`], so concurrent runtimes never both take the
+/// last slot; the loser re-reads and retries.
+pub struct StorageQuotaStore<S: ?Sized> {
+    store: Arc<S>,
+}
+
+impl<S: ConditionalStorage + ?Sized> StorageQuotaStore<S> {
+    /// Keep quota usage in `store`.
+    pub fn new(store: Arc<S>) -> Self {
+        Self { store }
+    }
+
+    /// Latest usage record of `subject`.
+    async fn latest(&self, intent: &IntentId) -> Result<Option<(EventHeader, QuotaUsage)>> {
+        let Some(head) = self.store.intent_head(intent).await? else {
+            return Ok(None);
+        };
+        let bytes = self
+            .store
+            .payload_bytes(&head.digest)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("quota record {} has no payload", head.id))?;
+        let usage = decode_payload(&head, &bytes)?;
+        Ok(Some((head, usage)))
+    }
+
+    /// Apply `update` to the usage of `subject` with compare-and-swap.
+    async fn update<F>(&self, subject: &str, update: F) -> Result<QuotaUsage>
+    where
+        F: Fn(QuotaUsage) -> Result<Option<QuotaUsage>> + Send + Sync,
+    {
+        let intent = quota_intent(subject);
+        for _ in 0..MAX_QUOTA_RETRIES {
+            let latest = self.latest(&intent).await?;
+            let current = latest.as_ref().map(|(_, usage)| *usage).unwrap_or_default();
+            let Some(next) = update(current)? else {
+                return Ok(current);
+            };
+
+            let parents: Vec<EventHeader> = latest.iter().map(|(header, _)| header.clone()).collect();
+            let (header, payload) = create_event_header_with_format(
+                &parents,
+                intent,
+                QUOTA_EVENT_KIND.to_string(),
+                &next,
+                PayloadFormat::MessagePack,
+            )?;
+            let precondition = Precondition::intent_head(intent, latest.map(|(header, _)| header.id));
+            match self.store.commit_if(&header, &payload, &precondition).await {
+                Ok(()) => return Ok(next),
+                Err(e) if as_conflict(&e).is_some() => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        anyhow::bail!("quota usage of '{}' is too contended to update", subject)
+    }
+}
+
+#[async_trait]
+impl<S: ConditionalStorage + ?Sized> QuotaStore for StorageQuotaStore<S> {
+    async fn acquire(&self, subject: &str, quota: &ExecutionQuota, now: DateTime<Utc>) -> Result<QuotaUsage> {
+        self.update(subject, |usage| Ok(Some(usage.acquire(subject, quota, now)?)))
+            .await
+    }
+
+    async fn release(&self, subject: &str) -> Result<()> {
+        self.update(subject, |usage| Ok((usage.active > 0).then(|| usage.release())))
+            .await?;
+        Ok(())
+    }
+
+    async fn usage(&self, subject: &str, now: DateTime<Utc>) -> Result<QuotaUsage> {
+        let latest = self.latest(&quota_intent(subject)).await?;
+        Ok(latest.map(|(_, usage)| usage).unwrap_or_default().at(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn test_in_memory_quota_limits() {
+        let store = InMemoryQuotaStore::default();
+        let quota = ExecutionQuota::unlimited().with_max_concurrent(2).with_max_daily(3);
+        let day_one = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+
+        store.acquire("integration", &quota, day_one).await.unwrap();
+        store.acquire("integration", &quota, day_one).await.unwrap();
+        let err = store.acquire("integration", &quota, day_one).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<QuotaError>(), Some(QuotaError::ConcurrentLimit { limit: 2, .. })));
+
+        // Other subjects have their own usage
+        store.acquire("other", &quota, day_one).await.unwrap();
+
+        store.release("integration").await.unwrap();
+        store.acquire("integration", &quota, day_one).await.unwrap();
+        store.release("integration").await.unwrap();
+        let err = store.acquire("integration", &quota, day_one).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<QuotaError>(), Some(QuotaError::DailyLimit { limit: 3, .. })));
+
+        // The daily allowance resets at midnight UTC
+        let day_two = day_one + chrono::Duration::days(1);
+        let usage = store.acquire("integration", &quota, day_two).await.unwrap();
+        assert_eq!(usage.executions_today, 1);
+        assert_eq!(usage.active, 2);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_lease_is_released() {
+        let store: Arc<dyn QuotaStore> = Arc::new(InMemoryQuotaStore::default());
+        let quota = ExecutionQuota::unlimited().with_max_concurrent(1);
+        let now = Utc::now();
+
+        let lease = QuotaLease::acquire(store.clone(), "integration", &quota, now).await.unwrap();
+        assert!(QuotaLease::acquire(store.clone(), "integration", &quota, now).await.is_err());
+        lease.release().await;
+        assert_eq!(store.usage("integration", now).await.unwrap().active, 0);
+
+        // An execution future dropped mid-flight still gives its slot back
+        let execution = async {
+            let _lease = QuotaLease::acquire(store.clone(), "integration", &quota, now).await.unwrap();
+            std::future::pending::<()>().await
+        };
+        assert!(tokio::time::timeout(std::time::Duration::from_millis(10), execution).await.is_err());
+        tokio::task::yield_now().await;
+        assert_eq!(store.usage("integration", now).await.unwrap().active, 0);
+    }
+
+    #[tokio::test]
+    async fn test_storage_quota_shared_between_runtimes() {
+        let backend = Arc::new(toka_store_memory::MemoryBackend::new());
+        let first = StorageQuotaStore::new(backend.clone());
+        let second = StorageQuotaStore::new(backend);
+        let quota = ExecutionQuota::unlimited().with_max_concurrent(1).with_max_daily(2);
+        let now = Utc::now();
+
+        first.acquire("integration", &quota, now).await.unwrap();
+        let err = second.acquire("integration", &quota, now).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<QuotaError>(), Some(QuotaError::ConcurrentLimit { .. })));
+
+        first.release("integration").await.unwrap();
+        let usage = second.acquire("integration", &quota, now).await.unwrap();
+        assert_eq!(usage.executions_today, 2);
+        assert_eq!(first.usage("integration", now).await.unwrap().active, 1);
+    }
+
+    #[test]
+    fn test_policy_overrides_and_intents() {
+        let policy = QuotaPolicy::new(ExecutionQuota::unlimited().with_max_daily(10))
+            .with_override("ci-bot", ExecutionQuota::unlimited().with_max_concurrent(1));
+        assert_eq!(policy.quota_for("anyone").max_daily, Some(10));
+        assert_eq!(policy.quota_for("ci-bot").max_daily, None);
+        assert_eq!(quota_intent("a"), quota_intent("a"));
+        assert_ne!(quota_intent("a"), quota_intent("b"));
+    }
+}
@@ -0,0 +1,711 @@
+//! OS-level sandboxing of the processes started by execution engines.
+//!
+//! A [`SandboxPolicy`], usually derived from the [`SecurityLevel`] of a
+//! request, is applied to a command before it is spawned:
+//!
+//! - **cgroups v2**: the process runs in its own cgroup below
+//!   [`SandboxPolicy::cgroup_parent`] with `memory.max`, `cpu.max` and
+//!   `pids.max` set. The parent must delegate the controllers the limits
+//!   need through its `cgroup.subtree_control`. Reading the cgroup afterwards
+//!   gives its peak memory and CPU time; dropping it kills what is left of
+//!   the execution.
+//! - **User and network namespaces**: the process gets its own user
+//!   namespace, mapping only its own uid and gid, and its own network
+//!   namespace without any interface but loopback when networking is off.
+//! - **seccomp**: a filter refuses syscalls tool code has no business making,
+//!   such as `ptrace`, `mount`, loading kernel modules or creating namespaces,
+//!   and IPv4/IPv6 sockets when networking is off.
+//! - **rlimits**: a CPU time limit, when the policy has one.
+//!
+//! Mechanisms the host does not offer are skipped with a warning, so the
+//! same policy works on a laptop without delegated cgroups; the returned
+//! [`SandboxGuard`] reports which ones were applied. The seccomp filter is
+//! the exception: a policy asking for one fails to apply if it cannot be
+//! built. On platforms other than Linux nothing is applied.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::{RuntimeResourceUsage, SecurityLevel};
+
+/// Default root of the cgroup v2 hierarchy.
+pub const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Distinguishes the cgroups of concurrent executions.
+static CGROUP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Limits and isolation applied to a sandboxed process.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SandboxPolicy {
+    /// Memory the process and its children may use, in bytes
+    pub memory_limit_bytes: Option<u64>,
+    /// CPU bandwidth in cores, e.g. `0.5` for half a core
+    pub cpu_cores: Option<f64>,
+    /// CPU time the process may use before it is killed
+    pub cpu_time_limit: Option<Duration>,
+    /// Number of processes and threads the process may have at once
+    pub max_processes: Option<u64>,
+    /// Whether to install the seccomp filter
+    pub seccomp: bool,
+    /// Whether to run the process in its own user namespace
+    pub user_namespace: bool,
+    /// Whether the process may use the network
+    pub network: bool,
+    /// cgroup below which per-execution cgroups are created, the runtime's
+    /// own cgroup if unset. It must delegate the controllers of the limits.
+    pub cgroup_parent: Option<PathBuf>,
+}
+
+impl SandboxPolicy {
+    /// Policy enforcing nothing.
+    pub fn unrestricted() -> Self {
+        Self {
+            memory_limit_bytes: None,
+            cpu_cores: None,
+            cpu_time_limit: None,
+            max_processes: None,
+            seccomp: false,
+            user_namespace: false,
+            network: true,
+            cgroup_parent: None,
+        }
+    }
+
+    /// Default policy of a security level.
+    ///
+    /// Levels from [`SecurityLevel::High`] up run without network in their
+    /// own user namespace.
+    pub fn for_level(level: SecurityLevel) -> Self {
+        const MIB: u64 = 1024 * 1024;
+        let (memory_mb, cpu_cores, max_processes) = match level {
+            SecurityLevel::Low => (1024, 2.0, 256),
+            SecurityLevel::Medium => (512, 1.0, 128),
+            SecurityLevel::High => (256, 0.5, 64),
+            SecurityLevel::Restricted => (128, 0.25, 32),
+        };
+        let isolated = matches!(level, SecurityLevel::High | SecurityLevel::Restricted);
+        Self {
+            memory_limit_bytes: Some(memory_mb * MIB),
+            cpu_cores: Some(cpu_cores),
+            cpu_time_limit: None,
+            max_processes: Some(max_processes),
+            seccomp: level != SecurityLevel::Low,
+            user_namespace: isolated,
+            network: !isolated,
+            cgroup_parent: None,
+        }
+    }
+
+    /// Tighten the limits to a resource budget
+    ///
+    /// Non-zero `peak_memory_mb` and `cpu_time_ms` of `limits` cap memory
+    /// and CPU time; zero leaves the limit as it is.
+    pub fn with_usage_limits(mut self, limits: &RuntimeResourceUsage) -> Self {
+        if limits.peak_memory_mb > 0 {
+            let budget = limits.peak_memory_mb * 1024 * 1024;
+            self.memory_limit_bytes = Some(self.memory_limit_bytes.map_or(budget, |limit| limit.min(budget)));
+        }
+        if limits.cpu_time_ms > 0 {
+            let budget = Duration::from_millis(limits.cpu_time_ms);
+            self.cpu_time_limit = Some(self.cpu_time_limit.map_or(budget, |limit| limit.min(budget)));
+        }
+        self
+    }
+
+    /// Allow or forbid networking.
+    pub fn with_network(mut self, network: bool) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Whether a cgroup is needed to enforce the policy.
+    fn needs_cgroup(&self) -> bool {
+        !self.cgroup_controllers().is_empty()
+    }
+
+    /// cgroup controllers enforcing the policy's limits.
+    fn cgroup_controllers(&self) -> Vec<&'static str> {
+        [
+            (self.memory_limit_bytes.is_some(), "memory"),
+            (self.cpu_cores.is_some(), "cpu"),
+            (self.max_processes.is_some(), "pids"),
+        ]
+        .into_iter()
+        .filter_map(|(needed, controller)| needed.then_some(controller))
+        .collect()
+    }
+
+    /// Apply the policy to `command`, which must be spawned while the
+    /// returned guard is alive.
+    ///
+    /// Fails if the policy asks for a seccomp filter that cannot be built.
+    pub fn apply(&self, command: &mut Command) -> anyhow::Result<SandboxGuard> {
+        let mut guard = SandboxGuard::default();
+        if self.needs_cgroup() {
+            let parent = self.cgroup_parent.clone().or_else(own_cgroup);
+            match parent.map(|parent| Cgroup::create(&parent, self)) {
+                Some(Ok(cgroup)) => guard.cgroup = Some(cgroup),
+                Some(Err(e)) => tracing::warn!("Running without cgroup limits: {}", e),
+                None => tracing::warn!("Running without cgroup limits: no cgroup v2 hierarchy"),
+            }
+        }
+        platform::apply(self, command, &mut guard)?;
+        Ok(guard)
+    }
+}
+
+/// Mechanisms applied to a sandboxed process; removes its cgroup when dropped.
+#[derive(Debug, Default)]
+pub struct SandboxGuard {
+    cgroup: Option<Cgroup>,
+    /// Whether the process got its own user namespace
+    pub user_namespace: bool,
+    /// Whether the process got its own network namespace
+    pub network_namespace: bool,
+    /// Whether the seccomp filter was installed
+    pub seccomp: bool,
+    /// Whether a CPU time limit was set
+    pub cpu_time_limit: bool,
+}
+
+impl SandboxGuard {
+    /// Whether the process runs in a cgroup with the policy's limits.
+    pub fn cgroup_limits(&self) -> bool {
+        self.cgroup.is_some()
+    }
+
+    /// Peak memory in bytes and CPU time of the process, if it ran in a cgroup.
+    pub fn usage(&self) -> Option<(u64, Duration)> {
+        let cgroup = self.cgroup.as_ref()?;
+        let peak = read_trimmed(&cgroup.path.join("memory.peak"))
+            .or_else(|| read_trimmed(&cgroup.path.join("memory.current")))
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0);
+        let cpu_usec = read_trimmed(&cgroup.path.join("cpu.stat"))
+            .and_then(|stat| {
+                stat.lines()
+                    .find_map(|line| line.strip_prefix("usage_usec "))
+                    .and_then(|value| value.parse().ok())
+            })
+            .unwrap_or(0);
+        Some((peak, Duration::from_micros(cpu_usec)))
+    }
+
+    /// Fill `usage` from the cgroup of the process, if it ran in one.
+    pub fn record_usage(&self, usage: &mut RuntimeResourceUsage) {
+        if let Some((peak, cpu_time)) = self.usage() {
+            usage.peak_memory_mb = usage.peak_memory_mb.max(peak / (1024 * 1024));
+            usage.cpu_time_ms = cpu_time.as_millis() as u64;
+        }
+    }
+}
+
+/// Per-execution cgroup.
+#[derive(Debug)]
+struct Cgroup {
+    path: PathBuf,
+    /// `cgroup.procs`, opened before the process starts so it can move
+    /// itself in before running any code
+    procs: std::fs::File,
+}
+
+impl Cgroup {
+    fn create(parent: &Path, policy: &SandboxPolicy) -> std::io::Result<Self> {
+        // Children only get the controllers enabled in the parent's subtree
+        let delegated = read_trimmed(&parent.join("cgroup.subtree_control")).unwrap_or_default();
+        let missing: Vec<_> = policy
+            .cgroup_controllers()
+            .into_iter()
+            .filter(|controller| !delegated.split_whitespace().any(|enabled| enabled == *controller))
+            .collect();
+        if !missing.is_empty() {
+            return Err(std::io::Error::other(format!(
+                "{} does not delegate the {} controller(s)",
+                parent.display(),
+                missing.join(", ")
+            )));
+        }
+
+        let path = parent.join(format!(
+            "toka-{}-{}",
+            std::process::id(),
+            CGROUP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir(&path)?;
+        let cgroup = Self::configure(&path, policy);
+        if cgroup.is_err() {
+            let _ = std::fs::remove_dir(&path);
+        }
+        cgroup
+    }
+
+    fn configure(path: &Path, policy: &SandboxPolicy) -> std::io::Result<Self> {
+        if let Some(limit) = policy.memory_limit_bytes {
+            std::fs::write(path.join("memory.max"), limit.to_string())?;
+            // Swap would let the process exceed its memory limit
+            let _ = std::fs::write(path.join("memory.swap.max"), "0");
+        }
+        if let Some(cores) = policy.cpu_cores {
+            const PERIOD_USEC: u64 = 100_000;
+            let quota = ((cores * PERIOD_USEC as f64) as u64).max(1_000);
+            std::fs::write(path.join("cpu.max"), format!("{} {}", quota, PERIOD_USEC))?;
+        }
+        if let Some(limit) = policy.max_processes {
+            std::fs::write(path.join("pids.max"), limit.to_string())?;
+        }
+        let procs = std::fs::OpenOptions::new().write(true).open(path.join("cgroup.procs"))?;
+        Ok(Self { path: path.to_path_buf(), procs })
+    }
+}
+
+impl Cgroup {
+    /// Whether processes are left in the cgroup or its descendants.
+    fn populated(path: &Path) -> bool {
+        read_trimmed(&path.join("cgroup.events"))
+            .is_some_and(|events| events.lines().any(|line| line == "populated 1"))
+    }
+
+    /// Wait for the killed processes to exit, then remove the cgroup.
+    fn remove(path: &Path) {
+        const ATTEMPTS: u32 = 100;
+        for _ in 0..ATTEMPTS {
+            if !Self::populated(path) {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        if let Err(e) = std::fs::remove_dir(path) {
+            tracing::warn!("Failed to remove cgroup {}: {}", path.display(), e);
+        }
+    }
+}
+
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        // Processes of the execution may outlive it, e.g. daemonized children
+        if Self::populated(&self.path) && std::fs::write(self.path.join("cgroup.kill"), "1").is_err() {
+            // cgroup.kill needs Linux 5.14; signal the members one by one
+            #[cfg(target_os = "linux")]
+            for pid in read_trimmed(&self.path.join("cgroup.procs")).unwrap_or_default().lines() {
+                if let Ok(pid) = pid.parse() {
+                    // SAFETY: kill has no memory safety requirements.
+                    unsafe { libc::kill(pid, libc::SIGKILL) };
+                }
+            }
+        }
+        if Self::populated(&self.path) {
+            // Killed processes take a moment to exit; don't block the caller
+            let path = self.path.clone();
+            std::thread::spawn(move || Self::remove(&path));
+        } else {
+            Self::remove(&self.path);
+        }
+    }
+}
+
+/// cgroup v2 directory of the runtime process, if it has one.
+///
+/// It can only hold per-execution cgroups if it delegates controllers, which
+/// a cgroup with processes of its own cannot do below the root.
+fn own_cgroup() -> Option<PathBuf> {
+    let membership = std::fs::read_to_string("/proc/self/cgroup").ok()?;
+    let relative = membership.lines().find_map(|line| line.strip_prefix("0::"))?;
+    let path = Path::new(CGROUP_ROOT).join(relative.trim_start_matches('/'));
+    path.join("cgroup.controllers").exists().then_some(path)
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path).ok().map(|value| value.trim().to_string())
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::collections::BTreeMap;
+    use std::ffi::CString;
+    use std::os::fd::AsRawFd;
+    use std::sync::OnceLock;
+
+    use seccompiler::{
+        BpfProgram, SeccompAction, SeccompCmpArgLen, SeccompCmpOp, SeccompCondition, SeccompFilter, SeccompRule,
+    };
+
+    use super::{SandboxGuard, SandboxPolicy};
+
+    /// Syscalls refused by the seccomp filter.
+    const DENIED_SYSCALLS: &[libc::c_long] = &[
+        libc::SYS_ptrace,
+        libc::SYS_process_vm_readv,
+        libc::SYS_process_vm_writev,
+        libc::SYS_mount,
+        libc::SYS_umount2,
+        libc::SYS_pivot_root,
+        libc::SYS_swapon,
+        libc::SYS_swapoff,
+        libc::SYS_reboot,
+        libc::SYS_kexec_load,
+        libc::SYS_kexec_file_load,
+        libc::SYS_init_module,
+        libc::SYS_finit_module,
+        libc::SYS_delete_module,
+        libc::SYS_bpf,
+        libc::SYS_perf_event_open,
+        libc::SYS_keyctl,
+        libc::SYS_add_key,
+        libc::SYS_request_key,
+        libc::SYS_acct,
+        libc::SYS_settimeofday,
+        libc::SYS_clock_settime,
+        libc::SYS_setns,
+        libc::SYS_unshare,
+        libc::SYS_open_by_handle_at,
+        libc::SYS_userfaultfd,
+    ];
+
+    /// `clone` flags creating namespaces, which `unshare` is refused for too.
+    const NAMESPACE_FLAGS: &[libc::c_int] = &[
+        libc::CLONE_NEWUSER,
+        libc::CLONE_NEWNS,
+        libc::CLONE_NEWNET,
+        libc::CLONE_NEWPID,
+        libc::CLONE_NEWIPC,
+        libc::CLONE_NEWUTS,
+        libc::CLONE_NEWCGROUP,
+    ];
+
+    /// seccomp filters of `policy`, to be installed together.
+    // Syscall numbers are `c_long`, which is only `i64` on 64-bit targets
+    #[allow(clippy::unnecessary_cast)]
+    pub(super) fn seccomp_filters(policy: &SandboxPolicy) -> Result<Vec<BpfProgram>, seccompiler::Error> {
+        let arch = std::env::consts::ARCH.try_into()?;
+        let rule = |arg: u8, len: SeccompCmpArgLen, op: SeccompCmpOp, value: u64| {
+            SeccompCondition::new(arg, len, op, value).and_then(|condition| SeccompRule::new(vec![condition]))
+        };
+
+        let mut rules: BTreeMap<i64, Vec<SeccompRule>> =
+            DENIED_SYSCALLS.iter().map(|syscall| (*syscall as i64, Vec::new())).collect();
+        let namespace_rules = NAMESPACE_FLAGS
+            .iter()
+            .map(|flag| rule(0, SeccompCmpArgLen::Qword, SeccompCmpOp::MaskedEq(*flag as u64), *flag as u64))
+            .collect::<Result<_, _>>()?;
+        rules.insert(libc::SYS_clone as i64, namespace_rules);
+        if !policy.network {
+            let family = |family: libc::c_int| rule(0, SeccompCmpArgLen::Dword, SeccompCmpOp::Eq, family as u64);
+            rules.insert(libc::SYS_socket as i64, vec![family(libc::AF_INET)?, family(libc::AF_INET6)?]);
+        }
+        let denied = SeccompFilter::new(rules, SeccompAction::Allow, SeccompAction::Errno(libc::EPERM as u32), arch)?;
+
+        // clone3 passes its flags in memory, out of the filter's reach.
+        // ENOSYS makes libc fall back to clone, whose flags are checked.
+        let clone3 = SeccompFilter::new(
+            BTreeMap::from([(libc::SYS_clone3 as i64, Vec::new())]),
+            SeccompAction::Allow,
+            SeccompAction::Errno(libc::ENOSYS as u32),
+            arch,
+        )?;
+        Ok(vec![denied.try_into()?, clone3.try_into()?])
+    }
+
+    /// Maps the process's own uid and gid into a new user namespace.
+    struct IdentityMap {
+        uid_map: Vec<u8>,
+        gid_map: Vec<u8>,
+        uid_map_path: CString,
+        gid_map_path: CString,
+        setgroups_path: CString,
+    }
+
+    impl IdentityMap {
+        fn new() -> Self {
+            // SAFETY: getuid and getgid cannot fail.
+            let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+            Self {
+                uid_map: format!("{} {} 1", uid, uid).into_bytes(),
+                gid_map: format!("{} {} 1", gid, gid).into_bytes(),
+                uid_map_path: CString::new("/proc/self/uid_map").expect("no NUL in path"),
+                gid_map_path: CString::new("/proc/self/gid_map").expect("no NUL in path"),
+                setgroups_path: CString::new("/proc/self/setgroups").expect("no NUL in path"),
+            }
+        }
+
+        /// Enter new namespaces and map the identity, without allocating.
+        fn unshare(&self, clone_flags: libc::c_int) -> std::io::Result<()> {
+            // SAFETY: only called in single-threaded children between fork and exec.
+            if unsafe { libc::unshare(clone_flags) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            write_file(&self.setgroups_path, b"deny")?;
+            write_file(&self.uid_map_path, &self.uid_map)?;
+            write_file(&self.gid_map_path, &self.gid_map)
+        }
+    }
+
+    /// Whether this process may enter new namespaces with `clone_flags`.
+    ///
+    /// `max_user_namespaces` alone does not tell: LSMs and sysctls such as
+    /// `kernel.unprivileged_userns_clone` refuse them too. A forked child
+    /// tries for real, once per set of flags.
+    pub(super) fn namespaces_available(clone_flags: libc::c_int) -> bool {
+        static USER: OnceLock<bool> = OnceLock::new();
+        static USER_AND_NETWORK: OnceLock<bool> = OnceLock::new();
+        let probe = if clone_flags & libc::CLONE_NEWNET != 0 { &USER_AND_NETWORK } else { &USER };
+        *probe.get_or_init(|| {
+            let identity = IdentityMap::new();
+            // SAFETY: the child only makes async-signal-safe syscalls on data
+            // prepared before the fork, then exits without unwinding.
+            unsafe {
+                match libc::fork() {
+                    -1 => false,
+                    0 => libc::_exit(if identity.unshare(clone_flags).is_ok() { 0 } else { 1 }),
+                    child => {
+                        let mut status = 0;
+                        while libc::waitpid(child, &mut status, 0) < 0 {
+                            if std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+                                return false;
+                            }
+                        }
+                        libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0
+                    }
+                }
+            }
+        })
+    }
+
+    /// Write `contents` to the file at `path`, without allocating.
+    fn write_file(path: &CString, contents: &[u8]) -> std::io::Result<()> {
+        // SAFETY: `path` is NUL-terminated and `contents` outlives the call.
+        unsafe {
+            let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+            if fd < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let written = libc::write(fd, contents.as_ptr().cast(), contents.len());
+            libc::close(fd);
+            if written < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn apply(
+        policy: &SandboxPolicy,
+        command: &mut tokio::process::Command,
+        guard: &mut SandboxGuard,
+    ) -> anyhow::Result<()> {
+        let cgroup_procs = guard.cgroup.as_ref().map(|cgroup| cgroup.procs.as_raw_fd());
+
+        let mut clone_flags = 0;
+        if policy.user_namespace || !policy.network {
+            clone_flags = libc::CLONE_NEWUSER;
+            if !policy.network {
+                clone_flags |= libc::CLONE_NEWNET;
+            }
+            if !namespaces_available(clone_flags) {
+                tracing::warn!("Running without namespaces: this process may not create user namespaces");
+                clone_flags = 0;
+            }
+        }
+        // Only set once a probe entered the same namespaces successfully
+        guard.user_namespace = clone_flags & libc::CLONE_NEWUSER != 0;
+        guard.network_namespace = clone_flags & libc::CLONE_NEWNET != 0;
+        let identity = IdentityMap::new();
+
+        let cpu_seconds = policy.cpu_time_limit.map(|limit| limit.as_secs().max(1));
+        guard.cpu_time_limit = cpu_seconds.is_some();
+
+        let filters = if policy.seccomp {
+            seccomp_filters(policy).map_err(|e| anyhow::anyhow!("Failed to build seccomp filter: {}", e))?
+        } else {
+            Vec::new()
+        };
+        guard.seccomp = !filters.is_empty();
+
+        let hook = move || -> std::io::Result<()> {
+            if let Some(fd) = cgroup_procs {
+                // SAFETY: the cgroup.procs file stays open in the guard until
+                // the child has been spawned; "0" moves the writing process.
+                if unsafe { libc::write(fd, b"0".as_ptr().cast(), 1) } < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            if clone_flags != 0 {
+                identity.unshare(clone_flags)?;
+            }
+            if let Some(seconds) = cpu_seconds {
+                let limit = libc::rlimit { rlim_cur: seconds, rlim_max: seconds };
+                // SAFETY: `limit` is a valid rlimit.
+                if unsafe { libc::setrlimit(libc::RLIMIT_CPU, &limit) } != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            for filter in &filters {
+                seccompiler::apply_filter(filter).map_err(std::io::Error::other)?;
+            }
+            Ok(())
+        };
+        // SAFETY: the hook only makes async-signal-safe syscalls on data
+        // prepared before the fork.
+        unsafe {
+            command.pre_exec(hook);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use super::{SandboxGuard, SandboxPolicy};
+
+    pub(super) fn apply(
+        policy: &SandboxPolicy,
+        _command: &mut tokio::process::Command,
+        _guard: &mut SandboxGuard,
+    ) -> anyhow::Result<()> {
+        if policy.seccomp || policy.user_namespace || !policy.network || policy.cpu_time_limit.is_some() {
+            tracing::warn!("Process sandboxing is only supported on Linux; running unsandboxed");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn run(policy: &SandboxPolicy, script: &str) -> (std::process::Output, SandboxGuard) {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        let guard = policy.apply(&mut command).unwrap();
+        (command.output().await.unwrap(), guard)
+    }
+
+    #[test]
+    fn test_level_policies_and_usage_limits() {
+        let restricted = SandboxPolicy::for_level(SecurityLevel::Restricted);
+        assert!(restricted.seccomp && restricted.user_namespace && !restricted.network);
+        assert!(SandboxPolicy::for_level(SecurityLevel::Low).network);
+
+        let limits = RuntimeResourceUsage {
+            peak_memory_mb: 64,
+            cpu_time_ms: 2_000,
+            syscall_count: 0,
+            files_accessed: Vec::new(),
+            network_attempts: 0,
+        };
+        let budgeted = restricted.with_usage_limits(&limits);
+        assert_eq!(budgeted.memory_limit_bytes, Some(64 * 1024 * 1024));
+        assert_eq!(budgeted.cpu_time_limit, Some(Duration::from_secs(2)));
+
+        // A budget above the level's limit does not loosen it
+        let generous = SandboxPolicy::for_level(SecurityLevel::Restricted)
+            .with_usage_limits(&RuntimeResourceUsage { peak_memory_mb: 4096, ..limits });
+        assert_eq!(generous.memory_limit_bytes, Some(128 * 1024 * 1024));
+    }
+
+    #[test]
+    fn test_cgroup_limits_written() {
+        let parent = tempfile::tempdir().unwrap();
+        let policy = SandboxPolicy {
+            cgroup_parent: Some(parent.path().to_path_buf()),
+            ..SandboxPolicy::for_level(SecurityLevel::High)
+        };
+        // A plain directory stands in for the cgroup hierarchy
+        let dir = parent.path().join("cgroup");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(dir.join("cgroup.procs"), "").unwrap();
+        let cgroup = Cgroup::configure(&dir, &policy).unwrap();
+
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read("memory.max"), (256 * 1024 * 1024).to_string());
+        assert_eq!(read("cpu.max"), "50000 100000");
+        assert_eq!(read("pids.max"), "64");
+
+        std::fs::write(dir.join("memory.peak"), "3145728\n").unwrap();
+        std::fs::write(dir.join("cpu.stat"), "usage_usec 1500\nuser_usec 1000\n").unwrap();
+        let guard = SandboxGuard { cgroup: Some(cgroup), ..SandboxGuard::default() };
+        assert_eq!(guard.usage(), Some((3 * 1024 * 1024, Duration::from_micros(1500))));
+    }
+
+    #[test]
+    fn test_cgroup_requires_delegated_controllers() {
+        let parent = tempfile::tempdir().unwrap();
+        std::fs::write(parent.path().join("cgroup.subtree_control"), "cpu pids\n").unwrap();
+
+        let error = Cgroup::create(parent.path(), &SandboxPolicy::for_level(SecurityLevel::High)).unwrap_err();
+        assert!(error.to_string().contains("memory"), "{}", error);
+        assert_eq!(std::fs::read_dir(parent.path()).unwrap().count(), 1, "no child cgroup is created");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_seccomp_refuses_namespace_clones() {
+        let filters = platform::seccomp_filters(&SandboxPolicy::for_level(SecurityLevel::Medium)).unwrap();
+        // SAFETY: the child only makes syscalls on data prepared before the
+        // fork, then exits without unwinding.
+        let status = unsafe {
+            match libc::fork() {
+                0 => {
+                    if filters.iter().any(|filter| seccompiler::apply_filter(filter).is_err()) {
+                        libc::_exit(2);
+                    }
+                    let flags = (libc::CLONE_NEWUSER | libc::SIGCHLD) as libc::c_ulong;
+                    let clone = libc::syscall(libc::SYS_clone, flags, 0, 0, 0, 0);
+                    let clone_errno = *libc::__errno_location();
+                    let clone3 = libc::syscall(libc::SYS_clone3, std::ptr::null::<u8>(), 0);
+                    let clone3_errno = *libc::__errno_location();
+                    let refused =
+                        clone == -1 && clone_errno == libc::EPERM && clone3 == -1 && clone3_errno == libc::ENOSYS;
+                    libc::_exit(if refused { 0 } else { 1 });
+                }
+                child => {
+                    let mut status = 0;
+                    libc::waitpid(child, &mut status, 0);
+                    status
+                }
+            }
+        };
+        assert!(libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0, "status {}", status);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    #[ignore = "needs unprivileged user and network namespaces; run with --ignored where the host allows them"]
+    async fn test_seccomp_and_namespaces() {
+        assert!(
+            platform::namespaces_available(libc::CLONE_NEWUSER | libc::CLONE_NEWNET),
+            "unprivileged user namespaces are unavailable"
+        );
+        let policy = SandboxPolicy {
+            memory_limit_bytes: None,
+            cpu_cores: None,
+            max_processes: None,
+            ..SandboxPolicy::for_level(SecurityLevel::Restricted)
+        };
+        let (output, guard) = run(&policy, "cat /proc/net/dev | wc -l; id -u; unshare -r true || echo denied").await;
+        assert!(guard.seccomp && guard.user_namespace && guard.network_namespace);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let lines: Vec<&str> = stdout.lines().collect();
+
+        // SAFETY: getuid cannot fail.
+        let uid = unsafe { libc::getuid() };
+        assert_eq!(lines[1], uid.to_string(), "identity is kept in the user namespace");
+        assert_eq!(lines[2], "denied");
+        assert_eq!(lines[0].trim(), "3", "only loopback below the two header lines");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_cpu_time_limit() {
+        let policy = SandboxPolicy {
+            cpu_time_limit: Some(Duration::from_secs(1)),
+            ..SandboxPolicy::unrestricted()
+        };
+        let (output, guard) = run(&policy, "while :; do :; done").await;
+        assert!(guard.cpu_time_limit);
+        assert!(!output.status.success());
+    }
+}
@@ -0,0 +1,191 @@
+//! Output streamed while an execution runs.
+//!
+//! [`RuntimeManager::execute_streaming`] starts an execution like
+//! [`RuntimeManager::execute_with_handle`] and returns an
+//! [`ExecutionStream`], a stream of [`OutputChunk`]s ending when the
+//! execution finishes. Engines send output through the [`OutputSink`] in
+//! [`ExecutionContext::output`] as it is produced; the process engines
+//! stream the output they keep, up to their output cap. For engines that do
+//! not stream, the runtime sends the output of the result once the
+//! execution finishes.
+//!
+//! Chunks are split at UTF-8 character boundaries, so every chunk is valid
+//! text on its own.
+//!
+//! [`ExecutionContext::output`]: crate::ExecutionContext::output
+
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::{ExecutionHandle, ExecutionResult, RuntimeManager};
+
+/// Stream a chunk of output was written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputStream {
+    /// Standard output
+    Stdout,
+    /// Standard error
+    Stderr,
+}
+
+/// Piece of output written by a running execution.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputChunk {
+    /// Stream the output was written to
+    pub stream: OutputStream,
+    /// The output
+    pub data: String,
+    /// When the runtime received the output
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Sending end of a streaming execution's output.
+#[derive(Debug, Clone)]
+pub struct OutputSink {
+    sender: mpsc::UnboundedSender<OutputChunk>,
+    used: Arc<AtomicBool>,
+}
+
+impl OutputSink {
+    /// Create a sink and the receiver of its chunks.
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<OutputChunk>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (Self { sender, used: Arc::new(AtomicBool::new(false)) }, receiver)
+    }
+
+    /// Send `data` written to `stream`; empty data is dropped.
+    ///
+    /// Output sent after the consumer went away is discarded.
+    pub fn send(&self, stream: OutputStream, data: impl Into<String>) {
+        let data = data.into();
+        if !data.is_empty() {
+            self.forward(OutputChunk { stream, data, timestamp: Utc::now() });
+        }
+    }
+
+    /// Send a chunk received from another sink.
+    pub(crate) fn forward(&self, chunk: OutputChunk) {
+        self.used.store(true, Ordering::Relaxed);
+        let _ = self.sender.send(chunk);
+    }
+
+    /// Send the output of `result`, unless the engine streamed any output.
+    pub(crate) fn replay(&self, result: &ExecutionResult) {
+        if !self.used.load(Ordering::Relaxed) {
+            self.send(OutputStream::Stdout, result.output.as_str());
+            self.send(OutputStream::Stderr, result.error.as_str());
+        }
+    }
+}
+
+/// Output of an execution running in the background, ending when the
+/// execution finishes.
+#[derive(Debug)]
+pub struct ExecutionStream {
+    chunks: mpsc::UnboundedReceiver<OutputChunk>,
+    handle: ExecutionHandle,
+}
+
+impl ExecutionStream {
+    /// Start executing `request` on `runtime`, streaming its output.
+    pub(crate) fn spawn(runtime: Arc<RuntimeManager>, request: crate::ExecutionRequest) -> Self {
+        let (sink, chunks) = OutputSink::channel();
+        let handle = ExecutionHandle::spawn(runtime, request, Some(sink));
+        Self { chunks, handle }
+    }
+
+    /// Handle to cancel or kill the execution.
+    pub fn handle(&self) -> &ExecutionHandle {
+        &self.handle
+    }
+
+    /// Wait for the execution to finish, dropping output not yet received.
+    pub async fn await_result(self) -> Result<ExecutionResult> {
+        self.handle.await_result().await
+    }
+}
+
+impl futures::Stream for ExecutionStream {
+    type Item = OutputChunk;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<OutputChunk>> {
+        self.chunks.poll_recv(cx)
+    }
+}
+
+/// Split `bytes` into the text made of its complete UTF-8 characters and the
+/// length of that text in bytes.
+///
+/// An incomplete character at the end is left for the next call, unless
+/// `last` is set; invalid bytes are replaced.
+#[cfg(any(feature = "javascript", feature = "python", feature = "shell"))]
+pub(crate) fn decode_complete(bytes: &[u8], last: bool) -> (String, usize) {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => (text.to_string(), bytes.len()),
+        Err(e) if e.error_len().is_none() && !last => {
+            let valid = e.valid_up_to();
+            (String::from_utf8_lossy(&bytes[..valid]).into_owned(), valid)
+        }
+        Err(_) => (String::from_utf8_lossy(bytes).into_owned(), bytes.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(any(feature = "javascript", feature = "python", feature = "shell"))]
+    #[test]
+    fn test_decode_keeps_split_characters() {
+        let text = "añb".as_bytes();
+        assert_eq!(decode_complete(&text[..2], false), ("a".to_string(), 1));
+        assert_eq!(decode_complete(&text[1..], false), ("ñb".to_string(), 3));
+        assert_eq!(decode_complete(&text[..2], true), ("a\u{fffd}".to_string(), 2));
+        assert_eq!(decode_complete(b"a\xffb", false), ("a\u{fffd}b".to_string(), 3));
+    }
+
+    #[tokio::test]
+    async fn test_replay_only_without_streamed_output() {
+        let (sink, mut chunks) = OutputSink::channel();
+        let result: ExecutionResult = serde_json::from_value(serde_json::json!({
+            "success": true,
+            "output": "out",
+            "error": "",
+            "exit_code": 0,
+            "metadata": {
+                "code_type": "Shell",
+                "session_id": "test",
+                "duration": {"secs": 0, "nanos": 0},
+                "resource_usage": {
+                    "peak_memory_mb": 0,
+                    "cpu_time_ms": 0,
+                    "syscall_count": 0,
+                    "files_accessed": [],
+                    "network_attempts": 0
+                },
+                "security_level": "Low",
+                "engine_version": "1",
+                "executed_at": {"secs_since_epoch": 0, "nanos_since_epoch": 0}
+            },
+            "artifacts": []
+        }))
+        .unwrap();
+
+        sink.replay(&result);
+        let chunk = chunks.recv().await.unwrap();
+        assert_eq!((chunk.stream, chunk.data.as_str()), (OutputStream::Stdout, "out"));
+
+        // Empty stderr is not sent, and once output went out nothing is replayed
+        sink.replay(&result);
+        drop(sink);
+        assert!(chunks.recv().await.is_none());
+    }
+}
@@ -0,0 +1,267 @@
+//! Per-execution timeouts.
+//!
+//! [`RuntimeManager`] enforces [`ExecutionRequest::timeout_override`] for
+//! every engine. Once the timeout elapses the execution is cancelled, so
+//! engines stop cooperatively and return what it produced until then, and
+//! the runtime waits up to [`TIMEOUT_GRACE_PERIOD`] for them. An engine that
+//! does not return in time is dropped, and the result is built from the
+//! output it streamed before the timeout.
+//!
+//! Either way the result fails with a timeout error, and
+//! [`KernelEvent::TaskTimeout`] is published for the agent named in the
+//! request, if any.
+//!
+//! [`RuntimeManager`]: crate::RuntimeManager
+//! [`KernelEvent::TaskTimeout`]: toka_bus_core::KernelEvent::TaskTimeout
+
+use std::future::Future;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    ExecutionRequest, ExecutionResult, OutputChunk, OutputSink, OutputStream, RuntimeMetadata, RuntimeResourceUsage,
+    StopReason,
+};
+
+/// How long a timed out execution may take to stop before it is dropped.
+pub const TIMEOUT_GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+/// Error reported for executions stopped by their timeout.
+pub(crate) fn timeout_message(timeout: Duration) -> String {
+    format!("execution timed out after {:?}", timeout)
+}
+
+/// Output an execution streamed so far.
+#[derive(Debug, Default)]
+pub(crate) struct PartialOutput {
+    stdout: String,
+    stderr: String,
+}
+
+impl PartialOutput {
+    fn record(&mut self, chunk: &OutputChunk) {
+        match chunk.stream {
+            OutputStream::Stdout => self.stdout.push_str(&chunk.data),
+            OutputStream::Stderr => self.stderr.push_str(&chunk.data),
+        }
+    }
+
+    /// Failed result carrying this output, for an execution that never
+    /// returned one.
+    pub(crate) fn into_result(
+        self,
+        request: &ExecutionRequest,
+        engine_version: String,
+        executed_at: SystemTime,
+        duration: Duration,
+    ) -> ExecutionResult {
+        ExecutionResult {
+            success: false,
+            output: self.stdout,
+            error: self.stderr,
+            exit_code: None,
+            metadata: RuntimeMetadata {
+                code_type: request.code_type.clone(),
+                session_id: request.session_id.clone(),
+                duration,
+                resource_usage: RuntimeResourceUsage {
+                    peak_memory_mb: 0,
+                    cpu_time_ms: duration.as_millis() as u64,
+                    syscall_count: 0,
+                    files_accessed: Vec::new(),
+                    network_attempts: 0,
+                },
+                security_level: request.security_level,
+                engine_version,
+                executed_at,
+            },
+            artifacts: Vec::new(),
+            stopped: None,
+        }
+    }
+}
+
+/// How an execution run by [`run_with_timeout`] ended.
+#[derive(Debug)]
+pub(crate) enum Completion {
+    /// The execution finished before its timeout
+    Finished(Result<ExecutionResult>),
+    /// The execution timed out
+    TimedOut {
+        /// The timeout that elapsed
+        timeout: Duration,
+        /// What the engine returned, if it stopped within the grace period
+        result: Option<Result<ExecutionResult>>,
+        /// Output streamed until the execution stopped or was dropped
+        partial: PartialOutput,
+    },
+}
+
+/// Run `execution`, relaying the output it sends through `chunks` to
+/// `output`, and cancel it through `cancellation` once `timeout` elapses.
+///
+/// Results of engines that enforced the timeout themselves also count as
+/// timed out.
+pub(crate) async fn run_with_timeout<F>(
+    execution: F,
+    chunks: &mut mpsc::UnboundedReceiver<OutputChunk>,
+    output: Option<&OutputSink>,
+    timeout: Option<Duration>,
+    cancellation: &CancellationToken,
+) -> Completion
+where
+    F: Future<Output = Result<ExecutionResult>>,
+{
+    tokio::pin!(execution);
+    let mut relay = Relay { chunks, output, partial: PartialOutput::default() };
+
+    let Some(timeout) = timeout else {
+        let result = relay.until(execution.as_mut(), std::future::pending::<()>()).await;
+        relay.drain();
+        return Completion::Finished(result.expect("execution without timeout finished"));
+    };
+
+    let result = match relay.until(execution.as_mut(), tokio::time::sleep(timeout)).await {
+        Some(Ok(result)) if !result.success && matches!(result.stopped, Some(StopReason::TimedOut(_))) => Some(Ok(result)),
+        Some(result) => {
+            relay.drain();
+            return Completion::Finished(result);
+        }
+        None => {
+            cancellation.cancel();
+            relay.until(execution.as_mut(), tokio::time::sleep(TIMEOUT_GRACE_PERIOD)).await
+        }
+    };
+    relay.drain();
+    Completion::TimedOut { timeout, result, partial: relay.partial }
+}
+
+/// Forwards streamed output while keeping a copy of it.
+struct Relay<'a> {
+    chunks: &'a mut mpsc::UnboundedReceiver<OutputChunk>,
+    output: Option<&'a OutputSink>,
+    partial: PartialOutput,
+}
+
+impl Relay<'_> {
+    /// Relay output until `execution` finishes, or `None` once `stop`
+    /// completes first.
+    async fn until<F>(&mut self, mut execution: std::pin::Pin<&mut F>, stop: impl Future) -> Option<F::Output>
+    where
+        F: Future,
+    {
+        tokio::pin!(stop);
+        loop {
+            tokio::select! {
+                result = &mut execution => return Some(result),
+                Some(chunk) = self.chunks.recv() => self.forward(chunk),
+                _ = &mut stop => return None,
+            }
+        }
+    }
+
+    /// Relay output sent but not received yet.
+    fn drain(&mut self) {
+        while let Ok(chunk) = self.chunks.try_recv() {
+            self.forward(chunk);
+        }
+    }
+
+    fn forward(&mut self, chunk: OutputChunk) {
+        self.partial.record(&chunk);
+        if let Some(output) = self.output {
+            output.forward(chunk);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CodeType, SecurityLevel};
+
+    fn request() -> ExecutionRequest {
+        ExecutionRequest {
+            code_type: CodeType::Shell,
+            code: "tick".to_string(),
+            session_id: "session".to_string(),
+            security_level: SecurityLevel::Restricted,
+            inputs: serde_json::json!({}),
+            timeout_override: Some(Duration::from_millis(100)),
+            environment: None,
+            agent: None,
+            correlation_id: None,
+        }
+    }
+
+    /// Execution printing a tick every 10ms, until cancelled when it honours
+    /// cancellation.
+    async fn ticker(sink: OutputSink, cancellation: CancellationToken, honours: bool) -> Result<ExecutionResult> {
+        let mut output = PartialOutput::default();
+        loop {
+            sink.send(OutputStream::Stdout, "tick\n");
+            output.stdout.push_str("tick\n");
+            tokio::select! {
+                _ = cancellation.cancelled(), if honours => break,
+                _ = tokio::time::sleep(Duration::from_millis(10)) => {}
+            }
+        }
+        let mut result = output.into_result(&request(), "1".to_string(), SystemTime::now(), Duration::ZERO);
+        result.stopped = Some(StopReason::Cancelled);
+        Ok(result)
+    }
+
+    #[tokio::test]
+    async fn test_cooperative_engine_returns_partial_result() {
+        let (sink, mut chunks) = OutputSink::channel();
+        let (output, mut relayed) = OutputSink::channel();
+        let cancellation = CancellationToken::new();
+        let execution = ticker(sink, cancellation.clone(), true);
+
+        match run_with_timeout(execution, &mut chunks, Some(&output), Some(Duration::from_millis(100)), &cancellation)
+            .await
+        {
+            Completion::TimedOut { timeout, result: Some(Ok(result)), .. } => {
+                assert_eq!(timeout, Duration::from_millis(100));
+                assert!(result.output.starts_with("tick\n"));
+            }
+            other => panic!("unexpected completion {:?}", other),
+        }
+        assert!(cancellation.is_cancelled());
+        assert_eq!(relayed.recv().await.unwrap().data, "tick\n");
+    }
+
+    #[tokio::test]
+    async fn test_unresponsive_engine_is_dropped_with_streamed_output() {
+        let (sink, mut chunks) = OutputSink::channel();
+        let cancellation = CancellationToken::new();
+        let execution = ticker(sink, cancellation.clone(), false);
+
+        let start = std::time::Instant::now();
+        match run_with_timeout(execution, &mut chunks, None, Some(Duration::from_millis(100)), &cancellation).await {
+            Completion::TimedOut { result: None, partial, .. } => {
+                assert!(partial.stdout.starts_with("tick\n"));
+                assert!(partial.stderr.is_empty());
+            }
+            other => panic!("unexpected completion {:?}", other),
+        }
+        assert!(start.elapsed() < TIMEOUT_GRACE_PERIOD * 2);
+    }
+
+    #[tokio::test]
+    async fn test_finished_execution_is_not_cancelled() {
+        let (sink, mut chunks) = OutputSink::channel();
+        let cancellation = CancellationToken::new();
+        let execution = async {
+            sink.send(OutputStream::Stdout, "done\n");
+            Ok(PartialOutput::default().into_result(&request(), "1".to_string(), SystemTime::now(), Duration::ZERO))
+        };
+
+        let completion = run_with_timeout(execution, &mut chunks, None, Some(Duration::from_secs(5)), &cancellation);
+        assert!(matches!(completion.await, Completion::Finished(Ok(_))));
+        assert!(!cancellation.is_cancelled());
+    }
+}
@@ -0,0 +1,452 @@
+//! Workspace quotas and cleanup.
+//!
+//! Each execution session gets a workspace directory under a common root,
+//! with an `artifacts/` subdirectory for generated artifacts. The
+//! [`WorkspaceManager`] tracks disk usage per session and per owning agent,
+//! refuses work once a quota is exhausted and removes workspaces whose
+//! sessions have been idle longer than the configured TTL.
+//!
+//! Quota violations are published as [`KernelEvent::ResourceError`] events
+//! (resource type [`ResourceType::Disk`]) when an event bus is attached.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::RwLock;
+use toka_bus_core::{EventBus, KernelEvent, ResourceType};
+use toka_types::EntityId;
+
+/// Name of the artifact directory inside each workspace.
+pub const ARTIFACTS_DIR: &str = "artifacts";
+
+/// Disk quotas and cleanup settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceConfig {
+    /// Directory holding all session workspaces
+    pub root: PathBuf,
+    /// Maximum bytes per session workspace
+    pub max_session_bytes: Option<u64>,
+    /// Maximum bytes across all workspaces owned by one agent
+    pub max_agent_bytes: Option<u64>,
+    /// Idle time after which a session's workspace is removed
+    pub session_ttl: Duration,
+    /// How often the background cleanup task runs
+    pub cleanup_interval: Duration,
+}
+
+impl WorkspaceConfig {
+    /// Configuration rooted at `root` with no quotas, a one hour TTL and
+    /// cleanup every five minutes.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            max_session_bytes: None,
+            max_agent_bytes: None,
+            session_ttl: Duration::from_secs(3600),
+            cleanup_interval: Duration::from_secs(300),
+        }
+    }
+
+    /// Limit each session workspace to `bytes`.
+    pub fn with_session_quota(mut self, bytes: u64) -> Self {
+        self.max_session_bytes = Some(bytes);
+        self
+    }
+
+    /// Limit the combined workspaces of each agent to `bytes`.
+    pub fn with_agent_quota(mut self, bytes: u64) -> Self {
+        self.max_agent_bytes = Some(bytes);
+        self
+    }
+
+    /// Remove workspaces idle for longer than `ttl`.
+    pub fn with_session_ttl(mut self, ttl: Duration) -> Self {
+        self.session_ttl = ttl;
+        self
+    }
+}
+
+/// Workspace errors.
+#[derive(Debug, Error)]
+pub enum WorkspaceError {
+    /// No workspace exists for the session
+    #[error("no workspace for session {0}")]
+    UnknownSession(String),
+    /// Session ids become directory names and must be plain
+    #[error("invalid session id '{0}'")]
+    InvalidSessionId(String),
+    /// A quota would be exceeded
+    #[error("{scope} disk quota exceeded: {used} of {limit} bytes used, {requested} more requested")]
+    QuotaExceeded {
+        /// Which quota was hit (`session` or `agent`)
+        scope: &'static str,
+        /// Bytes currently used
+        used: u64,
+        /// Additional bytes requested
+        requested: u64,
+        /// Configured limit
+        limit: u64,
+    },
+    /// Filesystem failure
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Disk usage of one workspace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceUsage {
+    /// Owning session
+    pub session_id: String,
+    /// Agent the session belongs to, if any
+    pub agent: Option<EntityId>,
+    /// Workspace directory
+    pub path: PathBuf,
+    /// Bytes used at the last measurement
+    pub bytes: u64,
+    /// Last time the session used its workspace
+    pub last_active: SystemTime,
+}
+
+/// Result of a cleanup pass.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CleanupReport {
+    /// Sessions whose workspaces were removed
+    pub removed_sessions: Vec<String>,
+    /// Bytes freed
+    pub bytes_freed: u64,
+}
+
+/// Tracks, limits and cleans up session workspaces.
+pub struct WorkspaceManager {
+    config: WorkspaceConfig,
+    workspaces: RwLock<HashMap<String, WorkspaceUsage>>,
+    event_bus: Option<Arc<dyn EventBus>>,
+}
+
+impl WorkspaceManager {
+    /// Create a manager for `config`.
+    pub fn new(config: WorkspaceConfig) -> Self {
+        Self {
+            config,
+            workspaces: RwLock::new(HashMap::new()),
+            event_bus: None,
+        }
+    }
+
+    /// Publish quota violations on `bus`.
+    pub fn with_event_bus(mut self, bus: Arc<dyn EventBus>) -> Self {
+        self.event_bus = Some(bus);
+        self
+    }
+
+    /// The active configuration.
+    pub fn config(&self) -> &WorkspaceConfig {
+        &self.config
+    }
+
+    /// Return the workspace for `session_id`, creating it if needed.
+    pub async fn open_workspace(
+        &self,
+        session_id: &str,
+        agent: Option<EntityId>,
+    ) -> Result<PathBuf, WorkspaceError> {
+        if session_id.is_empty()
+            || !session_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+            || session_id.starts_with('.')
+        {
+            return Err(WorkspaceError::InvalidSessionId(session_id.to_string()));
+        }
+
+        let mut workspaces = self.workspaces.write().await;
+        if let Some(usage) = workspaces.get_mut(session_id) {
+            usage.last_active = SystemTime::now();
+            return Ok(usage.path.clone());
+        }
+
+        let path = self.config.root.join(session_id);
+        tokio::fs::create_dir_all(path.join(ARTIFACTS_DIR)).await?;
+        let bytes = measure(path.clone()).await?;
+        workspaces.insert(
+            session_id.to_string(),
+            WorkspaceUsage {
+                session_id: session_id.to_string(),
+                agent,
+                path: path.clone(),
+                bytes,
+                last_active: SystemTime::now(),
+            },
+        );
+        Ok(path)
+    }
+
+    /// Artifact directory of `session_id`'s workspace.
+    pub async fn artifacts_dir(&self, session_id: &str) -> Result<PathBuf, WorkspaceError> {
+        let workspaces = self.workspaces.read().await;
+        let usage = workspaces
+            .get(session_id)
+            .ok_or_else(|| WorkspaceError::UnknownSession(session_id.to_string()))?;
+        Ok(usage.path.join(ARTIFACTS_DIR))
+    }
+
+    /// Mark `session_id` as active, postponing its expiry.
+    pub async fn touch(&self, session_id: &str) {
+        if let Some(usage) = self.workspaces.write().await.get_mut(session_id) {
+            usage.last_active = SystemTime::now();
+        }
+    }
+
+    /// Re-measure the disk usage of `session_id`'s workspace.
+    pub async fn refresh_usage(&self, session_id: &str) -> Result<u64, WorkspaceError> {
+        let path = self
+            .workspaces
+            .read()
+            .await
+            .get(session_id)
+            .map(|usage| usage.path.clone())
+            .ok_or_else(|| WorkspaceError::UnknownSession(session_id.to_string()))?;
+
+        let bytes = measure(path).await?;
+        if let Some(usage) = self.workspaces.write().await.get_mut(session_id) {
+            usage.bytes = bytes;
+        }
+        Ok(bytes)
+    }
+
+    /// Usage of `session_id`'s workspace as of the last measurement.
+    pub async fn usage(&self, session_id: &str) -> Option<WorkspaceUsage> {
+        self.workspaces.read().await.get(session_id).cloned()
+    }
+
+    /// Combined usage of every workspace owned by `agent`.
+    pub async fn agent_usage(&self, agent: EntityId) -> u64 {
+        self.workspaces
+            .read()
+            .await
+            .values()
+            .filter(|usage| usage.agent == Some(agent))
+            .map(|usage| usage.bytes)
+            .sum()
+    }
+
+    /// Check that `session_id` may write `additional_bytes` more.
+    ///
+    /// Usage is re-measured first. If the session or agent quota would be
+    /// exceeded a `ResourceError` event is published and the call fails.
+    pub async fn check_quota(
+        &self,
+        session_id: &str,
+        additional_bytes: u64,
+    ) -> Result<(), WorkspaceError> {
+        let used = self.refresh_usage(session_id).await?;
+        let agent = self
+            .workspaces
+            .read()
+            .await
+            .get(session_id)
+            .and_then(|usage| usage.agent);
+
+        if let Some(limit) = self.config.max_session_bytes {
+            self.enforce("session", used, additional_bytes, limit, agent)?;
+        }
+        if let (Some(limit), Some(agent)) = (self.config.max_agent_bytes, agent) {
+            let used = self.agent_usage(agent).await;
+            self.enforce("agent", used, additional_bytes, limit, Some(agent))?;
+        }
+        Ok(())
+    }
+
+    fn enforce(
+        &self,
+        scope: &'static str,
+        used: u64,
+        requested: u64,
+        limit: u64,
+        agent: Option<EntityId>,
+    ) -> Result<(), WorkspaceError> {
+        if used.saturating_add(requested) <= limit {
+            return Ok(());
+        }
+
+        if let Some(bus) = &self.event_bus {
+            let event = KernelEvent::ResourceError {
+                resource_type: ResourceType::Disk,
+                requested: used.saturating_add(requested),
+                available: limit.saturating_sub(used),
+                agent,
+                timestamp: Utc::now(),
+            };
+            if let Err(e) = bus.publish(&event) {
+                tracing::warn!("Failed to publish workspace quota event: {}", e);
+            }
+        }
+        Err(WorkspaceError::QuotaExceeded {
+            scope,
+            used,
+            requested,
+            limit,
+        })
+    }
+
+    /// Delete `session_id`'s workspace. Returns the bytes freed.
+    pub async fn remove_workspace(&self, session_id: &str) -> Result<u64, WorkspaceError> {
+        let usage = self
+            .workspaces
+            .write()
+            .await
+            .remove(session_id)
+            .ok_or_else(|| WorkspaceError::UnknownSession(session_id.to_string()))?;
+        let bytes = measure(usage.path.clone()).await?;
+        remove_dir(&usage.path).await?;
+        Ok(bytes)
+    }
+
+    /// Remove every workspace idle since before `now - session_ttl`.
+    pub async fn cleanup_expired(&self, now: SystemTime) -> Result<CleanupReport, WorkspaceError> {
+        let cutoff = now.checked_sub(self.config.session_ttl).unwrap_or(SystemTime::UNIX_EPOCH);
+        let expired: Vec<String> = self
+            .workspaces
+            .read()
+            .await
+            .values()
+            .filter(|usage| usage.last_active < cutoff)
+            .map(|usage| usage.session_id.clone())
+            .collect();
+
+        let mut report = CleanupReport::default();
+        for session_id in expired {
+            report.bytes_freed += self.remove_workspace(&session_id).await?;
+            report.removed_sessions.push(session_id);
+        }
+        Ok(report)
+    }
+
+    /// Run [`cleanup_expired`](Self::cleanup_expired) every
+    /// `cleanup_interval` until the returned task is aborted.
+    pub fn spawn_cleanup(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.config.cleanup_interval);
+            loop {
+                interval.tick().await;
+                match self.cleanup_expired(SystemTime::now()).await {
+                    Ok(report) if !report.removed_sessions.is_empty() => tracing::info!(
+                        "Removed {} expired workspaces ({} bytes)",
+                        report.removed_sessions.len(),
+                        report.bytes_freed
+                    ),
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!("Workspace cleanup failed: {}", e),
+                }
+            }
+        })
+    }
+}
+
+/// Total size of the files under `path`.
+async fn measure(path: PathBuf) -> Result<u64, WorkspaceError> {
+    tokio::task::spawn_blocking(move || directory_size(&path))
+        .await
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?
+        .map_err(WorkspaceError::from)
+}
+
+fn directory_size(path: &Path) -> std::io::Result<u64> {
+    let mut total = 0;
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let entry = entry?;
+            // Symlinks are not followed so a workspace cannot count (or
+            // escape into) files outside itself
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file() {
+                total += entry.metadata()?.len();
+            }
+        }
+    }
+    Ok(total)
+}
+
+async fn remove_dir(path: &Path) -> std::io::Result<()> {
+    match tokio::fs::remove_dir_all(path).await {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use toka_bus_core::InMemoryBus;
+
+    #[tokio::test]
+    async fn test_quota_emits_resource_error() {
+        let dir = TempDir::new().unwrap();
+        let bus = Arc::new(InMemoryBus::new(16));
+        let mut events = bus.subscribe();
+        let manager = WorkspaceManager::new(
+            WorkspaceConfig::new(dir.path()).with_session_quota(100).with_agent_quota(150),
+        )
+        .with_event_bus(bus);
+
+        let agent = EntityId(7);
+        let first = manager.open_workspace("s1", Some(agent)).await.unwrap();
+        std::fs::write(first.join(ARTIFACTS_DIR).join("out.bin"), vec![0u8; 80]).unwrap();
+        manager.check_quota("s1", 10).await.unwrap();
+        assert!(matches!(
+            manager.check_quota("s1", 30).await,
+            Err(WorkspaceError::QuotaExceeded { scope: "session", .. })
+        ));
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            KernelEvent::ResourceError { resource_type: ResourceType::Disk, available: 20, .. }
+        ));
+
+        // A second session of the same agent counts towards the agent quota
+        let second = manager.open_workspace("s2", Some(agent)).await.unwrap();
+        std::fs::write(second.join("data"), vec![0u8; 60]).unwrap();
+        assert!(matches!(
+            manager.check_quota("s2", 20).await,
+            Err(WorkspaceError::QuotaExceeded { scope: "agent", used: 140, .. })
+        ));
+        assert!(manager.open_workspace("../escape", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_removes_idle_sessions() {
+        let dir = TempDir::new().unwrap();
+        let manager = WorkspaceManager::new(
+            WorkspaceConfig::new(dir.path()).with_session_ttl(Duration::from_secs(60)),
+        );
+
+        let path = manager.open_workspace("idle", None).await.unwrap();
+        std::fs::write(path.join("file"), b"hello").unwrap();
+        manager.open_workspace("active", None).await.unwrap();
+
+        let now = SystemTime::now();
+        assert!(manager.cleanup_expired(now).await.unwrap().removed_sessions.is_empty());
+
+        // Backdate the idle session past its TTL
+        manager.workspaces.write().await.get_mut("idle").unwrap().last_active =
+            now - Duration::from_secs(120);
+        let report = manager.cleanup_expired(now).await.unwrap();
+        assert_eq!(report.removed_sessions, vec!["idle".to_string()]);
+        assert_eq!(report.bytes_freed, 5);
+        assert!(manager.usage("active").await.is_some());
+        assert!(!path.exists());
+    }
+}
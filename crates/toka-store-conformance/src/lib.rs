@@ -0,0 +1,811 @@
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+
+//! **toka-store-conformance** – Shared conformance suite for storage backends.
+//!
+//! Every storage driver has to honour the same contracts: committed events
+//! read back unchanged, WAL transactions apply atomically, replaying
+//! subscriptions neither skip nor duplicate events, idempotency keys are
+//! deduplicated and committed state survives a restart. This crate encodes
+//! those contracts once so new drivers can prove behavioural equivalence with
+//! the reference backends instead of copying their test modules.
+//!
+//! Each check is an async function over a backend that returns an error
+//! describing the first violated expectation. Checks are grouped by the trait
+//! they exercise:
+//!
+//! - [`storage`] – [`StorageBackend`]
+//! - [`wal`] – [`WriteAheadLog`]
+//! - [`subscription`] – [`ReplayableStorage`]
+//! - [`dedup`] – [`DeduplicatingStorage`]
+//! - [`recovery`] – reopening a persistent backend
+//! - [`crash`] – scripted crashes between WAL transactions
+//!
+//! The `*_conformance_tests!` macros expand to one `#[tokio::test]` per
+//! check, building a fresh backend from the given expression for each test:
+//!
+//! ```rust,ignore
+//! #[cfg(test)]
+//! mod tests {
+//!     use super::*;
+//!
+//!     toka_store_conformance::storage_conformance_tests!(storage_conformance, MyBackend::new());
+//!     toka_store_conformance::wal_conformance_tests!(wal_conformance, MyBackend::new());
+//! }
+//! ```
+//!
+//! The calling crate needs `tokio` with the `macros` and `rt` features as a
+//! dev-dependency.
+
+use std::time::Duration;
+
+use anyhow::{ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use toka_store_core::{
+    create_event_header, CausalDigest, DeduplicatingStorage, EventHeader, ReplayableStorage,
+    StorageBackend, WriteAheadLog,
+};
+
+/// How long subscription checks wait for an expected event.
+pub const EVENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Payload used by every check.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConformanceEvent {
+    /// Free-form label
+    pub label: String,
+    /// Distinguishing value
+    pub value: u64,
+}
+
+/// Build an event header and its MessagePack payload.
+pub fn sample_event(label: &str, value: u64) -> (EventHeader, Vec<u8>) {
+    sample_child(&[], label, value)
+}
+
+/// Build an event header with `parents` and its MessagePack payload.
+pub fn sample_child(parents: &[EventHeader], label: &str, value: u64) -> (EventHeader, Vec<u8>) {
+    let event = ConformanceEvent {
+        label: label.to_string(),
+        value,
+    };
+    let header = create_event_header(parents, Uuid::new_v4(), "conformance.event".to_string(), &event)
+        .expect("conformance events always encode");
+    let payload = rmp_serde::to_vec_named(&event).expect("conformance events always encode");
+    (header, payload)
+}
+
+/// Checks for the basic [`StorageBackend`] contract.
+pub mod storage {
+    use super::*;
+
+    /// Committed headers and payloads read back unchanged.
+    pub async fn commit_and_read<B: StorageBackend>(backend: &B) -> Result<()> {
+        let (header, payload) = sample_event("commit", 1);
+        backend.commit(&header, &payload).await.context("commit failed")?;
+
+        let stored = backend.header(&header.id).await?.context("committed header not found")?;
+        ensure!(stored == header, "header changed in storage: {:?} != {:?}", stored, header);
+        let stored = backend
+            .payload_bytes(&header.digest)
+            .await?
+            .context("committed payload not found")?;
+        ensure!(stored == payload, "payload bytes changed in storage");
+        Ok(())
+    }
+
+    /// Optional header fields survive a round trip.
+    pub async fn header_fields_roundtrip<B: StorageBackend>(backend: &B) -> Result<()> {
+        let (mut header, payload) = sample_event("fields", 2);
+        header.namespace = Some("conformance".to_string());
+        let header = header
+            .with_blob(toka_store_core::BlobRef::for_bytes(b"artifact"))
+            .with_idempotency_key("conformance-fields");
+        backend.commit(&header, &payload).await?;
+
+        let stored = backend.header(&header.id).await?.context("committed header not found")?;
+        ensure!(stored == header, "header fields were not preserved: {:?}", stored);
+        Ok(())
+    }
+
+    /// Unknown ids and digests are reported as absent, not as errors.
+    pub async fn missing_lookups<B: StorageBackend>(backend: &B) -> Result<()> {
+        ensure!(backend.header(&Uuid::new_v4()).await?.is_none(), "unknown id returned a header");
+        ensure!(
+            backend.payload_bytes(&[0u8; 32]).await?.is_none(),
+            "unknown digest returned a payload"
+        );
+        Ok(())
+    }
+
+    /// Committing the same event twice leaves a single, unchanged event.
+    pub async fn recommit_is_idempotent<B: StorageBackend>(backend: &B) -> Result<()> {
+        let (header, payload) = sample_event("recommit", 3);
+        backend.commit(&header, &payload).await?;
+        backend.commit(&header, &payload).await.context("second commit failed")?;
+
+        let stored = backend.header(&header.id).await?.context("header lost after recommit")?;
+        ensure!(stored == header, "header changed after recommit");
+        Ok(())
+    }
+
+    /// Events sharing a payload digest can all be read back.
+    pub async fn shared_payloads<B: StorageBackend>(backend: &B) -> Result<()> {
+        let (first, payload) = sample_event("shared", 4);
+        let mut second = first.clone();
+        second.id = Uuid::new_v4();
+        backend.commit(&first, &payload).await?;
+        backend.commit(&second, &payload).await?;
+
+        ensure!(backend.header(&first.id).await?.is_some(), "first event lost");
+        ensure!(backend.header(&second.id).await?.is_some(), "second event lost");
+        ensure!(
+            backend.payload_bytes(&first.digest).await? == Some(payload),
+            "shared payload not readable"
+        );
+        Ok(())
+    }
+
+    /// Bulk payload reads return every stored digest exactly once.
+    pub async fn payloads_bulk<B: StorageBackend>(backend: &B) -> Result<()> {
+        let mut expected = std::collections::HashMap::new();
+        for value in 0..40 {
+            let (header, payload) = sample_event("bulk", 100 + value);
+            backend.commit(&header, &payload).await?;
+            expected.insert(header.digest, payload);
+        }
+
+        let mut digests: Vec<CausalDigest> = expected.keys().copied().collect();
+        digests.push(digests[0]);
+        digests.push([0u8; 32]);
+        let payloads = backend.payloads_bulk(&digests).await?;
+        ensure!(
+            payloads == expected,
+            "bulk read returned {} payloads, expected {}",
+            payloads.len(),
+            expected.len()
+        );
+        Ok(())
+    }
+}
+
+/// Checks for the [`WriteAheadLog`] contract.
+pub mod wal {
+    use super::*;
+    use toka_store_core::WalStorageBackend;
+
+    /// Events logged in a transaction become visible only when it commits.
+    pub async fn commit_applies_events<B: StorageBackend + WriteAheadLog>(backend: &B) -> Result<()> {
+        let (header, payload) = sample_event("wal-commit", 10);
+        let tx = backend.begin_transaction().await?;
+        backend.commit_with_wal(tx, &header, &payload).await?;
+        ensure!(
+            backend.header(&header.id).await?.is_none(),
+            "event visible before its transaction committed"
+        );
+
+        backend.commit_transaction(tx).await?;
+        ensure!(
+            backend.header(&header.id).await?.is_some(),
+            "event missing after its transaction committed"
+        );
+        Ok(())
+    }
+
+    /// Rolled back transactions leave no events behind.
+    pub async fn rollback_discards_events<B: StorageBackend + WriteAheadLog>(backend: &B) -> Result<()> {
+        let (header, payload) = sample_event("wal-rollback", 11);
+        let tx = backend.begin_transaction().await?;
+        backend.commit_with_wal(tx, &header, &payload).await?;
+        backend.rollback_transaction(tx).await?;
+        ensure!(
+            backend.header(&header.id).await?.is_none(),
+            "rolled back event is visible"
+        );
+        Ok(())
+    }
+
+    /// Finished or unknown transactions reject further operations.
+    pub async fn finished_transactions_are_closed<B: StorageBackend + WriteAheadLog>(
+        backend: &B,
+    ) -> Result<()> {
+        let (header, payload) = sample_event("wal-closed", 12);
+        let tx = backend.begin_transaction().await?;
+        backend.commit_transaction(tx).await?;
+
+        ensure!(
+            backend.commit_with_wal(tx, &header, &payload).await.is_err(),
+            "write to a committed transaction succeeded"
+        );
+        ensure!(
+            backend.commit_transaction(tx).await.is_err(),
+            "transaction committed twice"
+        );
+        ensure!(
+            backend.rollback_transaction(Uuid::new_v4()).await.is_err(),
+            "unknown transaction rolled back"
+        );
+        Ok(())
+    }
+
+    /// The WAL sequence grows with every logged operation.
+    pub async fn sequence_advances<B: StorageBackend + WriteAheadLog>(backend: &B) -> Result<()> {
+        let (header, payload) = sample_event("wal-sequence", 13);
+        let start = backend.current_sequence().await?;
+        let tx = backend.begin_transaction().await?;
+        let begun = backend.current_sequence().await?;
+        backend.commit_with_wal(tx, &header, &payload).await?;
+        let written = backend.current_sequence().await?;
+
+        ensure!(begun > start, "begin did not advance the sequence");
+        ensure!(written > begun, "write did not advance the sequence");
+        backend.commit_transaction(tx).await
+    }
+
+    /// Recovery applies committed transactions and rolls back pending ones.
+    pub async fn recover_rolls_back_pending<B: StorageBackend + WriteAheadLog>(backend: &B) -> Result<()> {
+        let (committed, committed_payload) = sample_event("wal-recover-committed", 14);
+        let tx = backend.begin_transaction().await?;
+        backend.commit_with_wal(tx, &committed, &committed_payload).await?;
+        backend.commit_transaction(tx).await?;
+
+        let (pending, pending_payload) = sample_event("wal-recover-pending", 15);
+        let tx = backend.begin_transaction().await?;
+        backend.commit_with_wal(tx, &pending, &pending_payload).await?;
+
+        let result = backend.recover().await?;
+        ensure!(result.transactions_rolled_back >= 1, "pending transaction was not rolled back");
+        ensure!(result.transactions_committed >= 1, "committed transaction was not recovered");
+        ensure!(backend.header(&committed.id).await?.is_some(), "committed event lost in recovery");
+        ensure!(backend.header(&pending.id).await?.is_none(), "pending event applied by recovery");
+        Ok(())
+    }
+}
+
+/// Checks for the [`ReplayableStorage`] contract.
+pub mod subscription {
+    use super::*;
+    use futures::StreamExt;
+    use toka_store_core::{EventHeaderStream, ReplayFrom};
+
+    async fn next_id(stream: &mut EventHeaderStream<'_>) -> Result<Uuid> {
+        let item = tokio::time::timeout(EVENT_TIMEOUT, stream.next())
+            .await
+            .context("timed out waiting for an event")?
+            .context("subscription ended")?;
+        Ok(item?.id)
+    }
+
+    /// Stored events are replayed before live ones, without duplicates.
+    pub async fn replay_then_live<B: ReplayableStorage>(backend: &B) -> Result<()> {
+        let (first, first_payload) = sample_event("replay", 20);
+        let (second, second_payload) = sample_child(&[first.clone()], "replay", 21);
+        backend.commit(&first, &first_payload).await?;
+
+        let mut stream = backend.subscribe_from(ReplayFrom::Sequence(0)).await?;
+        backend.commit(&second, &second_payload).await?;
+
+        ensure!(next_id(&mut stream).await? == first.id, "history was not replayed first");
+        ensure!(next_id(&mut stream).await? == second.id, "live event not delivered after history");
+        Ok(())
+    }
+
+    /// Resuming from a sequence skips the events already processed.
+    pub async fn resume_from_sequence<B: ReplayableStorage>(backend: &B) -> Result<()> {
+        let (first, first_payload) = sample_event("resume", 22);
+        let (second, second_payload) = sample_child(&[first.clone()], "resume", 23);
+        backend.commit(&first, &first_payload).await?;
+        backend.commit(&second, &second_payload).await?;
+
+        let mut stream = backend.subscribe_from(ReplayFrom::Sequence(1)).await?;
+        ensure!(next_id(&mut stream).await? == second.id, "resumed stream did not skip processed events");
+        Ok(())
+    }
+}
+
+/// Checks for the [`DeduplicatingStorage`] contract.
+pub mod dedup {
+    use super::*;
+    use toka_store_core::CommitOutcome;
+
+    /// Re-emitted events resolve to the original and are not stored.
+    pub async fn duplicates_resolve_to_original<B: DeduplicatingStorage>(backend: &B) -> Result<()> {
+        let (original, payload) = sample_event("dedup", 30);
+        let original = original.with_idempotency_key("conformance-dedup");
+        let (retry, retry_payload) = sample_event("dedup", 30);
+        let retry = retry.with_idempotency_key("conformance-dedup");
+
+        let outcome = backend.commit_deduplicated(&original, &payload).await?;
+        ensure!(outcome == CommitOutcome::Committed(original.id), "original reported as {:?}", outcome);
+        let outcome = backend.commit_deduplicated(&retry, &retry_payload).await?;
+        ensure!(outcome == CommitOutcome::Duplicate(original.id), "retry reported as {:?}", outcome);
+
+        // Plain commits honour the key as well
+        backend.commit(&retry, &retry_payload).await?;
+        ensure!(backend.header(&retry.id).await?.is_none(), "duplicate event was stored");
+        Ok(())
+    }
+
+    /// Keys are scoped per namespace and unkeyed events are never dropped.
+    pub async fn keys_are_scoped<B: DeduplicatingStorage>(backend: &B) -> Result<()> {
+        let (first, payload) = sample_event("scoped", 31);
+        let first = first.with_idempotency_key("conformance-scoped");
+        let (mut other, other_payload) = sample_event("scoped", 31);
+        other.namespace = Some("conformance-other".to_string());
+        let other = other.with_idempotency_key("conformance-scoped");
+        let (plain, plain_payload) = sample_event("scoped", 32);
+        let (plain_again, _) = sample_event("scoped", 32);
+
+        backend.commit_deduplicated(&first, &payload).await?;
+        ensure!(
+            !backend.commit_deduplicated(&other, &other_payload).await?.is_duplicate(),
+            "key deduplicated across namespaces"
+        );
+        ensure!(
+            !backend.commit_deduplicated(&plain, &plain_payload).await?.is_duplicate()
+                && !backend.commit_deduplicated(&plain_again, &plain_payload).await?.is_duplicate(),
+            "event without an idempotency key was dropped"
+        );
+        Ok(())
+    }
+
+    /// Keys can be reused once the window has passed.
+    pub async fn window_expires<B: DeduplicatingStorage>(backend: &B) -> Result<()> {
+        let window = chrono::Duration::from_std(backend.dedup_window())?;
+        let (original, payload) = sample_event("window", 33);
+        let original = original.with_idempotency_key("conformance-window");
+        let (mut late, late_payload) = sample_event("window", 33);
+        late.timestamp = original.timestamp + window + chrono::Duration::seconds(1);
+        let late = late.with_idempotency_key("conformance-window");
+
+        backend.commit_deduplicated(&original, &payload).await?;
+        let outcome = backend.commit_deduplicated(&late, &late_payload).await?;
+        ensure!(!outcome.is_duplicate(), "key still deduplicated after the window");
+        Ok(())
+    }
+}
+
+/// Checks for persistent backends that are reopened from the same location.
+pub mod recovery {
+    use super::*;
+    use std::future::Future;
+
+    /// Committed events and WAL transactions survive closing and reopening.
+    ///
+    /// `open` must open the same underlying store on every call. Events of a
+    /// transaction that was still pending when the backend was closed must
+    /// not appear after recovery.
+    pub async fn reopen_preserves_committed<B, F, Fut>(open: F) -> Result<()>
+    where
+        B: StorageBackend + WriteAheadLog,
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<B>>,
+    {
+        let (plain, plain_payload) = sample_event("reopen-plain", 40);
+        let (logged, logged_payload) = sample_event("reopen-logged", 41);
+        let (pending, pending_payload) = sample_event("reopen-pending", 42);
+        {
+            let backend = open().await?;
+            backend.commit(&plain, &plain_payload).await?;
+
+            let tx = backend.begin_transaction().await?;
+            backend.write_entry(tx, logged_wal_op(&logged, &logged_payload)).await?;
+            backend.commit_transaction(tx).await?;
+
+            let tx = backend.begin_transaction().await?;
+            backend.write_entry(tx, logged_wal_op(&pending, &pending_payload)).await?;
+        }
+
+        let backend = open().await?;
+        backend.recover().await?;
+        ensure!(backend.header(&plain.id).await?.is_some(), "committed event lost on reopen");
+        ensure!(
+            backend.payload_bytes(&plain.digest).await? == Some(plain_payload),
+            "payload changed on reopen"
+        );
+        ensure!(backend.header(&logged.id).await?.is_some(), "WAL-committed event lost on reopen");
+        ensure!(backend.header(&pending.id).await?.is_none(), "pending WAL event applied on reopen");
+        Ok(())
+    }
+
+    fn logged_wal_op(header: &EventHeader, payload: &[u8]) -> toka_store_core::WalOperation {
+        toka_store_core::WalOperation::CommitEvent {
+            header: header.clone(),
+            payload: toka_store_core::Bytes::copy_from_slice(payload),
+        }
+    }
+}
+
+/// Crash-recovery simulation for [`WriteAheadLog`] backends.
+///
+/// [`crash::simulate`] runs a script of WAL transactions interleaved with
+/// crashes. Each crash hands the backend to a caller-supplied function that
+/// discards it the way a real failure would, then reopens the store, runs
+/// [`WriteAheadLog::recover`] and checks the recovery invariants:
+///
+/// - recovery reports no errors, and running it again changes nothing
+/// - every committed event reads back with its original header and payload
+/// - no event of a rolled back, abandoned or savepoint-discarded write is visible
+/// - transactions left open by the crash can no longer be committed
+///
+/// Persistent drivers reopen the same location and crash with
+/// [`crash::kill_backend`], which skips destructors like a killed process, so
+/// buffered WAL entries are lost. In-memory drivers reopen a retained handle
+/// and crash with [`crash::drop_backend`].
+pub mod crash {
+    use super::*;
+    use std::future::Future;
+    use toka_store_core::{EventId, TransactionId, WalStorageBackend};
+
+    /// One step of a crash-recovery script.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum CrashStep {
+        /// Commit events directly, outside the WAL
+        Direct {
+            /// Number of events
+            events: usize,
+        },
+        /// Log events in a transaction and commit it
+        Commit {
+            /// Number of events
+            events: usize,
+        },
+        /// Log events in a transaction and roll it back
+        Rollback {
+            /// Number of events
+            events: usize,
+        },
+        /// Log events in a transaction and leave it open
+        Abandon {
+            /// Number of events
+            events: usize,
+        },
+        /// Log events, set a savepoint, log more events, roll back to the
+        /// savepoint and commit
+        PartialRollback {
+            /// Events logged before the savepoint
+            kept: usize,
+            /// Events logged after the savepoint
+            discarded: usize,
+        },
+        /// Crash, reopen, recover and check the invariants
+        Crash,
+    }
+
+    /// Script exercising every step without savepoints.
+    pub fn standard_script() -> Vec<CrashStep> {
+        use CrashStep::*;
+        vec![
+            Commit { events: 3 },
+            Direct { events: 1 },
+            Abandon { events: 2 },
+            Crash,
+            Rollback { events: 2 },
+            Commit { events: 1 },
+            Abandon { events: 1 },
+            Commit { events: 2 },
+            Crash,
+            Crash,
+            Abandon { events: 0 },
+            Direct { events: 2 },
+            Crash,
+        ]
+    }
+
+    /// [`standard_script`] followed by savepoint rollbacks.
+    pub fn savepoint_script() -> Vec<CrashStep> {
+        use CrashStep::*;
+        let mut script = standard_script();
+        script.extend([
+            PartialRollback { kept: 2, discarded: 2 },
+            Abandon { events: 1 },
+            Crash,
+            PartialRollback { kept: 0, discarded: 1 },
+            Crash,
+        ]);
+        script
+    }
+
+    /// Totals of a completed simulation.
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    pub struct CrashReport {
+        /// Crashes simulated
+        pub crashes: usize,
+        /// Events that must survive
+        pub events_committed: usize,
+        /// Events that must not appear
+        pub events_discarded: usize,
+        /// Transactions rolled back by recovery
+        pub transactions_rolled_back: usize,
+    }
+
+    /// Crash by dropping the backend, as when its owner unwinds.
+    pub async fn drop_backend<B>(backend: B) -> Result<()> {
+        drop(backend);
+        Ok(())
+    }
+
+    /// Crash without running destructors, as when the process is killed.
+    ///
+    /// Nothing buffered is flushed and no handle is closed.
+    pub async fn kill_backend<B>(backend: B) -> Result<()> {
+        std::mem::forget(backend);
+        Ok(())
+    }
+
+    /// Expected contents of the store.
+    #[derive(Default)]
+    struct Model {
+        committed: Vec<(EventHeader, Vec<u8>)>,
+        discarded: Vec<EventId>,
+        open: Vec<TransactionId>,
+        next_value: u64,
+    }
+
+    impl Model {
+        fn events(&mut self, count: usize) -> Vec<(EventHeader, Vec<u8>)> {
+            (0..count)
+                .map(|_| {
+                    self.next_value += 1;
+                    sample_event("crash", self.next_value)
+                })
+                .collect()
+        }
+
+        fn discard(&mut self, events: &[(EventHeader, Vec<u8>)]) {
+            self.discarded.extend(events.iter().map(|(header, _)| header.id));
+        }
+    }
+
+    /// Run `script`, checking the recovery invariants after every crash.
+    ///
+    /// `open` must open the same underlying store on every call and `crash`
+    /// must discard a backend without shutting it down cleanly. A final
+    /// crash is simulated if the script does not end with one.
+    pub async fn simulate<B, O, OFut, C, CFut>(
+        script: &[CrashStep],
+        mut open: O,
+        mut crash: C,
+    ) -> Result<CrashReport>
+    where
+        B: StorageBackend + WriteAheadLog,
+        O: FnMut() -> OFut,
+        OFut: Future<Output = Result<B>>,
+        C: FnMut(B) -> CFut,
+        CFut: Future<Output = Result<()>>,
+    {
+        let mut model = Model::default();
+        let mut report = CrashReport::default();
+        let mut backend = open().await.context("initial open failed")?;
+
+        let mut steps = script.to_vec();
+        if steps.last() != Some(&CrashStep::Crash) {
+            steps.push(CrashStep::Crash);
+        }
+        for (index, step) in steps.into_iter().enumerate() {
+            let context = || format!("step {} ({:?})", index, step);
+            match step {
+                CrashStep::Direct { events } => {
+                    for (header, payload) in model.events(events) {
+                        backend.commit(&header, &payload).await.with_context(context)?;
+                        model.committed.push((header, payload));
+                    }
+                }
+                CrashStep::Commit { events } => {
+                    let events = model.events(events);
+                    let tx = log_events(&backend, &events).await.with_context(context)?;
+                    backend.commit_transaction(tx).await.with_context(context)?;
+                    model.committed.extend(events);
+                }
+                CrashStep::Rollback { events } => {
+                    let events = model.events(events);
+                    let tx = log_events(&backend, &events).await.with_context(context)?;
+                    backend.rollback_transaction(tx).await.with_context(context)?;
+                    model.discard(&events);
+                }
+                CrashStep::Abandon { events } => {
+                    let events = model.events(events);
+                    let tx = log_events(&backend, &events).await.with_context(context)?;
+                    model.discard(&events);
+                    model.open.push(tx);
+                }
+                CrashStep::PartialRollback { kept, discarded } => {
+                    let kept = model.events(kept);
+                    let discarded = model.events(discarded);
+                    let tx = log_events(&backend, &kept).await.with_context(context)?;
+                    let savepoint = backend.savepoint(tx).await.with_context(context)?;
+                    for (header, payload) in &discarded {
+                        backend.commit_with_wal(tx, header, payload).await.with_context(context)?;
+                    }
+                    backend.rollback_to_savepoint(tx, savepoint).await.with_context(context)?;
+                    backend.commit_transaction(tx).await.with_context(context)?;
+                    model.committed.extend(kept);
+                    model.discard(&discarded);
+                }
+                CrashStep::Crash => {
+                    crash(backend).await.with_context(context)?;
+                    report.crashes += 1;
+                    backend = open().await.with_context(context)?;
+                    report.transactions_rolled_back +=
+                        recover_and_check(&backend, &mut model).await.with_context(context)?;
+                }
+            }
+        }
+
+        report.events_committed = model.committed.len();
+        report.events_discarded = model.discarded.len();
+        Ok(report)
+    }
+
+    async fn log_events<B: StorageBackend + WriteAheadLog>(
+        backend: &B,
+        events: &[(EventHeader, Vec<u8>)],
+    ) -> Result<TransactionId> {
+        let tx = backend.begin_transaction().await?;
+        for (header, payload) in events {
+            backend.commit_with_wal(tx, header, payload).await?;
+        }
+        Ok(tx)
+    }
+
+    /// Recover twice and check the invariants, returning the number of
+    /// transactions the first recovery rolled back.
+    async fn recover_and_check<B: StorageBackend + WriteAheadLog>(
+        backend: &B,
+        model: &mut Model,
+    ) -> Result<usize> {
+        let result = backend.recover().await.context("recovery failed")?;
+        ensure!(
+            result.recovery_errors.is_empty(),
+            "recovery reported errors: {:?}",
+            result.recovery_errors
+        );
+        check_contents(backend, model).await?;
+
+        for tx in model.open.drain(..) {
+            ensure!(
+                backend.commit_transaction(tx).await.is_err(),
+                "transaction {} left open by the crash committed after recovery",
+                tx
+            );
+        }
+
+        let again = backend.recover().await.context("second recovery failed")?;
+        ensure!(
+            again.recovery_errors.is_empty(),
+            "second recovery reported errors: {:?}",
+            again.recovery_errors
+        );
+        ensure!(
+            again.transactions_rolled_back == 0,
+            "second recovery rolled back {} more transactions",
+            again.transactions_rolled_back
+        );
+        check_contents(backend, model).await.context("second recovery changed the store")?;
+        Ok(result.transactions_rolled_back)
+    }
+
+    async fn check_contents<B: StorageBackend>(backend: &B, model: &Model) -> Result<()> {
+        for (header, payload) in &model.committed {
+            let stored = backend
+                .header(&header.id)
+                .await?
+                .with_context(|| format!("committed event {} lost", header.id))?;
+            ensure!(stored == *header, "committed event {} changed: {:?}", header.id, stored);
+            ensure!(
+                backend.payload_bytes(&header.digest).await?.as_deref() == Some(payload.as_slice()),
+                "payload of committed event {} lost or changed",
+                header.id
+            );
+        }
+        for id in &model.discarded {
+            ensure!(backend.header(id).await?.is_none(), "discarded event {} is visible", id);
+        }
+        Ok(())
+    }
+}
+
+/// Expand to one test per [`storage`] check in a module named `$name`.
+///
+/// `$backend` is evaluated (and may `.await`) once per test. Append
+/// `, guarded` when it evaluates to `(guard, backend)` instead; the guard
+/// (e.g. the `TempDir` of an on-disk backend) is kept alive for the test.
+#[macro_export]
+macro_rules! storage_conformance_tests {
+    ($name:ident, $($backend:tt)+) => {
+        mod $name {
+            #[allow(unused_imports)]
+            use super::*;
+
+            $crate::__conformance_tests!(($($backend)+), storage {
+                commit_and_read,
+                header_fields_roundtrip,
+                missing_lookups,
+                recommit_is_idempotent,
+                shared_payloads,
+                payloads_bulk,
+            });
+        }
+    };
+}
+
+/// Expand to one test per [`wal`] check in a module named `$name`.
+#[macro_export]
+macro_rules! wal_conformance_tests {
+    ($name:ident, $($backend:tt)+) => {
+        mod $name {
+            #[allow(unused_imports)]
+            use super::*;
+
+            $crate::__conformance_tests!(($($backend)+), wal {
+                commit_applies_events,
+                rollback_discards_events,
+                finished_transactions_are_closed,
+                sequence_advances,
+                recover_rolls_back_pending,
+            });
+        }
+    };
+}
+
+/// Expand to one test per [`subscription`] check in a module named `$name`.
+#[macro_export]
+macro_rules! subscription_conformance_tests {
+    ($name:ident, $($backend:tt)+) => {
+        mod $name {
+            #[allow(unused_imports)]
+            use super::*;
+
+            $crate::__conformance_tests!(($($backend)+), subscription {
+                replay_then_live,
+                resume_from_sequence,
+            });
+        }
+    };
+}
+
+/// Expand to one test per [`dedup`] check in a module named `$name`.
+#[macro_export]
+macro_rules! dedup_conformance_tests {
+    ($name:ident, $($backend:tt)+) => {
+        mod $name {
+            #[allow(unused_imports)]
+            use super::*;
+
+            $crate::__conformance_tests!(($($backend)+), dedup {
+                duplicates_resolve_to_original,
+                keys_are_scoped,
+                window_expires,
+            });
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __conformance_tests {
+    (($backend:expr, guarded), $module:ident { $($check:ident),* $(,)? }) => {
+        $(
+            #[tokio::test]
+            async fn $check() {
+                let (_guard, backend) = $backend;
+                if let Err(e) = $crate::$module::$check(&backend).await {
+                    panic!("{} failed: {:#}", stringify!($check), e);
+                }
+            }
+        )*
+    };
+    (($backend:expr), $module:ident { $($check:ident),* $(,)? }) => {
+        $(
+            #[tokio::test]
+            async fn $check() {
+                let backend = $backend;
+                if let Err(e) = $crate::$module::$check(&backend).await {
+                    panic!("{} failed: {:#}", stringify!($check), e);
+                }
+            }
+        )*
+    };
+}
@@ -0,0 +1,508 @@
+#![forbid(unsafe_code)]
+
+//! Anonymized exports for sharing event datasets.
+//!
+//! Research partners need the shape of real event histories without the
+//! tenant data in them. [`anonymize_snapshot`] rewrites a
+//! [snapshot](crate::snapshot) according to an [`AnonymizationPolicy`]:
+//!
+//! - event IDs, parents and intents are replaced by keyed pseudonyms, so
+//!   the causal graph is intact but cannot be joined with the source store;
+//! - namespaces, idempotency keys and vector clock node names are hashed the
+//!   same way, and blob references are dropped;
+//! - payload fields selected by JSON pointer are hashed or stripped;
+//! - causal digests are recomputed over the rewritten payloads, so the
+//!   result verifies like any other snapshot.
+//!
+//! Pseudonyms are derived from a secret: the same secret maps an identifier
+//! to the same pseudonym across exports, so datasets can be extended later,
+//! while without it pseudonyms cannot be linked back. UUIDs found in hashed
+//! payload fields map to the same pseudonyms as header IDs, so a payload
+//! field naming an intent still points at that intent after anonymization.
+//!
+//! [`AnonymizedExportExt::export_anonymized`] runs the whole pipeline
+//! against any [`SnapshotStorage`] backend.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use futures::io::{AsyncRead, AsyncWrite, Cursor};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::snapshot::{SnapshotManifest, SnapshotReader, SnapshotStorage, SnapshotWriter, SNAPSHOT_VERSION};
+use crate::{causal_hash, CausalDigest, EventHeader, EventId, LogicalClock, StorageError, VectorClock};
+
+/// Context string deriving pseudonym keys from the policy secret.
+const KEY_CONTEXT: &str = "toka-store-core anonymized export v1";
+
+/// How a payload field is anonymized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldAction {
+    /// Replace the value with its pseudonym
+    Hash,
+    /// Remove the field
+    Strip,
+}
+
+/// Anonymization of one payload field.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldRule {
+    /// Family (`agent`), full kind (`agent.spawned`) or `*` for every event
+    pub kinds: String,
+    /// JSON pointer (RFC 6901) to the field, e.g. `/task/description`
+    pub pointer: String,
+    /// What to do with the field
+    pub action: FieldAction,
+}
+
+impl FieldRule {
+    /// Whether the rule applies to events of `kind`.
+    pub fn matches(&self, kind: &str) -> bool {
+        self.kinds == "*"
+            || kind == self.kinds
+            || kind
+                .strip_prefix(self.kinds.as_str())
+                .is_some_and(|rest| rest.starts_with('.'))
+    }
+}
+
+/// What an anonymized export rewrites, and the secret its pseudonyms derive from.
+#[derive(Clone)]
+pub struct AnonymizationPolicy {
+    key: [u8; 32],
+    rules: Vec<FieldRule>,
+    hash_namespaces: bool,
+}
+
+impl fmt::Debug for AnonymizationPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AnonymizationPolicy")
+            .field("rules", &self.rules)
+            .field("hash_namespaces", &self.hash_namespaces)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AnonymizationPolicy {
+    /// Policy deriving pseudonyms from `secret`, without field rules.
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            key: blake3::derive_key(KEY_CONTEXT, secret.as_ref()),
+            rules: Vec::new(),
+            hash_namespaces: true,
+        }
+    }
+
+    /// Hash the field at `pointer` in every payload.
+    pub fn hash_field(self, pointer: impl Into<String>) -> Self {
+        self.with_rule(FieldRule {
+            kinds: "*".to_string(),
+            pointer: pointer.into(),
+            action: FieldAction::Hash,
+        })
+    }
+
+    /// Strip the field at `pointer` from every payload.
+    pub fn strip_field(self, pointer: impl Into<String>) -> Self {
+        self.with_rule(FieldRule {
+            kinds: "*".to_string(),
+            pointer: pointer.into(),
+            action: FieldAction::Strip,
+        })
+    }
+
+    /// Add a fully specified rule.
+    pub fn with_rule(mut self, rule: FieldRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Export namespaces as they are instead of hashing them.
+    pub fn keep_namespaces(mut self) -> Self {
+        self.hash_namespaces = false;
+        self
+    }
+
+    /// Field rules, in the order they are applied.
+    pub fn rules(&self) -> &[FieldRule] {
+        &self.rules
+    }
+
+    /// Pseudonym of an event, intent or other UUID.
+    pub fn pseudonym(&self, id: &Uuid) -> Uuid {
+        let hash = self.keyed(b"uuid", id.as_bytes());
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&hash[..16]);
+        Uuid::from_bytes(bytes)
+    }
+
+    /// Pseudonym of a string, as 32 hex digits.
+    pub fn pseudonym_str(&self, value: &str) -> String {
+        blake3::Hash::from(self.keyed(b"str", value.as_bytes())).to_hex()[..32].to_string()
+    }
+
+    fn keyed(&self, domain: &[u8], data: &[u8]) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new_keyed(&self.key);
+        hasher.update(domain);
+        hasher.update(&[0]);
+        hasher.update(data);
+        *hasher.finalize().as_bytes()
+    }
+
+    /// Pseudonym of a JSON value, keeping UUIDs and unsigned integers in
+    /// their type so typed consumers can still decode them.
+    fn hash_value(&self, value: &Value) -> Value {
+        match value {
+            Value::Null => Value::Null,
+            Value::String(s) => match Uuid::parse_str(s) {
+                Ok(id) => Value::String(self.pseudonym(&id).to_string()),
+                Err(_) => Value::String(self.pseudonym_str(s)),
+            },
+            Value::Number(n) if n.is_u64() => {
+                let hash = self.keyed(b"u64", n.to_string().as_bytes());
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(&hash[..8]);
+                Value::from(u64::from_be_bytes(bytes))
+            }
+            other => Value::String(self.pseudonym_str(&other.to_string())),
+        }
+    }
+
+    /// Apply the rules matching `kind` to `payload`.
+    ///
+    /// Returns `None` when no rule applies, leaving the bytes untouched.
+    fn anonymize_payload(
+        &self,
+        header: &EventHeader,
+        payload: &[u8],
+        report: &mut AnonymizationReport,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        let rules: Vec<&FieldRule> = self.rules.iter().filter(|rule| rule.matches(&header.kind)).collect();
+        if rules.is_empty() {
+            return Ok(None);
+        }
+
+        let mut value: Value = header.format.decode(payload).map_err(|e| {
+            StorageError::DeserializationFailed(format!("payload of event {}: {}", header.id, e))
+        })?;
+        for rule in rules {
+            match rule.action {
+                FieldAction::Hash => {
+                    if let Some(field) = value.pointer_mut(&rule.pointer) {
+                        *field = self.hash_value(field);
+                        report.fields_hashed += 1;
+                    }
+                }
+                FieldAction::Strip => {
+                    if strip_pointer(&mut value, &rule.pointer) {
+                        report.fields_stripped += 1;
+                    }
+                }
+            }
+        }
+        Ok(Some(header.format.encode(&value)?))
+    }
+
+    fn anonymize_clock(&self, clock: &LogicalClock) -> LogicalClock {
+        match clock {
+            LogicalClock::Lamport(counter) => LogicalClock::Lamport(*counter),
+            LogicalClock::Vector(vector) => LogicalClock::Vector(
+                vector
+                    .iter()
+                    .map(|(node, counter)| (self.pseudonym_str(node), counter))
+                    .collect::<VectorClock>(),
+            ),
+        }
+    }
+}
+
+/// Remove the value at `pointer`; array elements become `null` so other
+/// pointers into the array stay valid.
+fn strip_pointer(value: &mut Value, pointer: &str) -> bool {
+    let Some((parent, token)) = pointer.rsplit_once('/') else {
+        return false;
+    };
+    let token = token.replace("~1", "/").replace("~0", "~");
+    match value.pointer_mut(parent) {
+        Some(Value::Object(fields)) => fields.remove(&token).is_some(),
+        Some(Value::Array(items)) => match token.parse::<usize>() {
+            Ok(index) if index < items.len() => {
+                items[index] = Value::Null;
+                true
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// What an anonymized export changed.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnonymizationReport {
+    /// Manifest of the anonymized snapshot
+    pub manifest: Option<SnapshotManifest>,
+    /// Events exported
+    pub events: u64,
+    /// Events whose payload was rewritten
+    pub payloads_rewritten: u64,
+    /// Payload fields replaced by pseudonyms
+    pub fields_hashed: u64,
+    /// Payload fields removed
+    pub fields_stripped: u64,
+    /// Blob references dropped
+    pub blobs_dropped: u64,
+}
+
+/// Indices of `headers` ordered so that parents precede their children.
+///
+/// Events keep their original relative order otherwise. Parents outside the
+/// set impose no constraint.
+fn causal_order(headers: &[EventHeader]) -> anyhow::Result<Vec<usize>> {
+    let index: HashMap<EventId, usize> = headers.iter().enumerate().map(|(i, h)| (h.id, i)).collect();
+    let mut waiting = vec![0usize; headers.len()];
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); headers.len()];
+    for (i, header) in headers.iter().enumerate() {
+        for parent in &header.parents {
+            if let Some(&p) = index.get(parent) {
+                waiting[i] += 1;
+                children[p].push(i);
+            }
+        }
+    }
+
+    let mut ready: VecDeque<usize> = (0..headers.len()).filter(|&i| waiting[i] == 0).collect();
+    let mut order = Vec::with_capacity(headers.len());
+    while let Some(i) = ready.pop_front() {
+        order.push(i);
+        for &child in &children[i] {
+            waiting[child] -= 1;
+            if waiting[child] == 0 {
+                ready.push_back(child);
+            }
+        }
+    }
+
+    if order.len() != headers.len() {
+        return Err(StorageError::InvalidSnapshot("snapshot events form a parent cycle".to_string()).into());
+    }
+    Ok(order)
+}
+
+/// Read the snapshot from `reader` and write its anonymized copy to `writer`.
+///
+/// The whole snapshot is held in memory, since digests can only be
+/// recomputed once every parent has been rewritten.
+pub async fn anonymize_snapshot(
+    reader: &mut (dyn AsyncRead + Unpin + Send),
+    writer: &mut (dyn AsyncWrite + Unpin + Send),
+    policy: &AnonymizationPolicy,
+) -> anyhow::Result<AnonymizationReport> {
+    let mut source = SnapshotReader::open(reader).await?;
+    let mut events = Vec::new();
+    while let Some(event) = source.next_event().await? {
+        events.push(event);
+    }
+    let headers: Vec<EventHeader> = events.iter().map(|(header, _)| header.clone()).collect();
+
+    let mut report = AnonymizationReport::default();
+    let mut digests: HashMap<EventId, CausalDigest> = HashMap::new();
+    let mut anonymized = Vec::with_capacity(events.len());
+    for i in causal_order(&headers)? {
+        let (header, payload) = &events[i];
+        let payload = match policy.anonymize_payload(header, payload, &mut report)? {
+            Some(rewritten) => {
+                report.payloads_rewritten += 1;
+                rewritten
+            }
+            None => payload.clone(),
+        };
+
+        // Parents outside the snapshot have no rewritten digest to chain
+        // from, so they contribute a pseudonym of their ID instead
+        let parent_digests: Vec<CausalDigest> = header
+            .parents
+            .iter()
+            .map(|parent| {
+                digests
+                    .get(parent)
+                    .copied()
+                    .unwrap_or_else(|| policy.keyed(b"parent", parent.as_bytes()))
+            })
+            .collect();
+        let digest = causal_hash(&payload, &parent_digests);
+        digests.insert(header.id, digest);
+
+        report.blobs_dropped += header.blobs.len() as u64;
+        let header = EventHeader {
+            id: policy.pseudonym(&header.id),
+            parents: header.parents.iter().map(|parent| policy.pseudonym(parent)).collect(),
+            timestamp: header.timestamp,
+            digest,
+            intent: policy.pseudonym(&header.intent),
+            kind: header.kind.clone(),
+            format: header.format,
+            clock: header.clock.as_ref().map(|clock| policy.anonymize_clock(clock)),
+            namespace: match &header.namespace {
+                Some(namespace) if policy.hash_namespaces => Some(policy.pseudonym_str(namespace)),
+                namespace => namespace.clone(),
+            },
+            blobs: Vec::new(),
+            idempotency_key: header.idempotency_key.as_deref().map(|key| policy.pseudonym_str(key)),
+        };
+        anonymized.push((header, payload));
+    }
+
+    let manifest = SnapshotManifest {
+        version: SNAPSHOT_VERSION,
+        created_at: Utc::now(),
+        // A dataset is not a backup; importing it must not move a store's WAL
+        wal_sequence: 0,
+        events: anonymized.len() as u64,
+        payloads: anonymized
+            .iter()
+            .map(|(header, _)| header.digest)
+            .collect::<std::collections::HashSet<_>>()
+            .len() as u64,
+    };
+    let mut output = SnapshotWriter::begin(writer, &manifest).await?;
+    for (header, payload) in &anonymized {
+        output.write_event(header, payload).await?;
+    }
+    output.finish().await?;
+
+    report.events = manifest.events;
+    report.manifest = Some(manifest);
+    Ok(report)
+}
+
+/// Anonymized dataset export for any snapshot-capable backend.
+#[async_trait]
+pub trait AnonymizedExportExt: SnapshotStorage {
+    /// Export every event to `writer` as a snapshot anonymized by `policy`.
+    async fn export_anonymized(
+        &self,
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+        policy: &AnonymizationPolicy,
+    ) -> anyhow::Result<AnonymizationReport> {
+        let mut snapshot = Vec::new();
+        self.export_snapshot(&mut Cursor::new(&mut snapshot)).await?;
+        anonymize_snapshot(&mut Cursor::new(snapshot), writer, policy).await
+    }
+}
+
+impl<T> AnonymizedExportExt for T where T: SnapshotStorage + ?Sized {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_event_header;
+    use futures::executor::block_on;
+
+    async fn snapshot_of(events: &[(EventHeader, Vec<u8>)]) -> Vec<u8> {
+        let manifest = SnapshotManifest {
+            version: SNAPSHOT_VERSION,
+            created_at: Utc::now(),
+            wal_sequence: 9,
+            events: events.len() as u64,
+            payloads: events.len() as u64,
+        };
+        let mut bytes = Vec::new();
+        let mut cursor = Cursor::new(&mut bytes);
+        let mut writer = SnapshotWriter::begin(&mut cursor, &manifest).await.unwrap();
+        for (header, payload) in events {
+            writer.write_event(header, payload).await.unwrap();
+        }
+        writer.finish().await.unwrap();
+        bytes
+    }
+
+    async fn read_all(bytes: Vec<u8>) -> Vec<(EventHeader, Value)> {
+        let mut cursor = Cursor::new(bytes);
+        let mut reader = SnapshotReader::open(&mut cursor).await.unwrap();
+        let mut events = Vec::new();
+        while let Some((header, payload)) = reader.next_event().await.unwrap() {
+            let value = header.format.decode(&payload).unwrap();
+            events.push((header, value));
+        }
+        events
+    }
+
+    #[test]
+    fn test_strip_pointer() {
+        let mut value = serde_json::json!({"a": {"b/c": 1, "d": [1, 2]}});
+        assert!(strip_pointer(&mut value, "/a/b~1c"));
+        assert!(strip_pointer(&mut value, "/a/d/0"));
+        assert!(!strip_pointer(&mut value, "/a/missing"));
+        assert!(!strip_pointer(&mut value, "/a/d/7"));
+        assert_eq!(value, serde_json::json!({"a": {"d": [null, 2]}}));
+    }
+
+    #[test]
+    fn test_anonymize_preserves_causal_structure() {
+        block_on(async {
+            let intent = Uuid::new_v4();
+            let agent = Uuid::new_v4();
+            let spawned = serde_json::json!({"agent": agent.to_string(), "note": "tenant secret", "cost": 5});
+            let root = create_event_header(&[], intent, "agent.spawned".to_string(), &spawned).unwrap();
+            let mut root = root.with_idempotency_key("spawn-1");
+            root.namespace = Some("tenant-a".to_string());
+            let finished = serde_json::json!({"agent": agent.to_string(), "result": "ok"});
+            let child = create_event_header(&[root.clone()], intent, "task.completed".to_string(), &finished).unwrap();
+
+            // Child first: the export must still write parents before children
+            let source = snapshot_of(&[
+                (child.clone(), rmp_serde::to_vec_named(&finished).unwrap()),
+                (root.clone(), rmp_serde::to_vec_named(&spawned).unwrap()),
+            ])
+            .await;
+
+            let policy = AnonymizationPolicy::new("partner-secret")
+                .hash_field("/agent")
+                .with_rule(FieldRule {
+                    kinds: "agent".to_string(),
+                    pointer: "/note".to_string(),
+                    action: FieldAction::Strip,
+                });
+            let mut output = Vec::new();
+            let report = anonymize_snapshot(&mut Cursor::new(source.clone()), &mut Cursor::new(&mut output), &policy)
+                .await
+                .unwrap();
+            assert_eq!(report.events, 2);
+            assert_eq!(report.payloads_rewritten, 2);
+            assert_eq!(report.fields_hashed, 2);
+            assert_eq!(report.fields_stripped, 1);
+            assert_eq!(report.manifest.as_ref().unwrap().wal_sequence, 0);
+
+            let events = read_all(output.clone()).await;
+            let (new_root, root_payload) = &events[0];
+            let (new_child, child_payload) = &events[1];
+
+            assert_eq!(new_root.id, policy.pseudonym(&root.id));
+            assert_ne!(new_root.id, root.id);
+            assert_eq!(new_child.parents.as_slice(), &[new_root.id]);
+            assert_eq!(new_child.intent, new_root.intent);
+            assert_eq!(new_root.namespace.as_deref(), Some(policy.pseudonym_str("tenant-a").as_str()));
+            assert_ne!(new_root.idempotency_key.as_deref(), Some("spawn-1"));
+
+            // Payload fields are rewritten and digests chain over the new bytes
+            assert_eq!(root_payload["agent"], Value::String(policy.pseudonym(&agent).to_string()));
+            assert_eq!(root_payload.get("note"), None);
+            assert_eq!(root_payload["cost"], Value::from(5));
+            assert_eq!(child_payload["agent"], root_payload["agent"]);
+            let child_bytes = rmp_serde::to_vec_named(child_payload).unwrap();
+            assert_eq!(new_child.digest, causal_hash(&child_bytes, &[new_root.digest]));
+
+            // The same secret yields the same dataset
+            let mut again = Vec::new();
+            anonymize_snapshot(&mut Cursor::new(source), &mut Cursor::new(&mut again), &policy).await.unwrap();
+            let repeated = read_all(again).await;
+            assert_eq!(repeated[1].0.id, new_child.id);
+            assert_eq!(repeated[1].0.digest, new_child.digest);
+        });
+    }
+}
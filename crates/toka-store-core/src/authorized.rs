@@ -0,0 +1,309 @@
+#![forbid(unsafe_code)]
+
+//! Store access control by event kind.
+//!
+//! In shared-store deployments every service holding a storage handle could
+//! otherwise read and write every event family. [`AuthorizedBackend`] binds a
+//! backend to the capability [`Claims`] of one service and checks each
+//! operation against a [`KindAcl`]: a kind is readable or writable only if a
+//! matching rule grants that access to a permission the claims hold.
+//!
+//! Rules select kinds with the same patterns as elsewhere in the store: `*`
+//! matches every kind, `agent` matches `agent` itself and dotted children such
+//! as `agent.spawned`. Kinds matching no rule are denied.
+//!
+//! Payloads are addressed by digest rather than by event, so
+//! [`payload_bytes`](StorageBackend::payload_bytes) is forwarded unchecked;
+//! digests are only learned from headers, which are checked.
+
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use toka_types::traits::Claims;
+
+use crate::{
+    CausalDigest, EventHeader, EventHeaderStream, EventId, IntentId, QueryableStorage, ReplayFrom,
+    ReplayableStorage, StorageBackend, StorageError,
+};
+
+/// Kind of access an ACL rule grants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KindAccess {
+    /// Read headers and stream events
+    Read,
+    /// Commit events
+    Write,
+}
+
+/// Grants `access` to kinds matching `selector` to holders of `permission`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KindAclRule {
+    /// `*`, an exact kind, or a kind family such as `agent`
+    pub selector: String,
+    /// Access granted
+    pub access: KindAccess,
+    /// Permission the claims must hold
+    pub permission: String,
+}
+
+impl KindAclRule {
+    /// Whether the rule applies to events of `kind`.
+    pub fn matches(&self, kind: &str) -> bool {
+        self.selector == "*"
+            || kind == self.selector
+            || kind
+                .strip_prefix(self.selector.as_str())
+                .is_some_and(|rest| rest.starts_with('.'))
+    }
+}
+
+/// Per-kind read and write rules.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KindAcl {
+    /// Rules; any matching rule whose permission is held grants access
+    pub rules: Vec<KindAclRule>,
+}
+
+impl KindAcl {
+    /// ACL denying everything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Let holders of `permission` read kinds matching `selector`.
+    pub fn allow_read(self, selector: impl Into<String>, permission: impl Into<String>) -> Self {
+        self.allow(selector, KindAccess::Read, permission)
+    }
+
+    /// Let holders of `permission` commit kinds matching `selector`.
+    pub fn allow_write(self, selector: impl Into<String>, permission: impl Into<String>) -> Self {
+        self.allow(selector, KindAccess::Write, permission)
+    }
+
+    /// Add a rule.
+    pub fn allow(
+        mut self,
+        selector: impl Into<String>,
+        access: KindAccess,
+        permission: impl Into<String>,
+    ) -> Self {
+        self.rules.push(KindAclRule {
+            selector: selector.into(),
+            access,
+            permission: permission.into(),
+        });
+        self
+    }
+
+    /// Whether holders of `permissions` have `access` to events of `kind`.
+    pub fn permits(&self, permissions: &HashSet<String>, kind: &str, access: KindAccess) -> bool {
+        self.rules.iter().any(|rule| {
+            rule.access == access && rule.matches(kind) && permissions.contains(&rule.permission)
+        })
+    }
+}
+
+/// Storage backend wrapper enforcing a [`KindAcl`] for one set of claims.
+#[derive(Debug, Clone)]
+pub struct AuthorizedBackend<B> {
+    inner: B,
+    acl: KindAcl,
+    subject: String,
+    permissions: HashSet<String>,
+    expires_at: u64,
+}
+
+impl<B> AuthorizedBackend<B> {
+    /// Restrict `inner` to what `acl` grants the holder of `claims`.
+    ///
+    /// Fails if the claims are malformed or expired at `now`. The expiry is
+    /// checked again on every operation.
+    pub fn new(
+        inner: B,
+        acl: KindAcl,
+        claims: &Claims,
+        now: DateTime<Utc>,
+    ) -> Result<Self, StorageError> {
+        claims
+            .validate()
+            .map_err(|e| StorageError::AccessDenied(e.to_string()))?;
+        let backend = Self {
+            inner,
+            acl,
+            subject: claims.sub.clone(),
+            permissions: claims.permissions.iter().cloned().collect(),
+            expires_at: claims.exp,
+        };
+        backend.check_expiry(now)?;
+        Ok(backend)
+    }
+
+    /// Access the wrapped backend.
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    /// Subject the backend acts for.
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// Whether the subject may access events of `kind`.
+    pub fn can(&self, kind: &str, access: KindAccess) -> bool {
+        self.acl.permits(&self.permissions, kind, access)
+    }
+
+    fn check_expiry(&self, now: DateTime<Utc>) -> Result<(), StorageError> {
+        if self.expires_at <= now.timestamp().max(0) as u64 {
+            return Err(StorageError::AccessDenied(format!(
+                "token for {} has expired",
+                self.subject
+            )));
+        }
+        Ok(())
+    }
+
+    fn check(&self, kind: &str, access: KindAccess) -> Result<(), StorageError> {
+        self.check_expiry(Utc::now())?;
+        if self.can(kind, access) {
+            Ok(())
+        } else {
+            let verb = match access {
+                KindAccess::Read => "read",
+                KindAccess::Write => "write",
+            };
+            Err(StorageError::AccessDenied(format!(
+                "{} may not {} events of kind {}",
+                self.subject, verb, kind
+            )))
+        }
+    }
+
+    /// Drop events the subject may not read from `stream`.
+    fn filter<'a>(
+        &'a self,
+        stream: EventHeaderStream<'a>,
+    ) -> anyhow::Result<EventHeaderStream<'a>> {
+        self.check_expiry(Utc::now())?;
+        // Borrow only the ACL so the stream does not require `B: Sync`
+        let (acl, permissions) = (&self.acl, &self.permissions);
+        Ok(Box::pin(stream.filter(move |item| {
+            let keep = match item {
+                Ok(header) => acl.permits(permissions, &header.kind, KindAccess::Read),
+                // Errors carry no event data, so they are passed through
+                Err(_) => true,
+            };
+            futures::future::ready(keep)
+        })))
+    }
+}
+
+#[async_trait]
+impl<B: StorageBackend> StorageBackend for AuthorizedBackend<B> {
+    async fn commit(&self, header: &EventHeader, payload: &[u8]) -> anyhow::Result<()> {
+        self.check(&header.kind, KindAccess::Write)?;
+        self.inner.commit(header, payload).await
+    }
+
+    async fn header(&self, id: &EventId) -> anyhow::Result<Option<EventHeader>> {
+        match self.inner.header(id).await? {
+            Some(header) => {
+                self.check(&header.kind, KindAccess::Read)?;
+                Ok(Some(header))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn payload_bytes(&self, digest: &CausalDigest) -> anyhow::Result<Option<Vec<u8>>> {
+        self.check_expiry(Utc::now())?;
+        self.inner.payload_bytes(digest).await
+    }
+
+    async fn payloads_bulk(
+        &self,
+        digests: &[CausalDigest],
+    ) -> anyhow::Result<std::collections::HashMap<CausalDigest, Vec<u8>>> {
+        self.check_expiry(Utc::now())?;
+        self.inner.payloads_bulk(digests).await
+    }
+}
+
+#[async_trait]
+impl<B: QueryableStorage> QueryableStorage for AuthorizedBackend<B> {
+    async fn events_by_kind(&self, kind: &str) -> anyhow::Result<EventHeaderStream<'_>> {
+        self.check(kind, KindAccess::Read)?;
+        self.inner.events_by_kind(kind).await
+    }
+
+    async fn events_in_range(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> anyhow::Result<EventHeaderStream<'_>> {
+        let stream = self.inner.events_in_range(from, to).await?;
+        self.filter(stream)
+    }
+
+    async fn events_by_intent(&self, intent: &IntentId) -> anyhow::Result<EventHeaderStream<'_>> {
+        let stream = self.inner.events_by_intent(intent).await?;
+        self.filter(stream)
+    }
+}
+
+#[async_trait]
+impl<B: ReplayableStorage> ReplayableStorage for AuthorizedBackend<B> {
+    async fn subscribe_from(&self, from: ReplayFrom) -> anyhow::Result<EventHeaderStream<'_>> {
+        let stream = self.inner.subscribe_from(from).await?;
+        self.filter(stream)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn claims(permissions: &[&str], exp_in: i64) -> Claims {
+        let now = Utc::now().timestamp();
+        Claims {
+            sub: "billing-service".to_string(),
+            vault: "default".to_string(),
+            permissions: permissions.iter().map(|p| p.to_string()).collect(),
+            iat: now as u64,
+            exp: (now + exp_in) as u64,
+            jti: Uuid::new_v4().to_string(),
+        }
+    }
+
+    #[test]
+    fn test_kind_acl() {
+        let acl = KindAcl::new()
+            .allow_read("*", "store:read:all")
+            .allow_read("billing", "billing:read")
+            .allow_write("billing.invoice", "billing:write");
+        let service = claims(&["billing:read", "billing:write"], 600);
+        let backend = AuthorizedBackend::new((), acl.clone(), &service, Utc::now()).unwrap();
+
+        assert!(backend.can("billing", KindAccess::Read));
+        assert!(backend.can("billing.invoice.paid", KindAccess::Read));
+        assert!(!backend.can("billingx", KindAccess::Read));
+        assert!(!backend.can("agent.spawned", KindAccess::Read));
+        assert!(backend.can("billing.invoice", KindAccess::Write));
+        assert!(!backend.can("billing.refund", KindAccess::Write));
+        assert!(backend.check("agent.spawned", KindAccess::Read).is_err());
+
+        let auditor = claims(&["store:read:all"], 600);
+        let auditor = AuthorizedBackend::new((), acl.clone(), &auditor, Utc::now()).unwrap();
+        assert!(auditor.can("agent.spawned", KindAccess::Read));
+        assert!(!auditor.can("billing.invoice", KindAccess::Write));
+
+        // Expired tokens are refused
+        let expired = claims(&["billing:read"], -1);
+        assert!(AuthorizedBackend::new((), acl, &expired, Utc::now()).is_err());
+    }
+}
@@ -0,0 +1,258 @@
+#![forbid(unsafe_code)]
+
+//! Content-addressed storage for large artifacts.
+//!
+//! Compiled binaries, large tool outputs and similar execution artifacts are
+//! too big to live in event payloads. A [`BlobStore`] keeps them on the side,
+//! addressed by the Blake3 hash of their content, and events refer to them
+//! through the [`BlobRef`]s listed in [`EventHeader::blobs`](crate::EventHeader::blobs).
+//!
+//! Blobs are streamed in and out through [`futures::io`] readers so an
+//! artifact never has to be held in memory as a whole. Storing the same
+//! content twice yields the same reference and keeps a single copy.
+//!
+//! [`FsBlobStore`] keeps one file per blob under a directory; backends may
+//! provide their own implementations.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use futures::io::{AllowStdIo, AsyncRead, AsyncReadExt, Cursor};
+use serde::{Deserialize, Serialize};
+
+/// Blake3 hash of a blob's content.
+pub type BlobDigest = [u8; 32];
+
+/// Size of the chunks blobs are read and written in (1 MiB).
+pub const BLOB_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Reader streaming a blob's content.
+pub type BlobReader = Box<dyn AsyncRead + Send + Unpin>;
+
+/// Reference from an event to a stored blob.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BlobRef {
+    /// Blake3 hash of the content
+    pub digest: BlobDigest,
+    /// Content length in bytes
+    pub size: u64,
+}
+
+impl BlobRef {
+    /// Reference to `bytes`, without storing them.
+    pub fn for_bytes(bytes: &[u8]) -> Self {
+        Self {
+            digest: *blake3::hash(bytes).as_bytes(),
+            size: bytes.len() as u64,
+        }
+    }
+
+    /// Hex encoding of the digest, as used in blob paths and keys.
+    pub fn hex(&self) -> String {
+        digest_hex(&self.digest)
+    }
+}
+
+/// Hex encoding of a blob digest.
+pub fn digest_hex(digest: &BlobDigest) -> String {
+    blake3::Hash::from(*digest).to_hex().to_string()
+}
+
+/// Read from `reader` until `chunk` is full or the reader is exhausted.
+///
+/// Returns the number of bytes read; fewer than `chunk.len()` means the end
+/// was reached. Filling whole chunks keeps chunk boundaries independent of
+/// how the reader happens to split its data.
+pub async fn read_chunk(
+    reader: &mut (dyn AsyncRead + Unpin + Send),
+    chunk: &mut [u8],
+) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < chunk.len() {
+        let read = reader.read(&mut chunk[filled..]).await?;
+        if read == 0 {
+            break;
+        }
+        filled += read;
+    }
+    Ok(filled)
+}
+
+/// Read `reader` to the end in [`BLOB_CHUNK_SIZE`] chunks, hashing as it goes.
+///
+/// Each chunk is handed to `sink` before the next one is read. Returns the
+/// reference of the content read.
+pub async fn stream_chunks<F>(
+    reader: &mut (dyn AsyncRead + Unpin + Send),
+    mut sink: F,
+) -> anyhow::Result<BlobRef>
+where
+    F: FnMut(&[u8]) -> anyhow::Result<()> + Send,
+{
+    let mut hasher = blake3::Hasher::new();
+    let mut size = 0u64;
+    let mut chunk = vec![0u8; BLOB_CHUNK_SIZE];
+    loop {
+        let filled = read_chunk(reader, &mut chunk).await?;
+        if filled == 0 {
+            break;
+        }
+        hasher.update(&chunk[..filled]);
+        size += filled as u64;
+        sink(&chunk[..filled])?;
+        if filled < chunk.len() {
+            break;
+        }
+    }
+    Ok(BlobRef {
+        digest: *hasher.finalize().as_bytes(),
+        size,
+    })
+}
+
+/// Content-addressed store for large binary artifacts.
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    /// Store the content of `reader` and return its reference.
+    ///
+    /// Storing content that is already present is a no-op returning the
+    /// same reference.
+    async fn put(&self, reader: &mut (dyn AsyncRead + Unpin + Send)) -> anyhow::Result<BlobRef>;
+
+    /// Stream the content of a blob, or `None` if it is not stored.
+    async fn get(&self, digest: &BlobDigest) -> anyhow::Result<Option<BlobReader>>;
+
+    /// Whether a blob is stored.
+    async fn contains(&self, digest: &BlobDigest) -> anyhow::Result<bool>;
+
+    /// Delete a blob. Returns whether it was stored.
+    ///
+    /// Callers are responsible for making sure no event still references it.
+    async fn delete(&self, digest: &BlobDigest) -> anyhow::Result<bool>;
+
+    /// Store an in-memory buffer.
+    async fn put_bytes(&self, bytes: &[u8]) -> anyhow::Result<BlobRef> {
+        self.put(&mut Cursor::new(bytes)).await
+    }
+
+    /// Read a whole blob into memory.
+    async fn get_bytes(&self, digest: &BlobDigest) -> anyhow::Result<Option<Vec<u8>>> {
+        match self.get(digest).await? {
+            Some(mut reader) => {
+                let mut bytes = Vec::new();
+                reader.read_to_end(&mut bytes).await?;
+                Ok(Some(bytes))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Blob store keeping one file per blob under a root directory.
+///
+/// Blobs live at `<root>/<first two hex digits>/<hex digest>`. Uploads are
+/// written to `<root>/tmp` first and renamed into place once their hash is
+/// known, so readers never see partial blobs. File I/O is blocking and runs
+/// on the calling task.
+#[derive(Debug, Clone)]
+pub struct FsBlobStore {
+    root: PathBuf,
+}
+
+impl FsBlobStore {
+    /// Open (creating if needed) a blob store rooted at `root`.
+    pub fn open(root: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(root.join("tmp"))?;
+        Ok(Self { root })
+    }
+
+    /// Root directory of the store.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Path a blob is stored at.
+    pub fn blob_path(&self, digest: &BlobDigest) -> PathBuf {
+        let hex = digest_hex(digest);
+        self.root.join(&hex[..2]).join(hex)
+    }
+}
+
+#[async_trait]
+impl BlobStore for FsBlobStore {
+    async fn put(&self, reader: &mut (dyn AsyncRead + Unpin + Send)) -> anyhow::Result<BlobRef> {
+        let temp_path = self.root.join("tmp").join(uuid::Uuid::new_v4().to_string());
+        let mut temp = fs::File::create(&temp_path)?;
+
+        let blob = match stream_chunks(reader, |chunk| Ok(temp.write_all(chunk)?)).await {
+            Ok(blob) => blob,
+            Err(e) => {
+                let _ = fs::remove_file(&temp_path);
+                return Err(e);
+            }
+        };
+        temp.sync_all()?;
+        drop(temp);
+
+        let path = self.blob_path(&blob.digest);
+        if path.exists() {
+            fs::remove_file(&temp_path)?;
+        } else {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::rename(&temp_path, &path)?;
+        }
+        Ok(blob)
+    }
+
+    async fn get(&self, digest: &BlobDigest) -> anyhow::Result<Option<BlobReader>> {
+        match fs::File::open(self.blob_path(digest)) {
+            Ok(file) => Ok(Some(Box::new(AllowStdIo::new(file)))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn contains(&self, digest: &BlobDigest) -> anyhow::Result<bool> {
+        Ok(self.blob_path(digest).is_file())
+    }
+
+    async fn delete(&self, digest: &BlobDigest) -> anyhow::Result<bool> {
+        match fs::remove_file(self.blob_path(digest)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    #[test]
+    fn test_fs_blob_store_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FsBlobStore::open(dir.path()).unwrap();
+
+        // Larger than one chunk so the streaming path is exercised
+        let artifact: Vec<u8> = (0..BLOB_CHUNK_SIZE + 17).map(|i| (i % 251) as u8).collect();
+        let blob = block_on(store.put_bytes(&artifact)).unwrap();
+        assert_eq!(blob, BlobRef::for_bytes(&artifact));
+        assert!(store.blob_path(&blob.digest).is_file());
+
+        // Storing the same content again is deduplicated
+        assert_eq!(block_on(store.put_bytes(&artifact)).unwrap(), blob);
+        assert_eq!(fs::read_dir(dir.path().join("tmp")).unwrap().count(), 0);
+
+        assert_eq!(block_on(store.get_bytes(&blob.digest)).unwrap(), Some(artifact));
+        assert!(block_on(store.delete(&blob.digest)).unwrap());
+        assert!(!block_on(store.contains(&blob.digest)).unwrap());
+        assert!(block_on(store.get(&blob.digest)).unwrap().is_none());
+    }
+}
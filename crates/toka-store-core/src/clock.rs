@@ -0,0 +1,306 @@
+#![forbid(unsafe_code)]
+
+//! Logical clocks for ordering events across nodes.
+//!
+//! Wall-clock timestamps from different machines drift, so two events from
+//! different nodes cannot be ordered reliably by [`EventHeader::timestamp`].
+//! This module stamps an optional [`LogicalClock`] into the header at commit
+//! time:
+//!
+//! - [`LamportClock`] yields a single counter that respects causality
+//!   (`a → b` implies `L(a) < L(b)`) and gives a cheap total order;
+//! - [`VectorClockSource`] yields a per-node [`VectorClock`] that additionally
+//!   detects concurrent events.
+//!
+//! [`ClockedStorageExt::commit_clocked`] ticks the clock, merges the clocks of
+//! the event's parents and commits the stamped header. Nodes receiving events
+//! from elsewhere should pass them to [`ClockSource::observe`] so local
+//! clocks move past them.
+
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{EventHeader, StorageBackend};
+
+/// How two logical timestamps relate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClockOrdering {
+    /// The first timestamp happened before the second
+    Before,
+    /// The first timestamp happened after the second
+    After,
+    /// Both timestamps are identical
+    Equal,
+    /// Neither happened before the other
+    Concurrent,
+}
+
+/// Vector clock mapping node (or agent) identifiers to event counters.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct VectorClock(BTreeMap<String, u64>);
+
+impl VectorClock {
+    /// Create an empty clock.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counter recorded for `node` (zero if absent).
+    pub fn get(&self, node: &str) -> u64 {
+        self.0.get(node).copied().unwrap_or(0)
+    }
+
+    /// Increment the counter of `node` and return its new value.
+    pub fn increment(&mut self, node: &str) -> u64 {
+        let counter = self.0.entry(node.to_string()).or_insert(0);
+        *counter += 1;
+        *counter
+    }
+
+    /// Take the element-wise maximum with `other`.
+    pub fn merge(&mut self, other: &VectorClock) {
+        for (node, counter) in &other.0 {
+            let entry = self.0.entry(node.clone()).or_insert(0);
+            *entry = (*entry).max(*counter);
+        }
+    }
+
+    /// Compare two clocks under the happened-before relation.
+    pub fn compare(&self, other: &VectorClock) -> ClockOrdering {
+        let mut less = false;
+        let mut greater = false;
+        for node in self.0.keys().chain(other.0.keys()) {
+            match self.get(node).cmp(&other.get(node)) {
+                CmpOrdering::Less => less = true,
+                CmpOrdering::Greater => greater = true,
+                CmpOrdering::Equal => {}
+            }
+        }
+        match (less, greater) {
+            (false, false) => ClockOrdering::Equal,
+            (true, false) => ClockOrdering::Before,
+            (false, true) => ClockOrdering::After,
+            (true, true) => ClockOrdering::Concurrent,
+        }
+    }
+
+    /// Iterate over `(node, counter)` pairs.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u64)> {
+        self.0.iter().map(|(node, counter)| (node.as_str(), *counter))
+    }
+}
+
+impl FromIterator<(String, u64)> for VectorClock {
+    fn from_iter<I: IntoIterator<Item = (String, u64)>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+/// Logical timestamp stamped into an [`EventHeader`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LogicalClock {
+    /// Lamport timestamp
+    Lamport(u64),
+    /// Vector timestamp
+    Vector(VectorClock),
+}
+
+impl LogicalClock {
+    /// Compare two logical timestamps.
+    ///
+    /// Lamport timestamps are totally ordered and never report
+    /// [`ClockOrdering::Concurrent`]. Returns `None` when the two timestamps
+    /// are of different kinds.
+    pub fn compare(&self, other: &LogicalClock) -> Option<ClockOrdering> {
+        match (self, other) {
+            (LogicalClock::Lamport(a), LogicalClock::Lamport(b)) => Some(match a.cmp(b) {
+                CmpOrdering::Less => ClockOrdering::Before,
+                CmpOrdering::Greater => ClockOrdering::After,
+                CmpOrdering::Equal => ClockOrdering::Equal,
+            }),
+            (LogicalClock::Vector(a), LogicalClock::Vector(b)) => Some(a.compare(b)),
+            _ => None,
+        }
+    }
+}
+
+/// A node-local logical clock that stamps and observes events.
+pub trait ClockSource: Send + Sync {
+    /// Advance the clock for a local event and return its timestamp.
+    ///
+    /// `parents` are the clocks of the event's causal parents; the result
+    /// is guaranteed to be after each of them.
+    fn tick(&self, parents: &[&LogicalClock]) -> LogicalClock;
+
+    /// Move the clock past an event received from another node.
+    fn observe(&self, clock: &LogicalClock);
+}
+
+/// Lamport clock shared by all writers of one node.
+#[derive(Debug, Default)]
+pub struct LamportClock {
+    counter: AtomicU64,
+}
+
+impl LamportClock {
+    /// Create a clock starting at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current counter value.
+    pub fn current(&self) -> u64 {
+        self.counter.load(Ordering::SeqCst)
+    }
+}
+
+impl ClockSource for LamportClock {
+    fn tick(&self, parents: &[&LogicalClock]) -> LogicalClock {
+        let floor = parents
+            .iter()
+            .filter_map(|clock| match clock {
+                LogicalClock::Lamport(value) => Some(*value),
+                LogicalClock::Vector(_) => None,
+            })
+            .max()
+            .unwrap_or(0);
+        let previous = self
+            .counter
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| Some(current.max(floor) + 1))
+            .unwrap_or_else(|current| current);
+        LogicalClock::Lamport(previous.max(floor) + 1)
+    }
+
+    fn observe(&self, clock: &LogicalClock) {
+        if let LogicalClock::Lamport(value) = clock {
+            self.counter.fetch_max(*value, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Vector clock owned by a single node or agent.
+#[derive(Debug)]
+pub struct VectorClockSource {
+    node: String,
+    clock: Mutex<VectorClock>,
+}
+
+impl VectorClockSource {
+    /// Create a clock for `node`, typically an agent or node identifier.
+    pub fn new(node: impl Into<String>) -> Self {
+        Self {
+            node: node.into(),
+            clock: Mutex::new(VectorClock::new()),
+        }
+    }
+
+    /// Identifier of the owning node.
+    pub fn node(&self) -> &str {
+        &self.node
+    }
+
+    /// Snapshot of the current clock.
+    pub fn current(&self) -> VectorClock {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VectorClock> {
+        // A poisoned clock is still a valid clock
+        self.clock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl ClockSource for VectorClockSource {
+    fn tick(&self, parents: &[&LogicalClock]) -> LogicalClock {
+        let mut clock = self.lock();
+        for parent in parents {
+            if let LogicalClock::Vector(parent) = parent {
+                clock.merge(parent);
+            }
+        }
+        clock.increment(&self.node);
+        LogicalClock::Vector(clock.clone())
+    }
+
+    fn observe(&self, clock: &LogicalClock) {
+        if let LogicalClock::Vector(remote) = clock {
+            self.lock().merge(remote);
+        }
+    }
+}
+
+/// Commit helper stamping logical clocks into headers.
+#[async_trait]
+pub trait ClockedStorageExt: StorageBackend {
+    /// Stamp `header` with the next timestamp of `clock` and commit it.
+    ///
+    /// The clocks of the header's parents are looked up in this store and
+    /// merged first, so the stamped clock is after every parent. Parents
+    /// that are missing or unstamped are ignored. Returns the committed header.
+    async fn commit_clocked(
+        &self,
+        clock: &dyn ClockSource,
+        mut header: EventHeader,
+        payload: &[u8],
+    ) -> anyhow::Result<EventHeader> {
+        let mut parent_clocks = Vec::with_capacity(header.parents.len());
+        for parent in &header.parents {
+            if let Some(parent) = self.header(parent).await? {
+                parent_clocks.extend(parent.clock);
+            }
+        }
+
+        header.clock = Some(clock.tick(&parent_clocks.iter().collect::<Vec<_>>()));
+        self.commit(&header, payload).await?;
+        Ok(header)
+    }
+}
+
+impl<T> ClockedStorageExt for T where T: StorageBackend + ?Sized {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vector_clock_ordering() {
+        let a = VectorClockSource::new("node-a");
+        let b = VectorClockSource::new("node-b");
+
+        let a1 = a.tick(&[]);
+        let b1 = b.tick(&[]);
+        assert_eq!(a1.compare(&b1), Some(ClockOrdering::Concurrent));
+
+        // b sees a1, so its next event happened after it
+        b.observe(&a1);
+        let b2 = b.tick(&[]);
+        assert_eq!(a1.compare(&b2), Some(ClockOrdering::Before));
+        assert_eq!(b2.compare(&b1), Some(ClockOrdering::After));
+
+        // Parents are merged when ticking
+        let a2 = a.tick(&[&b2]);
+        assert_eq!(b2.compare(&a2), Some(ClockOrdering::Before));
+        assert_eq!(a2.compare(&a2.clone()), Some(ClockOrdering::Equal));
+    }
+
+    #[test]
+    fn test_lamport_clock() {
+        let clock = LamportClock::new();
+        assert_eq!(clock.tick(&[]), LogicalClock::Lamport(1));
+        assert_eq!(clock.tick(&[&LogicalClock::Lamport(10)]), LogicalClock::Lamport(11));
+
+        clock.observe(&LogicalClock::Lamport(20));
+        assert_eq!(clock.tick(&[]), LogicalClock::Lamport(21));
+        assert_eq!(clock.current(), 21);
+        assert_eq!(
+            LogicalClock::Lamport(1).compare(&LogicalClock::Vector(VectorClock::new())),
+            None
+        );
+    }
+}
@@ -0,0 +1,201 @@
+#![forbid(unsafe_code)]
+
+//! Pluggable serialization formats for event payloads.
+//!
+//! Payloads have historically been encoded with MessagePack everywhere. This
+//! module lets producers choose a [`PayloadFormat`] per backend or per event
+//! kind via a [`FormatPolicy`]. The chosen format is recorded in the
+//! [`EventHeader`](crate::EventHeader), so stores holding a mix of formats stay
+//! readable: readers decode with [`PayloadFormat::decode`] using the header's
+//! format rather than assuming one.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{EventHeader, EventPayload, IntentId, StorageError};
+
+/// Serialization format used to encode an event payload.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadFormat {
+    /// MessagePack with named fields (the historical default)
+    #[default]
+    MessagePack,
+    /// CBOR (RFC 8949)
+    Cbor,
+    /// JSON
+    Json,
+}
+
+impl PayloadFormat {
+    /// Encode a payload in this format.
+    pub fn encode<P: EventPayload>(&self, payload: &P) -> Result<Vec<u8>, StorageError> {
+        match self {
+            PayloadFormat::MessagePack => rmp_serde::to_vec_named(payload)
+                .map_err(|e| StorageError::SerializationFailed(e.to_string())),
+            PayloadFormat::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(payload, &mut bytes)
+                    .map_err(|e| StorageError::SerializationFailed(e.to_string()))?;
+                Ok(bytes)
+            }
+            PayloadFormat::Json => serde_json::to_vec(payload)
+                .map_err(|e| StorageError::SerializationFailed(e.to_string())),
+        }
+    }
+
+    /// Decode a payload previously encoded in this format.
+    pub fn decode<P: EventPayload>(&self, bytes: &[u8]) -> Result<P, StorageError> {
+        match self {
+            PayloadFormat::MessagePack => rmp_serde::from_slice(bytes)
+                .map_err(|e| StorageError::DeserializationFailed(e.to_string())),
+            PayloadFormat::Cbor => ciborium::from_reader(bytes)
+                .map_err(|e| StorageError::DeserializationFailed(e.to_string())),
+            PayloadFormat::Json => serde_json::from_slice(bytes)
+                .map_err(|e| StorageError::DeserializationFailed(e.to_string())),
+        }
+    }
+
+    /// Short, stable name of the format (e.g. for logs or config files).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PayloadFormat::MessagePack => "messagepack",
+            PayloadFormat::Cbor => "cbor",
+            PayloadFormat::Json => "json",
+        }
+    }
+}
+
+/// Chooses a [`PayloadFormat`] for each event kind.
+///
+/// The policy holds a backend-wide default plus optional per-kind overrides.
+/// Kind overrides match either exactly (`agent.spawn`) or by prefix when the
+/// key ends in `.*` (`agent.*`); exact matches win over prefixes and longer
+/// prefixes win over shorter ones.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FormatPolicy {
+    /// Format used when no kind override matches
+    pub default: PayloadFormat,
+    /// Per-kind overrides
+    #[serde(default)]
+    pub by_kind: HashMap<String, PayloadFormat>,
+}
+
+impl FormatPolicy {
+    /// Create a policy using `default` for every kind.
+    pub fn new(default: PayloadFormat) -> Self {
+        Self {
+            default,
+            by_kind: HashMap::new(),
+        }
+    }
+
+    /// Override the format for a kind (or a `prefix.*` pattern).
+    pub fn with_kind(mut self, kind: impl Into<String>, format: PayloadFormat) -> Self {
+        self.by_kind.insert(kind.into(), format);
+        self
+    }
+
+    /// Resolve the format to use for `kind`.
+    pub fn format_for(&self, kind: &str) -> PayloadFormat {
+        resolve_kind(&self.by_kind, kind)
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    /// Build a header and the encoded payload bytes for an event of `kind`,
+    /// using the format this policy selects for the kind.
+    ///
+    /// See [`create_event_header_with_format`](crate::create_event_header_with_format).
+    pub fn create_event_header<P: EventPayload>(
+        &self,
+        parents: &[EventHeader],
+        intent: IntentId,
+        kind: String,
+        payload: &P,
+    ) -> Result<(EventHeader, Vec<u8>), StorageError> {
+        let format = self.format_for(&kind);
+        crate::create_event_header_with_format(parents, intent, kind, payload, format)
+    }
+}
+
+/// Look up `kind` in a map keyed by exact kinds or `prefix.*` patterns.
+///
+/// Exact matches win over prefixes and longer prefixes win over shorter ones.
+pub(crate) fn resolve_kind<'a, T>(by_kind: &'a HashMap<String, T>, kind: &str) -> Option<&'a T> {
+    if let Some(value) = by_kind.get(kind) {
+        return Some(value);
+    }
+
+    by_kind
+        .iter()
+        .filter_map(|(pattern, value)| {
+            let prefix = pattern.strip_suffix(".*")?;
+            let matches = kind
+                .strip_prefix(prefix)
+                .is_some_and(|rest| rest.starts_with('.'));
+            matches.then_some((prefix.len(), value))
+        })
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    struct TestEvent {
+        message: String,
+        value: i32,
+    }
+
+    #[test]
+    fn test_round_trip_all_formats() {
+        let event = TestEvent {
+            message: "codec".to_string(),
+            value: 7,
+        };
+
+        for format in [PayloadFormat::MessagePack, PayloadFormat::Cbor, PayloadFormat::Json] {
+            let bytes = format.encode(&event).unwrap();
+            let decoded: TestEvent = format.decode(&bytes).unwrap();
+            assert_eq!(decoded, event, "round trip failed for {}", format.as_str());
+        }
+    }
+
+    #[test]
+    fn test_policy_resolution() {
+        let policy = FormatPolicy::new(PayloadFormat::MessagePack)
+            .with_kind("agent.*", PayloadFormat::Json)
+            .with_kind("agent.llm.*", PayloadFormat::Cbor)
+            .with_kind("agent.spawn", PayloadFormat::MessagePack);
+
+        assert_eq!(policy.format_for("ledger.mint"), PayloadFormat::MessagePack);
+        assert_eq!(policy.format_for("agent.task"), PayloadFormat::Json);
+        assert_eq!(policy.format_for("agent.llm.request"), PayloadFormat::Cbor);
+        assert_eq!(policy.format_for("agent.spawn"), PayloadFormat::MessagePack);
+        assert_eq!(policy.format_for("agents.other"), PayloadFormat::MessagePack);
+    }
+
+    #[test]
+    fn test_policy_encodes_by_kind() {
+        let policy = FormatPolicy::new(PayloadFormat::MessagePack).with_kind("agent.*", PayloadFormat::Cbor);
+        let event = TestEvent {
+            message: "policy".to_string(),
+            value: 9,
+        };
+
+        let (header, bytes) = policy
+            .create_event_header(&[], uuid::Uuid::nil(), "agent.task".to_string(), &event)
+            .unwrap();
+        assert_eq!(header.format, PayloadFormat::Cbor);
+        assert_eq!(PayloadFormat::Cbor.decode::<TestEvent>(&bytes).unwrap(), event);
+
+        let (header, _) = policy
+            .create_event_header(&[], uuid::Uuid::nil(), "ledger.mint".to_string(), &event)
+            .unwrap();
+        assert_eq!(header.format, PayloadFormat::MessagePack);
+    }
+}
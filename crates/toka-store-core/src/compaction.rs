@@ -0,0 +1,248 @@
+#![forbid(unsafe_code)]
+
+//! Intent-level compaction of finished event chains.
+//!
+//! Long-running intents can accumulate thousands of intermediate events
+//! (progress ticks, partial results) whose only lasting value is the final
+//! state. Compaction folds those intermediate events into a single
+//! [`CompactionSummary`] event and removes them from the store.
+//!
+//! What gets folded is decided per kind by a [`CompactionPolicy`]. The summary
+//! records the IDs, time range and a combined digest of everything it
+//! archived, so the removed range stays auditable (e.g. against an external
+//! archive) even after the originals are gone.
+//!
+//! Compaction should only be run on intents that have finished; it does not
+//! check this itself.
+
+use std::collections::{BTreeMap, HashMap};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::codec::resolve_kind;
+use crate::{
+    causal_hash, create_event_header, CausalDigest, EventHeader, EventId, IntentId, QueryableStorage,
+    StorageError,
+};
+
+/// Event kind used for compaction summaries.
+pub const COMPACTION_SUMMARY_KIND: &str = "store.compaction.summary";
+
+/// How events of a given kind are treated during compaction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KindCompaction {
+    /// Keep every event of this kind
+    #[default]
+    Retain,
+    /// Keep only the most recent event of this kind and fold the rest
+    KeepLatest,
+    /// Fold every event of this kind into the summary
+    Archive,
+}
+
+/// Per-kind compaction rules.
+///
+/// Kind keys match exactly or by `prefix.*`, following the same resolution
+/// rules as [`FormatPolicy`](crate::FormatPolicy).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CompactionPolicy {
+    /// Rule used when no kind rule matches
+    pub default: KindCompaction,
+    /// Per-kind rules
+    #[serde(default)]
+    pub by_kind: HashMap<String, KindCompaction>,
+}
+
+impl CompactionPolicy {
+    /// Create a policy applying `default` to every kind.
+    pub fn new(default: KindCompaction) -> Self {
+        Self {
+            default,
+            by_kind: HashMap::new(),
+        }
+    }
+
+    /// Set the rule for a kind (or a `prefix.*` pattern).
+    pub fn with_kind(mut self, kind: impl Into<String>, rule: KindCompaction) -> Self {
+        self.by_kind.insert(kind.into(), rule);
+        self
+    }
+
+    /// Resolve the rule to apply to `kind`.
+    pub fn rule_for(&self, kind: &str) -> KindCompaction {
+        if kind == COMPACTION_SUMMARY_KIND {
+            // Summaries are the audit trail; never fold them away
+            return KindCompaction::Retain;
+        }
+        resolve_kind(&self.by_kind, kind)
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// Payload of a compaction summary event.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CompactionSummary {
+    /// Intent that was compacted
+    pub intent: IntentId,
+    /// Timestamp of the earliest archived event
+    pub archived_from: DateTime<Utc>,
+    /// Timestamp of the latest archived event
+    pub archived_to: DateTime<Utc>,
+    /// IDs of the archived events, oldest first
+    pub archived_events: Vec<EventId>,
+    /// Combined digest over the archived events' digests, in order
+    pub archived_digest: CausalDigest,
+    /// Number of archived events per kind
+    pub archived_kinds: BTreeMap<String, usize>,
+    /// IDs of the events left in place
+    pub retained_events: Vec<EventId>,
+}
+
+/// Decide which events of an intent to fold.
+///
+/// `headers` must all belong to `intent`. Returns `None` if the policy would
+/// not archive anything.
+pub fn plan_compaction(
+    intent: IntentId,
+    headers: &[EventHeader],
+    policy: &CompactionPolicy,
+) -> Option<CompactionSummary> {
+    let mut ordered: Vec<&EventHeader> = headers.iter().collect();
+    ordered.sort_by_key(|header| header.timestamp);
+
+    // Latest event per kind, for `KeepLatest`
+    let latest: HashMap<&str, EventId> = ordered
+        .iter()
+        .map(|header| (header.kind.as_str(), header.id))
+        .collect();
+
+    let mut archived = Vec::new();
+    let mut retained_events = Vec::new();
+    for header in ordered {
+        let fold = match policy.rule_for(&header.kind) {
+            KindCompaction::Retain => false,
+            KindCompaction::KeepLatest => latest.get(header.kind.as_str()) != Some(&header.id),
+            KindCompaction::Archive => true,
+        };
+        if fold {
+            archived.push(header);
+        } else {
+            retained_events.push(header.id);
+        }
+    }
+
+    let (first, last) = (archived.first()?, archived.last()?);
+    let digests: Vec<CausalDigest> = archived.iter().map(|header| header.digest).collect();
+    let mut archived_kinds = BTreeMap::new();
+    for header in &archived {
+        *archived_kinds.entry(header.kind.clone()).or_insert(0) += 1;
+    }
+
+    Some(CompactionSummary {
+        intent,
+        archived_from: first.timestamp,
+        archived_to: last.timestamp,
+        archived_events: archived.iter().map(|header| header.id).collect(),
+        archived_digest: causal_hash(&[], &digests),
+        archived_kinds,
+        retained_events,
+    })
+}
+
+/// Storage backends that can remove events and compact intents.
+#[async_trait]
+pub trait CompactableStorage: QueryableStorage {
+    /// Remove the given events.
+    ///
+    /// Payloads that are no longer referenced by any remaining event are
+    /// removed as well. Unknown IDs are ignored. Returns the number of
+    /// events removed.
+    async fn remove_events(&self, ids: &[EventId]) -> anyhow::Result<usize>;
+
+    /// Fold the intermediate events of a finished intent into a summary event.
+    ///
+    /// The summary is committed before anything is removed, so an interrupted
+    /// compaction leaves extra events behind rather than losing data. Returns
+    /// the summary header, or `None` if the policy archived nothing.
+    async fn compact_intent(
+        &self,
+        intent: &IntentId,
+        policy: &CompactionPolicy,
+    ) -> anyhow::Result<Option<EventHeader>> {
+        let headers: Vec<EventHeader> = self.events_by_intent(intent).await?.try_collect().await?;
+        let Some(summary) = plan_compaction(*intent, &headers, policy) else {
+            return Ok(None);
+        };
+
+        // Chain the summary onto the final event of the intent
+        let parents: Vec<EventHeader> = headers
+            .iter()
+            .max_by_key(|header| header.timestamp)
+            .cloned()
+            .into_iter()
+            .collect();
+        let header = create_event_header(&parents, *intent, COMPACTION_SUMMARY_KIND.to_string(), &summary)
+            .map_err(|e| StorageError::SerializationFailed(e.to_string()))?;
+        let payload = rmp_serde::to_vec_named(&summary)
+            .map_err(|e| StorageError::SerializationFailed(e.to_string()))?;
+
+        self.commit(&header, &payload).await?;
+        self.remove_events(&summary.archived_events).await?;
+        Ok(Some(header))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn header(intent: IntentId, kind: &str, value: u32) -> EventHeader {
+        create_event_header(&[], intent, kind.to_string(), &value).unwrap()
+    }
+
+    #[test]
+    fn test_plan_compaction() {
+        let intent = Uuid::new_v4();
+        let headers = vec![
+            header(intent, "task.started", 0),
+            header(intent, "task.progress", 1),
+            header(intent, "task.progress", 2),
+            header(intent, "task.log.debug", 3),
+            header(intent, "task.progress", 4),
+            header(intent, "task.completed", 5),
+        ];
+
+        let policy = CompactionPolicy::default()
+            .with_kind("task.progress", KindCompaction::KeepLatest)
+            .with_kind("task.log.*", KindCompaction::Archive);
+        let summary = plan_compaction(intent, &headers, &policy).unwrap();
+
+        assert_eq!(
+            summary.archived_events,
+            vec![headers[1].id, headers[2].id, headers[3].id]
+        );
+        assert_eq!(
+            summary.retained_events,
+            vec![headers[0].id, headers[4].id, headers[5].id]
+        );
+        assert_eq!(summary.archived_kinds["task.progress"], 2);
+        assert_eq!(summary.archived_from, headers[1].timestamp);
+        assert_eq!(summary.archived_to, headers[3].timestamp);
+
+        // Nothing to do under the default policy
+        assert!(plan_compaction(intent, &headers, &CompactionPolicy::default()).is_none());
+    }
+
+    #[test]
+    fn test_summaries_are_never_archived() {
+        let policy = CompactionPolicy::new(KindCompaction::Archive);
+        assert_eq!(policy.rule_for(COMPACTION_SUMMARY_KIND), KindCompaction::Retain);
+        assert_eq!(policy.rule_for("task.progress"), KindCompaction::Archive);
+    }
+}
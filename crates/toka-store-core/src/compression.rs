@@ -0,0 +1,190 @@
+#![forbid(unsafe_code)]
+
+//! Transparent payload compression for storage backends.
+//!
+//! Backends store payload bytes exactly as they were committed unless a
+//! [`CompressionPolicy`] is configured. With a policy, each payload is
+//! compressed individually and the [`Compression`] actually used is stored
+//! next to the payload's digest, so readers always know how to restore the
+//! original bytes. Small payloads, or payloads that do not shrink, are kept
+//! uncompressed.
+//!
+//! The causal digest always covers the *uncompressed* bytes; compression is
+//! purely a storage concern and never changes event identity.
+//!
+//! Codec implementations are gated behind the `zstd` and `lz4` features.
+
+use serde::{Deserialize, Serialize};
+
+use crate::StorageError;
+
+/// Compression codec applied to a stored payload.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// Stored as-is
+    #[default]
+    None,
+    /// Zstandard
+    Zstd,
+    /// LZ4 (block format with size prefix)
+    Lz4,
+}
+
+impl Compression {
+    /// Stable numeric identifier for persisting the codec next to a payload.
+    pub fn id(&self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Zstd => 1,
+            Compression::Lz4 => 2,
+        }
+    }
+
+    /// Look up a codec by its persisted identifier.
+    pub fn from_id(id: u8) -> Result<Self, StorageError> {
+        match id {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Zstd),
+            2 => Ok(Compression::Lz4),
+            other => Err(StorageError::BackendError(format!(
+                "unknown compression codec id: {}",
+                other
+            ))),
+        }
+    }
+
+    /// Compress `bytes` with this codec.
+    pub fn compress(&self, bytes: &[u8]) -> Result<Vec<u8>, StorageError> {
+        match self {
+            Compression::None => Ok(bytes.to_vec()),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::bulk::compress(bytes, ZSTD_LEVEL)
+                .map_err(|e| StorageError::BackendError(format!("zstd compression failed: {}", e))),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => Ok(lz4_flex::compress_prepend_size(bytes)),
+            #[allow(unreachable_patterns)]
+            other => Err(Self::disabled(*other)),
+        }
+    }
+
+    /// Restore bytes previously compressed with this codec.
+    pub fn decompress(&self, bytes: &[u8]) -> Result<Vec<u8>, StorageError> {
+        match self {
+            Compression::None => Ok(bytes.to_vec()),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::stream::decode_all(bytes)
+                .map_err(|e| StorageError::BackendError(format!("zstd decompression failed: {}", e))),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => lz4_flex::decompress_size_prepended(bytes)
+                .map_err(|e| StorageError::BackendError(format!("lz4 decompression failed: {}", e))),
+            #[allow(unreachable_patterns)]
+            other => Err(Self::disabled(*other)),
+        }
+    }
+
+    #[allow(dead_code)]
+    fn disabled(codec: Compression) -> StorageError {
+        StorageError::BackendError(format!(
+            "compression codec {:?} is not enabled in this build",
+            codec
+        ))
+    }
+}
+
+/// Zstandard level used for payloads; favours speed over ratio.
+#[cfg(feature = "zstd")]
+const ZSTD_LEVEL: i32 = 3;
+
+/// Default minimum payload size worth compressing, in bytes.
+pub const DEFAULT_MIN_COMPRESS_SIZE: usize = 256;
+
+/// Decides whether and how to compress each payload.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressionPolicy {
+    /// Preferred codec
+    pub codec: Compression,
+    /// Payloads smaller than this are stored uncompressed
+    pub min_size: usize,
+}
+
+impl Default for CompressionPolicy {
+    fn default() -> Self {
+        Self::disabled()
+    }
+}
+
+impl CompressionPolicy {
+    /// Store every payload uncompressed.
+    pub fn disabled() -> Self {
+        Self {
+            codec: Compression::None,
+            min_size: DEFAULT_MIN_COMPRESS_SIZE,
+        }
+    }
+
+    /// Compress payloads of at least [`DEFAULT_MIN_COMPRESS_SIZE`] bytes with `codec`.
+    pub fn new(codec: Compression) -> Self {
+        Self {
+            codec,
+            min_size: DEFAULT_MIN_COMPRESS_SIZE,
+        }
+    }
+
+    /// Set the minimum payload size worth compressing.
+    pub fn with_min_size(mut self, min_size: usize) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    /// Compress `payload` if worthwhile, returning the codec used and the stored bytes.
+    ///
+    /// Falls back to [`Compression::None`] when the payload is below
+    /// `min_size` or compression does not make it smaller.
+    pub fn apply(&self, payload: &[u8]) -> Result<(Compression, Vec<u8>), StorageError> {
+        if self.codec == Compression::None || payload.len() < self.min_size {
+            return Ok((Compression::None, payload.to_vec()));
+        }
+
+        let compressed = self.codec.compress(payload)?;
+        if compressed.len() < payload.len() {
+            Ok((self.codec, compressed))
+        } else {
+            Ok((Compression::None, payload.to_vec()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codec_ids_round_trip() {
+        for codec in [Compression::None, Compression::Zstd, Compression::Lz4] {
+            assert_eq!(Compression::from_id(codec.id()).unwrap(), codec);
+        }
+        assert!(Compression::from_id(42).is_err());
+    }
+
+    #[test]
+    fn test_policy_skips_small_payloads() {
+        let policy = CompressionPolicy::new(Compression::Zstd).with_min_size(1024);
+        let (codec, stored) = policy.apply(b"tiny").unwrap();
+        assert_eq!(codec, Compression::None);
+        assert_eq!(stored, b"tiny");
+    }
+
+    #[cfg(all(feature = "zstd", feature = "lz4"))]
+    #[test]
+    fn test_compression_round_trip() {
+        let payload = br#"{"message":"hello","value":42}"#.repeat(100);
+
+        for codec in [Compression::Zstd, Compression::Lz4] {
+            let (used, stored) = CompressionPolicy::new(codec).apply(&payload).unwrap();
+            assert_eq!(used, codec);
+            assert!(stored.len() < payload.len());
+            assert_eq!(used.decompress(&stored).unwrap(), payload);
+        }
+    }
+}
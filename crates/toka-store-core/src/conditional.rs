@@ -0,0 +1,256 @@
+#![forbid(unsafe_code)]
+
+//! Conditional commits for optimistic concurrency.
+//!
+//! Agents writing to the same store can coordinate without a separate lock
+//! service by attaching a [`Precondition`] to a commit. The backend checks
+//! the precondition and commits the event atomically; if it does not hold,
+//! nothing is written and the commit fails with
+//! [`StorageError::Conflict`] carrying a typed [`CommitConflict`].
+//!
+//! The usual pattern is compare-and-swap on an intent: read the latest event
+//! of the intent, build the next event on top of it and commit with
+//! [`Precondition::intent_head`]. A concurrent writer that got there first
+//! makes the commit fail, and the agent re-reads and retries.
+
+use std::collections::{HashMap, HashSet};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{EventHeader, EventId, IntentId, StorageBackend, StorageError};
+
+/// Condition that must hold for a conditional commit to go through.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Precondition {
+    /// The event must already be stored
+    Exists(EventId),
+    /// The event must not be stored yet
+    Absent(EventId),
+    /// The latest event of the intent must be `expected`, or the intent
+    /// must have no events when `expected` is `None`
+    IntentHead {
+        /// Intent to check
+        intent: IntentId,
+        /// Expected latest event
+        expected: Option<EventId>,
+    },
+    /// Every precondition must hold
+    All(Vec<Precondition>),
+}
+
+impl Precondition {
+    /// Require `id` to exist, e.g. the parent an event builds on.
+    pub fn parent_exists(id: EventId) -> Self {
+        Precondition::Exists(id)
+    }
+
+    /// Require every parent of `header` to exist.
+    pub fn parents_exist(header: &EventHeader) -> Self {
+        Precondition::All(header.parents.iter().copied().map(Precondition::Exists).collect())
+    }
+
+    /// Require `expected` to still be the latest event of `intent`.
+    ///
+    /// This is the "no newer event for this intent" check used for
+    /// compare-and-swap updates.
+    pub fn intent_head(intent: IntentId, expected: Option<EventId>) -> Self {
+        Precondition::IntentHead { intent, expected }
+    }
+
+    /// Combine with another precondition.
+    pub fn and(self, other: Precondition) -> Self {
+        match self {
+            Precondition::All(mut all) => {
+                all.push(other);
+                Precondition::All(all)
+            }
+            first => Precondition::All(vec![first, other]),
+        }
+    }
+
+    /// Events whose existence the precondition depends on.
+    pub fn events(&self) -> HashSet<EventId> {
+        let mut events = HashSet::new();
+        self.visit(&mut |precondition| match precondition {
+            Precondition::Exists(id) | Precondition::Absent(id) => {
+                events.insert(*id);
+            }
+            _ => {}
+        });
+        events
+    }
+
+    /// Intents whose latest event the precondition depends on.
+    pub fn intents(&self) -> HashSet<IntentId> {
+        let mut intents = HashSet::new();
+        self.visit(&mut |precondition| {
+            if let Precondition::IntentHead { intent, .. } = precondition {
+                intents.insert(*intent);
+            }
+        });
+        intents
+    }
+
+    fn visit(&self, f: &mut dyn FnMut(&Precondition)) {
+        match self {
+            Precondition::All(all) => all.iter().for_each(|precondition| precondition.visit(f)),
+            leaf => f(leaf),
+        }
+    }
+
+    /// Check the precondition against a view of the store.
+    ///
+    /// `exists` reports whether an event is stored and `head` returns the
+    /// latest event of an intent. Backends call this while holding whatever
+    /// lock makes the check and the following write atomic. The first
+    /// violated condition is reported.
+    pub fn evaluate(
+        &self,
+        exists: &dyn Fn(&EventId) -> bool,
+        head: &dyn Fn(&IntentId) -> Option<EventId>,
+    ) -> Result<(), CommitConflict> {
+        match self {
+            Precondition::Exists(id) if !exists(id) => Err(CommitConflict::MissingEvent(*id)),
+            Precondition::Absent(id) if exists(id) => Err(CommitConflict::AlreadyExists(*id)),
+            Precondition::IntentHead { intent, expected } => {
+                let actual = head(intent);
+                if actual == *expected {
+                    Ok(())
+                } else {
+                    Err(CommitConflict::IntentHeadMoved {
+                        intent: *intent,
+                        expected: *expected,
+                        actual,
+                    })
+                }
+            }
+            Precondition::All(all) => all
+                .iter()
+                .try_for_each(|precondition| precondition.evaluate(exists, head)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the precondition against snapshots of the relevant state.
+    ///
+    /// `stored` holds the events of [`Precondition::events`] that exist and
+    /// `heads` the latest event of each intent in [`Precondition::intents`].
+    pub fn evaluate_snapshot(
+        &self,
+        stored: &HashSet<EventId>,
+        heads: &HashMap<IntentId, EventId>,
+    ) -> Result<(), CommitConflict> {
+        self.evaluate(&|id| stored.contains(id), &|intent| heads.get(intent).copied())
+    }
+}
+
+/// Why a conditional commit was refused.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum CommitConflict {
+    /// A required event is not stored
+    #[error("required event {0} does not exist")]
+    MissingEvent(EventId),
+    /// An event that must be absent is already stored
+    #[error("event {0} already exists")]
+    AlreadyExists(EventId),
+    /// Another event was committed to the intent since it was read
+    #[error("intent {intent} has moved: expected head {expected:?}, found {actual:?}")]
+    IntentHeadMoved {
+        /// The intent
+        intent: IntentId,
+        /// Head the writer expected
+        expected: Option<EventId>,
+        /// Head actually found
+        actual: Option<EventId>,
+    },
+}
+
+/// Extract the conflict from an error returned by
+/// [`ConditionalStorage::commit_if`], if that is why it failed.
+pub fn as_conflict(error: &anyhow::Error) -> Option<&CommitConflict> {
+    match error.downcast_ref::<StorageError>() {
+        Some(StorageError::Conflict(conflict)) => Some(conflict),
+        _ => None,
+    }
+}
+
+/// Latest event among `headers`.
+///
+/// Events are ordered by timestamp; ties are broken by the larger id so
+/// every backend agrees on the same head.
+pub fn intent_head<'a>(headers: impl IntoIterator<Item = &'a EventHeader>) -> Option<EventId> {
+    headers
+        .into_iter()
+        .max_by_key(|header| (header.timestamp, header.id))
+        .map(|header| header.id)
+}
+
+/// Storage backends supporting atomic conditional commits.
+#[async_trait]
+pub trait ConditionalStorage: StorageBackend {
+    /// Commit the event only if `precondition` holds.
+    ///
+    /// The check and the write are atomic with respect to other commits on
+    /// the same backend. Fails with [`StorageError::Conflict`] when the
+    /// precondition does not hold.
+    async fn commit_if(
+        &self,
+        header: &EventHeader,
+        payload: &[u8],
+        precondition: &Precondition,
+    ) -> anyhow::Result<()>;
+
+    /// Latest event of `intent`, as [`Precondition::intent_head`] sees it.
+    ///
+    /// Reads only the head, however long the intent's chain is.
+    async fn intent_head(&self, intent: &IntentId) -> anyhow::Result<Option<EventHeader>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_evaluate_preconditions() {
+        let stored_id = Uuid::new_v4();
+        let missing_id = Uuid::new_v4();
+        let intent = Uuid::new_v4();
+        let stored: HashSet<_> = [stored_id].into_iter().collect();
+        let heads: HashMap<_, _> = [(intent, stored_id)].into_iter().collect();
+
+        let ok = Precondition::parent_exists(stored_id)
+            .and(Precondition::Absent(missing_id))
+            .and(Precondition::intent_head(intent, Some(stored_id)))
+            .and(Precondition::intent_head(Uuid::new_v4(), None));
+        assert_eq!(ok.evaluate_snapshot(&stored, &heads), Ok(()));
+        assert_eq!(ok.events().len(), 2);
+        assert_eq!(ok.intents().len(), 2);
+
+        assert_eq!(
+            Precondition::parent_exists(missing_id).evaluate_snapshot(&stored, &heads),
+            Err(CommitConflict::MissingEvent(missing_id))
+        );
+        assert_eq!(
+            Precondition::Absent(stored_id).evaluate_snapshot(&stored, &heads),
+            Err(CommitConflict::AlreadyExists(stored_id))
+        );
+        assert_eq!(
+            Precondition::intent_head(intent, None).evaluate_snapshot(&stored, &heads),
+            Err(CommitConflict::IntentHeadMoved {
+                intent,
+                expected: None,
+                actual: Some(stored_id),
+            })
+        );
+    }
+
+    #[test]
+    fn test_as_conflict() {
+        let conflict = CommitConflict::MissingEvent(Uuid::new_v4());
+        let error = anyhow::Error::from(StorageError::Conflict(conflict.clone()));
+        assert_eq!(as_conflict(&error), Some(&conflict));
+        assert!(as_conflict(&anyhow::anyhow!("unrelated")).is_none());
+    }
+}
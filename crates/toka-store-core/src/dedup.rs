@@ -0,0 +1,260 @@
+#![forbid(unsafe_code)]
+
+//! Event deduplication by idempotency key.
+//!
+//! Agents that retry after a timeout sometimes re-emit the same logical
+//! event under a fresh UUID. Writers can tag events with an idempotency key
+//! via [`EventHeader::with_idempotency_key`]; backends remember the keys they
+//! have committed and drop a later event carrying the same key (within the
+//! same namespace) if it arrives within the dedup window.
+//!
+//! [`StorageBackend::commit`] silently drops duplicates. Callers that need
+//! to know which event their write resolved to use
+//! [`DeduplicatingStorage::commit_deduplicated`], which returns the id of the
+//! original event for duplicates.
+//!
+//! The window is measured between the event timestamps, so it is unaffected
+//! by how late a retried commit reaches the backend.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{EventHeader, EventId, StorageBackend};
+
+/// Default window within which events with the same key are duplicates.
+pub const DEFAULT_DEDUP_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+/// Result of a deduplicated commit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CommitOutcome {
+    /// The event was stored
+    Committed(EventId),
+    /// The event duplicated an earlier one and was dropped; holds the
+    /// original event's id
+    Duplicate(EventId),
+}
+
+impl CommitOutcome {
+    /// Id of the stored event: the committed one or the original.
+    pub fn event_id(&self) -> EventId {
+        match self {
+            CommitOutcome::Committed(id) | CommitOutcome::Duplicate(id) => *id,
+        }
+    }
+
+    /// Whether the event was dropped as a duplicate.
+    pub fn is_duplicate(&self) -> bool {
+        matches!(self, CommitOutcome::Duplicate(_))
+    }
+}
+
+/// Whether an event at `candidate` falls within `window` of one at `original`.
+pub fn within_window(original: DateTime<Utc>, candidate: DateTime<Utc>, window: Duration) -> bool {
+    let delta = candidate.signed_duration_since(original).num_milliseconds().unsigned_abs();
+    u128::from(delta) <= window.as_millis()
+}
+
+/// In-memory index of recently committed idempotency keys.
+///
+/// Keys are scoped by namespace. Entries older than the window relative to
+/// the newest recorded event are pruned as new keys are recorded, so an
+/// event with a skewed or old timestamp never evicts newer keys.
+#[derive(Clone, Debug)]
+pub struct IdempotencyIndex {
+    window: Duration,
+    entries: HashMap<(String, String), (EventId, DateTime<Utc>)>,
+    /// Keys by event, for forgetting removed events
+    keys: HashMap<EventId, (String, String)>,
+    /// Recorded events, oldest first
+    by_time: BTreeMap<(DateTime<Utc>, EventId), (String, String)>,
+    newest: Option<DateTime<Utc>>,
+}
+
+impl Default for IdempotencyIndex {
+    fn default() -> Self {
+        Self::new(DEFAULT_DEDUP_WINDOW)
+    }
+}
+
+impl IdempotencyIndex {
+    /// Create an empty index with the given dedup window.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            entries: HashMap::new(),
+            keys: HashMap::new(),
+            by_time: BTreeMap::new(),
+            newest: None,
+        }
+    }
+
+    /// The dedup window.
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Number of remembered keys.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no keys are remembered.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Original event that `header` duplicates, if any.
+    pub fn find(&self, header: &EventHeader) -> Option<EventId> {
+        let key = Self::key(header)?;
+        self.entries
+            .get(&key)
+            .filter(|(_, timestamp)| within_window(*timestamp, header.timestamp, self.window))
+            .map(|(id, _)| *id)
+    }
+
+    /// Remember the key of a committed event.
+    pub fn record(&mut self, header: &EventHeader) {
+        let Some(key) = Self::key(header) else {
+            return;
+        };
+        self.forget(&header.id);
+        if let Some((previous, timestamp)) = self.entries.remove(&key) {
+            self.keys.remove(&previous);
+            self.by_time.remove(&(timestamp, previous));
+        }
+        self.entries.insert(key.clone(), (header.id, header.timestamp));
+        self.keys.insert(header.id, key.clone());
+        self.by_time.insert((header.timestamp, header.id), key);
+
+        let newest = self.newest.map_or(header.timestamp, |newest| newest.max(header.timestamp));
+        self.newest = Some(newest);
+        while let Some(entry) = self.by_time.first_entry() {
+            let (timestamp, id) = *entry.key();
+            if within_window(timestamp, newest, self.window) {
+                break;
+            }
+            let key = entry.remove();
+            self.entries.remove(&key);
+            self.keys.remove(&id);
+        }
+    }
+
+    /// Forget the key of a removed event.
+    pub fn forget(&mut self, id: &EventId) {
+        let Some(key) = self.keys.remove(id) else {
+            return;
+        };
+        if let Some((_, timestamp)) = self.entries.remove(&key) {
+            self.by_time.remove(&(timestamp, *id));
+        }
+    }
+
+    /// Forget every key.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.keys.clear();
+        self.by_time.clear();
+        self.newest = None;
+    }
+
+    fn key(header: &EventHeader) -> Option<(String, String)> {
+        let key = header.idempotency_key.as_ref()?;
+        Some((header.namespace_or_default().to_string(), key.clone()))
+    }
+}
+
+/// Storage backends that drop events duplicating an idempotency key.
+#[async_trait]
+pub trait DeduplicatingStorage: StorageBackend {
+    /// Commit the event unless it duplicates a recent one.
+    ///
+    /// Events without an idempotency key are always committed. The check
+    /// and the write are atomic with respect to other commits on the same
+    /// backend.
+    async fn commit_deduplicated(
+        &self,
+        header: &EventHeader,
+        payload: &[u8],
+    ) -> anyhow::Result<CommitOutcome>;
+
+    /// Window within which events with the same key are duplicates.
+    fn dedup_window(&self) -> Duration;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_event_header;
+    use uuid::Uuid;
+
+    fn keyed(key: &str) -> EventHeader {
+        create_event_header(&[], Uuid::new_v4(), "test.dedup".to_string(), &1u32)
+            .unwrap()
+            .with_idempotency_key(key)
+    }
+
+    #[test]
+    fn test_index_detects_duplicates_within_window() {
+        let mut index = IdempotencyIndex::new(Duration::from_secs(60));
+        let original = keyed("order-1");
+        assert_eq!(index.find(&original), None);
+        index.record(&original);
+
+        let retry = keyed("order-1");
+        assert_eq!(index.find(&retry), Some(original.id));
+        assert_eq!(index.find(&keyed("order-2")), None);
+
+        // Same key in another namespace is a different event
+        let mut other = keyed("order-1");
+        other.namespace = Some("tenant-b".to_string());
+        assert_eq!(index.find(&other), None);
+
+        // Outside the window the key is reusable and old entries are pruned
+        let mut late = keyed("order-1");
+        late.timestamp = original.timestamp + chrono::Duration::seconds(61);
+        assert_eq!(index.find(&late), None);
+        index.record(&late);
+        assert_eq!(index.len(), 1);
+
+        index.forget(&late.id);
+        assert!(index.is_empty());
+    }
+
+    #[test]
+    fn test_old_timestamps_do_not_evict_newer_keys() {
+        let mut index = IdempotencyIndex::new(Duration::from_secs(60));
+        let recent = keyed("order-1");
+        index.record(&recent);
+
+        // An event stamped by a lagging clock is pruned itself, not `recent`
+        let mut skewed = keyed("order-2");
+        skewed.timestamp = recent.timestamp - chrono::Duration::seconds(120);
+        index.record(&skewed);
+        assert_eq!(index.find(&keyed("order-1")), Some(recent.id));
+        assert_eq!(index.len(), 1);
+
+        // Entries within the window of the newest event survive
+        let mut next = keyed("order-3");
+        next.timestamp = recent.timestamp + chrono::Duration::seconds(30);
+        index.record(&next);
+        assert_eq!(index.len(), 2);
+
+        index.forget(&recent.id);
+        assert_eq!(index.find(&keyed("order-1")), None);
+        assert_eq!(index.len(), 1);
+    }
+
+    #[test]
+    fn test_unkeyed_events_are_never_duplicates() {
+        let mut index = IdempotencyIndex::default();
+        let header = create_event_header(&[], Uuid::new_v4(), "test.dedup".to_string(), &1u32).unwrap();
+        index.record(&header);
+        assert!(index.is_empty());
+        assert_eq!(index.find(&header), None);
+        assert_eq!(CommitOutcome::Duplicate(header.id).event_id(), header.id);
+    }
+}
@@ -0,0 +1,351 @@
+#![forbid(unsafe_code)]
+
+//! Atomic writes to two backends with two-phase commit.
+//!
+//! Deployments that keep the same events in a local store and a central one
+//! wrap both in a [`DualWriteBackend`]. Every write runs as a two-phase
+//! commit over the backends' write-ahead logs:
+//!
+//! 1. **Prepare**: a WAL transaction is opened on each backend and the events
+//!    are logged to both. Any failure rolls both transactions back and the
+//!    write fails with nothing applied.
+//! 2. **Commit**: the primary's transaction is committed first. That commit
+//!    is the decision point: once it succeeded the write is durable and the
+//!    secondary's transaction is committed after it.
+//!
+//! A crash or error between the two commits leaves the secondary behind; its
+//! WAL recovery rolls the prepared transaction back. [`reconcile`] closes the
+//! gap by re-applying every transaction committed in the primary's WAL whose
+//! events the secondary is missing, and [`recover`] runs both recoveries and
+//! then reconciles. A transaction the primary never committed is rolled back
+//! on both sides, so the two stores always converge on the primary's
+//! decision.
+//!
+//! Reads are served by the primary.
+//!
+//! [`reconcile`]: DualWriteBackend::reconcile
+//! [`recover`]: DualWriteBackend::recover
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::lock::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::replication::{committed_events, transaction_status, TransactionStatus};
+use crate::{
+    CausalDigest, EventHeader, EventHeaderStream, EventId, IntentId, QueryableStorage, ReplayFrom,
+    ReplayableStorage, SequenceNumber, StorageBackend, TransactionId, WalEntry, WalRecoveryResult,
+    WalSource, WalStorageBackend,
+};
+
+/// Default number of primary WAL entries read per reconciliation batch.
+pub const DEFAULT_RECONCILE_BATCH: usize = 512;
+
+/// Write transactions prepared on both backends, awaiting commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreparedWrite {
+    /// Transaction on the primary
+    pub primary_transaction: TransactionId,
+    /// Transaction on the secondary
+    pub secondary_transaction: TransactionId,
+    /// Events logged in both transactions
+    pub events: Vec<EventId>,
+}
+
+/// Result of committing a [`PreparedWrite`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DualWriteOutcome {
+    /// Transaction committed on the primary
+    pub primary_transaction: TransactionId,
+    /// Whether the secondary committed too; if not, the events reach it on
+    /// the next reconciliation
+    pub secondary_committed: bool,
+    /// Error from the secondary's commit, if it failed
+    pub secondary_error: Option<String>,
+}
+
+/// What a [`DualWriteBackend::reconcile`] run found and repaired.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReconcileReport {
+    /// Committed primary transactions examined
+    pub transactions_checked: usize,
+    /// Transactions with at least one event missing from the secondary
+    pub transactions_repaired: usize,
+    /// Events re-applied to the secondary
+    pub events_repaired: usize,
+    /// Transactions still open on the primary, left for a later run
+    pub transactions_pending: usize,
+}
+
+/// Outcome of [`DualWriteBackend::recover`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DualWriteRecovery {
+    /// WAL recovery of the primary
+    pub primary: WalRecoveryResult,
+    /// WAL recovery of the secondary
+    pub secondary: WalRecoveryResult,
+    /// Events re-applied to the secondary afterwards
+    pub reconciliation: ReconcileReport,
+    /// When recovery finished
+    pub completed_at: DateTime<Utc>,
+}
+
+/// Backend writing every event to a primary and a secondary store atomically.
+pub struct DualWriteBackend<P, S> {
+    primary: P,
+    secondary: S,
+    batch_size: usize,
+    /// Primary WAL position up to which reconciliation is complete
+    reconciled: Mutex<SequenceNumber>,
+}
+
+impl<P, S> DualWriteBackend<P, S>
+where
+    P: WalStorageBackend + WalSource,
+    S: WalStorageBackend,
+{
+    /// Write to `primary` and `secondary`; the primary decides every commit.
+    pub fn new(primary: P, secondary: S) -> Self {
+        Self {
+            primary,
+            secondary,
+            batch_size: DEFAULT_RECONCILE_BATCH,
+            reconciled: Mutex::new(0),
+        }
+    }
+
+    /// Read at most `batch_size` primary WAL entries per reconciliation batch.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// The primary backend.
+    pub fn primary(&self) -> &P {
+        &self.primary
+    }
+
+    /// The secondary backend.
+    pub fn secondary(&self) -> &S {
+        &self.secondary
+    }
+
+    /// Commit `events` to both backends as one transaction.
+    ///
+    /// Fails, with nothing applied anywhere, if preparing on either backend
+    /// or committing on the primary fails. A failed secondary commit is
+    /// reported in the outcome and repaired by [`reconcile`](Self::reconcile).
+    pub async fn commit_batch(&self, events: &[(EventHeader, Vec<u8>)]) -> anyhow::Result<DualWriteOutcome> {
+        let prepared = self.prepare(events).await?;
+        self.commit_prepared(&prepared).await
+    }
+
+    /// Phase one: log `events` in a new transaction on each backend.
+    ///
+    /// On error both transactions are rolled back. A successful prepare must
+    /// be followed by [`commit_prepared`](Self::commit_prepared) or
+    /// [`abort`](Self::abort).
+    pub async fn prepare(&self, events: &[(EventHeader, Vec<u8>)]) -> anyhow::Result<PreparedWrite> {
+        let primary_transaction = self.primary.begin_transaction().await?;
+        let secondary_transaction = match self.secondary.begin_transaction().await {
+            Ok(transaction) => transaction,
+            Err(e) => {
+                let _ = self.primary.rollback_transaction(primary_transaction).await;
+                return Err(e.context("Failed to begin transaction on secondary"));
+            }
+        };
+        let prepared = PreparedWrite {
+            primary_transaction,
+            secondary_transaction,
+            events: events.iter().map(|(header, _)| header.id).collect(),
+        };
+
+        for (header, payload) in events {
+            let logged = match self.primary.commit_with_wal(primary_transaction, header, payload).await {
+                Ok(()) => self
+                    .secondary
+                    .commit_with_wal(secondary_transaction, header, payload)
+                    .await
+                    .map_err(|e| e.context("Failed to prepare event on secondary")),
+                Err(e) => Err(e.context("Failed to prepare event on primary")),
+            };
+            if let Err(e) = logged {
+                let _ = self.abort(&prepared).await;
+                return Err(e);
+            }
+        }
+        Ok(prepared)
+    }
+
+    /// Phase two: commit on the primary, then on the secondary.
+    pub async fn commit_prepared(&self, prepared: &PreparedWrite) -> anyhow::Result<DualWriteOutcome> {
+        if let Err(e) = self.primary.commit_transaction(prepared.primary_transaction).await {
+            let _ = self.secondary.rollback_transaction(prepared.secondary_transaction).await;
+            return Err(e.context("Failed to commit transaction on primary"));
+        }
+
+        let secondary_error = match self.secondary.commit_transaction(prepared.secondary_transaction).await {
+            Ok(()) => None,
+            Err(e) => {
+                // The primary decided; drop the prepared transaction and let
+                // reconciliation re-apply the events
+                let _ = self.secondary.rollback_transaction(prepared.secondary_transaction).await;
+                Some(e.to_string())
+            }
+        };
+        Ok(DualWriteOutcome {
+            primary_transaction: prepared.primary_transaction,
+            secondary_committed: secondary_error.is_none(),
+            secondary_error,
+        })
+    }
+
+    /// Roll a prepared write back on both backends.
+    pub async fn abort(&self, prepared: &PreparedWrite) -> anyhow::Result<()> {
+        let primary = self.primary.rollback_transaction(prepared.primary_transaction).await;
+        let secondary = self.secondary.rollback_transaction(prepared.secondary_transaction).await;
+        primary.and(secondary)
+    }
+
+    /// Re-apply transactions committed on the primary that the secondary is
+    /// missing events of.
+    ///
+    /// Resumes after the last fully reconciled position; transactions still
+    /// open on the primary hold that position back until they finish.
+    pub async fn reconcile(&self) -> anyhow::Result<ReconcileReport> {
+        let mut reconciled = self.reconciled.lock().await;
+        let mut report = ReconcileReport::default();
+        let mut after = *reconciled;
+        let mut pinned = false;
+        loop {
+            let entries = self.primary.wal_entries_after(after, self.batch_size).await?;
+            let Some(last_read) = entries.last().map(|entry| entry.sequence) else {
+                break;
+            };
+            let full_batch = entries.len() == self.batch_size;
+
+            let mut order: BTreeMap<SequenceNumber, TransactionId> = BTreeMap::new();
+            let mut grouped: HashMap<TransactionId, Vec<WalEntry>> = HashMap::new();
+            for entry in entries {
+                let group = grouped.entry(entry.transaction_id).or_default();
+                if group.is_empty() {
+                    order.insert(entry.sequence, entry.transaction_id);
+                }
+                group.push(entry);
+            }
+
+            for (first_sequence, transaction_id) in order {
+                let entries = &grouped[&transaction_id];
+                match transaction_status(entries) {
+                    TransactionStatus::Open => {
+                        report.transactions_pending += 1;
+                        if !pinned {
+                            pinned = true;
+                            *reconciled = first_sequence.saturating_sub(1);
+                        }
+                    }
+                    TransactionStatus::RolledBack => {}
+                    TransactionStatus::Committed => {
+                        report.transactions_checked += 1;
+                        let mut repaired = 0;
+                        for (header, payload) in committed_events(entries) {
+                            if self.secondary.header(&header.id).await?.is_none() {
+                                self.secondary.commit(header, payload).await?;
+                                repaired += 1;
+                            }
+                        }
+                        if repaired > 0 {
+                            report.transactions_repaired += 1;
+                            report.events_repaired += repaired;
+                        }
+                    }
+                }
+            }
+
+            after = last_read;
+            if !pinned {
+                *reconciled = last_read;
+            }
+            if !full_batch {
+                break;
+            }
+        }
+        Ok(report)
+    }
+
+    /// Recover both WALs, then reconcile the secondary with the primary.
+    pub async fn recover(&self) -> anyhow::Result<DualWriteRecovery> {
+        let primary = self.primary.recover().await?;
+        let secondary = self.secondary.recover().await?;
+        // Recovery may replay transactions behind the reconciled position
+        *self.reconciled.lock().await = 0;
+        let reconciliation = self.reconcile().await?;
+        Ok(DualWriteRecovery {
+            primary,
+            secondary,
+            reconciliation,
+            completed_at: Utc::now(),
+        })
+    }
+}
+
+#[async_trait]
+impl<P, S> StorageBackend for DualWriteBackend<P, S>
+where
+    P: WalStorageBackend + WalSource,
+    S: WalStorageBackend,
+{
+    async fn commit(&self, header: &EventHeader, payload: &[u8]) -> anyhow::Result<()> {
+        self.commit_batch(&[(header.clone(), payload.to_vec())]).await.map(|_| ())
+    }
+
+    async fn header(&self, id: &EventId) -> anyhow::Result<Option<EventHeader>> {
+        self.primary.header(id).await
+    }
+
+    async fn payload_bytes(&self, digest: &CausalDigest) -> anyhow::Result<Option<Vec<u8>>> {
+        self.primary.payload_bytes(digest).await
+    }
+
+    async fn payloads_bulk(
+        &self,
+        digests: &[CausalDigest],
+    ) -> anyhow::Result<HashMap<CausalDigest, Vec<u8>>> {
+        self.primary.payloads_bulk(digests).await
+    }
+}
+
+#[async_trait]
+impl<P, S> QueryableStorage for DualWriteBackend<P, S>
+where
+    P: WalStorageBackend + WalSource + QueryableStorage,
+    S: WalStorageBackend,
+{
+    async fn events_by_kind(&self, kind: &str) -> anyhow::Result<EventHeaderStream<'_>> {
+        self.primary.events_by_kind(kind).await
+    }
+
+    async fn events_in_range(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> anyhow::Result<EventHeaderStream<'_>> {
+        self.primary.events_in_range(from, to).await
+    }
+
+    async fn events_by_intent(&self, intent: &IntentId) -> anyhow::Result<EventHeaderStream<'_>> {
+        self.primary.events_by_intent(intent).await
+    }
+}
+
+#[async_trait]
+impl<P, S> ReplayableStorage for DualWriteBackend<P, S>
+where
+    P: WalStorageBackend + WalSource + ReplayableStorage,
+    S: WalStorageBackend,
+{
+    async fn subscribe_from(&self, from: ReplayFrom) -> anyhow::Result<EventHeaderStream<'_>> {
+        self.primary.subscribe_from(from).await
+    }
+}
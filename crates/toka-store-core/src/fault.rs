@@ -0,0 +1,96 @@
+#![forbid(unsafe_code)]
+
+//! Fault-injecting storage wrapper for resilience testing.
+//!
+//! [`FaultyStorage`] wraps any backend and, according to a
+//! [`FaultInjector`], delays operations and fails them with
+//! [`StorageError::BackendError`] before they reach the inner backend. A
+//! failed commit therefore never partially persists, matching how a real
+//! backend reports a failed write.
+//!
+//! Only available with the `fault-injection` feature.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use toka_types::FaultInjector;
+
+use crate::{
+    CausalDigest, EventHeader, EventHeaderStream, EventId, IntentId, QueryableStorage,
+    StorageBackend, StorageError,
+};
+
+/// Storage backend wrapper that injects latency and failures.
+pub struct FaultyStorage<S> {
+    inner: S,
+    injector: Arc<FaultInjector>,
+}
+
+impl<S> FaultyStorage<S> {
+    /// Wrap `inner`, injecting faults decided by `injector`.
+    pub fn new(inner: S, injector: Arc<FaultInjector>) -> Self {
+        Self { inner, injector }
+    }
+
+    /// The wrapped backend, for assertions that must bypass injection.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// The injector deciding faults.
+    pub fn injector(&self) -> &Arc<FaultInjector> {
+        &self.injector
+    }
+
+    async fn inject(&self, operation: &str) -> anyhow::Result<()> {
+        let (delay, fail) = self.injector.next_fault();
+        if let Some(delay) = delay {
+            tokio::time::sleep(delay).await;
+        }
+        if fail {
+            return Err(StorageError::BackendError(format!("injected fault in {}", operation)).into());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<S: StorageBackend> StorageBackend for FaultyStorage<S> {
+    async fn commit(&self, header: &EventHeader, payload: &[u8]) -> anyhow::Result<()> {
+        self.inject("commit").await?;
+        self.inner.commit(header, payload).await
+    }
+
+    async fn header(&self, id: &EventId) -> anyhow::Result<Option<EventHeader>> {
+        self.inject("header").await?;
+        self.inner.header(id).await
+    }
+
+    async fn payload_bytes(&self, digest: &CausalDigest) -> anyhow::Result<Option<Vec<u8>>> {
+        self.inject("payload_bytes").await?;
+        self.inner.payload_bytes(digest).await
+    }
+}
+
+#[async_trait]
+impl<S: QueryableStorage> QueryableStorage for FaultyStorage<S> {
+    async fn events_by_kind(&self, kind: &str) -> anyhow::Result<EventHeaderStream<'_>> {
+        self.inject("events_by_kind").await?;
+        self.inner.events_by_kind(kind).await
+    }
+
+    async fn events_in_range(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> anyhow::Result<EventHeaderStream<'_>> {
+        self.inject("events_in_range").await?;
+        self.inner.events_in_range(from, to).await
+    }
+
+    async fn events_by_intent(&self, intent: &IntentId) -> anyhow::Result<EventHeaderStream<'_>> {
+        self.inject("events_by_intent").await?;
+        self.inner.events_by_intent(intent).await
+    }
+}
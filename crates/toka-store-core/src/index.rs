@@ -0,0 +1,224 @@
+#![forbid(unsafe_code)]
+
+//! Secondary indexes on header fields and payload JSON paths.
+//!
+//! [`QueryableStorage`] only offers the fixed kind, intent and time-range
+//! lookups. Backends implementing [`IndexedStorage`] additionally let callers
+//! register indexes at runtime and look events up through them without a
+//! full scan:
+//!
+//! - [`IndexedStorage::create_index`] indexes a header field
+//!   ([`HEADER_INDEX_FIELDS`]).
+//! - [`IndexedStorage::create_index_json`] indexes a value inside the
+//!   payload, addressed by a JSON path such as `$.agent_id` or
+//!   `$.items[0].name`. Payloads are decoded according to their
+//!   [`PayloadFormat`](crate::PayloadFormat), so this works for MessagePack
+//!   and CBOR payloads as well as JSON.
+//!
+//! Index values are compared in their [`index_key`] form: strings as-is,
+//! other scalars and composite values as JSON text.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use crate::{decode_payload, EventHeader, EventHeaderStream, QueryableStorage, StorageError};
+
+/// Header fields that can be indexed.
+pub const HEADER_INDEX_FIELDS: &[&str] = &["kind", "intent", "timestamp"];
+
+/// What an index is built over.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", content = "expr", rename_all = "snake_case")]
+pub enum IndexTarget {
+    /// A header field from [`HEADER_INDEX_FIELDS`]
+    Header(String),
+    /// A JSON path into the decoded payload
+    JsonPath(String),
+}
+
+/// A registered secondary index.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct IndexDefinition {
+    /// Stable index name, used for lookups
+    pub name: String,
+    /// Indexed field
+    pub target: IndexTarget,
+}
+
+impl IndexDefinition {
+    /// Define an index over a header field.
+    pub fn header(field: &str) -> Result<Self, StorageError> {
+        if !HEADER_INDEX_FIELDS.contains(&field) {
+            return Err(StorageError::InvalidIndex(format!(
+                "unknown header field '{}' (expected one of {})",
+                field,
+                HEADER_INDEX_FIELDS.join(", ")
+            )));
+        }
+        Ok(Self {
+            name: field.to_string(),
+            target: IndexTarget::Header(field.to_string()),
+        })
+    }
+
+    /// Define an index over a payload JSON path.
+    ///
+    /// The name is derived from the path (`$.agent_id` → `json_agent_id`)
+    /// and only contains `[a-z0-9_]`, so backends can embed it in
+    /// identifiers.
+    pub fn json_path(path: &str) -> Result<Self, StorageError> {
+        parse_json_path(path)?;
+        let mut name = String::from("json");
+        for c in path.trim_start_matches('$').chars() {
+            if c.is_ascii_alphanumeric() {
+                name.push(c.to_ascii_lowercase());
+            } else if !name.ends_with('_') {
+                name.push('_');
+            }
+        }
+        let name = name.trim_end_matches('_').to_string();
+        Ok(Self {
+            name,
+            target: IndexTarget::JsonPath(path.to_string()),
+        })
+    }
+
+    /// Extract this index's key from a header and its payload.
+    ///
+    /// Returns `None` if the event has no value at the indexed path.
+    pub fn key_for(&self, header: &EventHeader, payload: &[u8]) -> Result<Option<String>, StorageError> {
+        match &self.target {
+            IndexTarget::Header(field) => Ok(Some(match field.as_str() {
+                "kind" => header.kind.clone(),
+                "intent" => header.intent.to_string(),
+                "timestamp" => header.timestamp.to_rfc3339(),
+                other => return Err(StorageError::InvalidIndex(format!("unknown header field '{}'", other))),
+            })),
+            IndexTarget::JsonPath(path) => {
+                let document: JsonValue = decode_payload(header, payload)?;
+                Ok(extract_json_path(&document, path)?.map(index_key))
+            }
+        }
+    }
+}
+
+/// Normalise a JSON value into the form stored in and looked up from indexes.
+pub fn index_key(value: &JsonValue) -> String {
+    match value {
+        JsonValue::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum PathSegment {
+    Field(String),
+    Index(usize),
+}
+
+/// Parse `$.a.b[0]`-style paths.
+fn parse_json_path(path: &str) -> Result<Vec<PathSegment>, StorageError> {
+    let invalid = || StorageError::InvalidIndex(format!("invalid JSON path '{}'", path));
+    let mut rest = path.strip_prefix('$').ok_or_else(invalid)?;
+    let mut segments = Vec::new();
+
+    while !rest.is_empty() {
+        if let Some(after_dot) = rest.strip_prefix('.') {
+            let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+            let field = &after_dot[..end];
+            if field.is_empty() {
+                return Err(invalid());
+            }
+            segments.push(PathSegment::Field(field.to_string()));
+            rest = &after_dot[end..];
+        } else if let Some(after_bracket) = rest.strip_prefix('[') {
+            let end = after_bracket.find(']').ok_or_else(invalid)?;
+            let index = after_bracket[..end].parse().map_err(|_| invalid())?;
+            segments.push(PathSegment::Index(index));
+            rest = &after_bracket[end + 1..];
+        } else {
+            return Err(invalid());
+        }
+    }
+
+    if segments.is_empty() {
+        return Err(invalid());
+    }
+    Ok(segments)
+}
+
+/// Look up `path` in `document`.
+pub fn extract_json_path<'a>(document: &'a JsonValue, path: &str) -> Result<Option<&'a JsonValue>, StorageError> {
+    let mut current = document;
+    for segment in parse_json_path(path)? {
+        let next = match segment {
+            PathSegment::Field(field) => current.get(field.as_str()),
+            PathSegment::Index(index) => current.get(index),
+        };
+        match next {
+            Some(value) => current = value,
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(current))
+}
+
+/// Storage backends supporting runtime-registered secondary indexes.
+#[async_trait]
+pub trait IndexedStorage: QueryableStorage {
+    /// Index a header field such as `kind`.
+    ///
+    /// Creating an index that already exists is a no-op.
+    async fn create_index(&self, field: &str) -> anyhow::Result<IndexDefinition>;
+
+    /// Index a payload value addressed by a JSON path such as `$.agent_id`.
+    ///
+    /// Existing events are indexed as part of the call. Creating an index
+    /// that already exists is a no-op.
+    async fn create_index_json(&self, path: &str) -> anyhow::Result<IndexDefinition>;
+
+    /// Remove an index by name. Returns whether it existed.
+    async fn drop_index(&self, name: &str) -> anyhow::Result<bool>;
+
+    /// All registered indexes.
+    async fn indexes(&self) -> anyhow::Result<Vec<IndexDefinition>>;
+
+    /// Events whose value for index `name` equals `value`, oldest first.
+    async fn events_by_index(&self, name: &str, value: &JsonValue) -> anyhow::Result<EventHeaderStream<'_>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_event_header;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_json_path_extraction() {
+        let document = json!({"agent": {"id": "a-1", "tags": ["x", "y"]}, "count": 3});
+
+        assert_eq!(extract_json_path(&document, "$.agent.id").unwrap(), Some(&json!("a-1")));
+        assert_eq!(extract_json_path(&document, "$.agent.tags[1]").unwrap(), Some(&json!("y")));
+        assert_eq!(extract_json_path(&document, "$.missing").unwrap(), None);
+        assert!(extract_json_path(&document, "agent.id").is_err());
+        assert!(extract_json_path(&document, "$.agent[x]").is_err());
+    }
+
+    #[test]
+    fn test_index_definitions() {
+        assert_eq!(IndexDefinition::json_path("$.agent.id").unwrap().name, "json_agent_id");
+        assert_eq!(IndexDefinition::json_path("$.items[0].Name").unwrap().name, "json_items_0_name");
+        assert!(IndexDefinition::header("digest").is_err());
+
+        let header = create_event_header(&[], Uuid::new_v4(), "agent.spawned".to_string(), &json!({"agent_id": 42})).unwrap();
+        let payload = rmp_serde::to_vec_named(&json!({"agent_id": 42})).unwrap();
+
+        let by_kind = IndexDefinition::header("kind").unwrap();
+        assert_eq!(by_kind.key_for(&header, &payload).unwrap().as_deref(), Some("agent.spawned"));
+
+        let by_agent = IndexDefinition::json_path("$.agent_id").unwrap();
+        assert_eq!(by_agent.key_for(&header, &payload).unwrap().as_deref(), Some("42"));
+    }
+}
@@ -9,13 +9,21 @@
 //!
 //! Storage drivers (sled, SQLite, in-memory, etc.) implement these traits in
 //! separate crates that depend on this core abstraction.
+//!
+//! Payloads travel as reference-counted [`Bytes`] through the WAL and the
+//! `*_bytes` methods of [`StorageBackend`], so large observations are not
+//! copied on their way from the writer to the store and back to readers.
 
+use std::collections::HashMap;
+use std::pin::Pin;
 use std::vec::Vec;
 use core::fmt::Debug;
 
 use async_trait::async_trait;
 use blake3;
+pub use bytes::Bytes;
 use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
 use rmp_serde;
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
@@ -40,6 +48,9 @@ pub type TransactionId = Uuid;
 /// Sequence number for WAL entries to ensure ordering.
 pub type SequenceNumber = u64;
 
+/// Unique identifier for a savepoint within a WAL transaction (UUID v4).
+pub type SavepointId = Uuid;
+
 //─────────────────────────────
 //  Event payload trait
 //─────────────────────────────
@@ -76,6 +87,40 @@ pub struct EventHeader {
     pub intent: IntentId,
     /// Application-defined kind, e.g. `ledger.mint` or `agent.spawn`
     pub kind: String,
+    /// Serialization format of the payload bytes (MessagePack for older events)
+    #[serde(default)]
+    pub format: PayloadFormat,
+    /// Logical timestamp for cross-node ordering, if the writer keeps a clock
+    #[serde(default)]
+    pub clock: Option<LogicalClock>,
+    /// Tenant namespace; `None` means [`DEFAULT_NAMESPACE`]
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// Large artifacts kept in a [`BlobStore`] that this event refers to
+    #[serde(default)]
+    pub blobs: Vec<BlobRef>,
+    /// Key identifying the logical event for [deduplication](dedup)
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+}
+
+impl EventHeader {
+    /// Namespace the event belongs to.
+    pub fn namespace_or_default(&self) -> &str {
+        self.namespace.as_deref().unwrap_or(DEFAULT_NAMESPACE)
+    }
+
+    /// Attach a reference to a stored blob.
+    pub fn with_blob(mut self, blob: BlobRef) -> Self {
+        self.blobs.push(blob);
+        self
+    }
+
+    /// Tag the event with an idempotency key so re-emissions are dropped.
+    pub fn with_idempotency_key(mut self, key: impl Into<String>) -> Self {
+        self.idempotency_key = Some(key.into());
+        self
+    }
 }
 
 //─────────────────────────────
@@ -118,7 +163,7 @@ pub enum WalOperation {
         /// Event header to be committed
         header: EventHeader,
         /// Serialized payload bytes
-        payload: Vec<u8>,
+        payload: Bytes,
     },
     /// Commit a transaction (make all changes durable)
     CommitTransaction {
@@ -130,6 +175,20 @@ pub enum WalOperation {
         /// Transaction identifier
         transaction_id: TransactionId,
     },
+    /// Mark a position within a transaction that it can be rolled back to
+    Savepoint {
+        /// Transaction identifier
+        transaction_id: TransactionId,
+        /// Savepoint identifier
+        savepoint_id: SavepointId,
+    },
+    /// Discard the operations logged after a savepoint
+    RollbackToSavepoint {
+        /// Transaction identifier
+        transaction_id: TransactionId,
+        /// Savepoint identifier
+        savepoint_id: SavepointId,
+    },
     /// Mark a WAL entry as checkpointed (can be safely removed)
     Checkpoint {
         /// Sequence number up to which entries are checkpointed
@@ -167,6 +226,9 @@ pub struct WalRecoveryResult {
     pub transactions_committed: usize,
     /// Number of entries that were checkpointed
     pub entries_checkpointed: usize,
+    /// Number of undecodable entries moved to quarantine
+    #[serde(default)]
+    pub entries_quarantined: usize,
     /// Any errors encountered during recovery
     pub recovery_errors: Vec<String>,
 }
@@ -206,6 +268,32 @@ pub trait WriteAheadLog: Send + Sync {
     /// and will not be applied to the main storage.
     async fn rollback_transaction(&self, transaction_id: TransactionId) -> anyhow::Result<()>;
 
+    /// Mark the current position of an active transaction as a savepoint.
+    ///
+    /// The transaction can later be rolled back to this position with
+    /// [`rollback_to_savepoint`](Self::rollback_to_savepoint) while keeping
+    /// everything logged before it. Backends without savepoint support
+    /// return an error.
+    async fn savepoint(&self, transaction_id: TransactionId) -> anyhow::Result<SavepointId> {
+        let _ = transaction_id;
+        Err(StorageError::WalOperationFailed("savepoints are not supported by this backend".to_string()).into())
+    }
+
+    /// Discard the operations logged in a transaction after `savepoint_id`.
+    ///
+    /// The transaction stays active and the savepoint remains valid, so it
+    /// can be rolled back to again. Savepoints created after it are released.
+    /// Discarded entries are marked as rolled back and are neither applied
+    /// on commit nor replayed by recovery.
+    async fn rollback_to_savepoint(
+        &self,
+        transaction_id: TransactionId,
+        savepoint_id: SavepointId,
+    ) -> anyhow::Result<()> {
+        let _ = (transaction_id, savepoint_id);
+        Err(StorageError::WalOperationFailed("savepoints are not supported by this backend".to_string()).into())
+    }
+
     /// Recover from a previous crash by replaying the WAL.
     ///
     /// This method examines all WAL entries and applies any committed
@@ -224,6 +312,15 @@ pub trait WriteAheadLog: Send + Sync {
     /// This is useful for determining checkpoint positions and
     /// monitoring WAL growth.
     async fn current_sequence(&self) -> anyhow::Result<SequenceNumber>;
+
+    /// Collect [`WalHealth`] metrics for monitoring.
+    ///
+    /// Backends may scan the whole log, so this is meant to be called
+    /// periodically rather than on hot paths. Backends that do not report
+    /// WAL health fail with [`StorageError::WalOperationFailed`].
+    async fn wal_health(&self) -> anyhow::Result<WalHealth> {
+        Err(StorageError::WalOperationFailed("WAL health is not reported by this backend".to_string()).into())
+    }
 }
 
 //─────────────────────────────
@@ -256,26 +353,60 @@ pub fn causal_hash(payload_bytes: &[u8], parent_digests: &[CausalDigest]) -> Cau
 /// This function handles serialization of the payload, computation of the
 /// causal hash, and generation of a unique event ID. It ensures all events
 /// have proper causal ordering and integrity verification.
+///
+/// Payloads are encoded as MessagePack; use [`create_event_header_with_format`]
+/// to pick a different [`PayloadFormat`].
 pub fn create_event_header<P: EventPayload>(
     parents: &[EventHeader],
     intent: IntentId,
     kind: String,
     payload: &P,
 ) -> Result<EventHeader, rmp_serde::encode::Error> {
+    let payload_bytes = rmp_serde::to_vec_named(payload)?;
+    Ok(build_event_header(parents, intent, kind, &payload_bytes, PayloadFormat::MessagePack))
+}
+
+/// Build an [`EventHeader`] and the encoded payload bytes using `format`.
+///
+/// The returned bytes are exactly what must be passed to
+/// [`StorageBackend::commit`], since the causal digest covers the encoded form.
+pub fn create_event_header_with_format<P: EventPayload>(
+    parents: &[EventHeader],
+    intent: IntentId,
+    kind: String,
+    payload: &P,
+    format: PayloadFormat,
+) -> Result<(EventHeader, Vec<u8>), StorageError> {
+    let payload_bytes = format.encode(payload)?;
+    let header = build_event_header(parents, intent, kind, &payload_bytes, format);
+    Ok((header, payload_bytes))
+}
+
+/// Assemble a header for already-encoded payload bytes.
+fn build_event_header(
+    parents: &[EventHeader],
+    intent: IntentId,
+    kind: String,
+    payload_bytes: &[u8],
+    format: PayloadFormat,
+) -> EventHeader {
     let parent_ids: SmallVec<[EventId; 4]> = parents.iter().map(|h| h.id).collect();
     let parent_digests: Vec<CausalDigest> = parents.iter().map(|h| h.digest).collect();
+    let digest = causal_hash(payload_bytes, &parent_digests);
 
-    let payload_bytes = rmp_serde::to_vec_named(payload)?;
-    let digest = causal_hash(&payload_bytes, &parent_digests);
-
-    Ok(EventHeader {
+    EventHeader {
         id: Uuid::new_v4(),
         parents: parent_ids,
         timestamp: Utc::now(),
         digest,
         intent,
         kind,
-    })
+        format,
+        clock: None,
+        namespace: None,
+        blobs: Vec::new(),
+        idempotency_key: None,
+    }
 }
 
 /// Deserialize a payload from raw bytes.
@@ -286,6 +417,14 @@ pub fn deserialize_payload<P: EventPayload>(bytes: &[u8]) -> Result<P, rmp_serde
     rmp_serde::from_slice(bytes)
 }
 
+/// Decode a payload using the format recorded in its header.
+///
+/// Prefer this over [`deserialize_payload`] when a store may contain events
+/// written in more than one [`PayloadFormat`].
+pub fn decode_payload<P: EventPayload>(header: &EventHeader, bytes: &[u8]) -> Result<P, StorageError> {
+    header.format.decode(bytes)
+}
+
 //─────────────────────────────
 //  Storage backend traits
 //─────────────────────────────
@@ -304,6 +443,15 @@ pub trait StorageBackend: Send + Sync {
     /// batch writes for performance but must maintain event ordering.
     async fn commit(&self, header: &EventHeader, payload: &[u8]) -> anyhow::Result<()>;
 
+    /// Persist an event whose payload the caller already holds as [`Bytes`].
+    ///
+    /// Backends that keep payloads in memory should override this to store
+    /// the buffer without copying it. The default borrows it for
+    /// [`commit`](Self::commit).
+    async fn commit_bytes(&self, header: &EventHeader, payload: Bytes) -> anyhow::Result<()> {
+        self.commit(header, &payload).await
+    }
+
     /// Fetch an [`EventHeader`] by identifier.
     ///
     /// Returns `None` if no event with the given ID exists. This operation
@@ -316,6 +464,144 @@ pub trait StorageBackend: Send + Sync {
     /// serves as both a lookup key and integrity verification. Callers must
     /// deserialize the bytes themselves using the appropriate type.
     async fn payload_bytes(&self, digest: &CausalDigest) -> anyhow::Result<Option<Vec<u8>>>;
+
+    /// Get the payload for a given digest as shared [`Bytes`].
+    ///
+    /// Backends that keep payloads in memory should override this to hand
+    /// out their buffer instead of a copy. The default wraps
+    /// [`payload_bytes`](Self::payload_bytes) without copying.
+    async fn payload(&self, digest: &CausalDigest) -> anyhow::Result<Option<Bytes>> {
+        Ok(self.payload_bytes(digest).await?.map(Bytes::from))
+    }
+
+    /// Get the raw payload bytes for many digests at once.
+    ///
+    /// Digests without a stored payload are absent from the result, and
+    /// duplicates are fetched once. The default implementation issues up to
+    /// [`PAYLOAD_PREFETCH_CONCURRENCY`] [`payload_bytes`](Self::payload_bytes)
+    /// lookups concurrently; backends that can fetch many rows in one round
+    /// trip should override it.
+    async fn payloads_bulk(
+        &self,
+        digests: &[CausalDigest],
+    ) -> anyhow::Result<HashMap<CausalDigest, Vec<u8>>> {
+        let mut unique = digests.to_vec();
+        unique.sort_unstable();
+        unique.dedup();
+
+        let mut lookups = futures::stream::iter(unique)
+            .map(|digest| async move { (digest, self.payload_bytes(&digest).await) })
+            .buffer_unordered(PAYLOAD_PREFETCH_CONCURRENCY);
+
+        let mut payloads = HashMap::new();
+        while let Some((digest, payload)) = lookups.next().await {
+            if let Some(payload) = payload? {
+                payloads.insert(digest, payload);
+            }
+        }
+        Ok(payloads)
+    }
+}
+
+/// Number of concurrent lookups issued by the default
+/// [`StorageBackend::payloads_bulk`].
+pub const PAYLOAD_PREFETCH_CONCURRENCY: usize = 16;
+
+/// Boxed stream of event headers produced by [`QueryableStorage`] scans.
+///
+/// Items are yielded in ascending timestamp order. Individual items may fail
+/// (e.g. a corrupt header row) without terminating the whole stream.
+pub type EventHeaderStream<'a> = Pin<Box<dyn Stream<Item = anyhow::Result<EventHeader>> + Send + 'a>>;
+
+/// Query and iteration API over committed events.
+///
+/// Backends implement this trait to expose scans over their existing header
+/// indexes so consumers don't have to maintain their own. All scans return
+/// headers only; payloads can be fetched lazily via
+/// [`StorageBackend::payload_bytes`].
+#[async_trait]
+pub trait QueryableStorage: StorageBackend {
+    /// Stream all events with the given `kind`, oldest first.
+    async fn events_by_kind(&self, kind: &str) -> anyhow::Result<EventHeaderStream<'_>>;
+
+    /// Stream all events committed in the half-open range `[from, to)`,
+    /// oldest first.
+    async fn events_in_range(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> anyhow::Result<EventHeaderStream<'_>>;
+
+    /// Stream all events belonging to the given intent, oldest first.
+    async fn events_by_intent(&self, intent: &IntentId) -> anyhow::Result<EventHeaderStream<'_>>;
+}
+
+/// Starting point for [`ReplayableStorage::subscribe_from`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReplayFrom {
+    /// Replay events whose timestamp is at or after the given instant
+    Timestamp(DateTime<Utc>),
+    /// Skip the first `n` stored events (in commit order) and replay the rest.
+    ///
+    /// A consumer that counts the events it has processed can resume from
+    /// that count after a restart.
+    Sequence(u64),
+}
+
+/// Subscriptions that replay history before following the live stream.
+///
+/// Plain `subscribe()` only delivers events committed after the call, so a
+/// restarted consumer loses whatever happened while it was down. Backends
+/// implementing this trait first stream stored events from the requested
+/// starting point and then switch to live events without gaps or duplicates.
+#[async_trait]
+pub trait ReplayableStorage: StorageBackend {
+    /// Replay stored events from `from`, then continue with live events.
+    ///
+    /// If the consumer falls so far behind that the live buffer overflows,
+    /// the stream yields an error; resubscribe from the last processed
+    /// position to recover.
+    async fn subscribe_from(&self, from: ReplayFrom) -> anyhow::Result<EventHeaderStream<'_>>;
+}
+
+/// Chain a history stream with a live stream, dropping live duplicates.
+///
+/// The live stream must be subscribed *before* the history query runs so no
+/// commit falls between the two. Events delivered by both are yielded once:
+/// live events already replayed are skipped until the first genuinely new
+/// live event arrives, after which the live stream passes through untouched.
+pub fn replay_then_live<'a, L>(history: EventHeaderStream<'a>, live: L) -> EventHeaderStream<'a>
+where
+    L: Stream<Item = anyhow::Result<EventHeader>> + Send + 'a,
+{
+    use futures::{future, StreamExt, TryStreamExt};
+    use std::collections::HashSet;
+    use std::sync::{Arc, Mutex};
+
+    let replayed: Arc<Mutex<Option<HashSet<EventId>>>> = Arc::new(Mutex::new(Some(HashSet::new())));
+
+    let seen = replayed.clone();
+    let history = history.inspect_ok(move |header| {
+        if let Some(ids) = seen.lock().expect("replay set poisoned").as_mut() {
+            ids.insert(header.id);
+        }
+    });
+
+    let live = live.try_filter(move |header| {
+        let mut guard = replayed.lock().expect("replay set poisoned");
+        let keep = match guard.as_mut().map(|ids| ids.remove(&header.id)) {
+            Some(true) => false,
+            Some(false) => {
+                // Caught up: nothing after this point can have been replayed
+                *guard = None;
+                true
+            }
+            None => true,
+        };
+        future::ready(keep)
+    });
+
+    Box::pin(history.chain(live))
 }
 
 /// Enhanced storage backend with Write-Ahead Logging support.
@@ -334,13 +620,27 @@ pub trait WalStorageBackend: StorageBackend + WriteAheadLog {
         transaction_id: TransactionId,
         header: &EventHeader,
         payload: &[u8],
+    ) -> anyhow::Result<()> {
+        self.commit_bytes_with_wal(transaction_id, header, Bytes::copy_from_slice(payload))
+            .await
+    }
+
+    /// Commit an event within a WAL transaction without copying its payload.
+    ///
+    /// Same as [`commit_with_wal`](Self::commit_with_wal), but the logged
+    /// operation shares `payload` with the caller.
+    async fn commit_bytes_with_wal(
+        &self,
+        transaction_id: TransactionId,
+        header: &EventHeader,
+        payload: Bytes,
     ) -> anyhow::Result<()> {
         // Log the operation - it will be applied when transaction is committed
         self.write_entry(
             transaction_id,
             WalOperation::CommitEvent {
                 header: header.clone(),
-                payload: payload.to_vec(),
+                payload,
             },
         )
         .await
@@ -389,11 +689,365 @@ pub enum StorageError {
     /// Transaction already rolled back
     #[error("transaction already rolled back: {0}")]
     TransactionAlreadyRolledBack(TransactionId),
+    /// Savepoint does not exist in the transaction or was released
+    #[error("savepoint {savepoint_id} not found in transaction {transaction_id}")]
+    SavepointNotFound {
+        /// Transaction the savepoint was looked up in
+        transaction_id: TransactionId,
+        /// The missing savepoint
+        savepoint_id: SavepointId,
+    },
     /// Recovery failed
     #[error("WAL recovery failed: {0}")]
     RecoveryFailed(String),
+    /// Event kind is not well-formed
+    #[error("invalid event kind: {0}")]
+    InvalidEventKind(String),
+    /// Event kind is bound to a different payload type
+    #[error("event kind {kind} is bound to {expected}, not {actual}")]
+    KindMismatch {
+        /// The event kind
+        kind: String,
+        /// Type (or kind) the event is bound to
+        expected: String,
+        /// Type that was requested
+        actual: String,
+    },
+    /// Snapshot archive is malformed or unsupported
+    #[error("invalid snapshot: {0}")]
+    InvalidSnapshot(String),
+    /// Index definition or lookup is not valid
+    #[error("invalid index: {0}")]
+    InvalidIndex(String),
+    /// Caller's capabilities do not allow the requested read
+    #[error("access denied: {0}")]
+    AccessDenied(String),
+    /// Write attempted on a read-only backend
+    #[error("storage is read-only: {0} rejected")]
+    ReadOnly(String),
+    /// A namespace name was rejected
+    #[error("invalid namespace: {0}")]
+    InvalidNamespace(String),
+    /// A conditional commit's precondition did not hold
+    #[error("commit precondition failed: {0}")]
+    Conflict(#[from] conditional::CommitConflict),
+    /// Another writer held the database lock while a WAL sequence number
+    /// was being allocated
+    #[error("WAL sequence allocation contended: {0}")]
+    SequenceContention(String),
 }
 
+//─────────────────────────────
+//  Payload serialization formats
+//─────────────────────────────
+
+/// Pluggable serialization formats for event payloads.
+pub mod codec;
+
+pub use codec::{FormatPolicy, PayloadFormat};
+
+//─────────────────────────────
+//  Payload compression
+//─────────────────────────────
+
+/// Transparent, per-payload compression for storage backends.
+pub mod compression;
+
+pub use compression::{Compression, CompressionPolicy};
+
+//─────────────────────────────
+//  Transactional outbox
+//─────────────────────────────
+
+/// Transactional outbox relaying staged messages to the event bus.
+pub mod outbox;
+
+pub use outbox::{OutboxAck, OutboxExt, OutboxPublisher, OutboxRelay};
+
+//─────────────────────────────
+//  Durable work queue
+//─────────────────────────────
+
+/// Store-backed work queue with leases, retries and dead-lettering.
+pub mod queue;
+
+pub use queue::{DurableQueue, QueueConfig, QueueStats, QueuedTask, TaskId, TaskStatus};
+
+//─────────────────────────────
+//  Integrity verification
+//─────────────────────────────
+
+/// Causal-chain verification and integrity auditing.
+pub mod integrity;
+
+pub use integrity::{AuditReport, IntegrityExt, IntegrityIssue, IntegrityScanner};
+
+//─────────────────────────────
+//  Event kind registry
+//─────────────────────────────
+
+/// Event kind registry and typed commit/fetch helpers.
+pub mod registry;
+
+pub use registry::{EventKindRegistry, KindRegistration, TypedEvent, TypedStorageExt};
+
+//─────────────────────────────
+//  Intent compaction
+//─────────────────────────────
+
+/// Folding of finished intents into summary events.
+pub mod compaction;
+
+pub use compaction::{
+    CompactableStorage, CompactionPolicy, CompactionSummary, KindCompaction, COMPACTION_SUMMARY_KIND,
+};
+
+//─────────────────────────────
+//  Snapshots
+//─────────────────────────────
+
+/// Portable snapshot archives for export and import.
+pub mod snapshot;
+
+pub use snapshot::{
+    SnapshotCheckpoint, SnapshotCheckpointExt, SnapshotManifest, SnapshotReader, SnapshotStorage,
+    SnapshotWriter,
+};
+
+//─────────────────────────────
+//  Anonymized exports
+//─────────────────────────────
+
+/// Snapshot exports with pseudonymized IDs and redacted payload fields.
+pub mod anonymize;
+
+pub use anonymize::{
+    anonymize_snapshot, AnonymizationPolicy, AnonymizationReport, AnonymizedExportExt, FieldAction,
+    FieldRule,
+};
+
+//─────────────────────────────
+//  Secondary indexes
+//─────────────────────────────
+
+/// Runtime-registered indexes on header fields and payload JSON paths.
+pub mod index;
+
+pub use index::{IndexDefinition, IndexTarget, IndexedStorage};
+
+//─────────────────────────────
+//  Cross-backend migration
+//─────────────────────────────
+
+/// Resumable, verified copying of events between backends.
+pub mod migration;
+
+pub use migration::{MigrationProgress, MigrationReport, StorageMigrator};
+
+//─────────────────────────────
+//  Retention policies
+//─────────────────────────────
+
+/// Age, count and kind based pruning policies.
+pub mod retention;
+
+pub use retention::{plan_retention, RetentionPolicy, RetentionReport, RetentionRule, RetentionStorage};
+
+//─────────────────────────────
+//  Capability-scoped reads
+//─────────────────────────────
+
+/// Read-only store access limited to the intents granted by agent claims.
+pub mod scoped;
+
+pub use scoped::{intent_permission, ScopedReader, STORE_READ_PERMISSION};
+
+//─────────────────────────────
+//  Read-only access
+//─────────────────────────────
+
+/// Wrapper rejecting commits and WAL writes.
+pub mod readonly;
+
+pub use readonly::ReadOnlyBackend;
+
+//─────────────────────────────
+//  Kind-based access control
+//─────────────────────────────
+
+/// Wrapper checking capability claims against per-kind read/write ACLs.
+pub mod authorized;
+
+pub use authorized::{AuthorizedBackend, KindAccess, KindAcl, KindAclRule};
+
+//─────────────────────────────
+//  Storage statistics
+//─────────────────────────────
+
+/// Backend-independent counts, sizes and WAL depth.
+pub mod stats;
+
+pub use stats::{StorageStatistics, StorageStats};
+
+//─────────────────────────────
+//  Logical clocks
+//─────────────────────────────
+
+/// Lamport and vector clocks stamped into headers at commit time.
+pub mod clock;
+
+pub use clock::{
+    ClockOrdering, ClockSource, ClockedStorageExt, LamportClock, LogicalClock, VectorClock,
+    VectorClockSource,
+};
+
+//─────────────────────────────
+//  Namespaces
+//─────────────────────────────
+
+/// Per-tenant namespaces with counts, pruning and filtered subscriptions.
+pub mod namespace;
+
+pub use namespace::{
+    filter_namespace, validate_namespace, NamespaceStats, NamespacedStorage, DEFAULT_NAMESPACE,
+};
+
+//─────────────────────────────
+//  Conditional commits
+//─────────────────────────────
+
+/// Commits guarded by preconditions for optimistic concurrency.
+pub mod conditional;
+
+pub use conditional::{as_conflict, intent_head, CommitConflict, ConditionalStorage, Precondition};
+
+//─────────────────────────────
+//  Idempotent commits
+//─────────────────────────────
+
+/// Deduplication of re-emitted events by idempotency key.
+pub mod dedup;
+
+pub use dedup::{CommitOutcome, DeduplicatingStorage, IdempotencyIndex, DEFAULT_DEDUP_WINDOW};
+
+//─────────────────────────────
+//  Typed subscriptions
+//─────────────────────────────
+
+/// Subscriptions that join headers with decoded payloads.
+pub mod subscription;
+
+pub use subscription::{decode_events, DecodedEvent, TypedEventStream, TypedStreamError, TypedSubscriptionExt};
+
+//─────────────────────────────
+//  WAL compaction
+//─────────────────────────────
+
+/// Pruning of checkpointed and rolled back WAL entries.
+pub mod wal_compaction;
+
+pub use wal_compaction::{compactable_transactions, WalCompaction, WalCompactionPolicy, WalCompactionReport};
+
+//─────────────────────────────
+//  WAL entry format
+//─────────────────────────────
+
+/// Versioned encoding of WAL operations and quarantine of undecodable entries.
+pub mod wal_format;
+
+pub use wal_format::{
+    decode_wal_operation, encode_wal_operation, QuarantinedWalEntry, WalDecodeError,
+    WalQuarantine, WAL_FORMAT_VERSION,
+};
+
+//─────────────────────────────
+//  WAL health
+//─────────────────────────────
+
+/// WAL backlog metrics and their periodic publication on the event bus.
+pub mod wal_health;
+
+pub use wal_health::{WalHealth, WalHealthThresholds, WalStateCounts, DEFAULT_WAL_HEALTH_INTERVAL};
+
+#[cfg(feature = "bus")]
+pub use wal_health::WalHealthReporter;
+
+//─────────────────────────────
+//  WAL replication
+//─────────────────────────────
+
+/// Tailing a leader's WAL into a follower backend for warm standbys.
+pub mod replication;
+
+pub use replication::{
+    ConsistencyReport, ReplicationBatch, ReplicationLag, ResumeToken, WalReplicator, WalSource,
+    DEFAULT_REPLICATION_BATCH,
+};
+
+//─────────────────────────────
+//  Dual writes
+//─────────────────────────────
+
+/// Two-phase commit across a primary and a secondary backend.
+pub mod dual_write;
+
+pub use dual_write::{
+    DualWriteBackend, DualWriteOutcome, DualWriteRecovery, PreparedWrite, ReconcileReport,
+    DEFAULT_RECONCILE_BATCH,
+};
+
+//─────────────────────────────
+//  Transaction timeouts
+//─────────────────────────────
+
+/// Rolling back WAL transactions that stay open past their time to live.
+pub mod wal_timeout;
+
+pub use wal_timeout::{is_stale, TransactionTimedOut, TransactionTimeouts, DEFAULT_REAPER_INTERVAL};
+
+//─────────────────────────────
+//  Recovery hooks
+//─────────────────────────────
+
+/// Observers notified, and able to veto replay, during WAL recovery.
+pub mod recovery;
+
+pub use recovery::{RecoveryHooks, RecoveryObserver, ReplayDecision};
+
+//─────────────────────────────
+//  Blob storage
+//─────────────────────────────
+
+/// Content-addressed storage for large artifacts referenced by events.
+pub mod blob;
+
+pub use blob::{
+    digest_hex, read_chunk, stream_chunks, BlobDigest, BlobReader, BlobRef, BlobStore, FsBlobStore,
+    BLOB_CHUNK_SIZE,
+};
+
+//─────────────────────────────
+//  Bus tap
+//─────────────────────────────
+
+/// Persisting selected kernel bus events into a store.
+#[cfg(feature = "bus")]
+pub mod tap;
+
+#[cfg(feature = "bus")]
+pub use tap::{BusTap, TapConfig, TapDecision, TapRule, TapStats};
+
+//─────────────────────────────
+//  Fault injection
+//─────────────────────────────
+
+/// Fault-injecting backend wrapper for resilience testing.
+#[cfg(feature = "fault-injection")]
+pub mod fault;
+
+#[cfg(feature = "fault-injection")]
+pub use fault::FaultyStorage;
+
 //─────────────────────────────
 //  Semantic analysis support
 //─────────────────────────────
@@ -408,12 +1062,40 @@ pub mod semantic;
 /// Convenient prelude for importing the most common types.
 pub mod prelude {
     pub use super::{
-        CausalDigest, EventHeader, EventId, EventPayload, IntentId,
-        StorageBackend, StorageError,
+        Bytes, CausalDigest, EventHeader, EventId, EventPayload, IntentId,
+        StorageBackend, StorageError, QueryableStorage, EventHeaderStream,
+        ReplayableStorage, ReplayFrom, replay_then_live,
         causal_hash, create_event_header, deserialize_payload,
+        create_event_header_with_format, decode_payload, PayloadFormat, FormatPolicy,
+        Compression, CompressionPolicy,
+        OutboxExt, OutboxPublisher, OutboxRelay,
+        DurableQueue, QueueConfig, QueuedTask, TaskStatus,
+        AuditReport, IntegrityExt, IntegrityIssue, IntegrityScanner,
+        EventKindRegistry, TypedEvent, TypedStorageExt,
+        CompactableStorage, CompactionPolicy, CompactionSummary, KindCompaction,
+        RetentionPolicy, RetentionReport, RetentionRule, RetentionStorage,
+        SnapshotCheckpoint, SnapshotCheckpointExt, SnapshotManifest, SnapshotStorage,
+        AnonymizationPolicy, AnonymizedExportExt,
+        MigrationProgress, MigrationReport, StorageMigrator,
+        IndexDefinition, IndexTarget, IndexedStorage,
+        StorageStatistics, StorageStats, ScopedReader, ReadOnlyBackend,
+        AuthorizedBackend, KindAccess, KindAcl,
+        ClockOrdering, ClockedStorageExt, ClockSource, LamportClock, LogicalClock, VectorClock,
+        VectorClockSource, NamespaceStats, NamespacedStorage, DEFAULT_NAMESPACE,
+        CommitConflict, ConditionalStorage, Precondition,
+        CommitOutcome, DeduplicatingStorage,
+        DecodedEvent, TypedStreamError, TypedSubscriptionExt,
+        BlobDigest, BlobRef, BlobStore, FsBlobStore,
         // WAL types
-        TransactionId, SequenceNumber, WalEntry, WalOperation, WalEntryState,
+        TransactionId, SequenceNumber, SavepointId, WalEntry, WalOperation, WalEntryState,
         WalRecoveryResult, WriteAheadLog, WalStorageBackend,
+        WalCompaction, WalCompactionPolicy, WalCompactionReport,
+        QuarantinedWalEntry, WalQuarantine,
+        WalHealth, WalHealthThresholds, WalStateCounts,
+        TransactionTimedOut, TransactionTimeouts,
+        RecoveryHooks, RecoveryObserver, ReplayDecision,
+        ResumeToken, WalReplicator, WalSource,
+        DualWriteBackend, DualWriteOutcome, ReconcileReport,
         // Semantic analysis types
         semantic::{
             PluginId, SemanticResult, SemanticError, PluginMetadata, PluginConfig,
@@ -490,6 +1172,11 @@ mod tests {
             digest: [0u8; 32],
             intent: Uuid::new_v4(),
             kind: "test.event".to_string(),
+            format: PayloadFormat::default(),
+            clock: None,
+            namespace: None,
+            blobs: Vec::new(),
+            idempotency_key: None,
         };
 
         let serialized = serde_json::to_string(&header).unwrap();
@@ -497,4 +1184,100 @@ mod tests {
 
         assert_eq!(header, deserialized);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_mixed_format_headers() {
+        let event = TestEvent {
+            message: "mixed".to_string(),
+            value: 3,
+        };
+
+        let (json_header, json_bytes) = create_event_header_with_format(
+            &[],
+            Uuid::nil(),
+            "test.json".to_string(),
+            &event,
+            PayloadFormat::Json,
+        ).unwrap();
+        let (cbor_header, cbor_bytes) = create_event_header_with_format(
+            &[],
+            Uuid::nil(),
+            "test.cbor".to_string(),
+            &event,
+            PayloadFormat::Cbor,
+        ).unwrap();
+
+        assert_ne!(json_header.digest, cbor_header.digest);
+        assert_eq!(decode_payload::<TestEvent>(&json_header, &json_bytes).unwrap(), event);
+        assert_eq!(decode_payload::<TestEvent>(&cbor_header, &cbor_bytes).unwrap(), event);
+    }
+
+    #[test]
+    fn test_header_format_roundtrip() {
+        let event = TestEvent {
+            message: "format".to_string(),
+            value: 5,
+        };
+
+        for format in [PayloadFormat::MessagePack, PayloadFormat::Cbor, PayloadFormat::Json] {
+            let (header, _) =
+                create_event_header_with_format(&[], Uuid::nil(), "test.format".to_string(), &event, format).unwrap();
+
+            let json: EventHeader = serde_json::from_str(&serde_json::to_string(&header).unwrap()).unwrap();
+            assert_eq!(json.format, format);
+            let msgpack: EventHeader = rmp_serde::from_slice(&rmp_serde::to_vec_named(&header).unwrap()).unwrap();
+            assert_eq!(msgpack.format, format);
+        }
+    }
+
+    #[test]
+    fn test_legacy_header_defaults_to_messagepack() {
+        #[derive(Serialize)]
+        struct LegacyHeader {
+            id: EventId,
+            parents: SmallVec<[EventId; 4]>,
+            timestamp: DateTime<Utc>,
+            digest: CausalDigest,
+            intent: IntentId,
+            kind: String,
+        }
+
+        let legacy = LegacyHeader {
+            id: Uuid::new_v4(),
+            parents: SmallVec::new(),
+            timestamp: Utc::now(),
+            digest: [0u8; 32],
+            intent: Uuid::nil(),
+            kind: "test.legacy".to_string(),
+        };
+
+        let bytes = rmp_serde::to_vec_named(&legacy).unwrap();
+        let header: EventHeader = rmp_serde::from_slice(&bytes).unwrap();
+        assert_eq!(header.format, PayloadFormat::MessagePack);
+    }
+
+    #[test]
+    fn test_replay_then_live_skips_duplicates() {
+        use futures::{executor::block_on, stream, TryStreamExt};
+
+        let event = TestEvent { message: "replay".to_string(), value: 1 };
+        let headers: Vec<EventHeader> = (0..4)
+            .map(|_| create_event_header(&[], Uuid::new_v4(), "test.replay".to_string(), &event).unwrap())
+            .collect();
+
+        // Event 1 was committed while history was being read, so it shows up in both
+        let history = Box::pin(stream::iter(vec![Ok(headers[0].clone()), Ok(headers[1].clone())]));
+        let live = stream::iter(vec![
+            Ok(headers[1].clone()),
+            Ok(headers[2].clone()),
+            Ok(headers[3].clone()),
+        ]);
+
+        let ids: Vec<EventId> = block_on(replay_then_live(history, live).try_collect::<Vec<_>>())
+            .unwrap()
+            .into_iter()
+            .map(|h| h.id)
+            .collect();
+        assert_eq!(ids, headers.iter().map(|h| h.id).collect::<Vec<_>>());
+    }
+}
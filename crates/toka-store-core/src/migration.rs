@@ -0,0 +1,235 @@
+#![forbid(unsafe_code)]
+
+//! Copying event history between storage backends.
+//!
+//! [`StorageMigrator`] streams every event from a source backend into a
+//! target backend (for example SQLite into a new driver). Events are copied
+//! parents-first so the target never holds an event whose causal parents are
+//! missing, and payloads are committed under their original digests so the
+//! target's own deduplication applies unchanged.
+//!
+//! Migration is resumable: events already present in the target are skipped,
+//! so an interrupted run can simply be started again. After copying, every
+//! event can be re-verified in the target with the checks from
+//! [`IntegrityExt`].
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, TimeZone, Utc};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{EventHeader, EventId, IntegrityExt, IntegrityIssue, QueryableStorage, StorageBackend, StorageError};
+
+/// Default number of events between progress callbacks.
+pub const DEFAULT_PROGRESS_INTERVAL: usize = 1000;
+
+/// Progress of a running migration.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationProgress {
+    /// Events found in the source
+    pub total: usize,
+    /// Events copied so far
+    pub copied: usize,
+    /// Events skipped because the target already had them
+    pub skipped: usize,
+}
+
+/// Outcome of a migration.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationReport {
+    /// Final progress counters
+    pub progress: MigrationProgress,
+    /// Events re-verified in the target
+    pub events_verified: usize,
+    /// Problems found while verifying the target
+    pub issues: Vec<IntegrityIssue>,
+}
+
+impl MigrationReport {
+    /// Whether verification found no problems.
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+type ProgressCallback<'a> = Box<dyn Fn(&MigrationProgress) + Send + Sync + 'a>;
+
+/// Copies all events from one backend into another.
+pub struct StorageMigrator<'a, S: ?Sized, T: ?Sized> {
+    source: &'a S,
+    target: &'a T,
+    verify: bool,
+    progress_interval: usize,
+    on_progress: Option<ProgressCallback<'a>>,
+}
+
+impl<'a, S, T> StorageMigrator<'a, S, T>
+where
+    S: QueryableStorage + ?Sized,
+    T: StorageBackend + ?Sized,
+{
+    /// Create a migrator copying from `source` into `target`, with verification enabled.
+    pub fn new(source: &'a S, target: &'a T) -> Self {
+        Self {
+            source,
+            target,
+            verify: true,
+            progress_interval: DEFAULT_PROGRESS_INTERVAL,
+            on_progress: None,
+        }
+    }
+
+    /// Enable or disable digest verification of the target after copying.
+    pub fn with_verification(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Call `callback` every `interval` events and once at the end.
+    pub fn on_progress(
+        mut self,
+        interval: usize,
+        callback: impl Fn(&MigrationProgress) + Send + Sync + 'a,
+    ) -> Self {
+        self.progress_interval = interval.max(1);
+        self.on_progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Copy every event that the target does not have yet.
+    pub async fn run(&self) -> anyhow::Result<MigrationReport> {
+        let headers: Vec<EventHeader> = self
+            .source
+            .events_in_range(migration_start(), migration_end())
+            .await?
+            .try_collect()
+            .await?;
+        let ordered = causal_order(headers);
+
+        let mut report = MigrationReport {
+            progress: MigrationProgress {
+                total: ordered.len(),
+                ..MigrationProgress::default()
+            },
+            ..MigrationReport::default()
+        };
+
+        for (index, header) in ordered.iter().enumerate() {
+            if self.target.header(&header.id).await?.is_some() {
+                report.progress.skipped += 1;
+            } else {
+                let payload = self
+                    .source
+                    .payload_bytes(&header.digest)
+                    .await?
+                    .ok_or_else(|| StorageError::EventNotFound(format!("payload for {}", header.id)))?;
+                self.target.commit(header, &payload).await?;
+                report.progress.copied += 1;
+            }
+
+            if (index + 1) % self.progress_interval == 0 {
+                self.report_progress(&report.progress);
+            }
+        }
+        self.report_progress(&report.progress);
+
+        if self.verify {
+            for header in &ordered {
+                let audit = self.target.verify_event(&header.id).await?;
+                report.events_verified += audit.events_checked;
+                report.issues.extend(audit.issues);
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn report_progress(&self, progress: &MigrationProgress) {
+        if let Some(callback) = &self.on_progress {
+            callback(progress);
+        }
+    }
+}
+
+/// Lower bound of the migrated time range.
+fn migration_start() -> DateTime<Utc> {
+    Utc.timestamp_opt(0, 0).unwrap()
+}
+
+/// Upper bound of the migrated time range.
+///
+/// Kept within four-digit years so backends that compare RFC 3339 strings
+/// order it correctly.
+fn migration_end() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(9999, 12, 31, 23, 59, 59).unwrap()
+}
+
+/// Order headers so every event comes after the parents present in the set.
+///
+/// Ties are broken by timestamp. Parents outside the set are ignored.
+fn causal_order(mut headers: Vec<EventHeader>) -> Vec<EventHeader> {
+    headers.sort_by_key(|header| header.timestamp);
+    let index: HashMap<EventId, usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(position, header)| (header.id, position))
+        .collect();
+
+    let mut emitted = HashSet::with_capacity(headers.len());
+    let mut visiting = HashSet::new();
+    let mut order = Vec::with_capacity(headers.len());
+    for start in 0..headers.len() {
+        // Depth-first walk emitting parents before children
+        let mut stack = vec![(start, false)];
+        while let Some((position, expanded)) = stack.pop() {
+            let header = &headers[position];
+            if expanded {
+                emitted.insert(header.id);
+                order.push(position);
+                continue;
+            }
+            // Corrupt parent links could form a cycle; never revisit a node
+            if emitted.contains(&header.id) || !visiting.insert(header.id) {
+                continue;
+            }
+            stack.push((position, true));
+            for parent in header.parents.iter().rev() {
+                if let Some(&parent_position) = index.get(parent) {
+                    if !emitted.contains(parent) {
+                        stack.push((parent_position, false));
+                    }
+                }
+            }
+        }
+    }
+
+    let mut slots: Vec<Option<EventHeader>> = headers.into_iter().map(Some).collect();
+    order
+        .into_iter()
+        .filter_map(|position| slots[position].take())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_event_header;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_causal_order_puts_parents_first() {
+        let intent = Uuid::new_v4();
+        let root = create_event_header(&[], intent, "test".to_string(), &0u32).unwrap();
+        let mut child = create_event_header(&[root.clone()], intent, "test".to_string(), &1u32).unwrap();
+        let mut grandchild = create_event_header(&[child.clone()], intent, "test".to_string(), &2u32).unwrap();
+
+        // Skewed clocks: descendants stamped before their ancestors
+        child.timestamp = root.timestamp - chrono::Duration::seconds(10);
+        grandchild.timestamp = root.timestamp - chrono::Duration::seconds(20);
+
+        let ordered = causal_order(vec![grandchild.clone(), root.clone(), child.clone()]);
+        let ids: Vec<EventId> = ordered.iter().map(|header| header.id).collect();
+        assert_eq!(ids, vec![root.id, child.id, grandchild.id]);
+    }
+}
@@ -0,0 +1,184 @@
+#![forbid(unsafe_code)]
+
+//! Multi-tenant namespaces within a single store.
+//!
+//! Several agent workspaces can share one database by committing their
+//! events into separate namespaces. The namespace is recorded in
+//! [`EventHeader::namespace`]; events committed through the plain
+//! [`StorageBackend::commit`] without one belong to [`DEFAULT_NAMESPACE`].
+//!
+//! [`NamespacedStorage`] adds namespace-aware commits and lookups together
+//! with per-namespace counts, pruning and live subscriptions. Payloads stay
+//! deduplicated across namespaces, so pruning one namespace only deletes
+//! payloads that no other event still references.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{EventHeader, EventHeaderStream, EventId, StorageBackend, StorageError};
+
+/// Namespace of events committed without one.
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+/// Maximum length of a namespace name in bytes.
+pub const MAX_NAMESPACE_LEN: usize = 64;
+
+/// Check that `namespace` is a valid name.
+///
+/// Names are 1 to [`MAX_NAMESPACE_LEN`] ASCII letters, digits, `-`, `_` or `.`.
+pub fn validate_namespace(namespace: &str) -> Result<(), StorageError> {
+    if namespace.is_empty() || namespace.len() > MAX_NAMESPACE_LEN {
+        return Err(StorageError::InvalidNamespace(format!(
+            "namespace must be 1-{} characters",
+            MAX_NAMESPACE_LEN
+        )));
+    }
+    if let Some(c) = namespace
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+    {
+        return Err(StorageError::InvalidNamespace(format!(
+            "invalid character {:?} in namespace '{}'",
+            c, namespace
+        )));
+    }
+    Ok(())
+}
+
+/// Keep only the events of `namespace` in `stream`.
+///
+/// Errors are passed through, since they carry no event data.
+pub fn filter_namespace<'a>(stream: EventHeaderStream<'a>, namespace: &str) -> EventHeaderStream<'a> {
+    let namespace = namespace.to_string();
+    Box::pin(stream.filter(move |item| {
+        let keep = match item {
+            Ok(header) => header.namespace_or_default() == namespace,
+            Err(_) => true,
+        };
+        futures::future::ready(keep)
+    }))
+}
+
+/// Event counts and time bounds of one namespace.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamespaceStats {
+    /// Namespace name
+    pub namespace: String,
+    /// Number of events in the namespace
+    pub event_count: u64,
+    /// Timestamp of the oldest event
+    pub oldest_event: Option<DateTime<Utc>>,
+    /// Timestamp of the newest event
+    pub newest_event: Option<DateTime<Utc>>,
+}
+
+impl NamespaceStats {
+    /// Statistics of a namespace without events.
+    pub fn empty(namespace: &str) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            event_count: 0,
+            oldest_event: None,
+            newest_event: None,
+        }
+    }
+}
+
+/// Storage backends that partition events by namespace.
+#[async_trait]
+pub trait NamespacedStorage: StorageBackend {
+    /// Commit an event into `namespace`.
+    ///
+    /// Any namespace already set on `header` is replaced. Returns the header
+    /// as committed.
+    async fn commit_in(
+        &self,
+        namespace: &str,
+        header: &EventHeader,
+        payload: &[u8],
+    ) -> anyhow::Result<EventHeader> {
+        validate_namespace(namespace)?;
+        let mut header = header.clone();
+        header.namespace = Some(namespace.to_string());
+        self.commit(&header, payload).await?;
+        Ok(header)
+    }
+
+    /// Fetch a header, returning `None` if it belongs to another namespace.
+    async fn header_in(&self, namespace: &str, id: &EventId) -> anyhow::Result<Option<EventHeader>> {
+        Ok(self
+            .header(id)
+            .await?
+            .filter(|header| header.namespace_or_default() == namespace))
+    }
+
+    /// Fetch the payload of an event in `namespace`.
+    ///
+    /// Payloads are resolved through the header so a digest shared with an
+    /// event of another namespace cannot be used to read across tenants.
+    async fn payload_in(&self, namespace: &str, id: &EventId) -> anyhow::Result<Option<Vec<u8>>> {
+        match self.header_in(namespace, id).await? {
+            Some(header) => self.payload_bytes(&header.digest).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Statistics of every namespace that contains events, sorted by name.
+    async fn namespaces(&self) -> anyhow::Result<Vec<NamespaceStats>>;
+
+    /// Statistics of a single namespace.
+    async fn namespace_stats(&self, namespace: &str) -> anyhow::Result<NamespaceStats> {
+        Ok(self
+            .namespaces()
+            .await?
+            .into_iter()
+            .find(|stats| stats.namespace == namespace)
+            .unwrap_or_else(|| NamespaceStats::empty(namespace)))
+    }
+
+    /// Delete the events of `namespace`, or only those older than `before`.
+    ///
+    /// Returns the number of events removed.
+    async fn prune_namespace(
+        &self,
+        namespace: &str,
+        before: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<usize>;
+
+    /// Stream events committed to `namespace` from now on.
+    async fn subscribe_namespace(&self, namespace: &str) -> anyhow::Result<EventHeaderStream<'_>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_event_header;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_validate_namespace() {
+        assert!(validate_namespace("tenant-a.workspace_1").is_ok());
+        assert!(validate_namespace(DEFAULT_NAMESPACE).is_ok());
+        assert!(validate_namespace("").is_err());
+        assert!(validate_namespace("a/b").is_err());
+        assert!(validate_namespace(&"x".repeat(MAX_NAMESPACE_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn test_filter_namespace() {
+        let mut tenant = create_event_header(&[], Uuid::new_v4(), "test".to_string(), &1u32).unwrap();
+        tenant.namespace = Some("tenant".to_string());
+        let unscoped = create_event_header(&[], Uuid::new_v4(), "test".to_string(), &2u32).unwrap();
+
+        let stream: EventHeaderStream<'_> =
+            Box::pin(futures::stream::iter(vec![Ok(tenant.clone()), Ok(unscoped.clone())]));
+        let kept: Vec<_> = futures::executor::block_on(
+            filter_namespace(stream, DEFAULT_NAMESPACE)
+                .map(|item| item.unwrap().id)
+                .collect(),
+        );
+        assert_eq!(kept, vec![unscoped.id]);
+    }
+}
@@ -0,0 +1,179 @@
+#![forbid(unsafe_code)]
+
+//! Transactional outbox for keeping the store and the event bus consistent.
+//!
+//! Publishing to the bus after a storage commit leaves a window where the
+//! commit succeeds but the broadcast is lost (crash, full buffer, restart).
+//! With the outbox pattern, messages destined for the bus are staged as
+//! ordinary events ([`OUTBOX_MESSAGE_KIND`]) inside the *same* WAL transaction
+//! as the state change, so both become durable together or not at all.
+//!
+//! An [`OutboxRelay`] then scans for staged messages that have no
+//! acknowledgement ([`OUTBOX_ACK_KIND`]), hands them to an
+//! [`OutboxPublisher`] and records an acknowledgement once publishing
+//! succeeds. A crash between publishing and acknowledging causes the message
+//! to be published again, so delivery is **at-least-once**; consumers should
+//! be idempotent.
+
+use std::collections::HashSet;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    create_event_header, decode_payload, EventHeader, EventId, EventPayload, IntentId,
+    QueryableStorage, StorageError, TransactionId, WalStorageBackend,
+};
+
+/// Event kind of staged outbox messages.
+pub const OUTBOX_MESSAGE_KIND: &str = "outbox.message";
+
+/// Event kind of relay acknowledgements.
+pub const OUTBOX_ACK_KIND: &str = "outbox.published";
+
+/// Acknowledgement recorded once a staged message has been published.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OutboxAck {
+    /// ID of the staged message event
+    pub message: EventId,
+    /// When the relay published the message
+    pub published_at: DateTime<Utc>,
+}
+
+/// Destination for relayed outbox messages (typically the event bus).
+#[async_trait]
+pub trait OutboxPublisher<M>: Send + Sync {
+    /// Publish one message. Returning an error leaves it pending for retry.
+    async fn publish(&self, message: &M) -> anyhow::Result<()>;
+}
+
+/// Stage outbox messages inside a WAL transaction.
+#[async_trait]
+pub trait OutboxExt: WalStorageBackend {
+    /// Stage `message` for publication as part of `transaction_id`.
+    ///
+    /// The message only becomes visible to the relay once the transaction
+    /// commits, and is discarded if it rolls back.
+    async fn stage_outbox<M: EventPayload>(
+        &self,
+        transaction_id: TransactionId,
+        intent: IntentId,
+        message: &M,
+    ) -> anyhow::Result<EventHeader> {
+        let header = create_event_header(&[], intent, OUTBOX_MESSAGE_KIND.to_string(), message)
+            .map_err(|e| StorageError::SerializationFailed(e.to_string()))?;
+        let payload = rmp_serde::to_vec_named(message)
+            .map_err(|e| StorageError::SerializationFailed(e.to_string()))?;
+        self.commit_with_wal(transaction_id, &header, &payload).await?;
+        Ok(header)
+    }
+}
+
+impl<T> OutboxExt for T where T: WalStorageBackend + ?Sized {}
+
+/// Relays staged outbox messages to a publisher with at-least-once delivery.
+///
+/// The relay is stateless: everything it needs is read back from the store,
+/// so it can be restarted (or run as several instances, at the cost of
+/// duplicate publishes) at any time. Call [`relay_once`](Self::relay_once)
+/// periodically or whenever the store signals new commits.
+pub struct OutboxRelay<S: ?Sized, P, M> {
+    store: Arc<S>,
+    publisher: P,
+    _message: PhantomData<fn() -> M>,
+}
+
+impl<S, P, M> OutboxRelay<S, P, M>
+where
+    S: QueryableStorage + ?Sized,
+    P: OutboxPublisher<M>,
+    M: EventPayload,
+{
+    /// Create a relay reading from `store` and publishing to `publisher`.
+    pub fn new(store: Arc<S>, publisher: P) -> Self {
+        Self {
+            store,
+            publisher,
+            _message: PhantomData,
+        }
+    }
+
+    /// Staged messages that have not been acknowledged yet, oldest first.
+    pub async fn pending(&self) -> anyhow::Result<Vec<EventHeader>> {
+        let mut acknowledged = HashSet::new();
+        let mut acks = self.store.events_by_kind(OUTBOX_ACK_KIND).await?;
+        while let Some(header) = acks.try_next().await? {
+            let Some(bytes) = self.store.payload_bytes(&header.digest).await? else {
+                continue;
+            };
+            let ack: OutboxAck = decode_payload(&header, &bytes)?;
+            acknowledged.insert(ack.message);
+        }
+
+        let messages: Vec<EventHeader> = self
+            .store
+            .events_by_kind(OUTBOX_MESSAGE_KIND)
+            .await?
+            .try_collect()
+            .await?;
+        Ok(messages
+            .into_iter()
+            .filter(|header| !acknowledged.contains(&header.id))
+            .collect())
+    }
+
+    /// Publish every pending message in order and acknowledge each one.
+    ///
+    /// Stops at the first publish failure so ordering is preserved; the
+    /// failed message and those after it are retried on the next call.
+    /// Returns the number of messages published.
+    pub async fn relay_once(&self) -> anyhow::Result<usize> {
+        let mut published = 0;
+
+        for header in self.pending().await? {
+            let bytes = self
+                .store
+                .payload_bytes(&header.digest)
+                .await?
+                .ok_or_else(|| StorageError::EventNotFound(format!("outbox payload for {}", header.id)))?;
+            let message: M = decode_payload(&header, &bytes)?;
+
+            self.publisher.publish(&message).await?;
+
+            let ack = OutboxAck {
+                message: header.id,
+                published_at: Utc::now(),
+            };
+            let ack_header = create_event_header(
+                std::slice::from_ref(&header),
+                header.intent,
+                OUTBOX_ACK_KIND.to_string(),
+                &ack,
+            )
+            .map_err(|e| StorageError::SerializationFailed(e.to_string()))?;
+            let ack_payload = rmp_serde::to_vec_named(&ack)
+                .map_err(|e| StorageError::SerializationFailed(e.to_string()))?;
+            self.store.commit(&ack_header, &ack_payload).await?;
+
+            published += 1;
+        }
+
+        Ok(published)
+    }
+}
+
+/// [`OutboxPublisher`] forwarding kernel events to an event bus.
+#[cfg(feature = "bus")]
+pub struct BusPublisher(pub Arc<dyn toka_bus_core::EventBus>);
+
+#[cfg(feature = "bus")]
+#[async_trait]
+impl OutboxPublisher<toka_bus_core::KernelEvent> for BusPublisher {
+    async fn publish(&self, message: &toka_bus_core::KernelEvent) -> anyhow::Result<()> {
+        self.0.publish(message)
+    }
+}
@@ -0,0 +1,455 @@
+#![forbid(unsafe_code)]
+
+//! Durable, store-backed work queue.
+//!
+//! Scheduling a task by publishing a bus event is fire-and-forget: if the
+//! process crashes between "task scheduled" and the task actually running,
+//! the task is gone. [`DurableQueue`] records every state change of a task
+//! (enqueue, claim, heartbeat, completion, failure, dead-lettering) as an
+//! event in a [`QueryableStorage`] backend and rebuilds its state from those
+//! events on [`open`](DurableQueue::open), so work survives restarts.
+//!
+//! Workers *claim* a task under a time-limited lease and keep it alive with
+//! heartbeats. A lease that expires counts as a failed attempt and the task
+//! becomes claimable again after an exponential backoff. Tasks that exhaust
+//! their attempts are moved to a dead-letter set for inspection and manual
+//! requeueing.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures::lock::Mutex;
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    causal_hash, create_event_header, decode_payload, EventHeader, EventPayload, IntentId,
+    QueryableStorage, StorageError,
+};
+
+/// Identifier of a queued task.
+pub type TaskId = Uuid;
+
+/// Prefix of the event kinds written by the queue.
+pub const QUEUE_KIND_PREFIX: &str = "queue";
+
+/// Tuning options for a [`DurableQueue`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QueueConfig {
+    /// Attempts before a task is dead-lettered
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled for every further attempt
+    pub retry_backoff: Duration,
+    /// Upper bound for the retry delay
+    pub max_retry_backoff: Duration,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            retry_backoff: Duration::from_secs(1),
+            max_retry_backoff: Duration::from_secs(300),
+        }
+    }
+}
+
+/// Lifecycle state of a queued task.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    /// Waiting to be claimed (possibly after a retry delay)
+    Pending,
+    /// Claimed by a worker holding a lease
+    Claimed,
+    /// Finished successfully
+    Completed,
+    /// Out of attempts; needs manual attention
+    DeadLettered,
+}
+
+/// Snapshot of a queued task.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QueuedTask<T> {
+    /// Task identifier
+    pub id: TaskId,
+    /// Task payload
+    pub payload: T,
+    /// Current status
+    pub status: TaskStatus,
+    /// Attempts made so far
+    pub attempts: u32,
+    /// Attempts allowed before dead-lettering
+    pub max_attempts: u32,
+    /// Worker currently holding the lease
+    pub worker: Option<String>,
+    /// When the current lease expires
+    pub lease_until: Option<DateTime<Utc>>,
+    /// Earliest time the task may be claimed
+    pub available_at: DateTime<Utc>,
+    /// Error reported by the last failed attempt
+    pub last_error: Option<String>,
+    /// When the task was enqueued
+    pub enqueued_at: DateTime<Utc>,
+}
+
+/// Task counts by status.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueStats {
+    /// Tasks waiting to be claimed
+    pub pending: usize,
+    /// Tasks currently leased
+    pub claimed: usize,
+    /// Tasks finished successfully
+    pub completed: usize,
+    /// Tasks in the dead-letter set
+    pub dead_lettered: usize,
+}
+
+/// State change recorded in the store.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum QueueRecord<T> {
+    Enqueued { task: TaskId, payload: T, max_attempts: u32 },
+    Claimed { task: TaskId, worker: String, lease_until: DateTime<Utc> },
+    Heartbeat { task: TaskId, lease_until: DateTime<Utc> },
+    Completed { task: TaskId },
+    Failed { task: TaskId, error: String, retry_at: DateTime<Utc> },
+    DeadLettered { task: TaskId, error: String },
+    Requeued { task: TaskId },
+}
+
+impl<T> QueueRecord<T> {
+    fn task(&self) -> TaskId {
+        match self {
+            QueueRecord::Enqueued { task, .. }
+            | QueueRecord::Claimed { task, .. }
+            | QueueRecord::Heartbeat { task, .. }
+            | QueueRecord::Completed { task }
+            | QueueRecord::Failed { task, .. }
+            | QueueRecord::DeadLettered { task, .. }
+            | QueueRecord::Requeued { task } => *task,
+        }
+    }
+
+    fn kind(&self) -> String {
+        let action = match self {
+            QueueRecord::Enqueued { .. } => "enqueued",
+            QueueRecord::Claimed { .. } => "claimed",
+            QueueRecord::Heartbeat { .. } => "heartbeat",
+            QueueRecord::Completed { .. } => "completed",
+            QueueRecord::Failed { .. } => "failed",
+            QueueRecord::DeadLettered { .. } => "dead_lettered",
+            QueueRecord::Requeued { .. } => "requeued",
+        };
+        format!("{}.{}", QUEUE_KIND_PREFIX, action)
+    }
+}
+
+/// In-memory view of the queue rebuilt from stored records.
+struct QueueState<T> {
+    tasks: HashMap<TaskId, QueuedTask<T>>,
+    /// Enqueue order, used for FIFO claiming
+    order: Vec<TaskId>,
+    /// Latest record per task, used as the causal parent of the next one
+    heads: HashMap<TaskId, EventHeader>,
+}
+
+impl<T: Clone> QueueState<T> {
+    fn apply(&mut self, record: QueueRecord<T>, header: EventHeader) {
+        let task_id = record.task();
+        let timestamp = header.timestamp;
+        self.heads.insert(task_id, header);
+
+        if let QueueRecord::Enqueued { task, payload, max_attempts } = record {
+            self.order.push(task);
+            self.tasks.insert(
+                task,
+                QueuedTask {
+                    id: task,
+                    payload,
+                    status: TaskStatus::Pending,
+                    attempts: 0,
+                    max_attempts,
+                    worker: None,
+                    lease_until: None,
+                    available_at: timestamp,
+                    last_error: None,
+                    enqueued_at: timestamp,
+                },
+            );
+            return;
+        }
+
+        let Some(task) = self.tasks.get_mut(&task_id) else {
+            return;
+        };
+        match record {
+            QueueRecord::Enqueued { .. } => {}
+            QueueRecord::Claimed { worker, lease_until, .. } => {
+                task.status = TaskStatus::Claimed;
+                task.attempts += 1;
+                task.worker = Some(worker);
+                task.lease_until = Some(lease_until);
+            }
+            QueueRecord::Heartbeat { lease_until, .. } => {
+                task.lease_until = Some(lease_until);
+            }
+            QueueRecord::Completed { .. } => {
+                task.status = TaskStatus::Completed;
+                task.lease_until = None;
+            }
+            QueueRecord::Failed { error, retry_at, .. } => {
+                task.status = TaskStatus::Pending;
+                task.worker = None;
+                task.lease_until = None;
+                task.available_at = retry_at;
+                task.last_error = Some(error);
+            }
+            QueueRecord::DeadLettered { error, .. } => {
+                task.status = TaskStatus::DeadLettered;
+                task.worker = None;
+                task.lease_until = None;
+                task.last_error = Some(error);
+            }
+            QueueRecord::Requeued { .. } => {
+                task.status = TaskStatus::Pending;
+                task.attempts = 0;
+                task.available_at = timestamp;
+            }
+        }
+    }
+}
+
+/// Durable work queue persisted in a storage backend.
+///
+/// All operations on one queue instance are serialized. Run a single
+/// instance per queue name; separate instances over the same store do not
+/// see each other's changes until reopened.
+pub struct DurableQueue<T> {
+    store: Arc<dyn QueryableStorage>,
+    intent: IntentId,
+    config: QueueConfig,
+    state: Mutex<QueueState<T>>,
+}
+
+impl<T: EventPayload + Clone> DurableQueue<T> {
+    /// Open the queue `name`, replaying its history from `store`.
+    pub async fn open(store: Arc<dyn QueryableStorage>, name: &str, config: QueueConfig) -> anyhow::Result<Self> {
+        let intent = queue_intent(name);
+        let mut state = QueueState {
+            tasks: HashMap::new(),
+            order: Vec::new(),
+            heads: HashMap::new(),
+        };
+
+        let headers: Vec<EventHeader> = store.events_by_intent(&intent).await?.try_collect().await?;
+        for header in headers {
+            if !header.kind.starts_with(QUEUE_KIND_PREFIX) {
+                continue;
+            }
+            let bytes = store
+                .payload_bytes(&header.digest)
+                .await?
+                .ok_or_else(|| StorageError::EventNotFound(format!("queue record {}", header.id)))?;
+            let record: QueueRecord<T> = decode_payload(&header, &bytes)?;
+            state.apply(record, header);
+        }
+
+        Ok(Self {
+            store,
+            intent,
+            config,
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Add a task to the queue.
+    pub async fn enqueue(&self, payload: T) -> anyhow::Result<TaskId> {
+        let task = Uuid::new_v4();
+        let mut state = self.state.lock().await;
+        self.record(
+            &mut state,
+            QueueRecord::Enqueued { task, payload, max_attempts: self.config.max_attempts.max(1) },
+        )
+        .await?;
+        Ok(task)
+    }
+
+    /// Claim the oldest available task for `worker` under a lease of `lease`.
+    ///
+    /// Expired leases are reclaimed first. Returns `None` if nothing is
+    /// currently claimable.
+    pub async fn claim(&self, worker: &str, lease: Duration) -> anyhow::Result<Option<QueuedTask<T>>> {
+        let mut state = self.state.lock().await;
+        self.reclaim_expired(&mut state).await?;
+
+        let now = Utc::now();
+        let next = state.order.iter().copied().find(|id| {
+            state
+                .tasks
+                .get(id)
+                .is_some_and(|task| task.status == TaskStatus::Pending && task.available_at <= now)
+        });
+        let Some(task) = next else {
+            return Ok(None);
+        };
+
+        self.record(
+            &mut state,
+            QueueRecord::Claimed {
+                task,
+                worker: worker.to_string(),
+                lease_until: now + chrono::Duration::from_std(lease)?,
+            },
+        )
+        .await?;
+        Ok(state.tasks.get(&task).cloned())
+    }
+
+    /// Extend the lease on a claimed task.
+    pub async fn heartbeat(&self, task: TaskId, worker: &str, lease: Duration) -> anyhow::Result<()> {
+        let mut state = self.state.lock().await;
+        Self::check_holder(&state, task, worker)?;
+        let lease_until = Utc::now() + chrono::Duration::from_std(lease)?;
+        self.record(&mut state, QueueRecord::Heartbeat { task, lease_until }).await
+    }
+
+    /// Mark a claimed task as finished.
+    pub async fn complete(&self, task: TaskId, worker: &str) -> anyhow::Result<()> {
+        let mut state = self.state.lock().await;
+        Self::check_holder(&state, task, worker)?;
+        self.record(&mut state, QueueRecord::Completed { task }).await
+    }
+
+    /// Report a failed attempt; the task is retried or dead-lettered.
+    pub async fn fail(&self, task: TaskId, worker: &str, error: &str) -> anyhow::Result<TaskStatus> {
+        let mut state = self.state.lock().await;
+        Self::check_holder(&state, task, worker)?;
+        self.record_failure(&mut state, task, error.to_string()).await
+    }
+
+    /// Move a dead-lettered task back to the queue with a fresh attempt budget.
+    pub async fn requeue(&self, task: TaskId) -> anyhow::Result<()> {
+        let mut state = self.state.lock().await;
+        match state.tasks.get(&task) {
+            Some(t) if t.status == TaskStatus::DeadLettered => {}
+            Some(t) => anyhow::bail!("Task {} is not dead-lettered (status: {:?})", task, t.status),
+            None => anyhow::bail!("Task {} not found", task),
+        }
+        self.record(&mut state, QueueRecord::Requeued { task }).await
+    }
+
+    /// Look up a task.
+    pub async fn task(&self, task: TaskId) -> Option<QueuedTask<T>> {
+        self.state.lock().await.tasks.get(&task).cloned()
+    }
+
+    /// Tasks in the dead-letter set, oldest first.
+    pub async fn dead_letters(&self) -> Vec<QueuedTask<T>> {
+        let state = self.state.lock().await;
+        state
+            .order
+            .iter()
+            .filter_map(|id| state.tasks.get(id))
+            .filter(|task| task.status == TaskStatus::DeadLettered)
+            .cloned()
+            .collect()
+    }
+
+    /// Task counts by status.
+    pub async fn stats(&self) -> QueueStats {
+        let state = self.state.lock().await;
+        let mut stats = QueueStats::default();
+        for task in state.tasks.values() {
+            match task.status {
+                TaskStatus::Pending => stats.pending += 1,
+                TaskStatus::Claimed => stats.claimed += 1,
+                TaskStatus::Completed => stats.completed += 1,
+                TaskStatus::DeadLettered => stats.dead_lettered += 1,
+            }
+        }
+        stats
+    }
+
+    fn check_holder(state: &QueueState<T>, task: TaskId, worker: &str) -> anyhow::Result<()> {
+        let Some(t) = state.tasks.get(&task) else {
+            anyhow::bail!("Task {} not found", task);
+        };
+        if t.status != TaskStatus::Claimed || t.worker.as_deref() != Some(worker) {
+            anyhow::bail!("Task {} is not claimed by worker {}", task, worker);
+        }
+        if t.lease_until.is_some_and(|until| until < Utc::now()) {
+            anyhow::bail!("Lease on task {} held by {} has expired", task, worker);
+        }
+        Ok(())
+    }
+
+    async fn reclaim_expired(&self, state: &mut QueueState<T>) -> anyhow::Result<()> {
+        let now = Utc::now();
+        let expired: Vec<TaskId> = state
+            .order
+            .iter()
+            .copied()
+            .filter(|id| {
+                state.tasks.get(id).is_some_and(|task| {
+                    task.status == TaskStatus::Claimed && task.lease_until.is_some_and(|until| until < now)
+                })
+            })
+            .collect();
+
+        for task in expired {
+            self.record_failure(state, task, "lease expired".to_string()).await?;
+        }
+        Ok(())
+    }
+
+    async fn record_failure(
+        &self,
+        state: &mut QueueState<T>,
+        task: TaskId,
+        error: String,
+    ) -> anyhow::Result<TaskStatus> {
+        let attempts = state.tasks.get(&task).map(|t| (t.attempts, t.max_attempts));
+        let Some((attempts, max_attempts)) = attempts else {
+            anyhow::bail!("Task {} not found", task);
+        };
+
+        if attempts >= max_attempts {
+            self.record(state, QueueRecord::DeadLettered { task, error }).await?;
+            return Ok(TaskStatus::DeadLettered);
+        }
+
+        let backoff = self
+            .config
+            .retry_backoff
+            .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+            .min(self.config.max_retry_backoff);
+        let retry_at = Utc::now() + chrono::Duration::from_std(backoff)?;
+        self.record(state, QueueRecord::Failed { task, error, retry_at }).await?;
+        Ok(TaskStatus::Pending)
+    }
+
+    /// Persist a record, then apply it to the in-memory state.
+    async fn record(&self, state: &mut QueueState<T>, record: QueueRecord<T>) -> anyhow::Result<()> {
+        let parents: Vec<EventHeader> = state.heads.get(&record.task()).cloned().into_iter().collect();
+        let header = create_event_header(&parents, self.intent, record.kind(), &record)
+            .map_err(|e| StorageError::SerializationFailed(e.to_string()))?;
+        let payload = rmp_serde::to_vec_named(&record)
+            .map_err(|e| StorageError::SerializationFailed(e.to_string()))?;
+
+        self.store.commit(&header, &payload).await?;
+        state.apply(record, header);
+        Ok(())
+    }
+}
+
+/// Deterministic intent ID grouping all records of the queue `name`.
+pub fn queue_intent(name: &str) -> IntentId {
+    let digest = causal_hash(format!("{}:{}", QUEUE_KIND_PREFIX, name).as_bytes(), &[]);
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    Uuid::from_bytes(bytes)
+}
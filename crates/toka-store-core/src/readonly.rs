@@ -0,0 +1,144 @@
+#![forbid(unsafe_code)]
+
+//! Read-only wrapper for attaching consumers to production stores.
+//!
+//! Audit and analytics tools only need to read, but a plain backend handle
+//! lets them commit events or drive the WAL by mistake. [`ReadOnlyBackend`]
+//! forwards every read to the wrapped backend and rejects every write with
+//! [`StorageError::ReadOnly`], so such consumers can be attached safely.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::{
+    CausalDigest, EventHeader, EventHeaderStream, EventId, IntentId, QueryableStorage, ReplayFrom,
+    ReplayableStorage, SavepointId, SequenceNumber, StorageBackend, StorageError, StorageStatistics, StorageStats,
+    TransactionId, WalHealth, WalOperation, WalRecoveryResult, WriteAheadLog,
+};
+
+/// Storage backend wrapper that rejects all writes.
+#[derive(Debug, Clone)]
+pub struct ReadOnlyBackend<B> {
+    inner: B,
+}
+
+impl<B> ReadOnlyBackend<B> {
+    /// Wrap `inner`, exposing only its read operations.
+    pub fn new(inner: B) -> Self {
+        Self { inner }
+    }
+
+    /// Access the wrapped backend.
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+}
+
+fn rejected<T>(operation: &str) -> anyhow::Result<T> {
+    Err(StorageError::ReadOnly(operation.to_string()).into())
+}
+
+#[async_trait]
+impl<B: StorageBackend> StorageBackend for ReadOnlyBackend<B> {
+    async fn commit(&self, _header: &EventHeader, _payload: &[u8]) -> anyhow::Result<()> {
+        rejected("commit")
+    }
+
+    async fn header(&self, id: &EventId) -> anyhow::Result<Option<EventHeader>> {
+        self.inner.header(id).await
+    }
+
+    async fn payload_bytes(&self, digest: &CausalDigest) -> anyhow::Result<Option<Vec<u8>>> {
+        self.inner.payload_bytes(digest).await
+    }
+
+    async fn payloads_bulk(
+        &self,
+        digests: &[CausalDigest],
+    ) -> anyhow::Result<std::collections::HashMap<CausalDigest, Vec<u8>>> {
+        self.inner.payloads_bulk(digests).await
+    }
+}
+
+#[async_trait]
+impl<B: QueryableStorage> QueryableStorage for ReadOnlyBackend<B> {
+    async fn events_by_kind(&self, kind: &str) -> anyhow::Result<EventHeaderStream<'_>> {
+        self.inner.events_by_kind(kind).await
+    }
+
+    async fn events_in_range(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> anyhow::Result<EventHeaderStream<'_>> {
+        self.inner.events_in_range(from, to).await
+    }
+
+    async fn events_by_intent(&self, intent: &IntentId) -> anyhow::Result<EventHeaderStream<'_>> {
+        self.inner.events_by_intent(intent).await
+    }
+}
+
+#[async_trait]
+impl<B: ReplayableStorage> ReplayableStorage for ReadOnlyBackend<B> {
+    async fn subscribe_from(&self, from: ReplayFrom) -> anyhow::Result<EventHeaderStream<'_>> {
+        self.inner.subscribe_from(from).await
+    }
+}
+
+#[async_trait]
+impl<B: StorageStats> StorageStats for ReadOnlyBackend<B> {
+    async fn storage_stats(&self) -> anyhow::Result<StorageStatistics> {
+        self.inner.storage_stats().await
+    }
+}
+
+/// Only [`current_sequence`](WriteAheadLog::current_sequence) and
+/// [`wal_health`](WriteAheadLog::wal_health) are forwarded; every operation
+/// that writes or replays the log is rejected.
+#[async_trait]
+impl<B: StorageBackend + WriteAheadLog> WriteAheadLog for ReadOnlyBackend<B> {
+    async fn begin_transaction(&self) -> anyhow::Result<TransactionId> {
+        rejected("begin_transaction")
+    }
+
+    async fn write_entry(&self, _transaction_id: TransactionId, _operation: WalOperation) -> anyhow::Result<()> {
+        rejected("write_entry")
+    }
+
+    async fn commit_transaction(&self, _transaction_id: TransactionId) -> anyhow::Result<()> {
+        rejected("commit_transaction")
+    }
+
+    async fn rollback_transaction(&self, _transaction_id: TransactionId) -> anyhow::Result<()> {
+        rejected("rollback_transaction")
+    }
+
+    async fn savepoint(&self, _transaction_id: TransactionId) -> anyhow::Result<SavepointId> {
+        rejected("savepoint")
+    }
+
+    async fn rollback_to_savepoint(
+        &self,
+        _transaction_id: TransactionId,
+        _savepoint_id: SavepointId,
+    ) -> anyhow::Result<()> {
+        rejected("rollback_to_savepoint")
+    }
+
+    async fn recover(&self) -> anyhow::Result<WalRecoveryResult> {
+        rejected("recover")
+    }
+
+    async fn checkpoint(&self, _sequence: SequenceNumber) -> anyhow::Result<()> {
+        rejected("checkpoint")
+    }
+
+    async fn current_sequence(&self) -> anyhow::Result<SequenceNumber> {
+        self.inner.current_sequence().await
+    }
+
+    async fn wal_health(&self) -> anyhow::Result<WalHealth> {
+        self.inner.wal_health().await
+    }
+}
@@ -0,0 +1,159 @@
+#![forbid(unsafe_code)]
+
+//! Hooks into WAL recovery.
+//!
+//! [`WriteAheadLog::recover`](crate::WriteAheadLog::recover) re-applies the
+//! events of committed transactions and rolls back unfinished ones, reporting
+//! only totals. Embedders that need to know what happened (to emit kernel
+//! events, alert operators, or refuse to replay particular operations)
+//! register a [`RecoveryObserver`] with the backend. Backends keep their
+//! observers in [`RecoveryHooks`] and call it as recovery proceeds.
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{TransactionId, WalEntry};
+
+/// Whether recovery should re-apply a committed operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayDecision {
+    /// Re-apply the operation
+    Apply,
+    /// Leave the operation out of the store
+    Skip,
+}
+
+/// Callbacks invoked while a backend recovers its WAL.
+///
+/// Callbacks run inline with recovery and should return quickly; forward to a
+/// channel for anything slow. All methods default to doing nothing.
+pub trait RecoveryObserver: Send + Sync {
+    /// Decide whether a committed entry is re-applied.
+    fn before_replay(&self, _entry: &WalEntry) -> ReplayDecision {
+        ReplayDecision::Apply
+    }
+
+    /// A committed transaction was replayed; `skipped` entries were vetoed.
+    fn on_transaction_replayed(&self, _transaction_id: TransactionId, _applied: usize, _skipped: usize) {}
+
+    /// An unfinished transaction of `entries` WAL entries was rolled back.
+    fn on_rollback(&self, _transaction_id: TransactionId, _entries: usize) {}
+
+    /// Recovery hit an error, for a specific transaction if known.
+    ///
+    /// Recovery carries on; the error is also listed in
+    /// [`WalRecoveryResult::recovery_errors`](crate::WalRecoveryResult::recovery_errors).
+    fn on_error(&self, _transaction_id: Option<TransactionId>, _error: &str) {}
+}
+
+/// Observers registered with a backend.
+#[derive(Clone, Default)]
+pub struct RecoveryHooks {
+    observers: Vec<Arc<dyn RecoveryObserver>>,
+}
+
+impl fmt::Debug for RecoveryHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecoveryHooks")
+            .field("observers", &self.observers.len())
+            .finish()
+    }
+}
+
+impl RecoveryHooks {
+    /// No observers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an observer; observers are called in registration order.
+    pub fn register(&mut self, observer: Arc<dyn RecoveryObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// Whether no observer is registered.
+    pub fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+
+    /// Skip the entry if any observer vetoes it.
+    pub fn before_replay(&self, entry: &WalEntry) -> ReplayDecision {
+        // Every observer sees the entry, even after a veto
+        self.observers.iter().fold(ReplayDecision::Apply, |decision, observer| {
+            match observer.before_replay(entry) {
+                ReplayDecision::Skip => ReplayDecision::Skip,
+                ReplayDecision::Apply => decision,
+            }
+        })
+    }
+
+    /// Notify every observer of a replayed transaction.
+    pub fn on_transaction_replayed(&self, transaction_id: TransactionId, applied: usize, skipped: usize) {
+        for observer in &self.observers {
+            observer.on_transaction_replayed(transaction_id, applied, skipped);
+        }
+    }
+
+    /// Notify every observer of a rolled back transaction.
+    pub fn on_rollback(&self, transaction_id: TransactionId, entries: usize) {
+        for observer in &self.observers {
+            observer.on_rollback(transaction_id, entries);
+        }
+    }
+
+    /// Notify every observer of an error.
+    pub fn on_error(&self, transaction_id: Option<TransactionId>, error: &str) {
+        for observer in &self.observers {
+            observer.on_error(transaction_id, error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{WalEntryState, WalOperation};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use uuid::Uuid;
+
+    #[derive(Default)]
+    struct Counting {
+        seen: AtomicUsize,
+        veto: bool,
+    }
+
+    impl RecoveryObserver for Counting {
+        fn before_replay(&self, _entry: &WalEntry) -> ReplayDecision {
+            self.seen.fetch_add(1, Ordering::SeqCst);
+            if self.veto {
+                ReplayDecision::Skip
+            } else {
+                ReplayDecision::Apply
+            }
+        }
+    }
+
+    #[test]
+    fn test_any_observer_can_veto() {
+        let transaction_id = Uuid::new_v4();
+        let entry = WalEntry {
+            id: Uuid::new_v4(),
+            transaction_id,
+            sequence: 1,
+            timestamp: chrono::Utc::now(),
+            operation: WalOperation::CommitTransaction { transaction_id },
+            state: WalEntryState::Committed,
+        };
+
+        let mut hooks = RecoveryHooks::new();
+        assert_eq!(hooks.before_replay(&entry), ReplayDecision::Apply);
+
+        let vetoing = Arc::new(Counting { veto: true, ..Default::default() });
+        let approving = Arc::new(Counting::default());
+        hooks.register(vetoing.clone());
+        hooks.register(approving.clone());
+        assert_eq!(hooks.before_replay(&entry), ReplayDecision::Skip);
+        assert_eq!(vetoing.seen.load(Ordering::SeqCst), 1);
+        assert_eq!(approving.seen.load(Ordering::SeqCst), 1);
+    }
+}
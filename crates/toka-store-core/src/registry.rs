@@ -0,0 +1,292 @@
+#![forbid(unsafe_code)]
+
+//! Event kind registry binding `kind` strings to payload types.
+//!
+//! Event kinds are plain strings in the [`EventHeader`], which makes it easy
+//! for producers and consumers to drift apart. Payload types implementing
+//! [`TypedEvent`] declare their kind once, and the [`TypedStorageExt`] helpers
+//! use that binding to commit and fetch payloads without repeating it.
+//!
+//! The [`EventKindRegistry`] records which Rust type (and optionally which JSON
+//! schema) owns each kind so applications can validate headers coming from
+//! untyped producers. Kinds that are not registered are still required to be
+//! well-formed: dot-separated segments of lowercase ASCII letters, digits,
+//! `_` or `-` (e.g. `agent.task.completed`).
+
+use std::any::{type_name, TypeId};
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+
+use crate::{
+    create_event_header_with_format, decode_payload, EventHeader, EventId, EventPayload, IntentId,
+    PayloadFormat, StorageBackend, StorageError,
+};
+
+/// Maximum length of an event kind string.
+pub const MAX_KIND_LENGTH: usize = 128;
+
+/// A payload type bound to a single event kind.
+pub trait TypedEvent: EventPayload + 'static {
+    /// Event kind recorded in the header of every event of this type
+    const KIND: &'static str;
+
+    /// Serialization format used when committing this type
+    const FORMAT: PayloadFormat = PayloadFormat::MessagePack;
+}
+
+/// Registration details for one event kind.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KindRegistration {
+    /// The event kind string
+    pub kind: String,
+    /// Fully qualified name of the bound Rust type
+    pub type_name: &'static str,
+    /// Optional JSON schema describing the payload
+    pub schema: Option<serde_json::Value>,
+    type_id: TypeId,
+}
+
+/// Registry mapping event kinds to payload types and schemas.
+#[derive(Debug, Clone, Default)]
+pub struct EventKindRegistry {
+    kinds: HashMap<String, KindRegistration>,
+}
+
+impl EventKindRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the kind declared by `E`.
+    ///
+    /// Registering the same type twice is a no-op; binding a kind that is
+    /// already owned by a different type is an error.
+    pub fn register<E: TypedEvent>(&mut self) -> Result<&mut Self, StorageError> {
+        self.insert::<E>(None)
+    }
+
+    /// Register the kind declared by `E` together with a JSON schema.
+    pub fn register_with_schema<E: TypedEvent>(
+        &mut self,
+        schema: serde_json::Value,
+    ) -> Result<&mut Self, StorageError> {
+        self.insert::<E>(Some(schema))
+    }
+
+    fn insert<E: TypedEvent>(
+        &mut self,
+        schema: Option<serde_json::Value>,
+    ) -> Result<&mut Self, StorageError> {
+        validate_kind(E::KIND)?;
+
+        if let Some(existing) = self.kinds.get(E::KIND) {
+            if existing.type_id != TypeId::of::<E>() {
+                return Err(StorageError::KindMismatch {
+                    kind: E::KIND.to_string(),
+                    expected: existing.type_name.to_string(),
+                    actual: type_name::<E>().to_string(),
+                });
+            }
+        }
+
+        let previous_schema = self.kinds.get(E::KIND).and_then(|r| r.schema.clone());
+        self.kinds.insert(
+            E::KIND.to_string(),
+            KindRegistration {
+                kind: E::KIND.to_string(),
+                type_name: type_name::<E>(),
+                schema: schema.or(previous_schema),
+                type_id: TypeId::of::<E>(),
+            },
+        );
+        Ok(self)
+    }
+
+    /// Look up the registration for `kind`.
+    pub fn get(&self, kind: &str) -> Option<&KindRegistration> {
+        self.kinds.get(kind)
+    }
+
+    /// Whether `kind` has been registered.
+    pub fn is_registered(&self, kind: &str) -> bool {
+        self.kinds.contains_key(kind)
+    }
+
+    /// JSON schema registered for `kind`, if any.
+    pub fn schema(&self, kind: &str) -> Option<&serde_json::Value> {
+        self.kinds.get(kind).and_then(|r| r.schema.as_ref())
+    }
+
+    /// Iterate over all registered kinds.
+    pub fn kinds(&self) -> impl Iterator<Item = &str> {
+        self.kinds.keys().map(String::as_str)
+    }
+
+    /// Validate a kind: registered kinds are accepted, unknown kinds must be well-formed.
+    pub fn validate_kind(&self, kind: &str) -> Result<(), StorageError> {
+        if self.is_registered(kind) {
+            return Ok(());
+        }
+        validate_kind(kind)
+    }
+
+    /// Validate the kind recorded in a header.
+    pub fn validate(&self, header: &EventHeader) -> Result<(), StorageError> {
+        self.validate_kind(&header.kind)
+    }
+
+    /// Check that `kind` is bound to `E` (or is unregistered and well-formed).
+    pub fn check_binding<E: TypedEvent>(&self, kind: &str) -> Result<(), StorageError> {
+        match self.kinds.get(kind) {
+            Some(registration) if registration.type_id == TypeId::of::<E>() => Ok(()),
+            Some(registration) => Err(StorageError::KindMismatch {
+                kind: kind.to_string(),
+                expected: registration.type_name.to_string(),
+                actual: type_name::<E>().to_string(),
+            }),
+            None => validate_kind(kind),
+        }
+    }
+}
+
+/// Check that `kind` is well-formed.
+///
+/// A well-formed kind is non-empty, at most [`MAX_KIND_LENGTH`] bytes, and
+/// made of non-empty dot-separated segments of `[a-z0-9_-]`.
+pub fn validate_kind(kind: &str) -> Result<(), StorageError> {
+    let invalid = |reason: &str| StorageError::InvalidEventKind(format!("{:?}: {}", kind, reason));
+
+    if kind.is_empty() {
+        return Err(invalid("kind is empty"));
+    }
+    if kind.len() > MAX_KIND_LENGTH {
+        return Err(invalid("kind is too long"));
+    }
+
+    for segment in kind.split('.') {
+        if segment.is_empty() {
+            return Err(invalid("empty segment"));
+        }
+        if !segment
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_' || b == b'-')
+        {
+            return Err(invalid("segments may only contain [a-z0-9_-]"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Typed commit and fetch helpers for any [`StorageBackend`].
+#[async_trait]
+pub trait TypedStorageExt: StorageBackend {
+    /// Encode `payload`, build its header with `E::KIND` and commit it.
+    ///
+    /// Returns the committed header.
+    async fn commit_typed<E: TypedEvent>(
+        &self,
+        parents: &[EventHeader],
+        intent: IntentId,
+        payload: &E,
+    ) -> anyhow::Result<EventHeader> {
+        let (header, bytes) =
+            create_event_header_with_format(parents, intent, E::KIND.to_string(), payload, E::FORMAT)?;
+        self.commit(&header, &bytes).await?;
+        Ok(header)
+    }
+
+    /// Fetch an event and decode its payload as `E`.
+    ///
+    /// Returns `Ok(None)` if the event does not exist and an error if the
+    /// stored kind is not `E::KIND`.
+    async fn fetch_typed<E: TypedEvent>(&self, id: &EventId) -> anyhow::Result<Option<(EventHeader, E)>> {
+        let Some(header) = self.header(id).await? else {
+            return Ok(None);
+        };
+
+        if header.kind != E::KIND {
+            return Err(StorageError::KindMismatch {
+                kind: header.kind.clone(),
+                expected: E::KIND.to_string(),
+                actual: type_name::<E>().to_string(),
+            }
+            .into());
+        }
+
+        let bytes = self
+            .payload_bytes(&header.digest)
+            .await?
+            .ok_or_else(|| StorageError::EventNotFound(format!("payload for event {}", header.id)))?;
+        let payload = decode_payload(&header, &bytes)?;
+        Ok(Some((header, payload)))
+    }
+}
+
+impl<T> TypedStorageExt for T where T: StorageBackend + ?Sized {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+    use uuid::Uuid;
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    struct TaskCompleted {
+        task: String,
+    }
+
+    impl TypedEvent for TaskCompleted {
+        const KIND: &'static str = "agent.task.completed";
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    struct Impostor {
+        task: String,
+    }
+
+    impl TypedEvent for Impostor {
+        const KIND: &'static str = "agent.task.completed";
+    }
+
+    #[test]
+    fn test_kind_validation() {
+        assert!(validate_kind("agent.task.completed").is_ok());
+        assert!(validate_kind("ledger.mint_v2").is_ok());
+        assert!(validate_kind("").is_err());
+        assert!(validate_kind("agent..task").is_err());
+        assert!(validate_kind("Agent.Task").is_err());
+        assert!(validate_kind("agent task").is_err());
+        assert!(validate_kind(&"a".repeat(MAX_KIND_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn test_registry_bindings() {
+        let mut registry = EventKindRegistry::new();
+        registry
+            .register_with_schema::<TaskCompleted>(json!({"type": "object"}))
+            .unwrap();
+
+        // Re-registering the same type keeps the schema
+        registry.register::<TaskCompleted>().unwrap();
+        assert_eq!(registry.schema(TaskCompleted::KIND), Some(&json!({"type": "object"})));
+
+        // A different type cannot claim the same kind
+        assert!(matches!(
+            registry.register::<Impostor>(),
+            Err(StorageError::KindMismatch { .. })
+        ));
+        assert!(registry.check_binding::<TaskCompleted>(TaskCompleted::KIND).is_ok());
+        assert!(registry.check_binding::<Impostor>(TaskCompleted::KIND).is_err());
+
+        // Unknown kinds only need to be well-formed
+        assert!(registry.validate_kind("custom.kind").is_ok());
+        assert!(registry.validate_kind("Custom Kind").is_err());
+
+        let header = crate::create_event_header(&[], Uuid::new_v4(), "bad kind".to_string(), &1u8).unwrap();
+        assert!(registry.validate(&header).is_err());
+    }
+}
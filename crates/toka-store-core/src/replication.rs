@@ -0,0 +1,359 @@
+#![forbid(unsafe_code)]
+
+//! Streaming WAL replication to a follower backend.
+//!
+//! A [`WalReplicator`] tails the write-ahead log of a leader implementing
+//! [`WalSource`] and applies the events of every committed transaction to a
+//! follower [`StorageBackend`], which may be a local store or a client for a
+//! remote one. Rolled back transactions are skipped; open transactions are
+//! held back until they finish.
+//!
+//! Progress is captured in a [`ResumeToken`], so a restarted replicator picks
+//! up where the previous one stopped. Entries are only passed once every
+//! transaction before them has finished, so resuming never skips an event.
+//! Applying an event twice is harmless because commits are idempotent.
+//!
+//! Only transactions still present in the leader's WAL can be replicated:
+//! the follower must keep up with WAL compaction on the leader, or be seeded
+//! from a snapshot first.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::lock::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    EventHeader, EventId, SequenceNumber, StorageBackend, TransactionId, WalEntry, WalEntryState,
+    WalOperation, WriteAheadLog,
+};
+
+/// Default number of WAL entries fetched per poll.
+pub const DEFAULT_REPLICATION_BATCH: usize = 512;
+
+/// Write-ahead logs whose entries can be read back in sequence order.
+#[async_trait]
+pub trait WalSource: WriteAheadLog {
+    /// Up to `limit` entries with a sequence number greater than `after`,
+    /// in ascending sequence order.
+    async fn wal_entries_after(&self, after: SequenceNumber, limit: usize) -> anyhow::Result<Vec<WalEntry>>;
+}
+
+/// Position in the leader's WAL up to which everything was replicated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ResumeToken {
+    /// Last WAL sequence number that needs no further replication
+    pub sequence: SequenceNumber,
+}
+
+impl ResumeToken {
+    /// Token for replicating from the beginning of the WAL.
+    pub fn start() -> Self {
+        Self::default()
+    }
+}
+
+impl fmt::Display for ResumeToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "wal:{}", self.sequence)
+    }
+}
+
+impl FromStr for ResumeToken {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let sequence = s
+            .strip_prefix("wal:")
+            .ok_or_else(|| anyhow::anyhow!("Invalid resume token: {}", s))?
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid resume token {}: {}", s, e))?;
+        Ok(Self { sequence })
+    }
+}
+
+/// What a single [`WalReplicator::poll`] did.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplicationBatch {
+    /// WAL entries read from the leader
+    pub entries_read: usize,
+    /// Committed transactions applied to the follower
+    pub transactions_applied: usize,
+    /// Rolled back transactions skipped
+    pub transactions_skipped: usize,
+    /// Events committed to the follower
+    pub events_applied: usize,
+    /// Resume token after the poll
+    pub resume_token: ResumeToken,
+}
+
+/// How far a follower trails its leader.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplicationLag {
+    /// Current WAL sequence of the leader
+    pub leader_sequence: SequenceNumber,
+    /// Sequence up to which the follower is caught up
+    pub replicated_sequence: SequenceNumber,
+    /// WAL entries not yet replicated
+    pub entries_behind: u64,
+    /// When the follower last received an event
+    pub last_applied_at: Option<DateTime<Utc>>,
+}
+
+impl ReplicationLag {
+    /// Whether the follower has everything the leader has finished.
+    pub fn is_caught_up(&self) -> bool {
+        self.entries_behind == 0
+    }
+}
+
+/// Result of comparing replicated events between leader and follower.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsistencyReport {
+    /// Committed events compared
+    pub events_checked: usize,
+    /// Events missing on the follower
+    pub missing: Vec<EventId>,
+    /// Events whose header or payload differs on the follower
+    pub mismatched: Vec<EventId>,
+}
+
+impl ConsistencyReport {
+    /// Whether every checked event matched.
+    pub fn is_consistent(&self) -> bool {
+        self.missing.is_empty() && self.mismatched.is_empty()
+    }
+}
+
+#[derive(Debug, Default)]
+struct ReplicatorState {
+    token: ResumeToken,
+    /// Transactions applied past the resume token, not to be applied again
+    applied: HashSet<TransactionId>,
+    last_applied_at: Option<DateTime<Utc>>,
+}
+
+/// Applies committed WAL transactions of a leader to a follower backend.
+pub struct WalReplicator<'a, L: ?Sized, F: ?Sized> {
+    leader: &'a L,
+    follower: &'a F,
+    batch_size: usize,
+    state: Mutex<ReplicatorState>,
+}
+
+impl<'a, L, F> WalReplicator<'a, L, F>
+where
+    L: WalSource + ?Sized,
+    F: StorageBackend + ?Sized,
+{
+    /// Replicate `leader` to `follower` from the beginning of the WAL.
+    pub fn new(leader: &'a L, follower: &'a F) -> Self {
+        Self::resume(leader, follower, ResumeToken::start())
+    }
+
+    /// Continue a replication that stopped at `token`.
+    pub fn resume(leader: &'a L, follower: &'a F, token: ResumeToken) -> Self {
+        Self {
+            leader,
+            follower,
+            batch_size: DEFAULT_REPLICATION_BATCH,
+            state: Mutex::new(ReplicatorState { token, ..ReplicatorState::default() }),
+        }
+    }
+
+    /// Fetch at most `batch_size` WAL entries per poll.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Token to [`resume`](Self::resume) replication from later.
+    pub async fn resume_token(&self) -> ResumeToken {
+        self.state.lock().await.token
+    }
+
+    /// Read the next WAL entries and apply the transactions that finished.
+    ///
+    /// Call repeatedly, e.g. on an interval, to keep the follower warm.
+    pub async fn poll(&self) -> anyhow::Result<ReplicationBatch> {
+        let mut state = self.state.lock().await;
+        let entries = self.leader.wal_entries_after(state.token.sequence, self.batch_size).await?;
+        let mut batch = ReplicationBatch {
+            entries_read: entries.len(),
+            resume_token: state.token,
+            ..ReplicationBatch::default()
+        };
+        let Some(last_read) = entries.last().map(|entry| entry.sequence) else {
+            return Ok(batch);
+        };
+
+        let mut transactions: BTreeMap<SequenceNumber, TransactionId> = BTreeMap::new();
+        let mut grouped: HashMap<TransactionId, Vec<WalEntry>> = HashMap::new();
+        for entry in entries {
+            let group = grouped.entry(entry.transaction_id).or_default();
+            if group.is_empty() {
+                transactions.insert(entry.sequence, entry.transaction_id);
+            }
+            group.push(entry);
+        }
+
+        // Everything before the first unfinished transaction can be passed
+        let mut held_back_from = None;
+        for (first_sequence, transaction_id) in transactions {
+            let entries = &grouped[&transaction_id];
+            match transaction_status(entries) {
+                TransactionStatus::Open => {
+                    held_back_from.get_or_insert(first_sequence);
+                }
+                TransactionStatus::RolledBack => batch.transactions_skipped += 1,
+                TransactionStatus::Committed => {
+                    if state.applied.contains(&transaction_id) {
+                        continue;
+                    }
+                    for (header, payload) in committed_events(entries) {
+                        self.follower.commit(header, payload).await?;
+                        batch.events_applied += 1;
+                    }
+                    batch.transactions_applied += 1;
+                    state.applied.insert(transaction_id);
+                    state.last_applied_at = Some(Utc::now());
+                }
+            }
+        }
+
+        match held_back_from {
+            Some(first_open) => {
+                state.token.sequence = state.token.sequence.max(first_open.saturating_sub(1));
+            }
+            None => {
+                state.token.sequence = last_read;
+                state.applied.clear();
+            }
+        }
+        batch.resume_token = state.token;
+        Ok(batch)
+    }
+
+    /// Poll until no finished transaction is left to apply.
+    pub async fn catch_up(&self) -> anyhow::Result<ReplicationBatch> {
+        let mut total = ReplicationBatch {
+            resume_token: self.resume_token().await,
+            ..ReplicationBatch::default()
+        };
+        loop {
+            let batch = self.poll().await?;
+            // A long open transaction can pin the token on a full batch
+            let stalled = batch.resume_token == total.resume_token && batch.transactions_applied == 0;
+            total.entries_read += batch.entries_read;
+            total.transactions_applied += batch.transactions_applied;
+            total.transactions_skipped += batch.transactions_skipped;
+            total.events_applied += batch.events_applied;
+            total.resume_token = batch.resume_token;
+            if batch.entries_read < self.batch_size || stalled {
+                return Ok(total);
+            }
+        }
+    }
+
+    /// How far the follower trails the leader.
+    pub async fn lag(&self) -> anyhow::Result<ReplicationLag> {
+        let leader_sequence = self.leader.current_sequence().await?;
+        let state = self.state.lock().await;
+        Ok(ReplicationLag {
+            leader_sequence,
+            replicated_sequence: state.token.sequence,
+            entries_behind: leader_sequence.saturating_sub(state.token.sequence),
+            last_applied_at: state.last_applied_at,
+        })
+    }
+
+    /// Check that every committed event up to the resume token exists on
+    /// the follower with the same header and payload.
+    pub async fn verify_consistency(&self) -> anyhow::Result<ConsistencyReport> {
+        let until = self.resume_token().await.sequence;
+        let mut report = ConsistencyReport::default();
+        let mut grouped: HashMap<TransactionId, Vec<WalEntry>> = HashMap::new();
+        let mut after = 0;
+        while after < until {
+            let entries = self.leader.wal_entries_after(after, self.batch_size).await?;
+            let Some(last) = entries.last().map(|entry| entry.sequence) else {
+                break;
+            };
+            for entry in entries.into_iter().filter(|entry| entry.sequence <= until) {
+                grouped.entry(entry.transaction_id).or_default().push(entry);
+            }
+            after = last;
+        }
+
+        for entries in grouped.values() {
+            if transaction_status(entries) != TransactionStatus::Committed {
+                continue;
+            }
+            for (header, payload) in committed_events(entries) {
+                report.events_checked += 1;
+                let Some(replica) = self.follower.header(&header.id).await? else {
+                    report.missing.push(header.id);
+                    continue;
+                };
+                let payload_matches = self.follower.payload_bytes(&replica.digest).await?.as_deref() == Some(payload);
+                if replica != *header || !payload_matches {
+                    report.mismatched.push(header.id);
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum TransactionStatus {
+    Open,
+    Committed,
+    RolledBack,
+}
+
+pub(crate) fn transaction_status(entries: &[WalEntry]) -> TransactionStatus {
+    let committed = entries.iter().any(|entry| {
+        matches!(entry.operation, WalOperation::CommitTransaction { .. })
+            && matches!(entry.state, WalEntryState::Committed | WalEntryState::Checkpointed)
+    });
+    if committed {
+        return TransactionStatus::Committed;
+    }
+    let rolled_back = entries
+        .iter()
+        .any(|entry| matches!(entry.operation, WalOperation::RollbackTransaction { .. }));
+    if rolled_back {
+        TransactionStatus::RolledBack
+    } else {
+        TransactionStatus::Open
+    }
+}
+
+pub(crate) fn committed_events(entries: &[WalEntry]) -> impl Iterator<Item = (&EventHeader, &[u8])> {
+    // Entries discarded by a savepoint rollback stay marked as rolled back
+    entries
+        .iter()
+        .filter(|entry| entry.state != WalEntryState::RolledBack)
+        .filter_map(|entry| match &entry.operation {
+            WalOperation::CommitEvent { header, payload } => Some((header, payload.as_ref())),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resume_token_roundtrip() {
+        let token = ResumeToken { sequence: 42 };
+        assert_eq!(token.to_string(), "wal:42");
+        assert_eq!("wal:42".parse::<ResumeToken>().unwrap(), token);
+        assert!("42".parse::<ResumeToken>().is_err());
+        assert!("wal:x".parse::<ResumeToken>().is_err());
+    }
+}
@@ -0,0 +1,208 @@
+#![forbid(unsafe_code)]
+
+//! Retention policies for bounding store growth.
+//!
+//! Event stores are append-only and grow without bound unless old events are
+//! pruned. A [`RetentionPolicy`] assigns each event kind a [`RetentionRule`]
+//! that limits how old events may get (`max_age`) and how many events of the
+//! kind are kept (`max_events`). Both limits are optional; an event is pruned
+//! as soon as it violates either one.
+//!
+//! Payloads are deduplicated by digest and may be shared between events, so
+//! backends only delete a payload once no remaining header references it.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::codec::resolve_kind;
+use crate::{EventHeader, EventId};
+
+/// Retention limits for one event kind.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionRule {
+    /// Events older than this are pruned
+    #[serde(default)]
+    pub max_age: Option<Duration>,
+    /// Only the most recent `max_events` events of the kind are kept
+    #[serde(default)]
+    pub max_events: Option<usize>,
+}
+
+impl RetentionRule {
+    /// Keep every event.
+    pub fn keep_all() -> Self {
+        Self::default()
+    }
+
+    /// Prune every event of the kind.
+    pub fn discard() -> Self {
+        Self {
+            max_age: None,
+            max_events: Some(0),
+        }
+    }
+
+    /// Prune events older than `max_age`.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Keep at most `max_events` events of the kind.
+    pub fn with_max_events(mut self, max_events: usize) -> Self {
+        self.max_events = Some(max_events);
+        self
+    }
+
+    /// Whether this rule never prunes anything.
+    pub fn is_unbounded(&self) -> bool {
+        self.max_age.is_none() && self.max_events.is_none()
+    }
+
+    /// Oldest timestamp an event may have at `now` to be retained.
+    pub fn cutoff(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let max_age = chrono::Duration::from_std(self.max_age?).ok()?;
+        now.checked_sub_signed(max_age)
+    }
+}
+
+/// Per-kind retention rules.
+///
+/// Kind keys match exactly or by `prefix.*`, following the same resolution
+/// rules as [`FormatPolicy`](crate::FormatPolicy). Count limits apply to each
+/// kind separately, even when several kinds share a `prefix.*` rule.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// Rule used when no kind rule matches
+    pub default: RetentionRule,
+    /// Per-kind rules
+    #[serde(default)]
+    pub by_kind: HashMap<String, RetentionRule>,
+}
+
+impl RetentionPolicy {
+    /// Create a policy applying `default` to every kind.
+    pub fn new(default: RetentionRule) -> Self {
+        Self {
+            default,
+            by_kind: HashMap::new(),
+        }
+    }
+
+    /// Set the rule for a kind (or a `prefix.*` pattern).
+    pub fn with_kind(mut self, kind: impl Into<String>, rule: RetentionRule) -> Self {
+        self.by_kind.insert(kind.into(), rule);
+        self
+    }
+
+    /// Resolve the rule to apply to `kind`.
+    pub fn rule_for(&self, kind: &str) -> RetentionRule {
+        resolve_kind(&self.by_kind, kind)
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// Outcome of applying a retention policy.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionReport {
+    /// Events pruned
+    pub events_removed: usize,
+    /// Payloads deleted because no remaining event referenced them
+    pub payloads_removed: usize,
+    /// Events pruned per kind
+    pub removed_by_kind: BTreeMap<String, usize>,
+}
+
+/// Decide which events violate `policy` at `now`.
+///
+/// Returns the IDs of the events to prune, oldest first.
+pub fn plan_retention(
+    headers: &[EventHeader],
+    policy: &RetentionPolicy,
+    now: DateTime<Utc>,
+) -> Vec<EventId> {
+    let mut by_kind: HashMap<&str, Vec<&EventHeader>> = HashMap::new();
+    for header in headers {
+        by_kind.entry(header.kind.as_str()).or_default().push(header);
+    }
+
+    let mut pruned: Vec<&EventHeader> = Vec::new();
+    for (kind, mut events) in by_kind {
+        let rule = policy.rule_for(kind);
+        if rule.is_unbounded() {
+            continue;
+        }
+
+        // Newest first, so the count limit keeps the most recent events
+        events.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        let cutoff = rule.cutoff(now);
+        for (rank, header) in events.into_iter().enumerate() {
+            let too_old = cutoff.is_some_and(|cutoff| header.timestamp < cutoff);
+            let over_count = rule.max_events.is_some_and(|max| rank >= max);
+            if too_old || over_count {
+                pruned.push(header);
+            }
+        }
+    }
+
+    pruned.sort_by_key(|header| header.timestamp);
+    pruned.into_iter().map(|header| header.id).collect()
+}
+
+/// Storage backends that can prune events according to a retention policy.
+#[async_trait]
+pub trait RetentionStorage: Send + Sync {
+    /// Prune every event that violates `policy` at `now`.
+    ///
+    /// Payloads are only deleted once no remaining event references them.
+    async fn apply_retention(
+        &self,
+        policy: &RetentionPolicy,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<RetentionReport>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_event_header;
+    use uuid::Uuid;
+
+    fn header_at(kind: &str, timestamp: DateTime<Utc>) -> EventHeader {
+        let mut header = create_event_header(&[], Uuid::new_v4(), kind.to_string(), &0u32).unwrap();
+        header.timestamp = timestamp;
+        header
+    }
+
+    #[test]
+    fn test_plan_retention() {
+        let now = Utc::now();
+        let hours = |h: i64| now - chrono::Duration::hours(h);
+        let headers = vec![
+            header_at("metrics.cpu", hours(48)),
+            header_at("metrics.cpu", hours(2)),
+            header_at("metrics.cpu", hours(1)),
+            header_at("task.log", hours(3)),
+            header_at("task.log", hours(2)),
+            header_at("task.log", hours(1)),
+            header_at("debug.trace", hours(1)),
+            header_at("ledger.mint", hours(1000)),
+        ];
+
+        let policy = RetentionPolicy::default()
+            .with_kind("metrics.*", RetentionRule::keep_all().with_max_age(Duration::from_secs(24 * 3600)))
+            .with_kind("task.log", RetentionRule::keep_all().with_max_events(2))
+            .with_kind("debug.trace", RetentionRule::discard());
+
+        let pruned = plan_retention(&headers, &policy, now);
+        assert_eq!(pruned, vec![headers[0].id, headers[3].id, headers[6].id]);
+
+        // The default policy keeps everything
+        assert!(plan_retention(&headers, &RetentionPolicy::default(), now).is_empty());
+    }
+}
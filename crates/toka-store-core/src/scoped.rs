@@ -0,0 +1,228 @@
+#![forbid(unsafe_code)]
+
+//! Capability-scoped, read-only access to the event store for agents.
+//!
+//! Agents should be able to inspect their own history without holding a raw
+//! [`StorageBackend`] handle, which would give them read access to every
+//! event in the system. A [`ScopedReader`] is created from an agent's
+//! capability [`Claims`] and only exposes events belonging to the intents
+//! those claims grant:
+//!
+//! - [`STORE_READ_PERMISSION`] (`store:read`) must be present, and
+//! - each readable intent is granted by a `store:read:intent:<uuid>`
+//!   permission (see [`intent_permission`]).
+//!
+//! Events outside the granted intents are filtered out of scans, and direct
+//! lookups of such events fail with [`StorageError::AccessDenied`].
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use toka_types::traits::Claims;
+use uuid::Uuid;
+
+use crate::{EventHeader, EventHeaderStream, EventId, IntentId, QueryableStorage, StorageError};
+
+/// Permission required for any scoped store read.
+pub const STORE_READ_PERMISSION: &str = "store:read";
+
+/// Prefix of permissions granting read access to a single intent.
+pub const INTENT_PERMISSION_PREFIX: &str = "store:read:intent:";
+
+/// Permission string granting read access to `intent`.
+pub fn intent_permission(intent: &IntentId) -> String {
+    format!("{}{}", INTENT_PERMISSION_PREFIX, intent)
+}
+
+/// Read-only view of a store limited to the intents granted by a token.
+pub struct ScopedReader<S: ?Sized> {
+    store: Arc<S>,
+    subject: String,
+    intents: HashSet<IntentId>,
+}
+
+impl<S: QueryableStorage + ?Sized> ScopedReader<S> {
+    /// Create a reader for the subject of `claims`.
+    ///
+    /// Fails if the claims are malformed, expired at `now`, lack
+    /// [`STORE_READ_PERMISSION`] or contain an unparsable intent grant.
+    pub fn new(store: Arc<S>, claims: &Claims, now: DateTime<Utc>) -> Result<Self, StorageError> {
+        claims
+            .validate()
+            .map_err(|e| StorageError::AccessDenied(e.to_string()))?;
+        if claims.exp <= now.timestamp().max(0) as u64 {
+            return Err(StorageError::AccessDenied(format!("token for {} has expired", claims.sub)));
+        }
+        if !claims.permissions.iter().any(|p| p == STORE_READ_PERMISSION) {
+            return Err(StorageError::AccessDenied(format!(
+                "{} lacks the {} permission",
+                claims.sub, STORE_READ_PERMISSION
+            )));
+        }
+
+        let intents = claims
+            .permissions
+            .iter()
+            .filter_map(|permission| permission.strip_prefix(INTENT_PERMISSION_PREFIX))
+            .map(|intent| {
+                Uuid::parse_str(intent).map_err(|_| {
+                    StorageError::AccessDenied(format!("malformed intent grant '{}'", intent))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            store,
+            subject: claims.sub.clone(),
+            intents,
+        })
+    }
+
+    /// Subject (agent) the reader acts for.
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// Whether events of `intent` are visible to this reader.
+    pub fn can_read(&self, intent: &IntentId) -> bool {
+        self.intents.contains(intent)
+    }
+
+    /// Fetch a header, failing if it belongs to an intent outside the scope.
+    pub async fn header(&self, id: &EventId) -> anyhow::Result<Option<EventHeader>> {
+        match self.store.header(id).await? {
+            Some(header) => {
+                self.check(&header)?;
+                Ok(Some(header))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Fetch the payload of an in-scope event.
+    ///
+    /// Payloads are looked up through their header so a digest shared with
+    /// an out-of-scope event cannot be used to read it.
+    pub async fn payload(&self, id: &EventId) -> anyhow::Result<Option<Vec<u8>>> {
+        match self.header(id).await? {
+            Some(header) => self.store.payload_bytes(&header.digest).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Stream all events of a granted intent, oldest first.
+    pub async fn events_by_intent(&self, intent: &IntentId) -> anyhow::Result<EventHeaderStream<'_>> {
+        if !self.can_read(intent) {
+            return Err(self.denied(intent).into());
+        }
+        self.store.events_by_intent(intent).await
+    }
+
+    /// Stream in-scope events with the given kind, oldest first.
+    pub async fn events_by_kind(&self, kind: &str) -> anyhow::Result<EventHeaderStream<'_>> {
+        let stream = self.store.events_by_kind(kind).await?;
+        Ok(self.filter(stream))
+    }
+
+    /// Stream in-scope events committed in `[from, to)`, oldest first.
+    pub async fn events_in_range(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> anyhow::Result<EventHeaderStream<'_>> {
+        let stream = self.store.events_in_range(from, to).await?;
+        Ok(self.filter(stream))
+    }
+
+    fn check(&self, header: &EventHeader) -> Result<(), StorageError> {
+        if self.can_read(&header.intent) {
+            Ok(())
+        } else {
+            Err(self.denied(&header.intent))
+        }
+    }
+
+    fn denied(&self, intent: &IntentId) -> StorageError {
+        StorageError::AccessDenied(format!("{} may not read intent {}", self.subject, intent))
+    }
+
+    fn filter<'a>(&'a self, stream: EventHeaderStream<'a>) -> EventHeaderStream<'a> {
+        Box::pin(stream.filter(move |item| {
+            let keep = match item {
+                Ok(header) => self.can_read(&header.intent),
+                // Errors carry no event data, so they are passed through
+                Err(_) => true,
+            };
+            futures::future::ready(keep)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(permissions: Vec<String>) -> Claims {
+        let now = Utc::now().timestamp() as u64;
+        Claims {
+            sub: "agent-1".to_string(),
+            vault: "default".to_string(),
+            permissions,
+            iat: now,
+            exp: now + 600,
+            jti: Uuid::new_v4().to_string(),
+        }
+    }
+
+    struct NoStore;
+
+    #[async_trait::async_trait]
+    impl crate::StorageBackend for NoStore {
+        async fn commit(&self, _: &EventHeader, _: &[u8]) -> anyhow::Result<()> {
+            Ok(())
+        }
+        async fn header(&self, _: &EventId) -> anyhow::Result<Option<EventHeader>> {
+            Ok(None)
+        }
+        async fn payload_bytes(&self, _: &crate::CausalDigest) -> anyhow::Result<Option<Vec<u8>>> {
+            Ok(None)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl QueryableStorage for NoStore {
+        async fn events_by_kind(&self, _: &str) -> anyhow::Result<EventHeaderStream<'_>> {
+            Ok(Box::pin(futures::stream::empty()))
+        }
+        async fn events_in_range(&self, _: DateTime<Utc>, _: DateTime<Utc>) -> anyhow::Result<EventHeaderStream<'_>> {
+            Ok(Box::pin(futures::stream::empty()))
+        }
+        async fn events_by_intent(&self, _: &IntentId) -> anyhow::Result<EventHeaderStream<'_>> {
+            Ok(Box::pin(futures::stream::empty()))
+        }
+    }
+
+    #[test]
+    fn test_scope_from_claims() {
+        let store = Arc::new(NoStore);
+        let intent = Uuid::new_v4();
+
+        let reader = ScopedReader::new(
+            store.clone(),
+            &claims(vec![STORE_READ_PERMISSION.to_string(), intent_permission(&intent)]),
+            Utc::now(),
+        )
+        .unwrap();
+        assert!(reader.can_read(&intent));
+        assert!(!reader.can_read(&Uuid::new_v4()));
+
+        // Intent grants alone are not enough
+        assert!(ScopedReader::new(store.clone(), &claims(vec![intent_permission(&intent)]), Utc::now()).is_err());
+
+        // Expired tokens are refused
+        let later = Utc::now() + chrono::Duration::hours(1);
+        assert!(ScopedReader::new(store, &claims(vec![STORE_READ_PERMISSION.to_string()]), later).is_err());
+    }
+}
@@ -0,0 +1,431 @@
+#![forbid(unsafe_code)]
+
+//! Portable snapshot archives for moving event history between stores.
+//!
+//! A snapshot is a single byte stream containing every event header, every
+//! payload (once per digest) and the store's WAL high-water mark. The format
+//! is backend-independent, so a snapshot exported from one backend can be
+//! imported into any other.
+//!
+//! # Format
+//!
+//! ```text
+//! magic "TOKASNAP" | version (u32 BE) | record* | End record
+//! record = length (u32 BE) | MessagePack-encoded SnapshotRecord
+//! ```
+//!
+//! The first record is always the [`SnapshotManifest`]. Each payload is
+//! written immediately before the first header that references it, so an
+//! importer can commit events as it reads them.
+//!
+//! [`SnapshotCheckpointExt::checkpoint_with_snapshot`] pairs an export with a
+//! WAL checkpoint and compaction, so a backup and the log truncation it makes
+//! safe happen in one step.
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use serde::{Deserialize, Serialize};
+
+use crate::replication::DEFAULT_REPLICATION_BATCH;
+use crate::{
+    CausalDigest, EventHeader, SequenceNumber, StorageBackend, StorageError, TransactionId,
+    WalCompaction, WalCompactionReport, WalOperation, WalSource,
+};
+
+/// Magic bytes at the start of every snapshot.
+pub const SNAPSHOT_MAGIC: &[u8; 8] = b"TOKASNAP";
+
+/// Current snapshot format version.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Largest record accepted when reading a snapshot (64 MiB).
+const MAX_RECORD_SIZE: usize = 64 * 1024 * 1024;
+
+/// Summary of a snapshot's contents.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    /// Format version the snapshot was written with
+    pub version: u32,
+    /// When the snapshot was taken
+    pub created_at: DateTime<Utc>,
+    /// WAL sequence number of the source store at export time
+    pub wal_sequence: SequenceNumber,
+    /// Number of events in the snapshot
+    pub events: u64,
+    /// Number of distinct payloads in the snapshot
+    pub payloads: u64,
+}
+
+/// One framed record in a snapshot stream.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum SnapshotRecord {
+    Manifest(SnapshotManifest),
+    Payload {
+        digest: CausalDigest,
+        #[serde(with = "serde_bytes_compat")]
+        bytes: Vec<u8>,
+    },
+    Header(EventHeader),
+    End,
+}
+
+/// MessagePack encodes `Vec<u8>` as an array of integers by default; store
+/// payloads as a compact binary blob instead.
+mod serde_bytes_compat {
+    use std::fmt;
+
+    use serde::de::{self, SeqAccess, Visitor};
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(bytes)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        struct BytesVisitor;
+
+        impl<'de> Visitor<'de> for BytesVisitor {
+            type Value = Vec<u8>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a byte array")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+                Ok(v.to_vec())
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+                Ok(v)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+                Ok(bytes)
+            }
+        }
+
+        deserializer.deserialize_bytes(BytesVisitor)
+    }
+}
+
+/// Streaming writer producing a snapshot archive.
+pub struct SnapshotWriter<'a> {
+    writer: &'a mut (dyn AsyncWrite + Unpin + Send),
+    written_payloads: std::collections::HashSet<CausalDigest>,
+}
+
+impl<'a> SnapshotWriter<'a> {
+    /// Write the archive preamble and `manifest`.
+    pub async fn begin(
+        writer: &'a mut (dyn AsyncWrite + Unpin + Send),
+        manifest: &SnapshotManifest,
+    ) -> anyhow::Result<Self> {
+        writer.write_all(SNAPSHOT_MAGIC).await?;
+        writer.write_all(&SNAPSHOT_VERSION.to_be_bytes()).await?;
+
+        let mut this = Self {
+            writer,
+            written_payloads: std::collections::HashSet::new(),
+        };
+        this.write_record(&SnapshotRecord::Manifest(manifest.clone())).await?;
+        Ok(this)
+    }
+
+    /// Append an event, writing its payload first if not written yet.
+    pub async fn write_event(&mut self, header: &EventHeader, payload: &[u8]) -> anyhow::Result<()> {
+        if self.written_payloads.insert(header.digest) {
+            self.write_record(&SnapshotRecord::Payload {
+                digest: header.digest,
+                bytes: payload.to_vec(),
+            })
+            .await?;
+        }
+        self.write_record(&SnapshotRecord::Header(header.clone())).await
+    }
+
+    /// Write the end marker and flush.
+    pub async fn finish(mut self) -> anyhow::Result<()> {
+        self.write_record(&SnapshotRecord::End).await?;
+        self.writer.flush().await?;
+        Ok(())
+    }
+
+    async fn write_record(&mut self, record: &SnapshotRecord) -> anyhow::Result<()> {
+        let bytes = rmp_serde::to_vec_named(record)
+            .map_err(|e| StorageError::SerializationFailed(e.to_string()))?;
+        self.writer.write_all(&(bytes.len() as u32).to_be_bytes()).await?;
+        self.writer.write_all(&bytes).await?;
+        Ok(())
+    }
+}
+
+/// Streaming reader over a snapshot archive.
+pub struct SnapshotReader<'a> {
+    reader: &'a mut (dyn AsyncRead + Unpin + Send),
+    manifest: SnapshotManifest,
+    payloads: HashMap<CausalDigest, Vec<u8>>,
+    events_read: u64,
+    finished: bool,
+}
+
+impl<'a> SnapshotReader<'a> {
+    /// Validate the preamble and read the manifest.
+    pub async fn open(reader: &'a mut (dyn AsyncRead + Unpin + Send)) -> anyhow::Result<Self> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic).await?;
+        if &magic != SNAPSHOT_MAGIC {
+            return Err(StorageError::InvalidSnapshot("not a Toka snapshot".to_string()).into());
+        }
+
+        let mut version = [0u8; 4];
+        reader.read_exact(&mut version).await?;
+        let version = u32::from_be_bytes(version);
+        if version > SNAPSHOT_VERSION {
+            return Err(StorageError::InvalidSnapshot(format!(
+                "unsupported snapshot version {} (latest supported is {})",
+                version, SNAPSHOT_VERSION
+            ))
+            .into());
+        }
+
+        let mut this = Self {
+            reader,
+            manifest: SnapshotManifest {
+                version,
+                created_at: Utc::now(),
+                wal_sequence: 0,
+                events: 0,
+                payloads: 0,
+            },
+            payloads: HashMap::new(),
+            events_read: 0,
+            finished: false,
+        };
+        match this.read_record().await? {
+            SnapshotRecord::Manifest(manifest) => this.manifest = manifest,
+            _ => {
+                return Err(StorageError::InvalidSnapshot(
+                    "snapshot does not start with a manifest".to_string(),
+                )
+                .into())
+            }
+        }
+        Ok(this)
+    }
+
+    /// The snapshot's manifest.
+    pub fn manifest(&self) -> &SnapshotManifest {
+        &self.manifest
+    }
+
+    /// Read the next event and its payload, or `None` at the end marker.
+    ///
+    /// Fails if the archive is truncated or references a payload it never
+    /// contained.
+    pub async fn next_event(&mut self) -> anyhow::Result<Option<(EventHeader, Vec<u8>)>> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        loop {
+            match self.read_record().await? {
+                SnapshotRecord::Payload { digest, bytes } => {
+                    self.payloads.insert(digest, bytes);
+                }
+                SnapshotRecord::Header(header) => {
+                    let payload = self.payloads.get(&header.digest).cloned().ok_or_else(|| {
+                        StorageError::InvalidSnapshot(format!(
+                            "snapshot event {} references a missing payload",
+                            header.id
+                        ))
+                    })?;
+                    self.events_read += 1;
+                    return Ok(Some((header, payload)));
+                }
+                SnapshotRecord::End => {
+                    self.finished = true;
+                    if self.events_read != self.manifest.events {
+                        return Err(StorageError::InvalidSnapshot(format!(
+                            "snapshot manifest lists {} events but {} were read",
+                            self.manifest.events, self.events_read
+                        ))
+                        .into());
+                    }
+                    return Ok(None);
+                }
+                SnapshotRecord::Manifest(_) => {
+                    return Err(StorageError::InvalidSnapshot(
+                        "unexpected manifest inside snapshot".to_string(),
+                    )
+                    .into())
+                }
+            }
+        }
+    }
+
+    async fn read_record(&mut self) -> anyhow::Result<SnapshotRecord> {
+        let mut len = [0u8; 4];
+        self.reader.read_exact(&mut len).await?;
+        let len = u32::from_be_bytes(len) as usize;
+        if len > MAX_RECORD_SIZE {
+            return Err(StorageError::InvalidSnapshot(format!(
+                "snapshot record of {} bytes exceeds the {} byte limit",
+                len, MAX_RECORD_SIZE
+            ))
+            .into());
+        }
+
+        let mut bytes = vec![0u8; len];
+        self.reader.read_exact(&mut bytes).await?;
+        rmp_serde::from_slice(&bytes)
+            .map_err(|e| StorageError::SerializationFailed(e.to_string()).into())
+    }
+}
+
+/// Storage backends that can export and import portable snapshots.
+#[async_trait]
+pub trait SnapshotStorage: StorageBackend {
+    /// Write every event, payload and the WAL high-water mark to `writer`.
+    async fn export_snapshot(
+        &self,
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+    ) -> anyhow::Result<SnapshotManifest>;
+
+    /// Commit every event in the snapshot read from `reader`.
+    ///
+    /// Events already present are overwritten with the snapshot's copy, and
+    /// the WAL sequence is advanced to at least the snapshot's high-water
+    /// mark so new transactions never reuse sequence numbers.
+    async fn import_snapshot(
+        &self,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+    ) -> anyhow::Result<SnapshotManifest>;
+}
+
+/// Result of [`SnapshotCheckpointExt::checkpoint_with_snapshot`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotCheckpoint {
+    /// Manifest of the exported snapshot
+    pub manifest: SnapshotManifest,
+    /// Last sequence number checkpointed; every transaction finished by then
+    /// is contained in the snapshot
+    pub checkpointed_through: SequenceNumber,
+    /// WAL entries removed after the checkpoint
+    pub compaction: WalCompactionReport,
+}
+
+/// Backup-and-truncate workflow combining snapshots with WAL checkpoints.
+#[async_trait]
+pub trait SnapshotCheckpointExt: SnapshotStorage + WalCompaction + WalSource {
+    /// Export a snapshot to `writer`, then checkpoint and compact the WAL up
+    /// to the point the snapshot covers.
+    ///
+    /// The checkpoint stops before the first transaction that was still open
+    /// when the export started, since its events may be missing from the
+    /// snapshot. Nothing is checkpointed if the export fails, so a failed
+    /// backup never loses log entries.
+    async fn checkpoint_with_snapshot(
+        &self,
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+    ) -> anyhow::Result<SnapshotCheckpoint> {
+        let bound = self.current_sequence().await?;
+        let manifest = self.export_snapshot(writer).await?;
+        writer.flush().await?;
+
+        // First sequence number of each transaction unfinished at `bound`
+        let mut open: HashMap<TransactionId, SequenceNumber> = HashMap::new();
+        let mut after = 0;
+        'scan: loop {
+            let entries = self.wal_entries_after(after, DEFAULT_REPLICATION_BATCH).await?;
+            let Some(last) = entries.last() else {
+                break;
+            };
+            after = last.sequence;
+            for entry in entries {
+                if entry.sequence > bound {
+                    break 'scan;
+                }
+                match entry.operation {
+                    WalOperation::CommitTransaction { transaction_id }
+                    | WalOperation::RollbackTransaction { transaction_id } => {
+                        open.remove(&transaction_id);
+                    }
+                    _ => {
+                        open.entry(entry.transaction_id).or_insert(entry.sequence);
+                    }
+                }
+            }
+        }
+
+        let checkpointed_through = open
+            .values()
+            .min()
+            .map_or(bound, |first| bound.min(first.saturating_sub(1)));
+        self.checkpoint(checkpointed_through).await?;
+        let compaction = self.compact_wal(checkpointed_through + 1).await?;
+        Ok(SnapshotCheckpoint {
+            manifest,
+            checkpointed_through,
+            compaction,
+        })
+    }
+}
+
+impl<T> SnapshotCheckpointExt for T where T: SnapshotStorage + WalCompaction + WalSource + ?Sized {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_event_header;
+    use futures::executor::block_on;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        block_on(async {
+            let intent = Uuid::new_v4();
+            let first = create_event_header(&[], intent, "test.event".to_string(), &1u32).unwrap();
+            let second = create_event_header(&[], intent, "test.event".to_string(), &1u32).unwrap();
+            let payload = rmp_serde::to_vec_named(&1u32).unwrap();
+
+            let manifest = SnapshotManifest {
+                version: SNAPSHOT_VERSION,
+                created_at: Utc::now(),
+                wal_sequence: 7,
+                events: 2,
+                payloads: 1,
+            };
+
+            let mut buffer = Vec::new();
+            {
+                let mut cursor = futures::io::Cursor::new(&mut buffer);
+                let mut writer = SnapshotWriter::begin(&mut cursor, &manifest).await.unwrap();
+                writer.write_event(&first, &payload).await.unwrap();
+                writer.write_event(&second, &payload).await.unwrap();
+                writer.finish().await.unwrap();
+            }
+
+            let mut cursor = futures::io::Cursor::new(buffer);
+            let mut reader = SnapshotReader::open(&mut cursor).await.unwrap();
+            assert_eq!(reader.manifest(), &manifest);
+            assert_eq!(reader.next_event().await.unwrap(), Some((first, payload.clone())));
+            assert_eq!(reader.next_event().await.unwrap(), Some((second, payload)));
+            assert_eq!(reader.next_event().await.unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn test_rejects_foreign_data() {
+        block_on(async {
+            let mut cursor = futures::io::Cursor::new(b"NOTASNAPSHOT....".to_vec());
+            assert!(SnapshotReader::open(&mut cursor).await.is_err());
+        });
+    }
+}
@@ -0,0 +1,73 @@
+#![forbid(unsafe_code)]
+
+//! Backend-independent storage statistics.
+//!
+//! Every driver used to expose its own `event_count()`/`payload_count()`
+//! helpers with slightly different signatures. [`StorageStats`] gives
+//! dashboards and health checks one call that works against any backend.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::StorageBackend;
+
+/// Point-in-time statistics of a storage backend.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageStatistics {
+    /// Number of stored event headers
+    pub event_count: u64,
+    /// Number of distinct payloads (after deduplication)
+    pub payload_count: u64,
+    /// Bytes used by the backend.
+    ///
+    /// Persistent backends report their on-disk size; in-memory backends
+    /// report the size of stored payloads.
+    pub bytes_used: u64,
+    /// Timestamp of the oldest stored event
+    pub oldest_event: Option<DateTime<Utc>>,
+    /// Timestamp of the newest stored event
+    pub newest_event: Option<DateTime<Utc>>,
+    /// Number of WAL entries currently retained (0 for backends without a WAL)
+    pub wal_depth: u64,
+    /// Number of WAL transactions currently open
+    pub open_transactions: u64,
+    /// Number of WAL transactions rolled back because they timed out
+    pub transactions_timed_out: u64,
+}
+
+impl StorageStatistics {
+    /// Average number of events sharing each payload.
+    pub fn dedup_ratio(&self) -> f64 {
+        if self.payload_count == 0 {
+            return 0.0;
+        }
+        self.event_count as f64 / self.payload_count as f64
+    }
+}
+
+/// Storage backends that can report [`StorageStatistics`].
+#[async_trait]
+pub trait StorageStats: StorageBackend {
+    /// Collect current statistics.
+    ///
+    /// Depending on the backend this may scan indexes, so it is intended
+    /// for periodic monitoring rather than hot paths.
+    async fn storage_stats(&self) -> anyhow::Result<StorageStatistics>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dedup_ratio() {
+        let stats = StorageStatistics {
+            event_count: 6,
+            payload_count: 4,
+            ..StorageStatistics::default()
+        };
+        assert_eq!(stats.dedup_ratio(), 1.5);
+        assert_eq!(StorageStatistics::default().dedup_ratio(), 0.0);
+    }
+}
@@ -0,0 +1,217 @@
+#![forbid(unsafe_code)]
+
+//! Typed event subscriptions.
+//!
+//! Header streams only carry metadata, so every consumer that wants the
+//! actual events ends up filtering by kind, fetching each payload and
+//! decoding it by hand. [`TypedSubscriptionExt::subscribe_typed`] does that
+//! join once and yields [`DecodedEvent`]s.
+//!
+//! A payload that is missing or fails to decode does not end the stream: it
+//! is reported as a [`TypedStreamError`] carrying the offending header, and
+//! the next event is delivered as usual. Consumers decide whether to log,
+//! dead-letter or stop.
+
+use std::pin::Pin;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use futures::{Stream, StreamExt};
+
+use crate::{
+    decode_payload, EventHeader, EventHeaderStream, EventPayload, ReplayFrom, ReplayableStorage,
+    StorageBackend, StorageError,
+};
+
+/// An event header together with its decoded payload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedEvent<P> {
+    /// Header of the event
+    pub header: EventHeader,
+    /// Decoded payload
+    pub payload: P,
+}
+
+/// Failure to deliver one event of a typed subscription.
+#[derive(Debug, thiserror::Error)]
+pub enum TypedStreamError {
+    /// The underlying header stream yielded an error
+    #[error("subscription failed: {0}")]
+    Stream(anyhow::Error),
+    /// Fetching the payload failed
+    #[error("failed to fetch payload for event {}: {source}", header.id)]
+    Fetch {
+        /// Header of the affected event
+        header: EventHeader,
+        /// Backend error
+        source: anyhow::Error,
+    },
+    /// The payload is not in the store
+    #[error("payload for event {} is missing", header.id)]
+    MissingPayload {
+        /// Header of the affected event
+        header: EventHeader,
+    },
+    /// The payload could not be decoded into the requested type
+    #[error("failed to decode event {}: {source}", header.id)]
+    Decode {
+        /// Header of the affected event
+        header: EventHeader,
+        /// Decoding error
+        source: StorageError,
+    },
+}
+
+impl TypedStreamError {
+    /// Header of the affected event, if the failure concerns one.
+    pub fn header(&self) -> Option<&EventHeader> {
+        match self {
+            TypedStreamError::Stream(_) => None,
+            TypedStreamError::Fetch { header, .. }
+            | TypedStreamError::MissingPayload { header }
+            | TypedStreamError::Decode { header, .. } => Some(header),
+        }
+    }
+}
+
+/// Boxed stream of decoded events produced by typed subscriptions.
+pub type TypedEventStream<'a, P> =
+    Pin<Box<dyn Stream<Item = Result<DecodedEvent<P>, TypedStreamError>> + Send + 'a>>;
+
+/// Keep the events of `kind` in `headers` and decode their payloads.
+///
+/// Payloads are fetched from `backend` one event at a time, in stream order.
+pub fn decode_events<'a, B, P>(
+    backend: &'a B,
+    headers: EventHeaderStream<'a>,
+    kind: &str,
+) -> TypedEventStream<'a, P>
+where
+    B: StorageBackend + ?Sized,
+    P: EventPayload + 'a,
+{
+    let kind = kind.to_string();
+    Box::pin(
+        headers
+            .filter(move |item| {
+                let keep = match item {
+                    Ok(header) => header.kind == kind,
+                    Err(_) => true,
+                };
+                futures::future::ready(keep)
+            })
+            .then(move |item| async move {
+                let header = item.map_err(TypedStreamError::Stream)?;
+                let bytes = match backend.payload_bytes(&header.digest).await {
+                    Ok(Some(bytes)) => bytes,
+                    Ok(None) => return Err(TypedStreamError::MissingPayload { header }),
+                    Err(source) => return Err(TypedStreamError::Fetch { header, source }),
+                };
+                match decode_payload(&header, &bytes) {
+                    Ok(payload) => Ok(DecodedEvent { header, payload }),
+                    Err(source) => Err(TypedStreamError::Decode { header, source }),
+                }
+            }),
+    )
+}
+
+/// Typed subscriptions for any [`ReplayableStorage`].
+#[async_trait]
+pub trait TypedSubscriptionExt: ReplayableStorage {
+    /// Follow events of `kind` committed from now on, decoded as `P`.
+    async fn subscribe_typed<P: EventPayload + 'static>(
+        &self,
+        kind: &str,
+    ) -> anyhow::Result<TypedEventStream<'_, P>> {
+        self.subscribe_typed_from(kind, ReplayFrom::Timestamp(Utc::now())).await
+    }
+
+    /// Replay events of `kind` from `from`, then follow live ones, decoded as `P`.
+    async fn subscribe_typed_from<P: EventPayload + 'static>(
+        &self,
+        kind: &str,
+        from: ReplayFrom,
+    ) -> anyhow::Result<TypedEventStream<'_, P>> {
+        let headers = self.subscribe_from(from).await?;
+        Ok(decode_events(self, headers, kind))
+    }
+}
+
+impl<T> TypedSubscriptionExt for T where T: ReplayableStorage + ?Sized {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+
+    use crate::{create_event_header, CausalDigest, EventId};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct TaskStarted {
+        task: String,
+    }
+
+    #[derive(Default)]
+    struct PayloadStore {
+        payloads: Mutex<HashMap<CausalDigest, Vec<u8>>>,
+    }
+
+    #[async_trait]
+    impl StorageBackend for PayloadStore {
+        async fn commit(&self, header: &EventHeader, payload: &[u8]) -> anyhow::Result<()> {
+            self.payloads.lock().unwrap().insert(header.digest, payload.to_vec());
+            Ok(())
+        }
+        async fn header(&self, _: &EventId) -> anyhow::Result<Option<EventHeader>> {
+            Ok(None)
+        }
+        async fn payload_bytes(&self, digest: &CausalDigest) -> anyhow::Result<Option<Vec<u8>>> {
+            Ok(self.payloads.lock().unwrap().get(digest).cloned())
+        }
+    }
+
+    fn event<P: EventPayload>(kind: &str, payload: &P) -> (EventHeader, Vec<u8>) {
+        let header = create_event_header(&[], Uuid::new_v4(), kind.to_string(), payload).unwrap();
+        (header, rmp_serde::to_vec_named(payload).unwrap())
+    }
+
+    #[test]
+    fn test_decode_events_filters_and_reports_failures() {
+        futures::executor::block_on(async {
+            let store = PayloadStore::default();
+            let (started, bytes) = event("task.started", &TaskStarted { task: "build".to_string() });
+            store.commit(&started, &bytes).await.unwrap();
+            let (other, bytes) = event("task.finished", &42u32);
+            store.commit(&other, &bytes).await.unwrap();
+            let (malformed, bytes) = event("task.started", &42u32);
+            store.commit(&malformed, &bytes).await.unwrap();
+            let (missing, _) = event("task.started", &TaskStarted { task: "lost".to_string() });
+
+            let headers: EventHeaderStream<'_> = Box::pin(futures::stream::iter(vec![
+                Ok(started.clone()),
+                Ok(other),
+                Ok(malformed.clone()),
+                Err(anyhow::anyhow!("corrupt row")),
+                Ok(missing.clone()),
+            ]));
+            let items: Vec<_> = decode_events::<_, TaskStarted>(&store, headers, "task.started")
+                .collect()
+                .await;
+            assert_eq!(items.len(), 4);
+
+            let decoded = items[0].as_ref().unwrap();
+            assert_eq!(decoded.header, started);
+            assert_eq!(decoded.payload.task, "build");
+
+            // Failures carry the header and do not end the stream
+            assert!(matches!(&items[1], Err(TypedStreamError::Decode { .. })));
+            assert_eq!(items[1].as_ref().unwrap_err().header(), Some(&malformed));
+            assert!(matches!(&items[2], Err(TypedStreamError::Stream(_))));
+            assert!(matches!(&items[3], Err(TypedStreamError::MissingPayload { header }) if header.id == missing.id));
+        });
+    }
+}
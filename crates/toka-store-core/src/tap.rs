@@ -0,0 +1,417 @@
+#![forbid(unsafe_code)]
+
+//! Persisting kernel bus events into a store.
+//!
+//! The event bus is in-memory only, so anything published on it is gone once
+//! subscribers have seen it. A [`BusTap`] subscribes to an [`EventBus`] and
+//! commits the events selected by its [`TapConfig`] to a [`StorageBackend`],
+//! giving deployments durable telemetry without custom glue:
+//!
+//! ```rust,ignore
+//! let tap = BusTap::new(store, TapConfig::new().capture("task").capture_sampled("resource", 100));
+//! tokio::spawn(async move { tap.run(bus.as_ref()).await });
+//! ```
+//!
+//! Events are selected by family, the first segment of
+//! [`KernelEvent::kind`], or by full kind. High-volume families can be
+//! sampled, and events whose encoded size exceeds the configured limit are
+//! skipped rather than bloating the store. Stored events use the kind
+//! `<prefix>.<kernel kind>` (`bus.task.completed` by default) and share one
+//! intent per tap, so a tap's output can be scanned as a unit. Payloads are
+//! encoded in the format the configured [`FormatPolicy`] picks for the stored
+//! kind (MessagePack unless configured otherwise).
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+use toka_bus_core::{EventBus, KernelEvent};
+use uuid::Uuid;
+
+use crate::{FormatPolicy, IntentId, StorageBackend};
+
+/// Default kind prefix of persisted bus events.
+pub const DEFAULT_TAP_PREFIX: &str = "bus";
+
+/// Default maximum encoded size of a persisted event.
+pub const DEFAULT_TAP_MAX_EVENT_BYTES: usize = 64 * 1024;
+
+/// Selection rule for one event family or kind.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TapRule {
+    /// Family (`task`), full kind (`task.completed`) or `*` for every event
+    pub selector: String,
+    /// Persist one in every `sample_every` matching events (1 keeps all)
+    pub sample_every: u64,
+    /// Size limit overriding [`TapConfig::max_event_bytes`]
+    pub max_event_bytes: Option<usize>,
+}
+
+impl TapRule {
+    /// Whether the rule selects events of `kind`.
+    pub fn matches(&self, kind: &str) -> bool {
+        self.selector == "*"
+            || kind == self.selector
+            || kind
+                .strip_prefix(self.selector.as_str())
+                .is_some_and(|rest| rest.starts_with('.'))
+    }
+}
+
+/// Which bus events a [`BusTap`] persists.
+///
+/// Rules are checked in order and the first match decides; events matching
+/// no rule are ignored. The default configuration persists every event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TapConfig {
+    /// Selection rules
+    pub rules: Vec<TapRule>,
+    /// Maximum encoded size of a persisted event in bytes
+    pub max_event_bytes: usize,
+    /// Prefix of the stored event kinds
+    pub kind_prefix: String,
+    /// Payload format per stored kind
+    #[serde(default)]
+    pub format: FormatPolicy,
+}
+
+impl Default for TapConfig {
+    fn default() -> Self {
+        Self::new().capture("*")
+    }
+}
+
+impl TapConfig {
+    /// Configuration without rules; add some with [`capture`](Self::capture).
+    pub fn new() -> Self {
+        Self {
+            rules: Vec::new(),
+            max_event_bytes: DEFAULT_TAP_MAX_EVENT_BYTES,
+            kind_prefix: DEFAULT_TAP_PREFIX.to_string(),
+            format: FormatPolicy::default(),
+        }
+    }
+
+    /// Persist every event matching `selector`.
+    pub fn capture(self, selector: impl Into<String>) -> Self {
+        self.capture_sampled(selector, 1)
+    }
+
+    /// Persist one in every `sample_every` events matching `selector`.
+    pub fn capture_sampled(mut self, selector: impl Into<String>, sample_every: u64) -> Self {
+        self.rules.push(TapRule {
+            selector: selector.into(),
+            sample_every: sample_every.max(1),
+            max_event_bytes: None,
+        });
+        self
+    }
+
+    /// Add a fully specified rule.
+    pub fn with_rule(mut self, rule: TapRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Skip events whose encoded size exceeds `bytes`.
+    pub fn with_max_event_bytes(mut self, bytes: usize) -> Self {
+        self.max_event_bytes = bytes;
+        self
+    }
+
+    /// Store events under `<prefix>.<kernel kind>`.
+    pub fn with_kind_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.kind_prefix = prefix.into();
+        self
+    }
+
+    /// Encode payloads in the formats chosen by `policy`.
+    ///
+    /// The policy is keyed by the stored kind, including the prefix.
+    pub fn with_format(mut self, policy: FormatPolicy) -> Self {
+        self.format = policy;
+        self
+    }
+}
+
+/// What the tap did with one event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TapDecision {
+    /// The event was committed to the store
+    Persisted,
+    /// No rule selects the event
+    Unselected,
+    /// The event was dropped by sampling
+    SampledOut,
+    /// The encoded event exceeded the size limit
+    Oversized,
+}
+
+/// Counters describing a tap's activity.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TapStats {
+    /// Events received from the bus
+    pub received: u64,
+    /// Events committed to the store
+    pub persisted: u64,
+    /// Events no rule selected
+    pub unselected: u64,
+    /// Events dropped by sampling
+    pub sampled_out: u64,
+    /// Events skipped for exceeding the size limit
+    pub oversized: u64,
+    /// Events missed because the tap fell behind the bus
+    pub lagged: u64,
+    /// Events that could not be encoded or committed
+    pub failed: u64,
+}
+
+#[derive(Debug, Default)]
+struct TapCounters {
+    received: AtomicU64,
+    persisted: AtomicU64,
+    unselected: AtomicU64,
+    sampled_out: AtomicU64,
+    oversized: AtomicU64,
+    lagged: AtomicU64,
+    failed: AtomicU64,
+}
+
+/// Subscribes to an event bus and persists selected events.
+pub struct BusTap<S: ?Sized> {
+    store: Arc<S>,
+    config: TapConfig,
+    intent: IntentId,
+    matched: Vec<AtomicU64>,
+    counters: TapCounters,
+}
+
+impl<S: StorageBackend + ?Sized> BusTap<S> {
+    /// Create a tap writing to `store`.
+    pub fn new(store: Arc<S>, config: TapConfig) -> Self {
+        let matched = config.rules.iter().map(|_| AtomicU64::new(0)).collect();
+        Self {
+            store,
+            config,
+            intent: Uuid::new_v4(),
+            matched,
+            counters: TapCounters::default(),
+        }
+    }
+
+    /// Intent shared by every event this tap persists.
+    pub fn intent(&self) -> IntentId {
+        self.intent
+    }
+
+    /// The tap configuration.
+    pub fn config(&self) -> &TapConfig {
+        &self.config
+    }
+
+    /// Snapshot of the tap's counters.
+    pub fn stats(&self) -> TapStats {
+        let c = &self.counters;
+        TapStats {
+            received: c.received.load(Ordering::Relaxed),
+            persisted: c.persisted.load(Ordering::Relaxed),
+            unselected: c.unselected.load(Ordering::Relaxed),
+            sampled_out: c.sampled_out.load(Ordering::Relaxed),
+            oversized: c.oversized.load(Ordering::Relaxed),
+            lagged: c.lagged.load(Ordering::Relaxed),
+            failed: c.failed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Apply the configuration to one event, committing it if selected.
+    pub async fn record(&self, event: &KernelEvent) -> anyhow::Result<TapDecision> {
+        self.counters.received.fetch_add(1, Ordering::Relaxed);
+        let decision = self.persist(event).await;
+        let counter = match &decision {
+            Ok(TapDecision::Persisted) => &self.counters.persisted,
+            Ok(TapDecision::Unselected) => &self.counters.unselected,
+            Ok(TapDecision::SampledOut) => &self.counters.sampled_out,
+            Ok(TapDecision::Oversized) => &self.counters.oversized,
+            Err(_) => &self.counters.failed,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        decision
+    }
+
+    async fn persist(&self, event: &KernelEvent) -> anyhow::Result<TapDecision> {
+        let kind = event.kind();
+        let Some((index, rule)) = self
+            .config
+            .rules
+            .iter()
+            .enumerate()
+            .find(|(_, rule)| rule.matches(kind))
+        else {
+            return Ok(TapDecision::Unselected);
+        };
+
+        let seen = self.matched[index].fetch_add(1, Ordering::Relaxed);
+        if seen % rule.sample_every != 0 {
+            return Ok(TapDecision::SampledOut);
+        }
+
+        let (header, payload) = self.config.format.create_event_header(
+            &[],
+            self.intent,
+            format!("{}.{}", self.config.kind_prefix, kind),
+            event,
+        )?;
+        if payload.len() > rule.max_event_bytes.unwrap_or(self.config.max_event_bytes) {
+            return Ok(TapDecision::Oversized);
+        }
+
+        self.store.commit(&header, &payload).await?;
+        Ok(TapDecision::Persisted)
+    }
+
+    /// Persist events from `bus` until it shuts down.
+    ///
+    /// Commit failures are counted and do not stop the tap. Events missed
+    /// because the tap fell behind are counted as lagged.
+    pub async fn run(&self, bus: &dyn EventBus) {
+        self.run_from(bus.subscribe()).await
+    }
+
+    /// Persist events from an existing subscription until the bus shuts down.
+    ///
+    /// Subscribe before spawning the tap to capture events published while
+    /// the task is starting.
+    pub async fn run_from(&self, mut events: Receiver<KernelEvent>) {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    // Failures are reflected in the counters
+                    let _ = self.record(&event).await;
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    self.counters.lagged.fetch_add(missed, Ordering::Relaxed);
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    use chrono::Utc;
+    use toka_types::{EntityId, TaskSpec};
+
+    use crate::{CausalDigest, EventHeader, EventId, PayloadFormat};
+
+    #[derive(Default)]
+    struct RecordingStore {
+        headers: Mutex<Vec<EventHeader>>,
+    }
+
+    #[async_trait::async_trait]
+    impl StorageBackend for RecordingStore {
+        async fn commit(&self, header: &EventHeader, _: &[u8]) -> anyhow::Result<()> {
+            self.headers.lock().unwrap().push(header.clone());
+            Ok(())
+        }
+        async fn header(&self, _: &EventId) -> anyhow::Result<Option<EventHeader>> {
+            Ok(None)
+        }
+        async fn payload_bytes(&self, _: &CausalDigest) -> anyhow::Result<Option<Vec<u8>>> {
+            Ok(None)
+        }
+    }
+
+    fn scheduled(description: &str) -> KernelEvent {
+        KernelEvent::TaskScheduled {
+            agent: EntityId(1),
+            task: TaskSpec {
+                description: description.to_string(),
+                correlation_id: None,
+            },
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn cpu() -> KernelEvent {
+        KernelEvent::CPUUtilization {
+            agent: EntityId(1),
+            cpu_percent: 12.5,
+            duration_ms: 1000,
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_rule_matching() {
+        let config = TapConfig::new().capture("task").capture("resource.cpu");
+        assert!(config.rules[0].matches("task.completed"));
+        assert!(!config.rules[0].matches("tasks.completed"));
+        assert!(config.rules[1].matches("resource.cpu"));
+        assert!(!config.rules[1].matches("resource.io"));
+        assert!(TapConfig::default().rules[0].matches("report.generated"));
+    }
+
+    #[test]
+    fn test_tap_selection_sampling_and_size_guard() {
+        futures::executor::block_on(async {
+            let store = Arc::new(RecordingStore::default());
+            let tap = BusTap::new(
+                store.clone(),
+                TapConfig::new()
+                    .capture("task")
+                    .capture_sampled("resource", 3)
+                    .with_max_event_bytes(512),
+            );
+
+            assert_eq!(tap.record(&scheduled("build")).await.unwrap(), TapDecision::Persisted);
+            assert_eq!(tap.record(&scheduled(&"x".repeat(1024))).await.unwrap(), TapDecision::Oversized);
+            for expected in [TapDecision::Persisted, TapDecision::SampledOut, TapDecision::SampledOut, TapDecision::Persisted] {
+                assert_eq!(tap.record(&cpu()).await.unwrap(), expected);
+            }
+            let report = KernelEvent::ReportGenerated {
+                report_id: "r1".to_string(),
+                agent: None,
+                workstream: "core".to_string(),
+                content: String::new(),
+                timestamp: Utc::now(),
+            };
+            assert_eq!(tap.record(&report).await.unwrap(), TapDecision::Unselected);
+
+            let headers = store.headers.lock().unwrap();
+            let kinds: Vec<_> = headers.iter().map(|h| h.kind.as_str()).collect();
+            assert_eq!(kinds, ["bus.task.scheduled", "bus.resource.cpu", "bus.resource.cpu"]);
+            assert!(headers.iter().all(|h| h.intent == tap.intent()));
+
+            let stats = tap.stats();
+            assert_eq!(stats.received, 7);
+            assert_eq!(stats.persisted, 3);
+            assert_eq!(stats.sampled_out, 2);
+            assert_eq!(stats.oversized, 1);
+            assert_eq!(stats.unselected, 1);
+        });
+    }
+
+    #[test]
+    fn test_tap_applies_format_policy() {
+        futures::executor::block_on(async {
+            let store = Arc::new(RecordingStore::default());
+            let tap = BusTap::new(
+                store.clone(),
+                TapConfig::default().with_format(FormatPolicy::default().with_kind("bus.resource.*", PayloadFormat::Json)),
+            );
+
+            tap.record(&scheduled("build")).await.unwrap();
+            tap.record(&cpu()).await.unwrap();
+
+            let formats: Vec<_> = store.headers.lock().unwrap().iter().map(|h| h.format).collect();
+            assert_eq!(formats, [PayloadFormat::MessagePack, PayloadFormat::Json]);
+        });
+    }
+}
@@ -0,0 +1,139 @@
+#![forbid(unsafe_code)]
+
+//! Pruning of finished WAL entries.
+//!
+//! [`WriteAheadLog::checkpoint`] only marks entries as checkpointed, so the
+//! log keeps growing. Backends implementing [`WalCompaction`] can delete
+//! entries that recovery will never need again: those of transactions whose
+//! entries are all checkpointed or rolled back.
+//!
+//! Transactions are removed as a whole, so recovery never sees a transaction
+//! with some of its entries missing. Entries that are pending or committed
+//! but not yet checkpointed are always kept.
+
+use std::collections::{HashMap, HashSet};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{SequenceNumber, TransactionId, WalEntry, WalEntryState, WriteAheadLog};
+
+/// When a backend compacts its WAL on its own.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WalCompactionPolicy {
+    /// Only compact on explicit [`WalCompaction::compact_wal`] calls
+    #[default]
+    Manual,
+    /// Compact after every checkpoint, keeping the most recent `retain`
+    /// sequence numbers for inspection
+    OnCheckpoint {
+        /// Number of sequence numbers before the checkpoint to keep
+        retain: u64,
+    },
+}
+
+impl WalCompactionPolicy {
+    /// Bound to pass to [`WalCompaction::compact_wal`] after a checkpoint at
+    /// `checkpoint`, or `None` if the policy does not compact.
+    pub fn compaction_bound(&self, checkpoint: SequenceNumber) -> Option<SequenceNumber> {
+        match self {
+            WalCompactionPolicy::Manual => None,
+            WalCompactionPolicy::OnCheckpoint { retain } => Some((checkpoint + 1).saturating_sub(*retain)),
+        }
+    }
+}
+
+/// Rows reclaimed by WAL compaction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalCompactionReport {
+    /// Transactions whose entries were removed
+    pub transactions_removed: u64,
+    /// Checkpointed entries removed
+    pub checkpointed_entries: u64,
+    /// Rolled back entries removed
+    pub rolled_back_entries: u64,
+}
+
+impl WalCompactionReport {
+    /// Total number of entries removed.
+    pub fn entries_removed(&self) -> u64 {
+        self.checkpointed_entries + self.rolled_back_entries
+    }
+
+    /// Add the counts of `other` to this report.
+    pub fn absorb(&mut self, other: &WalCompactionReport) {
+        self.transactions_removed += other.transactions_removed;
+        self.checkpointed_entries += other.checkpointed_entries;
+        self.rolled_back_entries += other.rolled_back_entries;
+    }
+}
+
+/// Write-ahead logs that can delete finished entries.
+#[async_trait]
+pub trait WalCompaction: WriteAheadLog {
+    /// Remove the entries of finished transactions that end before `before`.
+    ///
+    /// A transaction is finished once every entry is checkpointed or rolled
+    /// back. Transactions with a later entry are kept entirely.
+    async fn compact_wal(&self, before: SequenceNumber) -> anyhow::Result<WalCompactionReport>;
+
+    /// Rows reclaimed by all compactions since the backend was opened.
+    fn wal_compaction_totals(&self) -> WalCompactionReport;
+}
+
+/// Transactions among `entries` that [`WalCompaction::compact_wal`] may
+/// remove for the bound `before`.
+pub fn compactable_transactions<'a>(
+    entries: impl IntoIterator<Item = &'a WalEntry>,
+    before: SequenceNumber,
+) -> HashSet<TransactionId> {
+    let mut finished: HashMap<TransactionId, bool> = HashMap::new();
+    for entry in entries {
+        let removable = entry.sequence < before
+            && matches!(entry.state, WalEntryState::Checkpointed | WalEntryState::RolledBack);
+        *finished.entry(entry.transaction_id).or_insert(true) &= removable;
+    }
+    finished
+        .into_iter()
+        .filter_map(|(transaction_id, removable)| removable.then_some(transaction_id))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WalOperation;
+    use uuid::Uuid;
+
+    fn entry(transaction_id: TransactionId, sequence: SequenceNumber, state: WalEntryState) -> WalEntry {
+        WalEntry {
+            id: Uuid::new_v4(),
+            transaction_id,
+            sequence,
+            timestamp: chrono::Utc::now(),
+            operation: WalOperation::BeginTransaction { transaction_id },
+            state,
+        }
+    }
+
+    #[test]
+    fn test_only_finished_transactions_are_compactable() {
+        let (checkpointed, rolled_back, committed, straddling) =
+            (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let entries = vec![
+            entry(checkpointed, 1, WalEntryState::Checkpointed),
+            entry(checkpointed, 2, WalEntryState::Checkpointed),
+            entry(rolled_back, 3, WalEntryState::RolledBack),
+            entry(committed, 4, WalEntryState::Committed),
+            entry(straddling, 5, WalEntryState::Checkpointed),
+            entry(straddling, 9, WalEntryState::Checkpointed),
+        ];
+
+        let removable = compactable_transactions(&entries, 8);
+        assert_eq!(removable, HashSet::from([checkpointed, rolled_back]));
+
+        assert_eq!(WalCompactionPolicy::Manual.compaction_bound(10), None);
+        assert_eq!(WalCompactionPolicy::OnCheckpoint { retain: 0 }.compaction_bound(10), Some(11));
+        assert_eq!(WalCompactionPolicy::OnCheckpoint { retain: 20 }.compaction_bound(10), Some(0));
+    }
+}
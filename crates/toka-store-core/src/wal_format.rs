@@ -0,0 +1,202 @@
+#![forbid(unsafe_code)]
+
+//! Versioned on-disk encoding of WAL operations.
+//!
+//! Backends that persist [`WalOperation`]s as bytes encode them with
+//! [`encode_wal_operation`], which prefixes the MessagePack body with a magic
+//! marker and the format version. [`decode_wal_operation`] reads any version
+//! up to [`WAL_FORMAT_VERSION`], upgrading older bodies one version at a time
+//! through the translators in [`UPGRADES`].
+//!
+//! Entries that cannot be decoded, because they are corrupt or were written
+//! by a newer release, are never dropped: backends move them to a quarantine
+//! exposed through [`WalQuarantine`], from where they can be restored once a
+//! release that understands them is deployed.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{SequenceNumber, TransactionId, WalEntryState, WalOperation};
+
+/// Format version written by [`encode_wal_operation`].
+pub const WAL_FORMAT_VERSION: u16 = 2;
+
+/// Marker preceding the version of a versioned entry.
+///
+/// MessagePack encodes an externally tagged enum as a map or a string, so a
+/// version 1 body never starts with these bytes.
+const WAL_MAGIC: [u8; 2] = *b"TW";
+
+/// Translates a body of one format version into the next version.
+pub type WalUpgrade = fn(&[u8]) -> anyhow::Result<Vec<u8>>;
+
+/// Translators indexed by the version they upgrade from.
+pub const UPGRADES: &[(u16, WalUpgrade)] = &[(1, upgrade_v1)];
+
+/// Version 1 entries are the unprefixed MessagePack written before the
+/// format was versioned; their body is unchanged in version 2.
+fn upgrade_v1(body: &[u8]) -> anyhow::Result<Vec<u8>> {
+    Ok(body.to_vec())
+}
+
+/// Why a WAL entry could not be decoded.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum WalDecodeError {
+    /// The entry was written by a newer release
+    #[error("WAL format version {found} is newer than supported version {supported}")]
+    UnsupportedVersion {
+        /// Version of the entry
+        found: u16,
+        /// Newest version this build reads
+        supported: u16,
+    },
+    /// No translator exists from an old version
+    #[error("no upgrade from WAL format version {0}")]
+    MissingUpgrade(u16),
+    /// A translator rejected the entry
+    #[error("upgrading WAL entry from version {version} failed: {reason}")]
+    UpgradeFailed {
+        /// Version the upgrade started from
+        version: u16,
+        /// Translator error
+        reason: String,
+    },
+    /// The body does not decode as an operation
+    #[error("malformed version {version} WAL entry: {reason}")]
+    Malformed {
+        /// Version of the entry
+        version: u16,
+        /// Decoder error
+        reason: String,
+    },
+}
+
+/// Format version of an encoded entry and its body.
+pub fn wal_format_version(bytes: &[u8]) -> (u16, &[u8]) {
+    match bytes {
+        [m0, m1, hi, lo, body @ ..] if [*m0, *m1] == WAL_MAGIC => {
+            (u16::from_be_bytes([*hi, *lo]), body)
+        }
+        _ => (1, bytes),
+    }
+}
+
+/// Encode `operation` in the current format version.
+pub fn encode_wal_operation(operation: &WalOperation) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(64);
+    bytes.extend_from_slice(&WAL_MAGIC);
+    bytes.extend_from_slice(&WAL_FORMAT_VERSION.to_be_bytes());
+    rmp_serde::encode::write_named(&mut bytes, operation)?;
+    Ok(bytes)
+}
+
+/// Decode an operation written in any supported format version.
+pub fn decode_wal_operation(bytes: &[u8]) -> Result<WalOperation, WalDecodeError> {
+    let (mut version, body) = wal_format_version(bytes);
+    if version > WAL_FORMAT_VERSION {
+        return Err(WalDecodeError::UnsupportedVersion {
+            found: version,
+            supported: WAL_FORMAT_VERSION,
+        });
+    }
+
+    let mut body = body.to_vec();
+    while version < WAL_FORMAT_VERSION {
+        let (_, upgrade) = UPGRADES
+            .iter()
+            .find(|(from, _)| *from == version)
+            .ok_or(WalDecodeError::MissingUpgrade(version))?;
+        body = upgrade(&body).map_err(|e| WalDecodeError::UpgradeFailed {
+            version,
+            reason: e.to_string(),
+        })?;
+        version += 1;
+    }
+
+    rmp_serde::from_slice(&body).map_err(|e| WalDecodeError::Malformed {
+        version,
+        reason: e.to_string(),
+    })
+}
+
+/// A WAL entry set aside because its operation could not be decoded.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct QuarantinedWalEntry {
+    /// Identifier of the original entry
+    pub id: Uuid,
+    /// Transaction the entry belonged to
+    pub transaction_id: TransactionId,
+    /// Sequence number of the original entry
+    pub sequence: SequenceNumber,
+    /// When the entry was logged
+    pub timestamp: DateTime<Utc>,
+    /// Format version the entry claims
+    pub version: u16,
+    /// The undecoded operation bytes
+    pub data: Vec<u8>,
+    /// State of the entry when it was quarantined
+    pub state: WalEntryState,
+    /// Why decoding failed
+    pub reason: String,
+    /// When the entry was quarantined
+    pub quarantined_at: DateTime<Utc>,
+}
+
+/// Access to WAL entries quarantined during recovery.
+#[async_trait]
+pub trait WalQuarantine: Send + Sync {
+    /// Quarantined entries, in sequence order.
+    async fn quarantined_entries(&self) -> anyhow::Result<Vec<QuarantinedWalEntry>>;
+
+    /// Move entries that decode now back into the WAL.
+    ///
+    /// Restored entries are picked up by the next
+    /// [`recover`](crate::WriteAheadLog::recover). Returns the number of
+    /// entries restored.
+    async fn restore_quarantined(&self) -> anyhow::Result<usize>;
+
+    /// Permanently delete a quarantined entry, returning whether it existed.
+    async fn purge_quarantined(&self, id: Uuid) -> anyhow::Result<bool>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versioned_round_trip_and_upgrade() {
+        let operation = WalOperation::Checkpoint { sequence: 42 };
+
+        let encoded = encode_wal_operation(&operation).unwrap();
+        assert_eq!(wal_format_version(&encoded).0, WAL_FORMAT_VERSION);
+        assert_eq!(decode_wal_operation(&encoded).unwrap(), operation);
+
+        // Entries logged before versioning are plain MessagePack
+        let legacy = rmp_serde::to_vec_named(&operation).unwrap();
+        assert_eq!(wal_format_version(&legacy).0, 1);
+        assert_eq!(decode_wal_operation(&legacy).unwrap(), operation);
+    }
+
+    #[test]
+    fn test_undecodable_entries() {
+        let mut future = encode_wal_operation(&WalOperation::Checkpoint { sequence: 1 }).unwrap();
+        future[2..4].copy_from_slice(&(WAL_FORMAT_VERSION + 1).to_be_bytes());
+        assert_eq!(
+            decode_wal_operation(&future),
+            Err(WalDecodeError::UnsupportedVersion {
+                found: WAL_FORMAT_VERSION + 1,
+                supported: WAL_FORMAT_VERSION,
+            })
+        );
+
+        let mut truncated = encode_wal_operation(&WalOperation::Checkpoint { sequence: 1 }).unwrap();
+        truncated.truncate(6);
+        assert!(matches!(
+            decode_wal_operation(&truncated),
+            Err(WalDecodeError::Malformed { version: WAL_FORMAT_VERSION, .. })
+        ));
+    }
+}
@@ -0,0 +1,271 @@
+#![forbid(unsafe_code)]
+
+//! Write-ahead log health metrics.
+//!
+//! A WAL that keeps growing usually means transactions are left open or
+//! checkpoints have stopped. [`WriteAheadLog::wal_health`] reports the
+//! figures needed to spot that on any backend: open transactions, the age of
+//! the oldest pending entry, entry counts by [`WalEntryState`] and the bytes
+//! the log occupies.
+//!
+//! With the `bus` feature, a `WalHealthReporter` publishes these figures
+//! periodically as `KernelEvent::WalHealthReported` events, flagged as
+//! backlogged when they exceed its [`WalHealthThresholds`], so orchestration
+//! can throttle work or alert before the log becomes a problem.
+//!
+//! [`WriteAheadLog::wal_health`]: crate::WriteAheadLog::wal_health
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{SequenceNumber, WalEntryState};
+
+/// Default interval between two reports of a `WalHealthReporter`.
+pub const DEFAULT_WAL_HEALTH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Number of retained WAL entries in each state.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalStateCounts {
+    /// Entries not yet committed
+    pub pending: u64,
+    /// Committed entries not yet checkpointed
+    pub committed: u64,
+    /// Rolled back entries
+    pub rolled_back: u64,
+    /// Checkpointed entries awaiting compaction
+    pub checkpointed: u64,
+}
+
+impl WalStateCounts {
+    /// Count one entry in `state`.
+    pub fn add(&mut self, state: &WalEntryState) {
+        match state {
+            WalEntryState::Pending => self.pending += 1,
+            WalEntryState::Committed => self.committed += 1,
+            WalEntryState::RolledBack => self.rolled_back += 1,
+            WalEntryState::Checkpointed => self.checkpointed += 1,
+        }
+    }
+
+    /// Number of entries across all states.
+    pub fn total(&self) -> u64 {
+        self.pending + self.committed + self.rolled_back + self.checkpointed
+    }
+}
+
+/// Point-in-time health of a write-ahead log.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalHealth {
+    /// Transactions begun but neither committed nor rolled back
+    pub active_transactions: u64,
+    /// Timestamp of the oldest pending entry
+    pub oldest_pending_at: Option<DateTime<Utc>>,
+    /// Retained entries by state
+    pub entries_by_state: WalStateCounts,
+    /// Bytes used by retained entries.
+    ///
+    /// Persistent backends report the size of the stored entries; in-memory
+    /// backends report the size of the encoded operations.
+    pub bytes_used: u64,
+    /// Last sequence number handed out
+    pub current_sequence: SequenceNumber,
+    /// When the figures were collected
+    pub measured_at: DateTime<Utc>,
+}
+
+impl WalHealth {
+    /// Health of a log without entries, measured now.
+    pub fn new(current_sequence: SequenceNumber) -> Self {
+        Self {
+            active_transactions: 0,
+            oldest_pending_at: None,
+            entries_by_state: WalStateCounts::default(),
+            bytes_used: 0,
+            current_sequence,
+            measured_at: Utc::now(),
+        }
+    }
+
+    /// Account for one retained entry of `bytes` bytes.
+    pub fn record_entry(&mut self, state: &WalEntryState, timestamp: DateTime<Utc>, bytes: u64) {
+        self.entries_by_state.add(state);
+        self.bytes_used += bytes;
+        if *state == WalEntryState::Pending && self.oldest_pending_at.is_none_or(|oldest| timestamp < oldest) {
+            self.oldest_pending_at = Some(timestamp);
+        }
+    }
+
+    /// How long the oldest pending entry has been waiting.
+    pub fn oldest_pending_age(&self) -> Option<Duration> {
+        self.oldest_pending_at
+            .map(|oldest| (self.measured_at - oldest).to_std().unwrap_or(Duration::ZERO))
+    }
+
+    /// Thresholds of `thresholds` that are exceeded, by name.
+    pub fn exceeded(&self, thresholds: &WalHealthThresholds) -> Vec<&'static str> {
+        let mut exceeded = Vec::new();
+        if thresholds.max_active_transactions.is_some_and(|max| self.active_transactions > max) {
+            exceeded.push("active_transactions");
+        }
+        if thresholds
+            .max_pending_age
+            .is_some_and(|max| self.oldest_pending_age().is_some_and(|age| age > max))
+        {
+            exceeded.push("pending_age");
+        }
+        if thresholds.max_pending_entries.is_some_and(|max| self.entries_by_state.pending > max) {
+            exceeded.push("pending_entries");
+        }
+        if thresholds.max_bytes.is_some_and(|max| self.bytes_used > max) {
+            exceeded.push("bytes_used");
+        }
+        exceeded
+    }
+
+    /// Whether any threshold of `thresholds` is exceeded.
+    pub fn is_backlogged(&self, thresholds: &WalHealthThresholds) -> bool {
+        !self.exceeded(thresholds).is_empty()
+    }
+
+    /// Kernel event reporting this health for the store `store`.
+    #[cfg(feature = "bus")]
+    pub fn to_kernel_event(&self, store: &str, thresholds: &WalHealthThresholds) -> toka_bus_core::KernelEvent {
+        toka_bus_core::KernelEvent::WalHealthReported {
+            store: store.to_string(),
+            active_transactions: self.active_transactions,
+            pending_entries: self.entries_by_state.pending,
+            committed_entries: self.entries_by_state.committed,
+            rolled_back_entries: self.entries_by_state.rolled_back,
+            checkpointed_entries: self.entries_by_state.checkpointed,
+            oldest_pending_age_ms: self.oldest_pending_age().map(|age| age.as_millis() as u64),
+            bytes_used: self.bytes_used,
+            backlogged: self.is_backlogged(thresholds),
+            timestamp: self.measured_at,
+        }
+    }
+}
+
+/// Limits beyond which a WAL counts as backlogged. `None` disables a limit.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalHealthThresholds {
+    /// Most transactions that may be open at once
+    pub max_active_transactions: Option<u64>,
+    /// Longest an entry may stay pending
+    pub max_pending_age: Option<Duration>,
+    /// Most entries that may be pending at once
+    pub max_pending_entries: Option<u64>,
+    /// Most bytes the retained entries may use
+    pub max_bytes: Option<u64>,
+}
+
+impl Default for WalHealthThresholds {
+    fn default() -> Self {
+        Self {
+            max_active_transactions: Some(64),
+            max_pending_age: Some(Duration::from_secs(60)),
+            max_pending_entries: Some(10_000),
+            max_bytes: None,
+        }
+    }
+}
+
+/// Publishes the health of a WAL on the kernel event bus.
+#[cfg(feature = "bus")]
+pub struct WalHealthReporter<W: ?Sized> {
+    wal: std::sync::Arc<W>,
+    store: String,
+    interval: Duration,
+    thresholds: WalHealthThresholds,
+}
+
+#[cfg(feature = "bus")]
+impl<W: crate::WriteAheadLog + ?Sized> WalHealthReporter<W> {
+    /// Report on `wal` under the name `store`, with default interval and
+    /// thresholds.
+    pub fn new(wal: std::sync::Arc<W>, store: impl Into<String>) -> Self {
+        Self {
+            wal,
+            store: store.into(),
+            interval: DEFAULT_WAL_HEALTH_INTERVAL,
+            thresholds: WalHealthThresholds::default(),
+        }
+    }
+
+    /// Report every `interval`.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Flag reports exceeding `thresholds` as backlogged.
+    pub fn with_thresholds(mut self, thresholds: WalHealthThresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    /// Collect the health once and publish it on `bus`.
+    pub async fn report(&self, bus: &dyn toka_bus_core::EventBus) -> anyhow::Result<WalHealth> {
+        let health = self.wal.wal_health().await?;
+        bus.publish(&health.to_kernel_event(&self.store, &self.thresholds))?;
+        Ok(health)
+    }
+
+    /// Report every interval, forever. Failed reports are skipped.
+    pub async fn run(&self, bus: &dyn toka_bus_core::EventBus) {
+        let mut ticker = tokio::time::interval(self.interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let _ = self.report(bus).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_entries_and_thresholds() {
+        let mut health = WalHealth::new(7);
+        let now = health.measured_at;
+        health.record_entry(&WalEntryState::Committed, now, 100);
+        health.record_entry(&WalEntryState::Pending, now - chrono::Duration::seconds(30), 40);
+        health.record_entry(&WalEntryState::Pending, now - chrono::Duration::seconds(90), 60);
+        health.active_transactions = 2;
+
+        assert_eq!(health.entries_by_state.pending, 2);
+        assert_eq!(health.entries_by_state.total(), 3);
+        assert_eq!(health.bytes_used, 200);
+        assert_eq!(health.oldest_pending_age(), Some(Duration::from_secs(90)));
+
+        assert_eq!(health.exceeded(&WalHealthThresholds::default()), vec!["pending_age"]);
+        let relaxed = WalHealthThresholds {
+            max_pending_age: None,
+            ..WalHealthThresholds::default()
+        };
+        assert!(!health.is_backlogged(&relaxed));
+        let tight = WalHealthThresholds {
+            max_active_transactions: Some(1),
+            max_bytes: Some(199),
+            ..relaxed
+        };
+        assert_eq!(health.exceeded(&tight), vec!["active_transactions", "bytes_used"]);
+    }
+
+    #[cfg(feature = "bus")]
+    #[test]
+    fn test_kernel_event() {
+        let mut health = WalHealth::new(3);
+        health.record_entry(&WalEntryState::Pending, health.measured_at, 10);
+        let event = health.to_kernel_event("primary", &WalHealthThresholds::default());
+        assert_eq!(event.kind(), "storage.wal_health");
+        assert!(event.validate().is_ok());
+        assert!(matches!(
+            event,
+            toka_bus_core::KernelEvent::WalHealthReported { pending_entries: 1, backlogged: false, .. }
+        ));
+    }
+}
@@ -0,0 +1,76 @@
+#![forbid(unsafe_code)]
+
+//! Automatic abort of stale WAL transactions.
+//!
+//! A caller that crashes between [`WriteAheadLog::begin_transaction`] and
+//! commit or rollback leaves its transaction open until the process restarts
+//! and [`WriteAheadLog::recover`] runs. Backends implementing
+//! [`TransactionTimeouts`] roll such transactions back once they outlive a
+//! configured time to live, and notify subscribers with a
+//! [`TransactionTimedOut`] event.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{TransactionId, WriteAheadLog};
+
+/// Default interval between two sweeps of a transaction reaper.
+pub const DEFAULT_REAPER_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Notification sent for every transaction rolled back because it timed out.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionTimedOut {
+    /// The rolled back transaction
+    pub transaction_id: TransactionId,
+    /// When the transaction was begun
+    pub began_at: DateTime<Utc>,
+    /// When the transaction was rolled back
+    pub aborted_at: DateTime<Utc>,
+    /// Number of WAL entries the transaction had written
+    pub entries: usize,
+}
+
+/// Write-ahead logs that roll back transactions left open for too long.
+#[async_trait]
+pub trait TransactionTimeouts: WriteAheadLog {
+    /// Time to live of open transactions, or `None` if they never time out.
+    fn transaction_ttl(&self) -> Option<Duration>;
+
+    /// Roll back every open transaction begun more than the time to live
+    /// before `now`.
+    ///
+    /// Returns the transactions that were rolled back. Does nothing when no
+    /// time to live is configured.
+    async fn abort_stale_transactions(&self, now: DateTime<Utc>) -> anyhow::Result<Vec<TransactionTimedOut>>;
+
+    /// Transactions rolled back by timeouts since the backend was opened.
+    fn transactions_timed_out(&self) -> u64;
+}
+
+/// Whether a transaction begun at `began_at` has outlived `ttl` at `now`.
+pub fn is_stale(began_at: DateTime<Utc>, now: DateTime<Utc>, ttl: Duration) -> bool {
+    match (now - began_at).to_std() {
+        Ok(age) => age > ttl,
+        // Began in the future according to `now`
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_stale() {
+        let began_at = Utc::now();
+        let ttl = Duration::from_secs(30);
+
+        assert!(!is_stale(began_at, began_at, ttl));
+        assert!(!is_stale(began_at, began_at + chrono::Duration::seconds(30), ttl));
+        assert!(is_stale(began_at, began_at + chrono::Duration::seconds(31), ttl));
+        assert!(!is_stale(began_at, began_at - chrono::Duration::seconds(60), ttl));
+    }
+}
@@ -0,0 +1,656 @@
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+
+//! **toka-store-encrypted** – At-rest encryption for Toka OS storage backends.
+//!
+//! [`EncryptedBackend`] wraps any [`StorageBackend`] and encrypts payload bytes
+//! with an AEAD cipher (AES-256-GCM or XChaCha20-Poly1305) before delegating to
+//! the inner backend. Headers are stored unencrypted so queries and causal
+//! ordering keep working.
+//!
+//! Causal digests are always computed over the *plaintext* payload, exactly as
+//! without encryption, so event identity and hash chains are unaffected. The
+//! digest is also bound to the ciphertext as AEAD associated data: a
+//! ciphertext moved under a different digest fails to decrypt.
+//!
+//! Every ciphertext records the ID of the key that produced it. A [`KeyRing`]
+//! holds one active key for new writes plus any number of older keys kept for
+//! reading, which makes key rotation a matter of adding a new active key.
+//! [`EncryptedBackend::key_usage`] reports which keys stored payloads still
+//! depend on, and [`EncryptedBackend::rewrap_into`] migrates every event into
+//! a fresh store under the active key so the old keys can be retired.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::Aes256Gcm;
+use anyhow::Result;
+use async_trait::async_trait;
+use chacha20poly1305::XChaCha20Poly1305;
+use chrono::{DateTime, TimeZone, Utc};
+use futures::TryStreamExt;
+
+use toka_store_core::{
+    CausalDigest, EventHeader, EventHeaderStream, EventId, IntentId, QueryableStorage,
+    SavepointId, SequenceNumber, StorageBackend, StorageStatistics, StorageStats, TransactionId, WalHealth, WalOperation, WalRecoveryResult, WriteAheadLog,
+};
+
+/// Envelope format version written in front of every ciphertext.
+const ENVELOPE_VERSION: u8 = 1;
+
+/// Envelope header size: version, cipher, key ID.
+const ENVELOPE_HEADER_LEN: usize = 1 + 1 + 4;
+
+/// Identifier of an encryption key within a [`KeyRing`].
+pub type KeyId = u32;
+
+//─────────────────────────────
+//  Errors
+//─────────────────────────────
+
+/// Errors produced while encrypting or decrypting payloads.
+#[derive(Debug, thiserror::Error)]
+pub enum EncryptionError {
+    /// The ciphertext was produced with a key that is not in the key ring
+    #[error("unknown encryption key: {0}")]
+    UnknownKey(KeyId),
+    /// Encryption failed
+    #[error("payload encryption failed")]
+    EncryptionFailed,
+    /// Authentication failed: wrong key, tampered ciphertext or wrong digest
+    #[error("payload decryption failed: ciphertext could not be authenticated")]
+    DecryptionFailed,
+    /// The stored bytes are not a valid envelope
+    #[error("malformed encrypted payload: {0}")]
+    MalformedEnvelope(String),
+    /// The key ring was used incorrectly
+    #[error("key ring error: {0}")]
+    KeyRing(String),
+}
+
+//─────────────────────────────
+//  Keys
+//─────────────────────────────
+
+/// AEAD cipher used by an [`EncryptionKey`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cipher {
+    /// AES-256 in Galois/Counter Mode (96-bit random nonces)
+    Aes256Gcm,
+    /// XChaCha20-Poly1305 (192-bit random nonces)
+    XChaCha20Poly1305,
+}
+
+impl Cipher {
+    fn id(&self) -> u8 {
+        match self {
+            Cipher::Aes256Gcm => 1,
+            Cipher::XChaCha20Poly1305 => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Result<Self, EncryptionError> {
+        match id {
+            1 => Ok(Cipher::Aes256Gcm),
+            2 => Ok(Cipher::XChaCha20Poly1305),
+            other => Err(EncryptionError::MalformedEnvelope(format!("unknown cipher id {}", other))),
+        }
+    }
+
+    fn nonce_len(&self) -> usize {
+        match self {
+            Cipher::Aes256Gcm => 12,
+            Cipher::XChaCha20Poly1305 => 24,
+        }
+    }
+}
+
+#[derive(Clone)]
+enum KeyCipher {
+    // Boxed: the expanded AES key schedule is far larger than a ChaCha key
+    Aes256Gcm(Box<Aes256Gcm>),
+    XChaCha20Poly1305(XChaCha20Poly1305),
+}
+
+/// A 256-bit symmetric key bound to a cipher.
+#[derive(Clone)]
+pub struct EncryptionKey {
+    cipher: KeyCipher,
+}
+
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print key material
+        f.debug_struct("EncryptionKey")
+            .field("cipher", &self.cipher_kind())
+            .finish_non_exhaustive()
+    }
+}
+
+impl EncryptionKey {
+    /// Create a key from raw key bytes.
+    pub fn new(cipher: Cipher, key: &[u8; 32]) -> Self {
+        let cipher = match cipher {
+            Cipher::Aes256Gcm => KeyCipher::Aes256Gcm(Box::new(Aes256Gcm::new(key.into()))),
+            Cipher::XChaCha20Poly1305 => {
+                KeyCipher::XChaCha20Poly1305(XChaCha20Poly1305::new(key.into()))
+            }
+        };
+        Self { cipher }
+    }
+
+    /// Generate a random key from the operating system RNG.
+    pub fn generate(cipher: Cipher) -> Self {
+        let cipher = match cipher {
+            Cipher::Aes256Gcm => KeyCipher::Aes256Gcm(Box::new(Aes256Gcm::new(&Aes256Gcm::generate_key(OsRng)))),
+            Cipher::XChaCha20Poly1305 => KeyCipher::XChaCha20Poly1305(XChaCha20Poly1305::new(
+                &XChaCha20Poly1305::generate_key(OsRng),
+            )),
+        };
+        Self { cipher }
+    }
+
+    /// The cipher this key is used with.
+    pub fn cipher_kind(&self) -> Cipher {
+        match self.cipher {
+            KeyCipher::Aes256Gcm(_) => Cipher::Aes256Gcm,
+            KeyCipher::XChaCha20Poly1305(_) => Cipher::XChaCha20Poly1305,
+        }
+    }
+
+    /// Encrypt `plaintext`, returning `nonce || ciphertext`.
+    fn seal(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let payload = Payload { msg: plaintext, aad };
+        let (mut sealed, ciphertext) = match &self.cipher {
+            KeyCipher::Aes256Gcm(cipher) => {
+                let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+                (nonce.to_vec(), cipher.encrypt(&nonce, payload))
+            }
+            KeyCipher::XChaCha20Poly1305(cipher) => {
+                let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+                (nonce.to_vec(), cipher.encrypt(&nonce, payload))
+            }
+        };
+        sealed.extend(ciphertext.map_err(|_| EncryptionError::EncryptionFailed)?);
+        Ok(sealed)
+    }
+
+    /// Decrypt `nonce || ciphertext`.
+    fn open(&self, sealed: &[u8], aad: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let nonce_len = self.cipher_kind().nonce_len();
+        if sealed.len() < nonce_len {
+            return Err(EncryptionError::MalformedEnvelope("truncated nonce".to_string()));
+        }
+        let (nonce, ciphertext) = sealed.split_at(nonce_len);
+        let payload = Payload { msg: ciphertext, aad };
+
+        match &self.cipher {
+            KeyCipher::Aes256Gcm(cipher) => cipher.decrypt(nonce.into(), payload),
+            KeyCipher::XChaCha20Poly1305(cipher) => cipher.decrypt(nonce.into(), payload),
+        }
+        .map_err(|_| EncryptionError::DecryptionFailed)
+    }
+}
+
+/// Set of keys with one active key for new writes.
+///
+/// Older keys stay in the ring so existing payloads remain readable after a
+/// rotation. Retire a key only once nothing encrypted with it is left.
+#[derive(Debug, Clone)]
+pub struct KeyRing {
+    active: KeyId,
+    keys: HashMap<KeyId, EncryptionKey>,
+}
+
+impl KeyRing {
+    /// Create a ring whose active key is `key`.
+    pub fn new(id: KeyId, key: EncryptionKey) -> Self {
+        Self {
+            active: id,
+            keys: HashMap::from([(id, key)]),
+        }
+    }
+
+    /// Add a key usable for decryption only.
+    pub fn with_key(mut self, id: KeyId, key: EncryptionKey) -> Self {
+        self.keys.insert(id, key);
+        self
+    }
+
+    /// Add `key` and make it the active key for new writes.
+    pub fn rotate(&mut self, id: KeyId, key: EncryptionKey) {
+        self.keys.insert(id, key);
+        self.active = id;
+    }
+
+    /// Remove a key that is no longer needed for decryption.
+    ///
+    /// Use [`EncryptedBackend::key_usage`] to check that no stored payload
+    /// still needs the key.
+    pub fn retire(&mut self, id: KeyId) -> Result<(), EncryptionError> {
+        if id == self.active {
+            return Err(EncryptionError::KeyRing(format!(
+                "cannot retire active key {}",
+                id
+            )));
+        }
+        self.keys.remove(&id);
+        Ok(())
+    }
+
+    /// ID of the key used for new writes.
+    pub fn active_key_id(&self) -> KeyId {
+        self.active
+    }
+
+    /// Encrypt a payload into a self-describing envelope.
+    pub fn encrypt(&self, digest: &CausalDigest, plaintext: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let key = self
+            .keys
+            .get(&self.active)
+            .ok_or(EncryptionError::UnknownKey(self.active))?;
+
+        let mut envelope = Vec::with_capacity(ENVELOPE_HEADER_LEN + plaintext.len() + 40);
+        envelope.push(ENVELOPE_VERSION);
+        envelope.push(key.cipher_kind().id());
+        envelope.extend_from_slice(&self.active.to_be_bytes());
+        envelope.extend(key.seal(plaintext, digest)?);
+        Ok(envelope)
+    }
+
+    /// Decrypt an envelope produced by [`encrypt`](Self::encrypt).
+    pub fn decrypt(&self, digest: &CausalDigest, envelope: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let key_id = Self::envelope_key_id(envelope)?;
+        let cipher = Cipher::from_id(envelope[1])?;
+        let key = self.keys.get(&key_id).ok_or(EncryptionError::UnknownKey(key_id))?;
+        if key.cipher_kind() != cipher {
+            return Err(EncryptionError::MalformedEnvelope(format!(
+                "key {} is not a {:?} key",
+                key_id, cipher
+            )));
+        }
+
+        key.open(&envelope[ENVELOPE_HEADER_LEN..], digest)
+    }
+
+    /// ID of the key an envelope was encrypted with.
+    pub fn envelope_key_id(envelope: &[u8]) -> Result<KeyId, EncryptionError> {
+        if envelope.len() < ENVELOPE_HEADER_LEN {
+            return Err(EncryptionError::MalformedEnvelope("truncated header".to_string()));
+        }
+        if envelope[0] != ENVELOPE_VERSION {
+            return Err(EncryptionError::MalformedEnvelope(format!(
+                "unsupported envelope version {}",
+                envelope[0]
+            )));
+        }
+        Ok(KeyId::from_be_bytes([envelope[2], envelope[3], envelope[4], envelope[5]]))
+    }
+
+    /// Re-encrypt an envelope under the active key.
+    pub fn rewrap(&self, digest: &CausalDigest, envelope: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        if Self::envelope_key_id(envelope)? == self.active {
+            return Ok(envelope.to_vec());
+        }
+        self.encrypt(digest, &self.decrypt(digest, envelope)?)
+    }
+}
+
+//─────────────────────────────
+//  Encrypted backend wrapper
+//─────────────────────────────
+
+/// Storage backend wrapper encrypting payloads at rest.
+///
+/// Query ([`QueryableStorage`]) and WAL ([`WriteAheadLog`]) support is
+/// forwarded when the inner backend provides it; payloads logged to the WAL
+/// are encrypted before they reach the inner backend as well.
+#[derive(Debug)]
+pub struct EncryptedBackend<B> {
+    inner: B,
+    keys: RwLock<KeyRing>,
+}
+
+impl<B: StorageBackend> EncryptedBackend<B> {
+    /// Wrap `inner`, encrypting payloads with the keys in `keys`.
+    pub fn new(inner: B, keys: KeyRing) -> Self {
+        Self {
+            inner,
+            keys: RwLock::new(keys),
+        }
+    }
+
+    /// Access the wrapped backend (payloads read from it are ciphertext).
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    /// Make `key` the active key for new writes, keeping old keys for reads.
+    pub fn rotate_key(&self, id: KeyId, key: EncryptionKey) {
+        self.keys.write().expect("key ring poisoned").rotate(id, key);
+    }
+
+    /// Remove a key that is no longer needed for decryption.
+    pub fn retire_key(&self, id: KeyId) -> Result<(), EncryptionError> {
+        self.keys.write().expect("key ring poisoned").retire(id)
+    }
+
+    /// ID of the key used for new writes.
+    pub fn active_key_id(&self) -> KeyId {
+        self.keys.read().expect("key ring poisoned").active_key_id()
+    }
+
+    /// Remove a key after checking that no stored payload still needs it.
+    pub async fn retire_unused_key(&self, id: KeyId) -> Result<()>
+    where
+        B: QueryableStorage,
+    {
+        if let Some(count) = self.key_usage().await?.get(&id) {
+            return Err(EncryptionError::KeyRing(format!(
+                "key {} still encrypts {} stored payloads",
+                id, count
+            ))
+            .into());
+        }
+        Ok(self.retire_key(id)?)
+    }
+
+    /// Count the stored payloads encrypted with each key.
+    ///
+    /// Keys missing from the result protect nothing in the inner backend and
+    /// can be retired. Payloads logged to an uncommitted WAL transaction are
+    /// not counted.
+    pub async fn key_usage(&self) -> Result<HashMap<KeyId, usize>>
+    where
+        B: QueryableStorage,
+    {
+        let (from, to) = all_time();
+        let mut events = self.inner.events_in_range(from, to).await?;
+        let mut seen = HashSet::new();
+        let mut usage = HashMap::new();
+        while let Some(header) = events.try_next().await? {
+            if !seen.insert(header.digest) {
+                continue;
+            }
+            if let Some(envelope) = self.inner.payload_bytes(&header.digest).await? {
+                *usage.entry(KeyRing::envelope_key_id(&envelope)?).or_insert(0) += 1;
+            }
+        }
+        Ok(usage)
+    }
+
+    /// Copy every event into `target`, re-encrypting payloads under the
+    /// active key.
+    ///
+    /// Stores do not overwrite committed payloads, so old keys are migrated
+    /// away from by rewrapping into a fresh store; wrap `target` with the same
+    /// key ring afterwards and retire the old keys. Events are copied oldest
+    /// first. Returns the number of events copied.
+    pub async fn rewrap_into<T: StorageBackend>(&self, target: &T) -> Result<usize>
+    where
+        B: QueryableStorage,
+    {
+        let (from, to) = all_time();
+        let mut events = self.inner.events_in_range(from, to).await?;
+        let mut copied = 0;
+        while let Some(header) = events.try_next().await? {
+            let envelope = self.inner.payload_bytes(&header.digest).await?.ok_or_else(|| {
+                EncryptionError::MalformedEnvelope(format!("missing payload for event {}", header.id))
+            })?;
+            let rewrapped = self.keys.read().expect("key ring poisoned").rewrap(&header.digest, &envelope)?;
+            target.commit(&header, &rewrapped).await?;
+            copied += 1;
+        }
+        Ok(copied)
+    }
+
+    fn encrypt(&self, digest: &CausalDigest, plaintext: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        self.keys.read().expect("key ring poisoned").encrypt(digest, plaintext)
+    }
+
+    fn decrypt(&self, digest: &CausalDigest, envelope: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        self.keys.read().expect("key ring poisoned").decrypt(digest, envelope)
+    }
+}
+
+/// Time range covering every stored event.
+///
+/// Bounded to four-digit years so backends comparing RFC 3339 strings still
+/// order the bounds correctly.
+fn all_time() -> (DateTime<Utc>, DateTime<Utc>) {
+    (
+        Utc.with_ymd_and_hms(0, 1, 1, 0, 0, 0).unwrap(),
+        Utc.with_ymd_and_hms(9999, 12, 31, 23, 59, 59).unwrap(),
+    )
+}
+
+#[async_trait]
+impl<B: StorageBackend> StorageBackend for EncryptedBackend<B> {
+    async fn commit(&self, header: &EventHeader, payload: &[u8]) -> Result<()> {
+        let ciphertext = self.encrypt(&header.digest, payload)?;
+        self.inner.commit(header, &ciphertext).await
+    }
+
+    async fn header(&self, id: &EventId) -> Result<Option<EventHeader>> {
+        self.inner.header(id).await
+    }
+
+    async fn payload_bytes(&self, digest: &CausalDigest) -> Result<Option<Vec<u8>>> {
+        match self.inner.payload_bytes(digest).await? {
+            Some(envelope) => Ok(Some(self.decrypt(digest, &envelope)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn payloads_bulk(&self, digests: &[CausalDigest]) -> Result<HashMap<CausalDigest, Vec<u8>>> {
+        self.inner
+            .payloads_bulk(digests)
+            .await?
+            .into_iter()
+            .map(|(digest, envelope)| Ok((digest, self.decrypt(&digest, &envelope)?)))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl<B: QueryableStorage> QueryableStorage for EncryptedBackend<B> {
+    async fn events_by_kind(&self, kind: &str) -> Result<EventHeaderStream<'_>> {
+        self.inner.events_by_kind(kind).await
+    }
+
+    async fn events_in_range(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<EventHeaderStream<'_>> {
+        self.inner.events_in_range(from, to).await
+    }
+
+    async fn events_by_intent(&self, intent: &IntentId) -> Result<EventHeaderStream<'_>> {
+        self.inner.events_by_intent(intent).await
+    }
+}
+
+#[async_trait]
+impl<B: StorageStats> StorageStats for EncryptedBackend<B> {
+    async fn storage_stats(&self) -> Result<StorageStatistics> {
+        // Sizes include encryption overhead, which is what actually occupies storage
+        self.inner.storage_stats().await
+    }
+}
+
+#[async_trait]
+impl<B: StorageBackend + WriteAheadLog> WriteAheadLog for EncryptedBackend<B> {
+    async fn begin_transaction(&self) -> Result<TransactionId> {
+        self.inner.begin_transaction().await
+    }
+
+    async fn write_entry(&self, transaction_id: TransactionId, operation: WalOperation) -> Result<()> {
+        // The inner backend replays logged payloads verbatim on commit and
+        // recovery, so they must already be ciphertext when logged.
+        let operation = match operation {
+            WalOperation::CommitEvent { header, payload } => {
+                let payload = self.encrypt(&header.digest, &payload)?.into();
+                WalOperation::CommitEvent { header, payload }
+            }
+            other => other,
+        };
+        self.inner.write_entry(transaction_id, operation).await
+    }
+
+    async fn commit_transaction(&self, transaction_id: TransactionId) -> Result<()> {
+        self.inner.commit_transaction(transaction_id).await
+    }
+
+    async fn rollback_transaction(&self, transaction_id: TransactionId) -> Result<()> {
+        self.inner.rollback_transaction(transaction_id).await
+    }
+
+    async fn savepoint(&self, transaction_id: TransactionId) -> Result<SavepointId> {
+        self.inner.savepoint(transaction_id).await
+    }
+
+    async fn rollback_to_savepoint(&self, transaction_id: TransactionId, savepoint_id: SavepointId) -> Result<()> {
+        self.inner.rollback_to_savepoint(transaction_id, savepoint_id).await
+    }
+
+    async fn recover(&self) -> Result<WalRecoveryResult> {
+        self.inner.recover().await
+    }
+
+    async fn checkpoint(&self, sequence: SequenceNumber) -> Result<()> {
+        self.inner.checkpoint(sequence).await
+    }
+
+    async fn current_sequence(&self) -> Result<SequenceNumber> {
+        self.inner.current_sequence().await
+    }
+
+    async fn wal_health(&self) -> Result<WalHealth> {
+        self.inner.wal_health().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use toka_store_core::{create_event_header, prelude::*};
+    use toka_store_memory::MemoryBackend;
+    use uuid::Uuid;
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    struct TestEvent {
+        message: String,
+        value: i32,
+    }
+
+    fn test_event(value: i32) -> (EventHeader, Vec<u8>) {
+        let event = TestEvent { message: "secret".to_string(), value };
+        let header = create_event_header(&[], Uuid::new_v4(), "test.secret".to_string(), &event).unwrap();
+        (header, rmp_serde::to_vec_named(&event).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_both_ciphers() {
+        for cipher in [Cipher::Aes256Gcm, Cipher::XChaCha20Poly1305] {
+            let backend = EncryptedBackend::new(
+                MemoryBackend::new(),
+                KeyRing::new(1, EncryptionKey::generate(cipher)),
+            );
+            let (header, payload) = test_event(1);
+
+            backend.commit(&header, &payload).await.unwrap();
+
+            assert_eq!(backend.payload_bytes(&header.digest).await.unwrap().unwrap(), payload);
+            assert_eq!(backend.header(&header.id).await.unwrap().unwrap(), header);
+
+            // The inner backend only ever sees ciphertext
+            let stored = backend.inner().payload_bytes(&header.digest).await.unwrap().unwrap();
+            assert_ne!(stored, payload);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_key_rotation() {
+        let backend = EncryptedBackend::new(
+            MemoryBackend::new(),
+            KeyRing::new(1, EncryptionKey::generate(Cipher::Aes256Gcm)),
+        );
+        let (old_header, old_payload) = test_event(1);
+        backend.commit(&old_header, &old_payload).await.unwrap();
+
+        backend.rotate_key(2, EncryptionKey::generate(Cipher::XChaCha20Poly1305));
+        assert_eq!(backend.active_key_id(), 2);
+        let (new_header, new_payload) = test_event(2);
+        backend.commit(&new_header, &new_payload).await.unwrap();
+
+        // Both generations stay readable
+        assert_eq!(backend.payload_bytes(&old_header.digest).await.unwrap().unwrap(), old_payload);
+        assert_eq!(backend.payload_bytes(&new_header.digest).await.unwrap().unwrap(), new_payload);
+
+        assert!(backend.retire_key(2).is_err());
+        backend.retire_key(1).unwrap();
+        assert!(backend.payload_bytes(&old_header.digest).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rewrap_frees_old_keys_for_retirement() {
+        let backend = EncryptedBackend::new(
+            MemoryBackend::new(),
+            KeyRing::new(1, EncryptionKey::generate(Cipher::Aes256Gcm)),
+        );
+        let (old_header, old_payload) = test_event(1);
+        backend.commit(&old_header, &old_payload).await.unwrap();
+        backend.rotate_key(2, EncryptionKey::generate(Cipher::XChaCha20Poly1305));
+        let (new_header, new_payload) = test_event(2);
+        backend.commit(&new_header, &new_payload).await.unwrap();
+
+        assert_eq!(backend.key_usage().await.unwrap(), HashMap::from([(1, 1), (2, 1)]));
+        assert!(backend.retire_unused_key(1).await.is_err());
+
+        let migrated = MemoryBackend::new();
+        assert_eq!(backend.rewrap_into(&migrated).await.unwrap(), 2);
+        let keys = backend.keys.read().unwrap().clone();
+        let migrated = EncryptedBackend::new(migrated, keys);
+
+        assert_eq!(migrated.key_usage().await.unwrap(), HashMap::from([(2, 2)]));
+        migrated.retire_unused_key(1).await.unwrap();
+        assert_eq!(migrated.payload_bytes(&old_header.digest).await.unwrap().unwrap(), old_payload);
+        assert_eq!(migrated.payload_bytes(&new_header.digest).await.unwrap().unwrap(), new_payload);
+    }
+
+    #[test]
+    fn test_ciphertext_is_bound_to_digest() {
+        let keys = KeyRing::new(7, EncryptionKey::new(Cipher::Aes256Gcm, &[42u8; 32]));
+        let envelope = keys.encrypt(&[1u8; 32], b"payload").unwrap();
+
+        assert_eq!(keys.decrypt(&[1u8; 32], &envelope).unwrap(), b"payload");
+        assert!(matches!(
+            keys.decrypt(&[2u8; 32], &envelope),
+            Err(EncryptionError::DecryptionFailed)
+        ));
+
+        let mut tampered = envelope.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(keys.decrypt(&[1u8; 32], &tampered).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_wal_payloads_are_encrypted() {
+        let backend = EncryptedBackend::new(
+            MemoryBackend::new(),
+            KeyRing::new(1, EncryptionKey::generate(Cipher::XChaCha20Poly1305)),
+        );
+        let (header, payload) = test_event(3);
+
+        let tx_id = backend.begin_transaction().await.unwrap();
+        backend.commit_with_wal(tx_id, &header, &payload).await.unwrap();
+        backend.commit_transaction(tx_id).await.unwrap();
+
+        assert_eq!(backend.payload_bytes(&header.digest).await.unwrap().unwrap(), payload);
+        let stored = backend.inner().payload_bytes(&header.digest).await.unwrap().unwrap();
+        assert_ne!(stored, payload);
+    }
+}
@@ -0,0 +1,83 @@
+//! Committing and reading multi-MB payloads as borrowed slices versus shared
+//! `Bytes`.
+//!
+//! Run with `cargo bench -p toka-store-memory --bench payload_bytes`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use toka_store_core::{
+    create_event_header_with_format, Bytes, PayloadFormat, StorageBackend, WalStorageBackend, WriteAheadLog,
+};
+use toka_store_memory::MemoryBackend;
+use uuid::Uuid;
+
+const SIZES_MB: [usize; 3] = [1, 4, 16];
+
+fn payload_bytes(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let mut group = c.benchmark_group("payload_bytes");
+
+    for mb in SIZES_MB {
+        let observation = Bytes::from(vec![0xA5u8; mb * 1024 * 1024]);
+        let (header, payload) = create_event_header_with_format(
+            &[],
+            Uuid::new_v4(),
+            "bench.observation".to_string(),
+            &observation,
+            PayloadFormat::MessagePack,
+        )
+        .unwrap();
+        let payload = Bytes::from(payload);
+        group.throughput(Throughput::Bytes(payload.len() as u64));
+
+        // Every iteration commits into a fresh backend, so nothing is deduplicated
+        group.bench_function(BenchmarkId::new("commit_slice", mb), |b| {
+            b.iter(|| runtime.block_on(MemoryBackend::new().commit(&header, &payload)).unwrap())
+        });
+
+        group.bench_function(BenchmarkId::new("commit_bytes", mb), |b| {
+            b.iter(|| {
+                runtime
+                    .block_on(MemoryBackend::new().commit_bytes(&header, payload.clone()))
+                    .unwrap()
+            })
+        });
+
+        group.bench_function(BenchmarkId::new("commit_wal_slice", mb), |b| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    let backend = MemoryBackend::new();
+                    let tx = backend.begin_transaction().await.unwrap();
+                    backend.commit_with_wal(tx, &header, &payload).await.unwrap();
+                    backend.commit_transaction(tx).await.unwrap();
+                })
+            })
+        });
+
+        group.bench_function(BenchmarkId::new("commit_wal_bytes", mb), |b| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    let backend = MemoryBackend::new();
+                    let tx = backend.begin_transaction().await.unwrap();
+                    backend.commit_bytes_with_wal(tx, &header, payload.clone()).await.unwrap();
+                    backend.commit_transaction(tx).await.unwrap();
+                })
+            })
+        });
+
+        let backend = MemoryBackend::new();
+        runtime.block_on(backend.commit_bytes(&header, payload.clone())).unwrap();
+
+        group.bench_function(BenchmarkId::new("read_vec", mb), |b| {
+            b.iter(|| runtime.block_on(backend.payload_bytes(&header.digest)).unwrap())
+        });
+
+        group.bench_function(BenchmarkId::new("read_bytes", mb), |b| {
+            b.iter(|| runtime.block_on(backend.payload(&header.digest)).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, payload_bytes);
+criterion_main!(benches);
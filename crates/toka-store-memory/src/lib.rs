@@ -10,23 +10,205 @@
 //! This implementation now includes Write-Ahead Logging (WAL) support for enhanced
 //! testing capabilities and consistent API with persistent backends.
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 use anyhow::Result;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
 use tokio::sync::{broadcast, RwLock};
 use uuid::Uuid;
 
 use toka_store_core::{
-    StorageBackend, EventHeader, EventId, CausalDigest,
+    StorageBackend, QueryableStorage, CompactableStorage, ReplayableStorage, ReplayFrom,
+    replay_then_live, EventHeaderStream, EventHeader, EventId, CausalDigest, IntentId,
     WriteAheadLog, WalEntry, WalOperation, WalEntryState, WalRecoveryResult,
-    TransactionId, SequenceNumber,
+    TransactionId, SequenceNumber, SavepointId, SnapshotManifest, SnapshotReader, SnapshotStorage, SnapshotWriter,
+    StorageStatistics, StorageStats, NamespaceStats, NamespacedStorage, filter_namespace,
+    ConditionalStorage, Precondition, StorageError, intent_head,
+    CommitOutcome, DeduplicatingStorage, IdempotencyIndex, DEFAULT_DEDUP_WINDOW,
+    compactable_transactions, WalCompaction, WalCompactionPolicy, WalCompactionReport,
+    is_stale, TransactionTimedOut, TransactionTimeouts, WalSource,
+    RecoveryHooks, RecoveryObserver, ReplayDecision, WalHealth, encode_wal_operation, Bytes,
 };
 
 /// Default buffer size for the live event broadcast channel.
 const DEFAULT_BUFFER: usize = 1024;
 
+//─────────────────────────────
+//  Capacity limits and eviction
+//─────────────────────────────
+
+/// Which events are evicted first once a capacity limit is exceeded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Evict the events with the oldest timestamps
+    #[default]
+    OldestFirst,
+    /// Evict the events that were committed or read least recently
+    LeastRecentlyUsed,
+}
+
+/// Size limits for a [`MemoryBackend`].
+///
+/// Limits are checked after every commit; events are evicted according to
+/// the policy until the store fits again. The event just committed is never
+/// evicted by its own commit.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CapacityConfig {
+    /// Maximum number of stored events
+    pub max_events: Option<usize>,
+    /// Maximum total size of stored payloads in bytes
+    pub max_bytes: Option<u64>,
+    /// Order in which events are evicted
+    pub policy: EvictionPolicy,
+}
+
+impl CapacityConfig {
+    /// No limits.
+    pub fn unbounded() -> Self {
+        Self::default()
+    }
+
+    /// Limit the number of stored events.
+    pub fn with_max_events(mut self, max_events: usize) -> Self {
+        self.max_events = Some(max_events);
+        self
+    }
+
+    /// Limit the total payload size.
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Set the eviction policy.
+    pub fn with_policy(mut self, policy: EvictionPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    fn is_bounded(&self) -> bool {
+        self.max_events.is_some() || self.max_bytes.is_some()
+    }
+}
+
+/// Notification sent for every event evicted to respect capacity limits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvictionNotice {
+    /// Header of the evicted event
+    pub header: EventHeader,
+    /// Payload bytes freed (zero if the payload is still shared)
+    pub bytes_freed: u64,
+}
+
+/// Running totals of evictions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EvictionMetrics {
+    /// Events evicted
+    pub evicted_events: u64,
+    /// Payload bytes freed by evictions
+    pub evicted_bytes: u64,
+}
+
+/// Position of an event in eviction order: last use (zero unless evicting
+/// least recently used events first), then timestamp.
+type EvictionRank = (u64, DateTime<Utc>);
+
+/// Running totals and eviction order of the stored events.
+///
+/// Updated together with the header and payload maps, under their locks,
+/// so enforcing capacity never has to scan the whole store.
+#[derive(Debug, Default)]
+struct StoreIndex {
+    /// Total size of the stored payloads
+    bytes: u64,
+    /// Number of stored headers referring to each payload
+    references: HashMap<CausalDigest, usize>,
+    /// Eviction rank of each stored event
+    ranks: HashMap<EventId, EvictionRank>,
+    /// Stored events, first to evict first
+    order: BTreeSet<(EvictionRank, EventId)>,
+    tick: u64,
+}
+
+impl StoreIndex {
+    /// Store `header`, adding its payload unless an event already shares it.
+    fn store(
+        &mut self,
+        headers: &mut HashMap<EventId, EventHeader>,
+        payloads: &mut HashMap<CausalDigest, Bytes>,
+        header: &EventHeader,
+        payload: impl FnOnce() -> Bytes,
+        lru: bool,
+    ) {
+        if let std::collections::hash_map::Entry::Vacant(entry) = payloads.entry(header.digest) {
+            let payload = entry.insert(payload());
+            self.bytes += payload.len() as u64;
+        }
+        *self.references.entry(header.digest).or_insert(0) += 1;
+
+        // Recommitting an id replaces the earlier header
+        if let Some(previous) = headers.insert(header.id, header.clone()) {
+            self.release(payloads, &previous.digest);
+        }
+        self.tick += 1;
+        self.rank(header.id, (if lru { self.tick } else { 0 }, header.timestamp));
+    }
+
+    /// Remove the event `id`; returns its header and the payload bytes freed.
+    fn remove(
+        &mut self,
+        headers: &mut HashMap<EventId, EventHeader>,
+        payloads: &mut HashMap<CausalDigest, Bytes>,
+        id: &EventId,
+    ) -> Option<(EventHeader, u64)> {
+        if let Some(rank) = self.ranks.remove(id) {
+            self.order.remove(&(rank, *id));
+        }
+        let header = headers.remove(id)?;
+        let freed = self.release(payloads, &header.digest);
+        Some((header, freed))
+    }
+
+    /// Record a use of `id` for least-recently-used eviction.
+    fn touch(&mut self, id: EventId) {
+        if let Some(&(_, timestamp)) = self.ranks.get(&id) {
+            self.tick += 1;
+            self.rank(id, (self.tick, timestamp));
+        }
+    }
+
+    /// Next event to evict, other than `keep`.
+    fn next_eviction(&self, keep: EventId) -> Option<EventId> {
+        self.order.iter().map(|(_, id)| *id).find(|id| *id != keep)
+    }
+
+    fn rank(&mut self, id: EventId, rank: EvictionRank) {
+        if let Some(previous) = self.ranks.insert(id, rank) {
+            self.order.remove(&(previous, id));
+        }
+        self.order.insert((rank, id));
+    }
+
+    /// Drop one reference to `digest`, removing the payload with the last one.
+    fn release(&mut self, payloads: &mut HashMap<CausalDigest, Bytes>, digest: &CausalDigest) -> u64 {
+        let Some(count) = self.references.get_mut(digest) else {
+            return 0;
+        };
+        *count -= 1;
+        if *count > 0 {
+            return 0;
+        }
+        self.references.remove(digest);
+        let freed = payloads.remove(digest).map_or(0, |payload| payload.len() as u64);
+        self.bytes -= freed;
+        freed
+    }
+}
+
 //─────────────────────────────
 //  In-memory storage backend with WAL
 //─────────────────────────────
@@ -43,12 +225,27 @@ const DEFAULT_BUFFER: usize = 1024;
 #[derive(Debug, Clone)]
 pub struct MemoryBackend {
     headers: Arc<RwLock<HashMap<EventId, EventHeader>>>,
-    payloads: Arc<RwLock<HashMap<CausalDigest, Vec<u8>>>>,
+    payloads: Arc<RwLock<HashMap<CausalDigest, Bytes>>>,
     broadcast_tx: broadcast::Sender<EventHeader>,
     // WAL state management
     wal_entries: Arc<RwLock<HashMap<SequenceNumber, WalEntry>>>,
     wal_sequence: Arc<RwLock<SequenceNumber>>,
     active_transactions: Arc<RwLock<HashMap<TransactionId, WalTransactionState>>>,
+    wal_compaction: WalCompactionPolicy,
+    wal_reclaimed: Arc<std::sync::Mutex<WalCompactionReport>>,
+    transaction_ttl: Option<std::time::Duration>,
+    timeout_tx: broadcast::Sender<TransactionTimedOut>,
+    timed_out: Arc<AtomicU64>,
+    recovery_hooks: RecoveryHooks,
+    // Capacity limits
+    capacity: CapacityConfig,
+    index: Arc<RwLock<StoreIndex>>,
+    eviction_tx: broadcast::Sender<EvictionNotice>,
+    evicted_events: Arc<AtomicU64>,
+    evicted_bytes: Arc<AtomicU64>,
+    // Idempotency keys of recent commits
+    dedup_window: std::time::Duration,
+    idempotency: Arc<RwLock<IdempotencyIndex>>,
 }
 
 /// State tracking for active WAL transactions.
@@ -62,6 +259,21 @@ struct WalTransactionState {
     operations: Vec<WalOperation>,
     /// Sequence numbers for this transaction's entries
     sequences: Vec<SequenceNumber>,
+    /// When the transaction was begun
+    began_at: DateTime<Utc>,
+    /// Savepoints in creation order
+    savepoints: Vec<SavepointMark>,
+}
+
+/// Position of a savepoint within a transaction.
+#[derive(Debug, Clone)]
+struct SavepointMark {
+    /// Savepoint identifier
+    savepoint_id: SavepointId,
+    /// Sequence number of the savepoint's WAL entry
+    sequence: SequenceNumber,
+    /// Number of operations logged up to and including the savepoint
+    operations: usize,
 }
 
 /// State types for WAL transactions.
@@ -100,6 +312,8 @@ impl MemoryBackend {
     /// subscribers before older events are dropped from the live stream.
     pub fn with_buffer_size(buffer_size: usize) -> Self {
         let (broadcast_tx, _) = broadcast::channel(buffer_size);
+        let (eviction_tx, _) = broadcast::channel(buffer_size);
+        let (timeout_tx, _) = broadcast::channel(buffer_size);
         Self {
             headers: Arc::new(RwLock::new(HashMap::new())),
             payloads: Arc::new(RwLock::new(HashMap::new())),
@@ -107,7 +321,188 @@ impl MemoryBackend {
             wal_entries: Arc::new(RwLock::new(HashMap::new())),
             wal_sequence: Arc::new(RwLock::new(0)),
             active_transactions: Arc::new(RwLock::new(HashMap::new())),
+            wal_compaction: WalCompactionPolicy::Manual,
+            wal_reclaimed: Arc::new(std::sync::Mutex::new(WalCompactionReport::default())),
+            transaction_ttl: None,
+            timeout_tx,
+            timed_out: Arc::new(AtomicU64::new(0)),
+            recovery_hooks: RecoveryHooks::new(),
+            capacity: CapacityConfig::unbounded(),
+            index: Arc::new(RwLock::new(StoreIndex::default())),
+            eviction_tx,
+            evicted_events: Arc::new(AtomicU64::new(0)),
+            evicted_bytes: Arc::new(AtomicU64::new(0)),
+            dedup_window: DEFAULT_DEDUP_WINDOW,
+            idempotency: Arc::new(RwLock::new(IdempotencyIndex::new(DEFAULT_DEDUP_WINDOW))),
+        }
+    }
+
+    /// Drop events whose idempotency key was committed less than `window` earlier.
+    pub fn with_dedup_window(self, window: std::time::Duration) -> Self {
+        Self {
+            dedup_window: window,
+            idempotency: Arc::new(RwLock::new(IdempotencyIndex::new(window))),
+            ..self
+        }
+    }
+
+    /// Compact the WAL automatically according to `policy`.
+    pub fn with_wal_compaction(mut self, policy: WalCompactionPolicy) -> Self {
+        self.wal_compaction = policy;
+        self
+    }
+
+    /// Roll back WAL transactions that stay open longer than `ttl`.
+    ///
+    /// Stale transactions are aborted by
+    /// [`abort_stale_transactions`](TransactionTimeouts::abort_stale_transactions),
+    /// which [`spawn_transaction_reaper`](Self::spawn_transaction_reaper)
+    /// calls periodically.
+    pub fn with_transaction_ttl(mut self, ttl: std::time::Duration) -> Self {
+        self.transaction_ttl = Some(ttl);
+        self
+    }
+
+    /// Notify `observer` of replays, rollbacks and errors during
+    /// [`recover`](WriteAheadLog::recover), and let it veto replays.
+    pub fn with_recovery_observer(mut self, observer: Arc<dyn RecoveryObserver>) -> Self {
+        self.recovery_hooks.register(observer);
+        self
+    }
+
+    /// Subscribe to notifications of transactions rolled back by timeouts.
+    pub fn subscribe_transaction_timeouts(&self) -> broadcast::Receiver<TransactionTimedOut> {
+        self.timeout_tx.subscribe()
+    }
+
+    /// Abort stale transactions every `interval` on a background task.
+    ///
+    /// The task runs until the returned handle is aborted.
+    pub fn spawn_transaction_reaper(&self, interval: std::time::Duration) -> tokio::task::JoinHandle<()> {
+        let backend = self.clone();
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            loop {
+                ticks.tick().await;
+                // Failed sweeps are retried on the next tick
+                let _ = backend.abort_stale_transactions(Utc::now()).await;
+            }
+        })
+    }
+
+    /// Creates a memory backend that evicts events to stay within `capacity`.
+    ///
+    /// Useful for long test runs that would otherwise grow without bound.
+    pub fn with_capacity(capacity: CapacityConfig) -> Self {
+        Self {
+            capacity,
+            ..Self::new()
+        }
+    }
+
+    /// Capacity limits of this backend.
+    pub fn capacity(&self) -> &CapacityConfig {
+        &self.capacity
+    }
+
+    /// Subscribe to notifications of evicted events.
+    pub fn subscribe_evictions(&self) -> broadcast::Receiver<EvictionNotice> {
+        self.eviction_tx.subscribe()
+    }
+
+    /// Totals of events and bytes evicted so far.
+    pub fn eviction_metrics(&self) -> EvictionMetrics {
+        EvictionMetrics {
+            evicted_events: self.evicted_events.load(Ordering::Relaxed),
+            evicted_bytes: self.evicted_bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Record a use of `id` for least-recently-used eviction.
+    async fn touch(&self, id: EventId) {
+        if self.capacity.policy == EvictionPolicy::LeastRecentlyUsed {
+            self.index.write().await.touch(id);
+        }
+    }
+
+    /// Evict events until the store fits its capacity limits again.
+    ///
+    /// `keep` (the event just committed) is never evicted. The keys of
+    /// evicted events are forgotten, so retries are committed again.
+    async fn enforce_capacity(&self, keep: EventId, idempotency: &mut IdempotencyIndex) {
+        if !self.capacity.is_bounded() {
+            return;
+        }
+
+        let mut evicted = Vec::new();
+        {
+            let mut headers = self.headers.write().await;
+            let mut payloads = self.payloads.write().await;
+            let mut index = self.index.write().await;
+
+            let over_limit = |headers: &HashMap<EventId, EventHeader>, index: &StoreIndex| {
+                self.capacity.max_events.is_some_and(|max| headers.len() > max)
+                    || self.capacity.max_bytes.is_some_and(|max| index.bytes > max)
+            };
+            while over_limit(&headers, &index) {
+                let Some(id) = index.next_eviction(keep) else {
+                    break;
+                };
+                if let Some((header, bytes_freed)) = index.remove(&mut headers, &mut payloads, &id) {
+                    idempotency.forget(&id);
+                    evicted.push(EvictionNotice { header, bytes_freed });
+                }
+            }
+        }
+
+        for notice in evicted {
+            self.evicted_events.fetch_add(1, Ordering::Relaxed);
+            self.evicted_bytes.fetch_add(notice.bytes_freed, Ordering::Relaxed);
+            // Ignore errors if no subscribers
+            let _ = self.eviction_tx.send(notice);
+        }
+    }
+
+    /// Store an event, broadcast it and enforce capacity limits.
+    ///
+    /// The payload buffer is kept as is, so readers share it with the writer.
+    async fn store_event(&self, header: &EventHeader, payload: Bytes, idempotency: &mut IdempotencyIndex) {
+        {
+            let mut headers = self.headers.write().await;
+            self.store_locked(&mut headers, header, || payload).await;
+        }
+
+        // Broadcast live update (ignore errors if no subscribers)
+        let _ = self.broadcast_tx.send(header.clone());
+
+        self.enforce_capacity(header.id, idempotency).await;
+    }
+
+    /// Store an event while the caller holds the header lock.
+    ///
+    /// Payloads are deduplicated by digest: events with the same content
+    /// share one copy.
+    async fn store_locked(
+        &self,
+        headers: &mut HashMap<EventId, EventHeader>,
+        header: &EventHeader,
+        payload: impl FnOnce() -> Bytes,
+    ) {
+        let mut payloads = self.payloads.write().await;
+        let lru = self.capacity.policy == EvictionPolicy::LeastRecentlyUsed;
+        self.index.write().await.store(headers, &mut payloads, header, payload, lru);
+    }
+
+    /// Commit with idempotency checks, keeping `payload` without copying it.
+    async fn commit_shared(&self, header: &EventHeader, payload: Bytes) -> Result<CommitOutcome> {
+        // Hold the index across the write so concurrent retries see each other
+        let mut idempotency = self.idempotency.write().await;
+        if let Some(original) = idempotency.find(header) {
+            return Ok(CommitOutcome::Duplicate(original));
         }
+        self.store_event(header, payload, &mut idempotency).await;
+        idempotency.record(header);
+        Ok(CommitOutcome::Committed(header.id))
     }
 
     /// Get the next sequence number for WAL entries.
@@ -117,6 +512,40 @@ impl MemoryBackend {
         *seq
     }
 
+    /// Collect all headers matching `predicate` into a timestamp-ordered stream.
+    async fn scan<F>(&self, predicate: F) -> EventHeaderStream<'_>
+    where
+        F: Fn(&EventHeader) -> bool,
+    {
+        let mut matched: Vec<EventHeader> = self
+            .headers
+            .read()
+            .await
+            .values()
+            .filter(|header| predicate(header))
+            .cloned()
+            .collect();
+        matched.sort_by_key(|header| header.timestamp);
+
+        Box::pin(futures::stream::iter(matched.into_iter().map(Ok)))
+    }
+
+    /// Adapt a broadcast receiver into a stream, surfacing lag as an error.
+    fn live_stream(
+        rx: broadcast::Receiver<EventHeader>,
+    ) -> impl futures::Stream<Item = Result<EventHeader>> + Send + 'static {
+        futures::stream::unfold(rx, |mut rx| async move {
+            match rx.recv().await {
+                Ok(header) => Some((Ok(header), rx)),
+                Err(broadcast::error::RecvError::Lagged(missed)) => Some((
+                    Err(anyhow::anyhow!("Live subscriber lagged behind by {} events", missed)),
+                    rx,
+                )),
+                Err(broadcast::error::RecvError::Closed) => None,
+            }
+        })
+    }
+
     /// Subscribe to the live event stream.
     ///
     /// Returns a receiver that will receive copies of all event headers
@@ -154,38 +583,258 @@ impl MemoryBackend {
         self.wal_entries.write().await.clear();
         *self.wal_sequence.write().await = 0;
         self.active_transactions.write().await.clear();
+        *self.index.write().await = StoreIndex::default();
+        self.idempotency.write().await.clear();
     }
 }
 
 #[async_trait]
 impl StorageBackend for MemoryBackend {
     async fn commit(&self, header: &EventHeader, payload: &[u8]) -> Result<()> {
-        // Store payload (deduplicated by digest)
-        // Multiple headers can reference the same payload via shared digest
-        self.payloads
-            .write()
-            .await
-            .entry(header.digest)
-            .or_insert_with(|| payload.to_vec());
+        self.commit_deduplicated(header, payload).await.map(|_| ())
+    }
 
-        // Store header
-        self.headers
-            .write()
+    async fn commit_bytes(&self, header: &EventHeader, payload: Bytes) -> Result<()> {
+        self.commit_shared(header, payload).await.map(|_| ())
+    }
+
+    async fn header(&self, id: &EventId) -> Result<Option<EventHeader>> {
+        let header = self.headers.read().await.get(id).cloned();
+        if header.is_some() {
+            self.touch(*id).await;
+        }
+        Ok(header)
+    }
+
+    async fn payload_bytes(&self, digest: &CausalDigest) -> Result<Option<Vec<u8>>> {
+        Ok(self.payloads.read().await.get(digest).map(|payload| payload.to_vec()))
+    }
+
+    async fn payload(&self, digest: &CausalDigest) -> Result<Option<Bytes>> {
+        Ok(self.payloads.read().await.get(digest).cloned())
+    }
+}
+
+#[async_trait]
+impl QueryableStorage for MemoryBackend {
+    async fn events_by_kind(&self, kind: &str) -> Result<EventHeaderStream<'_>> {
+        Ok(self.scan(|header| header.kind == kind).await)
+    }
+
+    async fn events_in_range(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<EventHeaderStream<'_>> {
+        Ok(self
+            .scan(|header| header.timestamp >= from && header.timestamp < to)
+            .await)
+    }
+
+    async fn events_by_intent(&self, intent: &IntentId) -> Result<EventHeaderStream<'_>> {
+        Ok(self.scan(|header| header.intent == *intent).await)
+    }
+}
+
+/// The memory backend does not record commit order, so sequence positions
+/// are counted in timestamp order.
+#[async_trait]
+impl ReplayableStorage for MemoryBackend {
+    async fn subscribe_from(&self, from: ReplayFrom) -> Result<EventHeaderStream<'_>> {
+        // Subscribe before snapshotting so nothing committed in between is missed
+        let live = Self::live_stream(self.subscribe());
+
+        let history: EventHeaderStream<'_> = match from {
+            ReplayFrom::Timestamp(timestamp) => self.scan(|header| header.timestamp >= timestamp).await,
+            ReplayFrom::Sequence(skip) => {
+                Box::pin(self.scan(|_| true).await.skip(usize::try_from(skip)?))
+            }
+        };
+
+        Ok(replay_then_live(history, live))
+    }
+}
+
+#[async_trait]
+impl NamespacedStorage for MemoryBackend {
+    async fn namespaces(&self) -> Result<Vec<NamespaceStats>> {
+        let mut by_namespace: std::collections::BTreeMap<String, NamespaceStats> = Default::default();
+        for header in self.headers.read().await.values() {
+            let namespace = header.namespace_or_default();
+            let stats = by_namespace
+                .entry(namespace.to_string())
+                .or_insert_with(|| NamespaceStats::empty(namespace));
+            stats.event_count += 1;
+            stats.oldest_event = match stats.oldest_event {
+                Some(oldest) if oldest <= header.timestamp => Some(oldest),
+                _ => Some(header.timestamp),
+            };
+            stats.newest_event = stats.newest_event.max(Some(header.timestamp));
+        }
+        Ok(by_namespace.into_values().collect())
+    }
+
+    async fn prune_namespace(
+        &self,
+        namespace: &str,
+        before: Option<DateTime<Utc>>,
+    ) -> Result<usize> {
+        let ids: Vec<EventId> = self
+            .headers
+            .read()
             .await
-            .insert(header.id, header.clone());
+            .values()
+            .filter(|h| h.namespace_or_default() == namespace)
+            .filter(|h| before.is_none_or(|before| h.timestamp < before))
+            .map(|h| h.id)
+            .collect();
+        self.remove_events(&ids).await
+    }
 
-        // Broadcast live update (ignore errors if no subscribers)
-        let _ = self.broadcast_tx.send(header.clone());
+    async fn subscribe_namespace(&self, namespace: &str) -> Result<EventHeaderStream<'_>> {
+        Ok(filter_namespace(Box::pin(Self::live_stream(self.subscribe())), namespace))
+    }
+}
+
+#[async_trait]
+impl DeduplicatingStorage for MemoryBackend {
+    async fn commit_deduplicated(&self, header: &EventHeader, payload: &[u8]) -> Result<CommitOutcome> {
+        self.commit_shared(header, Bytes::copy_from_slice(payload)).await
+    }
+
+    fn dedup_window(&self) -> std::time::Duration {
+        self.dedup_window
+    }
+}
+
+/// Preconditions are checked under the header write lock, so no other
+/// commit can interleave between the check and the write.
+#[async_trait]
+impl ConditionalStorage for MemoryBackend {
+    async fn commit_if(
+        &self,
+        header: &EventHeader,
+        payload: &[u8],
+        precondition: &Precondition,
+    ) -> Result<()> {
+        // A retry of a committed event is a no-op, as for `commit`
+        let mut idempotency = self.idempotency.write().await;
+        if idempotency.find(header).is_some() {
+            return Ok(());
+        }
 
+        let mut headers = self.headers.write().await;
+        precondition
+            .evaluate(
+                &|id| headers.contains_key(id),
+                &|intent| intent_head(headers.values().filter(|h| h.intent == *intent)),
+            )
+            .map_err(StorageError::Conflict)?;
+
+        self.store_locked(&mut headers, header, || Bytes::copy_from_slice(payload)).await;
+        drop(headers);
+
+        let _ = self.broadcast_tx.send(header.clone());
+        self.enforce_capacity(header.id, &mut idempotency).await;
+        idempotency.record(header);
         Ok(())
     }
 
-    async fn header(&self, id: &EventId) -> Result<Option<EventHeader>> {
-        Ok(self.headers.read().await.get(id).cloned())
+    async fn intent_head(&self, intent: &IntentId) -> Result<Option<EventHeader>> {
+        let headers = self.headers.read().await;
+        let head = intent_head(headers.values().filter(|h| h.intent == *intent));
+        Ok(head.and_then(|id| headers.get(&id).cloned()))
     }
+}
 
-    async fn payload_bytes(&self, digest: &CausalDigest) -> Result<Option<Vec<u8>>> {
-        Ok(self.payloads.read().await.get(digest).cloned())
+#[async_trait]
+impl CompactableStorage for MemoryBackend {
+    async fn remove_events(&self, ids: &[EventId]) -> Result<usize> {
+        let mut idempotency = self.idempotency.write().await;
+        let mut headers = self.headers.write().await;
+        let mut payloads = self.payloads.write().await;
+        let mut index = self.index.write().await;
+
+        // Payloads are dropped once no remaining header refers to them
+        let mut removed = 0;
+        for id in ids {
+            if index.remove(&mut headers, &mut payloads, id).is_some() {
+                idempotency.forget(id);
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+#[async_trait]
+impl SnapshotStorage for MemoryBackend {
+    async fn export_snapshot(
+        &self,
+        writer: &mut (dyn futures::io::AsyncWrite + Unpin + Send),
+    ) -> Result<SnapshotManifest> {
+        // Copy everything out first so no lock is held across writer I/O
+        let mut headers: Vec<EventHeader> = self.headers.read().await.values().cloned().collect();
+        headers.sort_by_key(|header| header.timestamp);
+        let payloads = self.payloads.read().await.clone();
+
+        let manifest = SnapshotManifest {
+            version: toka_store_core::snapshot::SNAPSHOT_VERSION,
+            created_at: Utc::now(),
+            wal_sequence: *self.wal_sequence.read().await,
+            events: headers.len() as u64,
+            payloads: headers
+                .iter()
+                .map(|header| header.digest)
+                .collect::<std::collections::HashSet<_>>()
+                .len() as u64,
+        };
+
+        let mut snapshot = SnapshotWriter::begin(writer, &manifest).await?;
+        for header in &headers {
+            let payload = payloads.get(&header.digest).ok_or_else(|| {
+                anyhow::anyhow!("payload missing for event {}", header.id)
+            })?;
+            snapshot.write_event(header, payload).await?;
+        }
+        snapshot.finish().await?;
+
+        Ok(manifest)
+    }
+
+    async fn import_snapshot(
+        &self,
+        reader: &mut (dyn futures::io::AsyncRead + Unpin + Send),
+    ) -> Result<SnapshotManifest> {
+        let mut snapshot = SnapshotReader::open(reader).await?;
+        while let Some((header, payload)) = snapshot.next_event().await? {
+            self.commit(&header, &payload).await?;
+        }
+
+        let manifest = snapshot.manifest().clone();
+        let mut sequence = self.wal_sequence.write().await;
+        *sequence = (*sequence).max(manifest.wal_sequence);
+        Ok(manifest)
+    }
+}
+
+#[async_trait]
+impl StorageStats for MemoryBackend {
+    async fn storage_stats(&self) -> Result<StorageStatistics> {
+        let headers = self.headers.read().await;
+        let payloads = self.payloads.read().await;
+
+        Ok(StorageStatistics {
+            event_count: headers.len() as u64,
+            payload_count: payloads.len() as u64,
+            bytes_used: self.index.read().await.bytes,
+            oldest_event: headers.values().map(|header| header.timestamp).min(),
+            newest_event: headers.values().map(|header| header.timestamp).max(),
+            wal_depth: self.wal_entries.read().await.len() as u64,
+            open_transactions: self.open_transaction_count().await,
+            transactions_timed_out: self.timed_out.load(Ordering::Relaxed),
+        })
     }
 }
 
@@ -214,6 +863,8 @@ impl WriteAheadLog for MemoryBackend {
             state: WalTransactionStateType::Active,
             operations: vec![wal_entry.operation],
             sequences: vec![sequence],
+            began_at: wal_entry.timestamp,
+            savepoints: Vec::new(),
         };
 
         self.active_transactions
@@ -302,7 +953,7 @@ impl WriteAheadLog for MemoryBackend {
             match operation {
                 WalOperation::CommitEvent { header, payload } => {
                     // Apply the event to storage
-                    self.commit(&header, &payload).await?;
+                    self.commit_bytes(&header, payload).await?;
                 }
                 _ => {
                     // Other operations don't need to be applied to storage
@@ -322,11 +973,12 @@ impl WriteAheadLog for MemoryBackend {
         };
         self.wal_entries.write().await.insert(commit_sequence, commit_wal_entry);
 
-        // Mark all WAL entries for this transaction as committed
+        // Mark all WAL entries for this transaction as committed, except
+        // those discarded by a savepoint rollback
         {
             let mut wal_entries = self.wal_entries.write().await;
             for entry in wal_entries.values_mut() {
-                if entry.transaction_id == transaction_id {
+                if entry.transaction_id == transaction_id && entry.state != WalEntryState::RolledBack {
                     entry.state = WalEntryState::Committed;
                 }
             }
@@ -397,12 +1049,77 @@ impl WriteAheadLog for MemoryBackend {
         Ok(())
     }
 
+    async fn savepoint(&self, transaction_id: TransactionId) -> Result<SavepointId> {
+        let savepoint_id = Uuid::new_v4();
+        let operation = WalOperation::Savepoint { transaction_id, savepoint_id };
+
+        // Hold the transaction lock so no entry slips in before the mark
+        let mut transactions = self.active_transactions.write().await;
+        let tx_state = Self::active_state(&mut transactions, transaction_id)?;
+        let sequence = self.next_sequence().await;
+        self.wal_entries.write().await.insert(sequence, WalEntry {
+            id: Uuid::new_v4(),
+            transaction_id,
+            sequence,
+            timestamp: chrono::Utc::now(),
+            operation: operation.clone(),
+            state: WalEntryState::Pending,
+        });
+
+        tx_state.operations.push(operation);
+        tx_state.sequences.push(sequence);
+        tx_state.savepoints.push(SavepointMark {
+            savepoint_id,
+            sequence,
+            operations: tx_state.operations.len(),
+        });
+        Ok(savepoint_id)
+    }
+
+    async fn rollback_to_savepoint(&self, transaction_id: TransactionId, savepoint_id: SavepointId) -> Result<()> {
+        let mut transactions = self.active_transactions.write().await;
+        let tx_state = Self::active_state(&mut transactions, transaction_id)?;
+        let position = tx_state
+            .savepoints
+            .iter()
+            .position(|mark| mark.savepoint_id == savepoint_id)
+            .ok_or(StorageError::SavepointNotFound { transaction_id, savepoint_id })?;
+
+        // Release later savepoints and forget the discarded operations
+        let mark = tx_state.savepoints[position].clone();
+        tx_state.savepoints.truncate(position + 1);
+        tx_state.operations.truncate(mark.operations);
+        tx_state.sequences.truncate(mark.operations);
+
+        let operation = WalOperation::RollbackToSavepoint { transaction_id, savepoint_id };
+        let sequence = self.next_sequence().await;
+        let mut wal_entries = self.wal_entries.write().await;
+        for entry in wal_entries.values_mut() {
+            if entry.transaction_id == transaction_id && entry.sequence > mark.sequence {
+                entry.state = WalEntryState::RolledBack;
+            }
+        }
+        wal_entries.insert(sequence, WalEntry {
+            id: Uuid::new_v4(),
+            transaction_id,
+            sequence,
+            timestamp: chrono::Utc::now(),
+            operation: operation.clone(),
+            state: WalEntryState::Pending,
+        });
+
+        tx_state.operations.push(operation);
+        tx_state.sequences.push(sequence);
+        Ok(())
+    }
+
     async fn recover(&self) -> Result<WalRecoveryResult> {
         let mut result = WalRecoveryResult {
             entries_recovered: 0,
             transactions_rolled_back: 0,
             transactions_committed: 0,
             entries_checkpointed: 0,
+            entries_quarantined: 0,
             recovery_errors: Vec::new(),
         };
 
@@ -435,20 +1152,28 @@ impl WriteAheadLog for MemoryBackend {
 
             if has_commit {
                 // Apply all committed operations
+                let (mut applied, mut skipped) = (0, 0);
                 for entry in entries {
                     if entry.state == WalEntryState::Committed {
                         match &entry.operation {
                             WalOperation::CommitEvent { header, payload } => {
-                                if let Err(e) = self.commit(header, payload).await {
-                                    result.recovery_errors.push(format!(
-                                        "Failed to apply committed event: {}", e
-                                    ));
+                                if self.recovery_hooks.before_replay(&entry) == ReplayDecision::Skip {
+                                    skipped += 1;
+                                    continue;
+                                }
+                                if let Err(e) = self.commit_bytes(header, payload.clone()).await {
+                                    let error = format!("Failed to apply committed event: {}", e);
+                                    self.recovery_hooks.on_error(Some(transaction_id), &error);
+                                    result.recovery_errors.push(error);
+                                } else {
+                                    applied += 1;
                                 }
                             }
                             _ => {} // Other operations don't need reapplication
                         }
                     }
                 }
+                self.recovery_hooks.on_transaction_replayed(transaction_id, applied, skipped);
                 result.transactions_committed += 1;
             } else {
                 // Check if transaction is incomplete (no commit or rollback)
@@ -459,10 +1184,11 @@ impl WriteAheadLog for MemoryBackend {
                 if !has_rollback {
                     // Incomplete transaction - roll it back
                     if let Err(e) = self.rollback_transaction(transaction_id).await {
-                        result.recovery_errors.push(format!(
-                            "Failed to rollback transaction {}: {}", transaction_id, e
-                        ));
+                        let error = format!("Failed to rollback transaction {}: {}", transaction_id, e);
+                        self.recovery_hooks.on_error(Some(transaction_id), &error);
+                        result.recovery_errors.push(error);
                     } else {
+                        self.recovery_hooks.on_rollback(transaction_id, entries.len());
                         result.transactions_rolled_back += 1;
                     }
                 }
@@ -484,28 +1210,179 @@ impl WriteAheadLog for MemoryBackend {
             }
         }
 
-        // Optionally remove old checkpointed entries to free memory
-        // For now we keep them for consistency with SQLite backend
-        
+        drop(wal_entries);
+
+        if let Some(before) = self.wal_compaction.compaction_bound(sequence) {
+            self.compact_wal(before).await?;
+        }
         Ok(())
     }
 
     async fn current_sequence(&self) -> Result<SequenceNumber> {
         Ok(*self.wal_sequence.read().await)
     }
+
+    async fn wal_health(&self) -> Result<WalHealth> {
+        let mut health = WalHealth::new(*self.wal_sequence.read().await);
+        for entry in self.wal_entries.read().await.values() {
+            let bytes = encode_wal_operation(&entry.operation)?.len() as u64;
+            health.record_entry(&entry.state, entry.timestamp, bytes);
+        }
+        health.active_transactions = self.open_transaction_count().await;
+        Ok(health)
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serde::{Deserialize, Serialize};
-    use uuid::Uuid;
-    use toka_store_core::{create_event_header, prelude::*};
+#[async_trait]
+impl WalCompaction for MemoryBackend {
+    async fn compact_wal(&self, before: SequenceNumber) -> Result<WalCompactionReport> {
+        let mut wal_entries = self.wal_entries.write().await;
+        let removable = compactable_transactions(wal_entries.values(), before);
+        if removable.is_empty() {
+            return Ok(WalCompactionReport::default());
+        }
 
-    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-    struct TestEvent {
-        message: String,
-        value: i32,
+        let mut report = WalCompactionReport {
+            transactions_removed: removable.len() as u64,
+            ..WalCompactionReport::default()
+        };
+        wal_entries.retain(|_, entry| {
+            if !removable.contains(&entry.transaction_id) {
+                return true;
+            }
+            match entry.state {
+                WalEntryState::RolledBack => report.rolled_back_entries += 1,
+                _ => report.checkpointed_entries += 1,
+            }
+            false
+        });
+        drop(wal_entries);
+
+        self.active_transactions
+            .write()
+            .await
+            .retain(|transaction_id, _| !removable.contains(transaction_id));
+        self.wal_reclaimed.lock().unwrap().absorb(&report);
+        Ok(report)
+    }
+
+    fn wal_compaction_totals(&self) -> WalCompactionReport {
+        *self.wal_reclaimed.lock().unwrap()
+    }
+}
+
+#[async_trait]
+impl WalSource for MemoryBackend {
+    async fn wal_entries_after(&self, after: SequenceNumber, limit: usize) -> Result<Vec<WalEntry>> {
+        let mut entries: Vec<WalEntry> = self
+            .wal_entries
+            .read()
+            .await
+            .values()
+            .filter(|entry| entry.sequence > after)
+            .cloned()
+            .collect();
+        entries.sort_by_key(|entry| entry.sequence);
+        entries.truncate(limit);
+        Ok(entries)
+    }
+}
+
+impl MemoryBackend {
+    /// State of `transaction_id`, if it is still active.
+    fn active_state(
+        transactions: &mut HashMap<TransactionId, WalTransactionState>,
+        transaction_id: TransactionId,
+    ) -> Result<&mut WalTransactionState> {
+        match transactions.get_mut(&transaction_id) {
+            Some(tx_state) if tx_state.state == WalTransactionStateType::Active => Ok(tx_state),
+            Some(tx_state) => Err(anyhow::anyhow!(
+                "Transaction {} is not active (state: {:?})",
+                transaction_id,
+                tx_state.state
+            )),
+            None => Err(anyhow::anyhow!("Transaction {} not found", transaction_id)),
+        }
+    }
+
+    /// Number of transactions that are still active.
+    async fn open_transaction_count(&self) -> u64 {
+        self.active_transactions
+            .read()
+            .await
+            .values()
+            .filter(|tx_state| tx_state.state == WalTransactionStateType::Active)
+            .count() as u64
+    }
+}
+
+#[async_trait]
+impl TransactionTimeouts for MemoryBackend {
+    fn transaction_ttl(&self) -> Option<std::time::Duration> {
+        self.transaction_ttl
+    }
+
+    async fn abort_stale_transactions(&self, now: DateTime<Utc>) -> Result<Vec<TransactionTimedOut>> {
+        let Some(ttl) = self.transaction_ttl else {
+            return Ok(Vec::new());
+        };
+        let stale: Vec<_> = self
+            .active_transactions
+            .read()
+            .await
+            .values()
+            .filter(|tx_state| tx_state.state == WalTransactionStateType::Active)
+            .filter(|tx_state| is_stale(tx_state.began_at, now, ttl))
+            .map(|tx_state| (tx_state.transaction_id, tx_state.began_at, tx_state.sequences.len()))
+            .collect();
+
+        let mut aborted = Vec::new();
+        for (transaction_id, began_at, entries) in stale {
+            // The owner may have finished the transaction in the meantime
+            if self.rollback_transaction(transaction_id).await.is_err() {
+                continue;
+            }
+            let notice = TransactionTimedOut {
+                transaction_id,
+                began_at,
+                aborted_at: Utc::now(),
+                entries,
+            };
+            self.timed_out.fetch_add(1, Ordering::Relaxed);
+            // Ignore errors if no subscribers
+            let _ = self.timeout_tx.send(notice.clone());
+            aborted.push(notice);
+        }
+        Ok(aborted)
+    }
+
+    fn transactions_timed_out(&self) -> u64 {
+        self.timed_out.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+    use toka_store_core::{create_event_header, prelude::*};
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    struct TestEvent {
+        message: String,
+        value: i32,
+    }
+
+    impl TypedEvent for TestEvent {
+        const KIND: &'static str = "test.typed";
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct OtherEvent;
+
+    impl TypedEvent for OtherEvent {
+        const KIND: &'static str = "test.other";
     }
 
     #[tokio::test]
@@ -543,6 +1420,152 @@ mod tests {
         assert_eq!(retrieved_event, event);
     }
 
+    fn capacity_event(value: i32) -> (EventHeader, Vec<u8>) {
+        let event = TestEvent {
+            message: "capacity".to_string(),
+            value,
+        };
+        let header = create_event_header(&[], Uuid::new_v4(), "test.capacity".to_string(), &event).unwrap();
+        (header, rmp_serde::to_vec_named(&event).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_capacity_eviction() {
+        let backend = MemoryBackend::with_capacity(
+            CapacityConfig::unbounded()
+                .with_max_events(2)
+                .with_policy(EvictionPolicy::LeastRecentlyUsed),
+        );
+        let mut evictions = backend.subscribe_evictions();
+
+        let (a, a_bytes) = capacity_event(1);
+        let (b, b_bytes) = capacity_event(2);
+        let (c, c_bytes) = capacity_event(3);
+        backend.commit(&a, &a_bytes).await.unwrap();
+        backend.commit(&b, &b_bytes).await.unwrap();
+
+        // Reading `a` makes `b` the least recently used event
+        backend.header(&a.id).await.unwrap();
+        backend.commit(&c, &c_bytes).await.unwrap();
+
+        assert!(backend.header(&a.id).await.unwrap().is_some());
+        assert!(backend.header(&b.id).await.unwrap().is_none());
+        assert!(backend.header(&c.id).await.unwrap().is_some());
+        assert!(backend.payload_bytes(&b.digest).await.unwrap().is_none());
+
+        let notice = evictions.recv().await.unwrap();
+        assert_eq!(notice.header, b);
+        assert_eq!(notice.bytes_freed, b_bytes.len() as u64);
+        assert_eq!(
+            backend.eviction_metrics(),
+            EvictionMetrics {
+                evicted_events: 1,
+                evicted_bytes: b_bytes.len() as u64,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_capacity_max_bytes() {
+        let (first, first_bytes) = capacity_event(1);
+        let backend = MemoryBackend::with_capacity(
+            CapacityConfig::unbounded().with_max_bytes(first_bytes.len() as u64),
+        );
+
+        backend.commit(&first, &first_bytes).await.unwrap();
+        assert_eq!(backend.eviction_metrics(), EvictionMetrics::default());
+
+        // The newest event is kept even though the oldest-first policy runs
+        let (second, second_bytes) = capacity_event(2);
+        backend.commit(&second, &second_bytes).await.unwrap();
+        assert!(backend.header(&first.id).await.unwrap().is_none());
+        assert!(backend.header(&second.id).await.unwrap().is_some());
+        assert_eq!(backend.eviction_metrics().evicted_events, 1);
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_eviction_is_committed() {
+        let backend = MemoryBackend::with_capacity(CapacityConfig::unbounded().with_max_events(1));
+        let (original, original_bytes) = capacity_event(1);
+        let original = original.with_idempotency_key("emit-1");
+        let (other, other_bytes) = capacity_event(1);
+        backend.commit_deduplicated(&original, &original_bytes).await.unwrap();
+
+        // `other` shares the payload, so evicting `original` frees nothing
+        backend.commit(&other, &other_bytes).await.unwrap();
+        assert!(backend.header(&original.id).await.unwrap().is_none());
+        assert_eq!(backend.eviction_metrics().evicted_bytes, 0);
+        let stats = backend.storage_stats().await.unwrap();
+        assert_eq!(stats.bytes_used, other_bytes.len() as u64);
+
+        let (retry, retry_bytes) = capacity_event(2);
+        let retry = retry.with_idempotency_key("emit-1");
+        let outcome = backend.commit_deduplicated(&retry, &retry_bytes).await.unwrap();
+        assert_eq!(outcome, CommitOutcome::Committed(retry.id));
+        assert_eq!(backend.storage_stats().await.unwrap().bytes_used, retry_bytes.len() as u64);
+    }
+
+    #[tokio::test]
+    async fn test_idempotency_key_deduplication() {
+        let backend = MemoryBackend::new();
+        let (original, original_bytes) = capacity_event(1);
+        let original = original.with_idempotency_key("emit-1");
+        let (retry, retry_bytes) = capacity_event(1);
+        let retry = retry.with_idempotency_key("emit-1");
+
+        let outcome = backend.commit_deduplicated(&original, &original_bytes).await.unwrap();
+        assert_eq!(outcome, CommitOutcome::Committed(original.id));
+
+        // The re-emitted event resolves to the original and is not stored
+        let outcome = backend.commit_deduplicated(&retry, &retry_bytes).await.unwrap();
+        assert_eq!(outcome, CommitOutcome::Duplicate(original.id));
+        backend.commit(&retry, &retry_bytes).await.unwrap();
+        assert!(backend.header(&retry.id).await.unwrap().is_none());
+        assert_eq!(backend.event_count().await, 1);
+
+        // Once the original is removed the key can be used again
+        backend.remove_events(&[original.id]).await.unwrap();
+        let outcome = backend.commit_deduplicated(&retry, &retry_bytes).await.unwrap();
+        assert_eq!(outcome, CommitOutcome::Committed(retry.id));
+    }
+
+    #[tokio::test]
+    async fn test_payloads_bulk() {
+        let backend = MemoryBackend::new();
+        let (first, first_bytes) = capacity_event(1);
+        let (second, second_bytes) = capacity_event(2);
+        backend.commit(&first, &first_bytes).await.unwrap();
+        backend.commit(&second, &second_bytes).await.unwrap();
+
+        let payloads = backend
+            .payloads_bulk(&[first.digest, second.digest, first.digest, [0u8; 32]])
+            .await
+            .unwrap();
+        assert_eq!(payloads.len(), 2);
+        assert_eq!(payloads[&first.digest], first_bytes);
+        assert_eq!(payloads[&second.digest], second_bytes);
+    }
+
+    #[tokio::test]
+    async fn test_bytes_payloads_are_shared() {
+        let backend = MemoryBackend::new();
+        let (direct, direct_bytes) = capacity_event(1);
+        let direct_bytes = Bytes::from(direct_bytes);
+        backend.commit_bytes(&direct, direct_bytes.clone()).await.unwrap();
+        let stored = backend.payload(&direct.digest).await.unwrap().unwrap();
+        assert_eq!(stored.as_ptr(), direct_bytes.as_ptr());
+
+        // Payloads logged in the WAL reach the store without a copy too
+        let (logged, logged_bytes) = capacity_event(2);
+        let logged_bytes = Bytes::from(logged_bytes);
+        let tx_id = backend.begin_transaction().await.unwrap();
+        backend.commit_bytes_with_wal(tx_id, &logged, logged_bytes.clone()).await.unwrap();
+        backend.commit_transaction(tx_id).await.unwrap();
+        let stored = backend.payload(&logged.digest).await.unwrap().unwrap();
+        assert_eq!(stored.as_ptr(), logged_bytes.as_ptr());
+        assert_eq!(backend.payload_bytes(&logged.digest).await.unwrap(), Some(logged_bytes.to_vec()));
+    }
+
     #[tokio::test]
     async fn test_missing_events() {
         let backend = MemoryBackend::new();
@@ -595,6 +1618,14 @@ mod tests {
         // Should have 2 events but only 1 unique payload
         assert_eq!(backend.event_count().await, 2);
         assert_eq!(backend.payload_count().await, 1);
+
+        let stats = backend.storage_stats().await.unwrap();
+        assert_eq!(stats.event_count, 2);
+        assert_eq!(stats.payload_count, 1);
+        assert_eq!(stats.bytes_used, payload_bytes.len() as u64);
+        assert_eq!(stats.oldest_event, Some(header1.timestamp.min(header2.timestamp)));
+        assert_eq!(stats.newest_event, Some(header1.timestamp.max(header2.timestamp)));
+        assert_eq!(stats.wal_depth, 0);
     }
 
     #[tokio::test]
@@ -658,6 +1689,644 @@ mod tests {
         assert!(result.is_none());
     }
 
+    #[tokio::test]
+    async fn test_typed_commit_and_fetch() {
+        let backend = MemoryBackend::new();
+        let event = TestEvent { message: "typed".to_string(), value: 5 };
+
+        let header = backend.commit_typed(&[], Uuid::new_v4(), &event).await.unwrap();
+        assert_eq!(header.kind, "test.typed");
+
+        let (fetched_header, fetched) = backend.fetch_typed::<TestEvent>(&header.id).await.unwrap().unwrap();
+        assert_eq!(fetched_header, header);
+        assert_eq!(fetched, event);
+
+        assert!(backend.fetch_typed::<TestEvent>(&Uuid::new_v4()).await.unwrap().is_none());
+        assert!(backend.fetch_typed::<OtherEvent>(&header.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_compact_intent() {
+        let backend = MemoryBackend::new();
+        let intent = Uuid::new_v4();
+
+        let mut headers = Vec::new();
+        for (i, kind) in ["task.progress", "task.progress", "task.progress", "task.done"].iter().enumerate() {
+            let event = TestEvent { message: kind.to_string(), value: i as i32 };
+            let header = create_event_header(&[], intent, kind.to_string(), &event).unwrap();
+            backend.commit(&header, &rmp_serde::to_vec_named(&event).unwrap()).await.unwrap();
+            headers.push(header);
+        }
+
+        let policy = CompactionPolicy::default().with_kind("task.progress", KindCompaction::KeepLatest);
+        let summary_header = backend.compact_intent(&intent, &policy).await.unwrap().unwrap();
+
+        // Two intermediate progress events folded, latest progress and completion kept
+        assert_eq!(backend.event_count().await, 3);
+        assert_eq!(backend.payload_count().await, 3);
+        assert!(backend.header(&headers[0].id).await.unwrap().is_none());
+        assert!(backend.header(&headers[2].id).await.unwrap().is_some());
+
+        let payload = backend.payload_bytes(&summary_header.digest).await.unwrap().unwrap();
+        let summary: CompactionSummary = rmp_serde::from_slice(&payload).unwrap();
+        assert_eq!(summary.archived_events, vec![headers[0].id, headers[1].id]);
+        assert_eq!(summary_header.parents[0], headers[3].id);
+
+        // Compacting again finds nothing new to fold
+        assert!(backend.compact_intent(&intent, &policy).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_from_replays_then_follows_live() {
+        let backend = MemoryBackend::new();
+        let start = chrono::Utc::now();
+
+        let event = TestEvent { message: "replay".to_string(), value: 1 };
+        let old = create_event_header(&[], Uuid::new_v4(), "test.replay".to_string(), &event).unwrap();
+        backend.commit(&old, &rmp_serde::to_vec_named(&event).unwrap()).await.unwrap();
+
+        let mut stream = backend.subscribe_from(ReplayFrom::Timestamp(start)).await.unwrap();
+
+        let event = TestEvent { message: "live".to_string(), value: 2 };
+        let live = create_event_header(&[], Uuid::new_v4(), "test.replay".to_string(), &event).unwrap();
+        backend.commit(&live, &rmp_serde::to_vec_named(&event).unwrap()).await.unwrap();
+
+        assert_eq!(stream.next().await.unwrap().unwrap().id, old.id);
+        assert_eq!(stream.next().await.unwrap().unwrap().id, live.id);
+
+        let mut stream = backend.subscribe_from(ReplayFrom::Sequence(1)).await.unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap().id, live.id);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_typed_decodes_payloads() {
+        let backend = MemoryBackend::new();
+        let mut stream = backend.subscribe_typed::<TestEvent>("test.typed").await.unwrap();
+
+        let other = create_event_header(&[], Uuid::new_v4(), "test.other".to_string(), &1u32).unwrap();
+        backend.commit(&other, &rmp_serde::to_vec_named(&1u32).unwrap()).await.unwrap();
+
+        let malformed = create_event_header(&[], Uuid::new_v4(), "test.typed".to_string(), &7u32).unwrap();
+        backend.commit(&malformed, &rmp_serde::to_vec_named(&7u32).unwrap()).await.unwrap();
+
+        let event = TestEvent { message: "typed".to_string(), value: 3 };
+        let header = create_event_header(&[], Uuid::new_v4(), "test.typed".to_string(), &event).unwrap();
+        backend.commit(&header, &rmp_serde::to_vec_named(&event).unwrap()).await.unwrap();
+
+        // The undecodable event is reported and the stream keeps going
+        let failure = stream.next().await.unwrap().unwrap_err();
+        assert_eq!(failure.header().unwrap().id, malformed.id);
+
+        let decoded = stream.next().await.unwrap().unwrap();
+        assert_eq!(decoded.header.id, header.id);
+        assert_eq!(decoded.payload, event);
+    }
+
+    #[tokio::test]
+    async fn test_outbox_relay_at_least_once() {
+        use std::sync::Mutex;
+
+        #[derive(Default)]
+        struct Collector {
+            published: Mutex<Vec<TestEvent>>,
+            fail: std::sync::atomic::AtomicBool,
+        }
+
+        #[async_trait]
+        impl OutboxPublisher<TestEvent> for Arc<Collector> {
+            async fn publish(&self, message: &TestEvent) -> Result<()> {
+                if self.fail.load(std::sync::atomic::Ordering::SeqCst) {
+                    anyhow::bail!("bus unavailable");
+                }
+                self.published.lock().unwrap().push(message.clone());
+                Ok(())
+            }
+        }
+
+        let backend = Arc::new(MemoryBackend::new());
+        let intent = Uuid::new_v4();
+
+        // State change and outbox message commit together
+        let tx_id = backend.begin_transaction().await.unwrap();
+        let state = TestEvent { message: "state".to_string(), value: 1 };
+        let header = create_event_header(&[], intent, "test.state".to_string(), &state).unwrap();
+        backend.commit_with_wal(tx_id, &header, &rmp_serde::to_vec_named(&state).unwrap()).await.unwrap();
+        let message = TestEvent { message: "notify".to_string(), value: 2 };
+        backend.stage_outbox(tx_id, intent, &message).await.unwrap();
+
+        // Rolled back transactions never reach the outbox
+        let rolled_back = backend.begin_transaction().await.unwrap();
+        backend.stage_outbox(rolled_back, intent, &message).await.unwrap();
+        backend.rollback_transaction(rolled_back).await.unwrap();
+
+        let collector = Arc::new(Collector::default());
+        let relay = OutboxRelay::new(backend.clone(), collector.clone());
+        assert!(relay.pending().await.unwrap().is_empty());
+
+        backend.commit_transaction(tx_id).await.unwrap();
+        assert_eq!(relay.pending().await.unwrap().len(), 1);
+
+        // A failed publish leaves the message pending
+        collector.fail.store(true, std::sync::atomic::Ordering::SeqCst);
+        assert!(relay.relay_once().await.is_err());
+        assert_eq!(relay.pending().await.unwrap().len(), 1);
+
+        collector.fail.store(false, std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(relay.relay_once().await.unwrap(), 1);
+        assert_eq!(relay.relay_once().await.unwrap(), 0);
+        assert_eq!(*collector.published.lock().unwrap(), vec![message]);
+    }
+
+    #[tokio::test]
+    async fn test_durable_queue_lifecycle() {
+        use std::time::Duration;
+
+        let backend: Arc<dyn QueryableStorage> = Arc::new(MemoryBackend::new());
+        let config = QueueConfig {
+            max_attempts: 2,
+            retry_backoff: Duration::ZERO,
+            ..QueueConfig::default()
+        };
+        let lease = Duration::from_secs(30);
+
+        let queue = DurableQueue::<TestEvent>::open(backend.clone(), "agents", config.clone()).await.unwrap();
+        let first = queue.enqueue(TestEvent { message: "first".to_string(), value: 1 }).await.unwrap();
+        let second = queue.enqueue(TestEvent { message: "second".to_string(), value: 2 }).await.unwrap();
+
+        // FIFO claiming, one holder per task
+        let claimed = queue.claim("worker-a", lease).await.unwrap().unwrap();
+        assert_eq!(claimed.id, first);
+        assert_eq!(claimed.attempts, 1);
+        queue.heartbeat(first, "worker-a", lease).await.unwrap();
+        assert!(queue.complete(first, "worker-b").await.is_err());
+        queue.complete(first, "worker-a").await.unwrap();
+
+        // Failures retry until attempts run out, then dead-letter
+        assert_eq!(queue.claim("worker-a", lease).await.unwrap().unwrap().id, second);
+        assert_eq!(queue.fail(second, "worker-a", "boom").await.unwrap(), TaskStatus::Pending);
+        assert_eq!(queue.claim("worker-b", lease).await.unwrap().unwrap().attempts, 2);
+        assert_eq!(queue.fail(second, "worker-b", "boom again").await.unwrap(), TaskStatus::DeadLettered);
+        assert!(queue.claim("worker-a", lease).await.unwrap().is_none());
+        assert_eq!(queue.dead_letters().await[0].last_error.as_deref(), Some("boom again"));
+
+        // State survives a restart
+        let queue = DurableQueue::<TestEvent>::open(backend.clone(), "agents", config.clone()).await.unwrap();
+        assert_eq!(queue.task(first).await.unwrap().status, TaskStatus::Completed);
+        assert_eq!(queue.stats().await.dead_lettered, 1);
+
+        queue.requeue(second).await.unwrap();
+        let third = queue.claim("worker-c", Duration::ZERO).await.unwrap().unwrap();
+        assert_eq!(third.id, second);
+
+        // An expired lease is reclaimed by the next claim
+        std::thread::sleep(Duration::from_millis(2));
+        let reclaimed = queue.claim("worker-d", lease).await.unwrap().unwrap();
+        assert_eq!(reclaimed.id, second);
+        assert_eq!(reclaimed.worker.as_deref(), Some("worker-d"));
+        assert_eq!(reclaimed.attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_export_import() {
+        let source = MemoryBackend::new();
+        let intent = Uuid::new_v4();
+
+        let event = TestEvent { message: "snapshot".to_string(), value: 1 };
+        let payload = rmp_serde::to_vec_named(&event).unwrap();
+        let root = create_event_header(&[], intent, "test.event".to_string(), &event).unwrap();
+        let duplicate = create_event_header(&[], intent, "test.event".to_string(), &event).unwrap();
+        let child = create_event_header(&[root.clone()], intent, "test.event".to_string(), &event).unwrap();
+        for header in [&root, &duplicate, &child] {
+            source.commit(header, &payload).await.unwrap();
+        }
+
+        let tx = source.begin_transaction().await.unwrap();
+        source.commit_transaction(tx).await.unwrap();
+        let sequence = source.current_sequence().await.unwrap();
+
+        let mut archive = Vec::new();
+        let exported = source
+            .export_snapshot(&mut futures::io::Cursor::new(&mut archive))
+            .await
+            .unwrap();
+        assert_eq!(exported.events, 3);
+        assert_eq!(exported.payloads, 2);
+
+        let target = MemoryBackend::new();
+        let imported = target
+            .import_snapshot(&mut futures::io::Cursor::new(archive))
+            .await
+            .unwrap();
+        assert_eq!(imported, exported);
+        assert_eq!(target.event_count().await, 3);
+        assert_eq!(target.payload_count().await, 2);
+        assert_eq!(target.header(&child.id).await.unwrap(), Some(child.clone()));
+        assert_eq!(target.payload_bytes(&child.digest).await.unwrap(), Some(payload));
+        assert!(target.current_sequence().await.unwrap() >= sequence);
+    }
+
+    #[tokio::test]
+    async fn test_export_anonymized() {
+        let source = MemoryBackend::new();
+        let intent = Uuid::new_v4();
+        let event = TestEvent { message: "tenant secret".to_string(), value: 7 };
+        let payload = rmp_serde::to_vec_named(&event).unwrap();
+        let root = create_event_header(&[], intent, "test.event".to_string(), &event).unwrap();
+        let child = create_event_header(&[root.clone()], intent, "test.event".to_string(), &event).unwrap();
+        for header in [&root, &child] {
+            source.commit(header, &payload).await.unwrap();
+        }
+
+        let policy = AnonymizationPolicy::new("research").strip_field("/message");
+        let mut archive = Vec::new();
+        let report = source
+            .export_anonymized(&mut futures::io::Cursor::new(&mut archive), &policy)
+            .await
+            .unwrap();
+        assert_eq!(report.events, 2);
+        assert_eq!(report.fields_stripped, 2);
+
+        let target = MemoryBackend::new();
+        target
+            .import_snapshot(&mut futures::io::Cursor::new(archive))
+            .await
+            .unwrap();
+        assert_eq!(target.event_count().await, 2);
+        assert!(target.header(&root.id).await.unwrap().is_none());
+        let anonymized = target
+            .header(&policy.pseudonym(&child.id))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(anonymized.parents.as_slice(), &[policy.pseudonym(&root.id)]);
+        assert_eq!(anonymized.intent, policy.pseudonym(&intent));
+        let bytes = target.payload_bytes(&anonymized.digest).await.unwrap().unwrap();
+        #[derive(Deserialize)]
+        struct Stripped {
+            message: Option<String>,
+            value: i32,
+        }
+        let stripped: Stripped = rmp_serde::from_slice(&bytes).unwrap();
+        assert!(stripped.message.is_none());
+        assert_eq!(stripped.value, 7);
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_with_snapshot() {
+        let source = MemoryBackend::new();
+        let event = TestEvent { message: "backup".to_string(), value: 1 };
+        let payload = rmp_serde::to_vec_named(&event).unwrap();
+        let header = |value| {
+            create_event_header(&[], Uuid::new_v4(), "test.event".to_string(), &TestEvent {
+                message: "backup".to_string(),
+                value,
+            })
+            .unwrap()
+        };
+
+        let finished = source.begin_transaction().await.unwrap();
+        source.commit_with_wal(finished, &header(1), &payload).await.unwrap();
+        source.commit_transaction(finished).await.unwrap();
+        let open = source.begin_transaction().await.unwrap();
+        let open_start = source.current_sequence().await.unwrap();
+        source.commit_with_wal(open, &header(2), &payload).await.unwrap();
+
+        let mut archive = Vec::new();
+        let checkpoint = source
+            .checkpoint_with_snapshot(&mut futures::io::Cursor::new(&mut archive))
+            .await
+            .unwrap();
+
+        // The open transaction keeps its entries and everything after them
+        assert_eq!(checkpoint.checkpointed_through, open_start - 1);
+        assert_eq!(checkpoint.compaction.transactions_removed, 1);
+        let remaining = source.wal_entries_after(0, 100).await.unwrap();
+        assert!(remaining.iter().all(|e| e.transaction_id == open));
+        assert!(!remaining.is_empty());
+
+        let target = MemoryBackend::new();
+        let imported = target
+            .import_snapshot(&mut futures::io::Cursor::new(archive))
+            .await
+            .unwrap();
+        assert_eq!(imported, checkpoint.manifest);
+        assert!(target.event_count().await >= 1);
+    }
+
+    #[tokio::test]
+    async fn test_storage_migrator_resumes_and_verifies() {
+        let source = MemoryBackend::new();
+        let target = MemoryBackend::new();
+        let intent = Uuid::new_v4();
+
+        let mut parents: Vec<EventHeader> = Vec::new();
+        let mut headers = Vec::new();
+        for i in 0..5 {
+            let event = TestEvent { message: "migrate".to_string(), value: i };
+            let header = create_event_header(&parents, intent, "test.event".to_string(), &event).unwrap();
+            source.commit(&header, &rmp_serde::to_vec_named(&event).unwrap()).await.unwrap();
+            parents = vec![header.clone()];
+            headers.push(header);
+        }
+
+        // Simulate an interrupted earlier run that copied the first two events
+        for header in &headers[..2] {
+            let payload = source.payload_bytes(&header.digest).await.unwrap().unwrap();
+            target.commit(header, &payload).await.unwrap();
+        }
+
+        let updates = std::sync::Mutex::new(Vec::new());
+        let report = StorageMigrator::new(&source, &target)
+            .on_progress(2, |progress| updates.lock().unwrap().push(*progress))
+            .run()
+            .await
+            .unwrap();
+
+        assert_eq!(report.progress, MigrationProgress { total: 5, copied: 3, skipped: 2 });
+        assert_eq!(report.events_verified, 5);
+        assert!(report.is_clean());
+        assert_eq!(target.event_count().await, 5);
+        assert_eq!(updates.lock().unwrap().last(), Some(&report.progress));
+    }
+
+    #[tokio::test]
+    async fn test_integrity_audit() {
+        let backend = MemoryBackend::new();
+        let intent = Uuid::new_v4();
+        let start = chrono::Utc::now();
+
+        let root_event = TestEvent { message: "root".to_string(), value: 0 };
+        let root = create_event_header(&[], intent, "test.chain".to_string(), &root_event).unwrap();
+        backend.commit(&root, &rmp_serde::to_vec_named(&root_event).unwrap()).await.unwrap();
+
+        let child_event = TestEvent { message: "child".to_string(), value: 1 };
+        let child = create_event_header(&[root.clone()], intent, "test.chain".to_string(), &child_event).unwrap();
+        backend.commit(&child, &rmp_serde::to_vec_named(&child_event).unwrap()).await.unwrap();
+
+        assert!(backend.verify_event(&child.id).await.unwrap().is_clean());
+        let report = backend.audit_chain(child.id).await.unwrap();
+        assert_eq!(report.events_checked, 2);
+        assert!(report.is_clean());
+
+        // Store bytes that don't match the header's digest
+        let tampered_event = TestEvent { message: "tampered".to_string(), value: 2 };
+        let tampered = create_event_header(&[child.clone()], intent, "test.chain".to_string(), &tampered_event).unwrap();
+        let forged = TestEvent { message: "forged".to_string(), value: 3 };
+        backend.commit(&tampered, &rmp_serde::to_vec_named(&forged).unwrap()).await.unwrap();
+
+        let report = backend.audit_chain(tampered.id).await.unwrap();
+        assert_eq!(report.events_checked, 3);
+        assert!(matches!(
+            report.issues.as_slice(),
+            [IntegrityIssue::DigestMismatch { event, .. }] if *event == tampered.id
+        ));
+
+        // Missing parents and events are reported too
+        let orphan = create_event_header(&[tampered.clone()], intent, "test.chain".to_string(), &root_event).unwrap();
+        let missing_parent = IntegrityIssue::MissingParent { event: orphan.id, parent: tampered.id };
+        backend.headers.write().await.remove(&tampered.id);
+        backend.commit(&orphan, &rmp_serde::to_vec_named(&root_event).unwrap()).await.unwrap();
+        assert_eq!(backend.verify_event(&orphan.id).await.unwrap().issues, vec![missing_parent]);
+        assert!(!backend.verify_event(&Uuid::new_v4()).await.unwrap().is_clean());
+
+        let scanner = IntegrityScanner::new(&backend, start);
+        let report = scanner.scan_once().await.unwrap();
+        assert_eq!(report.events_checked, 3);
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(scanner.scan_once().await.unwrap().events_checked, 0);
+    }
+
+    #[tokio::test]
+    async fn test_query_by_kind_intent_and_range() {
+        use futures::TryStreamExt;
+
+        let backend = MemoryBackend::new();
+        let intent = Uuid::new_v4();
+        let start = chrono::Utc::now();
+
+        for (i, kind) in ["test.a", "test.b", "test.a"].iter().enumerate() {
+            let event = TestEvent { message: "query".to_string(), value: i as i32 };
+            let header = create_event_header(&[], intent, kind.to_string(), &event).unwrap();
+            let payload_bytes = rmp_serde::to_vec_named(&event).unwrap();
+            backend.commit(&header, &payload_bytes).await.unwrap();
+        }
+        let other = TestEvent { message: "other".to_string(), value: 0 };
+        let other_header = create_event_header(&[], Uuid::new_v4(), "test.a".to_string(), &other).unwrap();
+        backend
+            .commit(&other_header, &rmp_serde::to_vec_named(&other).unwrap())
+            .await
+            .unwrap();
+
+        let by_kind: Vec<EventHeader> = backend
+            .events_by_kind("test.a")
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(by_kind.len(), 3);
+        assert!(by_kind.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+
+        let by_intent: Vec<EventHeader> = backend
+            .events_by_intent(&intent)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(by_intent.len(), 3);
+        assert!(by_intent.iter().all(|h| h.intent == intent));
+
+        let in_range: Vec<EventHeader> = backend
+            .events_in_range(start, chrono::Utc::now() + chrono::Duration::seconds(1))
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(in_range.len(), 4);
+
+        let before: Vec<EventHeader> = backend
+            .events_in_range(start - chrono::Duration::hours(1), start)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert!(before.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scoped_reader_limits_agent_to_granted_intents() {
+        use futures::TryStreamExt;
+        use toka_store_core::scoped::intent_permission;
+        use toka_store_core::STORE_READ_PERMISSION;
+
+        let backend = Arc::new(MemoryBackend::new());
+        let own = Uuid::new_v4();
+        let foreign = Uuid::new_v4();
+        let mut ids = Vec::new();
+        for intent in [own, foreign] {
+            let event = TestEvent { message: "scoped".to_string(), value: ids.len() as i32 };
+            let header = create_event_header(&[], intent, "test.scoped".to_string(), &event).unwrap();
+            backend.commit(&header, &rmp_serde::to_vec_named(&event).unwrap()).await.unwrap();
+            ids.push(header.id);
+        }
+
+        let now = Utc::now();
+        let claims = toka_types::traits::Claims {
+            sub: "agent-1".to_string(),
+            vault: "default".to_string(),
+            permissions: vec![STORE_READ_PERMISSION.to_string(), intent_permission(&own)],
+            iat: now.timestamp() as u64,
+            exp: now.timestamp() as u64 + 600,
+            jti: Uuid::new_v4().to_string(),
+        };
+        let reader = ScopedReader::new(backend, &claims, now).unwrap();
+
+        let visible: Vec<EventHeader> = reader
+            .events_by_kind("test.scoped")
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(visible.len(), 1);
+        assert_eq!(visible[0].intent, own);
+
+        assert!(reader.payload(&ids[0]).await.unwrap().is_some());
+        assert!(reader.header(&ids[1]).await.is_err());
+        assert!(reader.payload(&ids[1]).await.is_err());
+        assert!(reader.events_by_intent(&foreign).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_only_backend_forwards_reads_only() {
+        let backend = MemoryBackend::new();
+        let event = TestEvent { message: "ro".to_string(), value: 7 };
+        let payload = rmp_serde::to_vec_named(&event).unwrap();
+        let header = create_event_header(&[], Uuid::new_v4(), "test.ro".to_string(), &event).unwrap();
+        backend.commit(&header, &payload).await.unwrap();
+
+        let readonly = ReadOnlyBackend::new(backend);
+        assert!(readonly.header(&header.id).await.unwrap().is_some());
+        assert_eq!(readonly.storage_stats().await.unwrap().event_count, 1);
+
+        let err = readonly.commit(&header, &payload).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<StorageError>(), Some(StorageError::ReadOnly(_))));
+        assert!(readonly.begin_transaction().await.is_err());
+        assert_eq!(readonly.inner().event_count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_commit_clocked_orders_after_parents() {
+        let backend = MemoryBackend::new();
+        let node_a = VectorClockSource::new("node-a");
+        let node_b = VectorClockSource::new("node-b");
+        let event = TestEvent { message: "clocked".to_string(), value: 1 };
+        let payload = rmp_serde::to_vec_named(&event).unwrap();
+
+        let parent = create_event_header(&[], Uuid::new_v4(), "test.clock".to_string(), &event).unwrap();
+        let parent = backend.commit_clocked(&node_a, parent, &payload).await.unwrap();
+        let child = create_event_header(&[parent.clone()], Uuid::new_v4(), "test.clock".to_string(), &event).unwrap();
+        let child = backend.commit_clocked(&node_b, child, &payload).await.unwrap();
+
+        let stored = backend.header(&child.id).await.unwrap().unwrap();
+        assert_eq!(stored.clock, child.clock);
+        assert_eq!(
+            parent.clock.unwrap().compare(&stored.clock.unwrap()),
+            Some(ClockOrdering::Before)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_namespaces_are_isolated() {
+        use futures::StreamExt;
+
+        let backend = MemoryBackend::new();
+        let event = TestEvent { message: "tenant".to_string(), value: 1 };
+        let payload = rmp_serde::to_vec_named(&event).unwrap();
+        let header = || create_event_header(&[], Uuid::new_v4(), "test.tenant".to_string(), &event).unwrap();
+
+        let mut tenant_b_stream = backend.subscribe_namespace("tenant-b").await.unwrap();
+        let a1 = backend.commit_in("tenant-a", &header(), &payload).await.unwrap();
+        let b1 = backend.commit_in("tenant-b", &header(), &payload).await.unwrap();
+        let unscoped = header();
+        backend.commit(&unscoped, &payload).await.unwrap();
+        assert!(backend.commit_in("bad/name", &header(), &payload).await.is_err());
+
+        assert_eq!(tenant_b_stream.next().await.unwrap().unwrap().id, b1.id);
+        assert!(backend.header_in("tenant-b", &a1.id).await.unwrap().is_none());
+        assert!(backend.payload_in("tenant-a", &a1.id).await.unwrap().is_some());
+        assert!(backend.header_in(DEFAULT_NAMESPACE, &unscoped.id).await.unwrap().is_some());
+
+        let names: Vec<_> = backend.namespaces().await.unwrap().into_iter().map(|s| s.namespace).collect();
+        assert_eq!(names, vec!["default", "tenant-a", "tenant-b"]);
+        assert_eq!(backend.namespace_stats("tenant-a").await.unwrap().event_count, 1);
+
+        // Pruning a namespace keeps payloads still shared with other tenants
+        assert_eq!(backend.prune_namespace("tenant-a", None).await.unwrap(), 1);
+        assert_eq!(backend.namespace_stats("tenant-a").await.unwrap().event_count, 0);
+        assert!(backend.payload_in("tenant-b", &b1.id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_commit_if_detects_conflicts() {
+        let backend = MemoryBackend::new();
+        let intent = Uuid::new_v4();
+        let event = TestEvent { message: "cas".to_string(), value: 1 };
+        let payload = rmp_serde::to_vec_named(&event).unwrap();
+
+        let first = create_event_header(&[], intent, "test.cas".to_string(), &event).unwrap();
+        backend
+            .commit_if(&first, &payload, &Precondition::intent_head(intent, None))
+            .await
+            .unwrap();
+
+        // Two writers build on the same head; only the first one wins
+        let winner = create_event_header(&[first.clone()], intent, "test.cas".to_string(), &event).unwrap();
+        let loser = create_event_header(&[first.clone()], intent, "test.cas".to_string(), &event).unwrap();
+        let expect_first = Precondition::intent_head(intent, Some(first.id));
+        backend.commit_if(&winner, &payload, &expect_first).await.unwrap();
+
+        let err = backend.commit_if(&loser, &payload, &expect_first).await.unwrap_err();
+        assert_eq!(
+            toka_store_core::as_conflict(&err),
+            Some(&CommitConflict::IntentHeadMoved {
+                intent,
+                expected: Some(first.id),
+                actual: Some(winner.id),
+            })
+        );
+        assert!(backend.header(&loser.id).await.unwrap().is_none());
+        assert_eq!(backend.intent_head(&intent).await.unwrap(), Some(winner));
+
+        let orphan = create_event_header(&[], intent, "test.cas".to_string(), &event).unwrap();
+        let missing_parent = Precondition::parent_exists(Uuid::new_v4());
+        let err = backend.commit_if(&orphan, &payload, &missing_parent).await.unwrap_err();
+        assert!(matches!(toka_store_core::as_conflict(&err), Some(CommitConflict::MissingEvent(_))));
+        assert_eq!(backend.event_count().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_commit_if_deduplicates_retries() {
+        let backend = MemoryBackend::new();
+        let (original, payload) = capacity_event(1);
+        let original = original.with_idempotency_key("cas-1");
+        let (retry, _) = capacity_event(1);
+        let retry = retry.with_idempotency_key("cas-1");
+
+        let expect_empty = Precondition::intent_head(original.intent, None);
+        backend.commit_if(&original, &payload, &expect_empty).await.unwrap();
+
+        // The retry is dropped before its now stale precondition is checked
+        backend.commit_if(&retry, &payload, &expect_empty).await.unwrap();
+        assert!(backend.header(&retry.id).await.unwrap().is_none());
+
+        let outcome = backend.commit_deduplicated(&retry, &payload).await.unwrap();
+        assert_eq!(outcome, CommitOutcome::Duplicate(original.id));
+    }
+
     #[tokio::test]
     async fn test_wal_basic_transaction() {
         let backend = MemoryBackend::new();
@@ -688,7 +2357,7 @@ mod tests {
             tx_id,
             WalOperation::CommitEvent {
                 header: header.clone(),
-                payload: payload_bytes.clone(),
+                payload: payload_bytes.clone().into(),
             },
         ).await.unwrap();
         
@@ -705,6 +2374,31 @@ mod tests {
         assert_eq!(backend.event_count().await, 1);
     }
 
+    #[tokio::test]
+    async fn test_wal_health() {
+        let backend = MemoryBackend::new();
+        let healthy = backend.wal_health().await.unwrap();
+        assert_eq!(healthy.entries_by_state.total(), 0);
+        assert_eq!(healthy.oldest_pending_at, None);
+
+        let open = backend.begin_transaction().await.unwrap();
+        let done = backend.begin_transaction().await.unwrap();
+        backend.commit_transaction(done).await.unwrap();
+
+        let health = backend.wal_health().await.unwrap();
+        assert_eq!(health.active_transactions, 1);
+        assert_eq!(health.entries_by_state.pending, 1);
+        assert_eq!(health.entries_by_state.committed, 2);
+        assert_eq!(health.current_sequence, backend.current_sequence().await.unwrap());
+        assert!(health.bytes_used > 0);
+        assert!(health.oldest_pending_at.is_some());
+
+        backend.rollback_transaction(open).await.unwrap();
+        let health = backend.wal_health().await.unwrap();
+        assert_eq!(health.active_transactions, 0);
+        assert_eq!(health.entries_by_state.pending, 0);
+    }
+
     #[tokio::test]
     async fn test_wal_rollback() {
         let backend = MemoryBackend::new();
@@ -732,7 +2426,7 @@ mod tests {
             tx_id,
             WalOperation::CommitEvent {
                 header: header.clone(),
-                payload: payload_bytes.clone(),
+                payload: payload_bytes.clone().into(),
             },
         ).await.unwrap();
         
@@ -805,7 +2499,7 @@ mod tests {
                     "test.sequence".to_string(),
                     &TestEvent { message: "seq".to_string(), value: 1 },
                 ).unwrap(),
-                payload: rmp_serde::to_vec_named(&TestEvent { message: "seq".to_string(), value: 1 }).unwrap(),
+                payload: rmp_serde::to_vec_named(&TestEvent { message: "seq".to_string(), value: 1 }).unwrap().into(),
             },
         ).await.unwrap();
         
@@ -828,7 +2522,7 @@ mod tests {
                     "test.checkpoint".to_string(),
                     &TestEvent { message: "checkpoint".to_string(), value: 42 },
                 ).unwrap(),
-                payload: rmp_serde::to_vec_named(&TestEvent { message: "checkpoint".to_string(), value: 42 }).unwrap(),
+                payload: rmp_serde::to_vec_named(&TestEvent { message: "checkpoint".to_string(), value: 42 }).unwrap().into(),
             },
         ).await.unwrap();
         backend.commit_transaction(tx_id).await.unwrap();
@@ -858,7 +2552,7 @@ mod tests {
                     "test.recovery".to_string(),
                     &TestEvent { message: "recovery".to_string(), value: 99 },
                 ).unwrap(),
-                payload: rmp_serde::to_vec_named(&TestEvent { message: "recovery".to_string(), value: 99 }).unwrap(),
+                payload: rmp_serde::to_vec_named(&TestEvent { message: "recovery".to_string(), value: 99 }).unwrap().into(),
             },
         ).await.unwrap();
         
@@ -873,7 +2567,7 @@ mod tests {
                     "test.recovery2".to_string(),
                     &TestEvent { message: "recovery2".to_string(), value: 100 },
                 ).unwrap(),
-                payload: rmp_serde::to_vec_named(&TestEvent { message: "recovery2".to_string(), value: 100 }).unwrap(),
+                payload: rmp_serde::to_vec_named(&TestEvent { message: "recovery2".to_string(), value: 100 }).unwrap().into(),
             },
         ).await.unwrap();
         backend.commit_transaction(tx_id2).await.unwrap();
@@ -890,4 +2584,282 @@ mod tests {
         // Should have committed the committed transaction
         assert_eq!(recovery_result.transactions_committed, 1);
     }
+
+    #[tokio::test]
+    async fn test_wal_compaction() {
+        let backend = MemoryBackend::new();
+        let event = TestEvent { message: "compact".to_string(), value: 1 };
+        let header = create_event_header(&[], Uuid::new_v4(), "test.compact".to_string(), &event).unwrap();
+        let payload = rmp_serde::to_vec_named(&event).unwrap();
+
+        let committed = backend.begin_transaction().await.unwrap();
+        backend.commit_with_wal(committed, &header, &payload).await.unwrap();
+        backend.commit_transaction(committed).await.unwrap();
+        let rolled_back = backend.begin_transaction().await.unwrap();
+        backend.rollback_transaction(rolled_back).await.unwrap();
+        let pending = backend.begin_transaction().await.unwrap();
+        assert_eq!(backend.wal_entry_count().await, 6);
+
+        // Committed entries are kept until checkpointed
+        let report = backend.compact_wal(u64::MAX).await.unwrap();
+        assert_eq!(report.transactions_removed, 1);
+        assert_eq!(report.rolled_back_entries, 2);
+        assert_eq!(backend.wal_entry_count().await, 4);
+
+        let sequence = backend.current_sequence().await.unwrap();
+        backend.checkpoint(sequence).await.unwrap();
+        let report = backend.compact_wal(sequence + 1).await.unwrap();
+        assert_eq!(report.checkpointed_entries, 3);
+        assert_eq!(backend.wal_entry_count().await, 1);
+
+        // The pending transaction is untouched
+        backend.commit_transaction(pending).await.unwrap();
+        assert_eq!(backend.wal_compaction_totals().entries_removed(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_wal_compaction_on_checkpoint() {
+        let backend = MemoryBackend::new().with_wal_compaction(WalCompactionPolicy::OnCheckpoint { retain: 0 });
+        let tx_id = backend.begin_transaction().await.unwrap();
+        backend.commit_transaction(tx_id).await.unwrap();
+
+        backend.checkpoint(backend.current_sequence().await.unwrap()).await.unwrap();
+        assert_eq!(backend.wal_entry_count().await, 0);
+        assert_eq!(backend.wal_compaction_totals().transactions_removed, 1);
+    }
+
+    #[tokio::test]
+    async fn test_stale_transactions_are_aborted() {
+        let backend = MemoryBackend::new().with_transaction_ttl(std::time::Duration::from_secs(30));
+        let mut timeouts = backend.subscribe_transaction_timeouts();
+        let stale = backend.begin_transaction().await.unwrap();
+        let finished = backend.begin_transaction().await.unwrap();
+        backend.commit_transaction(finished).await.unwrap();
+        assert_eq!(backend.storage_stats().await.unwrap().open_transactions, 1);
+
+        // Nothing is stale yet
+        assert!(backend.abort_stale_transactions(Utc::now()).await.unwrap().is_empty());
+
+        let later = Utc::now() + chrono::Duration::minutes(1);
+        let aborted = backend.abort_stale_transactions(later).await.unwrap();
+        assert_eq!(aborted.len(), 1);
+        assert_eq!(aborted[0].transaction_id, stale);
+        assert_eq!(timeouts.recv().await.unwrap(), aborted[0]);
+        assert!(backend.commit_transaction(stale).await.is_err());
+
+        let stats = backend.storage_stats().await.unwrap();
+        assert_eq!(stats.open_transactions, 0);
+        assert_eq!(stats.transactions_timed_out, 1);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_reaper() {
+        let backend = MemoryBackend::new().with_transaction_ttl(std::time::Duration::from_millis(10));
+        let mut timeouts = backend.subscribe_transaction_timeouts();
+        let reaper = backend.spawn_transaction_reaper(std::time::Duration::from_millis(5));
+
+        let transaction_id = backend.begin_transaction().await.unwrap();
+        let notice = tokio::time::timeout(std::time::Duration::from_secs(5), timeouts.recv())
+            .await
+            .expect("reaper should abort the transaction")
+            .unwrap();
+        assert_eq!(notice.transaction_id, transaction_id);
+        assert_eq!(backend.transactions_timed_out(), 1);
+        reaper.abort();
+    }
+
+    #[tokio::test]
+    async fn test_savepoints() {
+        let backend = MemoryBackend::new();
+        let event = |value| TestEvent { message: "savepoint".to_string(), value };
+        let header = |value| create_event_header(&[], Uuid::new_v4(), "test.savepoint".to_string(), &event(value)).unwrap();
+        let payload = |value| rmp_serde::to_vec_named(&event(value)).unwrap();
+        let (kept, discarded, nested, retried) = (header(1), header(2), header(3), header(4));
+
+        let tx_id = backend.begin_transaction().await.unwrap();
+        backend.commit_with_wal(tx_id, &kept, &payload(1)).await.unwrap();
+        let outer = backend.savepoint(tx_id).await.unwrap();
+        backend.commit_with_wal(tx_id, &discarded, &payload(2)).await.unwrap();
+        let inner = backend.savepoint(tx_id).await.unwrap();
+        backend.commit_with_wal(tx_id, &nested, &payload(3)).await.unwrap();
+
+        // Rolling back to the outer savepoint releases the inner one
+        backend.rollback_to_savepoint(tx_id, outer).await.unwrap();
+        assert!(backend.rollback_to_savepoint(tx_id, inner).await.is_err());
+        backend.commit_with_wal(tx_id, &retried, &payload(4)).await.unwrap();
+        backend.commit_transaction(tx_id).await.unwrap();
+
+        for (header, expected) in [(&kept, true), (&discarded, false), (&nested, false), (&retried, true)] {
+            assert_eq!(backend.header(&header.id).await.unwrap().is_some(), expected);
+        }
+
+        // Recovery does not resurrect the discarded events
+        let recovered = MemoryBackend::new();
+        *recovered.wal_entries.write().await = backend.wal_entries.read().await.clone();
+        recovered.recover().await.unwrap();
+        assert_eq!(recovered.event_count().await, 2);
+        assert!(recovered.header(&discarded.id).await.unwrap().is_none());
+
+        assert!(backend.savepoint(tx_id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_recovery_observer() {
+        #[derive(Default)]
+        struct Recorder {
+            replayed: std::sync::Mutex<Vec<(TransactionId, usize, usize)>>,
+            rolled_back: std::sync::Mutex<Vec<TransactionId>>,
+        }
+
+        impl RecoveryObserver for Recorder {
+            fn before_replay(&self, entry: &WalEntry) -> ReplayDecision {
+                match &entry.operation {
+                    WalOperation::CommitEvent { header, .. } if header.kind == "test.secret" => ReplayDecision::Skip,
+                    _ => ReplayDecision::Apply,
+                }
+            }
+
+            fn on_transaction_replayed(&self, transaction_id: TransactionId, applied: usize, skipped: usize) {
+                self.replayed.lock().unwrap().push((transaction_id, applied, skipped));
+            }
+
+            fn on_rollback(&self, transaction_id: TransactionId, _entries: usize) {
+                self.rolled_back.lock().unwrap().push(transaction_id);
+            }
+        }
+
+        let backend = MemoryBackend::new();
+        let event = TestEvent { message: "observed".to_string(), value: 1 };
+        let payload = rmp_serde::to_vec_named(&event).unwrap();
+        let public = create_event_header(&[], Uuid::new_v4(), "test.public".to_string(), &event).unwrap();
+        let secret = create_event_header(&[], Uuid::new_v4(), "test.secret".to_string(), &event).unwrap();
+        let committed = backend.begin_transaction().await.unwrap();
+        backend.commit_with_wal(committed, &public, &payload).await.unwrap();
+        backend.commit_with_wal(committed, &secret, &payload).await.unwrap();
+        backend.commit_transaction(committed).await.unwrap();
+
+        // Replaying into an empty backend honours the veto
+        let recorder = Arc::new(Recorder::default());
+        let recovered = MemoryBackend::new().with_recovery_observer(recorder.clone());
+        *recovered.wal_entries.write().await = backend.wal_entries.read().await.clone();
+        recovered.recover().await.unwrap();
+        assert!(recovered.header(&public.id).await.unwrap().is_some());
+        assert!(recovered.header(&secret.id).await.unwrap().is_none());
+        assert_eq!(*recorder.replayed.lock().unwrap(), vec![(committed, 1, 1)]);
+
+        let recorder = Arc::new(Recorder::default());
+        let backend = backend.with_recovery_observer(recorder.clone());
+        let pending = backend.begin_transaction().await.unwrap();
+        backend.recover().await.unwrap();
+        assert_eq!(*recorder.rolled_back.lock().unwrap(), vec![pending]);
+    }
+
+    #[tokio::test]
+    async fn test_wal_replication() {
+        let leader = MemoryBackend::new();
+        let follower = MemoryBackend::new();
+        let event = |value| TestEvent { message: "replicate".to_string(), value };
+        let commit = |value| {
+            let leader = &leader;
+            async move {
+                let event = event(value);
+                let header = create_event_header(&[], Uuid::new_v4(), "test.replicate".to_string(), &event).unwrap();
+                let tx_id = leader.begin_transaction().await.unwrap();
+                leader.commit_with_wal(tx_id, &header, &rmp_serde::to_vec_named(&event).unwrap()).await.unwrap();
+                (tx_id, header)
+            }
+        };
+
+        let (first, first_header) = commit(1).await;
+        leader.commit_transaction(first).await.unwrap();
+        let (open, open_header) = commit(2).await;
+        let (third, _) = commit(3).await;
+        leader.commit_transaction(third).await.unwrap();
+        let (rolled_back, _) = commit(4).await;
+        leader.rollback_transaction(rolled_back).await.unwrap();
+
+        // The open transaction holds the resume token back but not later commits
+        let replicator = WalReplicator::new(&leader, &follower);
+        let batch = replicator.catch_up().await.unwrap();
+        assert_eq!(batch.transactions_applied, 2);
+        assert_eq!(follower.event_count().await, 2);
+        assert!(follower.header(&first_header.id).await.unwrap().is_some());
+        let token = replicator.resume_token().await;
+        assert!(!replicator.lag().await.unwrap().is_caught_up());
+
+        // A resumed replicator applies the transaction once it commits
+        leader.commit_transaction(open).await.unwrap();
+        let resumed = WalReplicator::resume(&leader, &follower, token);
+        resumed.catch_up().await.unwrap();
+        assert!(follower.header(&open_header.id).await.unwrap().is_some());
+        assert_eq!(follower.event_count().await, 3);
+        assert!(resumed.lag().await.unwrap().is_caught_up());
+
+        let report = resumed.verify_consistency().await.unwrap();
+        assert_eq!(report.events_checked, 3);
+        assert!(report.is_consistent());
+        follower.clear().await;
+        assert_eq!(resumed.verify_consistency().await.unwrap().missing.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_dual_write_two_phase_commit() {
+        let primary = MemoryBackend::new();
+        let secondary = MemoryBackend::new();
+        let dual = DualWriteBackend::new(primary.clone(), secondary.clone());
+        let event = |value| {
+            let event = TestEvent { message: "dual".to_string(), value };
+            let header = create_event_header(&[], Uuid::new_v4(), "test.dual".to_string(), &event).unwrap();
+            (header, rmp_serde::to_vec_named(&event).unwrap())
+        };
+
+        let outcome = dual.commit_batch(&[event(1), event(2)]).await.unwrap();
+        assert!(outcome.secondary_committed);
+        assert_eq!(secondary.event_count().await, 2);
+
+        // Crash after the primary decided but before the secondary committed
+        let decided = event(3);
+        let prepared = dual.prepare(&[decided.clone()]).await.unwrap();
+        primary.commit_transaction(prepared.primary_transaction).await.unwrap();
+        // Crash before either side committed
+        let undecided = event(4);
+        dual.prepare(&[undecided.clone()]).await.unwrap();
+
+        let recovery = dual.recover().await.unwrap();
+        assert_eq!(recovery.primary.transactions_rolled_back, 1);
+        assert_eq!(recovery.secondary.transactions_rolled_back, 2);
+        assert_eq!(recovery.reconciliation.events_repaired, 1);
+        assert!(secondary.header(&decided.0.id).await.unwrap().is_some());
+        assert!(primary.header(&undecided.0.id).await.unwrap().is_none());
+        assert!(secondary.header(&undecided.0.id).await.unwrap().is_none());
+        assert_eq!(primary.event_count().await, 3);
+        assert_eq!(secondary.event_count().await, 3);
+
+        assert_eq!(dual.reconcile().await.unwrap(), ReconcileReport::default());
+    }
+
+    #[tokio::test]
+    async fn test_conformance_crash_recovery() {
+        use toka_store_conformance::crash;
+
+        // The retained handle stands in for the durable store
+        let store = MemoryBackend::new();
+        let report = crash::simulate(
+            &crash::savepoint_script(),
+            || {
+                let backend = store.clone();
+                async move { Ok(backend) }
+            },
+            crash::drop_backend,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.crashes, 6);
+        assert!(report.transactions_rolled_back >= 4);
+    }
+
+    toka_store_conformance::storage_conformance_tests!(storage_conformance, MemoryBackend::new());
+    toka_store_conformance::wal_conformance_tests!(wal_conformance, MemoryBackend::new());
+    toka_store_conformance::subscription_conformance_tests!(subscription_conformance, MemoryBackend::new());
+    toka_store_conformance::dedup_conformance_tests!(dedup_conformance, MemoryBackend::new());
 }
\ No newline at end of file
@@ -0,0 +1,1152 @@
+//! RocksDB storage backend.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rocksdb::{
+    ColumnFamily, ColumnFamilyDescriptor, DBCompactionStyle, DBCompressionType, Direction,
+    IteratorMode, Options, WriteBatch, WriteOptions, DB,
+};
+use tokio::sync::{broadcast, RwLock};
+use uuid::Uuid;
+
+use toka_store_core::{
+    StorageBackend, QueryableStorage, CompactableStorage, EventHeaderStream, EventHeader, EventId, CausalDigest, IntentId,
+    WriteAheadLog, WalEntry, WalOperation, WalEntryState, WalHealth, WalRecoveryResult,
+    TransactionId, SequenceNumber, StorageStatistics, StorageStats,
+};
+
+/// Default broadcast channel size for live event streaming.
+const DEFAULT_BROADCAST_SIZE: usize = 256;
+
+/// Column family holding serialized event headers keyed by event ID.
+const CF_HEADERS: &str = "headers";
+/// Column family holding payload bytes keyed by causal digest.
+const CF_PAYLOADS: &str = "payloads";
+/// Column family holding WAL entries keyed by big-endian sequence number.
+const CF_WAL: &str = "wal";
+/// Index: `timestamp | id`.
+const CF_INDEX_TIME: &str = "idx_time";
+/// Index: `kind | 0x00 | timestamp | id`.
+const CF_INDEX_KIND: &str = "idx_kind";
+/// Index: `intent | timestamp | id`.
+const CF_INDEX_INTENT: &str = "idx_intent";
+
+/// All column families opened by the backend.
+const COLUMN_FAMILIES: [&str; 6] = [
+    CF_HEADERS,
+    CF_PAYLOADS,
+    CF_WAL,
+    CF_INDEX_TIME,
+    CF_INDEX_KIND,
+    CF_INDEX_INTENT,
+];
+
+//─────────────────────────────
+//  Configuration
+//─────────────────────────────
+
+/// Tuning options for [`RocksDbBackend`].
+#[derive(Debug, Clone)]
+pub struct RocksDbConfig {
+    /// Compaction style applied to every column family
+    pub compaction_style: DBCompactionStyle,
+    /// Compression applied to SST files
+    pub compression: DBCompressionType,
+    /// Memtable size in bytes before it is flushed to disk
+    pub write_buffer_size: usize,
+    /// Maximum number of concurrent background flush and compaction jobs
+    pub max_background_jobs: i32,
+    /// Whether each write batch is fsynced before the write returns
+    pub sync_writes: bool,
+}
+
+impl Default for RocksDbConfig {
+    fn default() -> Self {
+        Self {
+            compaction_style: DBCompactionStyle::Level,
+            compression: DBCompressionType::Lz4,
+            write_buffer_size: 64 * 1024 * 1024,
+            max_background_jobs: 4,
+            sync_writes: false,
+        }
+    }
+}
+
+impl RocksDbConfig {
+    /// Options shared by the database and every column family.
+    fn column_family_options(&self) -> Options {
+        let mut opts = Options::default();
+        opts.set_compaction_style(self.compaction_style);
+        opts.set_compression_type(self.compression);
+        opts.set_write_buffer_size(self.write_buffer_size);
+        opts
+    }
+
+    /// Database-wide options.
+    fn db_options(&self) -> Options {
+        let mut opts = self.column_family_options();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        opts.set_max_background_jobs(self.max_background_jobs);
+        opts
+    }
+}
+
+//─────────────────────────────
+//  RocksDB storage backend with WAL
+//─────────────────────────────
+
+/// A persistent storage backend using RocksDB with WAL support.
+///
+/// Payloads are deduplicated by content hash. Headers are indexed by
+/// timestamp, kind and intent to serve [`QueryableStorage`] scans without
+/// deserializing every stored header.
+#[derive(Debug)]
+pub struct RocksDbBackend {
+    db: DB,
+    sync_writes: bool,
+    broadcast_tx: broadcast::Sender<EventHeader>,
+    // WAL state management
+    wal_sequence: Arc<RwLock<SequenceNumber>>,
+    active_transactions: Arc<RwLock<HashMap<TransactionId, WalTransactionState>>>,
+}
+
+/// State tracking for active WAL transactions.
+#[derive(Debug, Clone)]
+struct WalTransactionState {
+    /// Current state of the transaction
+    state: WalTransactionStateType,
+    /// Operations logged in this transaction
+    operations: Vec<WalOperation>,
+    /// Sequence numbers for this transaction's entries
+    sequences: Vec<SequenceNumber>,
+}
+
+/// State types for WAL transactions.
+#[derive(Debug, Clone, PartialEq)]
+enum WalTransactionStateType {
+    /// Transaction is active and accepting operations
+    Active,
+    /// Transaction is being committed
+    Committing,
+    /// Transaction has been committed
+    Committed,
+    /// Transaction has been rolled back
+    RolledBack,
+}
+
+impl RocksDbBackend {
+    /// Opens or creates a RocksDB database at the specified path with default tuning.
+    ///
+    /// # Errors
+    /// Returns an error if the database cannot be opened or created.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with_config(path, RocksDbConfig::default())
+    }
+
+    /// Opens or creates a RocksDB database with custom tuning options.
+    ///
+    /// This allows choosing the compaction style, compression and memtable
+    /// sizing to match the expected workload.
+    pub fn open_with_config<P: AsRef<Path>>(path: P, config: RocksDbConfig) -> Result<Self> {
+        let descriptors = COLUMN_FAMILIES
+            .iter()
+            .map(|name| ColumnFamilyDescriptor::new(*name, config.column_family_options()))
+            .collect::<Vec<_>>();
+
+        let db = DB::open_cf_descriptors(&config.db_options(), path, descriptors)?;
+        let (broadcast_tx, _) = broadcast::channel(DEFAULT_BROADCAST_SIZE);
+
+        let backend = Self {
+            db,
+            sync_writes: config.sync_writes,
+            broadcast_tx,
+            wal_sequence: Arc::new(RwLock::new(0)),
+            active_transactions: Arc::new(RwLock::new(HashMap::new())),
+        };
+
+        let last_sequence = backend.last_wal_sequence()?;
+        *backend.wal_sequence.try_write()? = last_sequence;
+        Ok(backend)
+    }
+
+    /// Subscribe to the live event stream.
+    ///
+    /// Returns a receiver that will receive copies of all event headers
+    /// as they are committed to storage. Subscribers that fall behind
+    /// may miss events if the broadcast buffer overflows.
+    pub fn subscribe(&self) -> broadcast::Receiver<EventHeader> {
+        self.broadcast_tx.subscribe()
+    }
+
+    /// Commit several events in a single atomic write batch.
+    ///
+    /// This is considerably faster than committing events one by one for
+    /// bursty producers, since RocksDB only has to write (and optionally
+    /// fsync) its log once per batch.
+    pub async fn commit_batch(&self, events: &[(EventHeader, Vec<u8>)]) -> Result<()> {
+        self.write_events(events.iter().map(|(header, payload)| (header, payload.as_slice())))
+    }
+
+    /// Get the total number of events stored in the database.
+    ///
+    /// This walks the header column family and is intended for diagnostics
+    /// and tests rather than hot paths.
+    pub fn event_count(&self) -> Result<usize> {
+        self.count_keys(CF_HEADERS)
+    }
+
+    /// Get the total number of unique payloads stored.
+    ///
+    /// This may be less than the event count due to payload deduplication
+    /// when multiple events share the same content hash.
+    pub fn payload_count(&self) -> Result<usize> {
+        self.count_keys(CF_PAYLOADS)
+    }
+
+    /// Get the total number of WAL entries.
+    pub fn wal_entry_count(&self) -> Result<usize> {
+        self.count_keys(CF_WAL)
+    }
+
+    /// Flush all memtables to disk.
+    pub fn flush(&self) -> Result<()> {
+        for name in COLUMN_FAMILIES {
+            self.db.flush_cf(self.cf(name)?)?;
+        }
+        Ok(())
+    }
+
+    /// Run a full manual compaction over every column family.
+    ///
+    /// Background compaction normally keeps up on its own; this is useful
+    /// after bulk deletes or before taking a backup.
+    pub fn compact(&self) -> Result<()> {
+        for name in COLUMN_FAMILIES {
+            self.db
+                .compact_range_cf(self.cf(name)?, None::<&[u8]>, None::<&[u8]>);
+        }
+        Ok(())
+    }
+
+    /// Look up a column family handle.
+    fn cf(&self, name: &str) -> Result<&ColumnFamily> {
+        self.db
+            .cf_handle(name)
+            .ok_or_else(|| anyhow::anyhow!("Missing column family: {}", name))
+    }
+
+    /// Apply a write batch honoring the configured durability.
+    fn write(&self, batch: WriteBatch) -> Result<()> {
+        let mut opts = WriteOptions::default();
+        opts.set_sync(self.sync_writes);
+        self.db.write_opt(batch, &opts)?;
+        Ok(())
+    }
+
+    /// Count keys in a column family.
+    fn count_keys(&self, name: &str) -> Result<usize> {
+        let mut count = 0;
+        for item in self.db.iterator_cf(self.cf(name)?, IteratorMode::Start) {
+            item?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Order-preserving encoding of a timestamp (microsecond precision).
+    fn time_key(timestamp: &DateTime<Utc>) -> [u8; 8] {
+        ((timestamp.timestamp_micros() as u64) ^ (1 << 63)).to_be_bytes()
+    }
+
+    /// Prefix shared by all index keys for a kind.
+    fn kind_prefix(kind: &str) -> Vec<u8> {
+        let mut prefix = Vec::with_capacity(kind.len() + 1);
+        prefix.extend_from_slice(kind.as_bytes());
+        prefix.push(0);
+        prefix
+    }
+
+    /// Build an index key `prefix | timestamp | id`.
+    fn index_key(prefix: &[u8], header: &EventHeader) -> Vec<u8> {
+        let mut key = Vec::with_capacity(prefix.len() + 8 + 16);
+        key.extend_from_slice(prefix);
+        key.extend_from_slice(&Self::time_key(&header.timestamp));
+        key.extend_from_slice(header.id.as_bytes());
+        key
+    }
+
+    /// Add (or remove) all index entries for a header to a batch.
+    fn index_header(&self, batch: &mut WriteBatch, header: &EventHeader, remove: bool) -> Result<()> {
+        let entries = [
+            (CF_INDEX_TIME, Self::index_key(&[], header)),
+            (CF_INDEX_KIND, Self::index_key(&Self::kind_prefix(&header.kind), header)),
+            (CF_INDEX_INTENT, Self::index_key(header.intent.as_bytes(), header)),
+        ];
+
+        for (name, key) in entries {
+            let cf = self.cf(name)?;
+            if remove {
+                batch.delete_cf(cf, key);
+            } else {
+                batch.put_cf(cf, key, b"");
+            }
+        }
+        Ok(())
+    }
+
+    /// Write events, their payloads and index entries in one batch, then broadcast.
+    fn write_events<'a>(
+        &self,
+        events: impl IntoIterator<Item = (&'a EventHeader, &'a [u8])>,
+    ) -> Result<()> {
+        let headers_cf = self.cf(CF_HEADERS)?;
+        let payloads_cf = self.cf(CF_PAYLOADS)?;
+        let mut batch = WriteBatch::default();
+        let mut committed = Vec::new();
+
+        for (header, payload) in events {
+            // Payloads are content-addressed, so rewriting an existing digest is harmless
+            batch.put_cf(payloads_cf, header.digest, payload);
+
+            // Drop stale index entries if this header replaces a previous version
+            if let Some(previous) = self.db.get_cf(headers_cf, header.id.as_bytes())? {
+                let previous: EventHeader = rmp_serde::from_slice(&previous)?;
+                self.index_header(&mut batch, &previous, true)?;
+            }
+
+            batch.put_cf(headers_cf, header.id.as_bytes(), rmp_serde::to_vec_named(header)?);
+            self.index_header(&mut batch, header, false)?;
+            committed.push(header.clone());
+        }
+
+        self.write(batch)?;
+
+        // Broadcast live updates (ignore errors if no subscribers)
+        for header in committed {
+            let _ = self.broadcast_tx.send(header);
+        }
+
+        Ok(())
+    }
+
+    /// Collect headers referenced by index keys in `[start, end)` sharing `prefix`.
+    fn scan_index(
+        &self,
+        index: &str,
+        prefix: &[u8],
+        start: &[u8],
+        end: Option<&[u8]>,
+    ) -> Result<Vec<EventHeader>> {
+        let headers_cf = self.cf(CF_HEADERS)?;
+        let mut headers = Vec::new();
+
+        let iter = self
+            .db
+            .iterator_cf(self.cf(index)?, IteratorMode::From(start, Direction::Forward));
+        for item in iter {
+            let (key, _) = item?;
+            if !key.starts_with(prefix) || end.is_some_and(|end| &key[..] >= end) {
+                break;
+            }
+
+            let id = &key[key.len() - 16..];
+            if let Some(bytes) = self.db.get_cf(headers_cf, id)? {
+                headers.push(rmp_serde::from_slice(&bytes)?);
+            }
+        }
+
+        Ok(headers)
+    }
+
+    /// Header at one end of the time index (`IteratorMode::Start` or `End`).
+    fn boundary_header(&self, mode: IteratorMode) -> Result<Option<EventHeader>> {
+        match self.db.iterator_cf(self.cf(CF_INDEX_TIME)?, mode).next() {
+            Some(item) => {
+                let (key, _) = item?;
+                let id = &key[key.len() - 16..];
+                match self.db.get_cf(self.cf(CF_HEADERS)?, id)? {
+                    Some(bytes) => Ok(Some(rmp_serde::from_slice(&bytes)?)),
+                    None => Ok(None),
+                }
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Highest WAL sequence number on disk (0 if the WAL is empty).
+    fn last_wal_sequence(&self) -> Result<SequenceNumber> {
+        match self.db.iterator_cf(self.cf(CF_WAL)?, IteratorMode::End).next() {
+            Some(item) => {
+                let (key, _) = item?;
+                let bytes: [u8; 8] = key[..]
+                    .try_into()
+                    .map_err(|_| anyhow::anyhow!("Invalid WAL key length: {}", key.len()))?;
+                Ok(SequenceNumber::from_be_bytes(bytes))
+            }
+            None => Ok(0),
+        }
+    }
+
+    /// Get the next sequence number for WAL entries.
+    async fn next_sequence(&self) -> SequenceNumber {
+        let mut seq = self.wal_sequence.write().await;
+        *seq += 1;
+        *seq
+    }
+
+    /// Add a WAL entry to a batch.
+    fn put_wal_entry(&self, batch: &mut WriteBatch, entry: &WalEntry) -> Result<()> {
+        batch.put_cf(
+            self.cf(CF_WAL)?,
+            entry.sequence.to_be_bytes(),
+            rmp_serde::to_vec_named(entry)?,
+        );
+        Ok(())
+    }
+
+    /// Read a single WAL entry.
+    fn wal_entry(&self, sequence: SequenceNumber) -> Result<Option<WalEntry>> {
+        match self.db.get_cf(self.cf(CF_WAL)?, sequence.to_be_bytes())? {
+            Some(bytes) => Ok(Some(rmp_serde::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Add state updates for the given WAL entries to a batch.
+    fn set_wal_state(
+        &self,
+        batch: &mut WriteBatch,
+        sequences: &[SequenceNumber],
+        state: WalEntryState,
+    ) -> Result<()> {
+        for sequence in sequences {
+            if let Some(mut entry) = self.wal_entry(*sequence)? {
+                entry.state = state.clone();
+                self.put_wal_entry(batch, &entry)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Build a new WAL entry with the next sequence number.
+    async fn new_wal_entry(
+        &self,
+        transaction_id: TransactionId,
+        operation: WalOperation,
+        state: WalEntryState,
+    ) -> WalEntry {
+        WalEntry {
+            id: Uuid::new_v4(),
+            transaction_id,
+            sequence: self.next_sequence().await,
+            timestamp: Utc::now(),
+            operation,
+            state,
+        }
+    }
+
+    /// Number of WAL transactions begun but not yet finished.
+    async fn open_transaction_count(&self) -> u64 {
+        self.active_transactions
+            .read()
+            .await
+            .values()
+            .filter(|tx_state| tx_state.state == WalTransactionStateType::Active)
+            .count() as u64
+    }
+
+    /// Ensure a transaction is known and active, returning its state.
+    async fn active_transaction(&self, transaction_id: TransactionId) -> Result<WalTransactionState> {
+        let transactions = self.active_transactions.read().await;
+        match transactions.get(&transaction_id) {
+            Some(tx_state) if tx_state.state == WalTransactionStateType::Active => Ok(tx_state.clone()),
+            Some(tx_state) => Err(anyhow::anyhow!(
+                "Transaction {} is not active (state: {:?})",
+                transaction_id,
+                tx_state.state
+            )),
+            None => Err(anyhow::anyhow!("Transaction {} not found", transaction_id)),
+        }
+    }
+
+    /// Log a rollback for a transaction and mark its entries as rolled back.
+    async fn write_rollback(
+        &self,
+        transaction_id: TransactionId,
+        sequences: &[SequenceNumber],
+    ) -> Result<()> {
+        let rollback_entry = self
+            .new_wal_entry(
+                transaction_id,
+                WalOperation::RollbackTransaction { transaction_id },
+                WalEntryState::RolledBack,
+            )
+            .await;
+
+        let mut batch = WriteBatch::default();
+        self.set_wal_state(&mut batch, sequences, WalEntryState::RolledBack)?;
+        self.put_wal_entry(&mut batch, &rollback_entry)?;
+        self.write(batch)
+    }
+}
+
+#[async_trait]
+impl StorageBackend for RocksDbBackend {
+    async fn commit(&self, header: &EventHeader, payload: &[u8]) -> Result<()> {
+        self.write_events([(header, payload)])
+    }
+
+    async fn header(&self, id: &EventId) -> Result<Option<EventHeader>> {
+        match self.db.get_cf(self.cf(CF_HEADERS)?, id.as_bytes())? {
+            Some(bytes) => Ok(Some(rmp_serde::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn payload_bytes(&self, digest: &CausalDigest) -> Result<Option<Vec<u8>>> {
+        Ok(self.db.get_cf(self.cf(CF_PAYLOADS)?, digest)?)
+    }
+}
+
+#[async_trait]
+impl QueryableStorage for RocksDbBackend {
+    async fn events_by_kind(&self, kind: &str) -> Result<EventHeaderStream<'_>> {
+        let prefix = Self::kind_prefix(kind);
+        let headers = self.scan_index(CF_INDEX_KIND, &prefix, &prefix, None)?;
+        Ok(Box::pin(futures::stream::iter(headers.into_iter().map(Ok))))
+    }
+
+    async fn events_in_range(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<EventHeaderStream<'_>> {
+        let start = Self::time_key(&from);
+        let end = Self::time_key(&to);
+        let headers = self.scan_index(CF_INDEX_TIME, &[], &start, Some(&end))?;
+        Ok(Box::pin(futures::stream::iter(headers.into_iter().map(Ok))))
+    }
+
+    async fn events_by_intent(&self, intent: &IntentId) -> Result<EventHeaderStream<'_>> {
+        let prefix = intent.as_bytes();
+        let headers = self.scan_index(CF_INDEX_INTENT, prefix, prefix, None)?;
+        Ok(Box::pin(futures::stream::iter(headers.into_iter().map(Ok))))
+    }
+}
+
+#[async_trait]
+impl StorageStats for RocksDbBackend {
+    async fn storage_stats(&self) -> Result<StorageStatistics> {
+        // SST files plus memtables not yet flushed, across all column families
+        let mut bytes_used = 0;
+        for name in COLUMN_FAMILIES {
+            let cf = self.cf(name)?;
+            for property in ["rocksdb.total-sst-files-size", "rocksdb.cur-size-all-mem-tables"] {
+                bytes_used += self.db.property_int_value_cf(cf, property)?.unwrap_or(0);
+            }
+        }
+
+        Ok(StorageStatistics {
+            event_count: self.event_count()? as u64,
+            payload_count: self.payload_count()? as u64,
+            bytes_used,
+            oldest_event: self.boundary_header(IteratorMode::Start)?.map(|header| header.timestamp),
+            newest_event: self.boundary_header(IteratorMode::End)?.map(|header| header.timestamp),
+            wal_depth: self.wal_entry_count()? as u64,
+            open_transactions: self.open_transaction_count().await,
+            transactions_timed_out: 0,
+        })
+    }
+}
+
+#[async_trait]
+impl CompactableStorage for RocksDbBackend {
+    async fn remove_events(&self, ids: &[EventId]) -> Result<usize> {
+        let headers_cf = self.cf(CF_HEADERS)?;
+        let mut batch = WriteBatch::default();
+        let mut removed = Vec::new();
+
+        for id in ids {
+            if let Some(bytes) = self.db.get_cf(headers_cf, id.as_bytes())? {
+                let header: EventHeader = rmp_serde::from_slice(&bytes)?;
+                batch.delete_cf(headers_cf, id.as_bytes());
+                self.index_header(&mut batch, &header, true)?;
+                removed.push(header);
+            }
+        }
+
+        // Payloads are shared by digest; keep those still referenced elsewhere
+        if !removed.is_empty() {
+            let mut referenced = std::collections::HashSet::new();
+            for item in self.db.iterator_cf(headers_cf, IteratorMode::Start) {
+                let (key, value) = item?;
+                if removed.iter().any(|header| header.id.as_bytes()[..] == key[..]) {
+                    continue;
+                }
+                let header: EventHeader = rmp_serde::from_slice(&value)?;
+                referenced.insert(header.digest);
+            }
+
+            let payloads_cf = self.cf(CF_PAYLOADS)?;
+            for header in &removed {
+                if !referenced.contains(&header.digest) {
+                    batch.delete_cf(payloads_cf, header.digest);
+                }
+            }
+        }
+
+        self.write(batch)?;
+        Ok(removed.len())
+    }
+}
+
+#[async_trait]
+impl WriteAheadLog for RocksDbBackend {
+    async fn begin_transaction(&self) -> Result<TransactionId> {
+        let transaction_id = Uuid::new_v4();
+        let wal_entry = self
+            .new_wal_entry(
+                transaction_id,
+                WalOperation::BeginTransaction { transaction_id },
+                WalEntryState::Pending,
+            )
+            .await;
+
+        let mut batch = WriteBatch::default();
+        self.put_wal_entry(&mut batch, &wal_entry)?;
+        self.write(batch)?;
+
+        self.active_transactions.write().await.insert(
+            transaction_id,
+            WalTransactionState {
+                state: WalTransactionStateType::Active,
+                operations: vec![wal_entry.operation],
+                sequences: vec![wal_entry.sequence],
+            },
+        );
+
+        Ok(transaction_id)
+    }
+
+    async fn write_entry(
+        &self,
+        transaction_id: TransactionId,
+        operation: WalOperation,
+    ) -> Result<()> {
+        self.active_transaction(transaction_id).await?;
+
+        let wal_entry = self
+            .new_wal_entry(transaction_id, operation.clone(), WalEntryState::Pending)
+            .await;
+
+        let mut batch = WriteBatch::default();
+        self.put_wal_entry(&mut batch, &wal_entry)?;
+        self.write(batch)?;
+
+        let mut transactions = self.active_transactions.write().await;
+        if let Some(tx_state) = transactions.get_mut(&transaction_id) {
+            tx_state.operations.push(operation);
+            tx_state.sequences.push(wal_entry.sequence);
+        }
+
+        Ok(())
+    }
+
+    async fn commit_transaction(&self, transaction_id: TransactionId) -> Result<()> {
+        let tx_state = {
+            let mut transactions = self.active_transactions.write().await;
+            match transactions.get_mut(&transaction_id) {
+                Some(tx_state) if tx_state.state == WalTransactionStateType::Active => {
+                    tx_state.state = WalTransactionStateType::Committing;
+                    tx_state.clone()
+                }
+                Some(tx_state) => {
+                    return Err(anyhow::anyhow!(
+                        "Transaction {} is not active (state: {:?})",
+                        transaction_id,
+                        tx_state.state
+                    ));
+                }
+                None => return Err(anyhow::anyhow!("Transaction {} not found", transaction_id)),
+            }
+        };
+
+        // Apply every event in the transaction as one atomic batch
+        let events = tx_state.operations.iter().filter_map(|operation| match operation {
+            WalOperation::CommitEvent { header, payload } => Some((header, payload.as_ref())),
+            _ => None,
+        });
+        self.write_events(events)?;
+
+        // Log the commit and mark the transaction's entries committed together
+        let commit_entry = self
+            .new_wal_entry(
+                transaction_id,
+                WalOperation::CommitTransaction { transaction_id },
+                WalEntryState::Committed,
+            )
+            .await;
+        let mut batch = WriteBatch::default();
+        self.set_wal_state(&mut batch, &tx_state.sequences, WalEntryState::Committed)?;
+        self.put_wal_entry(&mut batch, &commit_entry)?;
+        self.write(batch)?;
+
+        if let Some(tx_state) = self.active_transactions.write().await.get_mut(&transaction_id) {
+            tx_state.state = WalTransactionStateType::Committed;
+        }
+
+        Ok(())
+    }
+
+    async fn rollback_transaction(&self, transaction_id: TransactionId) -> Result<()> {
+        let tx_state = self.active_transaction(transaction_id).await?;
+
+        self.write_rollback(transaction_id, &tx_state.sequences).await?;
+
+        if let Some(tx_state) = self.active_transactions.write().await.get_mut(&transaction_id) {
+            tx_state.state = WalTransactionStateType::RolledBack;
+        }
+
+        Ok(())
+    }
+
+    async fn recover(&self) -> Result<WalRecoveryResult> {
+        let mut result = WalRecoveryResult {
+            entries_recovered: 0,
+            transactions_rolled_back: 0,
+            transactions_committed: 0,
+            entries_checkpointed: 0,
+            entries_quarantined: 0,
+            recovery_errors: Vec::new(),
+        };
+
+        // Entries are keyed by big-endian sequence, so iteration is already ordered
+        let mut transaction_states: HashMap<TransactionId, Vec<WalEntry>> = HashMap::new();
+        for item in self.db.iterator_cf(self.cf(CF_WAL)?, IteratorMode::Start) {
+            let (_, value) = item?;
+            match rmp_serde::from_slice::<WalEntry>(&value) {
+                Ok(entry) => {
+                    if entry.state == WalEntryState::Checkpointed {
+                        result.entries_checkpointed += 1;
+                    }
+                    transaction_states
+                        .entry(entry.transaction_id)
+                        .or_default()
+                        .push(entry);
+                    result.entries_recovered += 1;
+                }
+                Err(e) => {
+                    result.recovery_errors.push(format!("Failed to deserialize WAL entry: {}", e));
+                }
+            }
+        }
+
+        for (transaction_id, entries) in transaction_states {
+            let has_commit = entries
+                .iter()
+                .any(|e| matches!(e.operation, WalOperation::CommitTransaction { .. }));
+            let has_rollback = entries
+                .iter()
+                .any(|e| matches!(e.operation, WalOperation::RollbackTransaction { .. }));
+
+            if has_commit {
+                // Re-apply committed events; checkpointed ones are already durable
+                let events = entries.iter().filter_map(|entry| match &entry.operation {
+                    WalOperation::CommitEvent { header, payload }
+                        if entry.state == WalEntryState::Committed =>
+                    {
+                        Some((header, payload.as_ref()))
+                    }
+                    _ => None,
+                });
+                if let Err(e) = self.write_events(events) {
+                    result.recovery_errors.push(format!("Failed to apply committed events: {}", e));
+                }
+                result.transactions_committed += 1;
+            } else if !has_rollback {
+                // Incomplete transaction - roll it back
+                let sequences: Vec<SequenceNumber> = entries.iter().map(|e| e.sequence).collect();
+                match self.write_rollback(transaction_id, &sequences).await {
+                    Ok(()) => {
+                        if let Some(tx_state) =
+                            self.active_transactions.write().await.get_mut(&transaction_id)
+                        {
+                            tx_state.state = WalTransactionStateType::RolledBack;
+                        }
+                        result.transactions_rolled_back += 1;
+                    }
+                    Err(e) => result.recovery_errors.push(format!(
+                        "Failed to rollback transaction {}: {}",
+                        transaction_id, e
+                    )),
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    async fn checkpoint(&self, sequence: SequenceNumber) -> Result<()> {
+        let mut batch = WriteBatch::default();
+
+        for item in self.db.iterator_cf(self.cf(CF_WAL)?, IteratorMode::Start) {
+            let (key, value) = item?;
+            if key[..] > sequence.to_be_bytes()[..] {
+                break;
+            }
+
+            let mut entry: WalEntry = rmp_serde::from_slice(&value)?;
+            if entry.state == WalEntryState::Committed {
+                entry.state = WalEntryState::Checkpointed;
+                self.put_wal_entry(&mut batch, &entry)?;
+            }
+        }
+
+        // Checkpointed entries are kept for audit, consistent with other backends
+        self.write(batch)
+    }
+
+    async fn current_sequence(&self) -> Result<SequenceNumber> {
+        Ok(*self.wal_sequence.read().await)
+    }
+
+    async fn wal_health(&self) -> Result<WalHealth> {
+        let mut health = WalHealth::new(*self.wal_sequence.read().await);
+        for item in self.db.iterator_cf(self.cf(CF_WAL)?, IteratorMode::Start) {
+            let (_, value) = item?;
+            let entry: WalEntry = rmp_serde::from_slice(&value)?;
+            health.record_entry(&entry.state, entry.timestamp, value.len() as u64);
+        }
+        health.active_transactions = self.open_transaction_count().await;
+        Ok(health)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::TryStreamExt;
+    use serde::{Deserialize, Serialize};
+    use tempfile::TempDir;
+    use uuid::Uuid;
+    use toka_store_core::{create_event_header, prelude::*};
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    struct TestEvent {
+        message: String,
+        value: i32,
+    }
+
+    fn temporary() -> (TempDir, RocksDbBackend) {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = RocksDbBackend::open(dir.path()).unwrap();
+        (dir, backend)
+    }
+
+    fn test_event(kind: &str, message: &str, value: i32) -> (EventHeader, Vec<u8>) {
+        let event = TestEvent { message: message.to_string(), value };
+        let header = create_event_header(&[], Uuid::new_v4(), kind.to_string(), &event).unwrap();
+        (header, rmp_serde::to_vec_named(&event).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_basic_storage_operations() {
+        let (_dir, backend) = temporary();
+        let (header, payload_bytes) = test_event("test.event", "test", 42);
+
+        backend.commit(&header, &payload_bytes).await.unwrap();
+
+        let retrieved_header = backend.header(&header.id).await.unwrap().unwrap();
+        assert_eq!(retrieved_header, header);
+
+        let payload = backend.payload_bytes(&header.digest).await.unwrap().unwrap();
+        let retrieved_event: TestEvent = rmp_serde::from_slice(&payload).unwrap();
+        assert_eq!(retrieved_event.value, 42);
+    }
+
+    #[tokio::test]
+    async fn test_missing_events() {
+        let (_dir, backend) = temporary();
+
+        assert!(backend.header(&Uuid::new_v4()).await.unwrap().is_none());
+        assert!(backend.payload_bytes(&[0u8; 32]).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_payload_deduplication() {
+        let (_dir, backend) = temporary();
+        let event = TestEvent { message: "duplicate".to_string(), value: 123 };
+
+        let header1 = create_event_header(&[], Uuid::new_v4(), "test.event".to_string(), &event).unwrap();
+        let header2 = create_event_header(&[], Uuid::new_v4(), "test.event".to_string(), &event).unwrap();
+        assert_eq!(header1.digest, header2.digest);
+
+        let payload_bytes = rmp_serde::to_vec_named(&event).unwrap();
+        backend.commit(&header1, &payload_bytes).await.unwrap();
+        backend.commit(&header2, &payload_bytes).await.unwrap();
+
+        assert_eq!(backend.event_count().unwrap(), 2);
+        assert_eq!(backend.payload_count().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_persistence() {
+        let dir = tempfile::tempdir().unwrap();
+        let (header, payload_bytes) = test_event("test.persist", "persistent", 999);
+
+        {
+            let backend = RocksDbBackend::open(dir.path()).unwrap();
+            backend.commit(&header, &payload_bytes).await.unwrap();
+        }
+
+        let backend = RocksDbBackend::open(dir.path()).unwrap();
+        assert_eq!(backend.header(&header.id).await.unwrap().unwrap(), header);
+        assert_eq!(
+            backend.payload_bytes(&header.digest).await.unwrap().unwrap(),
+            payload_bytes
+        );
+    }
+
+    #[tokio::test]
+    async fn test_live_event_stream() {
+        let (_dir, backend) = temporary();
+        let mut rx = backend.subscribe();
+        let (header, payload_bytes) = test_event("test.live", "live", 777);
+
+        backend.commit(&header, &payload_bytes).await.unwrap();
+
+        assert_eq!(rx.recv().await.unwrap(), header);
+    }
+
+    #[tokio::test]
+    async fn test_counts() {
+        let (_dir, backend) = temporary();
+
+        assert_eq!(backend.event_count().unwrap(), 0);
+        assert_eq!(backend.payload_count().unwrap(), 0);
+        assert_eq!(backend.wal_entry_count().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_commit_batch() {
+        let (_dir, backend) = temporary();
+        let mut rx = backend.subscribe();
+        let events: Vec<(EventHeader, Vec<u8>)> = (0..10)
+            .map(|i| test_event("test.batch", "batch", i))
+            .collect();
+
+        backend.commit_batch(&events).await.unwrap();
+
+        assert_eq!(backend.event_count().unwrap(), 10);
+        for (header, _) in &events {
+            assert_eq!(&rx.recv().await.unwrap(), header);
+        }
+
+        let stats = backend.storage_stats().await.unwrap();
+        assert_eq!(stats.event_count, 10);
+        assert_eq!(stats.payload_count, 10);
+        assert_eq!(stats.oldest_event, Some(events[0].0.timestamp));
+        assert_eq!(stats.newest_event, Some(events[9].0.timestamp));
+        assert!(stats.bytes_used > 0);
+    }
+
+    #[tokio::test]
+    async fn test_compact_intent() {
+        let (_dir, backend) = temporary();
+        let intent = Uuid::new_v4();
+
+        let mut headers = Vec::new();
+        for (i, kind) in ["task.progress", "task.progress", "task.done"].iter().enumerate() {
+            let event = TestEvent { message: kind.to_string(), value: i as i32 };
+            let header = create_event_header(&[], intent, kind.to_string(), &event).unwrap();
+            backend.commit(&header, &rmp_serde::to_vec_named(&event).unwrap()).await.unwrap();
+            headers.push(header);
+        }
+
+        let policy = CompactionPolicy::default().with_kind("task.progress", KindCompaction::Archive);
+        backend.compact_intent(&intent, &policy).await.unwrap().unwrap();
+
+        assert_eq!(backend.event_count().unwrap(), 2);
+        assert_eq!(backend.payload_count().unwrap(), 2);
+        assert!(backend.header(&headers[0].id).await.unwrap().is_none());
+
+        // Index entries for archived events are gone too
+        let progress: Vec<EventHeader> =
+            backend.events_by_kind("task.progress").await.unwrap().try_collect().await.unwrap();
+        assert!(progress.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_query_by_kind_intent_and_range() {
+        let (_dir, backend) = temporary();
+        let intent = Uuid::new_v4();
+        let start = Utc::now();
+
+        let mut headers = Vec::new();
+        for (i, kind) in ["test.a", "test.b", "test.a"].iter().enumerate() {
+            let event = TestEvent { message: "query".to_string(), value: i as i32 };
+            let header = create_event_header(&[], intent, kind.to_string(), &event).unwrap();
+            backend.commit(&header, &rmp_serde::to_vec_named(&event).unwrap()).await.unwrap();
+            headers.push(header);
+        }
+
+        let by_kind: Vec<EventHeader> =
+            backend.events_by_kind("test.a").await.unwrap().try_collect().await.unwrap();
+        assert_eq!(by_kind.len(), 2);
+        assert!(by_kind.iter().all(|h| h.kind == "test.a"));
+
+        // "test.a" must not match a kind that merely shares the prefix
+        let by_prefix: Vec<EventHeader> =
+            backend.events_by_kind("test.").await.unwrap().try_collect().await.unwrap();
+        assert!(by_prefix.is_empty());
+
+        let by_intent: Vec<EventHeader> =
+            backend.events_by_intent(&intent).await.unwrap().try_collect().await.unwrap();
+        assert_eq!(by_intent.len(), 3);
+
+        let in_range: Vec<EventHeader> = backend
+            .events_in_range(start, Utc::now() + chrono::Duration::seconds(1))
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(in_range.len(), 3);
+        assert!(in_range.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+    }
+
+    #[tokio::test]
+    async fn test_wal_basic_transaction() {
+        let (_dir, backend) = temporary();
+        let tx_id = backend.begin_transaction().await.unwrap();
+        assert_eq!(backend.wal_entry_count().unwrap(), 1);
+
+        let (header, payload) = test_event("test.wal", "wal test", 42);
+        backend
+            .write_entry(tx_id, WalOperation::CommitEvent { header, payload: payload.into() })
+            .await
+            .unwrap();
+        assert_eq!(backend.wal_entry_count().unwrap(), 2);
+
+        backend.commit_transaction(tx_id).await.unwrap();
+        assert_eq!(backend.wal_entry_count().unwrap(), 3);
+        assert_eq!(backend.event_count().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_wal_rollback() {
+        let (_dir, backend) = temporary();
+        let tx_id = backend.begin_transaction().await.unwrap();
+
+        let (header, payload) = test_event("test.rollback", "rollback test", 99);
+        backend
+            .write_entry(tx_id, WalOperation::CommitEvent { header, payload: payload.into() })
+            .await
+            .unwrap();
+        backend.rollback_transaction(tx_id).await.unwrap();
+
+        assert_eq!(backend.wal_entry_count().unwrap(), 3);
+        assert_eq!(backend.event_count().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_wal_commit_with_wal() {
+        let (_dir, backend) = temporary();
+        let tx_id = backend.begin_transaction().await.unwrap();
+        let (header, payload) = test_event("test.commit_wal", "commit with wal", 123);
+
+        backend.commit_with_wal(tx_id, &header, &payload).await.unwrap();
+        backend.commit_transaction(tx_id).await.unwrap();
+
+        assert_eq!(backend.event_count().unwrap(), 1);
+        assert_eq!(backend.header(&header.id).await.unwrap().unwrap(), header);
+    }
+
+    #[tokio::test]
+    async fn test_wal_sequence_numbers() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let backend = RocksDbBackend::open(dir.path()).unwrap();
+            assert_eq!(backend.current_sequence().await.unwrap(), 0);
+
+            let tx_id = backend.begin_transaction().await.unwrap();
+            assert_eq!(backend.current_sequence().await.unwrap(), 1);
+
+            let (header, payload) = test_event("test.sequence", "seq", 1);
+            backend
+                .write_entry(tx_id, WalOperation::CommitEvent { header, payload: payload.into() })
+                .await
+                .unwrap();
+            assert_eq!(backend.current_sequence().await.unwrap(), 2);
+        }
+
+        // Sequence numbers resume after reopening
+        let backend = RocksDbBackend::open(dir.path()).unwrap();
+        assert_eq!(backend.current_sequence().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_wal_checkpoint() {
+        let (_dir, backend) = temporary();
+        let tx_id = backend.begin_transaction().await.unwrap();
+        let (header, payload) = test_event("test.checkpoint", "checkpoint", 42);
+        backend
+            .write_entry(tx_id, WalOperation::CommitEvent { header, payload: payload.into() })
+            .await
+            .unwrap();
+        backend.commit_transaction(tx_id).await.unwrap();
+
+        let current_seq = backend.current_sequence().await.unwrap();
+        backend.checkpoint(current_seq).await.unwrap();
+
+        assert_eq!(backend.wal_entry_count().unwrap(), 3);
+        let entry = backend.wal_entry(1).unwrap().unwrap();
+        assert_eq!(entry.state, WalEntryState::Checkpointed);
+    }
+
+    #[tokio::test]
+    async fn test_wal_recovery() {
+        let (_dir, backend) = temporary();
+
+        // Begin a transaction but don't commit
+        let tx_id = backend.begin_transaction().await.unwrap();
+        let (header, payload) = test_event("test.recovery", "recovery", 99);
+        backend
+            .write_entry(tx_id, WalOperation::CommitEvent { header, payload: payload.into() })
+            .await
+            .unwrap();
+
+        // Begin another transaction and commit it
+        let tx_id2 = backend.begin_transaction().await.unwrap();
+        let (header, payload) = test_event("test.recovery2", "recovery2", 100);
+        backend
+            .write_entry(tx_id2, WalOperation::CommitEvent { header, payload: payload.into() })
+            .await
+            .unwrap();
+        backend.commit_transaction(tx_id2).await.unwrap();
+
+        let recovery_result = backend.recover().await.unwrap();
+
+        assert!(recovery_result.entries_recovered > 0);
+        assert_eq!(recovery_result.transactions_rolled_back, 1);
+        assert_eq!(recovery_result.transactions_committed, 1);
+        assert_eq!(backend.event_count().unwrap(), 1);
+    }
+
+    toka_store_conformance::storage_conformance_tests!(storage_conformance, temporary(), guarded);
+    toka_store_conformance::wal_conformance_tests!(wal_conformance, temporary(), guarded);
+
+    #[tokio::test]
+    async fn test_conformance_reopen_preserves_committed() {
+        let dir = tempfile::tempdir().unwrap();
+        toka_store_conformance::recovery::reopen_preserves_committed(|| async { RocksDbBackend::open(dir.path()) })
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,22 @@
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+
+//! **toka-store-rocksdb** – RocksDB-based persistent storage driver for Toka OS.
+//!
+//! This crate provides a storage backend tuned for high-throughput, append-heavy
+//! workloads using the RocksDB LSM engine. Headers, payloads, WAL entries and
+//! the secondary indexes used for queries each live in their own column family,
+//! so they can be compacted and tuned independently.
+//!
+//! All multi-key writes (an event plus its index entries, or every event in a
+//! WAL transaction) are applied through a single RocksDB `WriteBatch`, which
+//! makes them atomic and amortises the write cost across the batch.
+//!
+//! The backend is only compiled with the `rocksdb` feature enabled, as
+//! building the bundled RocksDB library requires a C++ toolchain and libclang.
+
+#[cfg(feature = "rocksdb")]
+mod backend;
+
+#[cfg(feature = "rocksdb")]
+pub use backend::{RocksDbBackend, RocksDbConfig};
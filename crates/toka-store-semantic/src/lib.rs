@@ -606,6 +606,11 @@ mod tests {
             digest: [0u8; 32],
             intent: Uuid::new_v4(),
             kind: "test.parent".to_string(),
+            format: Default::default(),
+            clock: None,
+            namespace: None,
+            blobs: Vec::new(),
+            idempotency_key: None,
         };
         
         let child_header = EventHeader {
@@ -615,6 +620,11 @@ mod tests {
             digest: [1u8; 32],
             intent: Uuid::new_v4(),
             kind: "test.child".to_string(),
+            format: Default::default(),
+            clock: None,
+            namespace: None,
+            blobs: Vec::new(),
+            idempotency_key: None,
         };
         
         let events = vec![
@@ -651,6 +661,11 @@ mod tests {
             digest: [0u8; 32],
             intent: Uuid::new_v4(),
             kind: "user.login".to_string(),
+            format: Default::default(),
+            clock: None,
+            namespace: None,
+            blobs: Vec::new(),
+            idempotency_key: None,
         };
         
         let result = classifier.analyze(&header, &[]).await.unwrap();
@@ -0,0 +1,179 @@
+//! SQLite-backed blob store.
+//!
+//! Blobs are split into [`BLOB_CHUNK_SIZE`] rows of the `blob_chunks` table
+//! so neither uploads nor downloads need the whole artifact in memory. While
+//! an upload is in progress its chunks are keyed by a temporary upload id;
+//! once the content hash is known they are re-keyed to the digest and the
+//! blob is recorded in the `blobs` table in one transaction, so readers never
+//! see partial blobs.
+
+use std::io;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::io::AsyncRead;
+use futures::TryStreamExt;
+use sqlx::{Row, Sqlite, SqlitePool};
+use uuid::Uuid;
+
+use toka_store_core::{
+    digest_hex, read_chunk, BlobDigest, BlobReader, BlobRef, BlobStore, StorageError,
+    BLOB_CHUNK_SIZE,
+};
+
+/// Blob store sharing the database of a [`SqliteBackend`](crate::SqliteBackend).
+#[derive(Debug, Clone)]
+pub struct SqliteBlobStore {
+    pool: SqlitePool,
+    read_only: bool,
+}
+
+impl SqliteBlobStore {
+    pub(crate) fn new(pool: SqlitePool, read_only: bool) -> Self {
+        Self { pool, read_only }
+    }
+
+    fn ensure_writable(&self, operation: &str) -> Result<(), StorageError> {
+        if self.read_only {
+            return Err(StorageError::ReadOnly(operation.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Number of stored blobs.
+    pub async fn blob_count(&self) -> Result<i64> {
+        let row = sqlx::query::<Sqlite>("SELECT COUNT(*) as count FROM blobs")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(row.get("count"))
+    }
+
+    async fn discard_upload(&self, upload_key: &str) -> Result<()> {
+        sqlx::query::<Sqlite>("DELETE FROM blob_chunks WHERE blob_key = ?")
+            .bind(upload_key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn upload(&self, reader: &mut (dyn AsyncRead + Unpin + Send), upload_key: &str) -> Result<BlobRef> {
+        let mut hasher = blake3::Hasher::new();
+        let mut size = 0u64;
+        let mut chunk = vec![0u8; BLOB_CHUNK_SIZE];
+        let mut seq = 0i64;
+        loop {
+            let filled = read_chunk(reader, &mut chunk).await?;
+            if filled == 0 {
+                break;
+            }
+            hasher.update(&chunk[..filled]);
+            size += filled as u64;
+            sqlx::query::<Sqlite>("INSERT INTO blob_chunks (blob_key, seq, data) VALUES (?, ?, ?)")
+                .bind(upload_key)
+                .bind(seq)
+                .bind(&chunk[..filled])
+                .execute(&self.pool)
+                .await?;
+            seq += 1;
+            if filled < chunk.len() {
+                break;
+            }
+        }
+
+        let blob = BlobRef {
+            digest: *hasher.finalize().as_bytes(),
+            size,
+        };
+
+        let mut tx = self.pool.begin().await?;
+        let exists = sqlx::query::<Sqlite>("SELECT 1 FROM blobs WHERE digest = ?")
+            .bind(&blob.digest[..])
+            .fetch_optional(&mut *tx)
+            .await?
+            .is_some();
+        if exists {
+            sqlx::query::<Sqlite>("DELETE FROM blob_chunks WHERE blob_key = ?")
+                .bind(upload_key)
+                .execute(&mut *tx)
+                .await?;
+        } else {
+            sqlx::query::<Sqlite>("UPDATE blob_chunks SET blob_key = ? WHERE blob_key = ?")
+                .bind(blob.hex())
+                .bind(upload_key)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query::<Sqlite>("INSERT INTO blobs (digest, size, chunks) VALUES (?, ?, ?)")
+                .bind(&blob.digest[..])
+                .bind(blob.size as i64)
+                .bind(seq)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(blob)
+    }
+}
+
+#[async_trait]
+impl BlobStore for SqliteBlobStore {
+    async fn put(&self, reader: &mut (dyn AsyncRead + Unpin + Send)) -> Result<BlobRef> {
+        self.ensure_writable("put blob")?;
+        let upload_key = format!("upload:{}", Uuid::new_v4());
+        match self.upload(reader, &upload_key).await {
+            Ok(blob) => Ok(blob),
+            Err(e) => {
+                // Best effort; leftover upload chunks are unreachable either way
+                let _ = self.discard_upload(&upload_key).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn get(&self, digest: &BlobDigest) -> Result<Option<BlobReader>> {
+        if !self.contains(digest).await? {
+            return Ok(None);
+        }
+
+        // Fetch one chunk at a time as the reader is consumed
+        let pool = self.pool.clone();
+        let key = digest_hex(digest);
+        let chunks = futures::stream::try_unfold(0i64, move |seq| {
+            let pool = pool.clone();
+            let key = key.clone();
+            async move {
+                let row = sqlx::query::<Sqlite>("SELECT data FROM blob_chunks WHERE blob_key = ? AND seq = ?")
+                    .bind(key)
+                    .bind(seq)
+                    .fetch_optional(&pool)
+                    .await
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+                Ok::<_, io::Error>(row.map(|row| (row.get::<Vec<u8>, _>("data"), seq + 1)))
+            }
+        });
+        Ok(Some(Box::new(Box::pin(chunks).into_async_read())))
+    }
+
+    async fn contains(&self, digest: &BlobDigest) -> Result<bool> {
+        Ok(sqlx::query::<Sqlite>("SELECT 1 FROM blobs WHERE digest = ?")
+            .bind(&digest[..])
+            .fetch_optional(&self.pool)
+            .await?
+            .is_some())
+    }
+
+    async fn delete(&self, digest: &BlobDigest) -> Result<bool> {
+        self.ensure_writable("delete blob")?;
+        let mut tx = self.pool.begin().await?;
+        let deleted = sqlx::query::<Sqlite>("DELETE FROM blobs WHERE digest = ?")
+            .bind(&digest[..])
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        sqlx::query::<Sqlite>("DELETE FROM blob_chunks WHERE blob_key = ?")
+            .bind(digest_hex(digest))
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(deleted > 0)
+    }
+}
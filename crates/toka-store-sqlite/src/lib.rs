@@ -11,24 +11,171 @@
 //! durability and crash recovery capabilities.
 
 use std::path::Path;
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
 
 use anyhow::Result;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use sqlx::sqlite::SqliteRow;
 use sqlx::{SqlitePool, Sqlite, Row};
-use tokio::sync::{broadcast, RwLock};
+use tokio::sync::{broadcast, Mutex, RwLock};
 use uuid::Uuid;
 
 use toka_store_core::{
-    StorageBackend, EventHeader, EventId, CausalDigest,
+    StorageBackend, QueryableStorage, CompactableStorage, ReplayableStorage, ReplayFrom,
+    RetentionPolicy, RetentionReport, RetentionStorage,
+    SnapshotManifest, SnapshotReader, SnapshotStorage, SnapshotWriter,
+    IndexDefinition, IndexTarget, IndexedStorage, StorageStatistics, StorageStats,
+    replay_then_live, EventHeaderStream, EventHeader, EventId, CausalDigest, IntentId,
     WriteAheadLog, WalEntry, WalOperation, WalEntryState, WalRecoveryResult,
-    TransactionId, SequenceNumber, StorageError,
+    TransactionId, SequenceNumber, SavepointId, StorageError, Compression, CompressionPolicy,
+    NamespaceStats, NamespacedStorage, filter_namespace, DEFAULT_NAMESPACE,
+    ConditionalStorage, Precondition, intent_head,
+    CommitOutcome, DeduplicatingStorage, DEFAULT_DEDUP_WINDOW,
+    WalCompaction, WalCompactionPolicy, WalCompactionReport,
+    is_stale, TransactionTimedOut, TransactionTimeouts, WalSource,
+    RecoveryHooks, RecoveryObserver, ReplayDecision,
+    decode_wal_operation, encode_wal_operation, QuarantinedWalEntry, WalQuarantine,
+    wal_format::wal_format_version, WalHealth,
 };
 
+pub mod blob;
+pub mod router;
+
+pub use blob::SqliteBlobStore;
+pub use router::{RoutingStats, SqliteRouter};
+
 /// Default broadcast channel size for live event streaming.
 const DEFAULT_BROADCAST_SIZE: usize = 256;
 
+/// Maximum number of digests bound in a single bulk payload query.
+const BULK_QUERY_CHUNK: usize = 500;
+
+//─────────────────────────────
+//  WAL durability
+//─────────────────────────────
+
+/// How eagerly WAL entries are written to the database.
+///
+/// Transaction commits and rollbacks always reach the database before they
+/// return, so batching never loses a committed transaction. It only delays
+/// the entries of transactions that are still open, which recovery would
+/// roll back after a crash anyway.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WalDurability {
+    /// Write every WAL entry in its own round trip
+    #[default]
+    Immediate,
+    /// Buffer WAL entries and write them in one transaction once
+    /// `max_entries` are pending, an entry has waited `max_delay`, or a
+    /// transaction finishes
+    Batched {
+        /// Number of buffered entries that forces a flush
+        max_entries: usize,
+        /// Longest time an entry stays buffered before the next write flushes it
+        max_delay: std::time::Duration,
+    },
+}
+
+impl WalDurability {
+    /// Batched durability with the default limits of 64 entries and 10ms.
+    pub fn batched() -> Self {
+        WalDurability::Batched {
+            max_entries: 64,
+            max_delay: std::time::Duration::from_millis(10),
+        }
+    }
+
+    fn should_flush(&self, buffer: &WalBuffer) -> bool {
+        match self {
+            WalDurability::Immediate => true,
+            WalDurability::Batched { max_entries, max_delay } => {
+                buffer.rows.len() >= *max_entries
+                    || buffer.oldest.is_some_and(|oldest| oldest.elapsed() >= *max_delay)
+            }
+        }
+    }
+}
+
+/// How hard SQLite works to make commits survive a crash.
+///
+/// This is SQLite's own `synchronous` setting, independent of
+/// [`WalDurability`], which controls how Toka batches its WAL entries. The
+/// database always runs in SQLite's WAL journal mode.
+///
+/// | Level     | Application crash | OS crash / power loss                    |
+/// |-----------|-------------------|------------------------------------------|
+/// | `Full`    | no loss           | no loss                                  |
+/// | `Normal`  | no loss           | recent commits may roll back             |
+/// | `Relaxed` | no loss           | recent commits lost, database may corrupt |
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DurabilityLevel {
+    /// Sync the SQLite WAL on every commit (`synchronous = FULL`)
+    #[default]
+    Full,
+    /// Sync only at SQLite checkpoints (`synchronous = NORMAL`); the usual
+    /// choice for services that can replay a few seconds of work
+    Normal,
+    /// Never sync and let the OS flush (`synchronous = OFF`); for benchmarks
+    /// and scratch databases only
+    Relaxed,
+}
+
+impl DurabilityLevel {
+    fn synchronous(&self) -> sqlx::sqlite::SqliteSynchronous {
+        use sqlx::sqlite::SqliteSynchronous;
+
+        match self {
+            DurabilityLevel::Full => SqliteSynchronous::Full,
+            DurabilityLevel::Normal => SqliteSynchronous::Normal,
+            DurabilityLevel::Relaxed => SqliteSynchronous::Off,
+        }
+    }
+}
+
+/// Options for [`SqliteBackend::open_with_options`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SqliteOptions {
+    /// SQLite sync level for commits
+    pub durability_level: DurabilityLevel,
+    /// Batching of Toka WAL entries
+    pub wal_durability: WalDurability,
+}
+
+impl SqliteOptions {
+    /// Set the SQLite sync level.
+    pub fn with_durability_level(mut self, level: DurabilityLevel) -> Self {
+        self.durability_level = level;
+        self
+    }
+
+    /// Set the WAL entry batching.
+    pub fn with_wal_durability(mut self, durability: WalDurability) -> Self {
+        self.wal_durability = durability;
+        self
+    }
+}
+
+/// A WAL entry waiting to be written.
+#[derive(Debug)]
+struct PendingWalRow {
+    id: Uuid,
+    transaction_id: TransactionId,
+    timestamp: DateTime<Utc>,
+    operation_bytes: Vec<u8>,
+    state: WalEntryState,
+}
+
+/// WAL entries buffered under [`WalDurability::Batched`].
+#[derive(Debug, Default)]
+struct WalBuffer {
+    rows: Vec<PendingWalRow>,
+    oldest: Option<std::time::Instant>,
+}
+
 //─────────────────────────────
 //  SQLite storage backend with WAL
 //─────────────────────────────
@@ -43,9 +190,21 @@ const DEFAULT_BROADCAST_SIZE: usize = 256;
 pub struct SqliteBackend {
     pool: SqlitePool,
     broadcast_tx: broadcast::Sender<EventHeader>,
+    compression: CompressionPolicy,
+    indexes: Arc<RwLock<Vec<IndexDefinition>>>,
+    read_only: bool,
+    dedup_window: std::time::Duration,
     // WAL state management
+    durability: WalDurability,
+    wal_buffer: Arc<Mutex<WalBuffer>>,
+    wal_compaction: WalCompactionPolicy,
+    wal_reclaimed: Arc<std::sync::Mutex<WalCompactionReport>>,
     wal_sequence: Arc<RwLock<SequenceNumber>>,
     active_transactions: Arc<RwLock<HashMap<TransactionId, WalTransactionState>>>,
+    transaction_ttl: Option<std::time::Duration>,
+    timeout_tx: broadcast::Sender<TransactionTimedOut>,
+    timed_out: AtomicU64,
+    recovery_hooks: RecoveryHooks,
 }
 
 /// State tracking for active WAL transactions.
@@ -57,8 +216,23 @@ struct WalTransactionState {
     state: WalTransactionStateType,
     /// Operations logged in this transaction
     operations: Vec<WalOperation>,
-    /// Sequence numbers for this transaction's entries
-    sequences: Vec<SequenceNumber>,
+    /// IDs of this transaction's WAL entries
+    entries: Vec<Uuid>,
+    /// When the transaction was begun
+    began_at: DateTime<Utc>,
+    /// Savepoints in creation order
+    savepoints: Vec<SavepointMark>,
+}
+
+/// Position of a savepoint within a transaction.
+#[derive(Debug, Clone)]
+struct SavepointMark {
+    /// Savepoint identifier
+    savepoint_id: SavepointId,
+    /// ID of the savepoint's WAL entry
+    entry: Uuid,
+    /// Number of operations logged up to and including the savepoint
+    operations: usize,
 }
 
 /// State types for WAL transactions.
@@ -88,16 +262,67 @@ impl SqliteBackend {
     /// # Errors
     /// Returns an error if the database cannot be opened or created.
     pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with_durability(path, WalDurability::Immediate).await
+    }
+
+    /// Opens or creates a database at `path` with the given WAL durability.
+    ///
+    /// [`WalDurability::Batched`] groups the WAL entries of concurrent
+    /// transactions into fewer database transactions, trading a little
+    /// latency on open transactions for write throughput.
+    pub async fn open_with_durability<P: AsRef<Path>>(path: P, durability: WalDurability) -> Result<Self> {
+        Self::open_with_options(path, SqliteOptions::default().with_wal_durability(durability)).await
+    }
+
+    /// Opens or creates a database at `path` with the given options.
+    ///
+    /// See [`DurabilityLevel`] for what each sync level risks; lower levels
+    /// make commits substantially faster on slow disks.
+    pub async fn open_with_options<P: AsRef<Path>>(path: P, options: SqliteOptions) -> Result<Self> {
         use sqlx::sqlite::SqliteConnectOptions;
         
         let opts = SqliteConnectOptions::new()
             .filename(&path)
             .create_if_missing(true)
             // Enable WAL mode for better concurrency
-            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal);
+            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal)
+            .synchronous(options.durability_level.synchronous());
         
         let pool = SqlitePool::connect_with(opts).await?;
-        Self::from_pool(pool).await
+        Ok(Self::from_pool(pool).await?.with_wal_durability(options.wal_durability))
+    }
+
+    /// Opens an existing SQLite database without write access.
+    ///
+    /// The connection is opened with SQLite's read-only flag and no
+    /// migrations are run, so this is safe to point at a production database
+    /// that another process is writing to. Commits, WAL operations and other
+    /// writes fail with [`StorageError::ReadOnly`].
+    pub async fn open_read_only<P: AsRef<Path>>(path: P) -> Result<Self> {
+        use sqlx::sqlite::SqliteConnectOptions;
+
+        let opts = SqliteConnectOptions::new()
+            .filename(&path)
+            .read_only(true);
+
+        let pool = SqlitePool::connect_with(opts).await?;
+        Self::read_only_from_pool(pool).await
+    }
+
+    /// Creates a read-only backend from an existing pool.
+    ///
+    /// Like [`open_read_only`](Self::open_read_only), but the pool's size and
+    /// connection options are up to the caller, e.g. a large pool of readers
+    /// serving as a replica behind a [`SqliteRouter`]. No migrations are
+    /// run, so the database must already have been created by a writer.
+    pub async fn read_only_from_pool(pool: SqlitePool) -> Result<Self> {
+        let backend = Self {
+            read_only: true,
+            ..Self::unmigrated(pool)
+        };
+        backend.load_indexes().await?;
+        backend.initialize_wal_sequence().await?;
+        Ok(backend)
     }
 
     /// Opens an in-memory SQLite database.
@@ -115,16 +340,46 @@ impl SqliteBackend {
     /// This allows sharing a database connection pool across multiple
     /// components or when using custom sqlx configurations.
     pub async fn from_pool(pool: SqlitePool) -> Result<Self> {
-        let backend = Self {
+        let backend = Self::unmigrated(pool);
+
+        backend.migrate().await?;
+        backend.load_indexes().await?;
+        backend.initialize_wal_sequence().await?;
+        Ok(backend)
+    }
+
+    fn unmigrated(pool: SqlitePool) -> Self {
+        Self {
             pool,
             broadcast_tx: broadcast::channel(DEFAULT_BROADCAST_SIZE).0,
+            compression: CompressionPolicy::disabled(),
+            indexes: Arc::new(RwLock::new(Vec::new())),
+            read_only: false,
+            dedup_window: DEFAULT_DEDUP_WINDOW,
+            durability: WalDurability::Immediate,
+            wal_buffer: Arc::new(Mutex::new(WalBuffer::default())),
+            wal_compaction: WalCompactionPolicy::Manual,
+            wal_reclaimed: Arc::new(std::sync::Mutex::new(WalCompactionReport::default())),
             wal_sequence: Arc::new(RwLock::new(0)),
             active_transactions: Arc::new(RwLock::new(HashMap::new())),
-        };
+            transaction_ttl: None,
+            timeout_tx: broadcast::channel(DEFAULT_BROADCAST_SIZE).0,
+            timed_out: AtomicU64::new(0),
+            recovery_hooks: RecoveryHooks::new(),
+        }
+    }
 
-        backend.migrate().await?;
-        backend.initialize_wal_sequence().await?;
-        Ok(backend)
+    /// Whether the backend was opened with [`open_read_only`](Self::open_read_only).
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Fail with [`StorageError::ReadOnly`] if writes are not allowed.
+    fn ensure_writable(&self, operation: &str) -> Result<(), StorageError> {
+        if self.read_only {
+            return Err(StorageError::ReadOnly(operation.to_string()));
+        }
+        Ok(())
     }
 
     /// Run database migrations to ensure schema is current.
@@ -137,19 +392,132 @@ impl SqliteBackend {
                 header_data BLOB NOT NULL,
                 timestamp TEXT NOT NULL,
                 intent TEXT NOT NULL,
-                kind TEXT NOT NULL
+                kind TEXT NOT NULL,
+                namespace TEXT NOT NULL DEFAULT 'default',
+                digest BLOB
             ) STRICT
             "#,
         )
         .execute(&self.pool)
         .await?;
 
+        // Events written before namespaces existed belong to the default one
+        let row = sqlx::query::<Sqlite>(
+            "SELECT COUNT(*) as count FROM pragma_table_info('event_headers') WHERE name = 'namespace'"
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if row.get::<i64, _>("count") == 0 {
+            sqlx::query::<Sqlite>(&format!(
+                "ALTER TABLE event_headers ADD COLUMN namespace TEXT NOT NULL DEFAULT '{}'",
+                DEFAULT_NAMESPACE
+            ))
+            .execute(&self.pool)
+            .await?;
+        }
+
+        // Payload digests are kept next to the header so payload cleanup can
+        // find references with an index; older rows are backfilled once
+        let row = sqlx::query::<Sqlite>(
+            "SELECT COUNT(*) as count FROM pragma_table_info('event_headers') WHERE name = 'digest'"
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if row.get::<i64, _>("count") == 0 {
+            sqlx::query::<Sqlite>("ALTER TABLE event_headers ADD COLUMN digest BLOB")
+                .execute(&self.pool)
+                .await?;
+        }
+        let mut tx = self.pool.begin().await?;
+        let rows = sqlx::query::<Sqlite>("SELECT id, header_data FROM event_headers WHERE digest IS NULL")
+            .fetch_all(&mut *tx)
+            .await?;
+        for row in &rows {
+            let header = Self::decode_header_row(row)?;
+            sqlx::query::<Sqlite>("UPDATE event_headers SET digest = ? WHERE id = ?")
+                .bind(&header.digest[..])
+                .bind(row.get::<Vec<u8>, _>("id"))
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+
         // Create payloads table with deduplication by digest
         sqlx::query::<Sqlite>(
             r#"
             CREATE TABLE IF NOT EXISTS event_payloads (
                 digest BLOB PRIMARY KEY,
-                payload_data BLOB NOT NULL
+                payload_data BLOB NOT NULL,
+                compression INTEGER NOT NULL DEFAULT 0
+            ) STRICT
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Databases created before payload compression lack the codec column;
+        // existing rows are uncompressed, which the default of 0 records.
+        let row = sqlx::query::<Sqlite>(
+            "SELECT COUNT(*) as count FROM pragma_table_info('event_payloads') WHERE name = 'compression'"
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if row.get::<i64, _>("count") == 0 {
+            sqlx::query::<Sqlite>(
+                "ALTER TABLE event_payloads ADD COLUMN compression INTEGER NOT NULL DEFAULT 0"
+            )
+            .execute(&self.pool)
+            .await?;
+        }
+
+        // Registry of secondary indexes created through `IndexedStorage`
+        sqlx::query::<Sqlite>(
+            r#"
+            CREATE TABLE IF NOT EXISTS event_indexes (
+                name TEXT PRIMARY KEY,
+                definition BLOB NOT NULL
+            ) STRICT
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Content-addressed blobs, stored in fixed-size chunks keyed by
+        // digest (or by upload id while an upload is in progress)
+        sqlx::query::<Sqlite>(
+            r#"
+            CREATE TABLE IF NOT EXISTS blobs (
+                digest BLOB PRIMARY KEY,
+                size INTEGER NOT NULL,
+                chunks INTEGER NOT NULL
+            ) STRICT
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query::<Sqlite>(
+            r#"
+            CREATE TABLE IF NOT EXISTS blob_chunks (
+                blob_key TEXT NOT NULL,
+                seq INTEGER NOT NULL,
+                data BLOB NOT NULL,
+                PRIMARY KEY (blob_key, seq)
+            ) STRICT
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Idempotency key of the latest event committed with each key
+        sqlx::query::<Sqlite>(
+            r#"
+            CREATE TABLE IF NOT EXISTS idempotency_keys (
+                namespace TEXT NOT NULL,
+                idempotency_key TEXT NOT NULL,
+                event_id BLOB NOT NULL,
+                timestamp TEXT NOT NULL,
+                PRIMARY KEY (namespace, idempotency_key)
             ) STRICT
             "#,
         )
@@ -186,6 +554,14 @@ impl SqliteBackend {
             .execute(&self.pool)
             .await?;
 
+        sqlx::query::<Sqlite>("CREATE INDEX IF NOT EXISTS idx_headers_namespace ON event_headers(namespace, timestamp)")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query::<Sqlite>("CREATE INDEX IF NOT EXISTS idx_headers_digest ON event_headers(digest)")
+            .execute(&self.pool)
+            .await?;
+
         // WAL-specific indexes
         sqlx::query::<Sqlite>("CREATE INDEX IF NOT EXISTS idx_wal_transaction ON wal_entries(transaction_id)")
             .execute(&self.pool)
@@ -199,27 +575,152 @@ impl SqliteBackend {
             .execute(&self.pool)
             .await?;
 
+        // Entries recovery could not decode, kept until they can be restored
+        sqlx::query::<Sqlite>(
+            r#"
+            CREATE TABLE IF NOT EXISTS wal_quarantine (
+                id BLOB PRIMARY KEY,
+                transaction_id BLOB NOT NULL,
+                sequence_number INTEGER NOT NULL,
+                timestamp TEXT NOT NULL,
+                version INTEGER NOT NULL,
+                operation_data BLOB NOT NULL,
+                state INTEGER NOT NULL,
+                reason TEXT NOT NULL,
+                quarantined_at TEXT NOT NULL
+            ) STRICT
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Sequence numbers are allocated here rather than in process memory so
+        // that every process writing to the file draws from the same counter.
+        // Databases created before the counter existed are seeded from the WAL.
+        sqlx::query::<Sqlite>(
+            r#"
+            CREATE TABLE IF NOT EXISTS wal_sequence (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                value INTEGER NOT NULL
+            ) STRICT
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query::<Sqlite>(
+            r#"
+            INSERT OR IGNORE INTO wal_sequence (id, value)
+            SELECT 1, COALESCE(MAX(sequence_number), 0) FROM wal_entries
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
         Ok(())
     }
 
-    /// Initialize the WAL sequence number from the database.
-    async fn initialize_wal_sequence(&self) -> Result<()> {
-        let row = sqlx::query::<Sqlite>(
-            "SELECT COALESCE(MAX(sequence_number), 0) as max_seq FROM wal_entries"
+    /// Load registered secondary indexes.
+    async fn load_indexes(&self) -> Result<()> {
+        // Read-only connections skip migrations, so older databases may lack the registry
+        let registry = sqlx::query::<Sqlite>(
+            "SELECT COUNT(*) as count FROM sqlite_master WHERE type = 'table' AND name = 'event_indexes'"
         )
         .fetch_one(&self.pool)
         .await?;
+        if registry.get::<i64, _>("count") == 0 {
+            return Ok(());
+        }
+
+        let rows = sqlx::query::<Sqlite>("SELECT definition FROM event_indexes ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?;
+        let mut indexes = self.indexes.write().await;
+        indexes.clear();
+        for row in &rows {
+            indexes.push(rmp_serde::from_slice(&row.get::<Vec<u8>, _>("definition"))?);
+        }
+        Ok(())
+    }
+
+    /// Column in `event_headers` holding the keys of `index`.
+    ///
+    /// Index names only contain `[a-z0-9_]`, so they are safe to embed in SQL.
+    fn index_column(index: &IndexDefinition) -> String {
+        match &index.target {
+            IndexTarget::Header(field) => field.clone(),
+            IndexTarget::JsonPath(_) => format!("ix_{}", index.name),
+        }
+    }
 
-        let max_seq: i64 = row.get("max_seq");
-        *self.wal_sequence.write().await = max_seq as SequenceNumber;
+    /// Initialize the WAL sequence number from the database.
+    async fn initialize_wal_sequence(&self) -> Result<()> {
+        let sequence = self.stored_sequence().await?;
+        *self.wal_sequence.write().await = sequence;
         Ok(())
     }
 
-    /// Get the next sequence number for WAL entries.
-    async fn next_sequence(&self) -> SequenceNumber {
-        let mut seq = self.wal_sequence.write().await;
-        *seq += 1;
-        *seq
+    /// Last sequence number allocated by any process using the database.
+    ///
+    /// Read-only handles may point at a database written before the
+    /// `wal_sequence` table existed; they fall back to the highest logged
+    /// sequence number.
+    async fn stored_sequence(&self) -> Result<SequenceNumber> {
+        let counter = sqlx::query::<Sqlite>("SELECT value FROM wal_sequence WHERE id = 1")
+            .fetch_optional(&self.pool)
+            .await;
+        let row = match counter {
+            Ok(Some(row)) => row,
+            Ok(None) | Err(_) if self.read_only => {
+                sqlx::query::<Sqlite>(
+                    "SELECT COALESCE(MAX(sequence_number), 0) as value FROM wal_entries"
+                )
+                .fetch_one(&self.pool)
+                .await?
+            }
+            Ok(None) => return Err(anyhow::anyhow!("WAL sequence counter is missing")),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(row.get::<i64, _>("value") as SequenceNumber)
+    }
+
+    /// Allocate `count` consecutive sequence numbers for WAL entries,
+    /// returning the first.
+    ///
+    /// The counter is incremented atomically in the database, so handles in
+    /// different processes never hand out the same number. Allocating inside
+    /// the transaction that inserts the entries keeps a whole batch to a
+    /// single counter update. A writer that cannot get the database lock
+    /// within the busy timeout fails with [`StorageError::SequenceContention`].
+    async fn allocate_sequences(
+        tx: &mut sqlx::Transaction<'_, Sqlite>,
+        count: usize,
+    ) -> Result<SequenceNumber> {
+        let row = sqlx::query::<Sqlite>(
+            "UPDATE wal_sequence SET value = value + ? WHERE id = 1 RETURNING value"
+        )
+        .bind(count as i64)
+        .fetch_one(&mut **tx)
+        .await
+        .map_err(Self::map_lock_error)?;
+
+        let last = row.get::<i64, _>("value") as SequenceNumber;
+        Ok(last + 1 - count as SequenceNumber)
+    }
+
+    /// Report `SQLITE_BUSY`/`SQLITE_LOCKED` as [`StorageError::SequenceContention`].
+    fn map_lock_error(error: sqlx::Error) -> anyhow::Error {
+        let locked = error
+            .as_database_error()
+            .and_then(|e| e.code())
+            .and_then(|code| code.parse::<i32>().ok())
+            // Extended result codes keep the primary code in the low byte
+            .is_some_and(|code| matches!(code & 0xff, 5 | 6));
+        if locked {
+            StorageError::SequenceContention(error.to_string()).into()
+        } else {
+            error.into()
+        }
     }
 
     /// Convert WalEntryState to integer for storage.
@@ -243,6 +744,71 @@ impl SqliteBackend {
         }
     }
 
+    /// Number of WAL transactions begun but not yet finished on this handle.
+    async fn open_transaction_count(&self) -> u64 {
+        self.active_transactions
+            .read()
+            .await
+            .values()
+            .filter(|tx_state| tx_state.state == WalTransactionStateType::Active)
+            .count() as u64
+    }
+
+    /// Decode a [`WalEntry`] from a row of the `wal_entries` table.
+    fn decode_wal_row(row: &SqliteRow) -> Result<WalEntry> {
+        let timestamp: String = row.get("timestamp");
+        let operation_bytes: Vec<u8> = row.get("operation_data");
+        Ok(WalEntry {
+            id: row.get("id"),
+            transaction_id: row.get("transaction_id"),
+            sequence: row.get::<i64, _>("sequence_number") as SequenceNumber,
+            timestamp: DateTime::parse_from_rfc3339(&timestamp)
+                .map_err(|e| anyhow::anyhow!("Invalid timestamp: {}", e))?
+                .with_timezone(&Utc),
+            operation: decode_wal_operation(&operation_bytes)?,
+            state: Self::int_to_state(row.get("state")),
+        })
+    }
+
+    /// Move a `wal_entries` row whose operation does not decode to `wal_quarantine`.
+    async fn quarantine_wal_entry(&self, row: &SqliteRow, reason: &str) -> Result<()> {
+        let entry_id: Uuid = row.get("id");
+        let operation_bytes: Vec<u8> = row.get("operation_data");
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query::<Sqlite>(
+            r#"
+            INSERT OR REPLACE INTO wal_quarantine
+            (id, transaction_id, sequence_number, timestamp, version, operation_data, state,
+             reason, quarantined_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(entry_id)
+        .bind(row.get::<Uuid, _>("transaction_id"))
+        .bind(row.get::<i64, _>("sequence_number"))
+        .bind(row.get::<String, _>("timestamp"))
+        .bind(wal_format_version(&operation_bytes).0 as i64)
+        .bind(&operation_bytes)
+        .bind(row.get::<i32, _>("state"))
+        .bind(reason)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query::<Sqlite>("DELETE FROM wal_entries WHERE id = ?")
+            .bind(entry_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Decode an [`EventHeader`] from a row containing a `header_data` column.
+    fn decode_header_row(row: &SqliteRow) -> Result<EventHeader> {
+        let header_bytes: Vec<u8> = row.get("header_data");
+        Ok(rmp_serde::from_slice(&header_bytes)?)
+    }
+
     /// Subscribe to the live event stream.
     ///
     /// Returns a receiver that will receive copies of all event headers
@@ -252,52 +818,316 @@ impl SqliteBackend {
         self.broadcast_tx.subscribe()
     }
 
-    /// Get the total number of events stored in the database.
-    pub async fn event_count(&self) -> Result<i64> {
-        let row = sqlx::query::<Sqlite>("SELECT COUNT(*) as count FROM event_headers")
-            .fetch_one(&self.pool)
-            .await?;
-        Ok(row.get("count"))
+    /// Compress newly committed payloads according to `policy`.
+    ///
+    /// Payloads already stored keep their codec; use
+    /// [`compress_existing_payloads`](Self::compress_existing_payloads) to
+    /// migrate them.
+    pub fn with_compression(mut self, policy: CompressionPolicy) -> Self {
+        self.compression = policy;
+        self
     }
 
-    /// Get the total number of unique payloads stored.
-    ///
-    /// This may be less than the event count due to payload deduplication
-    /// when multiple events share the same content hash.
-    pub async fn payload_count(&self) -> Result<i64> {
-        let row = sqlx::query::<Sqlite>("SELECT COUNT(*) as count FROM event_payloads")
-            .fetch_one(&self.pool)
-            .await?;
-        Ok(row.get("count"))
+    /// Drop events whose idempotency key was committed less than `window` earlier.
+    pub fn with_dedup_window(mut self, window: std::time::Duration) -> Self {
+        self.dedup_window = window;
+        self
     }
 
-    /// Get the total number of WAL entries.
-    pub async fn wal_entry_count(&self) -> Result<i64> {
-        let row = sqlx::query::<Sqlite>("SELECT COUNT(*) as count FROM wal_entries")
-            .fetch_one(&self.pool)
-            .await?;
-        Ok(row.get("count"))
+    /// Write WAL entries according to `durability`.
+    pub fn with_wal_durability(mut self, durability: WalDurability) -> Self {
+        self.durability = durability;
+        self
     }
 
-    /// Close the database connection pool.
-    pub async fn close(&self) {
-        self.pool.close().await;
+    /// Compact the WAL automatically according to `policy`.
+    pub fn with_wal_compaction(mut self, policy: WalCompactionPolicy) -> Self {
+        self.wal_compaction = policy;
+        self
     }
-}
 
-#[async_trait]
-impl StorageBackend for SqliteBackend {
-    async fn commit(&self, header: &EventHeader, payload: &[u8]) -> Result<()> {
+    /// Roll back WAL transactions that stay open longer than `ttl`.
+    ///
+    /// Stale transactions are aborted by
+    /// [`abort_stale_transactions`](TransactionTimeouts::abort_stale_transactions),
+    /// which [`spawn_transaction_reaper`](Self::spawn_transaction_reaper)
+    /// calls periodically.
+    pub fn with_transaction_ttl(mut self, ttl: std::time::Duration) -> Self {
+        self.transaction_ttl = Some(ttl);
+        self
+    }
+
+    /// Notify `observer` of replays, rollbacks and errors during
+    /// [`recover`](WriteAheadLog::recover), and let it veto replays.
+    pub fn with_recovery_observer(mut self, observer: Arc<dyn RecoveryObserver>) -> Self {
+        self.recovery_hooks.register(observer);
+        self
+    }
+
+    /// Subscribe to notifications of transactions rolled back by timeouts.
+    pub fn subscribe_transaction_timeouts(&self) -> broadcast::Receiver<TransactionTimedOut> {
+        self.timeout_tx.subscribe()
+    }
+
+    /// Abort stale transactions every `interval` on a background task.
+    ///
+    /// The task stops once the backend is dropped or the returned handle is
+    /// aborted.
+    pub fn spawn_transaction_reaper(self: &Arc<Self>, interval: std::time::Duration) -> tokio::task::JoinHandle<()> {
+        let backend: Weak<Self> = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            loop {
+                ticks.tick().await;
+                let Some(backend) = backend.upgrade() else {
+                    break;
+                };
+                // Failed sweeps are retried on the next tick
+                let _ = backend.abort_stale_transactions(Utc::now()).await;
+            }
+        })
+    }
+
+    /// The configured WAL durability.
+    pub fn wal_durability(&self) -> WalDurability {
+        self.durability
+    }
+
+    /// Write all buffered WAL entries to the database.
+    ///
+    /// Only needed with [`WalDurability::Batched`]; entries are otherwise
+    /// flushed by later writes or when their transaction finishes.
+    pub async fn flush_wal(&self) -> Result<()> {
+        let mut buffer = self.wal_buffer.lock().await;
+        self.flush_wal_buffer(&mut buffer, None).await
+    }
+
+    /// Buffer a WAL entry and flush if the durability policy asks for it.
+    ///
+    /// `finish` marks every entry of the transaction with the given state in
+    /// the same database transaction and forces a flush. Sequence numbers are
+    /// assigned when the entry is flushed; the entry's ID is returned.
+    async fn log_wal_entry(
+        &self,
+        transaction_id: TransactionId,
+        operation: &WalOperation,
+        state: WalEntryState,
+        finish: bool,
+    ) -> Result<Uuid> {
+        let operation_bytes = encode_wal_operation(operation)?;
+        let id = Uuid::new_v4();
+        let mut buffer = self.wal_buffer.lock().await;
+        buffer.rows.push(PendingWalRow {
+            id,
+            transaction_id,
+            timestamp: Utc::now(),
+            operation_bytes,
+            state: state.clone(),
+        });
+        buffer.oldest.get_or_insert_with(std::time::Instant::now);
+
+        if finish {
+            self.flush_wal_buffer(&mut buffer, Some((transaction_id, state))).await?;
+        } else if self.durability.should_flush(&buffer) {
+            self.flush_wal_buffer(&mut buffer, None).await?;
+        }
+        Ok(id)
+    }
+
+    /// Insert the buffered entries in one database transaction.
+    ///
+    /// Entries are numbered in the order they were buffered.
+    async fn flush_wal_buffer(
+        &self,
+        buffer: &mut WalBuffer,
+        finish: Option<(TransactionId, WalEntryState)>,
+    ) -> Result<()> {
+        if buffer.rows.is_empty() && finish.is_none() {
+            return Ok(());
+        }
+
         let mut tx = self.pool.begin().await?;
+        let mut sequence = 0;
+        if !buffer.rows.is_empty() {
+            sequence = Self::allocate_sequences(&mut tx, buffer.rows.len()).await?;
+        }
+        for row in &buffer.rows {
+            sqlx::query::<Sqlite>(
+                r#"
+                INSERT INTO wal_entries 
+                (id, transaction_id, sequence_number, timestamp, operation_data, state) 
+                VALUES (?, ?, ?, ?, ?, ?)
+                "#
+            )
+            .bind(row.id)
+            .bind(row.transaction_id)
+            .bind(sequence as i64)
+            .bind(row.timestamp.to_rfc3339())
+            .bind(&row.operation_bytes)
+            .bind(Self::state_to_int(row.state.clone()))
+            .execute(&mut *tx)
+            .await?;
+            sequence += 1;
+        }
+        if let Some((transaction_id, state)) = finish {
+            // Entries discarded by a savepoint rollback stay rolled back
+            sqlx::query::<Sqlite>(
+                "UPDATE wal_entries SET state = ? WHERE transaction_id = ? AND state != ?"
+            )
+            .bind(Self::state_to_int(state))
+            .bind(transaction_id)
+            .bind(Self::state_to_int(WalEntryState::RolledBack))
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
 
-        // Store payload (deduplicated by digest)
+        if !buffer.rows.is_empty() {
+            let mut cached = self.wal_sequence.write().await;
+            *cached = (*cached).max(sequence - 1);
+        }
+        buffer.rows.clear();
+        buffer.oldest = None;
+        Ok(())
+    }
+
+    /// Mark the entries of a transaction logged after the entry `after` as
+    /// rolled back.
+    async fn discard_wal_entries(&self, transaction_id: TransactionId, after: Uuid) -> Result<()> {
+        let mut buffer = self.wal_buffer.lock().await;
+        self.flush_wal_buffer(&mut buffer, None).await?;
+        sqlx::query::<Sqlite>(
+            r#"
+            UPDATE wal_entries SET state = ? WHERE transaction_id = ?
+            AND sequence_number > (SELECT sequence_number FROM wal_entries WHERE id = ?)
+            "#
+        )
+        .bind(Self::state_to_int(WalEntryState::RolledBack))
+        .bind(transaction_id)
+        .bind(after)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// State of `transaction_id`, if it is still active.
+    fn active_state(
+        transactions: &mut HashMap<TransactionId, WalTransactionState>,
+        transaction_id: TransactionId,
+    ) -> Result<&mut WalTransactionState> {
+        match transactions.get_mut(&transaction_id) {
+            Some(tx_state) if tx_state.state == WalTransactionStateType::Active => Ok(tx_state),
+            Some(tx_state) => Err(anyhow::anyhow!(
+                "Transaction {} is not active (state: {:?})",
+                transaction_id,
+                tx_state.state
+            )),
+            None => Err(anyhow::anyhow!("Transaction {} not found", transaction_id)),
+        }
+    }
+
+    /// Compress payloads that were stored uncompressed, `batch_size` rows at a time.
+    ///
+    /// This is the migration path for databases written before compression
+    /// was enabled. Each batch is updated in its own transaction, so the
+    /// migration can be interrupted and resumed. Returns the number of
+    /// payloads that were rewritten.
+    pub async fn compress_existing_payloads(&self, batch_size: u32) -> Result<usize> {
+        self.ensure_writable("compress_existing_payloads")?;
+        if self.compression.codec == Compression::None {
+            return Ok(0);
+        }
+
+        let mut rewritten = 0;
+        let mut last_rowid = 0i64;
+        loop {
+            let rows = sqlx::query::<Sqlite>(
+                r#"
+                SELECT rowid, digest, payload_data FROM event_payloads
+                WHERE compression = 0 AND rowid > ?
+                ORDER BY rowid ASC LIMIT ?
+                "#
+            )
+            .bind(last_rowid)
+            .bind(batch_size.max(1))
+            .fetch_all(&self.pool)
+            .await?;
+
+            let Some(last) = rows.last() else {
+                return Ok(rewritten);
+            };
+            last_rowid = last.get("rowid");
+
+            let mut tx = self.pool.begin().await?;
+            for row in &rows {
+                let payload: Vec<u8> = row.get("payload_data");
+                let (codec, stored) = self.compression.apply(&payload)?;
+                if codec == Compression::None {
+                    continue;
+                }
+
+                sqlx::query::<Sqlite>(
+                    "UPDATE event_payloads SET payload_data = ?, compression = ? WHERE digest = ? AND compression = 0"
+                )
+                .bind(stored)
+                .bind(codec.id() as i64)
+                .bind(row.get::<Vec<u8>, _>("digest"))
+                .execute(&mut *tx)
+                .await?;
+                rewritten += 1;
+            }
+            tx.commit().await?;
+        }
+    }
+
+    /// Adapt a broadcast receiver into a stream, surfacing lag as an error.
+    fn live_stream(
+        rx: broadcast::Receiver<EventHeader>,
+    ) -> impl Stream<Item = Result<EventHeader>> + Send + 'static {
+        futures::stream::unfold(rx, |mut rx| async move {
+            match rx.recv().await {
+                Ok(header) => Some((Ok(header), rx)),
+                Err(broadcast::error::RecvError::Lagged(missed)) => Some((
+                    Err(anyhow::anyhow!("Live subscriber lagged behind by {} events", missed)),
+                    rx,
+                )),
+                Err(broadcast::error::RecvError::Closed) => None,
+            }
+        })
+    }
+
+    /// Decode a row of per-namespace aggregates.
+    fn decode_namespace_row(row: &SqliteRow) -> Result<NamespaceStats> {
+        let parse = |column: &str| -> Result<Option<DateTime<Utc>>> {
+            row.get::<Option<String>, _>(column)
+                .map(|ts| Ok(DateTime::parse_from_rfc3339(&ts)?.with_timezone(&Utc)))
+                .transpose()
+        };
+        Ok(NamespaceStats {
+            namespace: row.get("namespace"),
+            event_count: row.get::<i64, _>("count") as u64,
+            oldest_event: parse("oldest")?,
+            newest_event: parse("newest")?,
+        })
+    }
+
+    /// Store an event's payload, header and index columns on `conn`.
+    async fn write_event(
+        &self,
+        conn: &mut sqlx::SqliteConnection,
+        header: &EventHeader,
+        payload: &[u8],
+    ) -> Result<()> {
+        // Store payload (deduplicated by digest), compressed per the policy
         // Use INSERT OR IGNORE to avoid errors on duplicate digests
+        let (codec, stored) = self.compression.apply(payload)?;
         sqlx::query::<Sqlite>(
-            "INSERT OR IGNORE INTO event_payloads (digest, payload_data) VALUES (?, ?)"
+            "INSERT OR IGNORE INTO event_payloads (digest, payload_data, compression) VALUES (?, ?, ?)"
         )
         .bind(&header.digest[..])
-        .bind(payload)
-        .execute(&mut *tx)
+        .bind(stored)
+        .bind(codec.id() as i64)
+        .execute(&mut *conn)
         .await?;
 
         // Store header (may overwrite previous version with same ID)
@@ -305,8 +1135,8 @@ impl StorageBackend for SqliteBackend {
         sqlx::query::<Sqlite>(
             r#"
             INSERT OR REPLACE INTO event_headers 
-            (id, header_data, timestamp, intent, kind) 
-            VALUES (?, ?, ?, ?, ?)
+            (id, header_data, timestamp, intent, kind, namespace, digest) 
+            VALUES (?, ?, ?, ?, ?, ?, ?)
             "#
         )
         .bind(header.id)
@@ -314,90 +1144,892 @@ impl StorageBackend for SqliteBackend {
         .bind(header.timestamp.to_rfc3339())
         .bind(header.intent.to_string())
         .bind(&header.kind)
+        .bind(header.namespace_or_default())
+        .bind(&header.digest[..])
+        .execute(&mut *conn)
+        .await?;
+
+        // Fill the columns backing payload indexes
+        for index in self.indexes.read().await.iter() {
+            if let IndexTarget::JsonPath(_) = index.target {
+                let key = index.key_for(header, payload)?;
+                sqlx::query::<Sqlite>(&format!(
+                    "UPDATE event_headers SET {} = ? WHERE id = ?",
+                    Self::index_column(index)
+                ))
+                .bind(key)
+                .bind(header.id)
+                .execute(&mut *conn)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check `precondition` against the events visible on `conn`.
+    async fn check_precondition(
+        conn: &mut sqlx::SqliteConnection,
+        precondition: &Precondition,
+    ) -> Result<()> {
+        let mut stored = HashSet::new();
+        for id in precondition.events() {
+            let row = sqlx::query::<Sqlite>("SELECT 1 FROM event_headers WHERE id = ?")
+                .bind(id)
+                .fetch_optional(&mut *conn)
+                .await?;
+            if row.is_some() {
+                stored.insert(id);
+            }
+        }
+
+        let mut heads = HashMap::new();
+        for intent in precondition.intents() {
+            if let Some(head) = Self::fetch_intent_head(conn, &intent).await? {
+                heads.insert(intent, head.id);
+            }
+        }
+
+        precondition
+            .evaluate_snapshot(&stored, &heads)
+            .map_err(StorageError::Conflict)?;
+        Ok(())
+    }
+
+    /// Latest event of `intent`.
+    async fn fetch_intent_head(
+        conn: &mut sqlx::SqliteConnection,
+        intent: &IntentId,
+    ) -> Result<Option<EventHeader>> {
+        // Only the events sharing the newest timestamp can be the head
+        let rows = sqlx::query::<Sqlite>(
+            r#"
+            SELECT header_data FROM event_headers
+            WHERE intent = ?1
+            AND timestamp = (SELECT MAX(timestamp) FROM event_headers WHERE intent = ?1)
+            "#
+        )
+        .bind(intent.to_string())
+        .fetch_all(&mut *conn)
+        .await?;
+        let headers = rows.iter().map(Self::decode_header_row).collect::<Result<Vec<_>>>()?;
+        let head = intent_head(&headers);
+        Ok(headers.into_iter().find(|header| Some(header.id) == head))
+    }
+
+    /// Forget the idempotency keys pointing at `removed` headers, so retries
+    /// are not reported as duplicates of events that no longer exist.
+    async fn delete_idempotency_keys(conn: &mut sqlx::SqliteConnection, removed: &[EventHeader]) -> Result<()> {
+        for header in removed {
+            sqlx::query::<Sqlite>("DELETE FROM idempotency_keys WHERE event_id = ?")
+                .bind(header.id)
+                .execute(&mut *conn)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Delete the payloads of `removed` headers that no remaining header references.
+    ///
+    /// Returns the number of payloads deleted.
+    async fn delete_unreferenced_payloads(
+        conn: &mut sqlx::SqliteConnection,
+        removed: &[EventHeader],
+    ) -> Result<usize> {
+        // Several removed headers may share a digest; check each one once
+        let digests: std::collections::HashSet<_> = removed.iter().map(|header| header.digest).collect();
+
+        let mut deleted = 0;
+        for digest in digests {
+            deleted += sqlx::query::<Sqlite>(
+                r#"
+                DELETE FROM event_payloads WHERE digest = ?
+                AND NOT EXISTS (SELECT 1 FROM event_headers WHERE event_headers.digest = event_payloads.digest)
+                "#
+            )
+            .bind(&digest[..])
+            .execute(&mut *conn)
+            .await?
+            .rows_affected() as usize;
+        }
+        Ok(deleted)
+    }
+
+    /// Blob store for large artifacts, kept in the same database.
+    pub fn blob_store(&self) -> SqliteBlobStore {
+        SqliteBlobStore::new(self.pool.clone(), self.read_only)
+    }
+
+    /// Get the total number of events stored in the database.
+    pub async fn event_count(&self) -> Result<i64> {
+        let row = sqlx::query::<Sqlite>("SELECT COUNT(*) as count FROM event_headers")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(row.get("count"))
+    }
+
+    /// Get the total number of unique payloads stored.
+    ///
+    /// This may be less than the event count due to payload deduplication
+    /// when multiple events share the same content hash.
+    pub async fn payload_count(&self) -> Result<i64> {
+        let row = sqlx::query::<Sqlite>("SELECT COUNT(*) as count FROM event_payloads")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(row.get("count"))
+    }
+
+    /// Get the total number of WAL entries.
+    pub async fn wal_entry_count(&self) -> Result<i64> {
+        self.flush_wal().await?;
+        let row = sqlx::query::<Sqlite>("SELECT COUNT(*) as count FROM wal_entries")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(row.get("count"))
+    }
+
+    /// Close the database connection pool.
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+}
+
+#[async_trait]
+impl StorageBackend for SqliteBackend {
+    async fn commit(&self, header: &EventHeader, payload: &[u8]) -> Result<()> {
+        self.commit_deduplicated(header, payload).await.map(|_| ())
+    }
+
+    async fn header(&self, id: &EventId) -> Result<Option<EventHeader>> {
+        let row = sqlx::query::<Sqlite>(
+            "SELECT header_data FROM event_headers WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => Ok(Some(Self::decode_header_row(&row)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn payload_bytes(&self, digest: &CausalDigest) -> Result<Option<Vec<u8>>> {
+        let row = sqlx::query::<Sqlite>(
+            "SELECT payload_data, compression FROM event_payloads WHERE digest = ?"
+        )
+        .bind(&digest[..])
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => {
+                let codec = Compression::from_id(row.get::<i64, _>("compression") as u8)?;
+                let stored: Vec<u8> = row.get("payload_data");
+                Ok(Some(codec.decompress(&stored)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn payloads_bulk(&self, digests: &[CausalDigest]) -> Result<HashMap<CausalDigest, Vec<u8>>> {
+        let mut unique = digests.to_vec();
+        unique.sort_unstable();
+        unique.dedup();
+
+        let mut payloads = HashMap::with_capacity(unique.len());
+        // One query per chunk keeps us under SQLite's bound parameter limit
+        for chunk in unique.chunks(BULK_QUERY_CHUNK) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let sql = format!(
+                "SELECT digest, payload_data, compression FROM event_payloads WHERE digest IN ({})",
+                placeholders
+            );
+            let mut query = sqlx::query::<Sqlite>(&sql);
+            for digest in chunk {
+                query = query.bind(&digest[..]);
+            }
+
+            for row in query.fetch_all(&self.pool).await? {
+                let digest: Vec<u8> = row.get("digest");
+                let digest: CausalDigest = digest
+                    .try_into()
+                    .map_err(|_| anyhow::anyhow!("stored payload digest is not 32 bytes"))?;
+                let codec = Compression::from_id(row.get::<i64, _>("compression") as u8)?;
+                let stored: Vec<u8> = row.get("payload_data");
+                payloads.insert(digest, codec.decompress(&stored)?);
+            }
+        }
+        Ok(payloads)
+    }
+}
+
+#[async_trait]
+impl QueryableStorage for SqliteBackend {
+    async fn events_by_kind(&self, kind: &str) -> Result<EventHeaderStream<'_>> {
+        let stream = sqlx::query::<Sqlite>(
+            "SELECT header_data FROM event_headers WHERE kind = ? ORDER BY timestamp ASC, rowid ASC"
+        )
+        .bind(kind.to_owned())
+        .fetch(&self.pool)
+        .map(|row| Self::decode_header_row(&row?));
+
+        Ok(Box::pin(stream))
+    }
+
+    async fn events_in_range(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<EventHeaderStream<'_>> {
+        // Timestamps are stored as RFC 3339 strings in UTC, which sort
+        // lexicographically in chronological order.
+        let stream = sqlx::query::<Sqlite>(
+            r#"
+            SELECT header_data FROM event_headers
+            WHERE timestamp >= ? AND timestamp < ?
+            ORDER BY timestamp ASC, rowid ASC
+            "#
+        )
+        .bind(from.to_rfc3339())
+        .bind(to.to_rfc3339())
+        .fetch(&self.pool)
+        .map(|row| Self::decode_header_row(&row?));
+
+        Ok(Box::pin(stream))
+    }
+
+    async fn events_by_intent(&self, intent: &IntentId) -> Result<EventHeaderStream<'_>> {
+        let stream = sqlx::query::<Sqlite>(
+            "SELECT header_data FROM event_headers WHERE intent = ? ORDER BY timestamp ASC, rowid ASC"
+        )
+        .bind(intent.to_string())
+        .fetch(&self.pool)
+        .map(|row| Self::decode_header_row(&row?));
+
+        Ok(Box::pin(stream))
+    }
+}
+
+#[async_trait]
+impl ReplayableStorage for SqliteBackend {
+    async fn subscribe_from(&self, from: ReplayFrom) -> Result<EventHeaderStream<'_>> {
+        // Subscribe before querying so nothing committed in between is missed
+        let live = Self::live_stream(self.subscribe());
+
+        let history: EventHeaderStream<'_> = match from {
+            ReplayFrom::Timestamp(timestamp) => Box::pin(
+                sqlx::query::<Sqlite>(
+                    "SELECT header_data FROM event_headers WHERE timestamp >= ? ORDER BY timestamp ASC, rowid ASC"
+                )
+                .bind(timestamp.to_rfc3339())
+                .fetch(&self.pool)
+                .map(|row| Self::decode_header_row(&row?)),
+            ),
+            ReplayFrom::Sequence(skip) => Box::pin(
+                sqlx::query::<Sqlite>(
+                    "SELECT header_data FROM event_headers ORDER BY rowid ASC LIMIT -1 OFFSET ?"
+                )
+                .bind(i64::try_from(skip)?)
+                .fetch(&self.pool)
+                .map(|row| Self::decode_header_row(&row?)),
+            ),
+        };
+
+        Ok(replay_then_live(history, live))
+    }
+}
+
+#[async_trait]
+impl NamespacedStorage for SqliteBackend {
+    async fn header_in(&self, namespace: &str, id: &EventId) -> Result<Option<EventHeader>> {
+        let row = sqlx::query::<Sqlite>(
+            "SELECT header_data FROM event_headers WHERE id = ? AND namespace = ?"
+        )
+        .bind(id)
+        .bind(namespace)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(|row| Self::decode_header_row(&row)).transpose()
+    }
+
+    async fn namespaces(&self) -> Result<Vec<NamespaceStats>> {
+        let rows = sqlx::query::<Sqlite>(
+            r#"
+            SELECT namespace, COUNT(*) as count, MIN(timestamp) as oldest, MAX(timestamp) as newest
+            FROM event_headers GROUP BY namespace ORDER BY namespace
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(Self::decode_namespace_row).collect()
+    }
+
+    async fn namespace_stats(&self, namespace: &str) -> Result<NamespaceStats> {
+        let row = sqlx::query::<Sqlite>(
+            r#"
+            SELECT namespace, COUNT(*) as count, MIN(timestamp) as oldest, MAX(timestamp) as newest
+            FROM event_headers WHERE namespace = ? GROUP BY namespace
+            "#
+        )
+        .bind(namespace)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => Self::decode_namespace_row(&row),
+            None => Ok(NamespaceStats::empty(namespace)),
+        }
+    }
+
+    async fn prune_namespace(
+        &self,
+        namespace: &str,
+        before: Option<DateTime<Utc>>,
+    ) -> Result<usize> {
+        self.ensure_writable("prune_namespace")?;
+        let rows = match before {
+            Some(before) => {
+                sqlx::query::<Sqlite>("SELECT id FROM event_headers WHERE namespace = ? AND timestamp < ?")
+                    .bind(namespace)
+                    .bind(before.to_rfc3339())
+                    .fetch_all(&self.pool)
+                    .await?
+            }
+            None => {
+                sqlx::query::<Sqlite>("SELECT id FROM event_headers WHERE namespace = ?")
+                    .bind(namespace)
+                    .fetch_all(&self.pool)
+                    .await?
+            }
+        };
+
+        let ids: Vec<EventId> = rows.iter().map(|row| row.get("id")).collect();
+        self.remove_events(&ids).await
+    }
+
+    async fn subscribe_namespace(&self, namespace: &str) -> Result<EventHeaderStream<'_>> {
+        Ok(filter_namespace(Box::pin(Self::live_stream(self.subscribe())), namespace))
+    }
+}
+
+/// Conditional commits run in an immediate transaction, which takes the
+/// database write lock before the precondition is read.
+#[async_trait]
+impl DeduplicatingStorage for SqliteBackend {
+    async fn commit_deduplicated(&self, header: &EventHeader, payload: &[u8]) -> Result<CommitOutcome> {
+        self.ensure_writable("commit")?;
+        let Some(key) = &header.idempotency_key else {
+            let mut tx = self.pool.begin().await?;
+            self.write_event(&mut tx, header, payload).await?;
+            tx.commit().await?;
+
+            // Broadcast live update (ignore errors if no subscribers)
+            let _ = self.broadcast_tx.send(header.clone());
+            return Ok(CommitOutcome::Committed(header.id));
+        };
+
+        // Take the write lock up front so concurrent retries serialize
+        let mut conn = self.pool.acquire().await?;
+        sqlx::query::<Sqlite>("BEGIN IMMEDIATE").execute(&mut *conn).await?;
+
+        let result = async {
+            let row = sqlx::query::<Sqlite>(
+                "SELECT event_id, timestamp FROM idempotency_keys WHERE namespace = ? AND idempotency_key = ?"
+            )
+            .bind(header.namespace_or_default())
+            .bind(key)
+            .fetch_optional(&mut *conn)
+            .await?;
+            if let Some(row) = row {
+                let timestamp = DateTime::parse_from_rfc3339(&row.get::<String, _>("timestamp"))?
+                    .with_timezone(&Utc);
+                if toka_store_core::dedup::within_window(timestamp, header.timestamp, self.dedup_window) {
+                    return Ok(CommitOutcome::Duplicate(row.get::<Uuid, _>("event_id")));
+                }
+            }
+
+            self.write_event(&mut conn, header, payload).await?;
+            sqlx::query::<Sqlite>(
+                r#"
+                INSERT OR REPLACE INTO idempotency_keys (namespace, idempotency_key, event_id, timestamp)
+                VALUES (?, ?, ?, ?)
+                "#
+            )
+            .bind(header.namespace_or_default())
+            .bind(key)
+            .bind(header.id)
+            .bind(header.timestamp.to_rfc3339())
+            .execute(&mut *conn)
+            .await?;
+            Ok::<_, anyhow::Error>(CommitOutcome::Committed(header.id))
+        }
+        .await;
+
+        match result {
+            Ok(outcome) => {
+                sqlx::query::<Sqlite>("COMMIT").execute(&mut *conn).await?;
+                if !outcome.is_duplicate() {
+                    let _ = self.broadcast_tx.send(header.clone());
+                }
+                Ok(outcome)
+            }
+            Err(e) => {
+                sqlx::query::<Sqlite>("ROLLBACK").execute(&mut *conn).await?;
+                Err(e)
+            }
+        }
+    }
+
+    fn dedup_window(&self) -> std::time::Duration {
+        self.dedup_window
+    }
+}
+
+#[async_trait]
+impl ConditionalStorage for SqliteBackend {
+    async fn commit_if(
+        &self,
+        header: &EventHeader,
+        payload: &[u8],
+        precondition: &Precondition,
+    ) -> Result<()> {
+        self.ensure_writable("commit_if")?;
+        let mut conn = self.pool.acquire().await?;
+        sqlx::query::<Sqlite>("BEGIN IMMEDIATE").execute(&mut *conn).await?;
+
+        let result = async {
+            Self::check_precondition(&mut conn, precondition).await?;
+            self.write_event(&mut conn, header, payload).await
+        }
+        .await;
+
+        match result {
+            Ok(()) => {
+                sqlx::query::<Sqlite>("COMMIT").execute(&mut *conn).await?;
+                let _ = self.broadcast_tx.send(header.clone());
+                Ok(())
+            }
+            Err(e) => {
+                sqlx::query::<Sqlite>("ROLLBACK").execute(&mut *conn).await?;
+                Err(e)
+            }
+        }
+    }
+
+    async fn intent_head(&self, intent: &IntentId) -> Result<Option<EventHeader>> {
+        let mut conn = self.pool.acquire().await?;
+        Self::fetch_intent_head(&mut conn, intent).await
+    }
+}
+
+#[async_trait]
+impl CompactableStorage for SqliteBackend {
+    async fn remove_events(&self, ids: &[EventId]) -> Result<usize> {
+        self.ensure_writable("remove_events")?;
+        let mut tx = self.pool.begin().await?;
+        let mut removed = Vec::new();
+
+        for id in ids {
+            let row = sqlx::query::<Sqlite>(
+                "DELETE FROM event_headers WHERE id = ? RETURNING header_data"
+            )
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            if let Some(row) = row {
+                removed.push(Self::decode_header_row(&row)?);
+            }
+        }
+
+        Self::delete_idempotency_keys(&mut tx, &removed).await?;
+        Self::delete_unreferenced_payloads(&mut tx, &removed).await?;
+
+        tx.commit().await?;
+        Ok(removed.len())
+    }
+}
+
+#[async_trait]
+impl RetentionStorage for SqliteBackend {
+    async fn apply_retention(
+        &self,
+        policy: &RetentionPolicy,
+        now: DateTime<Utc>,
+    ) -> Result<RetentionReport> {
+        self.ensure_writable("apply_retention")?;
+        let mut tx = self.pool.begin().await?;
+        let mut removed = Vec::new();
+
+        let kinds: Vec<String> = sqlx::query::<Sqlite>("SELECT DISTINCT kind FROM event_headers")
+            .fetch_all(&mut *tx)
+            .await?
+            .iter()
+            .map(|row| row.get("kind"))
+            .collect();
+
+        for kind in kinds {
+            let rule = policy.rule_for(&kind);
+            if rule.is_unbounded() {
+                continue;
+            }
+
+            if let Some(cutoff) = rule.cutoff(now) {
+                let rows = sqlx::query::<Sqlite>(
+                    "DELETE FROM event_headers WHERE kind = ? AND timestamp < ? RETURNING header_data"
+                )
+                .bind(&kind)
+                .bind(cutoff.to_rfc3339())
+                .fetch_all(&mut *tx)
+                .await?;
+                for row in &rows {
+                    removed.push(Self::decode_header_row(row)?);
+                }
+            }
+
+            // Expired events were the oldest, so removing them first does not
+            // change which of the remaining events fall outside the count limit.
+            if let Some(max_events) = rule.max_events {
+                let rows = sqlx::query::<Sqlite>(
+                    r#"
+                    DELETE FROM event_headers WHERE rowid IN (
+                        SELECT rowid FROM event_headers WHERE kind = ?
+                        ORDER BY timestamp DESC, rowid DESC LIMIT -1 OFFSET ?
+                    ) RETURNING header_data
+                    "#
+                )
+                .bind(&kind)
+                .bind(max_events as i64)
+                .fetch_all(&mut *tx)
+                .await?;
+                for row in &rows {
+                    removed.push(Self::decode_header_row(row)?);
+                }
+            }
+        }
+
+        Self::delete_idempotency_keys(&mut tx, &removed).await?;
+        let payloads_removed = Self::delete_unreferenced_payloads(&mut tx, &removed).await?;
+        tx.commit().await?;
+
+        let mut report = RetentionReport {
+            events_removed: removed.len(),
+            payloads_removed,
+            ..RetentionReport::default()
+        };
+        for header in &removed {
+            *report.removed_by_kind.entry(header.kind.clone()).or_insert(0) += 1;
+        }
+        Ok(report)
+    }
+}
+
+#[async_trait]
+impl IndexedStorage for SqliteBackend {
+    async fn create_index(&self, field: &str) -> Result<IndexDefinition> {
+        self.ensure_writable("create_index")?;
+        let index = IndexDefinition::header(field)?;
+
+        // Header fields are plain columns; make sure each has an index
+        sqlx::query::<Sqlite>(&format!(
+            "CREATE INDEX IF NOT EXISTS idx_headers_{0} ON event_headers({0})",
+            index.name
+        ))
+        .execute(&self.pool)
+        .await?;
+        self.register_index(&index).await?;
+        Ok(index)
+    }
+
+    async fn create_index_json(&self, path: &str) -> Result<IndexDefinition> {
+        self.ensure_writable("create_index_json")?;
+        let index = IndexDefinition::json_path(path)?;
+        if self.indexes.read().await.contains(&index) {
+            return Ok(index);
+        }
+        let column = Self::index_column(&index);
+
+        let mut tx = self.pool.begin().await?;
+        let row = sqlx::query::<Sqlite>(
+            "SELECT COUNT(*) as count FROM pragma_table_info('event_headers') WHERE name = ?"
+        )
+        .bind(&column)
+        .fetch_one(&mut *tx)
+        .await?;
+        if row.get::<i64, _>("count") > 0 {
+            return Err(StorageError::InvalidIndex(format!(
+                "index column {} already exists for a different path",
+                column
+            ))
+            .into());
+        }
+
+        sqlx::query::<Sqlite>(&format!("ALTER TABLE event_headers ADD COLUMN {} TEXT", column))
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query::<Sqlite>(&format!(
+            "CREATE INDEX idx_headers_{} ON event_headers({})",
+            index.name, column
+        ))
         .execute(&mut *tx)
         .await?;
 
+        // Backfill keys for events committed before the index existed
+        let rows = sqlx::query::<Sqlite>("SELECT header_data FROM event_headers")
+            .fetch_all(&mut *tx)
+            .await?;
+        for row in &rows {
+            let header = Self::decode_header_row(row)?;
+            let stored = sqlx::query::<Sqlite>(
+                "SELECT payload_data, compression FROM event_payloads WHERE digest = ?"
+            )
+            .bind(&header.digest[..])
+            .fetch_optional(&mut *tx)
+            .await?;
+            let Some(stored) = stored else {
+                continue;
+            };
+
+            let codec = Compression::from_id(stored.get::<i64, _>("compression") as u8)?;
+            let payload = codec.decompress(&stored.get::<Vec<u8>, _>("payload_data"))?;
+            sqlx::query::<Sqlite>(&format!("UPDATE event_headers SET {} = ? WHERE id = ?", column))
+                .bind(index.key_for(&header, &payload)?)
+                .bind(header.id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        Self::insert_index_definition(&mut *tx, &index).await?;
+        tx.commit().await?;
+
+        self.indexes.write().await.push(index.clone());
+        Ok(index)
+    }
+
+    async fn drop_index(&self, name: &str) -> Result<bool> {
+        self.ensure_writable("drop_index")?;
+        let mut indexes = self.indexes.write().await;
+        let Some(position) = indexes.iter().position(|index| index.name == name) else {
+            return Ok(false);
+        };
+
+        let mut tx = self.pool.begin().await?;
+        // Indexes on header fields are part of the base schema and stay in place
+        if let IndexTarget::JsonPath(_) = indexes[position].target {
+            sqlx::query::<Sqlite>(&format!("DROP INDEX IF EXISTS idx_headers_{}", name))
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query::<Sqlite>(&format!(
+                "ALTER TABLE event_headers DROP COLUMN {}",
+                Self::index_column(&indexes[position])
+            ))
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query::<Sqlite>("DELETE FROM event_indexes WHERE name = ?")
+            .bind(name)
+            .execute(&mut *tx)
+            .await?;
         tx.commit().await?;
 
-        // Broadcast live update (ignore errors if no subscribers)
-        let _ = self.broadcast_tx.send(header.clone());
+        indexes.remove(position);
+        Ok(true)
+    }
+
+    async fn indexes(&self) -> Result<Vec<IndexDefinition>> {
+        Ok(self.indexes.read().await.clone())
+    }
+
+    async fn events_by_index(&self, name: &str, value: &serde_json::Value) -> Result<EventHeaderStream<'_>> {
+        let column = self
+            .indexes
+            .read()
+            .await
+            .iter()
+            .find(|index| index.name == name)
+            .map(Self::index_column)
+            .ok_or_else(|| StorageError::InvalidIndex(format!("no index named '{}'", name)))?;
+
+        // The query text is built at runtime and cannot outlive this call,
+        // so the rows are fetched up front rather than streamed
+        let rows = sqlx::query::<Sqlite>(&format!(
+            "SELECT header_data FROM event_headers WHERE {} = ? ORDER BY timestamp ASC, rowid ASC",
+            column
+        ))
+        .bind(toka_store_core::index::index_key(value))
+        .fetch_all(&self.pool)
+        .await?;
+        let headers: Vec<_> = rows.iter().map(Self::decode_header_row).collect();
+
+        Ok(Box::pin(futures::stream::iter(headers)))
+    }
+}
+
+impl SqliteBackend {
+    /// Persist `index` in the registry and the in-memory list if it is new.
+    async fn register_index(&self, index: &IndexDefinition) -> Result<()> {
+        let mut indexes = self.indexes.write().await;
+        if !indexes.contains(index) {
+            Self::insert_index_definition(&self.pool, index).await?;
+            indexes.push(index.clone());
+        }
+        Ok(())
+    }
 
+    async fn insert_index_definition<'e, E>(executor: E, index: &IndexDefinition) -> Result<()>
+    where
+        E: sqlx::Executor<'e, Database = Sqlite>,
+    {
+        sqlx::query::<Sqlite>("INSERT OR REPLACE INTO event_indexes (name, definition) VALUES (?, ?)")
+            .bind(&index.name)
+            .bind(rmp_serde::to_vec_named(index)?)
+            .execute(executor)
+            .await?;
         Ok(())
     }
+}
 
-    async fn header(&self, id: &EventId) -> Result<Option<EventHeader>> {
+#[async_trait]
+impl StorageStats for SqliteBackend {
+    async fn storage_stats(&self) -> Result<StorageStatistics> {
+        self.flush_wal().await?;
         let row = sqlx::query::<Sqlite>(
-            "SELECT header_data FROM event_headers WHERE id = ?"
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM event_headers) as events,
+                (SELECT COUNT(*) FROM event_payloads) as payloads,
+                (SELECT COUNT(*) FROM wal_entries) as wal_depth,
+                (SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()) as bytes_used
+            "#,
         )
-        .bind(id)
-        .fetch_optional(&self.pool)
+        .fetch_one(&self.pool)
         .await?;
 
-        match row {
-            Some(row) => {
-                let header_bytes: Vec<u8> = row.get("header_data");
-                let header: EventHeader = rmp_serde::from_slice(&header_bytes)?;
-                Ok(Some(header))
+        // Decode the boundary headers so timestamps are exact rather than
+        // re-parsed from their text column
+        let mut boundaries = [None, None];
+        for (slot, order) in boundaries.iter_mut().zip(["ASC", "DESC"]) {
+            let boundary = sqlx::query::<Sqlite>(&format!(
+                "SELECT header_data FROM event_headers ORDER BY timestamp {0}, rowid {0} LIMIT 1",
+                order
+            ))
+            .fetch_optional(&self.pool)
+            .await?;
+            if let Some(boundary) = boundary {
+                *slot = Some(Self::decode_header_row(&boundary)?.timestamp);
             }
-            None => Ok(None),
         }
+        let [oldest_event, newest_event] = boundaries;
+
+        Ok(StorageStatistics {
+            event_count: row.get::<i64, _>("events") as u64,
+            payload_count: row.get::<i64, _>("payloads") as u64,
+            bytes_used: row.get::<i64, _>("bytes_used") as u64,
+            oldest_event,
+            newest_event,
+            wal_depth: row.get::<i64, _>("wal_depth") as u64,
+            open_transactions: self.open_transaction_count().await,
+            transactions_timed_out: self.timed_out.load(Ordering::Relaxed),
+        })
     }
+}
 
-    async fn payload_bytes(&self, digest: &CausalDigest) -> Result<Option<Vec<u8>>> {
-        let row = sqlx::query::<Sqlite>(
-            "SELECT payload_data FROM event_payloads WHERE digest = ?"
+#[async_trait]
+impl SnapshotStorage for SqliteBackend {
+    async fn export_snapshot(
+        &self,
+        writer: &mut (dyn futures::io::AsyncWrite + Unpin + Send),
+    ) -> Result<SnapshotManifest> {
+        // A read transaction keeps headers and payloads consistent
+        let mut tx = self.pool.begin().await?;
+
+        let rows = sqlx::query::<Sqlite>(
+            "SELECT header_data FROM event_headers ORDER BY timestamp ASC, rowid ASC"
         )
-        .bind(&digest[..])
-        .fetch_optional(&self.pool)
+        .fetch_all(&mut *tx)
         .await?;
+        let headers = rows
+            .iter()
+            .map(Self::decode_header_row)
+            .collect::<Result<Vec<_>>>()?;
+
+        let manifest = SnapshotManifest {
+            version: toka_store_core::snapshot::SNAPSHOT_VERSION,
+            created_at: Utc::now(),
+            wal_sequence: self.current_sequence().await?,
+            events: headers.len() as u64,
+            payloads: headers
+                .iter()
+                .map(|header| header.digest)
+                .collect::<std::collections::HashSet<_>>()
+                .len() as u64,
+        };
 
-        match row {
-            Some(row) => Ok(Some(row.get("payload_data"))),
-            None => Ok(None),
+        let mut snapshot = SnapshotWriter::begin(writer, &manifest).await?;
+        for header in &headers {
+            let row = sqlx::query::<Sqlite>(
+                "SELECT payload_data, compression FROM event_payloads WHERE digest = ?"
+            )
+            .bind(&header.digest[..])
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("payload missing for event {}", header.id))?;
+
+            let codec = Compression::from_id(row.get::<i64, _>("compression") as u8)?;
+            let payload = codec.decompress(&row.get::<Vec<u8>, _>("payload_data"))?;
+            snapshot.write_event(header, &payload).await?;
+        }
+        snapshot.finish().await?;
+        tx.commit().await?;
+
+        Ok(manifest)
+    }
+
+    async fn import_snapshot(
+        &self,
+        reader: &mut (dyn futures::io::AsyncRead + Unpin + Send),
+    ) -> Result<SnapshotManifest> {
+        self.ensure_writable("import_snapshot")?;
+        let mut snapshot = SnapshotReader::open(reader).await?;
+        while let Some((header, payload)) = snapshot.next_event().await? {
+            self.commit(&header, &payload).await?;
         }
+
+        let manifest = snapshot.manifest().clone();
+        sqlx::query::<Sqlite>("UPDATE wal_sequence SET value = MAX(value, ?) WHERE id = 1")
+            .bind(manifest.wal_sequence as i64)
+            .execute(&self.pool)
+            .await
+            .map_err(Self::map_lock_error)?;
+        let mut sequence = self.wal_sequence.write().await;
+        *sequence = (*sequence).max(manifest.wal_sequence);
+        Ok(manifest)
     }
 }
 
 #[async_trait]
 impl WriteAheadLog for SqliteBackend {
     async fn begin_transaction(&self) -> Result<TransactionId> {
+        self.ensure_writable("begin_transaction")?;
         let transaction_id = Uuid::new_v4();
-        let sequence = self.next_sequence().await;
-        
-        // Create WAL entry for transaction begin
-        let wal_entry = WalEntry {
-            id: Uuid::new_v4(),
-            transaction_id,
-            sequence,
-            timestamp: chrono::Utc::now(),
-            operation: WalOperation::BeginTransaction { transaction_id },
-            state: WalEntryState::Pending,
-        };
 
-        // Store WAL entry
-        let operation_bytes = rmp_serde::to_vec_named(&wal_entry.operation)?;
-        sqlx::query::<Sqlite>(
-            r#"
-            INSERT INTO wal_entries 
-            (id, transaction_id, sequence_number, timestamp, operation_data, state) 
-            VALUES (?, ?, ?, ?, ?, ?)
-            "#
-        )
-        .bind(wal_entry.id)
-        .bind(transaction_id)
-        .bind(sequence as i64)
-        .bind(wal_entry.timestamp.to_rfc3339())
-        .bind(&operation_bytes)
-        .bind(Self::state_to_int(wal_entry.state))
-        .execute(&self.pool)
-        .await?;
+        // Log the transaction begin
+        let operation = WalOperation::BeginTransaction { transaction_id };
+        let entry = self
+            .log_wal_entry(transaction_id, &operation, WalEntryState::Pending, false)
+            .await?;
 
         // Track transaction state
         let transaction_state = WalTransactionState {
             transaction_id,
             state: WalTransactionStateType::Active,
-            operations: vec![wal_entry.operation],
-            sequences: vec![sequence],
+            operations: vec![operation],
+            entries: vec![entry],
+            began_at: Utc::now(),
+            savepoints: Vec::new(),
         };
 
         self.active_transactions
@@ -413,6 +2045,7 @@ impl WriteAheadLog for SqliteBackend {
         transaction_id: TransactionId,
         operation: WalOperation,
     ) -> Result<()> {
+        self.ensure_writable("write_entry")?;
         // Check if transaction is active
         {
             let transactions = self.active_transactions.read().await;
@@ -432,42 +2065,17 @@ impl WriteAheadLog for SqliteBackend {
             }
         }
 
-        let sequence = self.next_sequence().await;
-        
-        // Create WAL entry
-        let wal_entry = WalEntry {
-            id: Uuid::new_v4(),
-            transaction_id,
-            sequence,
-            timestamp: chrono::Utc::now(),
-            operation: operation.clone(),
-            state: WalEntryState::Pending,
-        };
-
-        // Store WAL entry
-        let operation_bytes = rmp_serde::to_vec_named(&wal_entry.operation)?;
-        sqlx::query::<Sqlite>(
-            r#"
-            INSERT INTO wal_entries 
-            (id, transaction_id, sequence_number, timestamp, operation_data, state) 
-            VALUES (?, ?, ?, ?, ?, ?)
-            "#
-        )
-        .bind(wal_entry.id)
-        .bind(transaction_id)
-        .bind(sequence as i64)
-        .bind(wal_entry.timestamp.to_rfc3339())
-        .bind(&operation_bytes)
-        .bind(Self::state_to_int(wal_entry.state))
-        .execute(&self.pool)
-        .await?;
+        // Log the WAL entry
+        let entry = self
+            .log_wal_entry(transaction_id, &operation, WalEntryState::Pending, false)
+            .await?;
 
         // Update transaction state
         {
             let mut transactions = self.active_transactions.write().await;
             if let Some(tx_state) = transactions.get_mut(&transaction_id) {
                 tx_state.operations.push(operation);
-                tx_state.sequences.push(sequence);
+                tx_state.entries.push(entry);
             }
         }
 
@@ -475,6 +2083,7 @@ impl WriteAheadLog for SqliteBackend {
     }
 
     async fn commit_transaction(&self, transaction_id: TransactionId) -> Result<()> {
+        self.ensure_writable("commit_transaction")?;
         // Update transaction state to committing
         let operations = {
             let mut transactions = self.active_transactions.write().await;
@@ -509,32 +2118,14 @@ impl WriteAheadLog for SqliteBackend {
             }
         }
 
-        // Log the commit transaction operation
-        let commit_sequence = self.next_sequence().await;
-        let commit_operation_bytes = rmp_serde::to_vec_named(&WalOperation::CommitTransaction { transaction_id })?;
-        sqlx::query::<Sqlite>(
-            r#"
-            INSERT INTO wal_entries 
-            (id, transaction_id, sequence_number, timestamp, operation_data, state) 
-            VALUES (?, ?, ?, ?, ?, ?)
-            "#
-        )
-        .bind(Uuid::new_v4())
-        .bind(transaction_id)
-        .bind(commit_sequence as i64)
-        .bind(chrono::Utc::now().to_rfc3339())
-        .bind(&commit_operation_bytes)
-        .bind(Self::state_to_int(WalEntryState::Committed))
-        .execute(&self.pool)
-        .await?;
-
-        // Mark all WAL entries for this transaction as committed
-        sqlx::query::<Sqlite>(
-            "UPDATE wal_entries SET state = ? WHERE transaction_id = ?"
+        // Log the commit and mark all WAL entries for this transaction as
+        // committed, flushing any buffered entries along the way
+        self.log_wal_entry(
+            transaction_id,
+            &WalOperation::CommitTransaction { transaction_id },
+            WalEntryState::Committed,
+            true,
         )
-        .bind(Self::state_to_int(WalEntryState::Committed))
-        .bind(transaction_id)
-        .execute(&self.pool)
         .await?;
 
         // Update transaction state to committed
@@ -549,6 +2140,7 @@ impl WriteAheadLog for SqliteBackend {
     }
 
     async fn rollback_transaction(&self, transaction_id: TransactionId) -> Result<()> {
+        self.ensure_writable("rollback_transaction")?;
         // Update transaction state to rolling back
         {
             let mut transactions = self.active_transactions.write().await;
@@ -569,32 +2161,14 @@ impl WriteAheadLog for SqliteBackend {
             }
         }
 
-        // Log the rollback transaction operation
-        let rollback_sequence = self.next_sequence().await;
-        let rollback_operation_bytes = rmp_serde::to_vec_named(&WalOperation::RollbackTransaction { transaction_id })?;
-        sqlx::query::<Sqlite>(
-            r#"
-            INSERT INTO wal_entries 
-            (id, transaction_id, sequence_number, timestamp, operation_data, state) 
-            VALUES (?, ?, ?, ?, ?, ?)
-            "#
-        )
-        .bind(Uuid::new_v4())
-        .bind(transaction_id)
-        .bind(rollback_sequence as i64)
-        .bind(chrono::Utc::now().to_rfc3339())
-        .bind(&rollback_operation_bytes)
-        .bind(Self::state_to_int(WalEntryState::RolledBack))
-        .execute(&self.pool)
-        .await?;
-
-        // Mark all WAL entries for this transaction as rolled back
-        sqlx::query::<Sqlite>(
-            "UPDATE wal_entries SET state = ? WHERE transaction_id = ?"
+        // Log the rollback and mark all WAL entries for this transaction as
+        // rolled back, flushing any buffered entries along the way
+        self.log_wal_entry(
+            transaction_id,
+            &WalOperation::RollbackTransaction { transaction_id },
+            WalEntryState::RolledBack,
+            true,
         )
-        .bind(Self::state_to_int(WalEntryState::RolledBack))
-        .bind(transaction_id)
-        .execute(&self.pool)
         .await?;
 
         // Update transaction state to rolled back
@@ -608,12 +2182,64 @@ impl WriteAheadLog for SqliteBackend {
         Ok(())
     }
 
+    async fn savepoint(&self, transaction_id: TransactionId) -> Result<SavepointId> {
+        self.ensure_writable("savepoint")?;
+        let savepoint_id = Uuid::new_v4();
+        let operation = WalOperation::Savepoint { transaction_id, savepoint_id };
+
+        // Hold the transaction lock so no entry slips in before the mark
+        let mut transactions = self.active_transactions.write().await;
+        let tx_state = Self::active_state(&mut transactions, transaction_id)?;
+        let entry = self
+            .log_wal_entry(transaction_id, &operation, WalEntryState::Pending, false)
+            .await?;
+
+        tx_state.operations.push(operation);
+        tx_state.entries.push(entry);
+        tx_state.savepoints.push(SavepointMark {
+            savepoint_id,
+            entry,
+            operations: tx_state.operations.len(),
+        });
+        Ok(savepoint_id)
+    }
+
+    async fn rollback_to_savepoint(&self, transaction_id: TransactionId, savepoint_id: SavepointId) -> Result<()> {
+        self.ensure_writable("rollback_to_savepoint")?;
+        let mut transactions = self.active_transactions.write().await;
+        let tx_state = Self::active_state(&mut transactions, transaction_id)?;
+        let position = tx_state
+            .savepoints
+            .iter()
+            .position(|mark| mark.savepoint_id == savepoint_id)
+            .ok_or(StorageError::SavepointNotFound { transaction_id, savepoint_id })?;
+        let mark = tx_state.savepoints[position].clone();
+
+        // Discard the entries first: if logging the rollback fails, the
+        // transaction can no longer commit them by accident
+        self.discard_wal_entries(transaction_id, mark.entry).await?;
+        tx_state.savepoints.truncate(position + 1);
+        tx_state.operations.truncate(mark.operations);
+        tx_state.entries.truncate(mark.operations);
+
+        let operation = WalOperation::RollbackToSavepoint { transaction_id, savepoint_id };
+        let entry = self
+            .log_wal_entry(transaction_id, &operation, WalEntryState::Pending, false)
+            .await?;
+        tx_state.operations.push(operation);
+        tx_state.entries.push(entry);
+        Ok(())
+    }
+
     async fn recover(&self) -> Result<WalRecoveryResult> {
+        self.ensure_writable("recover")?;
+        self.flush_wal().await?;
         let mut result = WalRecoveryResult {
             entries_recovered: 0,
             transactions_rolled_back: 0,
             transactions_committed: 0,
             entries_checkpointed: 0,
+            entries_quarantined: 0,
             recovery_errors: Vec::new(),
         };
 
@@ -643,10 +2269,20 @@ impl WriteAheadLog for SqliteBackend {
                 .map_err(|e| anyhow::anyhow!("Invalid timestamp: {}", e))?
                 .with_timezone(&chrono::Utc);
 
-            let operation: WalOperation = match rmp_serde::from_slice(&operation_bytes) {
+            let operation: WalOperation = match decode_wal_operation(&operation_bytes) {
                 Ok(op) => op,
                 Err(e) => {
-                    result.recovery_errors.push(format!("Failed to deserialize operation: {}", e));
+                    // Keep the entry so a release that understands it can restore it
+                    let mut error = format!(
+                        "Failed to deserialize operation {} of transaction {}: {}",
+                        entry_id, transaction_id, e
+                    );
+                    match self.quarantine_wal_entry(&row, &e.to_string()).await {
+                        Ok(()) => result.entries_quarantined += 1,
+                        Err(q) => error = format!("{}; quarantine failed: {}", error, q),
+                    }
+                    self.recovery_hooks.on_error(Some(transaction_id), &error);
+                    result.recovery_errors.push(error);
                     continue;
                 }
             };
@@ -680,28 +2316,56 @@ impl WriteAheadLog for SqliteBackend {
 
             if has_commit {
                 // Apply all committed operations
+                let (mut applied, mut skipped) = (0, 0);
                 for entry in entries {
                     if entry.state == WalEntryState::Committed {
                         match &entry.operation {
                             WalOperation::CommitEvent { header, payload } => {
+                                if self.recovery_hooks.before_replay(&entry) == ReplayDecision::Skip {
+                                    skipped += 1;
+                                    continue;
+                                }
                                 if let Err(e) = self.commit(header, payload).await {
-                                    result.recovery_errors.push(format!(
-                                        "Failed to apply committed event: {}", e
-                                    ));
+                                    let error = format!("Failed to apply committed event: {}", e);
+                                    self.recovery_hooks.on_error(Some(transaction_id), &error);
+                                    result.recovery_errors.push(error);
+                                } else {
+                                    applied += 1;
                                 }
                             }
                             _ => {} // Other operations don't need reapplication
                         }
                     }
                 }
+                self.recovery_hooks.on_transaction_replayed(transaction_id, applied, skipped);
                 result.transactions_committed += 1;
+            } else if entries
+                .iter()
+                .any(|e| matches!(e.operation, WalOperation::RollbackTransaction { .. }))
+            {
+                // Already rolled back, nothing to undo
             } else {
-                // Roll back uncommitted transactions
-                if let Err(e) = self.rollback_transaction(transaction_id).await {
-                    result.recovery_errors.push(format!(
-                        "Failed to rollback transaction {}: {}", transaction_id, e
-                    ));
+                // Roll back uncommitted transactions. Those begun before a
+                // restart are no longer tracked, so only their log is updated.
+                let tracked = self.active_transactions.read().await.contains_key(&transaction_id);
+                let rolled_back = if tracked {
+                    self.rollback_transaction(transaction_id).await
+                } else {
+                    self.log_wal_entry(
+                        transaction_id,
+                        &WalOperation::RollbackTransaction { transaction_id },
+                        WalEntryState::RolledBack,
+                        true,
+                    )
+                    .await
+                    .map(|_| ())
+                };
+                if let Err(e) = rolled_back {
+                    let error = format!("Failed to rollback transaction {}: {}", transaction_id, e);
+                    self.recovery_hooks.on_error(Some(transaction_id), &error);
+                    result.recovery_errors.push(error);
                 } else {
+                    self.recovery_hooks.on_rollback(transaction_id, entries.len());
                     result.transactions_rolled_back += 1;
                 }
             }
@@ -711,6 +2375,8 @@ impl WriteAheadLog for SqliteBackend {
     }
 
     async fn checkpoint(&self, sequence: SequenceNumber) -> Result<()> {
+        self.ensure_writable("checkpoint")?;
+        self.flush_wal().await?;
         // Mark entries up to sequence as checkpointed
         let rows_affected = sqlx::query::<Sqlite>(
             "UPDATE wal_entries SET state = ? WHERE sequence_number <= ? AND state = ?"
@@ -722,14 +2388,283 @@ impl WriteAheadLog for SqliteBackend {
         .await?
         .rows_affected();
 
-        // Optionally remove old checkpointed entries to free space
-        // This is a policy decision - for now we keep them for audit purposes
-        
+        if let Some(before) = self.wal_compaction.compaction_bound(sequence) {
+            self.compact_wal(before).await?;
+        }
         Ok(())
     }
 
     async fn current_sequence(&self) -> Result<SequenceNumber> {
-        Ok(*self.wal_sequence.read().await)
+        // Buffered entries are only numbered once they are flushed
+        self.flush_wal().await?;
+        let sequence = self.stored_sequence().await?;
+        let mut cached = self.wal_sequence.write().await;
+        *cached = (*cached).max(sequence);
+        Ok(sequence)
+    }
+
+    async fn wal_health(&self) -> Result<WalHealth> {
+        self.flush_wal().await?;
+        let mut health = WalHealth::new(self.current_sequence().await?);
+
+        let rows = sqlx::query::<Sqlite>(
+            r#"
+            SELECT state, COUNT(*) as entries, COALESCE(SUM(length(operation_data)), 0) as bytes
+            FROM wal_entries
+            GROUP BY state
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        for row in rows {
+            let entries = row.get::<i64, _>("entries") as u64;
+            let counts = &mut health.entries_by_state;
+            match Self::int_to_state(row.get("state")) {
+                WalEntryState::Pending => counts.pending += entries,
+                WalEntryState::Committed => counts.committed += entries,
+                WalEntryState::RolledBack => counts.rolled_back += entries,
+                WalEntryState::Checkpointed => counts.checkpointed += entries,
+            }
+            health.bytes_used += row.get::<i64, _>("bytes") as u64;
+        }
+
+        // Timestamps are compared parsed, since their text form varies in
+        // the number of fractional digits
+        let pending = sqlx::query::<Sqlite>("SELECT timestamp FROM wal_entries WHERE state = ?")
+            .bind(Self::state_to_int(WalEntryState::Pending))
+            .fetch_all(&self.pool)
+            .await?;
+        health.oldest_pending_at = pending
+            .iter()
+            .filter_map(|row| DateTime::parse_from_rfc3339(&row.get::<String, _>("timestamp")).ok())
+            .map(|timestamp| timestamp.with_timezone(&Utc))
+            .min();
+
+        health.active_transactions = self.open_transaction_count().await;
+        Ok(health)
+    }
+}
+
+/// Transactions whose entries all precede the bound and are checkpointed or
+/// rolled back.
+const COMPACTABLE_TRANSACTIONS: &str = r#"
+    SELECT transaction_id FROM wal_entries
+    GROUP BY transaction_id
+    HAVING MAX(sequence_number) < ?
+       AND SUM(CASE WHEN state IN (?, ?) THEN 0 ELSE 1 END) = 0
+"#;
+
+#[async_trait]
+impl WalCompaction for SqliteBackend {
+    async fn compact_wal(&self, before: SequenceNumber) -> Result<WalCompactionReport> {
+        self.ensure_writable("compact_wal")?;
+        self.flush_wal().await?;
+
+        let before = i64::try_from(before).unwrap_or(i64::MAX);
+        let removable_states = [
+            Self::state_to_int(WalEntryState::Checkpointed),
+            Self::state_to_int(WalEntryState::RolledBack),
+        ];
+        let mut tx = self.pool.begin().await?;
+
+        let transaction_ids: Vec<TransactionId> = sqlx::query::<Sqlite>(COMPACTABLE_TRANSACTIONS)
+            .bind(before)
+            .bind(removable_states[0])
+            .bind(removable_states[1])
+            .fetch_all(&mut *tx)
+            .await?
+            .iter()
+            .map(|row| row.get("transaction_id"))
+            .collect();
+        if transaction_ids.is_empty() {
+            return Ok(WalCompactionReport::default());
+        }
+
+        let mut report = WalCompactionReport {
+            transactions_removed: transaction_ids.len() as u64,
+            ..WalCompactionReport::default()
+        };
+        let rows = sqlx::query::<Sqlite>(&format!(
+            "SELECT state, COUNT(*) as count FROM wal_entries WHERE transaction_id IN ({}) GROUP BY state",
+            COMPACTABLE_TRANSACTIONS
+        ))
+        .bind(before)
+        .bind(removable_states[0])
+        .bind(removable_states[1])
+        .fetch_all(&mut *tx)
+        .await?;
+        for row in rows {
+            let count = row.get::<i64, _>("count") as u64;
+            match Self::int_to_state(row.get("state")) {
+                WalEntryState::RolledBack => report.rolled_back_entries += count,
+                _ => report.checkpointed_entries += count,
+            }
+        }
+
+        sqlx::query::<Sqlite>(&format!(
+            "DELETE FROM wal_entries WHERE transaction_id IN ({})",
+            COMPACTABLE_TRANSACTIONS
+        ))
+        .bind(before)
+        .bind(removable_states[0])
+        .bind(removable_states[1])
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        let mut transactions = self.active_transactions.write().await;
+        for transaction_id in &transaction_ids {
+            transactions.remove(transaction_id);
+        }
+        self.wal_reclaimed.lock().unwrap().absorb(&report);
+        Ok(report)
+    }
+
+    fn wal_compaction_totals(&self) -> WalCompactionReport {
+        *self.wal_reclaimed.lock().unwrap()
+    }
+}
+
+#[async_trait]
+impl WalSource for SqliteBackend {
+    async fn wal_entries_after(&self, after: SequenceNumber, limit: usize) -> Result<Vec<WalEntry>> {
+        self.flush_wal().await?;
+        let rows = sqlx::query::<Sqlite>(
+            r#"
+            SELECT id, transaction_id, sequence_number, timestamp, operation_data, state
+            FROM wal_entries
+            WHERE sequence_number > ?
+            ORDER BY sequence_number ASC
+            LIMIT ?
+            "#
+        )
+        .bind(after as i64)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(Self::decode_wal_row).collect()
+    }
+}
+
+#[async_trait]
+impl WalQuarantine for SqliteBackend {
+    async fn quarantined_entries(&self) -> Result<Vec<QuarantinedWalEntry>> {
+        let rows = sqlx::query::<Sqlite>(
+            r#"
+            SELECT id, transaction_id, sequence_number, timestamp, version, operation_data,
+                   state, reason, quarantined_at
+            FROM wal_quarantine
+            ORDER BY sequence_number ASC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let parse_time = |value: String| -> Result<DateTime<Utc>> {
+            Ok(DateTime::parse_from_rfc3339(&value)
+                .map_err(|e| anyhow::anyhow!("Invalid timestamp: {}", e))?
+                .with_timezone(&Utc))
+        };
+        rows.into_iter()
+            .map(|row| {
+                Ok(QuarantinedWalEntry {
+                    id: row.get("id"),
+                    transaction_id: row.get("transaction_id"),
+                    sequence: row.get::<i64, _>("sequence_number") as SequenceNumber,
+                    timestamp: parse_time(row.get("timestamp"))?,
+                    version: row.get::<i64, _>("version") as u16,
+                    data: row.get("operation_data"),
+                    state: Self::int_to_state(row.get("state")),
+                    reason: row.get("reason"),
+                    quarantined_at: parse_time(row.get("quarantined_at"))?,
+                })
+            })
+            .collect()
+    }
+
+    async fn restore_quarantined(&self) -> Result<usize> {
+        self.ensure_writable("restore_quarantined")?;
+        let mut restored = 0;
+        for entry in self.quarantined_entries().await? {
+            if decode_wal_operation(&entry.data).is_err() {
+                continue;
+            }
+            let mut tx = self.pool.begin().await?;
+            sqlx::query::<Sqlite>(
+                r#"
+                INSERT INTO wal_entries
+                (id, transaction_id, sequence_number, timestamp, operation_data, state)
+                SELECT id, transaction_id, sequence_number, timestamp, operation_data, state
+                FROM wal_quarantine WHERE id = ?
+                "#
+            )
+            .bind(entry.id)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query::<Sqlite>("DELETE FROM wal_quarantine WHERE id = ?")
+                .bind(entry.id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+            restored += 1;
+        }
+        Ok(restored)
+    }
+
+    async fn purge_quarantined(&self, id: Uuid) -> Result<bool> {
+        self.ensure_writable("purge_quarantined")?;
+        let deleted = sqlx::query::<Sqlite>("DELETE FROM wal_quarantine WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(deleted > 0)
+    }
+}
+
+#[async_trait]
+impl TransactionTimeouts for SqliteBackend {
+    fn transaction_ttl(&self) -> Option<std::time::Duration> {
+        self.transaction_ttl
+    }
+
+    async fn abort_stale_transactions(&self, now: DateTime<Utc>) -> Result<Vec<TransactionTimedOut>> {
+        let Some(ttl) = self.transaction_ttl else {
+            return Ok(Vec::new());
+        };
+        let stale: Vec<_> = self
+            .active_transactions
+            .read()
+            .await
+            .values()
+            .filter(|tx_state| tx_state.state == WalTransactionStateType::Active)
+            .filter(|tx_state| is_stale(tx_state.began_at, now, ttl))
+            .map(|tx_state| (tx_state.transaction_id, tx_state.began_at, tx_state.entries.len()))
+            .collect();
+
+        let mut aborted = Vec::new();
+        for (transaction_id, began_at, entries) in stale {
+            // The owner may have finished the transaction in the meantime
+            if self.rollback_transaction(transaction_id).await.is_err() {
+                continue;
+            }
+            let notice = TransactionTimedOut {
+                transaction_id,
+                began_at,
+                aborted_at: Utc::now(),
+                entries,
+            };
+            self.timed_out.fetch_add(1, Ordering::Relaxed);
+            // Ignore errors if no subscribers
+            let _ = self.timeout_tx.send(notice.clone());
+            aborted.push(notice);
+        }
+        Ok(aborted)
+    }
+
+    fn transactions_timed_out(&self) -> u64 {
+        self.timed_out.load(Ordering::Relaxed)
     }
 }
 
@@ -738,7 +2673,7 @@ mod tests {
     use super::*;
     use serde::{Deserialize, Serialize};
     use uuid::Uuid;
-    use toka_store_core::{create_event_header, prelude::*};
+    use toka_store_core::{create_event_header, prelude::*, BLOB_CHUNK_SIZE, WAL_FORMAT_VERSION};
 
     #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
     struct TestEvent {
@@ -793,46 +2728,229 @@ mod tests {
         let result = backend
             .payload_bytes(&[0u8; 32])
             .await
-            .unwrap();
-        assert!(result.is_none());
+            .unwrap();
+        assert!(result.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_payload_deduplication() {
+        let backend = SqliteBackend::in_memory().await.unwrap();
+
+        let event = TestEvent {
+            message: "duplicate".to_string(),
+            value: 123,
+        };
+
+        // Create two headers with same payload content (will have same digest)
+        let header1 = create_event_header(
+            &[],
+            Uuid::new_v4(),
+            "test.event".to_string(),
+            &event,
+        ).unwrap();
+
+        let header2 = create_event_header(
+            &[],
+            Uuid::new_v4(),
+            "test.event".to_string(),
+            &event,
+        ).unwrap();
+
+        // Same payload, same digest
+        assert_eq!(header1.digest, header2.digest);
+
+        let payload_bytes = rmp_serde::to_vec_named(&event).unwrap();
+
+        // Commit both events
+        backend.commit(&header1, &payload_bytes).await.unwrap();
+        backend.commit(&header2, &payload_bytes).await.unwrap();
+
+        // Should have 2 events but only 1 unique payload
+        assert_eq!(backend.event_count().await.unwrap(), 2);
+        assert_eq!(backend.payload_count().await.unwrap(), 1);
+
+        let stats = backend.storage_stats().await.unwrap();
+        assert_eq!(stats.event_count, 2);
+        assert_eq!(stats.payload_count, 1);
+        assert!(stats.bytes_used > 0);
+        assert_eq!(stats.oldest_event, Some(header1.timestamp));
+        assert_eq!(stats.newest_event, Some(header2.timestamp));
+        assert_eq!(stats.wal_depth, 0);
+    }
+
+    #[tokio::test]
+    async fn test_namespaces() {
+        let backend = SqliteBackend::in_memory().await.unwrap();
+        let event = TestEvent { message: "tenant".to_string(), value: 3 };
+        let payload = rmp_serde::to_vec_named(&event).unwrap();
+        let header = || create_event_header(&[], Uuid::new_v4(), "test.tenant".to_string(), &event).unwrap();
+
+        let a1 = backend.commit_in("tenant-a", &header(), &payload).await.unwrap();
+        let a2 = backend.commit_in("tenant-a", &header(), &payload).await.unwrap();
+        let b1 = backend.commit_in("tenant-b", &header(), &payload).await.unwrap();
+        backend.commit(&header(), &payload).await.unwrap();
+
+        assert!(backend.header_in("tenant-a", &a1.id).await.unwrap().is_some());
+        assert!(backend.header_in("tenant-b", &a1.id).await.unwrap().is_none());
+
+        let stats = backend.namespaces().await.unwrap();
+        let counts: Vec<_> = stats.iter().map(|s| (s.namespace.as_str(), s.event_count)).collect();
+        assert_eq!(counts, vec![("default", 1), ("tenant-a", 2), ("tenant-b", 1)]);
+        let tenant_a = backend.namespace_stats("tenant-a").await.unwrap();
+        assert_eq!(tenant_a.oldest_event, Some(a1.timestamp));
+        assert_eq!(tenant_a.newest_event, Some(a2.timestamp));
+        assert_eq!(backend.namespace_stats("missing").await.unwrap().event_count, 0);
+
+        assert_eq!(backend.prune_namespace("tenant-a", None).await.unwrap(), 2);
+        assert_eq!(backend.event_count().await.unwrap(), 2);
+        assert!(backend.payload_in("tenant-b", &b1.id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_commit_if() {
+        let backend = SqliteBackend::in_memory().await.unwrap();
+        let intent = Uuid::new_v4();
+        let event = TestEvent { message: "cas".to_string(), value: 4 };
+        let payload = rmp_serde::to_vec_named(&event).unwrap();
+
+        let first = create_event_header(&[], intent, "test.cas".to_string(), &event).unwrap();
+        let guard = Precondition::intent_head(intent, None).and(Precondition::Absent(first.id));
+        backend.commit_if(&first, &payload, &guard).await.unwrap();
+
+        // Replaying the same commit now fails both conditions; the first is reported
+        let err = backend.commit_if(&first, &payload, &guard).await.unwrap_err();
+        assert!(matches!(
+            toka_store_core::as_conflict(&err),
+            Some(CommitConflict::IntentHeadMoved { actual: Some(id), .. }) if *id == first.id
+        ));
+
+        let child = create_event_header(&[first.clone()], intent, "test.cas".to_string(), &event).unwrap();
+        let guard = Precondition::parents_exist(&child).and(Precondition::intent_head(intent, Some(first.id)));
+        backend.commit_if(&child, &payload, &guard).await.unwrap();
+
+        let stale = create_event_header(&[first.clone()], intent, "test.cas".to_string(), &event).unwrap();
+        assert!(backend.commit_if(&stale, &payload, &guard).await.is_err());
+        assert!(backend.header(&stale.id).await.unwrap().is_none());
+        assert_eq!(backend.event_count().await.unwrap(), 2);
+        assert_eq!(backend.intent_head(&intent).await.unwrap(), Some(child));
+        assert_eq!(backend.intent_head(&Uuid::new_v4()).await.unwrap(), None);
+
+        // The connection is usable again after a rolled back conditional commit
+        backend.commit(&stale, &payload).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_commit_deduplicated() {
+        let backend = SqliteBackend::in_memory()
+            .await
+            .unwrap()
+            .with_dedup_window(std::time::Duration::from_secs(60));
+        let event = TestEvent { message: "emit".to_string(), value: 5 };
+        let payload = rmp_serde::to_vec_named(&event).unwrap();
+        let keyed = || {
+            create_event_header(&[], Uuid::new_v4(), "test.dedup".to_string(), &event)
+                .unwrap()
+                .with_idempotency_key("emit-5")
+        };
+
+        let original = keyed();
+        let outcome = backend.commit_deduplicated(&original, &payload).await.unwrap();
+        assert_eq!(outcome, CommitOutcome::Committed(original.id));
+
+        let retry = keyed();
+        let outcome = backend.commit_deduplicated(&retry, &payload).await.unwrap();
+        assert_eq!(outcome, CommitOutcome::Duplicate(original.id));
+        backend.commit(&retry, &payload).await.unwrap();
+        assert!(backend.header(&retry.id).await.unwrap().is_none());
+
+        // Past the window the key starts a new logical event
+        let mut late = keyed();
+        late.timestamp = original.timestamp + chrono::Duration::seconds(61);
+        let outcome = backend.commit_deduplicated(&late, &payload).await.unwrap();
+        assert_eq!(outcome, CommitOutcome::Committed(late.id));
+        assert_eq!(backend.event_count().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_payloads_bulk() {
+        let backend = SqliteBackend::in_memory()
+            .await
+            .unwrap()
+            .with_compression(CompressionPolicy::new(Compression::Zstd).with_min_size(0));
+
+        let mut expected = HashMap::new();
+        for value in 0..(BULK_QUERY_CHUNK as i32 + 10) {
+            let event = TestEvent { message: "bulk".to_string(), value };
+            let header = create_event_header(&[], Uuid::new_v4(), "test.bulk".to_string(), &event).unwrap();
+            let payload = rmp_serde::to_vec_named(&event).unwrap();
+            backend.commit(&header, &payload).await.unwrap();
+            expected.insert(header.digest, payload);
+        }
+
+        // Spans more than one chunk, with a duplicate and an unknown digest
+        let mut digests: Vec<CausalDigest> = expected.keys().copied().collect();
+        digests.push(digests[0]);
+        digests.push([0u8; 32]);
+        let payloads = backend.payloads_bulk(&digests).await.unwrap();
+        assert_eq!(payloads, expected);
     }
 
     #[tokio::test]
-    async fn test_payload_deduplication() {
+    async fn test_blob_store() {
         let backend = SqliteBackend::in_memory().await.unwrap();
+        let blobs = backend.blob_store();
+
+        let artifact: Vec<u8> = (0..BLOB_CHUNK_SIZE * 2 + 5).map(|i| (i % 253) as u8).collect();
+        let blob = blobs.put_bytes(&artifact).await.unwrap();
+        assert_eq!(blob, BlobRef::for_bytes(&artifact));
+        assert_eq!(blobs.put_bytes(&artifact).await.unwrap(), blob);
+        assert_eq!(blobs.blob_count().await.unwrap(), 1);
+        assert_eq!(blobs.get_bytes(&blob.digest).await.unwrap(), Some(artifact));
+
+        // Events link to the blob through their header
+        let event = TestEvent { message: "build".to_string(), value: 1 };
+        let header = create_event_header(&[], Uuid::new_v4(), "test.artifact".to_string(), &event)
+            .unwrap()
+            .with_blob(blob);
+        backend.commit(&header, &rmp_serde::to_vec_named(&event).unwrap()).await.unwrap();
+        let stored = backend.header(&header.id).await.unwrap().unwrap();
+        assert_eq!(stored.blobs, vec![blob]);
 
-        let event = TestEvent {
-            message: "duplicate".to_string(),
-            value: 123,
-        };
+        let empty = blobs.put_bytes(&[]).await.unwrap();
+        assert_eq!(blobs.get_bytes(&empty.digest).await.unwrap(), Some(Vec::new()));
 
-        // Create two headers with same payload content (will have same digest)
-        let header1 = create_event_header(
-            &[],
-            Uuid::new_v4(),
-            "test.event".to_string(),
-            &event,
-        ).unwrap();
+        assert!(blobs.delete(&blob.digest).await.unwrap());
+        assert!(blobs.get(&blob.digest).await.unwrap().is_none());
+        assert!(!blobs.delete(&blob.digest).await.unwrap());
+    }
 
-        let header2 = create_event_header(
-            &[],
-            Uuid::new_v4(),
-            "test.event".to_string(),
-            &event,
-        ).unwrap();
+    #[tokio::test]
+    async fn test_open_read_only_rejects_writes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("readonly.db");
 
-        // Same payload, same digest
-        assert_eq!(header1.digest, header2.digest);
+        let event = TestEvent { message: "audited".to_string(), value: 1 };
+        let header = create_event_header(&[], Uuid::new_v4(), "test.audit".to_string(), &event).unwrap();
+        let payload = rmp_serde::to_vec_named(&event).unwrap();
 
-        let payload_bytes = rmp_serde::to_vec_named(&event).unwrap();
+        {
+            let backend = SqliteBackend::open(&db_path).await.unwrap();
+            backend.commit(&header, &payload).await.unwrap();
+            backend.close().await;
+        }
 
-        // Commit both events
-        backend.commit(&header1, &payload_bytes).await.unwrap();
-        backend.commit(&header2, &payload_bytes).await.unwrap();
+        let backend = SqliteBackend::open_read_only(&db_path).await.unwrap();
+        assert!(backend.is_read_only());
+        assert_eq!(backend.header(&header.id).await.unwrap(), Some(header.clone()));
+        assert_eq!(backend.payload_bytes(&header.digest).await.unwrap(), Some(payload.clone()));
 
-        // Should have 2 events but only 1 unique payload
-        assert_eq!(backend.event_count().await.unwrap(), 2);
-        assert_eq!(backend.payload_count().await.unwrap(), 1);
+        let other = create_event_header(&[], Uuid::new_v4(), "test.audit".to_string(), &event).unwrap();
+        let err = backend.commit(&other, &payload).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<StorageError>(), Some(StorageError::ReadOnly(_))));
+
+        let err = backend.begin_transaction().await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<StorageError>(), Some(StorageError::ReadOnly(_))));
+        assert!(backend.header(&other.id).await.unwrap().is_none());
     }
 
     #[tokio::test]
@@ -914,6 +3032,353 @@ mod tests {
         assert_eq!(backend.wal_entry_count().await.unwrap(), 0);
     }
 
+    #[tokio::test]
+    async fn test_compact_intent() {
+        let backend = SqliteBackend::in_memory().await.unwrap();
+        let intent = Uuid::new_v4();
+
+        let mut headers = Vec::new();
+        for (i, kind) in ["task.progress", "task.progress", "task.done"].iter().enumerate() {
+            let event = TestEvent { message: kind.to_string(), value: i as i32 };
+            let header = create_event_header(&[], intent, kind.to_string(), &event).unwrap();
+            backend.commit(&header, &rmp_serde::to_vec_named(&event).unwrap()).await.unwrap();
+            headers.push(header);
+        }
+
+        let policy = CompactionPolicy::default().with_kind("task.progress", KindCompaction::Archive);
+        let summary_header = backend.compact_intent(&intent, &policy).await.unwrap().unwrap();
+
+        assert_eq!(backend.event_count().await.unwrap(), 2);
+        assert_eq!(backend.payload_count().await.unwrap(), 2);
+        assert!(backend.header(&headers[0].id).await.unwrap().is_none());
+        assert!(backend.header(&headers[2].id).await.unwrap().is_some());
+
+        let payload = backend.payload_bytes(&summary_header.digest).await.unwrap().unwrap();
+        let summary: CompactionSummary = rmp_serde::from_slice(&payload).unwrap();
+        assert_eq!(summary.archived_events, vec![headers[0].id, headers[1].id]);
+        assert_eq!(summary.retained_events, vec![headers[2].id]);
+    }
+
+    #[tokio::test]
+    async fn test_retention_policy() {
+        let backend = SqliteBackend::in_memory().await.unwrap();
+        let intent = Uuid::new_v4();
+        let now = Utc::now();
+
+        let event_at = |kind: &str, value: i32, age_hours: i64| {
+            let event = TestEvent { message: "shared".to_string(), value };
+            let mut header = create_event_header(&[], intent, kind.to_string(), &event).unwrap();
+            header.timestamp = now - chrono::Duration::hours(age_hours);
+            (header, rmp_serde::to_vec_named(&event).unwrap())
+        };
+        let events = vec![
+            event_at("metrics.cpu", 1, 48),
+            event_at("metrics.cpu", 2, 1),
+            event_at("task.log", 3, 3),
+            event_at("task.log", 4, 2),
+            event_at("task.log", 5, 1),
+            // Shares its payload with the pruned `task.log` event above
+            event_at("ledger.mint", 3, 3),
+        ];
+        for (header, payload) in &events {
+            backend.commit(header, payload).await.unwrap();
+        }
+        assert_eq!(backend.payload_count().await.unwrap(), 5);
+
+        let policy = RetentionPolicy::default()
+            .with_kind("metrics.*", RetentionRule::keep_all().with_max_age(std::time::Duration::from_secs(24 * 3600)))
+            .with_kind("task.log", RetentionRule::keep_all().with_max_events(2));
+        let report = backend.apply_retention(&policy, now).await.unwrap();
+
+        assert_eq!(report.events_removed, 2);
+        assert_eq!(report.payloads_removed, 1);
+        assert_eq!(report.removed_by_kind["metrics.cpu"], 1);
+        assert_eq!(report.removed_by_kind["task.log"], 1);
+        assert_eq!(backend.event_count().await.unwrap(), 4);
+        assert!(backend.header(&events[0].0.id).await.unwrap().is_none());
+        assert!(backend.header(&events[2].0.id).await.unwrap().is_none());
+
+        // The shared payload is still referenced by the ledger event
+        assert!(backend.payload_bytes(&events[5].0.digest).await.unwrap().is_some());
+
+        // Applying the same policy again is a no-op
+        let report = backend.apply_retention(&policy, now).await.unwrap();
+        assert_eq!(report, RetentionReport::default());
+    }
+
+    #[tokio::test]
+    async fn test_payload_references_backfilled_on_open() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("legacy.db");
+        let event = TestEvent { message: "shared".to_string(), value: 1 };
+        let payload = rmp_serde::to_vec_named(&event).unwrap();
+        let first = create_event_header(&[], Uuid::new_v4(), "test.shared".to_string(), &event).unwrap();
+        let second = create_event_header(&[], Uuid::new_v4(), "test.shared".to_string(), &event).unwrap();
+        assert_eq!(first.digest, second.digest);
+
+        {
+            let backend = SqliteBackend::open(&path).await.unwrap();
+            backend.commit(&first, &payload).await.unwrap();
+            backend.commit(&second, &payload).await.unwrap();
+            // Rows written before the digest column existed
+            sqlx::query::<Sqlite>("UPDATE event_headers SET digest = NULL")
+                .execute(&backend.pool)
+                .await
+                .unwrap();
+            backend.close().await;
+        }
+
+        let backend = SqliteBackend::open(&path).await.unwrap();
+        assert_eq!(backend.remove_events(&[first.id]).await.unwrap(), 1);
+        assert!(backend.payload_bytes(&first.digest).await.unwrap().is_some());
+        assert_eq!(backend.remove_events(&[second.id]).await.unwrap(), 1);
+        assert!(backend.payload_bytes(&first.digest).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_retention_is_committed() {
+        let backend = SqliteBackend::in_memory()
+            .await
+            .unwrap()
+            .with_dedup_window(std::time::Duration::from_secs(3600));
+        let payload = |value: i32| rmp_serde::to_vec_named(&TestEvent { message: "job".to_string(), value }).unwrap();
+        let keyed = |value: i32, key: &str| {
+            let event = TestEvent { message: "job".to_string(), value };
+            create_event_header(&[], Uuid::new_v4(), "task.log".to_string(), &event)
+                .unwrap()
+                .with_idempotency_key(key)
+        };
+
+        let pruned = keyed(1, "job-1");
+        backend.commit_deduplicated(&pruned, &payload(1)).await.unwrap();
+        let mut newer = keyed(2, "job-2");
+        newer.timestamp = pruned.timestamp + chrono::Duration::seconds(1);
+        backend.commit_deduplicated(&newer, &payload(2)).await.unwrap();
+
+        // Count-based pruning removes the keyed event well inside the window
+        let policy = RetentionPolicy::default().with_kind("task.log", RetentionRule::keep_all().with_max_events(1));
+        assert_eq!(backend.apply_retention(&policy, Utc::now()).await.unwrap().events_removed, 1);
+        assert!(backend.header(&pruned.id).await.unwrap().is_none());
+
+        let retry = keyed(1, "job-1");
+        let outcome = backend.commit_deduplicated(&retry, &payload(1)).await.unwrap();
+        assert_eq!(outcome, CommitOutcome::Committed(retry.id));
+        assert!(backend.header(&retry.id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_export_import() {
+        let source = SqliteBackend::in_memory()
+            .await
+            .unwrap()
+            .with_compression(CompressionPolicy::new(Compression::Zstd).with_min_size(0));
+        let intent = Uuid::new_v4();
+
+        let event = TestEvent { message: "snapshot ".repeat(20), value: 1 };
+        let payload = rmp_serde::to_vec_named(&event).unwrap();
+        let root = create_event_header(&[], intent, "test.event".to_string(), &event).unwrap();
+        let duplicate = create_event_header(&[], intent, "test.event".to_string(), &event).unwrap();
+        let child = create_event_header(&[root.clone()], intent, "test.event".to_string(), &event).unwrap();
+        for header in [&root, &duplicate, &child] {
+            source.commit(header, &payload).await.unwrap();
+        }
+
+        let mut archive = Vec::new();
+        let exported = source
+            .export_snapshot(&mut futures::io::Cursor::new(&mut archive))
+            .await
+            .unwrap();
+        assert_eq!(exported.events, 3);
+        assert_eq!(exported.payloads, 2);
+
+        // Snapshots carry uncompressed payloads, so any backend can import them
+        let target = SqliteBackend::in_memory().await.unwrap();
+        let imported = target
+            .import_snapshot(&mut futures::io::Cursor::new(archive))
+            .await
+            .unwrap();
+        assert_eq!(imported, exported);
+        assert_eq!(target.event_count().await.unwrap(), 3);
+        assert_eq!(target.payload_count().await.unwrap(), 2);
+        assert_eq!(target.header(&child.id).await.unwrap(), Some(child.clone()));
+        assert_eq!(target.payload_bytes(&child.digest).await.unwrap(), Some(payload));
+    }
+
+    #[tokio::test]
+    async fn test_secondary_indexes() {
+        use futures::TryStreamExt;
+
+        let backend = SqliteBackend::in_memory().await.unwrap();
+        let intent = Uuid::new_v4();
+        let commit = |message: &str, value: i32| {
+            let event = TestEvent { message: message.to_string(), value };
+            let header = create_event_header(&[], intent, "test.event".to_string(), &event).unwrap();
+            (header, rmp_serde::to_vec_named(&event).unwrap())
+        };
+
+        // Committed before the index exists, so it must be backfilled
+        let (early, payload) = commit("alpha", 1);
+        backend.commit(&early, &payload).await.unwrap();
+
+        let index = backend.create_index_json("$.message").await.unwrap();
+        assert_eq!(index.name, "json_message");
+        let (late, payload) = commit("alpha", 2);
+        backend.commit(&late, &payload).await.unwrap();
+        let (other, payload) = commit("beta", 3);
+        backend.commit(&other, &payload).await.unwrap();
+
+        let found: Vec<EventHeader> = backend
+            .events_by_index("json_message", &serde_json::json!("alpha"))
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        let ids: Vec<_> = found.iter().map(|h| h.id).collect();
+        assert_eq!(ids, vec![early.id, late.id]);
+
+        backend.create_index("kind").await.unwrap();
+        let by_kind: Vec<EventHeader> = backend
+            .events_by_index("kind", &serde_json::json!("test.event"))
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(by_kind.len(), 3);
+        assert_eq!(backend.indexes().await.unwrap().len(), 2);
+
+        assert!(backend.drop_index("json_message").await.unwrap());
+        assert!(!backend.drop_index("json_message").await.unwrap());
+        assert!(backend.events_by_index("json_message", &serde_json::json!("alpha")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_from_replays_then_follows_live() {
+        use futures::StreamExt;
+
+        let backend = SqliteBackend::in_memory().await.unwrap();
+        let mut headers = Vec::new();
+        for i in 0..3 {
+            let event = TestEvent { message: "replay".to_string(), value: i };
+            let header = create_event_header(&[], Uuid::new_v4(), "test.replay".to_string(), &event).unwrap();
+            backend.commit(&header, &rmp_serde::to_vec_named(&event).unwrap()).await.unwrap();
+            headers.push(header);
+        }
+
+        let mut stream = backend.subscribe_from(ReplayFrom::Sequence(1)).await.unwrap();
+
+        // Committed after subscribing but before the history query runs, so it
+        // reaches both the history and the live stream; it must appear once.
+        let event = TestEvent { message: "live".to_string(), value: 3 };
+        let live = create_event_header(&[], Uuid::new_v4(), "test.replay".to_string(), &event).unwrap();
+        backend.commit(&live, &rmp_serde::to_vec_named(&event).unwrap()).await.unwrap();
+
+        assert_eq!(stream.next().await.unwrap().unwrap().id, headers[1].id);
+        assert_eq!(stream.next().await.unwrap().unwrap().id, headers[2].id);
+        assert_eq!(stream.next().await.unwrap().unwrap().id, live.id);
+
+        let event = TestEvent { message: "live".to_string(), value: 4 };
+        let later = create_event_header(&[], Uuid::new_v4(), "test.replay".to_string(), &event).unwrap();
+        backend.commit(&later, &rmp_serde::to_vec_named(&event).unwrap()).await.unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap().id, later.id);
+        drop(stream);
+
+        let mut stream = backend
+            .subscribe_from(ReplayFrom::Timestamp(headers[2].timestamp))
+            .await
+            .unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap().id, headers[2].id);
+        assert_eq!(stream.next().await.unwrap().unwrap().id, live.id);
+    }
+
+    #[tokio::test]
+    async fn test_payload_compression() {
+        let backend = SqliteBackend::in_memory().await.unwrap();
+        let event = TestEvent { message: "compress me ".repeat(200), value: 1 };
+        let header = create_event_header(&[], Uuid::new_v4(), "test.compress".to_string(), &event).unwrap();
+        let payload_bytes = rmp_serde::to_vec_named(&event).unwrap();
+
+        // Written before compression was enabled
+        backend.commit(&header, &payload_bytes).await.unwrap();
+
+        let backend = backend.with_compression(CompressionPolicy::new(Compression::Zstd));
+        let event2 = TestEvent { message: "compress me too ".repeat(200), value: 2 };
+        let header2 = create_event_header(&[], Uuid::new_v4(), "test.compress".to_string(), &event2).unwrap();
+        let payload_bytes2 = rmp_serde::to_vec_named(&event2).unwrap();
+        backend.commit(&header2, &payload_bytes2).await.unwrap();
+
+        // Reads are transparent regardless of how each payload is stored
+        assert_eq!(backend.payload_bytes(&header.digest).await.unwrap().unwrap(), payload_bytes);
+        assert_eq!(backend.payload_bytes(&header2.digest).await.unwrap().unwrap(), payload_bytes2);
+
+        // Migrate the legacy row; only it needs rewriting
+        assert_eq!(backend.compress_existing_payloads(10).await.unwrap(), 1);
+        assert_eq!(backend.compress_existing_payloads(10).await.unwrap(), 0);
+        assert_eq!(backend.payload_bytes(&header.digest).await.unwrap().unwrap(), payload_bytes);
+
+        let row = sqlx::query::<Sqlite>("SELECT length(payload_data) as len FROM event_payloads WHERE digest = ?")
+            .bind(&header.digest[..])
+            .fetch_one(&backend.pool)
+            .await
+            .unwrap();
+        assert!((row.get::<i64, _>("len") as usize) < payload_bytes.len());
+    }
+
+    #[tokio::test]
+    async fn test_query_by_kind_intent_and_range() {
+        use futures::TryStreamExt;
+
+        let backend = SqliteBackend::in_memory().await.unwrap();
+        let intent = Uuid::new_v4();
+        let start = chrono::Utc::now();
+
+        let mut headers = Vec::new();
+        for (i, kind) in ["test.a", "test.b", "test.a"].iter().enumerate() {
+            let event = TestEvent { message: "query".to_string(), value: i as i32 };
+            let header = create_event_header(&[], intent, kind.to_string(), &event).unwrap();
+            let payload_bytes = rmp_serde::to_vec_named(&event).unwrap();
+            backend.commit(&header, &payload_bytes).await.unwrap();
+            headers.push(header);
+        }
+
+        let by_kind: Vec<EventHeader> = backend
+            .events_by_kind("test.a")
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(by_kind, vec![headers[0].clone(), headers[2].clone()]);
+
+        let by_intent: Vec<EventHeader> = backend
+            .events_by_intent(&intent)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(by_intent.len(), 3);
+
+        let in_range: Vec<EventHeader> = backend
+            .events_in_range(start, chrono::Utc::now() + chrono::Duration::seconds(1))
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(in_range, headers);
+
+        let before: Vec<EventHeader> = backend
+            .events_in_range(start - chrono::Duration::hours(1), start)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert!(before.is_empty());
+    }
+
     #[tokio::test]
     async fn test_wal_basic_transaction() {
         let backend = SqliteBackend::in_memory().await.unwrap();
@@ -944,7 +3409,7 @@ mod tests {
             tx_id,
             WalOperation::CommitEvent {
                 header: header.clone(),
-                payload: payload_bytes.clone(),
+                payload: payload_bytes.clone().into(),
             },
         ).await.unwrap();
         
@@ -988,7 +3453,7 @@ mod tests {
             tx_id,
             WalOperation::CommitEvent {
                 header: header.clone(),
-                payload: payload_bytes.clone(),
+                payload: payload_bytes.clone().into(),
             },
         ).await.unwrap();
         
@@ -1061,7 +3526,7 @@ mod tests {
                     "test.sequence".to_string(),
                     &TestEvent { message: "seq".to_string(), value: 1 },
                 ).unwrap(),
-                payload: rmp_serde::to_vec_named(&TestEvent { message: "seq".to_string(), value: 1 }).unwrap(),
+                payload: rmp_serde::to_vec_named(&TestEvent { message: "seq".to_string(), value: 1 }).unwrap().into(),
             },
         ).await.unwrap();
         
@@ -1069,6 +3534,50 @@ mod tests {
         assert_eq!(backend.current_sequence().await.unwrap(), 2);
     }
 
+    #[tokio::test]
+    async fn test_wal_sequence_shared_across_handles() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("shared.db");
+        let first = SqliteBackend::open(&db_path).await.unwrap();
+        let second = SqliteBackend::open(&db_path).await.unwrap();
+
+        // Interleaved writers would collide on UNIQUE(sequence_number) if
+        // each handle counted in memory
+        for _ in 0..5 {
+            first.begin_transaction().await.unwrap();
+            second.begin_transaction().await.unwrap();
+        }
+        assert_eq!(first.current_sequence().await.unwrap(), 10);
+        assert_eq!(second.current_sequence().await.unwrap(), 10);
+
+        first.close().await;
+        second.close().await;
+        let reopened = SqliteBackend::open(&db_path).await.unwrap();
+        assert_eq!(reopened.current_sequence().await.unwrap(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_wal_health() {
+        let backend = SqliteBackend::in_memory().await.unwrap();
+        let open = backend.begin_transaction().await.unwrap();
+        let done = backend.begin_transaction().await.unwrap();
+        backend.commit_transaction(done).await.unwrap();
+
+        let health = backend.wal_health().await.unwrap();
+        assert_eq!(health.active_transactions, 1);
+        assert_eq!(health.entries_by_state.pending, 1);
+        assert_eq!(health.entries_by_state.committed, 2);
+        assert_eq!(health.current_sequence, 3);
+        assert!(health.bytes_used > 0);
+        assert!(health.oldest_pending_at.is_some());
+
+        backend.rollback_transaction(open).await.unwrap();
+        let health = backend.wal_health().await.unwrap();
+        assert_eq!(health.active_transactions, 0);
+        assert_eq!(health.entries_by_state.pending, 0);
+        assert_eq!(health.oldest_pending_at, None);
+    }
+
     #[tokio::test]
     async fn test_wal_checkpoint() {
         let backend = SqliteBackend::in_memory().await.unwrap();
@@ -1084,7 +3593,7 @@ mod tests {
                     "test.checkpoint".to_string(),
                     &TestEvent { message: "checkpoint".to_string(), value: 42 },
                 ).unwrap(),
-                payload: rmp_serde::to_vec_named(&TestEvent { message: "checkpoint".to_string(), value: 42 }).unwrap(),
+                payload: rmp_serde::to_vec_named(&TestEvent { message: "checkpoint".to_string(), value: 42 }).unwrap().into(),
             },
         ).await.unwrap();
         backend.commit_transaction(tx_id).await.unwrap();
@@ -1114,7 +3623,7 @@ mod tests {
                     "test.recovery".to_string(),
                     &TestEvent { message: "recovery".to_string(), value: 99 },
                 ).unwrap(),
-                payload: rmp_serde::to_vec_named(&TestEvent { message: "recovery".to_string(), value: 99 }).unwrap(),
+                payload: rmp_serde::to_vec_named(&TestEvent { message: "recovery".to_string(), value: 99 }).unwrap().into(),
             },
         ).await.unwrap();
         
@@ -1129,7 +3638,7 @@ mod tests {
                     "test.recovery2".to_string(),
                     &TestEvent { message: "recovery2".to_string(), value: 100 },
                 ).unwrap(),
-                payload: rmp_serde::to_vec_named(&TestEvent { message: "recovery2".to_string(), value: 100 }).unwrap(),
+                payload: rmp_serde::to_vec_named(&TestEvent { message: "recovery2".to_string(), value: 100 }).unwrap().into(),
             },
         ).await.unwrap();
         backend.commit_transaction(tx_id2).await.unwrap();
@@ -1146,4 +3655,352 @@ mod tests {
         // Should have committed the committed transaction
         assert_eq!(recovery_result.transactions_committed, 1);
     }
+
+    #[tokio::test]
+    async fn test_wal_quarantine_and_legacy_entries() {
+        let backend = SqliteBackend::in_memory().await.unwrap();
+        let log_raw = |sequence: i64, transaction_id: Uuid, bytes: Vec<u8>| {
+            sqlx::query::<Sqlite>(
+                r#"
+                INSERT INTO wal_entries
+                (id, transaction_id, sequence_number, timestamp, operation_data, state)
+                VALUES (?, ?, ?, ?, ?, ?)
+                "#
+            )
+            .bind(Uuid::new_v4())
+            .bind(transaction_id)
+            .bind(sequence)
+            .bind(Utc::now().to_rfc3339())
+            .bind(bytes)
+            .bind(SqliteBackend::state_to_int(WalEntryState::Committed))
+            .execute(&backend.pool)
+        };
+
+        // An entry logged before versioning still decodes
+        let legacy_tx = Uuid::new_v4();
+        let legacy = WalOperation::BeginTransaction { transaction_id: legacy_tx };
+        log_raw(100, legacy_tx, rmp_serde::to_vec_named(&legacy).unwrap()).await.unwrap();
+
+        // One written by a newer release is quarantined, not dropped
+        let future_tx = Uuid::new_v4();
+        let mut future = encode_wal_operation(&WalOperation::BeginTransaction {
+            transaction_id: future_tx,
+        })
+        .unwrap();
+        future[2..4].copy_from_slice(&(WAL_FORMAT_VERSION + 1).to_be_bytes());
+        log_raw(101, future_tx, future.clone()).await.unwrap();
+
+        let result = backend.recover().await.unwrap();
+        assert_eq!(result.entries_recovered, 1);
+        assert_eq!(result.entries_quarantined, 1);
+        assert_eq!(result.recovery_errors.len(), 1);
+
+        let quarantined = backend.quarantined_entries().await.unwrap();
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(quarantined[0].transaction_id, future_tx);
+        assert_eq!(quarantined[0].sequence, 101);
+        assert_eq!(quarantined[0].version, WAL_FORMAT_VERSION + 1);
+        assert_eq!(quarantined[0].data, future);
+        assert_eq!(quarantined[0].state, WalEntryState::Committed);
+
+        // The WAL itself is readable again
+        let entries = backend.wal_entries_after(0, 100).await.unwrap();
+        assert!(entries.iter().all(|e| e.transaction_id != future_tx));
+        assert!(entries.iter().any(|e| e.operation == legacy));
+
+        assert_eq!(backend.restore_quarantined().await.unwrap(), 0);
+        assert!(backend.purge_quarantined(quarantined[0].id).await.unwrap());
+        assert!(backend.quarantined_entries().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_wal_group_commit() {
+        let backend = SqliteBackend::in_memory()
+            .await
+            .unwrap()
+            .with_wal_durability(WalDurability::Batched {
+                max_entries: 4,
+                max_delay: std::time::Duration::from_secs(3600),
+            });
+        // Count rows directly, since wal_entry_count() flushes the buffer
+        async fn stored_entries(backend: &SqliteBackend) -> i64 {
+            let row = sqlx::query::<Sqlite>("SELECT COUNT(*) as count FROM wal_entries")
+                .fetch_one(&backend.pool)
+                .await
+                .unwrap();
+            row.get("count")
+        }
+
+        let event = TestEvent { message: "batched".to_string(), value: 1 };
+        let header = create_event_header(&[], Uuid::new_v4(), "test.batched".to_string(), &event).unwrap();
+        let tx_id = backend.begin_transaction().await.unwrap();
+        backend.commit_with_wal(tx_id, &header, &rmp_serde::to_vec_named(&event).unwrap()).await.unwrap();
+
+        // Entries of an open transaction stay buffered, without touching the sequence counter
+        assert_eq!(stored_entries(&backend).await, 0);
+        assert_eq!(backend.stored_sequence().await.unwrap(), 0);
+
+        // Committing writes the buffer, the commit record and the state update together
+        backend.commit_transaction(tx_id).await.unwrap();
+        assert_eq!(stored_entries(&backend).await, 3);
+        assert_eq!(backend.stored_sequence().await.unwrap(), 3);
+        let sequences: Vec<_> = backend.wal_entries_after(0, 10).await.unwrap().iter().map(|e| e.sequence).collect();
+        assert_eq!(sequences, vec![1, 2, 3]);
+        assert!(backend.header(&header.id).await.unwrap().is_some());
+
+        // Reaching max_entries flushes without a commit
+        let tx_ids = [
+            backend.begin_transaction().await.unwrap(),
+            backend.begin_transaction().await.unwrap(),
+            backend.begin_transaction().await.unwrap(),
+        ];
+        assert_eq!(stored_entries(&backend).await, 3);
+        backend.begin_transaction().await.unwrap();
+        assert_eq!(stored_entries(&backend).await, 7);
+
+        backend.rollback_transaction(tx_ids[0]).await.unwrap();
+        assert_eq!(stored_entries(&backend).await, 8);
+    }
+
+    #[tokio::test]
+    async fn test_wal_compaction() {
+        let backend = SqliteBackend::in_memory().await.unwrap();
+        let event = TestEvent { message: "compact".to_string(), value: 1 };
+        let header = create_event_header(&[], Uuid::new_v4(), "test.compact".to_string(), &event).unwrap();
+
+        let committed = backend.begin_transaction().await.unwrap();
+        backend.commit_with_wal(committed, &header, &rmp_serde::to_vec_named(&event).unwrap()).await.unwrap();
+        backend.commit_transaction(committed).await.unwrap();
+        let rolled_back = backend.begin_transaction().await.unwrap();
+        backend.rollback_transaction(rolled_back).await.unwrap();
+        let pending = backend.begin_transaction().await.unwrap();
+        assert_eq!(backend.wal_entry_count().await.unwrap(), 6);
+
+        // Committed entries are kept until checkpointed
+        let report = backend.compact_wal(u64::MAX).await.unwrap();
+        assert_eq!(report.transactions_removed, 1);
+        assert_eq!(report.rolled_back_entries, 2);
+        assert_eq!(backend.wal_entry_count().await.unwrap(), 4);
+
+        let sequence = backend.current_sequence().await.unwrap();
+        backend.checkpoint(sequence).await.unwrap();
+        let report = backend.compact_wal(sequence + 1).await.unwrap();
+        assert_eq!(report.checkpointed_entries, 3);
+        assert_eq!(backend.wal_entry_count().await.unwrap(), 1);
+
+        // The pending transaction is untouched
+        backend.commit_transaction(pending).await.unwrap();
+        assert_eq!(backend.wal_compaction_totals().entries_removed(), 5);
+
+        // Automatic compaction after checkpoints
+        let backend = backend.with_wal_compaction(WalCompactionPolicy::OnCheckpoint { retain: 0 });
+        backend.checkpoint(backend.current_sequence().await.unwrap()).await.unwrap();
+        assert_eq!(backend.wal_entry_count().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_stale_transactions_are_aborted() {
+        let backend = Arc::new(
+            SqliteBackend::in_memory()
+                .await
+                .unwrap()
+                .with_transaction_ttl(std::time::Duration::from_millis(10)),
+        );
+        let mut timeouts = backend.subscribe_transaction_timeouts();
+        let reaper = backend.spawn_transaction_reaper(std::time::Duration::from_millis(5));
+
+        let stale = backend.begin_transaction().await.unwrap();
+        assert_eq!(backend.storage_stats().await.unwrap().open_transactions, 1);
+        let notice = tokio::time::timeout(std::time::Duration::from_secs(5), timeouts.recv())
+            .await
+            .expect("reaper should abort the transaction")
+            .unwrap();
+        assert_eq!(notice.transaction_id, stale);
+        assert_eq!(notice.entries, 1);
+        assert!(backend.commit_transaction(stale).await.is_err());
+
+        let stats = backend.storage_stats().await.unwrap();
+        assert_eq!(stats.open_transactions, 0);
+        assert_eq!(stats.transactions_timed_out, 1);
+
+        // The reaper stops with the backend
+        drop(backend);
+        tokio::time::timeout(std::time::Duration::from_secs(5), reaper)
+            .await
+            .expect("reaper should stop")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_savepoints() {
+        let backend = SqliteBackend::in_memory().await.unwrap().with_wal_durability(WalDurability::batched());
+        let event = |value| TestEvent { message: "savepoint".to_string(), value };
+        let header = |value| create_event_header(&[], Uuid::new_v4(), "test.savepoint".to_string(), &event(value)).unwrap();
+        let payload = |value| rmp_serde::to_vec_named(&event(value)).unwrap();
+        let (kept, discarded, retried) = (header(1), header(2), header(3));
+
+        let tx_id = backend.begin_transaction().await.unwrap();
+        backend.commit_with_wal(tx_id, &kept, &payload(1)).await.unwrap();
+        let savepoint = backend.savepoint(tx_id).await.unwrap();
+        backend.commit_with_wal(tx_id, &discarded, &payload(2)).await.unwrap();
+        backend.rollback_to_savepoint(tx_id, savepoint).await.unwrap();
+        backend.commit_with_wal(tx_id, &retried, &payload(3)).await.unwrap();
+        assert!(backend.rollback_to_savepoint(tx_id, Uuid::new_v4()).await.is_err());
+        backend.commit_transaction(tx_id).await.unwrap();
+
+        assert!(backend.header(&kept.id).await.unwrap().is_some());
+        assert!(backend.header(&discarded.id).await.unwrap().is_none());
+        assert!(backend.header(&retried.id).await.unwrap().is_some());
+
+        // The discarded entry stays rolled back in the persisted log
+        let entries = backend.wal_entries_after(0, usize::MAX).await.unwrap();
+        let states: Vec<_> = entries
+            .iter()
+            .filter(|entry| matches!(entry.operation, WalOperation::CommitEvent { .. }))
+            .map(|entry| entry.state.clone())
+            .collect();
+        assert_eq!(states, vec![WalEntryState::Committed, WalEntryState::RolledBack, WalEntryState::Committed]);
+    }
+
+    #[tokio::test]
+    async fn test_wal_replication_to_follower() {
+        let leader = SqliteBackend::in_memory().await.unwrap().with_wal_durability(WalDurability::batched());
+        let follower = SqliteBackend::in_memory().await.unwrap();
+        let event = TestEvent { message: "replicate".to_string(), value: 7 };
+        let header = create_event_header(&[], Uuid::new_v4(), "test.replicate".to_string(), &event).unwrap();
+
+        let tx_id = leader.begin_transaction().await.unwrap();
+        leader.commit_with_wal(tx_id, &header, &rmp_serde::to_vec_named(&event).unwrap()).await.unwrap();
+        let replicator = WalReplicator::new(&leader, &follower);
+        assert_eq!(replicator.poll().await.unwrap().transactions_applied, 0);
+
+        leader.commit_transaction(tx_id).await.unwrap();
+        let batch = replicator.catch_up().await.unwrap();
+        assert_eq!(batch.events_applied, 1);
+        assert_eq!(batch.resume_token.sequence, leader.current_sequence().await.unwrap());
+        assert_eq!(follower.header(&header.id).await.unwrap(), Some(header));
+        assert!(replicator.lag().await.unwrap().is_caught_up());
+        assert!(replicator.verify_consistency().await.unwrap().is_consistent());
+    }
+
+    toka_store_conformance::wal_conformance_tests!(
+        wal_conformance_batched,
+        SqliteBackend::in_memory().await.unwrap().with_wal_durability(WalDurability::batched())
+    );
+
+    toka_store_conformance::storage_conformance_tests!(storage_conformance, SqliteBackend::in_memory().await.unwrap());
+    toka_store_conformance::wal_conformance_tests!(wal_conformance, SqliteBackend::in_memory().await.unwrap());
+    toka_store_conformance::subscription_conformance_tests!(subscription_conformance, SqliteBackend::in_memory().await.unwrap());
+    toka_store_conformance::dedup_conformance_tests!(dedup_conformance, SqliteBackend::in_memory().await.unwrap());
+
+    #[tokio::test]
+    async fn test_recovery_observer_after_restart() {
+        #[derive(Default)]
+        struct Recorder {
+            replayed: std::sync::Mutex<Vec<(TransactionId, usize, usize)>>,
+            rolled_back: std::sync::Mutex<Vec<(TransactionId, usize)>>,
+        }
+
+        impl RecoveryObserver for Recorder {
+            fn before_replay(&self, entry: &WalEntry) -> ReplayDecision {
+                match &entry.operation {
+                    WalOperation::CommitEvent { header, .. } if header.kind == "test.secret" => ReplayDecision::Skip,
+                    _ => ReplayDecision::Apply,
+                }
+            }
+
+            fn on_transaction_replayed(&self, transaction_id: TransactionId, applied: usize, skipped: usize) {
+                self.replayed.lock().unwrap().push((transaction_id, applied, skipped));
+            }
+
+            fn on_rollback(&self, transaction_id: TransactionId, entries: usize) {
+                self.rolled_back.lock().unwrap().push((transaction_id, entries));
+            }
+        }
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("observed.db");
+        let event = TestEvent { message: "observed".to_string(), value: 1 };
+        let payload = rmp_serde::to_vec_named(&event).unwrap();
+        let secret = create_event_header(&[], Uuid::new_v4(), "test.secret".to_string(), &event).unwrap();
+        let pending = create_event_header(&[], Uuid::new_v4(), "test.pending".to_string(), &event).unwrap();
+
+        let (committed, open) = {
+            let backend = SqliteBackend::open(&path).await.unwrap();
+            let committed = backend.begin_transaction().await.unwrap();
+            backend.write_entry(committed, WalOperation::CommitEvent { header: secret.clone(), payload: payload.clone().into() }).await.unwrap();
+            backend.commit_transaction(committed).await.unwrap();
+            let open = backend.begin_transaction().await.unwrap();
+            backend.commit_with_wal(open, &pending, &payload).await.unwrap();
+            backend.close().await;
+            (committed, open)
+        };
+
+        let recorder = Arc::new(Recorder::default());
+        let backend = SqliteBackend::open(&path).await.unwrap().with_recovery_observer(recorder.clone());
+        let result = backend.recover().await.unwrap();
+        assert!(result.recovery_errors.is_empty());
+        assert!(backend.header(&pending.id).await.unwrap().is_none());
+        assert_eq!(*recorder.replayed.lock().unwrap(), vec![(committed, 0, 1)]);
+        // Begin and the logged event
+        assert_eq!(*recorder.rolled_back.lock().unwrap(), vec![(open, 2)]);
+    }
+
+    #[tokio::test]
+    async fn test_conformance_reopen_preserves_committed() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("conformance.db");
+        toka_store_conformance::recovery::reopen_preserves_committed(|| SqliteBackend::open(&path))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_open_with_durability_levels() {
+        let levels = [
+            (DurabilityLevel::Full, 2),
+            (DurabilityLevel::Normal, 1),
+            (DurabilityLevel::Relaxed, 0),
+        ];
+        for (level, synchronous) in levels {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let path = temp_dir.path().join("durability.db");
+            let options = SqliteOptions::default().with_durability_level(level);
+
+            let event = TestEvent { message: "durable".to_string(), value: 1 };
+            let header = create_event_header(&[], Uuid::new_v4(), "test.durable".to_string(), &event).unwrap();
+            {
+                let backend = SqliteBackend::open_with_options(&path, options).await.unwrap();
+                let row = sqlx::query::<Sqlite>("PRAGMA synchronous")
+                    .fetch_one(&backend.pool)
+                    .await
+                    .unwrap();
+                assert_eq!(row.get::<i64, _>(0), synchronous, "{:?}", level);
+                backend.commit(&header, &rmp_serde::to_vec_named(&event).unwrap()).await.unwrap();
+                backend.close().await;
+            }
+
+            // A clean shutdown keeps commits at every level
+            let reopened = SqliteBackend::open(&path).await.unwrap();
+            assert_eq!(reopened.header(&header.id).await.unwrap(), Some(header), "{:?}", level);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_conformance_crash_recovery() {
+        use toka_store_conformance::crash;
+
+        for durability in [WalDurability::Immediate, WalDurability::batched()] {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let path = temp_dir.path().join("crash.db");
+            let report = crash::simulate(
+                &crash::savepoint_script(),
+                || SqliteBackend::open_with_durability(&path, durability),
+                crash::kill_backend,
+            )
+            .await
+            .unwrap_or_else(|e| panic!("{:?}: {:#}", durability, e));
+            assert_eq!(report.crashes, 6);
+        }
+    }
 }
\ No newline at end of file
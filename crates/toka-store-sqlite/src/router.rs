@@ -0,0 +1,294 @@
+//! Routing reads to read-only replicas.
+//!
+//! SQLite serializes writers, and in read-heavy analytical workloads the
+//! writer's pool also spends its connections on queries. A [`SqliteRouter`]
+//! sends commits and WAL operations to a primary [`SqliteBackend`] and
+//! spreads `header`, `payload_bytes` and query traffic round-robin over
+//! read-only replicas. A replica is either a separate read-only pool on the
+//! primary's own file, which sees every commit, or a follower file kept up to
+//! date by external replication, which may lag behind.
+//!
+//! Point lookups that miss on a replica are retried on the primary by
+//! default, so an event is readable right after it was committed through the
+//! router. Scans are served by the replica alone and may not include the
+//! latest events of a lagging follower. Replays need the primary's live
+//! broadcast and always go to the primary.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use toka_store_core::{
+    CausalDigest, EventHeader, EventHeaderStream, EventId, IntentId, QueryableStorage, ReplayFrom,
+    ReplayableStorage, SavepointId, SequenceNumber, StorageBackend, TransactionId, WalHealth,
+    WalOperation, WalRecoveryResult, WriteAheadLog,
+};
+
+use crate::SqliteBackend;
+
+/// Number of reads served by each side of a [`SqliteRouter`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RoutingStats {
+    /// Reads served by a replica
+    pub replica_reads: u64,
+    /// Reads served by the primary, including fallbacks
+    pub primary_reads: u64,
+    /// Point lookups retried on the primary after missing on a replica
+    pub fallbacks: u64,
+}
+
+/// Storage backend writing to a primary and reading from replicas.
+#[derive(Debug)]
+pub struct SqliteRouter {
+    primary: Arc<SqliteBackend>,
+    replicas: Vec<Arc<SqliteBackend>>,
+    fallback_to_primary: bool,
+    next_replica: AtomicUsize,
+    replica_reads: AtomicU64,
+    primary_reads: AtomicU64,
+    fallbacks: AtomicU64,
+}
+
+impl SqliteRouter {
+    /// Route everything to `primary` until replicas are added.
+    pub fn new(primary: Arc<SqliteBackend>) -> Self {
+        Self {
+            primary,
+            replicas: Vec::new(),
+            fallback_to_primary: true,
+            next_replica: AtomicUsize::new(0),
+            replica_reads: AtomicU64::new(0),
+            primary_reads: AtomicU64::new(0),
+            fallbacks: AtomicU64::new(0),
+        }
+    }
+
+    /// Open the database at `primary` for writing and each of `replicas`
+    /// read-only.
+    ///
+    /// Passing the primary's own path as a replica adds a reader pool on the
+    /// same file.
+    pub async fn open<P, R>(primary: P, replicas: &[R]) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        R: AsRef<Path>,
+    {
+        let mut router = Self::new(Arc::new(SqliteBackend::open(primary).await?));
+        for replica in replicas {
+            router = router.with_replica(Arc::new(SqliteBackend::open_read_only(replica).await?))?;
+        }
+        Ok(router)
+    }
+
+    /// Also serve reads from `replica`, which must be read-only.
+    pub fn with_replica(mut self, replica: Arc<SqliteBackend>) -> Result<Self> {
+        if !replica.is_read_only() {
+            anyhow::bail!("Replicas must be opened read-only");
+        }
+        self.replicas.push(replica);
+        Ok(self)
+    }
+
+    /// Whether point lookups missing on a replica are retried on the
+    /// primary (the default).
+    pub fn with_fallback_to_primary(mut self, fallback: bool) -> Self {
+        self.fallback_to_primary = fallback;
+        self
+    }
+
+    /// The backend receiving writes.
+    pub fn primary(&self) -> &Arc<SqliteBackend> {
+        &self.primary
+    }
+
+    /// The backends serving reads.
+    pub fn replicas(&self) -> &[Arc<SqliteBackend>] {
+        &self.replicas
+    }
+
+    /// Reads served so far by each side.
+    pub fn stats(&self) -> RoutingStats {
+        RoutingStats {
+            replica_reads: self.replica_reads.load(Ordering::Relaxed),
+            primary_reads: self.primary_reads.load(Ordering::Relaxed),
+            fallbacks: self.fallbacks.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Backend serving the next read, and whether it is a replica.
+    fn reader(&self) -> (&SqliteBackend, bool) {
+        if self.replicas.is_empty() {
+            self.primary_reads.fetch_add(1, Ordering::Relaxed);
+            return (&self.primary, false);
+        }
+        let index = self.next_replica.fetch_add(1, Ordering::Relaxed) % self.replicas.len();
+        self.replica_reads.fetch_add(1, Ordering::Relaxed);
+        (&self.replicas[index], true)
+    }
+
+    /// Whether a miss on a replica should be retried on the primary.
+    fn falls_back(&self, from_replica: bool) -> bool {
+        if from_replica && self.fallback_to_primary {
+            self.fallbacks.fetch_add(1, Ordering::Relaxed);
+            self.primary_reads.fetch_add(1, Ordering::Relaxed);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[async_trait]
+impl StorageBackend for SqliteRouter {
+    async fn commit(&self, header: &EventHeader, payload: &[u8]) -> Result<()> {
+        self.primary.commit(header, payload).await
+    }
+
+    async fn header(&self, id: &EventId) -> Result<Option<EventHeader>> {
+        let (reader, replica) = self.reader();
+        match reader.header(id).await? {
+            None if self.falls_back(replica) => self.primary.header(id).await,
+            header => Ok(header),
+        }
+    }
+
+    async fn payload_bytes(&self, digest: &CausalDigest) -> Result<Option<Vec<u8>>> {
+        let (reader, replica) = self.reader();
+        match reader.payload_bytes(digest).await? {
+            None if self.falls_back(replica) => self.primary.payload_bytes(digest).await,
+            payload => Ok(payload),
+        }
+    }
+
+    async fn payloads_bulk(&self, digests: &[CausalDigest]) -> Result<HashMap<CausalDigest, Vec<u8>>> {
+        let (reader, replica) = self.reader();
+        let mut payloads = reader.payloads_bulk(digests).await?;
+        let missing: Vec<CausalDigest> = digests
+            .iter()
+            .filter(|digest| !payloads.contains_key(*digest))
+            .copied()
+            .collect();
+        if !missing.is_empty() && self.falls_back(replica) {
+            payloads.extend(self.primary.payloads_bulk(&missing).await?);
+        }
+        Ok(payloads)
+    }
+}
+
+#[async_trait]
+impl QueryableStorage for SqliteRouter {
+    async fn events_by_kind(&self, kind: &str) -> Result<EventHeaderStream<'_>> {
+        self.reader().0.events_by_kind(kind).await
+    }
+
+    async fn events_in_range(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<EventHeaderStream<'_>> {
+        self.reader().0.events_in_range(from, to).await
+    }
+
+    async fn events_by_intent(&self, intent: &IntentId) -> Result<EventHeaderStream<'_>> {
+        self.reader().0.events_by_intent(intent).await
+    }
+}
+
+#[async_trait]
+impl ReplayableStorage for SqliteRouter {
+    async fn subscribe_from(&self, from: ReplayFrom) -> Result<EventHeaderStream<'_>> {
+        self.primary.subscribe_from(from).await
+    }
+}
+
+#[async_trait]
+impl WriteAheadLog for SqliteRouter {
+    async fn begin_transaction(&self) -> Result<TransactionId> {
+        self.primary.begin_transaction().await
+    }
+
+    async fn write_entry(&self, transaction_id: TransactionId, operation: WalOperation) -> Result<()> {
+        self.primary.write_entry(transaction_id, operation).await
+    }
+
+    async fn commit_transaction(&self, transaction_id: TransactionId) -> Result<()> {
+        self.primary.commit_transaction(transaction_id).await
+    }
+
+    async fn rollback_transaction(&self, transaction_id: TransactionId) -> Result<()> {
+        self.primary.rollback_transaction(transaction_id).await
+    }
+
+    async fn savepoint(&self, transaction_id: TransactionId) -> Result<SavepointId> {
+        self.primary.savepoint(transaction_id).await
+    }
+
+    async fn rollback_to_savepoint(&self, transaction_id: TransactionId, savepoint_id: SavepointId) -> Result<()> {
+        self.primary.rollback_to_savepoint(transaction_id, savepoint_id).await
+    }
+
+    async fn recover(&self) -> Result<WalRecoveryResult> {
+        self.primary.recover().await
+    }
+
+    async fn checkpoint(&self, sequence: SequenceNumber) -> Result<()> {
+        self.primary.checkpoint(sequence).await
+    }
+
+    async fn current_sequence(&self) -> Result<SequenceNumber> {
+        self.primary.current_sequence().await
+    }
+
+    async fn wal_health(&self) -> Result<WalHealth> {
+        self.primary.wal_health().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::TryStreamExt;
+    use toka_store_core::create_event_header;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_reads_go_to_replicas_and_writes_to_primary() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let primary_path = temp_dir.path().join("primary.db");
+        let follower_path = temp_dir.path().join("follower.db");
+        // A follower that has not caught up with the primary yet
+        SqliteBackend::open(&follower_path).await.unwrap().close().await;
+        SqliteBackend::open(&primary_path).await.unwrap().close().await;
+
+        let router = SqliteRouter::open(&primary_path, &[&primary_path, &follower_path])
+            .await
+            .unwrap();
+        assert!(SqliteRouter::new(router.primary().clone())
+            .with_replica(Arc::new(SqliteBackend::in_memory().await.unwrap()))
+            .is_err());
+
+        let intent = Uuid::new_v4();
+        let header = create_event_header(&[], intent, "test.routed".to_string(), &"payload".to_string()).unwrap();
+        let payload = rmp_serde::to_vec_named(&"payload".to_string()).unwrap();
+        router.commit(&header, &payload).await.unwrap();
+        assert_eq!(router.primary().event_count().await.unwrap(), 1);
+
+        // Same-file replica, then the lagging follower with a fallback
+        assert_eq!(router.header(&header.id).await.unwrap(), Some(header.clone()));
+        assert_eq!(router.header(&header.id).await.unwrap(), Some(header.clone()));
+        assert_eq!(
+            router.stats(),
+            RoutingStats { replica_reads: 2, primary_reads: 1, fallbacks: 1 }
+        );
+
+        let strict = SqliteRouter::new(router.primary().clone())
+            .with_replica(Arc::new(SqliteBackend::open_read_only(&follower_path).await.unwrap()))
+            .unwrap()
+            .with_fallback_to_primary(false);
+        assert!(strict.payload_bytes(&header.digest).await.unwrap().is_none());
+        let found: Vec<EventHeader> = strict.events_by_intent(&intent).await.unwrap().try_collect().await.unwrap();
+        assert!(found.is_empty());
+        assert_eq!(strict.stats().replica_reads, 2);
+    }
+}
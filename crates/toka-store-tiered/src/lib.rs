@@ -0,0 +1,384 @@
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+
+//! **toka-store-tiered** – Hot/cold tiered storage for Toka OS.
+//!
+//! [`TieredBackend`] keeps recent events in a hot backend (SQLite by default)
+//! and moves events older than a configurable threshold to an object store
+//! such as S3, GCS or Azure Blob Storage via the `object_store` crate.
+//! Reads are transparent: header and payload lookups fall back to the cold
+//! tier when the hot tier no longer has the data.
+//!
+//! Archived objects are laid out under a configurable prefix:
+//!
+//! - `<prefix>/headers/<event id>` – MessagePack-encoded [`EventHeader`]
+//! - `<prefix>/payloads/<hex digest>` – raw payload bytes, deduplicated by digest
+//!
+//! Archival uploads before it deletes, so a crash in between leaves the event
+//! in both tiers rather than in neither.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, TryStreamExt};
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectStore, PutPayload};
+
+use toka_store_core::{
+    CausalDigest, CompactableStorage, EventHeader, EventHeaderStream, EventId, IntentId,
+    QueryableStorage, StorageBackend,
+};
+use toka_store_sqlite::SqliteBackend;
+
+/// Default number of events moved per archival pass.
+pub const DEFAULT_ARCHIVE_BATCH: usize = 1000;
+
+/// Tiered backend with the SQLite driver as hot tier.
+pub type SqliteTieredBackend = TieredBackend<SqliteBackend>;
+
+//─────────────────────────────
+//  Archive policy
+//─────────────────────────────
+
+/// Controls when events leave the hot tier.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArchivePolicy {
+    /// Events older than this are moved to the cold tier
+    pub age_threshold: Duration,
+    /// Maximum number of events moved per archival pass
+    pub batch_size: usize,
+    /// Interval between passes of the background archiver
+    pub interval: Duration,
+}
+
+impl ArchivePolicy {
+    /// Archive events older than `age_threshold`, checking every hour.
+    pub fn new(age_threshold: Duration) -> Self {
+        Self {
+            age_threshold,
+            batch_size: DEFAULT_ARCHIVE_BATCH,
+            interval: Duration::from_secs(3600),
+        }
+    }
+
+    /// Set the number of events moved per pass.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Set the interval of the background archiver.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Events committed before the returned instant are eligible at `now`.
+    pub fn cutoff(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        chrono::Duration::from_std(self.age_threshold)
+            .ok()
+            .and_then(|age| now.checked_sub_signed(age))
+            .unwrap_or(DateTime::<Utc>::MIN_UTC)
+    }
+}
+
+/// Summary of one archival pass.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArchiveReport {
+    /// Events moved to the cold tier and removed from the hot tier
+    pub events_archived: usize,
+    /// Payloads uploaded (payloads already in the cold tier are not counted)
+    pub payloads_archived: usize,
+    /// Bytes of payload data uploaded
+    pub bytes_archived: u64,
+}
+
+//─────────────────────────────
+//  Tiered backend
+//─────────────────────────────
+
+/// Storage backend combining a hot tier with an object-store cold tier.
+pub struct TieredBackend<H> {
+    hot: H,
+    cold: Arc<dyn ObjectStore>,
+    prefix: ObjectPath,
+    policy: ArchivePolicy,
+}
+
+impl<H> TieredBackend<H>
+where
+    H: QueryableStorage + CompactableStorage,
+{
+    /// Combine `hot` with the object store `cold`, archiving under `prefix`.
+    pub fn new(hot: H, cold: Arc<dyn ObjectStore>, prefix: impl Into<ObjectPath>, policy: ArchivePolicy) -> Self {
+        Self {
+            hot,
+            cold,
+            prefix: prefix.into(),
+            policy,
+        }
+    }
+
+    /// The hot tier.
+    pub fn hot(&self) -> &H {
+        &self.hot
+    }
+
+    /// The archive policy.
+    pub fn policy(&self) -> &ArchivePolicy {
+        &self.policy
+    }
+
+    /// Move events older than the policy threshold at `now` to the cold tier.
+    ///
+    /// At most [`ArchivePolicy::batch_size`] events are moved per call, oldest
+    /// first; call repeatedly (or use [`spawn_archiver`](Self::spawn_archiver))
+    /// to drain a backlog.
+    pub async fn archive(&self, now: DateTime<Utc>) -> Result<ArchiveReport> {
+        let mut report = ArchiveReport::default();
+        let candidates: Vec<EventHeader> = self
+            .hot
+            .events_in_range(DateTime::<Utc>::MIN_UTC, self.policy.cutoff(now))
+            .await?
+            .take(self.policy.batch_size)
+            .try_collect()
+            .await?;
+        if candidates.is_empty() {
+            return Ok(report);
+        }
+
+        for header in &candidates {
+            let payload_path = self.payload_path(&header.digest);
+            if !self.cold_exists(&payload_path).await? {
+                let payload = self
+                    .hot
+                    .payload_bytes(&header.digest)
+                    .await?
+                    .with_context(|| format!("payload of event {} missing from hot tier", header.id))?;
+                report.bytes_archived += payload.len() as u64;
+                report.payloads_archived += 1;
+                self.cold.put(&payload_path, PutPayload::from(payload)).await?;
+            }
+            let encoded = rmp_serde::to_vec_named(header)?;
+            self.cold.put(&self.header_path(&header.id), PutPayload::from(encoded)).await?;
+        }
+
+        // Only remove from the hot tier once everything is safely uploaded
+        let ids: Vec<EventId> = candidates.iter().map(|h| h.id).collect();
+        report.events_archived = self.hot.remove_events(&ids).await?;
+        Ok(report)
+    }
+
+    /// Run [`archive`](Self::archive) every [`ArchivePolicy::interval`].
+    pub fn spawn_archiver(self: Arc<Self>) -> tokio::task::JoinHandle<()>
+    where
+        H: 'static,
+    {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.policy.interval);
+            loop {
+                interval.tick().await;
+                // Drain the backlog in batches before waiting again
+                loop {
+                    match self.archive(Utc::now()).await {
+                        Ok(report) if report.events_archived >= self.policy.batch_size => continue,
+                        Ok(report) => {
+                            if report.events_archived > 0 {
+                                tracing::info!("Archived {} events to cold storage", report.events_archived);
+                            }
+                            break;
+                        }
+                        Err(e) => {
+                            tracing::warn!("Archival pass failed: {}", e);
+                            break;
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    fn header_path(&self, id: &EventId) -> ObjectPath {
+        self.prefix.child("headers").child(id.to_string())
+    }
+
+    fn payload_path(&self, digest: &CausalDigest) -> ObjectPath {
+        let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+        self.prefix.child("payloads").child(hex)
+    }
+
+    async fn cold_get(&self, path: &ObjectPath) -> Result<Option<Vec<u8>>> {
+        match self.cold.get(path).await {
+            Ok(result) => Ok(Some(result.bytes().await?.to_vec())),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn cold_exists(&self, path: &ObjectPath) -> Result<bool> {
+        match self.cold.head(path).await {
+            Ok(_) => Ok(true),
+            Err(object_store::Error::NotFound { .. }) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Archived headers matching `keep`, oldest first.
+    ///
+    /// The cold tier has no secondary indexes, so this lists and decodes
+    /// every archived header.
+    async fn cold_headers(&self, keep: impl Fn(&EventHeader) -> bool) -> Result<Vec<EventHeader>> {
+        let prefix = self.prefix.child("headers");
+        let objects: Vec<_> = self.cold.list(Some(&prefix)).try_collect().await?;
+
+        let mut headers = Vec::new();
+        for object in objects {
+            if let Some(bytes) = self.cold_get(&object.location).await? {
+                let header: EventHeader = rmp_serde::from_slice(&bytes)?;
+                if keep(&header) {
+                    headers.push(header);
+                }
+            }
+        }
+        headers.sort_by_key(|h| h.timestamp);
+        Ok(headers)
+    }
+
+    /// Archived events precede everything still in the hot tier.
+    async fn with_cold<'a>(
+        &'a self,
+        keep: impl Fn(&EventHeader) -> bool,
+        hot: EventHeaderStream<'a>,
+    ) -> Result<EventHeaderStream<'a>> {
+        let cold = self.cold_headers(keep).await?;
+        Ok(Box::pin(futures::stream::iter(cold.into_iter().map(Ok)).chain(hot)))
+    }
+}
+
+#[async_trait]
+impl<H> StorageBackend for TieredBackend<H>
+where
+    H: QueryableStorage + CompactableStorage,
+{
+    async fn commit(&self, header: &EventHeader, payload: &[u8]) -> Result<()> {
+        self.hot.commit(header, payload).await
+    }
+
+    async fn header(&self, id: &EventId) -> Result<Option<EventHeader>> {
+        if let Some(header) = self.hot.header(id).await? {
+            return Ok(Some(header));
+        }
+        match self.cold_get(&self.header_path(id)).await? {
+            Some(bytes) => Ok(Some(rmp_serde::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn payload_bytes(&self, digest: &CausalDigest) -> Result<Option<Vec<u8>>> {
+        if let Some(payload) = self.hot.payload_bytes(digest).await? {
+            return Ok(Some(payload));
+        }
+        self.cold_get(&self.payload_path(digest)).await
+    }
+}
+
+/// Queries span both tiers. Scanning the cold tier lists every archived
+/// header, so range queries skip it when the range starts after the archive
+/// cutoff.
+#[async_trait]
+impl<H> QueryableStorage for TieredBackend<H>
+where
+    H: QueryableStorage + CompactableStorage,
+{
+    async fn events_by_kind(&self, kind: &str) -> Result<EventHeaderStream<'_>> {
+        let hot = self.hot.events_by_kind(kind).await?;
+        self.with_cold(|h| h.kind == kind, hot).await
+    }
+
+    async fn events_in_range(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<EventHeaderStream<'_>> {
+        let hot = self.hot.events_in_range(from, to).await?;
+        if from >= self.policy.cutoff(Utc::now()) {
+            return Ok(hot);
+        }
+        self.with_cold(|h| h.timestamp >= from && h.timestamp < to, hot).await
+    }
+
+    async fn events_by_intent(&self, intent: &IntentId) -> Result<EventHeaderStream<'_>> {
+        let hot = self.hot.events_by_intent(intent).await?;
+        self.with_cold(|h| h.intent == *intent, hot).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object_store::memory::InMemory;
+    use serde::{Deserialize, Serialize};
+    use toka_store_core::create_event_header;
+    use uuid::Uuid;
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    struct TestEvent {
+        value: i32,
+    }
+
+    async fn commit_at(backend: &SqliteTieredBackend, value: i32, age: chrono::Duration) -> EventHeader {
+        let event = TestEvent { value };
+        let mut header = create_event_header(&[], Uuid::new_v4(), "test.tiered".to_string(), &event).unwrap();
+        header.timestamp = Utc::now() - age;
+        backend.commit(&header, &rmp_serde::to_vec_named(&event).unwrap()).await.unwrap();
+        header
+    }
+
+    #[tokio::test]
+    async fn test_archive_and_transparent_reads() {
+        let backend = TieredBackend::new(
+            SqliteBackend::in_memory().await.unwrap(),
+            Arc::new(InMemory::new()),
+            "archive",
+            ArchivePolicy::new(Duration::from_secs(24 * 3600)),
+        );
+
+        let old = commit_at(&backend, 1, chrono::Duration::days(3)).await;
+        let recent = commit_at(&backend, 2, chrono::Duration::minutes(5)).await;
+
+        let report = backend.archive(Utc::now()).await.unwrap();
+        assert_eq!(report.events_archived, 1);
+        assert_eq!(report.payloads_archived, 1);
+
+        // Gone from the hot tier, still readable through the tiered backend
+        assert!(backend.hot().header(&old.id).await.unwrap().is_none());
+        assert!(backend.hot().payload_bytes(&old.digest).await.unwrap().is_none());
+        assert_eq!(backend.header(&old.id).await.unwrap(), Some(old.clone()));
+        let payload = backend.payload_bytes(&old.digest).await.unwrap().unwrap();
+        assert_eq!(rmp_serde::from_slice::<TestEvent>(&payload).unwrap(), TestEvent { value: 1 });
+
+        // Queries return archived events first
+        let all: Vec<EventHeader> = backend
+            .events_by_kind("test.tiered")
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(all.iter().map(|h| h.id).collect::<Vec<_>>(), vec![old.id, recent.id]);
+
+        // Nothing left to archive
+        assert_eq!(backend.archive(Utc::now()).await.unwrap(), ArchiveReport::default());
+    }
+
+    #[test]
+    fn test_cutoff() {
+        let now = Utc::now();
+        let policy = ArchivePolicy::new(Duration::from_secs(60));
+        assert_eq!(policy.cutoff(now), now - chrono::Duration::seconds(60));
+        assert_eq!(ArchivePolicy::new(Duration::MAX).cutoff(now), DateTime::<Utc>::MIN_UTC);
+    }
+}
@@ -80,7 +80,7 @@ impl TestingEnvironment {
         let event_bus = Arc::new(toka_bus_core::InMemoryBus::new(1024));
         let kernel = toka_kernel::Kernel::new(world_state, auth, event_bus);
         let runtime_kernel = toka_runtime::RuntimeKernel::new(kernel);
-        let runtime = RuntimeManager::new(runtime_kernel).await?;
+        let runtime = RuntimeManager::with_default_engines(runtime_kernel).await?;
 
         let mut env = Self {
             runtime,
@@ -156,6 +156,7 @@ impl TestingEnvironment {
             origin,
             capability: token,
             op: Operation::SpawnSubAgent { parent: origin, spec },
+            correlation_id: None,
         };
 
         let event = self.runtime.submit(message).await?;
@@ -183,12 +184,13 @@ impl TestingEnvironment {
             _ => EntityId(0),           // Default to system entity
         };
 
-        let task = TaskSpec { description: description.to_string() };
+        let task = TaskSpec { description: description.to_string(), correlation_id: None };
 
         let message = Message {
             origin,
             capability: token,
             op: Operation::ScheduleAgentTask { agent: *agent_id, task },
+            correlation_id: None,
         };
 
         let event = self.runtime.submit(message).await?;
@@ -18,6 +18,9 @@ use tokio::sync::RwLock;
 use tracing::info;
 
 use crate::errors::ToolError;
+use crate::trust::{ToolProvenance, ToolTrust, TrustPolicy};
+use toka_runtime::flags::{tool_flag, FeatureFlags, FlagContext};
+use toka_runtime::output::OutputLimiter;
 
 // Re-export so downstream modules can `use crate::core::Tool`.
 pub use toka_types::traits::{Tool, ToolParams};
@@ -56,12 +59,20 @@ pub use toka_types::{ToolMetadata, ToolResult};
 /// ```
 pub struct ToolRegistry {
     tools: Arc<RwLock<HashMap<String, Arc<dyn Tool + Send + Sync>>>>,
+    trust: Arc<RwLock<HashMap<String, ToolTrust>>>,
+    trust_policy: RwLock<Arc<TrustPolicy>>,
+    feature_flags: Option<Arc<FeatureFlags>>,
+    output_limiter: Option<Arc<OutputLimiter>>,
 }
 
 impl Default for ToolRegistry {
     fn default() -> Self {
         Self {
             tools: Arc::new(RwLock::new(HashMap::new())),
+            trust: Arc::new(RwLock::new(HashMap::new())),
+            trust_policy: RwLock::new(Arc::new(TrustPolicy::default())),
+            feature_flags: None,
+            output_limiter: None,
         }
     }
 }
@@ -86,6 +97,44 @@ impl ToolRegistry {
         Self::default()
     }
 
+    /// Gate tools behind feature flags
+    ///
+    /// Each execution first evaluates the tool's flag (`tool.<name>`, see
+    /// [`tool_flag`]); tools without a defined flag stay enabled, so new
+    /// tools can be rolled out gradually and any tool killed instantly.
+    pub fn with_feature_flags(mut self, flags: Arc<FeatureFlags>) -> Self {
+        self.feature_flags = Some(flags);
+        self
+    }
+
+    /// Cap tool output
+    ///
+    /// Outputs over the limit for the tool's name are truncated with a
+    /// marker; with a blob store configured on the limiter the complete
+    /// output is kept there and the marker names the blob.
+    pub fn with_output_limiter(mut self, limiter: Arc<OutputLimiter>) -> Self {
+        self.output_limiter = Some(limiter);
+        self
+    }
+
+    /// Admit tools according to `policy`
+    ///
+    /// Without a policy the [default](TrustPolicy::default) applies, which
+    /// trusts no signing keys.
+    pub fn with_trust_policy(mut self, policy: Arc<TrustPolicy>) -> Self {
+        self.trust_policy = RwLock::new(policy);
+        self
+    }
+
+    /// Replace the trust policy
+    ///
+    /// Registered tools are checked against the new policy on their next
+    /// execution, so revoking a signing key or narrowing a tier's grant
+    /// takes effect without re-registering anything.
+    pub async fn set_trust_policy(&self, policy: Arc<TrustPolicy>) {
+        *self.trust_policy.write().await = policy;
+    }
+
     /// Create a new registry (alias for `new_empty`)
     /// 
     /// This method exists for historical compatibility and creates
@@ -141,16 +190,55 @@ impl ToolRegistry {
     /// # });
     /// ```
     pub async fn register_tool(&self, tool: Arc<dyn Tool + Send + Sync>) -> Result<(), ToolError> {
+        self.register_tool_with_provenance(tool, ToolProvenance::builtin()).await
+    }
+
+    /// Register a tool of known provenance
+    ///
+    /// `register_tool` treats tools as builtin; WASM modules, MCP imports and
+    /// workspace tools are registered here so the trust policy can assign
+    /// their tier.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ToolError::TrustViolation`] if the signature does not verify
+    /// or the tool's tier may not request its security level or
+    /// capabilities, and [`ToolError::ToolAlreadyRegistered`] for duplicate
+    /// names.
+    pub async fn register_tool_with_provenance(
+        &self,
+        tool: Arc<dyn Tool + Send + Sync>,
+        provenance: ToolProvenance,
+    ) -> Result<(), ToolError> {
         let name = tool.name().to_string();
+        let tier = self
+            .trust_policy
+            .read()
+            .await
+            .admit(&name, &provenance)
+            .map_err(|source| ToolError::TrustViolation {
+                tool_name: name.clone(),
+                source,
+            })?;
+
         let mut map = self.tools.write().await;
         if map.contains_key(&name) {
             return Err(ToolError::ToolAlreadyRegistered { name });
         }
         map.insert(name.clone(), tool);
-        info!("Registered tool: {name}");
+        self.trust
+            .write()
+            .await
+            .insert(name.clone(), ToolTrust { provenance, tier });
+        info!("Registered tool: {name} ({tier:?})");
         Ok(())
     }
 
+    /// Provenance and tier of a registered tool
+    pub async fn tool_trust(&self, name: &str) -> Option<ToolTrust> {
+        self.trust.read().await.get(name).cloned()
+    }
+
     /// Fetch a tool by name
     /// 
     /// Retrieves a tool from the registry by its name. Returns None
@@ -202,6 +290,8 @@ impl ToolRegistry {
     /// 
     /// Returns an error if:
     /// - The tool is not found
+    /// - The tool is disabled by a feature flag
+    /// - The trust policy no longer admits the tool
     /// - Parameter validation fails
     /// - Tool execution fails
     /// 
@@ -229,12 +319,53 @@ impl ToolRegistry {
     /// # });
     /// ```
     pub async fn execute_tool(&self, name: &str, params: &ToolParams) -> Result<ToolResult, ToolError> {
+        self.execute_tool_for(name, params, &FlagContext::default()).await
+    }
+
+    /// Execute a tool on behalf of `context`
+    ///
+    /// Like [`execute_tool`](Self::execute_tool), but feature flags scoped to
+    /// the caller's session, agent or tenant apply.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ToolError::FeatureDisabled`] if the tool's flag is off for
+    /// `context`, [`ToolError::TrustViolation`] if the current trust policy
+    /// no longer admits the tool, plus every error of `execute_tool`.
+    pub async fn execute_tool_for(
+        &self,
+        name: &str,
+        params: &ToolParams,
+        context: &FlagContext,
+    ) -> Result<ToolResult, ToolError> {
+        if let Some(flags) = &self.feature_flags {
+            let flag = tool_flag(name);
+            if !flags.is_enabled(&flag, context, true).await {
+                return Err(ToolError::FeatureDisabled {
+                    tool_name: name.to_string(),
+                    flag,
+                });
+            }
+        }
+
         let tool = {
             let map = self.tools.read().await;
             map.get(name).cloned()
         }
         .ok_or_else(|| ToolError::ToolNotFound { name: name.to_string() })?;
 
+        // Re-check provenance, as the policy may have changed since registration
+        if let Some(trust) = self.trust.read().await.get(name) {
+            self.trust_policy
+                .read()
+                .await
+                .admit(name, &trust.provenance)
+                .map_err(|source| ToolError::TrustViolation {
+                    tool_name: name.to_string(),
+                    source,
+                })?;
+        }
+
         // Validate parameters first
         tool.validate_params(params)
             .map_err(|e| ToolError::ParameterValidation {
@@ -251,6 +382,16 @@ impl ToolRegistry {
                 reason: e.to_string(),
             })?;
         
+        if let Some(limiter) = &self.output_limiter {
+            limiter
+                .apply(name, &mut result.output)
+                .await
+                .map_err(|e| ToolError::ExecutionFailed {
+                    tool_name: name.to_string(),
+                    reason: format!("failed to store oversized output: {}", e),
+                })?;
+        }
+
         result.metadata.execution_time_ms = start.elapsed().as_millis() as u64;
         Ok(result)
     }
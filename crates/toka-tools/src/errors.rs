@@ -86,6 +86,25 @@ pub enum ToolError {
         timeout_ms: u64,
     },
 
+    /// Tool is turned off by a feature flag
+    #[error("Tool '{tool_name}' is disabled by feature flag '{flag}'")]
+    FeatureDisabled {
+        /// Name of the tool
+        tool_name: String,
+        /// Flag that is off
+        flag: String,
+    },
+
+    /// Tool is refused by the trust policy
+    #[error("Tool '{tool_name}' violates trust policy: {source}")]
+    TrustViolation {
+        /// Name of the tool
+        tool_name: String,
+        /// Why the policy refused the tool
+        #[source]
+        source: crate::trust::TrustError,
+    },
+
     /// File operation error
     #[error("File operation failed for '{path}': {reason}")]
     FileOperation {
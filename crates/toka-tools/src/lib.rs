@@ -29,6 +29,8 @@
 //! - **Runtime Flexibility**: Support for Python, WASM, JavaScript, and native code execution
 //! - **Security-First**: Multi-layered security with sandboxing and resource limits
 //! - **Performance**: Efficient caching and resource management
+//! - **Feature Flags**: Tools can be rolled out per session, agent or tenant and killed instantly
+//! - **Provenance**: Tools carry their source and signature, and their trust tier limits what they may request
 //!
 //! # Quick Start
 //!
@@ -64,6 +66,7 @@ use anyhow::Result;
 pub mod core;
 pub mod errors;
 pub mod tools;
+pub mod trust;
 pub mod wrappers;
 pub mod runtime_integration;
 
@@ -74,10 +77,19 @@ pub use toka_runtime::{
     RuntimeMetadata, RuntimeResourceUsage, Artifact,
     SecurityLevel, Capability, CapabilitySet, ExecutionContext,
 };
+pub use toka_runtime::output::{OutputLimiter, OutputLimits, OutputOverflow, TruncationPolicy};
+pub use toka_runtime::flags::{
+    FeatureFlags, FlagContext, FlagScope, FlagState, FlagStore, InMemoryFlagStore, StorageFlagStore,
+};
 
 // Re-export core types
 pub use crate::core::{Tool, ToolRegistry, ToolParams, ToolResult, ToolMetadata};
 
+// Re-export provenance and trust types
+pub use crate::trust::{
+    sign_tool, TierGrant, ToolProvenance, ToolSource, ToolTrust, TrustError, TrustPolicy, TrustTier,
+};
+
 // Re-export error types
 pub use crate::errors::{ToolError, RegistryError, ValidationError, SecurityError};
 
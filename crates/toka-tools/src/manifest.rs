@@ -3,13 +3,148 @@
 //! Provides versioned, serialisable data-structures that describe a tool's
 //! public contract.  Stored as JSON (or embedded YAML/TOML) and compatible with
 //! existing ecosystems (JSON-RPC 2.0, MCP, Google A2A …).
+//!
+//! Every manifest records the schema it was written against in
+//! `manifest_version`.  Manifests loaded through [`ToolManifest::from_json`],
+//! [`ToolManifest::from_yaml`] or [`ToolManifest::from_value`] are migrated
+//! from older schema versions to [`SCHEMA_VERSION`] before they are parsed, so
+//! tool packages built against an older schema keep loading.  Manifests
+//! written for a newer schema are rejected with a
+//! [`ManifestVersionError::Unsupported`] instead of being half-understood.
 
 #![allow(dead_code)]
 
 use serde::{Deserialize, Serialize};
 
 /// Current schema version – increment **major** on breaking changes.
-pub const SCHEMA_VERSION: &str = "1.1";
+pub const SCHEMA_VERSION: &str = "1.2";
+
+/// Oldest schema version that can still be migrated.
+pub const MIN_SUPPORTED_VERSION: &str = "1.0";
+
+/// Parsed `major.minor` manifest schema version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ManifestVersion {
+    /// Incremented on breaking changes.
+    pub major: u32,
+    /// Incremented when optional fields are added.
+    pub minor: u32,
+}
+
+impl ManifestVersion {
+    /// Version of [`SCHEMA_VERSION`].
+    pub fn current() -> Self {
+        SCHEMA_VERSION.parse().expect("SCHEMA_VERSION is a valid version")
+    }
+
+    /// Version of [`MIN_SUPPORTED_VERSION`].
+    pub fn min_supported() -> Self {
+        MIN_SUPPORTED_VERSION.parse().expect("MIN_SUPPORTED_VERSION is a valid version")
+    }
+}
+
+impl std::str::FromStr for ManifestVersion {
+    type Err = ManifestVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ManifestVersionError::Invalid(s.to_string());
+        let (major, minor) = s.trim().split_once('.').ok_or_else(invalid)?;
+        Ok(Self {
+            major: major.parse().map_err(|_| invalid())?,
+            minor: minor.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl std::fmt::Display for ManifestVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Why a manifest's schema version cannot be handled.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ManifestVersionError {
+    /// `manifest_version` is not a `major.minor` version.
+    #[error("invalid manifest_version '{0}', expected MAJOR.MINOR")]
+    Invalid(String),
+    /// The manifest was written for a newer schema than this build knows.
+    #[error("manifest_version {found} is newer than the supported {supported}; upgrade toka-tools to load this tool")]
+    Unsupported {
+        /// Version declared by the manifest.
+        found: ManifestVersion,
+        /// Newest version this build understands.
+        supported: ManifestVersion,
+    },
+    /// The manifest predates the oldest schema that can be migrated.
+    #[error("manifest_version {found} is older than {oldest} and can no longer be migrated")]
+    TooOld {
+        /// Version declared by the manifest.
+        found: ManifestVersion,
+        /// Oldest version that can still be migrated.
+        oldest: ManifestVersion,
+    },
+}
+
+/// One schema upgrade, applied to the raw manifest object.
+struct Migration {
+    from: &'static str,
+    to: &'static str,
+    apply: fn(&mut serde_json::Map<String, serde_json::Value>),
+}
+
+/// Upgrades between consecutive schema versions, oldest first.
+///
+/// Add a step here whenever [`SCHEMA_VERSION`] changes.  Steps only adding
+/// optional fields have nothing to rewrite, as serde fills in the defaults.
+const MIGRATIONS: &[Migration] = &[
+    // 1.1 added `protocols` and `metadata`
+    Migration { from: "1.0", to: "1.1", apply: |_| {} },
+    // 1.2 added `examples`
+    Migration { from: "1.1", to: "1.2", apply: |_| {} },
+];
+
+/// Upgrade a raw manifest to [`SCHEMA_VERSION`].
+///
+/// Manifests without a `manifest_version` predate versioning and are treated
+/// as [`MIN_SUPPORTED_VERSION`].  Returns the version the manifest declared.
+pub fn migrate_manifest(value: &mut serde_json::Value) -> anyhow::Result<ManifestVersion> {
+    let object = value
+        .as_object_mut()
+        .ok_or_else(|| anyhow::anyhow!("manifest must be an object"))?;
+    let declared: ManifestVersion = match object.get("manifest_version") {
+        None => ManifestVersion::min_supported(),
+        Some(serde_json::Value::String(version)) => version.parse()?,
+        Some(other) => return Err(ManifestVersionError::Invalid(other.to_string()).into()),
+    };
+
+    let current = ManifestVersion::current();
+    if declared > current {
+        return Err(ManifestVersionError::Unsupported { found: declared, supported: current }.into());
+    }
+    let oldest = ManifestVersion::min_supported();
+    if declared < oldest {
+        return Err(ManifestVersionError::TooOld { found: declared, oldest }.into());
+    }
+
+    let mut version = declared;
+    for step in MIGRATIONS {
+        let from: ManifestVersion = step.from.parse()?;
+        if from < version {
+            continue;
+        }
+        if from != version {
+            anyhow::bail!("no migration from manifest_version {version}");
+        }
+        (step.apply)(object);
+        version = step.to.parse()?;
+    }
+    if version != current {
+        anyhow::bail!("no migration from manifest_version {version} to {current}");
+    }
+    object.insert("manifest_version".to_string(), serde_json::Value::String(SCHEMA_VERSION.to_string()));
+    Ok(declared)
+}
 
 /// Supported higher-level protocol mapping (MCP / A2A) so external frameworks
 /// can automatically translate the manifest.
@@ -86,6 +221,53 @@ pub enum SideEffect {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Schema(pub String);
 
+/// Worked example of a tool call, used for few-shot prompting.
+///
+/// Examples live next to the schema they exercise, so prompts stay accurate
+/// as the tool evolves instead of relying on hand-maintained snippets.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolExample {
+    /// What the call achieves, phrased like a task ("Read the workspace manifest").
+    pub description: String,
+    /// Call parameters; must satisfy the manifest's input schema.
+    pub input: serde_json::Value,
+    /// Representative result of the call.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<serde_json::Value>,
+    /// Keywords that help match the example to a task.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl ToolExample {
+    /// Render the example as a single prompt line invoking `capability`.
+    pub fn render(&self, capability: &str) -> String {
+        let mut rendered = format!("{}: {} {}", self.description, capability, self.input);
+        if let Some(output) = &self.output {
+            rendered.push_str(&format!(" -> {}", output));
+        }
+        rendered
+    }
+
+    /// Number of distinct task words found in the description or tags.
+    fn relevance(&self, task_words: &std::collections::BTreeSet<String>) -> usize {
+        let mut text = self.description.clone();
+        for tag in &self.tags {
+            text.push(' ');
+            text.push_str(tag);
+        }
+        words(&text).intersection(task_words).count()
+    }
+}
+
+/// Lower-cased alphanumeric words of `text`.
+fn words(text: &str) -> std::collections::BTreeSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
 /// Top-level manifest object.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolManifest {
@@ -116,7 +298,7 @@ pub struct ToolManifest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub action_id: Option<String>,
 
-    /// Schema version for forward/backward compat.
+    /// Schema version for forward/backward compat; see [`migrate_manifest`].
     #[serde(default = "schema_version")]
     pub manifest_version: String,
 
@@ -127,6 +309,10 @@ pub struct ToolManifest {
     /// Arbitrary extension metadata for future or domain-specific keys.
     #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
     pub metadata: std::collections::BTreeMap<String, String>,
+
+    /// Example calls rendered into prompts during tool selection (since 1.2).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub examples: Vec<ToolExample>,
 }
 
 fn schema_version() -> String {
@@ -134,9 +320,49 @@ fn schema_version() -> String {
 }
 
 impl ToolManifest {
+    /// Parse a manifest from a JSON value, migrating older schema versions.
+    pub fn from_value(mut value: serde_json::Value) -> anyhow::Result<Self> {
+        use anyhow::Context;
+        let declared = migrate_manifest(&mut value)?;
+        let manifest: Self = serde_json::from_value(value)
+            .with_context(|| format!("invalid manifest (manifest_version {declared})"))?;
+        if declared != ManifestVersion::current() {
+            tracing::debug!(
+                "Migrated manifest {} from version {} to {}",
+                manifest.id,
+                declared,
+                SCHEMA_VERSION
+            );
+        }
+        Ok(manifest)
+    }
+
+    /// Parse a JSON manifest, migrating older schema versions.
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        Self::from_value(serde_json::from_str(json)?)
+    }
+
+    /// Parse a YAML manifest, migrating older schema versions.
+    pub fn from_yaml(yaml: &str) -> anyhow::Result<Self> {
+        Self::from_value(serde_yaml::from_str(yaml)?)
+    }
+
+    /// Parsed schema version of the manifest.
+    pub fn schema_version(&self) -> Result<ManifestVersion, ManifestVersionError> {
+        self.manifest_version.parse()
+    }
+
     /// Perform static validation of the manifest. See original docs for rules.
     pub fn validate(&self) -> anyhow::Result<()> {
         use anyhow::{anyhow, Context};
+        let version = self.schema_version()?;
+        if version > ManifestVersion::current() {
+            return Err(ManifestVersionError::Unsupported {
+                found: version,
+                supported: ManifestVersion::current(),
+            }
+            .into());
+        }
         if self.id.trim().is_empty() {
             return Err(anyhow!("manifest.id must not be empty"));
         }
@@ -187,8 +413,85 @@ impl ToolManifest {
         ensure_schema_compiles(&self.input_schema, "input")?;
         ensure_schema_compiles(&self.output_schema, "output")?;
 
+        for (i, example) in self.examples.iter().enumerate() {
+            if example.description.trim().is_empty() {
+                return Err(anyhow!("examples[{i}].description must not be empty"));
+            }
+        }
+        ensure_examples_match_schema(&self.input_schema, &self.examples)?;
+
         Ok(())
     }
+
+    /// Pick up to `limit` examples most relevant to `task`.
+    ///
+    /// Examples sharing more words with the task (in their description or
+    /// tags) come first; ties keep the order of the manifest, so authors list
+    /// their most instructive examples first.
+    pub fn select_examples(&self, task: &str, limit: usize) -> Vec<&ToolExample> {
+        let task_words = words(task);
+        let mut ranked: Vec<(usize, &ToolExample)> = self
+            .examples
+            .iter()
+            .map(|example| (example.relevance(&task_words), example))
+            .collect();
+        ranked.sort_by(|a, b| b.0.cmp(&a.0));
+        ranked.into_iter().take(limit).map(|(_, example)| example).collect()
+    }
+
+    /// Render the examples selected for `task` as prompt lines.
+    ///
+    /// The lines fit the `examples` of an agent runtime tool suggestion.
+    pub fn prompt_examples(&self, task: &str, limit: usize) -> Vec<String> {
+        self.select_examples(task, limit)
+            .into_iter()
+            .map(|example| example.render(&self.capability))
+            .collect()
+    }
+}
+
+/// Render a few-shot prompt section with up to `per_tool` examples of each
+/// manifest, selected for `task`.
+///
+/// Tools without examples are left out; an empty string is returned when no
+/// tool has any.
+pub fn render_few_shot_examples(manifests: &[ToolManifest], task: &str, per_tool: usize) -> String {
+    let mut rendered = String::new();
+    for manifest in manifests {
+        let examples = manifest.prompt_examples(task, per_tool);
+        if examples.is_empty() {
+            continue;
+        }
+        if rendered.is_empty() {
+            rendered.push_str("Tool Examples:");
+        }
+        rendered.push_str(&format!("\n- {}:", manifest.name));
+        for example in examples {
+            rendered.push_str(&format!("\n  Example: {}", example));
+        }
+    }
+    rendered
+}
+
+/// Ensures every example input satisfies the input schema, when there is one.
+fn ensure_examples_match_schema(opt: &Option<Schema>, examples: &[ToolExample]) -> anyhow::Result<()> {
+    let raw = match opt {
+        Some(s) if !examples.is_empty() => &s.0,
+        _ => return Ok(()),
+    };
+    let doc: serde_json::Value = serde_json::from_str(raw)?;
+    let schema = jsonschema::JSONSchema::options()
+        .with_draft(jsonschema::Draft::Draft7)
+        .compile(&doc)
+        .map_err(|e| anyhow::anyhow!("input schema: invalid draft-07: {}", e))?;
+
+    for (i, example) in examples.iter().enumerate() {
+        if let Err(errors) = schema.validate(&example.input) {
+            let reasons: Vec<String> = errors.map(|e| e.to_string()).collect();
+            anyhow::bail!("examples[{i}].input does not match the input schema: {}", reasons.join("; "));
+        }
+    }
+    Ok(())
 }
 
 /// Compile-time size limit (bytes) applied to every embedded JSON Schema.
@@ -324,6 +627,7 @@ mod tests {
                 manifest_version: "1.1".to_string(),
                 protocols: vec![],
                 metadata: Default::default(),
+                examples: vec![],
             };
             
             // This should not leak memory anymore due to our fixes
@@ -333,6 +637,103 @@ mod tests {
         println!("✅ Schema validation memory leak test completed successfully");
     }
     
+    #[test]
+    fn test_examples_are_validated_and_selected_for_task() {
+        let mut manifest = ToolManifest {
+            id: "toka.file_reader".to_string(),
+            name: "file-reader".to_string(),
+            version: "1.0.0".to_string(),
+            description: "Read files from the workspace".to_string(),
+            capability: "read_file".to_string(),
+            side_effect: SideEffect::ReadOnly,
+            input_schema: Some(Schema(
+                json!({
+                    "type": "object",
+                    "properties": {"path": {"type": "string"}, "lines": {"type": "integer"}},
+                    "required": ["path"]
+                })
+                .to_string(),
+            )),
+            output_schema: None,
+            transports: vec![Transport::InProcess],
+            action_id: None,
+            manifest_version: SCHEMA_VERSION.to_string(),
+            protocols: vec![],
+            metadata: Default::default(),
+            examples: vec![
+                ToolExample {
+                    description: "Read the workspace manifest".to_string(),
+                    input: json!({"path": "Cargo.toml"}),
+                    output: None,
+                    tags: vec!["cargo".to_string()],
+                },
+                ToolExample {
+                    description: "Read the head of a log file".to_string(),
+                    input: json!({"path": "app.log", "lines": 20}),
+                    output: Some(json!("2024-01-01 started")),
+                    tags: vec!["logs".to_string()],
+                },
+            ],
+        };
+        manifest.validate().expect("examples match the schema");
+
+        let selected = manifest.prompt_examples("Check the latest log entries", 1);
+        assert_eq!(
+            selected,
+            vec![r#"Read the head of a log file: read_file {"lines":20,"path":"app.log"} -> "2024-01-01 started""#]
+        );
+        // Without matching words the manifest order decides
+        assert_eq!(manifest.select_examples("unrelated", 1)[0].description, "Read the workspace manifest");
+
+        let rendered = render_few_shot_examples(std::slice::from_ref(&manifest), "update cargo dependencies", 1);
+        assert_eq!(
+            rendered,
+            "Tool Examples:\n- file-reader:\n  Example: Read the workspace manifest: read_file {\"path\":\"Cargo.toml\"}"
+        );
+
+        manifest.examples[0].input = json!({"lines": 5});
+        assert!(manifest.validate().is_err());
+    }
+
+    #[test]
+    fn test_older_manifests_are_migrated_and_newer_rejected() {
+        let legacy = json!({
+            "id": "toka.echo",
+            "name": "echo",
+            "version": "0.1.0",
+            "description": "Echo the input",
+            "capability": "echo",
+            "input_schema": null,
+            "output_schema": null,
+            "transports": [{"kind": "in_process"}]
+        });
+        let manifest = ToolManifest::from_value(legacy.clone()).unwrap();
+        assert_eq!(manifest.manifest_version, SCHEMA_VERSION);
+        assert!(manifest.examples.is_empty());
+        manifest.validate().unwrap();
+
+        let mut value = legacy.clone();
+        value["manifest_version"] = json!("1.1");
+        assert_eq!(migrate_manifest(&mut value).unwrap(), ManifestVersion { major: 1, minor: 1 });
+        assert_eq!(value["manifest_version"], json!(SCHEMA_VERSION));
+
+        let mut future = legacy.clone();
+        future["manifest_version"] = json!("2.0");
+        let err = ToolManifest::from_json(&future.to_string()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ManifestVersionError>(),
+            Some(ManifestVersionError::Unsupported { .. })
+        ));
+
+        let mut garbled = legacy;
+        garbled["manifest_version"] = json!("one");
+        let err = ToolManifest::from_value(garbled).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ManifestVersionError>(),
+            Some(&ManifestVersionError::Invalid("one".to_string()))
+        );
+    }
+
     fn create_test_schema(id: usize) -> Schema {
         let schema_json = json!({
             "type": "object",
@@ -0,0 +1,409 @@
+//! Provenance and trust tiers for registered tools.
+//!
+//! Every tool in a [`ToolRegistry`](crate::ToolRegistry) carries a
+//! [`ToolProvenance`]: where it came from, the [`SecurityLevel`] and
+//! [`CapabilitySet`] it requests and, for third-party code, an Ed25519
+//! signature. A [`TrustPolicy`] turns the provenance into a [`TrustTier`]
+//! and decides what that tier may request:
+//!
+//! | Source                     | Tier                                  |
+//! |----------------------------|---------------------------------------|
+//! | [`ToolSource::Builtin`]    | [`TrustTier::System`]                 |
+//! | [`ToolSource::Workspace`]  | [`TrustTier::Trusted`]                |
+//! | WASM module or MCP import  | [`TrustTier::Verified`] when signed by a trusted key, else [`TrustTier::Untrusted`] |
+//!
+//! A signature that does not verify is an error rather than a downgrade, as
+//! it means the tool was tampered with or signed by an unknown party.
+//!
+//! The signature covers the tool name, source, artifact digest, security
+//! level and capabilities, so a signed tool cannot later ask for more than
+//! its publisher vouched for.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use toka_runtime::{Capability, CapabilitySet, SecurityLevel};
+
+/// Where a tool comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolSource {
+    /// Compiled into the host
+    Builtin,
+    /// Defined in the operator's workspace
+    Workspace,
+    /// Third-party WebAssembly module
+    Wasm,
+    /// Imported from an MCP server
+    Mcp,
+}
+
+impl fmt::Display for ToolSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ToolSource::Builtin => "builtin",
+            ToolSource::Workspace => "workspace",
+            ToolSource::Wasm => "wasm",
+            ToolSource::Mcp => "mcp",
+        })
+    }
+}
+
+/// How far a tool is trusted, from least to most.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrustTier {
+    /// Unsigned third-party code
+    Untrusted,
+    /// Third-party code signed by a trusted key
+    Verified,
+    /// Workspace tools
+    Trusted,
+    /// Builtin tools
+    System,
+}
+
+/// Provenance of a registered tool.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolProvenance {
+    /// Where the tool comes from
+    pub source: ToolSource,
+    /// Security level the tool requests
+    pub security_level: SecurityLevel,
+    /// Capabilities the tool requests
+    #[serde(default)]
+    pub capabilities: CapabilitySet,
+    /// Digest of the tool artifact, e.g. the sha256 of a WASM module
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    /// Base64-encoded Ed25519 signature, see [`sign_tool`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl ToolProvenance {
+    /// Unsigned provenance requesting `security_level` and no capabilities.
+    pub fn new(source: ToolSource, security_level: SecurityLevel) -> Self {
+        Self {
+            source,
+            security_level,
+            capabilities: CapabilitySet::default(),
+            digest: None,
+            signature: None,
+        }
+    }
+
+    /// Provenance of builtin tools, which run without restrictions.
+    pub fn builtin() -> Self {
+        Self::new(ToolSource::Builtin, SecurityLevel::Low)
+    }
+
+    /// Request `capabilities`.
+    pub fn with_capabilities(mut self, capabilities: CapabilitySet) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Record the digest of the tool artifact.
+    pub fn with_digest(mut self, digest: impl Into<String>) -> Self {
+        self.digest = Some(digest.into());
+        self
+    }
+
+    /// Attach a signature made with [`sign_tool`].
+    pub fn with_signature(mut self, signature: impl Into<String>) -> Self {
+        self.signature = Some(signature.into());
+        self
+    }
+
+    /// Bytes covered by the signature of tool `name`.
+    pub fn signing_payload(&self, name: &str) -> Vec<u8> {
+        let mut capabilities: Vec<String> = self
+            .capabilities
+            .capabilities
+            .iter()
+            .map(|capability| format!("{:?}", capability))
+            .collect();
+        capabilities.sort();
+        capabilities.dedup();
+        format!(
+            "{}\n{}\n{}\n{:?}\n{}",
+            name,
+            self.source,
+            self.digest.as_deref().unwrap_or(""),
+            self.security_level,
+            capabilities.join(",")
+        )
+        .into_bytes()
+    }
+}
+
+/// Sign tool `name` with `provenance`, returning the base64-encoded
+/// signature for [`ToolProvenance::with_signature`].
+pub fn sign_tool(name: &str, provenance: &ToolProvenance, key: &SigningKey) -> String {
+    BASE64.encode(key.sign(&provenance.signing_payload(name)).to_bytes())
+}
+
+/// What tools of one tier may request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TierGrant {
+    /// Security levels the tier may request
+    pub security_levels: Vec<SecurityLevel>,
+    /// Capabilities the tier may request
+    pub capabilities: Vec<Capability>,
+}
+
+impl TierGrant {
+    /// Every security level and capability.
+    pub fn unrestricted() -> Self {
+        Self {
+            security_levels: vec![
+                SecurityLevel::Low,
+                SecurityLevel::Medium,
+                SecurityLevel::High,
+                SecurityLevel::Restricted,
+            ],
+            capabilities: vec![
+                Capability::CodeGeneration,
+                Capability::FileSystem,
+                Capability::Network,
+                Capability::Process,
+            ],
+        }
+    }
+}
+
+/// Why a tool was refused.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TrustError {
+    /// The signature matches no trusted key
+    #[error("signature of tool '{0}' does not verify against any trusted key")]
+    InvalidSignature(String),
+    /// The signature could not be decoded
+    #[error("malformed signature for tool '{tool}': {reason}")]
+    MalformedSignature {
+        /// Tool the signature belongs to
+        tool: String,
+        /// What was wrong with it
+        reason: String,
+    },
+    /// A trusted key could not be decoded
+    #[error("invalid trusted key: {0}")]
+    InvalidKey(String),
+    /// The tier may not request the security level
+    #[error("{tier:?} tools may not request security level {level:?}")]
+    SecurityLevelDenied {
+        /// Tier of the tool
+        tier: TrustTier,
+        /// Requested security level
+        level: SecurityLevel,
+    },
+    /// The tier may not request the capability
+    #[error("{tier:?} tools may not request capability {capability:?}")]
+    CapabilityDenied {
+        /// Tier of the tool
+        tier: TrustTier,
+        /// Requested capability
+        capability: Capability,
+    },
+}
+
+/// Trusted signing keys and the grants of each tier.
+///
+/// By default no keys are trusted; untrusted tools may only run
+/// [`Restricted`](SecurityLevel::Restricted) or [`High`](SecurityLevel::High)
+/// without capabilities, verified tools may additionally run at
+/// [`Medium`](SecurityLevel::Medium) with filesystem and network access, and
+/// workspace and builtin tools are unrestricted.
+#[derive(Debug, Clone)]
+pub struct TrustPolicy {
+    trusted_keys: Vec<VerifyingKey>,
+    grants: BTreeMap<TrustTier, TierGrant>,
+}
+
+impl Default for TrustPolicy {
+    fn default() -> Self {
+        let mut grants = BTreeMap::new();
+        grants.insert(
+            TrustTier::Untrusted,
+            TierGrant {
+                security_levels: vec![SecurityLevel::Restricted, SecurityLevel::High],
+                capabilities: vec![],
+            },
+        );
+        grants.insert(
+            TrustTier::Verified,
+            TierGrant {
+                security_levels: vec![SecurityLevel::Restricted, SecurityLevel::High, SecurityLevel::Medium],
+                capabilities: vec![Capability::FileSystem, Capability::Network],
+            },
+        );
+        grants.insert(TrustTier::Trusted, TierGrant::unrestricted());
+        grants.insert(TrustTier::System, TierGrant::unrestricted());
+        Self {
+            trusted_keys: Vec::new(),
+            grants,
+        }
+    }
+}
+
+impl TrustPolicy {
+    /// Default grants without trusted keys.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trust signatures made with the private half of `key`.
+    pub fn with_trusted_key(mut self, key: VerifyingKey) -> Self {
+        self.trusted_keys.push(key);
+        self
+    }
+
+    /// Trust a base64-encoded Ed25519 public key.
+    pub fn with_encoded_key(self, encoded: &str) -> Result<Self, TrustError> {
+        let bytes = BASE64
+            .decode(encoded.trim())
+            .map_err(|e| TrustError::InvalidKey(e.to_string()))?;
+        let bytes: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| TrustError::InvalidKey("expected 32 key bytes".to_string()))?;
+        let key = VerifyingKey::from_bytes(&bytes).map_err(|e| TrustError::InvalidKey(e.to_string()))?;
+        Ok(self.with_trusted_key(key))
+    }
+
+    /// Replace what `tier` may request.
+    pub fn with_grant(mut self, tier: TrustTier, grant: TierGrant) -> Self {
+        self.grants.insert(tier, grant);
+        self
+    }
+
+    /// What `tier` may request.
+    pub fn grant(&self, tier: TrustTier) -> Option<&TierGrant> {
+        self.grants.get(&tier)
+    }
+
+    /// Tier of tool `name`, verifying its signature if it has one.
+    pub fn tier_of(&self, name: &str, provenance: &ToolProvenance) -> Result<TrustTier, TrustError> {
+        let signed = match &provenance.signature {
+            Some(signature) => {
+                self.verify(name, provenance, signature)?;
+                true
+            }
+            None => false,
+        };
+        Ok(match provenance.source {
+            ToolSource::Builtin => TrustTier::System,
+            ToolSource::Workspace => TrustTier::Trusted,
+            ToolSource::Wasm | ToolSource::Mcp if signed => TrustTier::Verified,
+            ToolSource::Wasm | ToolSource::Mcp => TrustTier::Untrusted,
+        })
+    }
+
+    /// Tier of tool `name`, provided the tier grants everything it requests.
+    pub fn admit(&self, name: &str, provenance: &ToolProvenance) -> Result<TrustTier, TrustError> {
+        let tier = self.tier_of(name, provenance)?;
+        let grant = self.grants.get(&tier);
+        let level = provenance.security_level;
+        if !grant.is_some_and(|grant| grant.security_levels.contains(&level)) {
+            return Err(TrustError::SecurityLevelDenied { tier, level });
+        }
+        if let Some(capability) = provenance
+            .capabilities
+            .capabilities
+            .iter()
+            .find(|capability| !grant.is_some_and(|grant| grant.capabilities.contains(capability)))
+        {
+            return Err(TrustError::CapabilityDenied {
+                tier,
+                capability: capability.clone(),
+            });
+        }
+        Ok(tier)
+    }
+
+    fn verify(&self, name: &str, provenance: &ToolProvenance, encoded: &str) -> Result<(), TrustError> {
+        let malformed = |reason: String| TrustError::MalformedSignature {
+            tool: name.to_string(),
+            reason,
+        };
+        let bytes = BASE64.decode(encoded.trim()).map_err(|e| malformed(e.to_string()))?;
+        let bytes: [u8; 64] = bytes
+            .try_into()
+            .map_err(|_| malformed("expected 64 signature bytes".to_string()))?;
+        let signature = Signature::from_bytes(&bytes);
+        let payload = provenance.signing_payload(name);
+        if self
+            .trusted_keys
+            .iter()
+            .any(|key| key.verify_strict(&payload, &signature).is_ok())
+        {
+            Ok(())
+        } else {
+            Err(TrustError::InvalidSignature(name.to_string()))
+        }
+    }
+}
+
+/// Provenance of a registered tool together with its tier.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolTrust {
+    /// Provenance given at registration
+    pub provenance: ToolProvenance,
+    /// Tier assigned by the trust policy
+    pub tier: TrustTier,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tiers_and_grants() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let policy = TrustPolicy::new().with_trusted_key(key.verifying_key());
+
+        assert_eq!(policy.admit("echo", &ToolProvenance::builtin()), Ok(TrustTier::System));
+
+        let unsigned = ToolProvenance::new(ToolSource::Wasm, SecurityLevel::Restricted);
+        assert_eq!(policy.admit("wasm-tool", &unsigned), Ok(TrustTier::Untrusted));
+        let networked = unsigned
+            .clone()
+            .with_capabilities(CapabilitySet::with_capabilities(vec![Capability::Network]));
+        assert!(matches!(
+            policy.admit("wasm-tool", &networked),
+            Err(TrustError::CapabilityDenied { tier: TrustTier::Untrusted, .. })
+        ));
+
+        // Signed by a trusted key, the same request is admitted
+        let signature = sign_tool("wasm-tool", &networked, &key);
+        let signed = networked.clone().with_signature(signature.clone());
+        assert_eq!(policy.admit("wasm-tool", &signed), Ok(TrustTier::Verified));
+
+        // The signature covers the name and the requested security level
+        assert_eq!(
+            policy.tier_of("other-tool", &signed),
+            Err(TrustError::InvalidSignature("other-tool".to_string()))
+        );
+        let escalated = ToolProvenance {
+            security_level: SecurityLevel::Low,
+            ..signed.clone()
+        };
+        assert!(matches!(policy.admit("wasm-tool", &escalated), Err(TrustError::InvalidSignature(_))));
+
+        let mcp = ToolProvenance::new(ToolSource::Mcp, SecurityLevel::Low);
+        assert_eq!(
+            policy.admit("mcp-tool", &mcp),
+            Err(TrustError::SecurityLevelDenied {
+                tier: TrustTier::Untrusted,
+                level: SecurityLevel::Low
+            })
+        );
+        assert!(TrustPolicy::new().admit("wasm-tool", &signed).is_err());
+    }
+}
@@ -33,4 +33,28 @@ async fn test_individual_tool_registration() -> Result<()> {
     assert!(tools.contains(&"file-reader".to_string()));
     
     Ok(())
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn test_output_limits_truncate_tool_output() -> Result<()> {
+    use std::collections::HashMap;
+    use toka_tools::{OutputLimiter, OutputLimits, ToolParams};
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("verbose.log");
+    std::fs::write(&path, "line\n".repeat(1000))?;
+
+    let limiter = OutputLimiter::new(OutputLimits::unlimited().with_limit("file-reader", 100));
+    let registry = ToolRegistry::new().await?.with_output_limiter(Arc::new(limiter));
+    registry.register_tool(Arc::new(FileReader::new())).await?;
+
+    let mut params = ToolParams {
+        name: "file-reader".to_string(),
+        args: HashMap::new(),
+    };
+    params.args.insert("path".to_string(), path.display().to_string());
+    let result = registry.execute_tool("file-reader", &params).await?;
+
+    assert!(result.output.starts_with(&"line\n".repeat(20)));
+    assert!(result.output.ends_with("[output truncated: 4900 of 5000 bytes omitted]"));
+    Ok(())
+}
@@ -20,4 +20,84 @@ async fn list_tools_returns_sorted_names() -> Result<()> {
     names.sort();
     assert_eq!(names, vec!["file-reader".to_string()]);
     Ok(())
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn feature_flags_gate_tool_execution() -> Result<()> {
+    use std::collections::HashMap;
+    use toka_tools::{FeatureFlags, FlagContext, FlagScope, FlagState, ToolParams};
+    use toka_tools::errors::ToolError;
+
+    let flags = Arc::new(FeatureFlags::default());
+    let registry = ToolRegistry::new().await?.with_feature_flags(flags.clone());
+    registry.register_tool(Arc::new(FileReader::new())).await?;
+    let params = ToolParams {
+        name: "file-reader".to_string(),
+        args: HashMap::new(),
+    };
+
+    flags.set("tool.file-reader", FlagScope::Global, FlagState::Off).await?;
+    flags.set("tool.file-reader", FlagScope::Agent("ops".to_string()), FlagState::On).await?;
+    let err = registry.execute_tool("file-reader", &params).await.unwrap_err();
+    assert!(matches!(err, ToolError::FeatureDisabled { ref flag, .. } if flag == "tool.file-reader"));
+
+    // Enabled for the agent, so execution proceeds to parameter validation
+    let ops = FlagContext {
+        agent: Some("ops".to_string()),
+        ..FlagContext::default()
+    };
+    let err = registry.execute_tool_for("file-reader", &params, &ops).await.unwrap_err();
+    assert!(matches!(err, ToolError::ParameterValidation { .. }));
+
+    flags.kill("tool.file-reader").await?;
+    let err = registry.execute_tool_for("file-reader", &params, &ops).await.unwrap_err();
+    assert!(matches!(err, ToolError::FeatureDisabled { .. }));
+    Ok(())
+}
+
+#[tokio::test]
+async fn trust_policy_gates_registration_and_execution() -> Result<()> {
+    use std::collections::HashMap;
+    use ed25519_dalek::SigningKey;
+    use toka_tools::errors::ToolError;
+    use toka_tools::{
+        sign_tool, Capability, CapabilitySet, SecurityLevel, ToolParams, ToolProvenance, ToolSource, TrustError,
+        TrustPolicy, TrustTier,
+    };
+
+    let key = SigningKey::from_bytes(&[3u8; 32]);
+    let policy = Arc::new(TrustPolicy::new().with_trusted_key(key.verifying_key()));
+    let registry = ToolRegistry::new().await?.with_trust_policy(policy);
+
+    // Unsigned WASM tools may not ask for network access
+    let provenance = ToolProvenance::new(ToolSource::Wasm, SecurityLevel::High)
+        .with_capabilities(CapabilitySet::with_capabilities(vec![Capability::Network]))
+        .with_digest("sha256:abc");
+    let err = registry
+        .register_tool_with_provenance(Arc::new(FileReader::new()), provenance.clone())
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        ToolError::TrustViolation { source: TrustError::CapabilityDenied { tier: TrustTier::Untrusted, .. }, .. }
+    ));
+    assert!(registry.list_tools().await.is_empty());
+
+    let signature = sign_tool("file-reader", &provenance, &key);
+    registry
+        .register_tool_with_provenance(Arc::new(FileReader::new()), provenance.with_signature(signature))
+        .await?;
+    assert_eq!(registry.tool_trust("file-reader").await.unwrap().tier, TrustTier::Verified);
+
+    let params = ToolParams {
+        name: "file-reader".to_string(),
+        args: HashMap::new(),
+    };
+    let err = registry.execute_tool("file-reader", &params).await.unwrap_err();
+    assert!(matches!(err, ToolError::ParameterValidation { .. }));
+
+    // Revoking the key refuses the tool at its next execution
+    registry.set_trust_policy(Arc::new(TrustPolicy::new())).await;
+    let err = registry.execute_tool("file-reader", &params).await.unwrap_err();
+    assert!(matches!(err, ToolError::TrustViolation { source: TrustError::InvalidSignature(_), .. }));
+    Ok(())
+}
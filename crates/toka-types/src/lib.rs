@@ -6,6 +6,11 @@
 //! The crate is dependency‐light and sits at the very bottom of the crate
 //! graph so that *every* other crate can depend on it without causing cycles.
 //! It intentionally makes no assumptions about I/O, cryptography, or storage.
+//!
+//! It also has no platform dependencies and builds for
+//! `wasm32-unknown-unknown`, so browser dashboards and WASM tools can use the
+//! same operation and message types as the kernel. Keep it that way: clocks,
+//! threads and async runtimes belong in the crates above.
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -26,6 +31,9 @@ pub const MAX_OBSERVATION_DATA_LEN: usize = 1_048_576; // 1MB
 /// Maximum allowed size for capability tokens to prevent memory exhaustion attacks
 pub const MAX_CAPABILITY_TOKEN_LEN: usize = 8192;
 
+/// Maximum allowed size for correlation identifiers
+pub const MAX_CORRELATION_ID_LEN: usize = 128;
+
 //─────────────────────────────
 //  Core behaviour traits
 //─────────────────────────────
@@ -34,6 +42,16 @@ pub const MAX_CAPABILITY_TOKEN_LEN: usize = 8192;
 pub mod traits;
 pub use traits::{Agent, Tool, Resource, Params, ToolResult, ToolMetadata};
 
+//─────────────────────────────
+//  Fault injection
+//─────────────────────────────
+
+/// Seeded fault injection for resilience testing.
+#[cfg(feature = "fault-injection")]
+pub mod fault;
+#[cfg(feature = "fault-injection")]
+pub use fault::{FaultConfig, FaultInjector, FaultStats};
+
 //─────────────────────────────
 //  Core identifiers
 //─────────────────────────────
@@ -56,6 +74,12 @@ pub struct EntityId(pub u128);
 pub struct TaskSpec {
     /// Human-readable description (v0.1 placeholder).
     pub description: String,
+    /// Correlation identifier of the request that scheduled the task.
+    ///
+    /// Stamped by the kernel when the task is scheduled, so whoever executes
+    /// the task can tag its outcome events with the same identifier.
+    #[serde(default)]
+    pub correlation_id: Option<String>,
 }
 
 impl TaskSpec {
@@ -75,7 +99,7 @@ impl TaskSpec {
         if description.trim().is_empty() {
             return Err("Task description cannot be empty".to_string());
         }
-        Ok(Self { description })
+        Ok(Self { description, correlation_id: None })
     }
 
     /// Validate an existing task specification.
@@ -86,7 +110,7 @@ impl TaskSpec {
         if self.description.trim().is_empty() {
             return Err("Task description cannot be empty".to_string());
         }
-        Ok(())
+        validate_correlation_id(self.correlation_id.as_deref())
     }
 }
 
@@ -158,6 +182,11 @@ pub struct Message {
     pub capability: String,
     /// Requested operation.
     pub op: Operation,
+    /// Identifier tying together every event caused by this request.
+    ///
+    /// The kernel assigns a fresh one when absent.
+    #[serde(default)]
+    pub correlation_id: Option<String>,
 }
 
 impl Message {
@@ -183,7 +212,13 @@ impl Message {
         // SECURITY: Validate the operation
         op.validate()?;
 
-        Ok(Self { origin, capability, op })
+        Ok(Self { origin, capability, op, correlation_id: None })
+    }
+
+    /// Tag the message with the correlation identifier of an ongoing request.
+    pub fn with_correlation_id(mut self, correlation_id: impl Into<String>) -> Self {
+        self.correlation_id = Some(correlation_id.into());
+        self
     }
 
     /// Validate an existing message.
@@ -194,10 +229,22 @@ impl Message {
         if self.capability.trim().is_empty() {
             return Err("Capability token cannot be empty".to_string());
         }
+        validate_correlation_id(self.correlation_id.as_deref())?;
         self.op.validate()
     }
 }
 
+/// Reject empty or oversized correlation identifiers.
+fn validate_correlation_id(correlation_id: Option<&str>) -> Result<(), String> {
+    match correlation_id {
+        Some(id) if id.len() > MAX_CORRELATION_ID_LEN => {
+            Err("Correlation id exceeds maximum length".to_string())
+        }
+        Some(id) if id.trim().is_empty() => Err("Correlation id cannot be empty".to_string()),
+        _ => Ok(()),
+    }
+}
+
 impl Operation {
     /// Validate the operation to ensure it meets security constraints.
     /// 
@@ -249,6 +296,9 @@ pub struct AgentConfig {
     pub reporting: ReportingConfig,
     /// Security configuration
     pub security: SecurityConfig,
+    /// Constraints on which worker node runs the agent
+    #[serde(default)]
+    pub placement: PlacementConstraints,
 }
 
 /// Agent metadata from configuration files.
@@ -393,3 +443,19 @@ pub struct ResourceLimits {
     /// Timeout for agent operations (e.g., "1h")
     pub timeout: String,
 }
+
+/// Constraints on the worker node an agent is placed on.
+///
+/// An empty set of constraints allows any node.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlacementConstraints {
+    /// Labels a node must carry with exactly these values (e.g., `region: eu-west`)
+    #[serde(default)]
+    pub node_selector: HashMap<String, String>,
+    /// Agents that must not share a node with this one
+    #[serde(default)]
+    pub anti_affinity: Vec<String>,
+    /// Minimum number of GPUs the node must provide
+    #[serde(default)]
+    pub min_gpus: u32,
+}
@@ -1,4 +1,4 @@
-use toka_types::{AgentSpec, EntityId, Operation};
+use toka_types::{AgentSpec, EntityId, Message, Operation, TaskSpec};
 
 #[test]
 fn test_operation_serde_roundtrip() {
@@ -13,4 +13,15 @@ fn test_operation_serde_roundtrip() {
     let decoded: Operation = serde_json::from_str(&json).expect("deserialization failed");
 
     assert_eq!(original, decoded);
-}
\ No newline at end of file
+}
+#[test]
+fn test_message_without_correlation_id_deserializes() {
+    let json = r#"{"origin":7,"capability":"cap","op":{"ScheduleAgentTask":{"agent":7,"task":{"description":"demo"}}}}"#;
+    let msg: Message = serde_json::from_str(json).expect("deserialization failed");
+
+    assert_eq!(msg.correlation_id, None);
+    match msg.op {
+        Operation::ScheduleAgentTask { task, .. } => assert_eq!(task, TaskSpec::new("demo".into()).unwrap()),
+        other => panic!("unexpected operation {:?}", other),
+    }
+}